@@ -5,6 +5,8 @@
 
 mod jsonrpc;
 
+pub use jsonrpc::ConstString;
+
 use std::borrow::Cow;
 use std::fmt::{Debug, Display, Formatter};
 
@@ -39,6 +41,12 @@ pub struct JsonRpcError {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ErrorCode(pub i32);
 
+impl ErrorCode {
+	// A2A specific error codes, see https://google.github.io/A2A/specification/#8-error-handling
+	pub const TASK_NOT_FOUND: Self = Self(-32001);
+	pub const TASK_NOT_CANCELABLE: Self = Self(-32002);
+}
+
 /// Error information for JSON-RPC error responses.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ErrorData {
@@ -78,6 +86,23 @@ pub enum A2aRequest {
 }
 
 impl A2aRequest {
+	/// Every method a request may have, legacy ones included.
+	pub const METHODS: &[&str] = &[
+		SendTaskRequestMethod::VALUE,
+		SendSubscribeTaskRequestMethod::VALUE,
+		TaskPushNotificationGetRequestMethod::VALUE,
+		TaskPushNotificationSetRequestMethod::VALUE,
+		TaskResubscribeRequestMethod::VALUE,
+		SendMessageRequestMethod::VALUE,
+		SendStreamingMessageRequestMethod::VALUE,
+		GetTaskRequestMethod::VALUE,
+		CancelTaskRequestMethod::VALUE,
+		SetTaskPushNotificationConfigRequestMethod::VALUE,
+		GetTaskPushNotificationConfigRequestMethod::VALUE,
+		ListTaskPushNotificationConfigRequestMethod::VALUE,
+		DeleteTaskPushNotificationConfigRequestMethod::VALUE,
+	];
+
 	pub fn method(&self) -> &'static str {
 		match self {
 			A2aRequest::SendTaskRequest(i) => i.method.as_string(),
@@ -96,6 +121,29 @@ impl A2aRequest {
 			A2aRequest::DeleteTaskPushNotificationConfigRequest(i) => i.method.as_string(),
 		}
 	}
+
+	/// Returns the push notification config the request asks the agent to call back, if any.
+	pub fn push_notification_config(&self) -> Option<&PushNotificationConfig> {
+		match self {
+			A2aRequest::SendTaskRequest(i) => i.params.push_notification.as_ref(),
+			A2aRequest::SendSubscribeTaskRequest(i) => i.params.push_notification.as_ref(),
+			A2aRequest::TaskPushNotificationSetRequest(i) => Some(&i.params.push_notification_config),
+			A2aRequest::SetTaskPushNotificationConfigRequest(i) => {
+				Some(&i.params.push_notification_config)
+			},
+			A2aRequest::SendMessageRequest(i) => i
+				.params
+				.configuration
+				.as_ref()
+				.and_then(|c| c.push_notification_config.as_ref()),
+			A2aRequest::SendStreamingMessageRequest(i) => i
+				.params
+				.configuration
+				.as_ref()
+				.and_then(|c| c.push_notification_config.as_ref()),
+			_ => None,
+		}
+	}
 }
 
 // TODO: this is not complete, add the rest
@@ -128,6 +176,9 @@ impl A2aResponse {
 			A2aResponse::SendTaskUpdateResponse(SendTaskStreamingResponseResult::Artifact(i)) => {
 				Some(i.id.clone())
 			},
+			A2aResponse::SendTaskUpdateResponse(SendTaskStreamingResponseResult::StreamingStatus(i)) => {
+				Some(i.task_id.clone())
+			},
 			A2aResponse::SendTaskUpdateResponse(SendTaskStreamingResponseResult::None) => None,
 			A2aResponse::SendMessageResponse(i) => Some(i.id.to_string()),
 			A2aResponse::SendStreamingMessageResponse(i) => Some(i.id.to_string()),
@@ -630,6 +681,8 @@ pub type DeleteTaskPushNotificationConfigRequest =
 pub enum SendTaskStreamingResponseResult {
 	Status(TaskStatusUpdateEvent),
 	Artifact(TaskArtifactUpdateEvent),
+	/// A status update of a `message/stream` response.
+	StreamingStatus(StreamingTaskStatusUpdateEvent),
 	#[default]
 	None,
 }
@@ -684,6 +737,32 @@ pub struct TaskNotFoundError {
 	pub data: serde_json::Value,
 	pub message: String,
 }
+
+// The error carries its own code, which the A2A schema fixes; the constant is only a fallback for one
+// that does not fit.
+impl From<TaskNotCancelableError> for ErrorData {
+	fn from(value: TaskNotCancelableError) -> Self {
+		ErrorData {
+			code: i32::try_from(value.code)
+				.map(ErrorCode)
+				.unwrap_or(ErrorCode::TASK_NOT_CANCELABLE),
+			message: value.message.into(),
+			data: Some(value.data).filter(|d| !d.is_null()),
+		}
+	}
+}
+
+impl From<TaskNotFoundError> for ErrorData {
+	fn from(value: TaskNotFoundError) -> Self {
+		ErrorData {
+			code: i32::try_from(value.code)
+				.map(ErrorCode)
+				.unwrap_or(ErrorCode::TASK_NOT_FOUND),
+			message: value.message.into(),
+			data: Some(value.data).filter(|d| !d.is_null()),
+		}
+	}
+}
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
 pub struct TaskPushNotificationConfig {
 	pub id: String,
@@ -778,6 +857,28 @@ pub enum TaskState {
 	#[serde(rename = "auth-required")]
 	AuthRequired,
 }
+impl TaskState {
+	/// A terminal state is one a task can never leave.
+	pub fn is_terminal(&self) -> bool {
+		matches!(
+			self,
+			Self::Completed | Self::Canceled | Self::Failed | Self::Rejected
+		)
+	}
+
+	/// Reports whether a task may move from this state to `next`.
+	/// Repeating the same state is always allowed; `unknown` on either side is not validated.
+	pub fn can_transition_to(&self, next: TaskState) -> bool {
+		if *self == next || *self == Self::Unknown || next == Self::Unknown {
+			return true;
+		}
+		if self.is_terminal() {
+			return false;
+		}
+		// Once a task has left the submitted state it cannot go back
+		next != Self::Submitted
+	}
+}
 impl Display for TaskState {
 	fn fmt(&self, f: &mut Formatter<'_>) -> ::std::fmt::Result {
 		match *self {
@@ -846,6 +947,23 @@ pub struct TaskStatusUpdateEvent {
 	pub status: TaskStatus,
 }
 
+/// A task status update in a `message/stream` response, where the task is identified by `taskId`
+/// rather than `id` as in `tasks/sendSubscribe`.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamingTaskStatusUpdateEvent {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub kind: Option<String>,
+	pub task_id: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub context_id: Option<String>,
+	#[serde(rename = "final", default)]
+	pub final_: bool,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+	pub status: TaskStatus,
+}
+
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
 pub struct TextPart {
 	pub text: String,
@@ -913,6 +1031,151 @@ mod tests {
 		};
 		let _: crate::JsonRpcMessage = serde_json::from_value(js).unwrap();
 	}
+
+	#[test]
+	fn test_streaming_status_update() {
+		let js = serde_json::json! {
+		{
+			"jsonrpc": "2.0",
+			"id": 1,
+			"result": {
+				"kind": "status-update",
+				"taskId": "8b34914c735a464986e1d5ce5b6ec478",
+				"contextId": "c1",
+				"status": {
+					"state": "working"
+				},
+				"final": false
+			}
+		}
+		};
+		let msg: crate::JsonRpcMessage = serde_json::from_value(js.clone()).unwrap();
+		let Some(crate::A2aResponse::SendTaskUpdateResponse(
+			crate::SendTaskStreamingResponseResult::StreamingStatus(ev),
+		)) = msg.response()
+		else {
+			panic!("expected a status update, got {msg:?}");
+		};
+		assert_eq!(ev.task_id, "8b34914c735a464986e1d5ce5b6ec478");
+		// Sent on under the names it was received with
+		assert_eq!(serde_json::to_value(&msg).unwrap(), js);
+	}
+
+	#[test]
+	fn test_task_state_transitions() {
+		use crate::TaskState::*;
+		assert!(Submitted.can_transition_to(Working));
+		assert!(Working.can_transition_to(InputRequired));
+		assert!(InputRequired.can_transition_to(Working));
+		assert!(Working.can_transition_to(AuthRequired));
+		assert!(Working.can_transition_to(Completed));
+		assert!(Submitted.can_transition_to(Rejected));
+		assert!(Completed.can_transition_to(Completed));
+		assert!(Failed.can_transition_to(Unknown));
+
+		assert!(!Completed.can_transition_to(Working));
+		assert!(!Canceled.can_transition_to(Completed));
+		assert!(!Failed.can_transition_to(Submitted));
+		assert!(!Working.can_transition_to(Submitted));
+	}
+
+	#[test]
+	fn test_cancel_task_request() {
+		let js = serde_json::json! {
+		{
+			"jsonrpc": "2.0",
+			"id": 1,
+			"method": "tasks/cancel",
+			"params": {
+				"id": "8b34914c735a464986e1d5ce5b6ec478"
+			}
+		}
+		};
+		let msg: crate::JsonRpcMessage = serde_json::from_value(js.clone()).unwrap();
+		let crate::JsonRpcMessage::Request(req) = &msg else {
+			panic!("expected request, got {msg:?}");
+		};
+		assert_eq!(req.request.method(), "tasks/cancel");
+		assert!(matches!(
+			req.request,
+			crate::A2aRequest::CancelTaskRequest(_)
+		));
+		assert_eq!(serde_json::to_value(&msg).unwrap(), js);
+	}
+
+	#[test]
+	fn test_cancel_task_response() {
+		let js = serde_json::json! {
+		{
+			"jsonrpc": "2.0",
+			"id": 1,
+			"result": {
+				"id": "8b34914c735a464986e1d5ce5b6ec478",
+				"contextId": "c1",
+				"status": {
+					"state": "canceled"
+				}
+			}
+		}
+		};
+		let msg: crate::JsonRpcMessage = serde_json::from_value(js.clone()).unwrap();
+		assert_eq!(
+			msg.response().and_then(|r| r.id()).as_deref(),
+			Some("8b34914c735a464986e1d5ce5b6ec478")
+		);
+		assert_eq!(serde_json::to_value(&msg).unwrap(), js);
+	}
+
+	#[test]
+	fn test_task_not_cancelable_error() {
+		let err = crate::TaskNotCancelableError {
+			code: -32002,
+			data: serde_json::Value::Null,
+			message: "Task cannot be canceled".to_string(),
+		};
+		let msg = crate::JsonRpcMessage::Error(crate::JsonRpcError {
+			jsonrpc: Default::default(),
+			id: crate::jsonrpc::NumberOrString::Number(1),
+			error: err.into(),
+		});
+		let js = serde_json::json! {
+		{
+			"jsonrpc": "2.0",
+			"id": 1,
+			"error": {
+				"code": -32002,
+				"message": "Task cannot be canceled"
+			}
+		}
+		};
+		assert_eq!(serde_json::to_value(&msg).unwrap(), js);
+		let crate::JsonRpcMessage::Error(rt) = serde_json::from_value(js).unwrap() else {
+			panic!("expected error");
+		};
+		assert_eq!(rt.error.code, crate::ErrorCode::TASK_NOT_CANCELABLE);
+	}
+
+	#[test]
+	fn test_task_not_found_error() {
+		let err = crate::TaskNotFoundError {
+			code: -32001,
+			data: serde_json::json!({"id": "t1"}),
+			message: "Task not found".to_string(),
+		};
+		let data: crate::ErrorData = err.into();
+		assert_eq!(data.code, crate::ErrorCode::TASK_NOT_FOUND);
+		assert_eq!(data.data, Some(serde_json::json!({"id": "t1"})));
+
+		// The code of the error is kept
+		let err = crate::TaskNotFoundError {
+			code: -32099,
+			data: serde_json::Value::Null,
+			message: "Task not found".to_string(),
+		};
+		let data: crate::ErrorData = err.into();
+		assert_eq!(data.code, crate::ErrorCode(-32099));
+		assert_eq!(data.data, None);
+	}
 }
 
 // New response types (current version)
@@ -1007,6 +1270,6 @@ pub enum SendMessageSuccessResponseResult {
 pub enum SendStreamingMessageSuccessResponseResult {
 	Task(Task),
 	Message(Message),
-	TaskStatusUpdateEvent(TaskStatusUpdateEvent),
+	TaskStatusUpdateEvent(StreamingTaskStatusUpdateEvent),
 	TaskArtifactUpdateEvent(TaskArtifactUpdateEvent),
 }
@@ -1,10 +1,10 @@
 // Originally derived from https://github.com/istio/ztunnel (Apache 2.0 licensed)
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use agent_core::{telemetry, version};
-use agentgateway::{Config, client, serdes};
+use agentgateway::{Config, ConfigSource, client, serdes};
 use clap::Parser;
 use tracing::info;
 
@@ -23,22 +23,52 @@ struct Args {
 	#[arg(short, long, value_name = "config")]
 	config: Option<String>,
 
-	/// Use config from file
+	/// Use config from file. Repeat it, or pass a directory, to merge several files
 	#[arg(short, long, value_name = "file")]
-	file: Option<PathBuf>,
+	file: Vec<PathBuf>,
 
+	/// Validate the config, including the backends it references, then exit without serving
 	#[arg(long, value_name = "validate-only")]
 	validate_only: bool,
+
+	/// The output format of --validate-only
+	#[arg(long, value_enum, default_value_t = Format::Text)]
+	format: Format,
+
+	/// Print the JSON Schema of the config file, for editor validation. Requires the `schema` feature
+	#[arg(long)]
+	print_config_schema: bool,
+
+	/// Compare the tools generated from two versions of an OpenAPI schema, failing if any change is
+	/// breaking
+	#[arg(long, num_args = 2, value_names = ["old", "new"])]
+	openapi_diff: Option<Vec<PathBuf>>,
 }
 
-fn main() -> anyhow::Result<()> {
-	let _log_flush = telemetry::setup_logging();
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+	/// Human readable
+	Text,
+	/// A JSON object, with whether the config is `valid` and the `errors` found
+	Json,
+}
 
+fn main() -> anyhow::Result<()> {
 	let args = Args::parse();
-	#[cfg(feature = "schema")]
-	println!("{}", agentgateway::types::local::generate_schema());
-	#[cfg(feature = "schema")]
-	return Ok(());
+	// Keep stdout for the JSON report
+	let _log_flush = if args.validate_only && args.format == Format::Json {
+		telemetry::setup_logging_to(std::io::stderr())
+	} else {
+		telemetry::setup_logging()
+	};
+	if args.print_config_schema {
+		#[cfg(feature = "schema")]
+		println!("{}", agentgateway::types::local::generate_schema());
+		#[cfg(feature = "schema")]
+		return Ok(());
+		#[cfg(not(feature = "schema"))]
+		anyhow::bail!("printing the config schema requires building with the `schema` feature");
+	}
 
 	tokio::runtime::Builder::new_current_thread()
 		.enable_all()
@@ -49,35 +79,115 @@ fn main() -> anyhow::Result<()> {
 				config,
 				file,
 				validate_only,
+				format,
+				print_config_schema: _,
+				openapi_diff,
 			} = args;
+			if let Some(files) = openapi_diff {
+				return diff_openapi(&files[0], &files[1]);
+			}
 
-			let (contents, filename) = match (config, file) {
-				(Some(_), Some(_)) => {
-					anyhow::bail!("only one of --config or --file")
-				},
-				(Some(config), None) => (config, None),
-				(None, Some(file)) => {
-					let contents = fs_err::read_to_string(&file)?;
-					(contents, Some(file))
-				},
-				(None, None) => ("{}".to_string(), None),
-			};
 			if validate_only {
-				return validate(contents, filename).await;
+				return validate(config, &file, format).await;
 			}
-			let config = agentgateway::config::parse_config(contents, filename)?;
+			let (contents, filename, merged) = read_config(config, &file).await?;
+			let config = parse_config(contents, filename, merged)?;
+			telemetry::set_format(config.log_format)?;
 			proxy(Arc::new(config)).await
 		})
 }
 
-async fn validate(contents: String, filename: Option<PathBuf>) -> anyhow::Result<()> {
-	let config = agentgateway::config::parse_config(contents, filename)?;
-	let client = client::Client::new(&config.dns, None);
-	if let Some(cfg) = config.xds.local_config {
-		let cs = cfg.read_to_string().await?;
-		agentgateway::types::local::NormalizedLocalConfig::from(client, cs.as_str()).await?;
+/// Reads the config passed inline or from files. Several files, or a directory, are merged.
+async fn read_config(
+	config: Option<String>,
+	file: &[PathBuf],
+) -> anyhow::Result<(String, Option<PathBuf>, Option<ConfigSource>)> {
+	Ok(match (config, file) {
+		(Some(_), [_, ..]) => {
+			anyhow::bail!("only one of --config or --file")
+		},
+		(Some(config), []) => (config, None, None),
+		(None, [file]) if !file.is_dir() => {
+			let contents = fs_err::read_to_string(file)?;
+			(contents, Some(file.clone()), None)
+		},
+		(None, []) => ("{}".to_string(), None, None),
+		(None, files) => {
+			let source = ConfigSource::Files(files.to_vec());
+			(source.read_to_string().await?, None, Some(source))
+		},
+	})
+}
+
+/// Parses the config. When it is merged from several files, the merged config is watched for
+/// changes, rather than a single file.
+fn parse_config(
+	contents: String,
+	filename: Option<PathBuf>,
+	merged: Option<ConfigSource>,
+) -> anyhow::Result<Config> {
+	let mut config = agentgateway::config::parse_config(contents, filename)?;
+	if config.xds.local_config.is_none() {
+		config.xds.local_config = merged;
+	}
+	Ok(config)
+}
+
+/// Loads the config as the proxy would, converting every bind, policy and backend, and checks each
+/// MCP target could be set up, but without binding any listener or connecting to targets.
+async fn validate(config: Option<String>, file: &[PathBuf], format: Format) -> anyhow::Result<()> {
+	let errors = async {
+		let (contents, filename, merged) = read_config(config, file).await?;
+		let config = parse_config(contents, filename, merged)?;
+		let client = client::Client::new(&config.dns, None);
+		let Some(cfg) = config.xds.local_config else {
+			return anyhow::Ok(vec![]);
+		};
+		let local =
+			agentgateway::types::local::NormalizedLocalConfig::from_source(client.clone(), &cfg).await?;
+		Ok(
+			local
+				.check_targets(&client, &config.stdio_command_allowlist)
+				.await,
+		)
+	}
+	.await
+	.unwrap_or_else(|e| vec![e]);
+	match format {
+		Format::Text => {
+			for e in &errors {
+				eprintln!("{e:#}");
+			}
+			if !errors.is_empty() {
+				anyhow::bail!("configuration is invalid");
+			}
+			println!("Configuration is valid!");
+		},
+		Format::Json => {
+			let errors: Vec<String> = errors.iter().map(|e| format!("{e:#}")).collect();
+			let report = serde_json::json!({
+				"valid": errors.is_empty(),
+				"errors": errors,
+			});
+			println!("{report}");
+			if !errors.is_empty() {
+				anyhow::bail!("configuration is invalid");
+			}
+		},
+	}
+	Ok(())
+}
+
+fn diff_openapi(old: &Path, new: &Path) -> anyhow::Result<()> {
+	let changes = agentgateway::mcp::openapi::compat::compare_files(old, new)?;
+	for change in &changes {
+		println!("{change}");
+	}
+	let breaking = changes.iter().filter(|c| c.is_breaking()).count();
+	if breaking > 0 {
+		anyhow::bail!("found {breaking} breaking changes");
 	}
-	println!("Configuration is valid!");
+	println!("No breaking changes");
 	Ok(())
 }
 
@@ -0,0 +1,65 @@
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Runs the gateway with a bind on a port that is already in use.
+fn run(fatal_bind_errors: bool) -> (Child, std::net::TcpListener) {
+	let taken = std::net::TcpListener::bind("[::]:0").unwrap();
+	let port = taken.local_addr().unwrap().port();
+	let config = format!(
+		r#"
+config:
+  fatalBindErrors: {fatal_bind_errors}
+  adminAddr: localhost:0
+  statsAddr: localhost:0
+  readinessAddr: localhost:0
+binds:
+- port: {port}
+  listeners: []
+"#
+	);
+	let child = Command::new(env!("CARGO_BIN_EXE_agentgateway"))
+		.args(["--config", &config])
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	(child, taken)
+}
+
+/// Waits up to `timeout` for the process to exit, returning its status if it did.
+fn exited(child: &mut Child, timeout: Duration) -> Option<std::process::ExitStatus> {
+	let start = Instant::now();
+	while start.elapsed() < timeout {
+		if let Some(status) = child.try_wait().unwrap() {
+			return Some(status);
+		}
+		std::thread::sleep(Duration::from_millis(50));
+	}
+	None
+}
+
+#[test]
+fn test_fatal_bind_error_exits() {
+	let (mut child, taken) = run(true);
+	let port = taken.local_addr().unwrap().port();
+	let Some(status) = exited(&mut child, Duration::from_secs(30)) else {
+		child.kill().unwrap();
+		panic!("the gateway kept running after failing to bind");
+	};
+	assert!(!status.success());
+	let out = child.wait_with_output().unwrap();
+	let stderr = String::from_utf8_lossy(&out.stderr);
+	assert!(
+		stderr.contains(&format!("failed to bind [::]:{port}")),
+		"{stderr}"
+	);
+}
+
+#[test]
+fn test_bind_error_not_fatal_by_default() {
+	let (mut child, _taken) = run(false);
+	let status = exited(&mut child, Duration::from_secs(2));
+	child.kill().unwrap();
+	child.wait().unwrap();
+	assert_eq!(status, None, "the gateway should keep running");
+}
@@ -0,0 +1,51 @@
+use std::process::Command;
+
+fn validate(name: &str, config: &str) -> (bool, serde_json::Value) {
+	let file = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+	fs_err::write(&file, config).unwrap();
+	let out = Command::new(env!("CARGO_BIN_EXE_agentgateway"))
+		.args(["--validate-only", "--format", "json", "--file"])
+		.arg(&file)
+		.output()
+		.unwrap();
+	let stdout = String::from_utf8(out.stdout).unwrap();
+	let report = serde_json::from_str(&stdout)
+		.unwrap_or_else(|e| panic!("stdout is not only the JSON report ({e}): {stdout}"));
+	(out.status.success(), report)
+}
+
+#[test]
+fn test_validate_json() {
+	let (ok, report) = validate("valid.yaml", "binds: []");
+	assert!(ok);
+	assert_eq!(report, serde_json::json!({ "valid": true, "errors": [] }));
+
+	let (ok, report) = validate(
+		"stdio.yaml",
+		r#"
+config:
+  stdioCommandAllowlist: [uvx]
+binds:
+- port: 3000
+  listeners:
+  - routes:
+    - backends:
+      - mcp:
+          targets:
+          - name: everything
+            stdio:
+              cmd: npx
+"#,
+	);
+	assert!(!ok);
+	assert_eq!(report["valid"], false);
+	let errors = report["errors"].as_array().unwrap();
+	assert_eq!(errors.len(), 1, "{report}");
+	let error = errors[0].as_str().unwrap();
+	assert!(error.contains("MCP target 'everything'"), "{error}");
+
+	let (ok, report) = validate("invalid.yaml", "binds: [{ port: not-a-port }]");
+	assert!(!ok);
+	assert_eq!(report["valid"], false);
+	assert_eq!(report["errors"].as_array().unwrap().len(), 1, "{report}");
+}
@@ -1,23 +1,46 @@
-use axum::body::to_bytes;
-use http::{Method, Request, header};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use a2a_sdk::{
+	A2aResponse, CancelTaskRequestMethod, ConstString, SendStreamingMessageSuccessResponseResult,
+	SendTaskStreamingResponseResult, TaskState,
+};
+use http::{Method, Request, StatusCode, header};
 use serde_json::{Value, json};
-use tracing::warn;
+use tracing::{info, warn};
 
+use crate::http::jwt::Claims;
 use crate::http::{Body, Response, filters};
 use crate::llm::AIError;
-use crate::types::agent::A2aPolicy;
+use crate::mcp::rbac::{Identity, ResourceId, ResourceType, RuleSets};
+use crate::proxy::ProxyError;
+use crate::telemetry::metrics::{A2aTaskTransitionLabels, Metrics};
+use crate::types::agent::{A2aPolicy, BackendName};
 use crate::{json, parse};
 
-pub async fn apply_to_request(pol: Option<&A2aPolicy>, req: &mut Request<Body>) -> RequestType {
+pub async fn apply_to_request(
+	pol: Option<&A2aPolicy>,
+	req: &mut Request<Body>,
+) -> Result<RequestType, ProxyError> {
 	let Some(pol) = pol else {
-		return RequestType::Unknown;
+		return Ok(RequestType::Unknown);
 	};
 	// Possible options are POST a JSON-RPC message or GET /.well-known/agent.json
 	// For agent card, we will process only on the response
-	classify_request(req).await
+	classify_request(req, pol).await
 }
 
-async fn classify_request(req: &mut Request<Body>) -> RequestType {
+const DEFAULT_MAX_REQUEST_SIZE: usize = 4 * 1024 * 1024;
+
+// How much of a response body is logged, when bodies are logged.
+const LOGGED_BODY_LIMIT: usize = 16 * 1024;
+
+async fn classify_request(
+	req: &mut Request<Body>,
+	pol: &A2aPolicy,
+) -> Result<RequestType, ProxyError> {
+	let limit = pol.max_request_size.unwrap_or(DEFAULT_MAX_REQUEST_SIZE);
 	// Possible options are POST a JSON-RPC message or GET /.well-known/agent.json
 	// For agent card, we will process only on the response
 	match (req.method(), req.uri().path()) {
@@ -28,44 +51,211 @@ async fn classify_request(req: &mut Request<Body>) -> RequestType {
 				.get::<filters::OriginalUrl>()
 				.map(|u| u.0.clone())
 				.unwrap_or_else(|| req.uri().clone());
-			RequestType::AgentCard(uri)
+			let identity = Identity::new(req.extensions().get::<Claims>().cloned(), None);
+			Ok(RequestType::AgentCard(uri, identity))
 		},
 		(m, _) if m == http::Method::POST => {
-			let method = match crate::http::classify_content_type(req.headers()) {
+			// Reject early if the client tells us the body is too large, rather than reading it
+			let declared = req
+				.headers()
+				.get(header::CONTENT_LENGTH)
+				.and_then(|v| v.to_str().ok())
+				.and_then(|v| v.parse::<u64>().ok());
+			if declared.is_some_and(|len| len > limit as u64) {
+				return Err(ProxyError::RequestTooLarge);
+			}
+			let call = match crate::http::classify_content_type(req.headers()) {
 				crate::http::WellKnownContentTypes::Json => {
-					match json::inspect_body::<a2a_sdk::A2aRequest>(req.body_mut()).await {
-						Ok(call) => call.method(),
+					let body = json::inspect_body_with_limit::<Value>(req.body_mut(), limit).await;
+					if let Err(e) = body.as_ref()
+						&& e.is::<json::BodyTooLarge>()
+					{
+						warn!("rejecting a2a request: {e}");
+						return Err(ProxyError::RequestTooLarge);
+					}
+					// Keep the id even if we fail to understand the call, so errors can still be correlated
+					let id = body.as_ref().ok().and_then(|b| b.get("id")).cloned();
+					let call =
+						body.and_then(|b| serde_json::from_value::<a2a_sdk::A2aRequest>(b).map_err(Into::into));
+					match call {
+						Ok(call) => {
+							if let Some(logging) = &pol.logging {
+								let identity = Identity::new(req.extensions().get::<Claims>().cloned(), None);
+								let params = logging.body(|| {
+									serde_json::to_value(&call)
+										.ok()
+										.and_then(|v| v.get("params").cloned())
+								});
+								info!(
+									method = call.method(),
+									identity = identity.get_claim("sub", ".").unwrap_or("unknown"),
+									params = params.as_deref(),
+									"a2a request",
+								);
+							}
+							if let Some(cfg) = call.push_notification_config() {
+								let deny_private = pol.deny_private_push_notification_urls;
+								if let Err(e) = validate_push_notification_url(&cfg.url, deny_private) {
+									warn!("rejecting a2a {} request: {e}", call.method());
+									return Ok(RequestType::Rejected(error_response(
+										StatusCode::BAD_REQUEST,
+										id.as_ref(),
+										INVALID_PARAMS,
+										&e.to_string(),
+									)));
+								}
+							}
+							Call {
+								method: call.method(),
+								id,
+							}
+						},
 						Err(e) => {
 							warn!("failed to read a2a request: {e}");
-							"unknown"
+							Call {
+								method: "unknown",
+								id,
+							}
 						},
 					}
 				},
 				_ => {
 					warn!("unknown content type from A2A");
-					"unknown"
+					Call {
+						method: "unknown",
+						id: None,
+					}
 				},
 			};
-			RequestType::Call(method)
+			Ok(RequestType::Call(call))
+		},
+		_ => Ok(RequestType::Unknown),
+	}
+}
+
+/// Push notifications are webhooks the agent will call, so they must be an absolute HTTP(S) URL.
+/// With `deny_private`, addresses on the local network are rejected too, to keep clients from using
+/// the agent to reach internal services.
+fn validate_push_notification_url(raw: &str, deny_private: bool) -> anyhow::Result<()> {
+	let url = url::Url::parse(raw)
+		.map_err(|e| anyhow::anyhow!("invalid push notification url {raw:?}: {e}"))?;
+	if !matches!(url.scheme(), "http" | "https") || !url.has_host() {
+		anyhow::bail!("invalid push notification url {raw:?}: must be an absolute http(s) URL");
+	}
+	if !deny_private {
+		return Ok(());
+	}
+	let internal = match url.host() {
+		Some(url::Host::Ipv4(ip)) => is_internal_ip(IpAddr::V4(ip)),
+		Some(url::Host::Ipv6(ip)) => is_internal_ip(IpAddr::V6(ip)),
+		Some(url::Host::Domain(d)) => {
+			d.eq_ignore_ascii_case("localhost") || d.to_ascii_lowercase().ends_with(".localhost")
+		},
+		None => false,
+	};
+	if internal {
+		anyhow::bail!("invalid push notification url {raw:?}: must not be a local or private address");
+	}
+	Ok(())
+}
+
+fn is_internal_ip(ip: IpAddr) -> bool {
+	match ip {
+		IpAddr::V4(ip) => {
+			ip.is_loopback()
+				|| ip.is_private()
+				|| ip.is_link_local()
+				|| ip.is_unspecified()
+				|| ip.is_broadcast()
+		},
+		IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+			Some(v4) => is_internal_ip(IpAddr::V4(v4)),
+			None => {
+				ip.is_loopback()
+					|| ip.is_unspecified()
+					|| ip.is_unique_local()
+					|| ip.is_unicast_link_local()
+			},
 		},
-		_ => RequestType::Unknown,
 	}
 }
 
 pub enum RequestType {
 	Unknown,
-	AgentCard(http::Uri),
-	Call(&'static str),
+	AgentCard(http::Uri, Identity),
+	Call(Call),
+	/// The request was rejected by the gateway, and the response should be sent directly.
+	Rejected(Response),
+}
+
+/// A JSON-RPC call to an agent.
+#[derive(Debug, Clone)]
+pub struct Call {
+	pub method: &'static str,
+	/// The JSON-RPC request id, echoed back in any error we generate for the call.
+	pub id: Option<Value>,
+}
+
+// Standard JSON-RPC error codes, see https://www.jsonrpc.org/specification#error_object
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const INTERNAL_ERROR: i32 = -32603;
+
+/// Converts an error from proxying an A2A call into a JSON-RPC error response, so clients get an
+/// error object correlated with their request rather than a bare status. The HTTP status is kept.
+pub fn error_response_for(err: &ProxyError, call: &Call) -> Response {
+	let code = match err {
+		ProxyError::RequestTooLarge | ProxyError::InvalidRequest => INVALID_REQUEST,
+		ProxyError::RouteNotFound
+		| ProxyError::BackendDoesNotExist
+		| ProxyError::ServiceNotFound
+		| ProxyError::NoValidBackends => METHOD_NOT_FOUND,
+		_ => INTERNAL_ERROR,
+	};
+	error_response(
+		err.as_response().status(),
+		call.id.as_ref(),
+		code,
+		&err.to_string(),
+	)
+}
+
+fn error_response(status: StatusCode, id: Option<&Value>, code: i32, message: &str) -> Response {
+	::http::Response::builder()
+		.status(status)
+		.header(header::CONTENT_TYPE, "application/json")
+		.body(Body::from(json_rpc_error(id, code, message).to_string()))
+		.expect("static response is valid")
+}
+
+fn json_rpc_error(id: Option<&Value>, code: i32, message: &str) -> Value {
+	json!({
+		"jsonrpc": "2.0",
+		"id": id.unwrap_or(&Value::Null),
+		"error": {
+			"code": code,
+			"message": message,
+		},
+	})
+}
+
+/// Authorization rules for the skills advertised by an agent, keyed by the backend serving it.
+pub struct SkillAuthorization {
+	pub backend: BackendName,
+	pub rules: RuleSets,
 }
 
 pub async fn apply_to_response(
 	pol: Option<&A2aPolicy>,
 	a2a_type: RequestType,
+	authz: Option<SkillAuthorization>,
+	metrics: Option<Arc<Metrics>>,
 	resp: &mut Response,
 ) -> anyhow::Result<()> {
 	let Some(pol) = pol else { return Ok(()) };
 	match a2a_type {
-		RequestType::AgentCard(uri) => {
+		RequestType::AgentCard(uri, identity) => {
 			// For agent card, we need to mutate the request to insert the proper URL to reach it
 			// through the gateway.
 			let body = std::mem::replace(resp.body_mut(), Body::empty());
@@ -85,29 +275,249 @@ pub async fn apply_to_response(
 
 			*url_field = Value::String(new_uri);
 
+			let authenticated = identity.claims.is_some();
+			filter_agent_card(&mut agent_card, authenticated, |skill| {
+				authz.as_ref().is_none_or(|authz| {
+					authz.rules.validate(
+						&ResourceType::Tool(ResourceId::new(
+							authz.backend.to_string(),
+							skill.to_string(),
+						)),
+						&identity,
+					)
+				})
+			});
+
 			resp.headers_mut().remove(header::CONTENT_LENGTH);
 			*resp.body_mut() = json::to_body(agent_card)?;
 			Ok(())
 		},
-		RequestType::Call(_) => {
-			// TODO: we don't really do anything with the response... but if we did, we could do this.
+		RequestType::Call(Call { method, id }) => {
+			let logging = pol.logging.clone();
+			if logging.as_ref().is_some_and(|l| !l.include_bodies) {
+				info!(method, status = %resp.status(), "a2a response");
+			}
+			match crate::http::classify_content_type(resp.headers()) {
+				crate::http::WellKnownContentTypes::Json => {
+					if let Some(logging) = logging.filter(|l| l.include_bodies) {
+						// The body is logged once it has been passed on, so large responses are not held up
+						let status = resp.status();
+						let body = std::mem::replace(resp.body_mut(), Body::empty());
+						*resp.body_mut() =
+							parse::passthrough::prefix(body, LOGGED_BODY_LIMIT, move |prefix, truncated| {
+								let result = if truncated {
+									// Redactions cannot be applied to a partial document, so it is only logged without them
+									logging
+										.redact
+										.is_empty()
+										.then(|| String::from_utf8_lossy(&prefix).into_owned())
+								} else {
+									logging.body(|| {
+										serde_json::from_slice::<Value>(&prefix)
+											.ok()
+											.and_then(|v| v.get("result").or(v.get("error")).cloned())
+									})
+								};
+								info!(method, %status, result = result.as_deref(), truncated, "a2a response");
+							});
+					}
+				},
+				crate::http::WellKnownContentTypes::Sse => {
+					let status = resp.status();
+					let logging = logging.filter(|l| l.include_bodies);
+					let mut tracker = pol.validate_task_states.then(TaskStateTracker::default);
+					let orig = std::mem::replace(resp.body_mut(), Body::empty());
+					*resp.body_mut() = parse::sse::json_passthrough_until_error::<Value>(orig, move |msg| {
+						let msg = match msg {
+							Ok(msg) => msg,
+							Err(e) => {
+								warn!(method, "invalid event from a2a stream: {e}");
+								return Err(invalid_event_error(&e));
+							},
+						};
+						if let Some(logging) = &logging {
+							let result = logging.body(|| msg.get("result").or(msg.get("error")).cloned());
+							info!(method, %status, result = result.as_deref(), "a2a response event");
+						}
+						// Only streaming responses can carry multiple states for a task
+						let Some(tracker) = tracker.as_mut() else {
+							return Ok(());
+						};
+						let Some((id, state)) = serde_json::from_value::<a2a_sdk::JsonRpcMessage>(msg)
+							.ok()
+							.as_ref()
+							.and_then(|m| m.response())
+							.and_then(task_state)
+						else {
+							return Ok(());
+						};
+						if let Err(prev) = tracker.observe(&id, state) {
+							if let Some(m) = &metrics {
+								m.a2a_invalid_task_transitions
+									.get_or_create(&A2aTaskTransitionLabels {
+										from: prev.into(),
+										to: state.into(),
+									})
+									.inc();
+							}
+							warn!(
+								method,
+								task = %id,
+								"a2a task moved from {prev} to {state}, which is not a valid transition"
+							);
+						}
+						Ok(())
+					});
+				},
+				crate::http::WellKnownContentTypes::Unknown if !resp.status().is_success() => {
+					// Typically a proxy or web server in front of the agent, rather than the agent itself.
+					// Replace the response so the client still gets a JSON-RPC error.
+					warn!(method, status = %resp.status(), "a2a call failed with a non JSON-RPC response");
+					let err = status_error(method, resp.status());
+					*resp = error_response(resp.status(), id.as_ref(), err.code.0, &err.message);
+				},
+				crate::http::WellKnownContentTypes::Unknown => {
+					warn!(
+						method,
+						status = %resp.status(),
+						"a2a response has an unexpected content type; expected JSON or an event stream"
+					);
+				},
+			}
 			Ok(())
-			// match crate::http::classify_content_type(resp.headers()) {
-			// 	crate::http::WellKnownContentTypes::Json => {
-			// 		let call = json::inspect_body::<a2a_sdk::JsonRpcMessage>(resp.body_mut()).await?;
-			// 	},
-			// 	crate::http::WellKnownContentTypes::Sse => {
-			// 		let orig = std::mem::replace(resp.body_mut(), crate::http::Body::empty());
-			// 		let new_body = parse::sse::json_parser::<a2a_sdk::JsonRpcMessage>(orig, |f| {
-			// 		});
-			// 		*resp.body_mut() = new_body;
-			// 	},
-			// 	_ => {
-			// 		warn!("unknown content type from A2A");
-			// 	}
-			// }
-			// Ok(())
 		},
-		RequestType::Unknown => Ok(()),
+		RequestType::Unknown | RequestType::Rejected(_) => Ok(()),
+	}
+}
+
+/// Removes skills the caller is not allowed to see from the agent card, and narrows the advertised
+/// default modes to those still used by a remaining skill. When the caller is already
+/// authenticated, the (legacy) authentication block is dropped as well.
+fn filter_agent_card(card: &mut Value, authenticated: bool, allowed: impl Fn(&str) -> bool) {
+	let Some(card) = card.as_object_mut() else {
+		return;
+	};
+	if authenticated {
+		card.remove("authentication");
+	}
+	let Some(Value::Array(skills)) = card.get_mut("skills") else {
+		return;
+	};
+	let before = skills.len();
+	skills.retain(|s| s.get("id").and_then(Value::as_str).is_some_and(&allowed));
+	if skills.len() == before {
+		// Nothing was removed, so leave the advertised modes as the agent defined them
+		return;
+	}
+	let skills = skills.clone();
+	for (default_key, skill_key) in [
+		("defaultInputModes", "inputModes"),
+		("defaultOutputModes", "outputModes"),
+	] {
+		let mut supported = HashSet::new();
+		for skill in &skills {
+			match skill.get(skill_key).and_then(Value::as_array) {
+				Some(modes) if !modes.is_empty() => {
+					supported.extend(modes.iter().filter_map(Value::as_str));
+				},
+				// A skill without explicit modes uses the defaults, so all of them are still needed
+				_ => {
+					supported.clear();
+					break;
+				},
+			}
+		}
+		if supported.is_empty() {
+			continue;
+		}
+		if let Some(Value::Array(defaults)) = card.get_mut(default_key) {
+			defaults.retain(|m| m.as_str().is_some_and(|m| supported.contains(m)));
+		}
+	}
+}
+
+/// The JSON-RPC error for a call the agent failed with a bare HTTP status. An agent refusing to
+/// cancel a task gets the A2A error for it.
+fn status_error(method: &str, status: StatusCode) -> a2a_sdk::ErrorData {
+	let message = format!("agent returned {status}");
+	let cancel = method == CancelTaskRequestMethod::VALUE;
+	match status {
+		StatusCode::NOT_FOUND if cancel => a2a_sdk::TaskNotFoundError {
+			code: a2a_sdk::ErrorCode::TASK_NOT_FOUND.0.into(),
+			data: Value::Null,
+			message,
+		}
+		.into(),
+		StatusCode::CONFLICT if cancel => a2a_sdk::TaskNotCancelableError {
+			code: a2a_sdk::ErrorCode::TASK_NOT_CANCELABLE.0.into(),
+			data: Value::Null,
+			message,
+		}
+		.into(),
+		StatusCode::NOT_FOUND => a2a_sdk::ErrorData {
+			code: a2a_sdk::ErrorCode(METHOD_NOT_FOUND),
+			message: message.into(),
+			data: None,
+		},
+		_ => a2a_sdk::ErrorData {
+			code: a2a_sdk::ErrorCode(INTERNAL_ERROR),
+			message: message.into(),
+			data: None,
+		},
 	}
 }
+
+/// Builds the JSON-RPC error event sent in place of an event we could not parse. The request id is
+/// unknown at this point, so it is null as the JSON-RPC spec requires.
+fn invalid_event_error(err: &anyhow::Error) -> bytes::Bytes {
+	let err = json_rpc_error(
+		None,
+		INTERNAL_ERROR,
+		&format!("upstream agent sent an invalid event: {err}"),
+	);
+	bytes::Bytes::from(err.to_string())
+}
+
+fn task_state(resp: &A2aResponse) -> Option<(String, TaskState)> {
+	match resp {
+		A2aResponse::SendTaskResponse(Some(task)) => Some((task.id.clone(), task.status.state)),
+		A2aResponse::SendTaskUpdateResponse(SendTaskStreamingResponseResult::Status(ev)) => {
+			Some((ev.id.clone(), ev.status.state))
+		},
+		A2aResponse::SendTaskUpdateResponse(SendTaskStreamingResponseResult::StreamingStatus(ev)) => {
+			Some((ev.task_id.clone(), ev.status.state))
+		},
+		A2aResponse::SendStreamingMessageResponse(resp) => match &resp.result.result {
+			SendStreamingMessageSuccessResponseResult::Task(task) => {
+				Some((task.id.clone(), task.status.state))
+			},
+			SendStreamingMessageSuccessResponseResult::TaskStatusUpdateEvent(ev) => {
+				Some((ev.task_id.clone(), ev.status.state))
+			},
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// Tracks the last seen state of each task in a stream, so invalid transitions from a misbehaving
+/// agent can be detected.
+#[derive(Debug, Default)]
+pub struct TaskStateTracker {
+	last: HashMap<String, TaskState>,
+}
+
+impl TaskStateTracker {
+	/// Records the new state of a task. If moving to it from the previously seen state is not
+	/// allowed, the previous state is returned as an error.
+	pub fn observe(&mut self, id: &str, state: TaskState) -> Result<(), TaskState> {
+		match self.last.insert(id.to_string(), state) {
+			Some(prev) if !prev.can_transition_to(state) => Err(prev),
+			_ => Ok(()),
+		}
+	}
+}
+
+#[cfg(test)]
+#[path = "tests.rs"]
+mod tests;
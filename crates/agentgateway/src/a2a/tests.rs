@@ -0,0 +1,443 @@
+use a2a_sdk::TaskState;
+use axum::body::to_bytes;
+
+use super::*;
+
+#[test]
+fn test_task_state_tracker() {
+	let mut tracker = TaskStateTracker::default();
+	assert_eq!(tracker.observe("a", TaskState::Submitted), Ok(()));
+	assert_eq!(tracker.observe("a", TaskState::Working), Ok(()));
+	assert_eq!(tracker.observe("a", TaskState::Working), Ok(()));
+	assert_eq!(tracker.observe("a", TaskState::Completed), Ok(()));
+	assert_eq!(
+		tracker.observe("a", TaskState::Working),
+		Err(TaskState::Completed)
+	);
+	// Tasks are tracked independently
+	assert_eq!(tracker.observe("b", TaskState::Working), Ok(()));
+	assert_eq!(tracker.observe("b", TaskState::Failed), Ok(()));
+}
+
+#[test]
+fn test_validate_push_notification_url() {
+	for url in [
+		"https://example.com/webhook",
+		"http://203.0.113.7:8080/notify",
+		"https://[2001:db8::1]/notify",
+	] {
+		assert!(validate_push_notification_url(url, true).is_ok(), "{url}");
+	}
+	// Not an absolute http(s) URL
+	for url in ["file:///etc/passwd", "/webhook"] {
+		assert!(validate_push_notification_url(url, false).is_err(), "{url}");
+	}
+	let private = [
+		// Loopback
+		"http://127.0.0.1/notify",
+		"http://localhost:8080/notify",
+		"http://[::1]/notify",
+		// Private
+		"http://10.0.0.5/notify",
+		"http://192.168.1.1/notify",
+		"http://172.16.0.1/notify",
+		"http://[fd00::1]/notify",
+		"http://[::ffff:10.0.0.1]/notify",
+		// Link-local, including cloud metadata endpoints
+		"http://169.254.169.254/latest/meta-data",
+		"http://[fe80::1]/notify",
+	];
+	for url in private {
+		assert!(validate_push_notification_url(url, false).is_ok(), "{url}");
+		assert!(validate_push_notification_url(url, true).is_err(), "{url}");
+	}
+}
+
+#[test]
+fn test_task_state_from_response() {
+	let js = serde_json::json! {
+	{
+		"jsonrpc": "2.0",
+		"id": 1,
+		"result": {
+			"id": "task-1",
+			"status": {
+				"state": "working"
+			},
+			"final": false
+		}
+	}
+	};
+	let msg: a2a_sdk::JsonRpcMessage = serde_json::from_value(js).unwrap();
+	assert_eq!(
+		msg.response().and_then(task_state),
+		Some(("task-1".to_string(), TaskState::Working))
+	);
+}
+
+#[test]
+fn test_task_state_tracker_message_stream() {
+	let status_update = |state: &str| {
+		serde_json::json!({
+			"kind": "status-update",
+			"taskId": "task-1",
+			"contextId": "ctx-1",
+			"status": { "state": state },
+			"final": state == "completed",
+		})
+	};
+	// The events of a message/stream response: the task, then updates to its status
+	let events = [
+		serde_json::json!({
+			"kind": "task",
+			"id": "task-1",
+			"contextId": "ctx-1",
+			"status": { "state": "submitted" },
+		}),
+		status_update("working"),
+		status_update("completed"),
+		status_update("working"),
+	];
+	let mut tracker = TaskStateTracker::default();
+	let observed = events
+		.into_iter()
+		.map(|result| {
+			let msg: a2a_sdk::JsonRpcMessage =
+				serde_json::from_value(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+					.unwrap();
+			let (id, state) = msg.response().and_then(task_state).unwrap();
+			assert_eq!(id, "task-1");
+			tracker.observe(&id, state)
+		})
+		.collect::<Vec<_>>();
+	assert_eq!(
+		observed,
+		vec![Ok(()), Ok(()), Ok(()), Err(TaskState::Completed)]
+	);
+}
+
+fn multi_skill_card() -> Value {
+	serde_json::json!({
+		"name": "agent",
+		"url": "http://localhost:9999",
+		"authentication": { "schemes": ["bearer"] },
+		"defaultInputModes": ["text", "image", "audio"],
+		"defaultOutputModes": ["text", "image"],
+		"skills": [
+			{ "id": "chat", "name": "chat", "tags": [], "inputModes": ["text"], "outputModes": ["text"] },
+			{ "id": "vision", "name": "vision", "tags": [], "inputModes": ["image"], "outputModes": ["text", "image"] },
+			{ "id": "voice", "name": "voice", "tags": [], "inputModes": ["audio"], "outputModes": ["text"] }
+		]
+	})
+}
+
+fn skill_ids(card: &Value) -> Vec<&str> {
+	card["skills"]
+		.as_array()
+		.unwrap()
+		.iter()
+		.map(|s| s["id"].as_str().unwrap())
+		.collect()
+}
+
+#[test]
+fn test_filter_agent_card_recomputes_modes() {
+	let mut card = multi_skill_card();
+	filter_agent_card(&mut card, false, |skill| skill != "vision");
+	assert_eq!(skill_ids(&card), vec!["chat", "voice"]);
+	assert_eq!(
+		card["defaultInputModes"],
+		serde_json::json!(["text", "audio"])
+	);
+	assert_eq!(card["defaultOutputModes"], serde_json::json!(["text"]));
+	// Not authenticated, so the authentication block is still advertised
+	assert!(card.get("authentication").is_some());
+}
+
+#[test]
+fn test_filter_agent_card_unchanged_when_all_allowed() {
+	let mut card = multi_skill_card();
+	filter_agent_card(&mut card, false, |_| true);
+	assert_eq!(card, multi_skill_card());
+}
+
+#[test]
+fn test_filter_agent_card_skill_without_modes_keeps_defaults() {
+	let mut card = multi_skill_card();
+	card["skills"][0]
+		.as_object_mut()
+		.unwrap()
+		.remove("inputModes");
+	filter_agent_card(&mut card, false, |skill| skill != "voice");
+	assert_eq!(skill_ids(&card), vec!["chat", "vision"]);
+	assert_eq!(
+		card["defaultInputModes"],
+		serde_json::json!(["text", "image", "audio"])
+	);
+	assert_eq!(
+		card["defaultOutputModes"],
+		serde_json::json!(["text", "image"])
+	);
+}
+
+#[test]
+fn test_filter_agent_card_authenticated() {
+	let mut card = multi_skill_card();
+	filter_agent_card(&mut card, true, |_| true);
+	assert!(card.get("authentication").is_none());
+	assert_eq!(skill_ids(&card).len(), 3);
+}
+
+fn call_request(body: Vec<u8>, content_length: bool) -> Request<Body> {
+	let mut rb = Request::builder()
+		.method(Method::POST)
+		.uri("http://agent/")
+		.header(header::CONTENT_TYPE, "application/json");
+	if content_length {
+		rb = rb.header(header::CONTENT_LENGTH, body.len());
+	}
+	rb.body(Body::from(body)).unwrap()
+}
+
+fn oversized_call(limit: usize) -> Vec<u8> {
+	let call = json!({
+		"jsonrpc": "2.0",
+		"id": 1,
+		"method": "tasks/get",
+		"params": { "id": "x".repeat(limit) },
+	});
+	serde_json::to_vec(&call).unwrap()
+}
+
+#[tokio::test]
+async fn test_request_size_limit() {
+	let pol: A2aPolicy = serde_json::from_value(json!({ "maxRequestSize": 1024 })).unwrap();
+	// Rejected up front from the content-length, and while reading when it is not declared
+	for content_length in [true, false] {
+		let mut req = call_request(oversized_call(1024), content_length);
+		let res = apply_to_request(Some(&pol), &mut req).await;
+		assert!(
+			matches!(res, Err(ProxyError::RequestTooLarge)),
+			"content_length={content_length}"
+		);
+		assert_eq!(
+			ProxyError::RequestTooLarge.as_response().status(),
+			http::StatusCode::PAYLOAD_TOO_LARGE
+		);
+	}
+
+	let small = serde_json::to_vec(&json!({
+		"jsonrpc": "2.0",
+		"id": 1,
+		"method": "tasks/get",
+		"params": { "id": "x" },
+	}))
+	.unwrap();
+	let mut req = call_request(small.clone(), true);
+	let res = apply_to_request(Some(&pol), &mut req).await;
+	assert!(matches!(
+		res,
+		Ok(RequestType::Call(Call {
+			method: "tasks/get",
+			id: Some(_),
+		}))
+	));
+	// The body is still forwarded intact
+	let body = to_bytes(std::mem::replace(req.body_mut(), Body::empty()), usize::MAX)
+		.await
+		.unwrap();
+	assert_eq!(body.as_ref(), small.as_slice());
+}
+
+async fn response_json(resp: Response) -> Value {
+	let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+	serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_error_response_for_missing_target() {
+	let call = Call {
+		method: "tasks/get",
+		id: Some(json!("req-1")),
+	};
+	let resp = error_response_for(&ProxyError::BackendDoesNotExist, &call);
+	assert_eq!(resp.status(), http::StatusCode::INTERNAL_SERVER_ERROR);
+	assert_eq!(
+		resp.headers().get(header::CONTENT_TYPE).unwrap(),
+		"application/json"
+	);
+	let body = response_json(resp).await;
+	assert_eq!(body["jsonrpc"], "2.0");
+	assert_eq!(body["id"], "req-1");
+	assert_eq!(body["error"]["code"], METHOD_NOT_FOUND);
+
+	// Failures reaching the agent are internal errors
+	let resp = error_response_for(&ProxyError::RequestTimeout, &call);
+	assert_eq!(resp.status(), http::StatusCode::GATEWAY_TIMEOUT);
+	assert_eq!(response_json(resp).await["error"]["code"], INTERNAL_ERROR);
+}
+
+#[tokio::test]
+async fn test_non_json_rpc_error_response() {
+	let pol: A2aPolicy = serde_json::from_value(json!({})).unwrap();
+	let call = Call {
+		method: "tasks/get",
+		id: Some(json!(7)),
+	};
+	let mut resp = ::http::Response::builder()
+		.status(http::StatusCode::NOT_FOUND)
+		.header(header::CONTENT_TYPE, "text/html")
+		.body(Body::from("<h1>Not Found</h1>"))
+		.unwrap();
+	apply_to_response(Some(&pol), RequestType::Call(call), None, None, &mut resp)
+		.await
+		.unwrap();
+	assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+	let body = response_json(resp).await;
+	assert_eq!(body["id"], 7);
+	assert_eq!(body["error"]["code"], METHOD_NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_cancel_task_error_response() {
+	let pol: A2aPolicy = serde_json::from_value(json!({})).unwrap();
+	for (status, code) in [
+		(http::StatusCode::CONFLICT, a2a_sdk::ErrorCode::TASK_NOT_CANCELABLE),
+		(http::StatusCode::NOT_FOUND, a2a_sdk::ErrorCode::TASK_NOT_FOUND),
+	] {
+		let call = Call {
+			method: "tasks/cancel",
+			id: Some(json!(3)),
+		};
+		let mut resp = ::http::Response::builder()
+			.status(status)
+			.header(header::CONTENT_TYPE, "text/plain")
+			.body(Body::from(status.to_string()))
+			.unwrap();
+		apply_to_response(Some(&pol), RequestType::Call(call), None, None, &mut resp)
+			.await
+			.unwrap();
+		assert_eq!(resp.status(), status);
+		let body = response_json(resp).await;
+		assert_eq!(body["id"], 3);
+		assert_eq!(body["error"]["code"], code.0);
+	}
+}
+
+#[tokio::test]
+async fn test_invalid_push_notification_rejected() {
+	let pol: A2aPolicy = serde_json::from_value(json!({})).unwrap();
+	let call = json!({
+		"jsonrpc": "2.0",
+		"id": "abc",
+		"method": "tasks/pushNotification/set",
+		"params": {
+			"id": "task",
+			"pushNotificationConfig": { "url": "file:///etc/passwd" },
+		},
+	});
+	let mut req = call_request(serde_json::to_vec(&call).unwrap(), true);
+	let Ok(RequestType::Rejected(resp)) = apply_to_request(Some(&pol), &mut req).await else {
+		panic!("expected the call to be rejected");
+	};
+	assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+	let body = response_json(resp).await;
+	assert_eq!(body["id"], "abc");
+	assert_eq!(body["error"]["code"], INVALID_PARAMS);
+}
+
+#[tokio::test]
+async fn test_private_push_notification_policy() {
+	let call = json!({
+		"jsonrpc": "2.0",
+		"id": "abc",
+		"method": "tasks/pushNotification/set",
+		"params": {
+			"id": "task",
+			"pushNotificationConfig": { "url": "http://10.0.0.5/notify" },
+		},
+	});
+	let body = serde_json::to_vec(&call).unwrap();
+
+	let pol: A2aPolicy = serde_json::from_value(json!({})).unwrap();
+	let mut req = call_request(body.clone(), true);
+	let res = apply_to_request(Some(&pol), &mut req).await;
+	assert!(matches!(res, Ok(RequestType::Call(_))));
+
+	let pol: A2aPolicy =
+		serde_json::from_value(json!({ "denyPrivatePushNotificationUrls": true })).unwrap();
+	let mut req = call_request(body, true);
+	let Ok(RequestType::Rejected(resp)) = apply_to_request(Some(&pol), &mut req).await else {
+		panic!("expected the call to be rejected");
+	};
+	assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_invalid_task_transition_counted() {
+	let pol: A2aPolicy = serde_json::from_value(json!({ "validateTaskStates": true })).unwrap();
+	let metrics = Arc::new(Metrics::new(
+		&mut prometheus_client::registry::Registry::default(),
+	));
+	let event = |state: &str| {
+		let msg = json!({
+			"jsonrpc": "2.0",
+			"id": 1,
+			"result": { "id": "task-1", "status": { "state": state }, "final": false },
+		});
+		format!("data: {msg}\n\n")
+	};
+	let body = [event("working"), event("completed"), event("working")].concat();
+	let mut resp = ::http::Response::builder()
+		.header(header::CONTENT_TYPE, "text/event-stream")
+		.body(Body::from(body))
+		.unwrap();
+	let call = Call {
+		method: "tasks/sendSubscribe",
+		id: Some(json!(1)),
+	};
+	apply_to_response(
+		Some(&pol),
+		RequestType::Call(call),
+		None,
+		Some(metrics.clone()),
+		&mut resp,
+	)
+	.await
+	.unwrap();
+	to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+
+	let count = |from: TaskState, to: TaskState| {
+		metrics
+			.a2a_invalid_task_transitions
+			.get_or_create(&A2aTaskTransitionLabels {
+				from: from.into(),
+				to: to.into(),
+			})
+			.get()
+	};
+	assert_eq!(count(TaskState::Completed, TaskState::Working), 1);
+	assert_eq!(count(TaskState::Working, TaskState::Completed), 0);
+}
+
+#[tokio::test]
+async fn test_logged_response_passed_through() {
+	let pol: A2aPolicy =
+		serde_json::from_value(json!({ "logging": { "includeBodies": true } })).unwrap();
+	// Larger than what is logged, so only a prefix is
+	let result = "x".repeat(4 * 1024 * 1024);
+	let body = serde_json::to_vec(&json!({ "jsonrpc": "2.0", "id": 1, "result": result })).unwrap();
+	let mut resp = ::http::Response::builder()
+		.header(header::CONTENT_TYPE, "application/json")
+		.body(Body::from(body.clone()))
+		.unwrap();
+	let call = Call {
+		method: "message/send",
+		id: Some(json!(1)),
+	};
+	apply_to_response(Some(&pol), RequestType::Call(call), None, None, &mut resp)
+		.await
+		.unwrap();
+	let got = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+	assert_eq!(got.as_ref(), body.as_slice());
+}
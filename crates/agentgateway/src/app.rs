@@ -4,9 +4,12 @@ use std::thread;
 
 use agent_core::prelude::*;
 use agent_core::{drain, metrics, readiness, signal, trcng};
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
 use prometheus_client::registry::Registry;
 use serde_json::Value;
 use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 
 use crate::control::caclient;
 use crate::management::admin::ConfigDumpHandler;
@@ -14,15 +17,78 @@ use crate::telemetry::trc;
 use crate::telemetry::trc::Tracer;
 use crate::transport::hbone;
 use crate::types::agent::Policy;
-use crate::{Config, ProxyInputs, client, mcp, proxy, state_manager};
+use crate::{Config, ConfigSource, ProxyInputs, client, mcp, proxy, state_manager};
+
+#[cfg(test)]
+#[path = "app_tests.rs"]
+mod tests;
+
+/// Runs the proxy in process, for embedding it or for end to end tests. It runs like the binary,
+/// with the listeners and the admin, metrics and readiness servers:
+///
+/// ```ignore
+/// let proxy = Proxy::from_config(yaml)?.cancel_on(token.clone()).run().await?;
+/// // ... until the token is cancelled, or:
+/// proxy.shutdown().await?;
+/// ```
+///
+/// No crate features are needed besides a TLS provider, `tls-ring` by default.
+pub struct Proxy {
+	config: Config,
+	cancel: Option<CancellationToken>,
+}
+
+impl Proxy {
+	/// A proxy configured with the contents of a config file, in YAML or JSON. The proxy settings
+	/// are read from its `config` section, and the binds and backends from the rest, as from a local
+	/// config file. Unless `localXdsPath` is set, nothing is read from disk.
+	pub fn from_config(contents: &str) -> anyhow::Result<Self> {
+		let mut config = crate::config::parse_config(contents.to_string(), None)?;
+		if config.xds.local_config.is_none() {
+			config.xds.local_config = Some(ConfigSource::Static(Bytes::copy_from_slice(
+				contents.as_bytes(),
+			)));
+		}
+		Ok(Self::new(config))
+	}
+
+	pub(crate) fn new(config: Config) -> Self {
+		Proxy {
+			config,
+			cancel: None,
+		}
+	}
+
+	/// Shuts the proxy down gracefully once `token` is cancelled, whether or not the [Bound] handle is
+	/// still held.
+	pub fn cancel_on(mut self, token: CancellationToken) -> Self {
+		self.cancel = Some(token);
+		self
+	}
+
+	/// Starts the proxy. It runs until shut down, with [Bound::shutdown] or [Bound::wait_termination].
+	pub async fn run(self) -> anyhow::Result<Bound> {
+		// A child token, so shutting down through the handle does not cancel the caller's token
+		let cancel = self.cancel.map(|t| t.child_token()).unwrap_or_default();
+		start(Arc::new(self.config), cancel).await
+	}
+}
 
 pub async fn run(config: Arc<Config>) -> anyhow::Result<Bound> {
+	start(config, CancellationToken::new()).await
+}
+
+async fn start(config: Arc<Config>, cancel: CancellationToken) -> anyhow::Result<Bound> {
 	let data_plane_pool = new_data_plane_pool(config.num_worker_threads);
 
 	// TODO consolidate this
 	trcng::init_tracer(trcng::Config {
 		tracer: trcng::Tracer::Otlp {
 			endpoint: config.tracing.endpoint.clone(),
+			protocol: config.tracing.protocol,
+			headers: config.tracing.headers.clone(),
+			sampling_ratio: config.tracing.random_sampling,
+			service_name: Some(config.tracing.service_name.clone()),
 		},
 		tags: Default::default(),
 	});
@@ -76,8 +142,14 @@ pub async fn run(config: Arc<Config>) -> anyhow::Result<Bound> {
 	let client = client::Client::new(&config.dns, pool);
 
 	let (xds_tx, xds_rx) = tokio::sync::watch::channel(());
-	let state_mgr =
-		state_manager::StateManager::new(&config.xds, client.clone(), xds_metrics, xds_tx).await?;
+	let state_mgr = state_manager::StateManager::new(
+		&config.xds,
+		config.stdio_command_allowlist.clone(),
+		client.clone(),
+		xds_metrics,
+		xds_tx,
+	)
+	.await?;
 	let mut xds_rx_for_task = xds_rx.clone();
 	tokio::spawn(async move {
 		// When we get the initial XDS state, unblock readiness
@@ -99,8 +171,17 @@ pub async fn run(config: Arc<Config>) -> anyhow::Result<Bound> {
 	#[cfg(feature = "ui")]
 	admin_server.set_admin_handler(Arc::new(crate::ui::UiHandler::new(config.clone())));
 
-	let sub_registry = metrics::sub_registry(&mut registry);
 	let tracer = trc::Tracer::new(&config.tracing)?;
+	let meter_provider = trc::meter_provider(&config.tracing)?;
+	let mut mcp_metrics = crate::mcp::relay::metrics::Metrics::new(
+		&mut registry,
+		None, // TODO custom tags
+	);
+	if let Some(provider) = &meter_provider {
+		mcp_metrics = mcp_metrics.with_meter(&provider.meter("agentgateway"));
+	}
+	let sub_registry = metrics::sub_registry(&mut registry);
+	let inflight = Arc::new(proxy::inflight::InFlight::default());
 	let pi = ProxyInputs {
 		cfg: config.clone(),
 		stores: stores.clone(),
@@ -108,36 +189,40 @@ pub async fn run(config: Arc<Config>) -> anyhow::Result<Bound> {
 		metrics: Arc::new(crate::metrics::Metrics::new(sub_registry)),
 		upstream: client.clone(),
 		ca,
+		inflight: inflight.clone(),
 
 		mcp_state: mcp::sse::App::new(
+			&config,
 			stores.clone(),
-			Arc::new(crate::mcp::relay::metrics::Metrics::new(
-				&mut registry,
-				None, // TODO custom tags
-			)),
+			Arc::new(mcp_metrics),
 			client.clone(),
 			drain_rx.clone(),
+			inflight,
 		),
 	};
 
+	admin_server.set_target_status(pi.mcp_state.target_status());
 	let gw = proxy::Gateway::new(Arc::new(pi), drain_rx.clone());
 
 	// Run the agentgateway in the data plane worker pool.
 	let mut xds_rx_for_proxy = xds_rx.clone();
+	let (fatal_tx, fatal_rx) = tokio::sync::oneshot::channel();
 	data_plane_pool.send(DataPlaneTask {
 		block_shutdown: true,
 		fut: Box::pin(async move {
 			// Wait for XDS to be ready
 			let _ = xds_rx_for_proxy.changed().await;
-			// Now run
-			gw.run().in_current_span().await;
+			// Now run. Readiness is released once the initial binds are listening.
+			if let Err(e) = gw.run(proxy_task).in_current_span().await {
+				error!("gateway failed: {e:#}");
+				let _ = fatal_tx.send(e);
+			}
 			Ok(())
 		}),
 	})?;
 
-	drop(proxy_task);
-
 	// Run the admin server in the current tokio worker pool.
+	let admin_address = admin_server.address();
 	admin_server.spawn();
 
 	// Create and start the metrics server.
@@ -149,22 +234,44 @@ pub async fn run(config: Arc<Config>) -> anyhow::Result<Bound> {
 	// Run the metrics sever in the current tokio worker pool.
 	metrics_server.spawn();
 	Ok(Bound {
-		drain_tx,
-		shutdown,
-		tracer,
+		terminated: tokio::spawn(
+			Termination {
+				shutdown,
+				cancel: cancel.clone(),
+				drain_tx,
+				tracer,
+				meter_provider,
+				fatal: fatal_rx,
+			}
+			.wait(),
+		),
+		cancel,
+		admin_address,
+		metrics_address,
+		readiness_address,
 	})
 }
 
-pub struct Bound {
-	pub shutdown: signal::Shutdown,
+/// What shuts the proxy down. It runs on its own task, so the proxy drains on a signal, an admin
+/// shutdown, cancellation or a fatal error even if the [Bound] handle is dropped.
+struct Termination {
+	shutdown: signal::Shutdown,
+	cancel: CancellationToken,
 	drain_tx: drain::DrainTrigger,
 	tracer: Option<Tracer>,
+	meter_provider: Option<SdkMeterProvider>,
+	fatal: tokio::sync::oneshot::Receiver<anyhow::Error>,
 }
 
-impl Bound {
-	pub async fn wait_termination(self) -> anyhow::Result<()> {
-		// Wait for a signal to shutdown from explicit admin shutdown or signal
-		self.shutdown.wait().await;
+impl Termination {
+	async fn wait(self) -> anyhow::Result<()> {
+		// Wait for a signal to shutdown from explicit admin shutdown or signal, cancellation, or a
+		// fatal error
+		let fatal = tokio::select! {
+			_ = self.shutdown.wait() => None,
+			_ = self.cancel.cancelled() => None,
+			Ok(e) = self.fatal => Some(e),
+		};
 
 		if let Some(tracer) = self.tracer {
 			tracer.shutdown()
@@ -177,7 +284,51 @@ impl Bound {
 			.start_drain_and_wait(drain::DrainMode::Graceful)
 			.await;
 
-		Ok(())
+		// Only flush metrics once drained, so those recorded by the last requests are exported
+		if let Some(provider) = self.meter_provider
+			&& let Err(e) = provider.shutdown()
+		{
+			warn!("failed to flush metrics: {e}");
+		}
+
+		match fatal {
+			Some(e) => Err(e),
+			None => Ok(()),
+		}
+	}
+}
+
+pub struct Bound {
+	cancel: CancellationToken,
+	terminated: tokio::task::JoinHandle<anyhow::Result<()>>,
+	admin_address: Option<SocketAddr>,
+	metrics_address: SocketAddr,
+	readiness_address: SocketAddr,
+}
+
+impl Bound {
+	/// The address the admin server listens on, unless it listens on a Unix domain socket.
+	pub fn admin_address(&self) -> Option<SocketAddr> {
+		self.admin_address
+	}
+
+	pub fn metrics_address(&self) -> SocketAddr {
+		self.metrics_address
+	}
+
+	pub fn readiness_address(&self) -> SocketAddr {
+		self.readiness_address
+	}
+
+	/// Shuts the proxy down gracefully, draining connections, and waits until it is done.
+	pub async fn shutdown(self) -> anyhow::Result<()> {
+		self.cancel.cancel();
+		self.wait_termination().await
+	}
+
+	/// Waits until the proxy is shut down and drained.
+	pub async fn wait_termination(self) -> anyhow::Result<()> {
+		self.terminated.await?
 	}
 }
 
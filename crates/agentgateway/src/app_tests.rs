@@ -0,0 +1,99 @@
+use agent_core::{drain, signal};
+use tokio_util::sync::CancellationToken;
+
+use super::*;
+
+fn bound() -> (
+	Bound,
+	tokio::sync::oneshot::Sender<anyhow::Error>,
+	drain::DrainWatcher,
+) {
+	let (drain_tx, drain_rx) = drain::new();
+	let (fatal_tx, fatal) = tokio::sync::oneshot::channel();
+	let cancel = CancellationToken::new();
+	let bound = Bound {
+		terminated: tokio::spawn(
+			Termination {
+				shutdown: signal::Shutdown::new(),
+				cancel: cancel.clone(),
+				drain_tx,
+				tracer: None,
+				meter_provider: None,
+				fatal,
+			}
+			.wait(),
+		),
+		cancel,
+		admin_address: None,
+		metrics_address: "127.0.0.1:0".parse().unwrap(),
+		readiness_address: "127.0.0.1:0".parse().unwrap(),
+	};
+	(bound, fatal_tx, drain_rx)
+}
+
+#[tokio::test]
+async fn test_fatal_error_terminates() {
+	let (bound, fatal_tx, _) = bound();
+	fatal_tx
+		.send(anyhow::anyhow!("failed to bind 127.0.0.1:8080"))
+		.unwrap();
+	let err = bound.wait_termination().await.unwrap_err();
+	assert!(err.to_string().contains("127.0.0.1:8080"), "{err}");
+}
+
+#[tokio::test]
+async fn test_shutdown_without_fatal_error() {
+	let (bound, _fatal_tx, _) = bound();
+	bound.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_cancel_drains_without_handle() {
+	let (bound, _fatal_tx, drain_rx) = bound();
+	let cancel = bound.cancel.clone();
+	drop(bound);
+	cancel.cancel();
+	tokio::time::timeout(Duration::from_secs(5), drain_rx.wait_for_drain())
+		.await
+		.expect("cancellation should start the drain");
+}
+
+#[tokio::test]
+async fn test_metrics_flushed_after_drain() {
+	use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader};
+	let exporter = InMemoryMetricExporter::default();
+	let provider = SdkMeterProvider::builder()
+		.with_reader(PeriodicReader::builder(exporter.clone()).build())
+		.build();
+	let requests = provider.meter("test").u64_counter("requests").build();
+	let (drain_tx, drain_rx) = drain::new();
+	let (_fatal_tx, fatal) = tokio::sync::oneshot::channel();
+	let cancel = CancellationToken::new();
+	let terminated = tokio::spawn(
+		Termination {
+			shutdown: signal::Shutdown::new(),
+			cancel: cancel.clone(),
+			drain_tx,
+			tracer: None,
+			meter_provider: Some(provider),
+			fatal,
+		}
+		.wait(),
+	);
+	cancel.cancel();
+	// A request finishing while the proxy drains
+	let release = drain_rx.wait_for_drain().await;
+	requests.add(1, &[]);
+	drop(release);
+	terminated.await.unwrap().unwrap();
+
+	let exported = exporter.get_finished_metrics().unwrap();
+	assert!(
+		exported
+			.iter()
+			.flat_map(|m| m.scope_metrics())
+			.flat_map(|s| s.metrics())
+			.any(|m| m.name() == "requests"),
+		"metrics recorded while draining are exported"
+	);
+}
@@ -32,12 +32,14 @@ impl<T> CircularBuffer<T> {
 }
 
 impl<T: Copy> CircularBuffer<T> {
-	fn get_and_advance(&self) -> Option<T> {
+	/// Returns all items, starting from the next one in round-robin order, and advances.
+	fn get_all_and_advance(&self) -> Option<Vec<T>> {
 		if self.data.is_empty() {
 			return None;
 		}
-		let current = self.index.fetch_add(1, Ordering::Relaxed);
-		Some(self.data[current % self.data.len()])
+		let current = self.index.fetch_add(1, Ordering::Relaxed) % self.data.len();
+		let (head, tail) = self.data.split_at(current);
+		Some(tail.iter().chain(head).copied().collect())
 	}
 }
 
@@ -99,13 +101,13 @@ impl CacheEntry {
 		}
 	}
 
-	pub async fn next(&self) -> Option<IpAddr> {
+	pub async fn next(&self) -> Option<Vec<IpAddr>> {
 		// Mark as active
 		self.active.store(true, Ordering::Relaxed);
 		// Is there an entry right now? If so return it.
 		let notify = self.notify.notified();
 		if let Some(entry) = self.entries.load().as_ref() {
-			return entry.get_and_advance();
+			return entry.get_all_and_advance();
 		}
 		// Wait until a change happens
 		notify.await;
@@ -114,7 +116,7 @@ impl CacheEntry {
 			.entries
 			.load()
 			.as_ref()
-			.and_then(|cb| cb.get_and_advance())
+			.and_then(|cb| cb.get_all_and_advance())
 	}
 }
 
@@ -153,6 +155,13 @@ impl CachedResolver {
 	}
 
 	pub async fn resolve(&self, name: Strng) -> anyhow::Result<IpAddr> {
+		let ips = self.resolve_all(name).await?;
+		Ok(ips[0])
+	}
+
+	/// Returns all addresses for the name. The first address rotates between calls, and the rest follow
+	/// in order, so callers can fall back to them if the first is unreachable.
+	pub async fn resolve_all(&self, name: Strng) -> anyhow::Result<Vec<IpAddr>> {
 		// Check if we already have an entry
 		let entry = {
 			let mut cache = self.entries.lock().unwrap();
@@ -184,7 +193,7 @@ impl CachedResolver {
 			}
 		};
 
-		// Return next IP
+		// Return IPs, starting with the next one
 		entry.next().await.ok_or(anyhow!("no ip"))
 	}
 }
@@ -61,6 +61,24 @@ async fn test_basic_resolution() {
 	assert_eq!(ip1, ip3);
 }
 
+#[tokio::test]
+async fn test_resolve_all() {
+	let mock = Arc::new(Mock::new());
+	mock.add_response("example.com", vec![IP1, IP2, IP3], 60);
+
+	let resolver = CachedResolver {
+		dns: Arc::new(Resolver::Mock(mock)),
+		entries: Arc::new(Mutex::new(HashMap::new())),
+	};
+
+	// Every address is returned, rotating which one comes first
+	let all = resolver.resolve_all("example.com".into()).await.unwrap();
+	assert_eq!(all, vec![IP1, IP2, IP3]);
+	let all = resolver.resolve_all("example.com".into()).await.unwrap();
+	assert_eq!(all, vec![IP2, IP3, IP1]);
+	assert_eq!(resolver.resolve("example.com".into()).await.unwrap(), IP3);
+}
+
 #[tokio::test(start_paused = true)]
 async fn test_ip_change() {
 	agent_core::telemetry::testing::setup_test_logging();
@@ -5,6 +5,7 @@ use std::fmt::Display;
 use std::task;
 
 use crate::http::backendtls::BackendTLS;
+use crate::http::outboundproxy::{OutboundProxy, ProxyEndpoint};
 use crate::proxy::ProxyError;
 use crate::transport::hbone::WorkloadKey;
 use crate::transport::stream::{LoggingMode, Socket};
@@ -19,14 +20,26 @@ use hyper_util::client::legacy::connect::HttpConnector;
 use hyper_util_fork::rt::TokioIo;
 use rand::prelude::IteratorRandom;
 use rustls_pki_types::{DnsName, ServerName};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
 use tracing::event;
 
 #[derive(Clone)]
 pub struct Client {
 	resolver: Arc<dns::CachedResolver>,
+	connector: Connector,
 	client: hyper_util_fork::client::legacy::Client<Connector, http::Body, PoolKey>,
 }
 
+/// Settings for the idle connections a [Client] keeps open to reuse for later requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolConfig {
+	/// The most idle connections kept per destination. Unlimited if not set.
+	pub max_idle_per_host: Option<usize>,
+	/// How long a connection may stay idle before it is closed. Defaults to 90s.
+	pub idle_timeout: Option<Duration>,
+}
+
 impl Debug for Client {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		f.debug_struct("Client").finish()
@@ -37,6 +50,8 @@ pub struct Call {
 	pub req: http::Request,
 	pub target: Target,
 	pub transport: Transport,
+	/// A forward proxy to connect through. It is not used for HBONE, which is already tunneled.
+	pub proxy: Option<OutboundProxy>,
 }
 
 #[derive(Default, Debug, Clone, Hash, PartialEq, Eq)]
@@ -66,8 +81,17 @@ impl From<Option<BackendTLS>> for Transport {
 	}
 }
 
+// The second field is the address to connect to; the fifth holds any other resolved addresses to try, in
+// order, if it cannot be reached. When a proxy is set, these are the proxy's addresses.
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
-struct PoolKey(Target, SocketAddr, Transport, ::http::Version);
+struct PoolKey(
+	Target,
+	SocketAddr,
+	Transport,
+	::http::Version,
+	Vec<SocketAddr>,
+	Option<ProxyEndpoint>,
+);
 
 impl Transport {
 	pub fn scheme(&self) -> Scheme {
@@ -106,40 +130,81 @@ impl tower::Service<::http::Extensions> for Connector {
 		let mut it = self.clone();
 
 		Box::pin(async move {
-			let PoolKey(target, ep, transport, ver) =
+			let PoolKey(target, ep, transport, ver, fallbacks, proxy) =
 				dst.remove::<PoolKey>().expect("pool key must be set");
 
 			match transport {
 				Transport::Plaintext => {
-					let mut res = Socket::dial(ep)
-						.await
-						.context("http call failed")
-						.map_err(crate::http::Error::new)?;
+					let res = match &proxy {
+						// Plain HTTP requests are sent to an HTTP proxy as they are, in absolute-form
+						Some(proxy) if proxy.forwards_http() => {
+							dial_in_order(ep, &fallbacks, |ep| async move {
+								let mut socket = Socket::dial(ep)
+									.await
+									.with_context(|| format!("failed to connect to proxy {proxy}"))?;
+								socket.with_forward_proxy();
+								anyhow::Ok(socket)
+							})
+							.await
+						},
+						Some(proxy) => dial_proxy(proxy, ep, &fallbacks, &target)
+							.await
+							.and_then(Socket::from_tcp),
+						None => {
+							dial_in_order(ep, &fallbacks, |ep| async move {
+								Socket::dial(ep).await.context("http call failed")
+							})
+							.await
+						},
+					};
+					let mut res = res.map_err(crate::http::Error::new)?;
 					res.with_logging(LoggingMode::Upstream);
 					Ok(TokioIo::new(res))
 				},
 				Transport::Tls(tls) => {
-					let server_name = match target {
-						Target::Address(_) => ServerName::IpAddress(ep.ip().into()),
+					let server_name = match &target {
+						Target::Address(addr) => ServerName::IpAddress(addr.ip().into()),
 						Target::Hostname(host, _) => ServerName::DnsName(
 							DnsName::try_from(host.to_string()).expect("TODO: hostname conversion failed"),
 						),
 					};
+					if let Some(proxy) = &proxy {
+						let tcp = dial_proxy(proxy, ep, &fallbacks, &target)
+							.await
+							.map_err(crate::http::Error::new)?;
+						let (_, metrics, tcp) = Socket::from_tcp(tcp)
+							.map_err(crate::http::Error::new)?
+							.into_parts();
+						let tls = TlsConnector::from(tls.config.clone())
+							.connect(server_name, Box::new(tcp))
+							.await
+							.map_err(crate::http::Error::new)?;
+						let mut res = Socket::from_tls(stream::Extension::new(), metrics, tls.into())
+							.map_err(crate::http::Error::new)?;
+						res.with_logging(LoggingMode::Upstream);
+						return Ok(TokioIo::new(res));
+					}
 					// TODO: replace with Socket::dial
-					let mut https = self::hyperrustls::HttpsConnector {
+					let https = self::hyperrustls::HttpsConnector {
 						http: it.http,
 						tls_config: tls.config.clone(),
 						server_name,
 					};
 
-					let uri = Uri::builder()
-						.scheme(Scheme::HTTPS)
-						.authority(ep.to_string())
-						.path_and_query("/")
-						.build()
-						.expect("todo");
-
-					let mut res = https.call(uri).await.map_err(crate::http::Error::new)?;
+					let mut res = dial_in_order(ep, &fallbacks, |ep| {
+						let mut https = https.clone();
+						async move {
+							let uri = Uri::builder()
+								.scheme(Scheme::HTTPS)
+								.authority(ep.to_string())
+								.path_and_query("/")
+								.build()
+								.expect("todo");
+							https.call(uri).await
+						}
+					})
+					.await
+					.map_err(crate::http::Error::new)?;
 					res.with_logging(LoggingMode::Upstream);
 					Ok(TokioIo::new(res))
 				},
@@ -188,6 +253,46 @@ impl tower::Service<::http::Extensions> for Connector {
 	}
 }
 
+/// Connects to the proxy at `ep` (or one of `fallbacks`), and opens a tunnel through it to `target`.
+async fn dial_proxy(
+	proxy: &ProxyEndpoint,
+	ep: SocketAddr,
+	fallbacks: &[SocketAddr],
+	target: &Target,
+) -> anyhow::Result<TcpStream> {
+	dial_in_order(ep, fallbacks, |ep| async move {
+		let mut tcp = TcpStream::connect(ep)
+			.await
+			.with_context(|| format!("failed to connect to proxy {proxy}"))?;
+		proxy.handshake(&mut tcp, target).await?;
+		anyhow::Ok(tcp)
+	})
+	.await
+}
+
+/// Connects to `ep`, falling back to each of `fallbacks` in order if that fails. Returns the last error if
+/// none can be reached.
+async fn dial_in_order<F, Fut, T, E>(
+	ep: SocketAddr,
+	fallbacks: &[SocketAddr],
+	dial: F,
+) -> Result<T, E>
+where
+	F: Fn(SocketAddr) -> Fut,
+	Fut: Future<Output = Result<T, E>>,
+	E: Display,
+{
+	let mut res = dial(ep).await;
+	for next in fallbacks {
+		let Err(e) = &res else {
+			break;
+		};
+		debug!(endpoint=%ep, fallback=%next, "connection failed, trying next address: {e}");
+		res = dial(*next).await;
+	}
+	res
+}
+
 #[derive(serde::Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
@@ -203,17 +308,41 @@ impl Client {
 		let resolver = dns::CachedResolver::new(cfg.resolver_cfg.clone(), cfg.resolver_opts.clone());
 		let mut base = HttpConnector::new();
 		base.enforce_http(false);
-		let client =
-			::hyper_util_fork::client::legacy::Client::builder(::hyper_util::rt::TokioExecutor::new())
-				.timer(hyper_util::rt::tokio::TokioTimer::new())
-				.build_with_pool_key(Connector {
-					http: base,
-					hbone_pool,
-				});
+		let connector = Connector {
+			http: base,
+			hbone_pool,
+		};
 		Client {
 			resolver: Arc::new(resolver),
-			client,
+			client: Self::builder().build_with_pool_key(connector.clone()),
+			connector,
+		}
+	}
+
+	/// A client with its own pool of idle connections, configured by `pool`. It shares the DNS cache
+	/// of this client.
+	pub fn with_pool(&self, pool: &PoolConfig) -> Client {
+		let mut builder = Self::builder();
+		// Without a pool timer, idle connections are only closed when they are next checked out
+		builder.pool_timer(hyper_util::rt::tokio::TokioTimer::new());
+		if let Some(max) = pool.max_idle_per_host {
+			builder.pool_max_idle_per_host(max);
 		}
+		if let Some(timeout) = pool.idle_timeout {
+			builder.pool_idle_timeout(timeout);
+		}
+		Client {
+			resolver: self.resolver.clone(),
+			client: builder.build_with_pool_key(self.connector.clone()),
+			connector: self.connector.clone(),
+		}
+	}
+
+	fn builder() -> ::hyper_util_fork::client::legacy::Builder {
+		let mut builder =
+			::hyper_util_fork::client::legacy::Client::builder(::hyper_util::rt::TokioExecutor::new());
+		builder.timer(hyper_util::rt::tokio::TokioTimer::new());
+		builder
 	}
 
 	pub async fn simple_call(&self, req: http::Request) -> Result<http::Response, ProxyError> {
@@ -242,6 +371,7 @@ impl Client {
 				req,
 				target,
 				transport,
+				proxy: None,
 			})
 			.await
 	}
@@ -252,30 +382,53 @@ impl Client {
 			mut req,
 			target,
 			transport,
+			proxy,
 		} = call;
-		let dest = match &target {
-			Target::Address(addr) => *addr,
+		let proxy = match &transport {
+			Transport::Hbone(..) => None,
+			_ => proxy
+				.as_ref()
+				.and_then(|p| p.endpoint_for(&target, matches!(transport, Transport::Tls(_))))
+				.cloned(),
+		};
+		// With a proxy, it is the proxy that is dialed; the target is reached through the tunnel.
+		let (dest, fallbacks) = match proxy.as_ref().map(|p| &p.target).unwrap_or(&target) {
+			Target::Address(addr) => (*addr, vec![]),
 			Target::Hostname(hostname, port) => {
-				// TODO we need caching here!
-				let ip = self
+				let ips = self
 					.resolver
-					.resolve(hostname.clone())
+					.resolve_all(hostname.clone())
 					.await
 					.map_err(|_| ProxyError::DnsResolution)?;
-				SocketAddr::from((ip, *port))
+				let mut addrs = ips.into_iter().map(|ip| SocketAddr::from((ip, *port)));
+				let dest = addrs.next().ok_or(ProxyError::DnsResolution)?;
+				(dest, addrs.collect())
 			},
 		};
+		// Requests forwarded to a proxy name their target in the request line, and carry the proxy
+		// credentials themselves.
+		let forwarded = matches!(transport, Transport::Plaintext)
+			&& proxy.as_ref().is_some_and(|p| p.forwards_http());
 		http::modify_req_uri(&mut req, |uri| {
 			uri.scheme = Some(transport.scheme());
+			if forwarded && uri.authority.is_none() {
+				uri.authority = Some(target.to_string().parse()?);
+			}
 			Ok(())
 		})
 		.map_err(ProxyError::Processing)?;
+		if forwarded && let Some(auth) = proxy.as_ref().and_then(|p| p.authorization()) {
+			let auth =
+				::http::HeaderValue::try_from(auth).map_err(|e| ProxyError::Processing(e.into()))?;
+			req.headers_mut().insert(::http::header::PROXY_AUTHORIZATION, auth);
+		}
 		let version = req.version();
 		let transport_name = transport.name();
+		let proxy_name = proxy.as_ref().map(|p| p.to_string());
 		let target_name = target.to_string();
 		req
 			.extensions_mut()
-			.insert(PoolKey(target, dest, transport, version));
+			.insert(PoolKey(target, dest, transport, version, fallbacks, proxy));
 		trace!(?req, "sending request");
 		let method = req.method().clone();
 		let uri = req.uri().clone();
@@ -291,6 +444,7 @@ impl Client {
 			target = %target_name,
 			endpoint = %dest,
 			transport = %transport_name,
+			proxy = proxy_name,
 
 			http.method = %method,
 			http.host = host.as_ref().map(display),
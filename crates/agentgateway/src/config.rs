@@ -5,13 +5,18 @@ use std::time::Duration;
 use std::{cmp, env};
 
 use agent_core::prelude::*;
+use agent_core::telemetry::LogFormat;
+use agent_core::trcng;
 use anyhow::anyhow;
 use hickory_resolver::config::ResolveHosts;
 
 use crate::control::caclient;
 use crate::telemetry::trc;
 use crate::types::discovery::Identity;
-use crate::{Address, Config, ConfigSource, NestedRawConfig, RawConfig, XDSConfig, client, serdes};
+use crate::{
+	Address, AdminAddress, Config, ConfigSource, NestedRawConfig, RawConfig, XDSConfig, client,
+	serdes,
+};
 
 pub fn parse_config(contents: String, filename: Option<PathBuf>) -> anyhow::Result<Config> {
 	let nested: NestedRawConfig = serdes::yamlviajson::from_str(&contents)?;
@@ -143,13 +148,81 @@ pub fn parse_config(contents: String, filename: Option<PathBuf>) -> anyhow::Resu
 		.or(raw.connection_min_termination_deadline)
 		.unwrap_or_default();
 	let termination_max_deadline =
-		parse_duration("CONNECTION_TERMINATION_DEADLINE")?.or(raw.connection_min_termination_deadline);
-	let otlp = empty_to_none(parse("OTLP_ENDPOINT")?).or(raw.tracing.map(|t| t.otlp_endpoint));
+		parse_duration("CONNECTION_TERMINATION_DEADLINE")?.or(raw.connection_termination_deadline);
+	let raw_tracing = raw.tracing.unwrap_or_default();
+	let otlp = empty_to_none(parse("OTLP_ENDPOINT")?).or(raw_tracing.otlp_endpoint);
+	let random_sampling = raw_tracing.random_sampling;
+	if random_sampling.is_some_and(|r| !(0.0..=1.0).contains(&r)) {
+		anyhow::bail!("tracing.randomSampling must be between 0 and 1");
+	}
+	// A bad log format should not stop the process, so it is reported and ignored
+	let log_format = parse::<LogFormat>("LOG_FORMAT")
+		.unwrap_or_else(|e| {
+			warn!("{e}, ignoring it");
+			None
+		})
+		.or(raw.logging.and_then(|l| l.format))
+		.unwrap_or_default();
+	let mcp_sse_buffer_size = parse("MCP_SSE_BUFFER_SIZE")?
+		.or(raw.mcp_sse_buffer_size)
+		.unwrap_or(64);
+	if mcp_sse_buffer_size == 0 {
+		anyhow::bail!("mcpSseBufferSize must be greater than 0");
+	}
+	let mcp_connection_idle_timeout =
+		parse_duration("MCP_CONNECTION_IDLE_TIMEOUT")?.or(raw.mcp_connection_idle_timeout);
+	// Zero disables the keep-alive
+	let mcp_sse_keep_alive = Some(
+		parse_duration("MCP_SSE_KEEP_ALIVE")?
+			.or(raw.mcp_sse_keep_alive)
+			.unwrap_or(Duration::from_secs(15)),
+	)
+	.filter(|d| !d.is_zero());
+	let stdio_command_allowlist = parse::<String>("STDIO_COMMAND_ALLOWLIST")?
+		.map(|s| {
+			s.split(',')
+				.map(str::trim)
+				.filter(|c| !c.is_empty())
+				.map(String::from)
+				.collect()
+		})
+		.or(raw.stdio_command_allowlist)
+		.unwrap_or_default();
+	let fatal_bind_errors = parse("FATAL_BIND_ERRORS")?
+		.or(raw.fatal_bind_errors)
+		.unwrap_or(false);
+	let admin_socket_mode = parse::<String>("ADMIN_SOCKET_MODE")?
+		.or(raw.admin_socket_mode)
+		.map(|m| {
+			u32::from_str_radix(&m, 8).map_err(|e| anyhow::anyhow!("invalid adminSocketMode {m}: {e}"))
+		})
+		.transpose()?
+		.unwrap_or(0o600);
+	let admin_addr = match parse::<String>("ADMIN_ADDR")?.or(raw.admin_addr) {
+		Some(addr) => AdminAddress::new(ipv6_localhost_enabled, &addr, admin_socket_mode)?,
+		None => AdminAddress::Tcp(Address::Localhost(ipv6_localhost_enabled, 15000)),
+	};
+	let admin_auth = raw.admin_auth;
+	if let Some(auth) = &admin_auth {
+		auth.validate()?;
+	} else if !admin_addr.is_local() {
+		anyhow::bail!(
+			"adminAddr {admin_addr} is reachable from other hosts; adminAuth must be configured to expose the admin API"
+		);
+	}
+	let stats_addr = match parse::<String>("STATS_ADDR")?.or(raw.stats_addr) {
+		Some(addr) => Address::new(ipv6_localhost_enabled, &addr)?,
+		None => Address::SocketAddr(SocketAddr::new(bind_wildcard, 15020)),
+	};
+	let readiness_addr = match parse::<String>("READINESS_ADDR")?.or(raw.readiness_addr) {
+		Some(addr) => Address::new(ipv6_localhost_enabled, &addr)?,
+		None => Address::SocketAddr(SocketAddr::new(bind_wildcard, 15021)),
+	};
 	Ok(crate::Config {
 		network: network.into(),
-		admin_addr: Address::Localhost(ipv6_localhost_enabled, 15000),
-		stats_addr: Address::SocketAddr(SocketAddr::new(bind_wildcard, 15020)),
-		readiness_addr: Address::SocketAddr(SocketAddr::new(bind_wildcard, 15021)),
+		admin_addr,
+		stats_addr,
+		readiness_addr,
 		self_addr,
 		xds,
 		ca,
@@ -175,7 +248,23 @@ pub fn parse_config(contents: String, filename: Option<PathBuf>) -> anyhow::Resu
 				None => Duration::from_secs(5),
 			},
 		},
-		tracing: trc::Config { endpoint: otlp },
+		tracing: trc::Config {
+			endpoint: otlp,
+			protocol: raw_tracing.otlp_protocol.unwrap_or_default(),
+			headers: raw_tracing.headers,
+			random_sampling,
+			service_name: raw_tracing
+				.service_name
+				.unwrap_or_else(|| trcng::DEFAULT_SERVICE_NAME.to_string()),
+			metrics: raw_tracing.metrics,
+		},
+		log_format,
+		mcp_sse_buffer_size,
+		mcp_connection_idle_timeout,
+		mcp_sse_keep_alive,
+		stdio_command_allowlist,
+		fatal_bind_errors,
+		admin_auth,
 		dns: client::Config {
 			// TODO: read from file
 			resolver_cfg,
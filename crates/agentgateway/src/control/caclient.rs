@@ -160,9 +160,7 @@ impl WorkloadCertificate {
 		cc.alpn_protocols = vec![b"istio".into()];
 		cc.resumption = Resumption::disabled();
 		// cc.enable_sni = false;
-		Ok(BackendTLS {
-			config: Arc::new(cc),
-		})
+		Ok(BackendTLS::new(cc))
 	}
 	pub fn hbone_mtls(&self, identity: Vec<Identity>) -> Result<BackendTLS, Error> {
 		// TODO: this is (way) too expensive to build per request
@@ -180,9 +178,7 @@ impl WorkloadCertificate {
 		cc.alpn_protocols = vec![b"h2".into()];
 		cc.resumption = Resumption::disabled();
 		cc.enable_sni = false;
-		Ok(BackendTLS {
-			config: Arc::new(cc),
-		})
+		Ok(BackendTLS::new(cc))
 	}
 	pub fn hbone_termination(&self) -> Result<ServerConfig, Error> {
 		let Identity::Spiffe { trust_domain, .. } = &self.identity;
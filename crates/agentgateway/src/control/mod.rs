@@ -49,9 +49,7 @@ impl RootCert {
 			.with_root_certificates(roots)
 			.with_no_client_auth();
 		ccb.alpn_protocols = vec![b"h2".to_vec()];
-		Ok(BackendTLS {
-			config: Arc::new(ccb),
-		})
+		Ok(BackendTLS::new(ccb))
 	}
 }
 
@@ -162,6 +160,7 @@ impl tower::Service<::http::Request<tonic::body::Body>> for GrpcChannel {
 						req,
 						target,
 						transport,
+						proxy: None,
 					})
 					.await?,
 			)
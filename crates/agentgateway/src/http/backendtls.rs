@@ -1,6 +1,6 @@
 use crate::transport;
 use crate::transport::tls;
-use crate::types::agent::{parse_cert, parse_key};
+use crate::types::agent::{HttpVersionPreference, parse_cert, parse_key};
 use once_cell::sync::Lazy;
 use rustls::ClientConfig;
 use serde::Serializer;
@@ -26,6 +26,10 @@ pub static INSECURE_TRUST: Lazy<BackendTLS> = Lazy::new(|| {
 #[derive(Debug, Clone)]
 pub struct BackendTLS {
 	pub config: Arc<ClientConfig>,
+	/// Copies of `config` offering only `http/1.1` or only `h2` during ALPN. They are built along
+	/// with it, so connections made with either are pooled together like those made with `config`.
+	http1: Arc<ClientConfig>,
+	http2: Arc<ClientConfig>,
 }
 
 impl std::hash::Hash for BackendTLS {
@@ -110,11 +114,37 @@ impl LocalBackendTLS {
 			cc.dangerous()
 				.set_certificate_verifier(Arc::new(tls::insecure::NoVerifier));
 		}
-		// TODO: support configuring
 		cc.alpn_protocols = vec![b"h2".into(), b"http/1.1".into()];
-		// cc.alpn_protocols = vec![b"http/1.1".into()];
-		Ok(BackendTLS {
-			config: Arc::new(cc),
-		})
+		Ok(BackendTLS::new(cc))
+	}
+}
+
+impl BackendTLS {
+	pub fn new(config: ClientConfig) -> BackendTLS {
+		let only = |protocol: &[u8]| {
+			let mut cc = config.clone();
+			cc.alpn_protocols = vec![protocol.to_vec()];
+			Arc::new(cc)
+		};
+		BackendTLS {
+			http1: only(b"http/1.1"),
+			http2: only(b"h2"),
+			config: Arc::new(config),
+		}
+	}
+
+	/// Returns this configuration as used for `version`, offering only that version during ALPN
+	/// when it is pinned.
+	pub fn for_http_version(&self, version: HttpVersionPreference) -> BackendTLS {
+		let config = match version {
+			HttpVersionPreference::Auto => return self.clone(),
+			HttpVersionPreference::Http1 => &self.http1,
+			HttpVersionPreference::Http2 => &self.http2,
+		};
+		BackendTLS {
+			config: config.clone(),
+			http1: self.http1.clone(),
+			http2: self.http2.clone(),
+		}
 	}
 }
@@ -9,6 +9,10 @@ use crate::http::{Request, Response, filters};
 use crate::types::agent::{HostRedirect, PathRedirect};
 use crate::*;
 
+#[cfg(test)]
+#[path = "cors_tests.rs"]
+mod tests;
+
 #[derive(Default, Debug, Clone)]
 enum WildcardOrList<T> {
 	#[default]
@@ -87,7 +91,7 @@ pub struct Cors {
 	#[serde(skip_serializing_if = "WildcardOrList::is_none")]
 	allow_methods: WildcardOrList<::http::Method>,
 	#[serde(skip_serializing_if = "WildcardOrList::is_none")]
-	allow_origins: WildcardOrList<Strng>,
+	allow_origins: WildcardOrList<OriginMatch>,
 	#[serde(skip_serializing_if = "WildcardOrList::is_none")]
 	expose_headers: WildcardOrList<http::HeaderName>,
 	#[serde(serialize_with = "ser_string_or_bytes_option")]
@@ -113,6 +117,8 @@ struct CorsSerde {
 	allow_headers: Vec<String>,
 	#[serde(default)]
 	allow_methods: Vec<String>,
+	/// Origins allowed to make cross-origin requests. Each entry is either an exact origin, or
+	/// contains a single `*` matching any non-empty sequence, such as `https://*.example.com`.
 	#[serde(default)]
 	allow_origins: Vec<String>,
 	#[serde(default)]
@@ -125,6 +131,11 @@ struct CorsSerde {
 impl TryFrom<CorsSerde> for Cors {
 	type Error = anyhow::Error;
 	fn try_from(value: CorsSerde) -> Result<Self, Self::Error> {
+		// Browsers refuse a wildcard response with credentials anyway; reflecting every origin instead
+		// would allow any site to make credentialed requests, so refuse the combination outright.
+		if value.allow_credentials && value.allow_origins.iter().any(|o| o == "*") {
+			anyhow::bail!("allowCredentials cannot be used with a wildcard allowOrigins");
+		}
 		Ok(Cors {
 			allow_credentials: value.allow_credentials,
 			allow_headers: WildcardOrList::try_from(value.allow_headers)?,
@@ -153,9 +164,8 @@ impl Cors {
 			WildcardOrList::None => false,
 			WildcardOrList::Wildcard => true,
 			WildcardOrList::List(origins) => {
-				// TODO: allow wildcards
 				let os = origin.as_bytes();
-				origins.iter().any(|want| want.as_bytes() == os)
+				origins.iter().any(|want| want.matches(os))
 			},
 		};
 		if !allowed {
@@ -167,7 +177,11 @@ impl Cors {
 			// Handle preflight request
 			let mut rb = ::http::Response::builder()
 				.status(StatusCode::OK)
-				.header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+				.header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin)
+				.header(header::VARY, HEADER_VALUE_ORIGIN);
+			if self.allow_credentials {
+				rb = rb.header(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HEADER_VALUE_TRUE);
+			}
 			if let Some(h) = self.allow_methods.to_header_value() {
 				rb = rb.header(header::ACCESS_CONTROL_ALLOW_METHODS, h);
 			}
@@ -184,8 +198,10 @@ impl Cors {
 			});
 		}
 
-		let mut response_headers = http::HeaderMap::with_capacity(3);
+		let mut response_headers = http::HeaderMap::with_capacity(4);
 		response_headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+		// The allowed origin is reflected, so caches must key on it.
+		response_headers.insert(header::VARY, HEADER_VALUE_ORIGIN);
 		if self.allow_credentials {
 			response_headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HEADER_VALUE_TRUE);
 		}
@@ -201,7 +217,54 @@ impl Cors {
 	}
 }
 
+/// An allowed origin, optionally containing a single `*` wildcard.
+#[derive(Debug, Clone)]
+enum OriginMatch {
+	Exact(Strng),
+	Wildcard { prefix: Strng, suffix: Strng },
+}
+
+impl OriginMatch {
+	fn matches(&self, origin: &[u8]) -> bool {
+		match self {
+			OriginMatch::Exact(want) => want.as_bytes() == origin,
+			OriginMatch::Wildcard { prefix, suffix } => {
+				origin.len() > prefix.len() + suffix.len()
+					&& origin.starts_with(prefix.as_bytes())
+					&& origin.ends_with(suffix.as_bytes())
+			},
+		}
+	}
+}
+
+impl FromStr for OriginMatch {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.split_once('*') {
+			None => Ok(OriginMatch::Exact(strng::new(s))),
+			Some((_, suffix)) if suffix.contains('*') => {
+				anyhow::bail!("origin {s} may contain at most one wildcard")
+			},
+			Some((prefix, suffix)) => Ok(OriginMatch::Wildcard {
+				prefix: strng::new(prefix),
+				suffix: strng::new(suffix),
+			}),
+		}
+	}
+}
+
+impl Display for OriginMatch {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			OriginMatch::Exact(o) => write!(f, "{o}"),
+			OriginMatch::Wildcard { prefix, suffix } => write!(f, "{prefix}*{suffix}"),
+		}
+	}
+}
+
 const HEADER_VALUE_TRUE: http::HeaderValue = HeaderValue::from_static("true");
+const HEADER_VALUE_ORIGIN: http::HeaderValue = HeaderValue::from_static("origin");
 
 #[derive(Debug, Default)]
 pub struct CorsResponse {
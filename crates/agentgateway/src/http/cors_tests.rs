@@ -0,0 +1,135 @@
+use serde_json::json;
+
+use super::*;
+use crate::http::tests_common::*;
+
+fn cors(v: serde_json::Value) -> Cors {
+	serde_json::from_value(v).unwrap()
+}
+
+fn get(origin: &str) -> Request {
+	request("http://example.com/mcp", Method::GET, &[("origin", origin)])
+}
+
+fn preflight(origin: &str) -> Request {
+	request(
+		"http://example.com/mcp",
+		Method::OPTIONS,
+		&[
+			("origin", origin),
+			("access-control-request-method", "POST"),
+		],
+	)
+}
+
+#[test]
+fn test_disallowed_origin() {
+	let c = cors(json!({
+		"allowOrigins": ["https://app.example.com"],
+		"allowMethods": ["POST"],
+	}));
+	let res = c.apply(&mut get("https://evil.example.com")).unwrap();
+	assert!(res.direct_response.is_none());
+	assert!(res.response_headers.is_none());
+
+	// Disallowed preflights are forwarded without any CORS headers
+	let res = c.apply(&mut preflight("https://evil.example.com")).unwrap();
+	assert!(res.direct_response.is_none());
+	assert!(res.response_headers.is_none());
+}
+
+#[test]
+fn test_allowed_origin() {
+	let c = cors(json!({
+		"allowOrigins": ["https://app.example.com"],
+		"allowMethods": ["POST"],
+		"allowCredentials": true,
+		"maxAge": "10m",
+	}));
+	let res = c.apply(&mut get("https://app.example.com")).unwrap();
+	let h = res.response_headers.unwrap();
+	assert_eq!(
+		h.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+		"https://app.example.com"
+	);
+	assert_eq!(
+		h.get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(),
+		"true"
+	);
+	assert_eq!(h.get(header::VARY).unwrap(), "origin");
+
+	let res = c.apply(&mut preflight("https://app.example.com")).unwrap();
+	let resp = res.direct_response.unwrap();
+	let h = resp.headers();
+	assert_eq!(
+		h.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+		"https://app.example.com"
+	);
+	assert_eq!(h.get(header::ACCESS_CONTROL_ALLOW_METHODS).unwrap(), "POST");
+	assert_eq!(
+		h.get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(),
+		"true"
+	);
+	assert_eq!(h.get(header::ACCESS_CONTROL_MAX_AGE).unwrap(), "600");
+}
+
+#[test]
+fn test_vary_appended_to_upstream() {
+	let c = cors(json!({
+		"allowOrigins": ["https://app.example.com"],
+	}));
+	let res = c.apply(&mut get("https://app.example.com")).unwrap();
+	let mut upstream = http::HeaderMap::new();
+	upstream.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+	crate::http::merge_in_headers(res.response_headers.clone(), &mut upstream);
+	let vary = upstream.get_all(header::VARY).iter().collect::<Vec<_>>();
+	assert_eq!(vary, vec!["accept-encoding", "origin"]);
+
+	// Not repeated if the upstream already varies on the origin
+	let mut upstream = http::HeaderMap::new();
+	upstream.insert(header::VARY, HeaderValue::from_static("Accept-Encoding, Origin"));
+	crate::http::merge_in_headers(res.response_headers, &mut upstream);
+	let vary = upstream.get_all(header::VARY).iter().collect::<Vec<_>>();
+	assert_eq!(vary, vec!["Accept-Encoding, Origin"]);
+}
+
+#[test]
+fn test_wildcard_origin_pattern() {
+	let c = cors(json!({
+		"allowOrigins": ["https://*.example.com"],
+	}));
+	assert!(
+		c.apply(&mut get("https://app.example.com"))
+			.unwrap()
+			.response_headers
+			.is_some()
+	);
+	for origin in [
+		"https://.example.com",
+		"https://example.com",
+		"http://app.example.com",
+		"https://app.example.com.evil",
+	] {
+		assert!(
+			c.apply(&mut get(origin))
+				.unwrap()
+				.response_headers
+				.is_none(),
+			"{origin}"
+		);
+	}
+}
+
+#[test]
+fn test_invalid_config() {
+	// A wildcard origin must not be combined with credentials
+	let res = serde_json::from_value::<Cors>(json!({
+		"allowOrigins": ["*"],
+		"allowCredentials": true,
+	}));
+	assert!(res.is_err());
+	let res = serde_json::from_value::<Cors>(json!({
+		"allowOrigins": ["https://*.*.example.com"],
+	}));
+	assert!(res.is_err());
+}
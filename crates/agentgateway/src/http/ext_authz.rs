@@ -63,6 +63,8 @@ impl ExtAuthz {
 			// transport: Transport::Tls(agent::INSECURE_TRUST.clone()),
 			transport: Transport::Plaintext,
 			client,
+			backend_auth: None,
+			proxy: None,
 		};
 		let mut client = AuthorizationClient::new(chan);
 		let tcp_info = req.extensions().get::<TCPConnectionInfo>().unwrap();
@@ -112,6 +112,8 @@ impl ExtProc {
 			transport: Transport::Tls(http::backendtls::INSECURE_TRUST.clone()),
 			// transport: Transport::Plaintext,
 			client,
+			backend_auth: None,
+			proxy: None,
 		};
 		let mut c = proto::external_processor_client::ExternalProcessorClient::new(chan);
 		let (tx_req, rx_req) = tokio::sync::mpsc::channel(10);
@@ -413,6 +415,10 @@ pub struct GrpcChannel {
 	pub target: Target,
 	pub transport: Transport,
 	pub client: client::Client,
+	/// Credentials added to each call, if any.
+	pub backend_auth: Option<http::auth::BackendAuth>,
+	/// A forward proxy to connect through, if any.
+	pub proxy: Option<http::outboundproxy::OutboundProxy>,
 }
 
 impl tower::Service<::http::Request<tonic::body::Body>> for GrpcChannel {
@@ -428,9 +434,12 @@ impl tower::Service<::http::Request<tonic::body::Body>> for GrpcChannel {
 		let client = self.client.clone();
 		let target = self.target.clone();
 		let transport = self.transport.clone();
+		let backend_auth = self.backend_auth.clone();
+		let proxy = self.proxy.clone();
 		let mut req = req.map(http::Body::new);
 
 		Box::pin(async move {
+			http::auth::apply_backend_auth(backend_auth.as_ref(), &mut req).await?;
 			http::modify_req_uri(&mut req, |uri| {
 				uri.authority = Some(Authority::try_from(target.to_string())?);
 				uri.scheme = Some(transport.scheme());
@@ -442,6 +451,7 @@ impl tower::Service<::http::Request<tonic::body::Body>> for GrpcChannel {
 						req,
 						target,
 						transport,
+						proxy,
 					})
 					.await?,
 			)
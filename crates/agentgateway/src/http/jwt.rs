@@ -1,5 +1,6 @@
 // Inspired by https://github.com/cdriehuys/axum-jwks/blob/main/axum-jwks/src/jwks.rs (MIT license)
 use std::collections::HashMap;
+use std::hash::BuildHasher;
 use std::str::FromStr;
 
 use axum_core::RequestExt;
@@ -8,7 +9,9 @@ use axum_extra::headers::Authorization;
 use axum_extra::headers::authorization::Bearer;
 use jsonwebtoken::jwk::{self, AlgorithmParameters, JwkSet, KeyAlgorithm};
 use jsonwebtoken::{DecodingKey, TokenData, Validation, decode, decode_header};
-use secrecy::SecretString;
+use moka::policy::EvictionPolicy;
+use moka::sync::Cache;
+use secrecy::{ExposeSecret, SecretString};
 use serde::de::Error;
 use serde::ser::SerializeMap;
 use serde_json::{Map, Value};
@@ -16,9 +19,14 @@ use serde_json::{Map, Value};
 use crate::client::Client;
 use crate::http::Request;
 use crate::telemetry::log::RequestLog;
+use crate::telemetry::metrics::{JwtCacheLabels, JwtCacheResult};
 use crate::types::agent::{HostRedirect, PathRedirect};
 use crate::*;
 
+#[cfg(test)]
+#[path = "jwt_tests.rs"]
+mod tests;
+
 #[derive(Debug, thiserror::Error, PartialEq)]
 pub enum TokenError {
 	#[error("the token is invalid or malformed: {0:?}")]
@@ -60,6 +68,10 @@ pub enum JwkError {
 #[derive(Clone)]
 pub struct Jwt {
 	keys: HashMap<String, Jwk>,
+	// Set if any audience is a pattern. In that case audiences are checked after decoding, rather than
+	// by jsonwebtoken.
+	audience_patterns: Option<Vec<AudienceMatch>>,
+	cache: Option<Arc<ClaimsCache>>,
 }
 
 // TODO: can we give anything useful here?
@@ -83,8 +95,149 @@ impl Debug for Jwt {
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct LocalJwtConfig {
 	pub issuer: String,
-	pub audiences: Vec<String>,
+	/// Tokens are accepted if any of their audiences matches any of these.
+	#[cfg_attr(feature = "schema", schemars(with = "Vec<RawAudienceMatch>"))]
+	pub audiences: Vec<AudienceMatch>,
 	pub jwks: serdes::FileInlineOrRemote,
+	/// If set, validated tokens are cached so repeated requests with the same token skip signature
+	/// verification.
+	#[serde(default)]
+	pub cache: Option<JwtCacheConfig>,
+}
+
+/// Controls caching of validated tokens.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct JwtCacheConfig {
+	/// Maximum number of tokens to cache. The least recently used token is evicted when full.
+	/// Defaults to 1024.
+	#[serde(default)]
+	pub max_entries: Option<usize>,
+	/// Maximum time a token is cached for. Tokens are never cached past their own `exp`. Defaults to
+	/// 1 minute.
+	#[serde(default, with = "serde_dur_option")]
+	#[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+	pub max_ttl: Option<Duration>,
+}
+
+const DEFAULT_CACHE_ENTRIES: usize = 1024;
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// A least recently used cache of validated claims, keyed by a hash of the token.
+struct ClaimsCache {
+	max_ttl: Duration,
+	hasher: std::hash::RandomState,
+	entries: Cache<u64, CachedClaims>,
+}
+
+#[derive(Clone)]
+struct CachedClaims {
+	claims: Claims,
+	expires: Instant,
+}
+
+impl ClaimsCache {
+	fn new(cfg: &JwtCacheConfig) -> Self {
+		let max_entries = cfg.max_entries.unwrap_or(DEFAULT_CACHE_ENTRIES);
+		let max_ttl = cfg.max_ttl.unwrap_or(DEFAULT_CACHE_TTL);
+		Self {
+			max_ttl,
+			hasher: Default::default(),
+			entries: Cache::builder()
+				.max_capacity(max_entries as u64)
+				.time_to_live(max_ttl)
+				.eviction_policy(EvictionPolicy::lru())
+				.build(),
+		}
+	}
+
+	fn get(&self, token: &str) -> Option<Claims> {
+		let entry = self.entries.get(&self.hasher.hash_one(token))?;
+		// The full token is compared, so a hash collision can never return another token's claims.
+		if entry.expires <= Instant::now() || entry.claims.jwt.expose_secret() != token {
+			return None;
+		}
+		Some(entry.claims)
+	}
+
+	fn insert(&self, token: &str, claims: &Claims) {
+		if self.entries.policy().max_capacity() == Some(0) {
+			return;
+		}
+		let Some(exp) = claims.inner.get("exp").and_then(Value::as_u64) else {
+			return;
+		};
+		let now = std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_secs();
+		let ttl = Duration::from_secs(exp.saturating_sub(now)).min(self.max_ttl);
+		if ttl.is_zero() {
+			return;
+		}
+		self.entries.insert(
+			self.hasher.hash_one(token),
+			CachedClaims {
+				claims: claims.clone(),
+				expires: Instant::now() + ttl,
+			},
+		);
+	}
+}
+
+// Patterns are small in practice; this bounds the compiled size of a hostile or mistaken one.
+const AUDIENCE_REGEX_SIZE_LIMIT: usize = 64 * 1024;
+
+/// An accepted token audience.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(try_from = "RawAudienceMatch")]
+pub enum AudienceMatch {
+	Exact(String),
+	Prefix(String),
+	Regex(regex::Regex),
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(untagged)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+enum RawAudienceMatch {
+	/// The audience must be exactly this string.
+	Exact(String),
+	/// The audience must start with this string.
+	Prefix { prefix: String },
+	/// The entire audience must match this regular expression.
+	Regex { regex: String },
+}
+
+impl TryFrom<RawAudienceMatch> for AudienceMatch {
+	type Error = String;
+
+	/// Regexes are anchored, so they must match the whole audience. The regex engine matches in linear
+	/// time, so patterns cannot cause catastrophic backtracking; the size limit rejects patterns that
+	/// would compile to an excessively large program.
+	fn try_from(raw: RawAudienceMatch) -> Result<Self, Self::Error> {
+		Ok(match raw {
+			RawAudienceMatch::Exact(exact) => AudienceMatch::Exact(exact),
+			RawAudienceMatch::Prefix { prefix } => AudienceMatch::Prefix(prefix),
+			RawAudienceMatch::Regex { regex } => AudienceMatch::Regex(
+				regex::RegexBuilder::new(&format!("^(?:{regex})$"))
+					.size_limit(AUDIENCE_REGEX_SIZE_LIMIT)
+					.build()
+					.map_err(|e| format!("invalid audience regex {regex:?}: {e}"))?,
+			),
+		})
+	}
+}
+
+impl AudienceMatch {
+	pub fn matches(&self, aud: &str) -> bool {
+		match self {
+			AudienceMatch::Exact(exact) => exact == aud,
+			AudienceMatch::Prefix(prefix) => aud.starts_with(prefix.as_str()),
+			AudienceMatch::Regex(regex) => regex.is_match(aud),
+		}
+	}
 }
 
 impl LocalJwtConfig {
@@ -124,8 +277,7 @@ impl LocalJwtConfig {
 					},
 				};
 
-				let mut validation = Validation::new(key_alg);
-				validation.set_audience(self.audiences.as_slice());
+				let validation = self.validation(key_alg);
 
 				keys.insert(
 					kid,
@@ -142,10 +294,54 @@ impl LocalJwtConfig {
 			}
 		}
 
-		Ok(Jwt { keys })
+		Ok(Jwt {
+			keys,
+			audience_patterns: self.audience_patterns(),
+			cache: self.cache.as_ref().map(|c| Arc::new(ClaimsCache::new(c))),
+		})
+	}
+
+	fn exact_audiences(&self) -> Option<Vec<&str>> {
+		self
+			.audiences
+			.iter()
+			.map(|a| match a {
+				AudienceMatch::Exact(exact) => Some(exact.as_str()),
+				_ => None,
+			})
+			.collect()
+	}
+
+	fn audience_patterns(&self) -> Option<Vec<AudienceMatch>> {
+		match self.exact_audiences() {
+			Some(_) => None,
+			None => Some(self.audiences.clone()),
+		}
+	}
+
+	fn validation(&self, alg: jsonwebtoken::Algorithm) -> Validation {
+		let mut validation = Validation::new(alg);
+		match self.exact_audiences() {
+			Some(exact) => validation.set_audience(&exact),
+			// Checked in validate_claims instead
+			None => validation.validate_aud = false,
+		}
+		validation
 	}
 }
 
+/// Whether any audience in the `aud` claim, which may be a string or an array, matches any pattern.
+fn audience_matches(claims: &Map<String, Value>, patterns: &[AudienceMatch]) -> bool {
+	let auds: Vec<&str> = match claims.get("aud") {
+		Some(Value::String(aud)) => vec![aud.as_str()],
+		Some(Value::Array(auds)) => auds.iter().filter_map(Value::as_str).collect(),
+		_ => vec![],
+	};
+	auds
+		.iter()
+		.any(|aud| patterns.iter().any(|p| p.matches(aud)))
+}
+
 #[derive(Clone)]
 struct Jwk {
 	decoding: DecodingKey,
@@ -168,7 +364,7 @@ impl Jwt {
 			// TODO: we need authorization policies to allow requiring it
 			return Ok(());
 		};
-		let claims = self.validate_claims(bearer.token())?;
+		let claims = self.validate_claims_cached(bearer.token(), log)?;
 		if let Some(serde_json::Value::String(sub)) = claims.inner.get("sub") {
 			log.jwt_sub = Some(sub.to_string());
 		};
@@ -179,6 +375,28 @@ impl Jwt {
 		Ok(())
 	}
 
+	fn validate_claims_cached(&self, token: &str, log: &RequestLog) -> Result<Claims, TokenError> {
+		let Some(cache) = &self.cache else {
+			return self.validate_claims(token);
+		};
+		let cached = cache.get(token);
+		if let Some(m) = &log.metrics {
+			let result = match cached {
+				Some(_) => JwtCacheResult::Hit,
+				None => JwtCacheResult::Miss,
+			};
+			m.jwt_cache_lookups
+				.get_or_create(&JwtCacheLabels { result })
+				.inc();
+		}
+		if let Some(claims) = cached {
+			return Ok(claims);
+		}
+		let claims = self.validate_claims(token)?;
+		cache.insert(token, &claims);
+		Ok(claims)
+	}
+
 	pub fn validate_claims(&self, token: &str) -> Result<Claims, TokenError>
 where {
 		let header = decode_header(token).map_err(|error| {
@@ -205,6 +423,15 @@ where {
 				TokenError::Invalid(error)
 			})?;
 
+		if let Some(patterns) = &self.audience_patterns {
+			if !audience_matches(&decoded_token.claims, patterns) {
+				debug!("Token audience does not match any configured audience.");
+				return Err(TokenError::Invalid(
+					jsonwebtoken::errors::ErrorKind::InvalidAudience.into(),
+				));
+			}
+		}
+
 		let claims = Claims {
 			inner: decoded_token.claims,
 			jwt: SecretString::new(token.into()),
@@ -0,0 +1,183 @@
+use jsonwebtoken::{EncodingKey, Header, encode};
+use serde_json::json;
+
+use super::*;
+
+const SECRET: &[u8] = b"secret";
+
+fn jwt(audiences: Value) -> Jwt {
+	let cfg: LocalJwtConfig = serde_json::from_value(json!({
+		"issuer": "me",
+		"audiences": audiences,
+		"jwks": { "file": "/dev/null" },
+	}))
+	.unwrap();
+	let key = Jwk {
+		decoding: DecodingKey::from_secret(SECRET),
+		validation: cfg.validation(jsonwebtoken::Algorithm::HS256),
+	};
+	Jwt {
+		keys: HashMap::from([("kid".to_string(), key)]),
+		audience_patterns: cfg.audience_patterns(),
+		cache: None,
+	}
+}
+
+fn token(aud: Value) -> String {
+	token_with_exp(aud, 4_102_444_800)
+}
+
+fn token_with_exp(aud: Value, exp: u64) -> String {
+	let mut header = Header::new(jsonwebtoken::Algorithm::HS256);
+	header.kid = Some("kid".to_string());
+	encode(
+		&header,
+		&json!({ "sub": "user", "aud": aud, "exp": exp }),
+		&EncodingKey::from_secret(SECRET),
+	)
+	.unwrap()
+}
+
+fn is_invalid_audience(res: Result<Claims, TokenError>) -> bool {
+	matches!(res, Err(TokenError::Invalid(e)) if *e.kind() == jsonwebtoken::errors::ErrorKind::InvalidAudience)
+}
+
+#[test]
+fn test_audience_exact() {
+	let jwt = jwt(json!(["https://api.example.com"]));
+	assert!(jwt.audience_patterns.is_none());
+	assert!(
+		jwt
+			.validate_claims(&token(json!("https://api.example.com")))
+			.is_ok()
+	);
+	assert!(is_invalid_audience(
+		jwt.validate_claims(&token(json!("https://api.example.com.evil")))
+	));
+}
+
+#[test]
+fn test_audience_prefix() {
+	let jwt = jwt(json!([{ "prefix": "https://tenant-" }]));
+	assert!(
+		jwt
+			.validate_claims(&token(json!("https://tenant-123.example.com")))
+			.is_ok()
+	);
+	// Any audience in the claim may match
+	assert!(
+		jwt
+			.validate_claims(&token(json!(["other", "https://tenant-9.example.com"])))
+			.is_ok()
+	);
+	assert!(is_invalid_audience(
+		jwt.validate_claims(&token(json!("https://api.example.com")))
+	));
+}
+
+#[test]
+fn test_audience_regex() {
+	let jwt = jwt(json!([
+		"exact",
+		{ "regex": r"https://tenant-\d+\.example\.com" }
+	]));
+	assert!(jwt.validate_claims(&token(json!("exact"))).is_ok());
+	assert!(
+		jwt
+			.validate_claims(&token(json!("https://tenant-123.example.com")))
+			.is_ok()
+	);
+	// The regex must match the whole audience
+	assert!(is_invalid_audience(jwt.validate_claims(&token(json!(
+		"https://tenant-123.example.com.evil"
+	)))));
+	assert!(is_invalid_audience(
+		jwt.validate_claims(&token(json!("https://tenant-abc.example.com")))
+	));
+}
+
+#[test]
+fn test_audience_regex_invalid() {
+	let res = serde_json::from_value::<LocalJwtConfig>(json!({
+		"issuer": "me",
+		"audiences": [{ "regex": "(unclosed" }],
+		"jwks": { "file": "/dev/null" },
+	}));
+	assert!(res.is_err());
+	// Patterns compiling to a huge program are rejected at load
+	let res = serde_json::from_value::<LocalJwtConfig>(json!({
+		"issuer": "me",
+		"audiences": [{ "regex": r"\w{1000}{1000}" }],
+		"jwks": { "file": "/dev/null" },
+	}));
+	assert!(res.is_err());
+}
+
+fn claims(token: &str) -> Claims {
+	claims_with_exp(token, 4_102_444_800)
+}
+
+fn claims_with_exp(token: &str, exp: u64) -> Claims {
+	Claims {
+		inner: Map::from_iter([("exp".to_string(), json!(exp))]),
+		jwt: SecretString::new(token.into()),
+	}
+}
+
+fn cache(max_entries: usize, max_ttl: Duration) -> ClaimsCache {
+	ClaimsCache::new(&JwtCacheConfig {
+		max_entries: Some(max_entries),
+		max_ttl: Some(max_ttl),
+	})
+}
+
+#[test]
+fn test_cache_hit() {
+	let cache = cache(10, Duration::from_secs(60));
+	let tok = token(json!("aud"));
+	assert!(cache.get(&tok).is_none());
+	cache.insert(&tok, &claims(&tok));
+	let hit = cache.get(&tok).unwrap();
+	assert_eq!(hit.jwt.expose_secret(), tok);
+	assert!(cache.get(&token(json!(["aud", "other"]))).is_none());
+}
+
+#[test]
+fn test_cache_expiry() {
+	let now = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.unwrap()
+		.as_secs();
+	// Tokens already past their `exp` are never cached
+	let cache = cache(10, Duration::from_secs(60));
+	let expired = token_with_exp(json!("aud"), now - 1);
+	cache.insert(&expired, &claims_with_exp(&expired, now - 1));
+	assert!(cache.get(&expired).is_none());
+
+	// Entries are dropped after the max TTL, even if the token is still valid
+	let short = self::cache(10, Duration::from_millis(10));
+	let tok = token(json!("aud"));
+	short.insert(&tok, &claims(&tok));
+	assert!(short.get(&tok).is_some());
+	std::thread::sleep(Duration::from_millis(20));
+	assert!(short.get(&tok).is_none());
+}
+
+#[test]
+fn test_cache_eviction() {
+	let cache = cache(2, Duration::from_secs(60));
+	let a = token(json!("a"));
+	let b = token(json!("b"));
+	let c = token(json!("c"));
+	cache.insert(&a, &claims(&a));
+	cache.insert(&b, &claims(&b));
+	cache.entries.run_pending_tasks();
+	// Touch a, so b is the least recently used
+	assert!(cache.get(&a).is_some());
+	cache.entries.run_pending_tasks();
+	cache.insert(&c, &claims(&c));
+	cache.entries.run_pending_tasks();
+	assert!(cache.get(&a).is_some());
+	assert!(cache.get(&b).is_none());
+	assert!(cache.get(&c).is_some());
+}
@@ -1,15 +1,35 @@
+use std::task::{Context, Poll};
+
+use futures_util::future::Either;
+use moka::sync::Cache;
 use serde::de::Error;
 use serde::ser::SerializeMap;
 
-use crate::http::Request;
+use crate::http::jwt::Claims;
+use crate::http::{Request, Response};
 use crate::llm::LLMRequest;
-use crate::types::agent::{HostRedirect, PathRedirect};
+use crate::proxy::ProxyError;
+use crate::telemetry::metrics::{BindLabels, Metrics};
+use crate::transport::stream::TCPConnectionInfo;
+use crate::types::agent::{BindName, HostRedirect, PathRedirect};
 use crate::*;
 
+#[cfg(test)]
+#[path = "localratelimit_tests.rs"]
+mod tests;
+
+// The most clients a keyed limit tracks at once. Past this, the least recently used buckets are evicted.
+const MAX_TRACKED_KEYS: u64 = 10_000;
+
 #[derive(Clone)]
 pub struct RateLimit {
 	ratelimit: Arc<ratelimit::Ratelimiter>,
 	pub limit_type: RateLimitType,
+	pub key: Option<RateLimitKey>,
+	spec: RateLimitSerde,
+	// Per client buckets, for keyed limits. A bucket expires once it has been idle long enough to refill
+	// completely, as a new bucket would then be identical.
+	buckets: Option<Cache<String, Arc<ratelimit::Ratelimiter>>>,
 }
 
 impl serde::Serialize for RateLimit {
@@ -49,6 +69,41 @@ pub struct RateLimitSerde {
 	#[serde(default)]
 	#[serde(rename = "type")]
 	pub limit_type: RateLimitType,
+	/// If set, a separate bucket is kept for each client instead of one shared by all requests.
+	/// Only supported for request limits.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub key: Option<RateLimitKey>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RateLimitKey {
+	/// The client IP address.
+	SourceIp,
+	/// The `sub` claim of the validated JWT, falling back to the client IP address if there is none.
+	JwtSubject,
+}
+
+impl RateLimitKey {
+	fn extract<B>(&self, req: &::http::Request<B>) -> String {
+		let sub = match self {
+			RateLimitKey::SourceIp => None,
+			RateLimitKey::JwtSubject => req
+				.extensions()
+				.get::<Claims>()
+				.and_then(|c| c.inner.get("sub"))
+				.and_then(|s| s.as_str())
+				.map(|s| format!("sub/{s}")),
+		};
+		sub.unwrap_or_else(|| {
+			let ip = req
+				.extensions()
+				.get::<TCPConnectionInfo>()
+				.map(|t| t.peer_addr.ip().to_string())
+				.unwrap_or_default();
+			format!("ip/{ip}")
+		})
+	}
 }
 
 #[derive(Default, Debug, Eq, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
@@ -61,33 +116,62 @@ pub enum RateLimitType {
 	Tokens,
 }
 
+fn build(value: &RateLimitSerde) -> Result<ratelimit::Ratelimiter, ratelimit::Error> {
+	ratelimit::Ratelimiter::builder(value.tokens_per_fill, value.fill_interval)
+		.initial_available(value.max_tokens)
+		.max_tokens(value.max_tokens)
+		.build()
+}
+
 impl TryFrom<RateLimitSerde> for RateLimit {
 	type Error = ratelimit::Error;
 	fn try_from(value: RateLimitSerde) -> Result<Self, Self::Error> {
-		let rl = ratelimit::Ratelimiter::builder(value.tokens_per_fill, value.fill_interval)
-			.initial_available(value.max_tokens)
-			.max_tokens(value.max_tokens)
-			.build()?;
+		if value.key.is_some() && value.limit_type != RateLimitType::Requests {
+			return Err(ratelimit::Error::KeyNotSupported);
+		}
+		let rl = build(&value)?;
+		let buckets = value.key.map(|_| {
+			let refills = value.max_tokens.div_ceil(value.tokens_per_fill.max(1));
+			let refill_time = value
+				.fill_interval
+				.saturating_mul(refills.try_into().unwrap_or(u32::MAX));
+			Cache::builder()
+				.max_capacity(MAX_TRACKED_KEYS)
+				.time_to_idle(refill_time)
+				.build()
+		});
 		Ok(RateLimit {
 			ratelimit: Arc::new(rl),
-			limit_type: value.limit_type,
+			limit_type: value.limit_type.clone(),
+			key: value.key,
+			spec: value,
+			buckets,
 		})
 	}
 }
 
 impl RateLimit {
-	pub fn check_request(&self, req: &Request) -> bool {
+	/// Checks the request against the limit. On failure, returns how long the client should wait
+	/// before retrying.
+	pub fn check_request<B>(&self, req: &::http::Request<B>) -> Result<(), Duration> {
 		if self.limit_type != RateLimitType::Requests {
-			return true;
+			return Ok(());
+		}
+		match (&self.key, &self.buckets) {
+			(Some(key), Some(buckets)) => buckets
+				.get_with(key.extract(req), || {
+					Arc::new(build(&self.spec).expect("parameters were validated on creation"))
+				})
+				.try_wait(),
+			_ => self.ratelimit.try_wait(),
 		}
-		self.ratelimit.try_wait().is_ok()
 	}
 	// TODO: add true-up for the response toke usage
-	pub fn check_llm_request(&self, req: &LLMRequest) -> bool {
+	pub fn check_llm_request(&self, req: &LLMRequest) -> Result<(), Duration> {
 		if self.limit_type != RateLimitType::Tokens {
-			return true;
+			return Ok(());
 		}
-		self.ratelimit.try_wait_n(req.input_tokens).is_ok()
+		self.ratelimit.try_wait_n(req.input_tokens)
 	}
 
 	/// Remove tokens from the rate limiter after the fact. This is useful for true-up
@@ -100,6 +184,71 @@ impl RateLimit {
 	}
 }
 
+/// Applies a request rate limit in front of a service. Requests over the limit are rejected with a 429
+/// and a `Retry-After` header.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+	limit: RateLimit,
+	bind: BindName,
+	metrics: Arc<Metrics>,
+}
+
+impl RateLimitLayer {
+	pub fn new(limit: RateLimit, bind: BindName, metrics: Arc<Metrics>) -> Self {
+		RateLimitLayer {
+			limit,
+			bind,
+			metrics,
+		}
+	}
+}
+
+impl<S> tower::Layer<S> for RateLimitLayer {
+	type Service = RateLimitService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		RateLimitService {
+			inner,
+			layer: self.clone(),
+		}
+	}
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+	inner: S,
+	layer: RateLimitLayer,
+}
+
+impl<S, B> tower::Service<::http::Request<B>> for RateLimitService<S>
+where
+	S: tower::Service<::http::Request<B>, Response = Response>,
+{
+	type Response = Response;
+	type Error = S::Error;
+	type Future = Either<std::future::Ready<Result<Response, S::Error>>, S::Future>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: ::http::Request<B>) -> Self::Future {
+		if let Err(retry_after) = self.layer.limit.check_request(&req) {
+			self
+				.layer
+				.metrics
+				.rate_limited_bind_requests
+				.get_or_create(&BindLabels {
+					bind: self.layer.bind.clone().into(),
+				})
+				.inc();
+			let resp = ProxyError::RateLimitExceeded(Some(retry_after)).as_response();
+			return Either::Left(std::future::ready(Ok(resp)));
+		}
+		Either::Right(self.inner.call(req))
+	}
+}
+
 // Forked from https://github.com/pelikan-io/rustcommon/tree/main/ratelimit to provide some additional functions
 mod ratelimit {
 	use core::sync::atomic::{AtomicU64, Ordering};
@@ -119,6 +268,8 @@ mod ratelimit {
 		RefillAmountTooHigh,
 		#[error("refill interval in nanoseconds exceeds maximum u64")]
 		RefillIntervalTooLong,
+		#[error("a rate limit key can only be used with request limits")]
+		KeyNotSupported,
 	}
 
 	#[derive(Debug, Clone, Copy, Eq, PartialEq)]
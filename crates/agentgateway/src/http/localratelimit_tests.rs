@@ -0,0 +1,97 @@
+use prometheus_client::registry::Registry;
+use tower::{Layer, ServiceExt};
+
+use super::*;
+use crate::http::Body;
+
+fn limit(fill_interval: Duration, key: Option<RateLimitKey>) -> RateLimit {
+	RateLimitSerde {
+		max_tokens: 1,
+		tokens_per_fill: 1,
+		fill_interval,
+		limit_type: RateLimitType::Requests,
+		key,
+	}
+	.try_into()
+	.unwrap()
+}
+
+fn request(ip: &str) -> Request {
+	let mut req = ::http::Request::new(Body::empty());
+	req.extensions_mut().insert(TCPConnectionInfo {
+		peer_addr: SocketAddr::new(ip.parse().unwrap(), 12345),
+		local_addr: "127.0.0.1:80".parse().unwrap(),
+		start: std::time::Instant::now(),
+	});
+	req
+}
+
+#[test]
+fn test_keyed_limit() {
+	let rl = limit(Duration::from_secs(3600), Some(RateLimitKey::SourceIp));
+	assert!(rl.check_request(&request("10.0.0.1")).is_ok());
+	assert!(rl.check_request(&request("10.0.0.1")).is_err());
+	// Each client has its own bucket
+	assert!(rl.check_request(&request("10.0.0.2")).is_ok());
+
+	// Without a key, all clients share one bucket
+	let rl = limit(Duration::from_secs(3600), None);
+	assert!(rl.check_request(&request("10.0.0.1")).is_ok());
+	assert!(rl.check_request(&request("10.0.0.2")).is_err());
+}
+
+#[test]
+fn test_keyed_limit_bounded() {
+	let rl = limit(Duration::from_secs(3600), Some(RateLimitKey::SourceIp));
+	for i in 0..MAX_TRACKED_KEYS + 100 {
+		let ip = std::net::Ipv4Addr::from(i as u32);
+		let _ = rl.check_request(&request(&ip.to_string()));
+	}
+	let buckets = rl.buckets.as_ref().unwrap();
+	buckets.run_pending_tasks();
+	assert!(buckets.entry_count() <= MAX_TRACKED_KEYS);
+}
+
+#[test]
+fn test_keyed_limit_expires() {
+	let rl = limit(Duration::from_millis(20), Some(RateLimitKey::SourceIp));
+	assert!(rl.check_request(&request("10.0.0.1")).is_ok());
+	let buckets = rl.buckets.as_ref().unwrap();
+	buckets.run_pending_tasks();
+	assert_eq!(buckets.entry_count(), 1);
+
+	// Once a bucket has been idle long enough to refill, it is dropped
+	std::thread::sleep(Duration::from_millis(50));
+	buckets.run_pending_tasks();
+	assert_eq!(buckets.entry_count(), 0);
+	assert!(rl.check_request(&request("10.0.0.1")).is_ok());
+}
+
+#[tokio::test]
+async fn test_layer() {
+	let metrics = Arc::new(Metrics::new(&mut Registry::default()));
+	let layer = RateLimitLayer::new(
+		limit(Duration::from_secs(3600), Some(RateLimitKey::SourceIp)),
+		strng::new("bind"),
+		metrics.clone(),
+	);
+	let svc = layer.layer(tower::service_fn(|_req: Request| async {
+		Ok::<_, std::convert::Infallible>(::http::Response::new(Body::empty()))
+	}));
+
+	let resp = svc.clone().oneshot(request("10.0.0.1")).await.unwrap();
+	assert_eq!(resp.status(), ::http::StatusCode::OK);
+	let resp = svc.clone().oneshot(request("10.0.0.1")).await.unwrap();
+	assert_eq!(resp.status(), ::http::StatusCode::TOO_MANY_REQUESTS);
+	assert!(resp.headers().contains_key(::http::header::RETRY_AFTER));
+	let resp = svc.clone().oneshot(request("10.0.0.2")).await.unwrap();
+	assert_eq!(resp.status(), ::http::StatusCode::OK);
+
+	let rejected = metrics
+		.rate_limited_bind_requests
+		.get_or_create(&BindLabels {
+			bind: strng::new("bind").into(),
+		})
+		.get();
+	assert_eq!(rejected, 1);
+}
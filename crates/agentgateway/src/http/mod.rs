@@ -5,6 +5,8 @@ mod buflist;
 pub mod cors;
 pub mod jwt;
 pub mod localratelimit;
+pub mod outboundproxy;
+pub mod redact;
 pub mod retry;
 pub mod route;
 
@@ -154,6 +156,19 @@ pub fn merge_in_headers(additional_headers: Option<HeaderMap>, dest: &mut Header
 	if let Some(rh) = additional_headers {
 		for (k, v) in rh.into_iter() {
 			let Some(k) = k else { continue };
+			// Vary lists everything the response depends on, so it is added to rather than replaced
+			if k == header::VARY {
+				let present = dest.get_all(&k).iter().any(|have| {
+					have
+						.as_bytes()
+						.split(|b| *b == b',')
+						.any(|h| h.trim_ascii().eq_ignore_ascii_case(v.as_bytes()))
+				});
+				if !present {
+					dest.append(k, v);
+				}
+				continue;
+			}
 			dest.insert(k, v);
 		}
 	}
@@ -0,0 +1,421 @@
+use std::net::IpAddr;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ipnet::IpNet;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::types::agent::Target;
+use crate::*;
+
+#[cfg(test)]
+#[path = "outboundproxy_tests.rs"]
+mod tests;
+
+/// The largest CONNECT response head we accept from an HTTP proxy.
+const MAX_CONNECT_RESPONSE: usize = 8192;
+
+/// Routes upstream connections through a forward proxy.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LocalOutboundProxy {
+	/// The proxy to connect through, as `http://[user:password@]host:port` or
+	/// `socks5://[user:password@]host:port`. If unset, `HTTPS_PROXY` (for TLS backends) or
+	/// `HTTP_PROXY` is read from the environment, falling back to `ALL_PROXY`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	/// Hosts that are connected to directly. Each entry is `*`, a domain (which also matches its
+	/// subdomains), or an IP address or CIDR range. A domain or IP address may be followed by `:port`
+	/// to only match that port. If unset, `NO_PROXY` is read from the environment.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub no_proxy: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboundProxy {
+	#[serde(serialize_with = "ser_display_option")]
+	http: Option<ProxyEndpoint>,
+	#[serde(serialize_with = "ser_display_option")]
+	https: Option<ProxyEndpoint>,
+	#[serde(serialize_with = "ser_display_iter")]
+	no_proxy: Vec<NoProxyRule>,
+}
+
+impl TryFrom<LocalOutboundProxy> for OutboundProxy {
+	type Error = anyhow::Error;
+
+	fn try_from(value: LocalOutboundProxy) -> Result<Self, Self::Error> {
+		Self::from_lookup(value, |k| std::env::var(k).ok())
+	}
+}
+
+impl OutboundProxy {
+	fn from_lookup(
+		value: LocalOutboundProxy,
+		env: impl Fn(&str) -> Option<String>,
+	) -> anyhow::Result<Self> {
+		let env_any = |keys: &[&str]| {
+			keys
+				.iter()
+				.find_map(|k| env(k).or_else(|| env(&k.to_ascii_lowercase())))
+				.filter(|v| !v.is_empty())
+		};
+		let (http, https) = match value.url {
+			Some(url) => {
+				let ep = ProxyEndpoint::parse(&url)?;
+				(Some(ep.clone()), Some(ep))
+			},
+			None => (
+				env_any(&["HTTP_PROXY", "ALL_PROXY"])
+					.map(|u| ProxyEndpoint::parse(&u))
+					.transpose()?,
+				env_any(&["HTTPS_PROXY", "ALL_PROXY"])
+					.map(|u| ProxyEndpoint::parse(&u))
+					.transpose()?,
+			),
+		};
+		let no_proxy = match value.no_proxy {
+			Some(rules) => rules,
+			None => env_any(&["NO_PROXY"])
+				.map(|v| v.split(',').map(str::to_string).collect())
+				.unwrap_or_default(),
+		};
+		let no_proxy = no_proxy
+			.iter()
+			.map(|r| r.trim())
+			.filter(|r| !r.is_empty())
+			.map(NoProxyRule::parse)
+			.collect();
+		Ok(OutboundProxy {
+			http,
+			https,
+			no_proxy,
+		})
+	}
+
+	/// Returns the proxy to use to reach `target`, if any.
+	pub fn endpoint_for(&self, target: &Target, tls: bool) -> Option<&ProxyEndpoint> {
+		if self.no_proxy.iter().any(|r| r.matches(target)) {
+			return None;
+		}
+		if tls {
+			self.https.as_ref()
+		} else {
+			self.http.as_ref()
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum ProxyScheme {
+	/// An HTTP proxy. TLS connections are tunneled through it with `CONNECT`, while plain HTTP
+	/// requests are forwarded to it in absolute-form.
+	Http,
+	Socks5,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+struct ProxyCredentials {
+	username: String,
+	password: String,
+}
+
+/// A forward proxy to dial. Credentials are never displayed.
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct ProxyEndpoint {
+	pub scheme: ProxyScheme,
+	pub target: Target,
+	credentials: Option<ProxyCredentials>,
+}
+
+impl Debug for ProxyEndpoint {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{self}")
+	}
+}
+
+impl Display for ProxyEndpoint {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let scheme = match self.scheme {
+			ProxyScheme::Http => "http",
+			ProxyScheme::Socks5 => "socks5",
+		};
+		write!(f, "{scheme}://{}", self.target)
+	}
+}
+
+impl ProxyEndpoint {
+	pub fn parse(s: &str) -> anyhow::Result<Self> {
+		let url = url::Url::parse(s).map_err(|e| anyhow::anyhow!("invalid proxy url: {e}"))?;
+		let (scheme, default_port) = match url.scheme() {
+			"http" => (ProxyScheme::Http, 80),
+			// The proxy always resolves the destination, so these are equivalent
+			"socks5" | "socks5h" => (ProxyScheme::Socks5, 1080),
+			other => anyhow::bail!("unsupported proxy scheme '{other}', expected http or socks5"),
+		};
+		let port = url.port().unwrap_or(default_port);
+		let target = match url.host() {
+			Some(url::Host::Domain(d)) => Target::Hostname(strng::new(d), port),
+			Some(url::Host::Ipv4(ip)) => Target::Address(SocketAddr::from((ip, port))),
+			Some(url::Host::Ipv6(ip)) => Target::Address(SocketAddr::from((ip, port))),
+			None => anyhow::bail!("proxy url must have a host"),
+		};
+		let credentials = if url.username().is_empty() {
+			None
+		} else {
+			let decode = |s: &str| {
+				percent_encoding::percent_decode_str(s)
+					.decode_utf8()
+					.map(|s| s.into_owned())
+					.map_err(|e| anyhow::anyhow!("invalid proxy credentials: {e}"))
+			};
+			Some(ProxyCredentials {
+				username: decode(url.username())?,
+				password: decode(url.password().unwrap_or_default())?,
+			})
+		};
+		Ok(ProxyEndpoint {
+			scheme,
+			target,
+			credentials,
+		})
+	}
+
+	/// Whether plain HTTP requests are sent to the proxy itself, in absolute-form, rather than
+	/// through a tunnel.
+	pub fn forwards_http(&self) -> bool {
+		self.scheme == ProxyScheme::Http
+	}
+
+	/// The `Proxy-Authorization` header value for the proxy credentials, if any.
+	pub fn authorization(&self) -> Option<String> {
+		let c = self.credentials.as_ref()?;
+		let token = STANDARD.encode(format!("{}:{}", c.username, c.password));
+		Some(format!("Basic {token}"))
+	}
+
+	/// Asks the proxy, over the established connection `io`, to open a tunnel to `target`.
+	pub async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
+		&self,
+		io: &mut S,
+		target: &Target,
+	) -> anyhow::Result<()> {
+		match self.scheme {
+			ProxyScheme::Http => self.http_connect(io, target).await,
+			ProxyScheme::Socks5 => self.socks5_connect(io, target).await,
+		}
+	}
+
+	async fn http_connect<S: AsyncRead + AsyncWrite + Unpin>(
+		&self,
+		io: &mut S,
+		target: &Target,
+	) -> anyhow::Result<()> {
+		let authority = target.to_string();
+		let mut req = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+		if let Some(auth) = self.authorization() {
+			req.push_str(&format!("Proxy-Authorization: {auth}\r\n"));
+		}
+		req.push_str("\r\n");
+		io.write_all(req.as_bytes()).await?;
+
+		// Read the response head a byte at a time, so nothing past it is consumed from the tunnel.
+		let mut head = Vec::with_capacity(128);
+		while !head.ends_with(b"\r\n\r\n") {
+			if head.len() >= MAX_CONNECT_RESPONSE {
+				anyhow::bail!("proxy CONNECT response too large");
+			}
+			head.push(io.read_u8().await?);
+		}
+		let status_line = head
+			.split(|b| *b == b'\n')
+			.next()
+			.map(|l| String::from_utf8_lossy(l).trim().to_string())
+			.unwrap_or_default();
+		match status_line.split_whitespace().nth(1) {
+			Some(code) if code.starts_with('2') => Ok(()),
+			_ => anyhow::bail!("proxy refused CONNECT to {authority}: {status_line}"),
+		}
+	}
+
+	async fn socks5_connect<S: AsyncRead + AsyncWrite + Unpin>(
+		&self,
+		io: &mut S,
+		target: &Target,
+	) -> anyhow::Result<()> {
+		const VERSION: u8 = 0x05;
+		const NO_AUTH: u8 = 0x00;
+		const USERNAME_PASSWORD: u8 = 0x02;
+
+		// Method negotiation (RFC 1928)
+		let method = if self.credentials.is_some() {
+			USERNAME_PASSWORD
+		} else {
+			NO_AUTH
+		};
+		io.write_all(&[VERSION, 1, method]).await?;
+		let mut reply = [0u8; 2];
+		io.read_exact(&mut reply).await?;
+		if reply[0] != VERSION || reply[1] != method {
+			anyhow::bail!("socks5 proxy rejected the authentication method");
+		}
+
+		// Username/password authentication (RFC 1929)
+		if let Some(c) = &self.credentials {
+			let (user, pass) = (c.username.as_bytes(), c.password.as_bytes());
+			if user.len() > 255 || pass.len() > 255 {
+				anyhow::bail!("socks5 credentials must be at most 255 bytes");
+			}
+			let mut msg = vec![0x01, user.len() as u8];
+			msg.extend_from_slice(user);
+			msg.push(pass.len() as u8);
+			msg.extend_from_slice(pass);
+			io.write_all(&msg).await?;
+			io.read_exact(&mut reply).await?;
+			if reply[1] != 0 {
+				anyhow::bail!("socks5 proxy rejected the credentials");
+			}
+		}
+
+		let mut msg = vec![VERSION, 0x01, 0x00];
+		let port = match target {
+			Target::Address(addr) => {
+				match addr.ip() {
+					IpAddr::V4(ip) => {
+						msg.push(0x01);
+						msg.extend_from_slice(&ip.octets());
+					},
+					IpAddr::V6(ip) => {
+						msg.push(0x04);
+						msg.extend_from_slice(&ip.octets());
+					},
+				}
+				addr.port()
+			},
+			Target::Hostname(host, port) => {
+				if host.len() > 255 {
+					anyhow::bail!("hostname too long for socks5");
+				}
+				msg.push(0x03);
+				msg.push(host.len() as u8);
+				msg.extend_from_slice(host.as_bytes());
+				*port
+			},
+		};
+		msg.extend_from_slice(&port.to_be_bytes());
+		io.write_all(&msg).await?;
+
+		let mut head = [0u8; 4];
+		io.read_exact(&mut head).await?;
+		if head[1] != 0 {
+			anyhow::bail!(
+				"socks5 proxy failed to connect to {target} (reply {})",
+				head[1]
+			);
+		}
+		// Skip the bound address, which we have no use for
+		let addr_len = match head[3] {
+			0x01 => 4,
+			0x04 => 16,
+			0x03 => io.read_u8().await? as usize,
+			other => anyhow::bail!("socks5 proxy sent unknown address type {other}"),
+		};
+		let mut bound = vec![0u8; addr_len + 2];
+		io.read_exact(&mut bound).await?;
+		Ok(())
+	}
+}
+
+/// A `NO_PROXY` entry, optionally limited to a single port.
+#[derive(Debug, Clone)]
+struct NoProxyRule {
+	host: NoProxyHost,
+	port: Option<u16>,
+}
+
+#[derive(Debug, Clone)]
+enum NoProxyHost {
+	All,
+	Network(IpNet),
+	Ip(IpAddr),
+	/// A domain, without a leading dot, matching itself and its subdomains.
+	Domain(String),
+}
+
+impl Display for NoProxyRule {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let host = match &self.host {
+			NoProxyHost::All => "*".to_string(),
+			NoProxyHost::Network(n) => n.to_string(),
+			NoProxyHost::Ip(IpAddr::V6(ip)) if self.port.is_some() => format!("[{ip}]"),
+			NoProxyHost::Ip(ip) => ip.to_string(),
+			NoProxyHost::Domain(d) => d.clone(),
+		};
+		match self.port {
+			Some(port) => write!(f, "{host}:{port}"),
+			None => write!(f, "{host}"),
+		}
+	}
+}
+
+impl NoProxyRule {
+	fn parse(s: &str) -> Self {
+		let (host, port) = Self::split_port(s);
+		let host = if host == "*" {
+			NoProxyHost::All
+		} else if let Ok(n) = host.parse::<IpNet>() {
+			NoProxyHost::Network(n)
+		} else if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse() {
+			NoProxyHost::Ip(ip)
+		} else {
+			NoProxyHost::Domain(
+				host
+					.trim_start_matches("*.")
+					.trim_start_matches('.')
+					.to_ascii_lowercase(),
+			)
+		};
+		NoProxyRule { host, port }
+	}
+
+	/// Splits a trailing `:port` from the entry. A bare IPv6 address has no port; one with a port
+	/// must be bracketed, as in `[::1]:8080`.
+	fn split_port(s: &str) -> (&str, Option<u16>) {
+		let Some((host, port)) = s.rsplit_once(':') else {
+			return (s, None);
+		};
+		let bracketed = host.starts_with('[') && host.ends_with(']');
+		if (bracketed || !host.contains(':'))
+			&& let Ok(port) = port.parse()
+		{
+			(host, Some(port))
+		} else {
+			(s, None)
+		}
+	}
+
+	fn matches(&self, target: &Target) -> bool {
+		let (ip, port) = match target {
+			Target::Address(addr) => (Some(addr.ip()), addr.port()),
+			Target::Hostname(host, port) => (host.parse::<IpAddr>().ok(), *port),
+		};
+		if self.port.is_some_and(|p| p != port) {
+			return false;
+		}
+		match (&self.host, target) {
+			(NoProxyHost::All, _) => true,
+			(NoProxyHost::Network(n), _) => ip.is_some_and(|ip| n.contains(&ip)),
+			(NoProxyHost::Ip(want), _) => ip == Some(*want),
+			(NoProxyHost::Domain(d), Target::Hostname(host, _)) => {
+				let host = host.trim_end_matches('.').to_ascii_lowercase();
+				host == *d
+					|| host
+						.strip_suffix(d.as_str())
+						.is_some_and(|h| h.ends_with('.'))
+			},
+			(NoProxyHost::Domain(_), Target::Address(_)) => false,
+		}
+	}
+}
@@ -0,0 +1,98 @@
+use crate::http::{HeaderMap, HeaderName, header};
+
+#[cfg(test)]
+#[path = "redact_tests.rs"]
+mod tests;
+
+/// Placeholder for a redacted value, matching the one used by [crate::json::redact].
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Captured values shorter than this are not scrubbed from text; masking every occurrence of a
+/// couple of characters would garble the message without hiding anything meaningful.
+const MIN_SCRUB_LEN: usize = 4;
+
+/// Headers whose values are always sensitive.
+static ALWAYS_SENSITIVE: [HeaderName; 4] = [
+	header::AUTHORIZATION,
+	header::PROXY_AUTHORIZATION,
+	header::COOKIE,
+	header::SET_COOKIE,
+];
+
+/// Decides which header values must be kept out of logs and error messages: credentials headers,
+/// any configured names, and any value marked sensitive.
+#[derive(Debug, Clone, Default)]
+pub struct SensitiveHeaders {
+	extra: Vec<HeaderName>,
+}
+
+impl SensitiveHeaders {
+	pub fn new(extra: Vec<HeaderName>) -> Self {
+		Self { extra }
+	}
+
+	pub fn is_sensitive(&self, name: &HeaderName) -> bool {
+		ALWAYS_SENSITIVE.contains(name) || self.extra.contains(name)
+	}
+
+	/// Returns `value` if it can be logged as the value of `name`, or a placeholder otherwise.
+	pub fn loggable<'a>(&self, name: &HeaderName, value: &'a str) -> &'a str {
+		if self.is_sensitive(name) {
+			REDACTED
+		} else {
+			value
+		}
+	}
+
+	/// Captures the sensitive values in `headers`, so they can be scrubbed from text that may echo
+	/// them, such as an upstream error body.
+	pub fn secrets(&self, headers: &HeaderMap) -> Secrets {
+		let mut values = Vec::new();
+		for (name, value) in headers {
+			if !value.is_sensitive() && !self.is_sensitive(name) {
+				continue;
+			}
+			let Ok(value) = value.to_str() else {
+				continue;
+			};
+			values.push(value.to_string());
+			if *name == header::COOKIE {
+				// Cookie values may be echoed individually
+				values.extend(
+					value
+						.split(';')
+						.filter_map(|c| c.split_once('=').map(|(_, v)| v.trim().to_string())),
+				);
+			} else if let Some((_, credentials)) = value.split_once(' ') {
+				// Credentials may be echoed without their scheme, as in `Bearer <token>`
+				values.push(credentials.trim().to_string());
+			}
+		}
+		Secrets::default().with(values)
+	}
+}
+
+/// Sensitive header values captured from a request. This intentionally does not implement `Debug`.
+#[derive(Default)]
+pub struct Secrets(Vec<String>);
+
+impl Secrets {
+	/// Adds values captured from elsewhere in the request, such as API keys sent in the query.
+	pub fn with(mut self, values: impl IntoIterator<Item = String>) -> Self {
+		self.0.extend(values);
+		self.0.retain(|v| v.len() >= MIN_SCRUB_LEN);
+		// Longest first, so a value is never partially masked by one it contains
+		self
+			.0
+			.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+		self.0.dedup();
+		self
+	}
+
+	/// Replaces every occurrence of a captured value in `text` with a placeholder.
+	pub fn scrub(&self, text: &str) -> String {
+		self.0.iter().fold(text.to_string(), |text, secret| {
+			text.replace(secret, REDACTED)
+		})
+	}
+}
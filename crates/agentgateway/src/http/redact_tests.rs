@@ -0,0 +1,47 @@
+use super::*;
+use crate::http::HeaderValue;
+
+#[test]
+fn test_scrub() {
+	let sensitive = SensitiveHeaders::new(vec![HeaderName::from_static("x-api-key")]);
+	let mut headers = HeaderMap::new();
+	headers.insert(
+		header::AUTHORIZATION,
+		HeaderValue::from_static("Bearer secret-token"),
+	);
+	headers.insert(
+		header::COOKIE,
+		HeaderValue::from_static("a=cookie-one; b=cookie-two"),
+	);
+	headers.insert("x-api-key", HeaderValue::from_static("key-123"));
+	let mut marked = HeaderValue::from_static("marked-value");
+	marked.set_sensitive(true);
+	headers.insert("x-other", marked);
+	headers.insert("x-public", HeaderValue::from_static("public-value"));
+
+	let secrets = sensitive.secrets(&headers);
+	let text = "echo: Authorization: Bearer secret-token, token=secret-token, cookie-two, key-123, \
+	            marked-value, public-value";
+	assert_eq!(
+		secrets.scrub(text),
+		"echo: Authorization: [REDACTED], token=[REDACTED], [REDACTED], [REDACTED], [REDACTED], \
+		 public-value"
+	);
+}
+
+#[test]
+fn test_loggable() {
+	let sensitive = SensitiveHeaders::default();
+	assert_eq!(
+		sensitive.loggable(&header::AUTHORIZATION, "Bearer x"),
+		REDACTED
+	);
+	assert_eq!(
+		sensitive.loggable(&header::ACCEPT, "text/plain"),
+		"text/plain"
+	);
+	// Short values are left alone rather than masked everywhere they appear
+	let mut headers = HeaderMap::new();
+	headers.insert(header::AUTHORIZATION, HeaderValue::from_static("x"));
+	assert_eq!(sensitive.secrets(&headers).scrub("x marks"), "x marks");
+}
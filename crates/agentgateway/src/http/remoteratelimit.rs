@@ -94,6 +94,8 @@ impl RemoteRateLimit {
 			// transport: Transport::Tls(agent::INSECURE_TRUST.clone()),
 			transport: Transport::Plaintext,
 			client,
+			backend_auth: None,
+			proxy: None,
 		};
 		let mut client = RateLimitServiceClient::new(chan);
 		let resp = client.should_rate_limit(request).await;
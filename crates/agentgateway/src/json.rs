@@ -1,4 +1,5 @@
 use axum::body::to_bytes;
+use http_body_util::BodyExt;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
 
@@ -38,6 +39,60 @@ pub fn traverse_mut<'a>(value: &'a mut Value, path: &[&str]) -> Option<&'a mut V
 	})
 }
 
+/// Replace the value at each dot separated path with a placeholder, so the document can be logged.
+/// A `*` segment matches every field of an object or element of an array.
+pub fn redact(value: &mut Value, paths: &[String]) {
+	for path in paths {
+		let path = path.split('.').collect::<Vec<_>>();
+		redact_path(value, &path);
+	}
+}
+
+fn redact_path(value: &mut Value, path: &[&str]) {
+	let Some((token, rest)) = path.split_first() else {
+		*value = Value::String("[REDACTED]".to_string());
+		return;
+	};
+	match (value, *token) {
+		(Value::Object(map), "*") => map.values_mut().for_each(|v| redact_path(v, rest)),
+		(Value::Array(list), "*") => list.iter_mut().for_each(|v| redact_path(v, rest)),
+		(Value::Object(map), token) => {
+			if let Some(v) = map.get_mut(token) {
+				redact_path(v, rest)
+			}
+		},
+		(Value::Array(list), token) => {
+			if let Some(v) = parse_index(token).and_then(|x| list.get_mut(x)) {
+				redact_path(v, rest)
+			}
+		},
+		_ => {},
+	}
+}
+
+/// Apply a JSON merge patch (RFC 7396) to `target`. Objects in the patch are merged into the
+/// existing object field by field, recursively, and a `null` field removes the field. Any other
+/// value, including an array, replaces the existing value as a whole.
+pub fn merge_patch(target: &mut Value, patch: Value) {
+	let Value::Object(patch) = patch else {
+		*target = patch;
+		return;
+	};
+	if !target.is_object() {
+		*target = Value::Object(serde_json::Map::new());
+	}
+	let Value::Object(map) = target else {
+		unreachable!()
+	};
+	for (k, v) in patch {
+		if v.is_null() {
+			map.remove(&k);
+		} else {
+			merge_patch(map.entry(k).or_insert(Value::Null), v);
+		}
+	}
+}
+
 fn parse_index(s: &str) -> Option<usize> {
 	if s.starts_with('+') || (s.starts_with('0') && s.len() != 1) {
 		return None;
@@ -53,9 +108,26 @@ pub async fn from_body<T: DeserializeOwned>(body: http::Body) -> anyhow::Result<
 	Ok(t)
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("body exceeds the limit of {0} bytes")]
+pub struct BodyTooLarge(pub usize);
+
 pub async fn inspect_body<T: DeserializeOwned>(body: &mut http::Body) -> anyhow::Result<T> {
+	inspect_body_with_limit(body, 2_097_152).await
+}
+
+/// Like [`inspect_body`], but reads at most `limit` bytes. Larger bodies fail with [`BodyTooLarge`],
+/// and are left partially consumed, so the request should be rejected.
+pub async fn inspect_body_with_limit<T: DeserializeOwned>(
+	body: &mut http::Body,
+	limit: usize,
+) -> anyhow::Result<T> {
 	let orig = std::mem::replace(body, http::Body::empty());
-	let bytes = to_bytes(orig, 2_097_152).await?;
+	let bytes = match http_body_util::Limited::new(orig, limit).collect().await {
+		Ok(b) => b.to_bytes(),
+		Err(e) if e.is::<http_body_util::LengthLimitError>() => return Err(BodyTooLarge(limit).into()),
+		Err(e) => return Err(anyhow::anyhow!(e)),
+	};
 	// Try to parse the response body as JSON
 	let t = serde_json::from_slice::<T>(bytes.as_ref());
 	// Regardless of an error or not, we should reset the body back
@@ -67,3 +139,7 @@ pub fn to_body<T: Serialize>(j: T) -> anyhow::Result<http::Body> {
 	let bytes = serde_json::to_vec(&j)?;
 	Ok(http::Body::from(bytes))
 }
+
+#[cfg(test)]
+#[path = "json_tests.rs"]
+mod tests;
@@ -0,0 +1,72 @@
+use serde_json::json;
+
+use super::*;
+
+#[test]
+fn test_redact() {
+	let mut v = json!({
+		"arguments": {"user": "bob", "password": "hunter2"},
+		"headers": [{"name": "a", "value": "1"}, {"name": "b", "value": "2"}],
+		"token": "abc",
+	});
+	redact(
+		&mut v,
+		&[
+			"arguments.password".to_string(),
+			"headers.*.value".to_string(),
+			"token".to_string(),
+			"missing.field".to_string(),
+		],
+	);
+	assert_eq!(
+		v,
+		json!({
+			"arguments": {"user": "bob", "password": "[REDACTED]"},
+			"headers": [{"name": "a", "value": "[REDACTED]"}, {"name": "b", "value": "[REDACTED]"}],
+			"token": "[REDACTED]",
+		})
+	);
+}
+
+#[test]
+fn test_merge_patch() {
+	let mut v = json!({
+		"name": "petstore",
+		"openapi": {
+			"host": "localhost",
+			"staticHeaders": [{"name": "x-a", "value": "1"}],
+			"env": {"A": "1", "B": "2"},
+		},
+		"filters": ["a"],
+	});
+	merge_patch(
+		&mut v,
+		json!({
+			"openapi": {
+				"port": 8080,
+				"staticHeaders": [{"name": "x-b", "value": "2"}],
+				"env": {"B": null, "C": "3"},
+			},
+			"filters": null,
+			"missing": null,
+		}),
+	);
+	assert_eq!(
+		v,
+		json!({
+			"name": "petstore",
+			"openapi": {
+				"host": "localhost",
+				"port": 8080,
+				"staticHeaders": [{"name": "x-b", "value": "2"}],
+				"env": {"A": "1", "C": "3"},
+			},
+		})
+	);
+
+	// A non-object patch replaces the whole value
+	merge_patch(&mut v, json!("replaced"));
+	assert_eq!(v, json!("replaced"));
+	merge_patch(&mut v, json!({"a": {"b": 1}}));
+	assert_eq!(v, json!({"a": {"b": 1}}));
+}
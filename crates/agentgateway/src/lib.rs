@@ -1,5 +1,6 @@
 // For now, the entire package is not linked up to anything so squash the warnings
 #![allow(unused)]
+use std::collections::HashMap;
 use std::fmt::{Debug, Display};
 use std::fs::File;
 use std::io::Read;
@@ -56,11 +57,12 @@ pub struct NestedRawConfig {
 
 #[derive(serde::Deserialize, Default, Clone, Debug)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 /// RawConfig represents the inputs a user can pass in. Config represents the internal representation of this.
 pub struct RawConfig {
 	enable_ipv6: Option<bool>,
 
-	// Local XDS path. If not specified, the current configuration file will be used.
+	/// Local XDS path. If not specified, the current configuration file will be used.
 	local_xds_path: Option<PathBuf>,
 
 	ca_address: Option<String>,
@@ -74,18 +76,43 @@ pub struct RawConfig {
 
 	auth_token: Option<String>,
 
+	/// The grace period of a shutdown: how long to wait for open connections, and the tool calls in
+	/// flight on them, before closing them.
 	connection_termination_deadline: Option<Duration>,
 	connection_min_termination_deadline: Option<Duration>,
 
 	worker_threads: Option<StringOrInt>,
 
 	tracing: Option<RawTracing>,
+	logging: Option<RawLogging>,
 
 	http2: Option<RawHTTP2>,
+
+	mcp_sse_buffer_size: Option<usize>,
+	mcp_connection_idle_timeout: Option<Duration>,
+	/// How often an idle MCP SSE stream sends a keep-alive comment. Defaults to 15s.
+	mcp_sse_keep_alive: Option<Duration>,
+	/// The commands stdio MCP targets may run. If unset, none may be run; `*` allows any command.
+	stdio_command_allowlist: Option<Vec<String>>,
+	fatal_bind_errors: Option<bool>,
+
+	/// Either `<host>:<port>`, `localhost:<port>`, or `unix:<path>` to serve over a Unix domain
+	/// socket.
+	admin_addr: Option<String>,
+	/// Octal file mode of the admin socket. Defaults to 0600.
+	admin_socket_mode: Option<String>,
+	/// Authentication required to call the admin API. Without it, the admin API may only listen on
+	/// localhost or a Unix domain socket.
+	admin_auth: Option<management::admin::AdminAuth>,
+	/// Either `<host>:<port>` or `localhost:<port>`. Defaults to port 15020 on all interfaces.
+	stats_addr: Option<String>,
+	/// Either `<host>:<port>` or `localhost:<port>`. Defaults to port 15021 on all interfaces.
+	readiness_addr: Option<String>,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct RawHTTP2 {
 	window_size: Option<u32>,
 	connection_window_size: Option<u32>,
@@ -94,10 +121,27 @@ pub struct RawHTTP2 {
 	pool_unused_release_timeout: Option<Duration>,
 }
 
-#[derive(serde::Deserialize, Clone, Debug)]
+#[derive(serde::Deserialize, Default, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct RawTracing {
-	otlp_endpoint: String,
+	otlp_endpoint: Option<String>,
+	otlp_protocol: Option<agent_core::trcng::Protocol>,
+	#[serde(default)]
+	headers: HashMap<String, String>,
+	random_sampling: Option<f64>,
+	service_name: Option<String>,
+	/// Export metrics to the OTLP collector too. With the http protocol, they are sent to the
+	/// `/v1/metrics` path beside the traces endpoint.
+	#[serde(default)]
+	metrics: bool,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct RawLogging {
+	format: Option<agent_core::telemetry::LogFormat>,
 }
 
 #[derive(Clone, Debug)]
@@ -133,6 +177,17 @@ impl<'de> Deserialize<'de> for StringOrInt {
 	}
 }
 
+#[cfg(feature = "schema")]
+impl JsonSchema for StringOrInt {
+	fn schema_name() -> std::borrow::Cow<'static, str> {
+		"StringOrInt".into()
+	}
+
+	fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+		schemars::json_schema!({ "type": ["string", "integer"] })
+	}
+}
+
 #[derive(serde::Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
@@ -143,7 +198,7 @@ pub struct Config {
 	pub termination_min_deadline: Duration,
 	/// Specify the number of worker threads the Tokio Runtime will use.
 	pub num_worker_threads: usize,
-	pub admin_addr: Address,
+	pub admin_addr: AdminAddress,
 	pub stats_addr: Address,
 	pub readiness_addr: Address,
 	// For waypoint identification
@@ -153,8 +208,33 @@ pub struct Config {
 	pub xds: XDSConfig,
 	pub ca: Option<caclient::Config>,
 	pub tracing: trc::Config,
+	/// The format logs are written in, once the configuration is loaded.
+	pub log_format: agent_core::telemetry::LogFormat,
 	pub dns: client::Config,
 	pub proxy_metadata: ProxyMetadata,
+	/// Number of messages buffered for each legacy SSE MCP client before we stop reading from the
+	/// server.
+	pub mcp_sse_buffer_size: usize,
+	/// If set, upstream MCP connections that are unused for this long are closed, and re-established
+	/// on next use.
+	#[serde(with = "serde_dur_option")]
+	pub mcp_connection_idle_timeout: Option<Duration>,
+	/// How often an SSE stream to an MCP client sends a `:ping` comment when no message was sent, so
+	/// intermediaries do not close it for being idle. Clients ignore comments. Zero disables it. This
+	/// is set for the whole process rather than per listener, as it has to stay below the idle timeout
+	/// of the load balancers and proxies in front of the gateway, which all listeners share.
+	#[serde(with = "serde_dur_option")]
+	pub mcp_sse_keep_alive: Option<Duration>,
+	/// The commands stdio MCP targets may run; other targets fail to connect. Each entry must match
+	/// the `cmd` of the target exactly: an absolute path only allows that path, and a name only
+	/// allows running that name from `PATH`. Empty by default, so no command may be run, while `*`
+	/// allows any.
+	pub stdio_command_allowlist: Vec<String>,
+	/// If set, failing to bind any of the binds present at startup stops the process with an error,
+	/// rather than continuing without it.
+	pub fatal_bind_errors: bool,
+	/// If set, admin API requests must authenticate.
+	pub admin_auth: Option<management::admin::AdminAuth>,
 }
 
 #[derive(serde::Serialize, Clone, Debug)]
@@ -171,6 +251,8 @@ pub struct XDSConfig {
 #[derive(Clone, Debug)]
 pub enum ConfigSource {
 	File(PathBuf),
+	/// Several files, merged. A directory stands for the config files in it.
+	Files(Vec<PathBuf>),
 	Static(Bytes),
 	// #[cfg(any(test, feature = "testing"))]
 	// Dynamic(Arc<tokio::sync::Mutex<MpscAckReceiver<LocalConfig>>>),
@@ -183,6 +265,9 @@ impl Serialize for ConfigSource {
 	{
 		match self {
 			ConfigSource::File(name) => serializer.serialize_str(&name.to_string_lossy()),
+			ConfigSource::Files(names) => {
+				serializer.collect_seq(names.iter().map(|n| n.to_string_lossy()))
+			},
 			ConfigSource::Static(_) => serializer.serialize_str("static"),
 		}
 	}
@@ -192,6 +277,10 @@ impl ConfigSource {
 	pub async fn read_to_string(&self) -> anyhow::Result<String> {
 		Ok(match self {
 			ConfigSource::File(path) => fs_err::tokio::read_to_string(path).await?,
+			ConfigSource::Files(paths) => {
+				let paths = paths.clone();
+				tokio::task::spawn_blocking(move || types::local::merge_files(&paths)).await??
+			},
 			ConfigSource::Static(data) => std::str::from_utf8(data).map(|s| s.to_string())?,
 			// #[cfg(any(test, feature = "testing"))]
 			// _ => "{}".to_string(),
@@ -200,6 +289,7 @@ impl ConfigSource {
 	pub fn read_to_string_sync(&self) -> anyhow::Result<String> {
 		Ok(match self {
 			ConfigSource::File(path) => fs_err::read_to_string(path)?,
+			ConfigSource::Files(paths) => types::local::merge_files(paths)?,
 			ConfigSource::Static(data) => std::str::from_utf8(data).map(|s| s.to_string())?,
 			// #[cfg(any(test, feature = "testing"))]
 			// _ => "{}".to_string(),
@@ -219,6 +309,7 @@ pub struct ProxyInputs {
 
 	mcp_state: mcp::sse::App,
 	ca: Option<Arc<CaClient>>,
+	inflight: Arc<proxy::inflight::InFlight>,
 }
 
 #[derive(Debug, Clone, Copy, serde::Serialize)]
@@ -231,6 +322,51 @@ pub enum Address {
 	SocketAddr(SocketAddr),
 }
 
+/// Where the admin server listens.
+#[derive(Debug, Clone, serde::Serialize)]
+pub enum AdminAddress {
+	Tcp(Address),
+	/// A Unix domain socket, created with the given file mode.
+	Unix {
+		path: PathBuf,
+		mode: u32,
+	},
+}
+
+impl AdminAddress {
+	/// Parses `unix:<path>` as a Unix domain socket, and anything else as a TCP address.
+	fn new(ipv6_enabled: bool, s: &str, mode: u32) -> anyhow::Result<Self> {
+		match s.strip_prefix("unix:") {
+			Some("") => anyhow::bail!("admin socket path must not be empty"),
+			Some(path) => Ok(AdminAddress::Unix {
+				path: PathBuf::from(path),
+				mode,
+			}),
+			None => Ok(AdminAddress::Tcp(Address::new(ipv6_enabled, s)?)),
+		}
+	}
+}
+
+impl AdminAddress {
+	/// Whether only local clients can reach the address.
+	pub fn is_local(&self) -> bool {
+		match self {
+			AdminAddress::Tcp(Address::Localhost(..)) => true,
+			AdminAddress::Tcp(Address::SocketAddr(s)) => s.ip().is_loopback(),
+			AdminAddress::Unix { .. } => true,
+		}
+	}
+}
+
+impl Display for AdminAddress {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			AdminAddress::Tcp(a) => write!(f, "{a}"),
+			AdminAddress::Unix { path, .. } => write!(f, "unix:{}", path.display()),
+		}
+	}
+}
+
 impl Display for Address {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match self {
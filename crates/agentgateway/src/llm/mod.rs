@@ -14,6 +14,7 @@ use tiktoken_rs::tokenizer::{Tokenizer, get_tokenizer};
 use crate::http::auth::BackendAuth;
 use crate::http::backendtls::BackendTLS;
 use crate::http::localratelimit::RateLimit;
+use crate::http::outboundproxy::OutboundProxy;
 use crate::http::{Body, Request, Response};
 use crate::llm::universal::{
 	ChatCompletionError, ChatCompletionErrorResponse, ChatCompletionResponse,
@@ -95,6 +96,7 @@ impl AIProvider {
 			backend_tls: Some(http::backendtls::SYSTEM_TRUST.clone()),
 			// We will use original request for now
 			backend_auth: None,
+			outbound_proxy: None,
 			a2a: None,
 			llm: None,
 			llm_provider: Some((self.clone(), true)),
@@ -106,6 +108,7 @@ impl AIProvider {
 				let bp = BackendPolicies {
 					backend_tls: Some(http::backendtls::SYSTEM_TRUST.clone()),
 					backend_auth: Some(BackendAuth::Gcp {}),
+					outbound_proxy: None,
 					a2a: None,
 					llm: None,
 					llm_provider: Some((self.clone(), true)),
@@ -117,6 +120,7 @@ impl AIProvider {
 				let bp = BackendPolicies {
 					backend_tls: Some(http::backendtls::SYSTEM_TRUST.clone()),
 					backend_auth: Some(BackendAuth::Aws {}),
+					outbound_proxy: None,
 					a2a: None,
 					llm: None,
 					llm_provider: Some((self.clone(), true)),
@@ -194,6 +198,7 @@ impl AIProvider {
 		&self,
 		client: client::Client,
 		policies: Option<&Policy>,
+		proxy: Option<&OutboundProxy>,
 		req: Request,
 	) -> (Result<RequestResult, AIError>) {
 		// Buffer the body, max 2mb
@@ -212,10 +217,13 @@ impl AIProvider {
 		};
 		if let Some(p) = policies {
 			let http_headers = &parts.headers;
-			if let Some(dr) = p.apply(client, &mut req, http_headers).await.map_err(|e| {
-				warn!("failed to call prompt guard webhook: {e}");
-				AIError::PromptWebhookError
-			})? {
+			if let Some(dr) = p
+				.apply(client, proxy, &mut req, http_headers)
+				.await
+				.map_err(|e| {
+					warn!("failed to call prompt guard webhook: {e}");
+					AIError::PromptWebhookError
+				})? {
 				return Ok(RequestResult::Rejected(dr));
 			}
 		}
@@ -4,6 +4,7 @@ use std::ops::Deref;
 use ::http::HeaderMap;
 use bytes::Bytes;
 
+use crate::http::outboundproxy::OutboundProxy;
 use crate::http::{PolicyResponse, Response, StatusCode};
 use crate::llm::policy::webhook::{MaskActionBody, RequestAction};
 use crate::llm::universal::{ChatCompletionMessage, ChatCompletionRequest, Content, MessageRole};
@@ -27,6 +28,7 @@ impl Policy {
 	pub async fn apply(
 		&self,
 		client: client::Client,
+		proxy: Option<&OutboundProxy>,
 		req: &mut ChatCompletionRequest,
 		http_headers: &HeaderMap,
 	) -> anyhow::Result<Option<Response>> {
@@ -34,7 +36,14 @@ impl Policy {
 			return Ok(None);
 		};
 		if let Some(webhook) = &g.webhook {
-			let whr = webhook::send_request(client.clone(), &webhook.target, http_headers, req).await?;
+			let whr = webhook::send_request(
+				client.clone(),
+				&webhook.target,
+				proxy.cloned(),
+				http_headers,
+				req,
+			)
+			.await?;
 			match whr.action {
 				RequestAction::Mask(mask) => {
 					debug!(
@@ -241,6 +250,7 @@ mod webhook {
 	use serde::{Deserialize, Serialize};
 
 	use crate::client::Client;
+	use crate::http::outboundproxy::OutboundProxy;
 	use crate::llm::universal::ChatCompletionRequest;
 	use crate::types::agent::Target;
 	use crate::*;
@@ -405,6 +415,7 @@ mod webhook {
 	pub async fn send_request(
 		client: Client,
 		target: &Target,
+		proxy: Option<OutboundProxy>,
 		http_headers: &HeaderMap,
 		req: &ChatCompletionRequest,
 	) -> anyhow::Result<GuardrailsPromptResponse> {
@@ -414,6 +425,7 @@ mod webhook {
 				req: whr,
 				target: target.clone(),
 				transport: Default::default(), // TODO: use policies
+				proxy,
 			})
 			.await?;
 		let bb = axum::body::to_bytes(res.into_body(), 2_097_152).await?;
@@ -85,3 +85,53 @@ fn test_anthropic() {
 	test_request("anthropic", "basic_input", request);
 	test_request("anthropic", "full_input", request);
 }
+
+#[tokio::test]
+async fn test_webhook_through_proxy() {
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+	// A proxy that refuses every request, so the call only fails if it went through it
+	let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let proxy_addr = listener.local_addr().unwrap();
+	let (tx, mut connects) = tokio::sync::mpsc::unbounded_channel();
+	tokio::spawn(async move {
+		while let Ok((mut stream, _)) = listener.accept().await {
+			let mut head = Vec::new();
+			while !head.ends_with(b"\r\n\r\n") {
+				head.push(stream.read_u8().await.unwrap());
+			}
+			let _ = tx.send(String::from_utf8(head).unwrap());
+			let _ = stream.write_all(b"HTTP/1.1 403 Forbidden\r\n\r\n").await;
+		}
+	});
+	let proxy: crate::http::outboundproxy::LocalOutboundProxy = serde_json::from_value(
+		serde_json::json!({"url": format!("http://{proxy_addr}"), "noProxy": []}),
+	)
+	.unwrap();
+	let proxy: OutboundProxy = proxy.try_into().unwrap();
+
+	let policy: Policy =
+		serde_json::from_str(r#"{"promptGuard": {"request": {"webhook": {"target": "127.0.0.1:9"}}}}"#)
+			.unwrap();
+	let mut req: universal::ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+		"model": "gpt-4",
+		"messages": [{"role": "user", "content": "hello"}]
+	}))
+	.unwrap();
+	let client = client::Client::new(
+		&client::Config {
+			resolver_cfg: hickory_resolver::config::ResolverConfig::default(),
+			resolver_opts: hickory_resolver::config::ResolverOpts::default(),
+		},
+		None,
+	);
+	let res = policy
+		.apply(client, Some(&proxy), &mut req, &::http::HeaderMap::new())
+		.await;
+	assert!(res.is_err());
+	let head = connects.recv().await.unwrap();
+	assert!(
+		head.starts_with("POST http://127.0.0.1:9/request HTTP/1.1\r\n"),
+		"{head}"
+	);
+}
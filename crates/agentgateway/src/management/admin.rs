@@ -10,19 +10,31 @@ use std::time::{Duration, SystemTime};
 use agent_core::drain::DrainWatcher;
 use agent_core::version::BuildInfo;
 use agent_core::{signal, telemetry};
+use anyhow::Context;
+use axum::response::IntoResponse;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use base64::engine::general_purpose::STANDARD;
 use bytes::Bytes;
+use futures_util::StreamExt;
 use http_body_util::Full;
 use hyper::Request;
 use hyper::body::Incoming;
 use hyper::header::{CONTENT_TYPE, HeaderValue};
+use secrecy::{ExposeSecret, SecretString};
+use tokio::sync::broadcast;
 use tokio::time;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 use tracing::{error, info, warn};
 use tracing_subscriber::filter;
 
 use super::hyper_helpers::{Server, empty_response, plaintext_response};
-use crate::Config;
+use crate::client;
 use crate::http::Response;
+use crate::http::jwt::{Jwt, LocalJwtConfig};
+use crate::mcp::relay::status::{Registry as TargetStatusRegistry, TargetEvent};
+use crate::serdes::{deser_key_from_file_option, ser_redact};
+use crate::{AdminAddress, Config};
 
 pub trait ConfigDumpHandler: Sync + Send {
 	fn key(&self) -> &'static str;
@@ -41,12 +53,117 @@ pub trait AdminFallback: Sync + Send {
 	fn handle(&self, req: http::Request<Incoming>) -> AdminResponse;
 }
 
+/// Authentication for the admin API. Requests present a bearer token, which must match the static
+/// token or be a valid JWT for the configured provider.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(crate::JsonSchema))]
+pub struct AdminAuth {
+	#[cfg_attr(feature = "schema", schemars(with = "Option<crate::serdes::FileOrInline>"))]
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		serialize_with = "ser_redact",
+		deserialize_with = "deser_key_from_file_option"
+	)]
+	token: Option<SecretString>,
+	#[serde(default, skip_serializing)]
+	jwt: Option<LocalJwtConfig>,
+	/// If set, read-only (GET) requests do not need to authenticate. Mutating requests always do.
+	#[serde(default)]
+	public_reads: bool,
+}
+
+impl AdminAuth {
+	pub fn validate(&self) -> anyhow::Result<()> {
+		if self.token.is_none() && self.jwt.is_none() {
+			anyhow::bail!("adminAuth requires a token or jwt");
+		}
+		if self
+			.token
+			.as_ref()
+			.is_some_and(|t| t.expose_secret().is_empty())
+		{
+			anyhow::bail!("adminAuth token must not be empty");
+		}
+		Ok(())
+	}
+}
+
+struct Authenticator {
+	token: Option<SecretString>,
+	jwt: Option<Jwt>,
+	public_reads: bool,
+}
+
+impl Authenticator {
+	async fn new(auth: &AdminAuth, client: client::Client) -> anyhow::Result<Self> {
+		let jwt = match &auth.jwt {
+			Some(jwt) => Some(
+				jwt
+					.clone()
+					.try_into(client)
+					.await
+					.context("admin jwt authentication")?,
+			),
+			None => None,
+		};
+		Ok(Authenticator {
+			token: auth.token.clone(),
+			jwt,
+			public_reads: auth.public_reads,
+		})
+	}
+
+	fn allows(&self, method: &hyper::Method, headers: &hyper::HeaderMap) -> bool {
+		// CORS preflights never carry credentials, and do not act on anything
+		if method == hyper::Method::OPTIONS {
+			return true;
+		}
+		if self.public_reads && (method == hyper::Method::GET || method == hyper::Method::HEAD) {
+			return true;
+		}
+		let Some(token) = headers
+			.get(hyper::header::AUTHORIZATION)
+			.and_then(|h| h.to_str().ok())
+			.and_then(|h| h.strip_prefix("Bearer "))
+		else {
+			return false;
+		};
+		if let Some(expected) = &self.token
+			&& constant_time_eq(expected.expose_secret().as_bytes(), token.as_bytes())
+		{
+			return true;
+		}
+		self
+			.jwt
+			.as_ref()
+			.is_some_and(|jwt| jwt.validate_claims(token).is_ok())
+	}
+}
+
+/// Compares secrets without leaking, through timing, how much of them matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn unauthorized_response() -> Response {
+	let mut response = plaintext_response(hyper::StatusCode::UNAUTHORIZED, "unauthorized\n".into());
+	response.headers_mut().insert(
+		hyper::header::WWW_AUTHENTICATE,
+		HeaderValue::from_static("Bearer"),
+	);
+	response
+}
+
 struct State {
 	stores: crate::store::Stores,
 	config: Arc<Config>,
 	shutdown_trigger: signal::ShutdownTrigger,
 	config_dump_handlers: Vec<Arc<dyn ConfigDumpHandler>>,
 	admin_fallback: Option<Arc<dyn AdminFallback>>,
+	target_status: Arc<TargetStatusRegistry>,
+	auth: Option<Authenticator>,
 }
 
 pub struct Service {
@@ -62,6 +179,59 @@ pub struct ConfigDump {
 	config: Arc<Config>,
 }
 
+/// Summary of what this gateway build and configuration supports, to help debug compatibility
+/// issues between clients and the gateway.
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+	version: BuildInfo,
+	listener_protocols: Vec<String>,
+	backend_types: Vec<&'static str>,
+	mcp_target_types: Vec<&'static str>,
+	authentication: Vec<&'static str>,
+	protocol_versions: ProtocolVersions,
+	a2a_methods: &'static [&'static str],
+	xds: bool,
+	features: Vec<&'static str>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtocolVersions {
+	mcp: &'static [rmcp::model::ProtocolVersion],
+}
+
+impl Capabilities {
+	pub fn new(config: &Config) -> Self {
+		let mut features = vec![];
+		if cfg!(feature = "tls-ring") {
+			features.push("tls-ring");
+		}
+		if cfg!(feature = "jemalloc") {
+			features.push("jemalloc");
+		}
+		if cfg!(feature = "ui") {
+			features.push("ui");
+		}
+		if cfg!(feature = "schema") {
+			features.push("schema");
+		}
+		Capabilities {
+			version: BuildInfo::new(),
+			listener_protocols: crate::types::local::listener_protocols(),
+			backend_types: crate::types::local::backend_types(),
+			mcp_target_types: crate::serdes::variants::<crate::types::agent::McpTargetSpec>().to_vec(),
+			authentication: crate::types::local::authentication_policies(),
+			protocol_versions: ProtocolVersions {
+				mcp: crate::mcp::relay::SUPPORTED_PROTOCOL_VERSIONS,
+			},
+			a2a_methods: a2a_sdk::A2aRequest::METHODS,
+			xds: config.xds.address.is_some(),
+			features,
+		}
+	}
+}
+
 #[derive(serde::Serialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct CertDump {
@@ -88,24 +258,37 @@ impl Service {
 		shutdown_trigger: signal::ShutdownTrigger,
 		drain_rx: DrainWatcher,
 	) -> anyhow::Result<Self> {
-		Server::<State>::bind(
-			"admin",
-			config.admin_addr,
-			drain_rx,
-			State {
-				config,
-				stores,
-				shutdown_trigger,
-				config_dump_handlers: vec![],
-				admin_fallback: None,
+		let addr = config.admin_addr.clone();
+		let auth = match &config.admin_auth {
+			Some(auth) => Some(Authenticator::new(auth, client::Client::new(&config.dns, None)).await?),
+			None => None,
+		};
+		let state = State {
+			config,
+			stores,
+			shutdown_trigger,
+			config_dump_handlers: vec![],
+			admin_fallback: None,
+			target_status: Default::default(),
+			auth,
+		};
+		match addr {
+			AdminAddress::Tcp(addr) => Server::<State>::bind("admin", addr, drain_rx, state).await,
+			#[cfg(unix)]
+			AdminAddress::Unix { path, mode } => {
+				Server::<State>::bind_unix("admin", &path, mode, drain_rx, state).await
+			},
+			#[cfg(not(unix))]
+			AdminAddress::Unix { .. } => {
+				anyhow::bail!("serving the admin API over a Unix domain socket is not supported")
 			},
-		)
-		.await
+		}
 		.map(|s| Service { s })
 	}
 
-	pub fn address(&self) -> SocketAddr {
-		self.s.address()
+	/// The TCP address the admin server listens on, or None if it listens on a Unix domain socket.
+	pub fn address(&self) -> Option<SocketAddr> {
+		self.s.tcp_address()
 	}
 
 	pub fn add_config_dump_handler(&mut self, handler: Arc<dyn ConfigDumpHandler>) {
@@ -116,8 +299,19 @@ impl Service {
 		self.s.state_mut().admin_fallback = Some(handler);
 	}
 
+	/// Sets where the connection state reported by `/backends/{backend}/targets/{name}/status` is
+	/// read from.
+	pub fn set_target_status(&mut self, status: Arc<TargetStatusRegistry>) {
+		self.s.state_mut().target_status = status;
+	}
+
 	pub fn spawn(self) {
 		self.s.spawn(|state, req| async move {
+			if let Some(auth) = &state.auth
+				&& !auth.allows(req.method(), req.headers())
+			{
+				return Ok(unauthorized_response());
+			}
 			match req.uri().path() {
 				#[cfg(target_os = "linux")]
 				"/debug/pprof/profile" => handle_pprof(req).await,
@@ -143,6 +337,12 @@ impl Service {
 					.await
 				},
 				"/logging" => Ok(handle_logging(req).await),
+				"/capabilities" => handle_capabilities(req, Capabilities::new(&state.config)).await,
+				"/openapi.json" => handle_openapi(req).await,
+				p if target_status_route(p).is_some() => {
+					handle_target_status(req, &state.target_status).await
+				},
+				"/events" => handle_events(req, &state.target_status).await,
 				_ => {
 					if let Some(h) = &state.admin_fallback {
 						Ok(h.handle(req).await)
@@ -170,6 +370,11 @@ async fn handle_dashboard(_req: Request<Incoming>) -> Response {
 		("quitquitquit", "shut down the server"),
 		("config_dump", "dump the current agentgateway configuration"),
 		("logging", "query/changing logging levels"),
+		(
+			"capabilities",
+			"summary of the protocols, backends, and features this gateway supports",
+		),
+		("events", "stream MCP target connection events"),
 	];
 
 	let mut api_rows = String::new();
@@ -265,6 +470,105 @@ async fn handle_config_dump(
 	)
 }
 
+async fn handle_capabilities(
+	req: Request<Incoming>,
+	capabilities: Capabilities,
+) -> anyhow::Result<Response> {
+	if req.method() != hyper::Method::GET {
+		return Ok(empty_response(hyper::StatusCode::METHOD_NOT_ALLOWED));
+	}
+	let body = serde_json::to_string_pretty(&capabilities)?;
+	Ok(
+		::http::Response::builder()
+			.status(hyper::StatusCode::OK)
+			.header(hyper::header::CONTENT_TYPE, "application/json")
+			.body(body.into())
+			.expect("builder with known status code should not fail"),
+	)
+}
+
+async fn handle_openapi(req: Request<Incoming>) -> anyhow::Result<Response> {
+	if req.method() != hyper::Method::GET {
+		return Ok(empty_response(hyper::StatusCode::METHOD_NOT_ALLOWED));
+	}
+	let body = serde_json::to_string_pretty(&super::openapi::document())?;
+	Ok(
+		::http::Response::builder()
+			.status(hyper::StatusCode::OK)
+			.header(hyper::header::CONTENT_TYPE, "application/json")
+			.body(body.into())
+			.expect("builder with known status code should not fail"),
+	)
+}
+
+async fn handle_target_status(
+	req: Request<Incoming>,
+	registry: &TargetStatusRegistry,
+) -> anyhow::Result<Response> {
+	if req.method() != hyper::Method::GET {
+		return Ok(empty_response(hyper::StatusCode::METHOD_NOT_ALLOWED));
+	}
+	target_status(req.uri().path(), registry)
+}
+
+/// Splits a `/backends/{backend}/targets/{name}/status` path into the backend and target names.
+fn target_status_route(path: &str) -> Option<(&str, &str)> {
+	match path.split('/').collect::<Vec<_>>()[..] {
+		["", "backends", backend, "targets", name, "status"]
+			if !backend.is_empty() && !name.is_empty() =>
+		{
+			Some((backend, name))
+		},
+		_ => None,
+	}
+}
+
+/// Reports the connection state of the MCP target named by a
+/// `/backends/{backend}/targets/{name}/status` path.
+fn target_status(path: &str, registry: &TargetStatusRegistry) -> anyhow::Result<Response> {
+	let Some((backend, name)) = target_status_route(path) else {
+		return Ok(empty_response(hyper::StatusCode::NOT_FOUND));
+	};
+	let Some(status) = registry.status(backend, name) else {
+		return Ok(plaintext_response(
+			hyper::StatusCode::NOT_FOUND,
+			format!("target {name} of backend {backend} has not been connected to\n"),
+		));
+	};
+	let body = serde_json::to_string_pretty(&status)?;
+	Ok(
+		::http::Response::builder()
+			.status(hyper::StatusCode::OK)
+			.header(hyper::header::CONTENT_TYPE, "application/json")
+			.body(body.into())
+			.expect("builder with known status code should not fail"),
+	)
+}
+
+async fn handle_events(
+	req: Request<Incoming>,
+	registry: &TargetStatusRegistry,
+) -> anyhow::Result<Response> {
+	if req.method() != hyper::Method::GET {
+		return Ok(empty_response(hyper::StatusCode::METHOD_NOT_ALLOWED));
+	}
+	Ok(target_events(registry.subscribe()))
+}
+
+/// Streams target events as server-sent events, named after the type of the event. A subscriber
+/// that falls behind gets a `lagged` event with the number of events it missed.
+fn target_events(events: broadcast::Receiver<TargetEvent>) -> Response {
+	let stream = BroadcastStream::new(events).map(|e| match e {
+		Ok(e) => Event::default().event(e.kind.as_str()).json_data(&e),
+		Err(BroadcastStreamRecvError::Lagged(missed)) => Event::default()
+			.event("lagged")
+			.json_data(serde_json::json!({ "missed": missed })),
+	});
+	Sse::new(stream)
+		.keep_alive(KeepAlive::default())
+		.into_response()
+}
+
 // mirror envoy's behavior: https://www.envoyproxy.io/docs/envoy/latest/operations/admin#post--logging
 // NOTE: multiple query parameters is not supported, for example
 // curl -X POST http://127.0.0.1:15000/logging?"tap=debug&router=debug"
@@ -397,3 +701,7 @@ fn base64_encode(data: String) -> String {
 	use base64::Engine;
 	STANDARD.encode(data)
 }
+
+#[cfg(test)]
+#[path = "admin_tests.rs"]
+mod tests;
@@ -0,0 +1,350 @@
+use http_body_util::BodyExt;
+
+use super::*;
+
+#[test]
+fn test_capabilities() {
+	let config = crate::config::parse_config("{}".to_string(), None).unwrap();
+	let caps = serde_json::to_value(Capabilities::new(&config)).unwrap();
+
+	assert_eq!(
+		caps["mcpTargetTypes"],
+		serde_json::json!(["sse", "mcp", "stdio", "openapi", "grpc"])
+	);
+	assert_eq!(
+		caps["backendTypes"],
+		serde_json::json!(["service", "host", "dynamic", "mcp", "ai"])
+	);
+	assert_eq!(
+		caps["authentication"],
+		serde_json::json!(["mcpAuthentication", "backendAuth", "jwtAuth", "extAuthz"])
+	);
+	assert_eq!(
+		caps["listenerProtocols"],
+		serde_json::json!(["http", "https", "tls", "tcp", "hbone"])
+	);
+	assert_eq!(
+		caps["protocolVersions"]["mcp"],
+		serde_json::json!(["2025-03-26", "2024-11-05"])
+	);
+	assert!(
+		caps["a2aMethods"]
+			.as_array()
+			.unwrap()
+			.contains(&"message/stream".into())
+	);
+	assert_eq!(caps["xds"], false);
+	assert_eq!(
+		caps["features"]
+			.as_array()
+			.unwrap()
+			.contains(&"tls-ring".into()),
+		cfg!(feature = "tls-ring")
+	);
+}
+
+#[tokio::test]
+async fn test_target_status() {
+	let registry = Arc::new(TargetStatusRegistry::default());
+	let path = "/backends/backend/targets/everything/status";
+	let res = target_status(path, &registry).unwrap();
+	assert_eq!(res.status(), hyper::StatusCode::NOT_FOUND);
+
+	let _conn = registry.connected("backend".into(), "everything".into(), Some(7));
+	// Only the exact route shape is served
+	for path in [
+		"/backends/backend/targets/everything",
+		"/backends/backend/targets/everything/status/extra",
+		"/backends//targets/everything/status",
+		"/targets/everything/status",
+	] {
+		assert!(target_status_route(path).is_none(), "{path}");
+	}
+	let res = target_status("/backends/other/targets/everything/status", &registry).unwrap();
+	assert_eq!(res.status(), hyper::StatusCode::NOT_FOUND);
+	let res = target_status(path, &registry).unwrap();
+	assert_eq!(res.status(), hyper::StatusCode::OK);
+	let body = res.into_body().collect().await.unwrap().to_bytes();
+	let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	assert_eq!(status["backend"], "backend");
+	assert_eq!(status["name"], "everything");
+	assert_eq!(status["connected"], true);
+	assert_eq!(status["connections"][0]["pid"], 7);
+}
+
+#[tokio::test]
+async fn test_target_events() {
+	let registry = Arc::new(TargetStatusRegistry::default());
+	let res = target_events(registry.subscribe());
+	assert_eq!(res.headers()[CONTENT_TYPE], "text/event-stream");
+	let mut body = res.into_body();
+
+	let conn = registry.connected("backend".into(), "everything".into(), Some(7));
+	registry.error(
+		"backend".into(),
+		&"everything".into(),
+		"connection refused".to_string(),
+	);
+	conn.exited("exit status: 1".to_string());
+	drop(conn);
+	drop(registry.connected("backend".into(), "everything".into(), None));
+
+	let mut events = vec![];
+	while events.len() < 4 {
+		let frame = body.frame().await.unwrap().unwrap().into_data().unwrap();
+		let frame = String::from_utf8(frame.to_vec()).unwrap();
+		let (kind, data) = frame
+			.trim_end()
+			.split_once('\n')
+			.expect("event and data lines");
+		let data: serde_json::Value =
+			serde_json::from_str(data.strip_prefix("data: ").unwrap()).unwrap();
+		assert_eq!(kind, format!("event: {}", data["type"].as_str().unwrap()));
+		assert_eq!(data["target"], "everything");
+		events.push(data);
+	}
+	assert_eq!(events[0]["type"], "connected");
+	assert_eq!(events[0]["pid"], 7);
+	assert_eq!(events[1]["type"], "error");
+	assert_eq!(events[1]["message"], "connection refused");
+	// The exited connection is not reported again as disconnected when dropped
+	assert_eq!(events[2]["type"], "exited");
+	assert_eq!(events[2]["message"], "exit status: 1");
+	assert_eq!(events[3]["type"], "connected");
+	assert!(events[3].get("pid").is_none());
+	let frame = body.frame().await.unwrap().unwrap().into_data().unwrap();
+	assert!(frame.starts_with(b"event: disconnected\n"));
+}
+
+#[tokio::test]
+async fn test_target_events_lagged() {
+	let registry = Arc::new(TargetStatusRegistry::default());
+	let mut body = target_events(registry.subscribe()).into_body();
+	for i in 0..300 {
+		registry.error("backend".into(), &"everything".into(), format!("error {i}"));
+	}
+	// The oldest events are dropped for a subscriber that fell behind
+	let frame = body.frame().await.unwrap().unwrap().into_data().unwrap();
+	assert_eq!(
+		String::from_utf8(frame.to_vec()).unwrap(),
+		"event: lagged\ndata: {\"missed\":44}\n\n"
+	);
+	let frame = body.frame().await.unwrap().unwrap().into_data().unwrap();
+	assert!(
+		String::from_utf8(frame.to_vec())
+			.unwrap()
+			.contains("error 44")
+	);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_admin_over_unix_socket() {
+	use std::os::unix::fs::PermissionsExt;
+
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("admin.sock");
+	// A socket left behind by a previous run is replaced
+	drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+	let mut config = crate::config::parse_config("{}".to_string(), None).unwrap();
+	config.admin_addr = AdminAddress::Unix {
+		path: path.clone(),
+		mode: 0o600,
+	};
+	let shutdown = signal::Shutdown::new();
+	let (drain_tx, drain_rx) = agent_core::drain::new();
+	let admin = Service::new(
+		Arc::new(config),
+		crate::store::Stores::new(),
+		shutdown.trigger(),
+		drain_rx,
+	)
+	.await
+	.unwrap();
+	assert!(admin.address().is_none());
+	admin.spawn();
+	let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+	assert_eq!(mode & 0o777, 0o600);
+	// The private directory the socket was created in is removed
+	let entries = std::fs::read_dir(dir.path()).unwrap().count();
+	assert_eq!(entries, 1);
+
+	let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+	let (mut sender, conn) =
+		hyper::client::conn::http1::handshake(hyper_util::rt::TokioIo::new(stream))
+			.await
+			.unwrap();
+	tokio::spawn(conn);
+	let req = ::http::Request::builder()
+		.uri("/capabilities")
+		.header(hyper::header::HOST, "localhost")
+		.body(http_body_util::Empty::<Bytes>::new())
+		.unwrap();
+	let res = sender.send_request(req).await.unwrap();
+	assert_eq!(res.status(), hyper::StatusCode::OK);
+	let body = res.into_body().collect().await.unwrap().to_bytes();
+	let caps: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	assert_eq!(caps["xds"], false);
+	drop(sender);
+
+	// The socket is removed once the server drains
+	drain_tx
+		.start_drain_and_wait(agent_core::drain::DrainMode::Graceful)
+		.await;
+	assert!(!path.exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket_does_not_replace_files() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("admin.sock");
+	std::fs::write(&path, "not a socket").unwrap();
+	let mut config = crate::config::parse_config("{}".to_string(), None).unwrap();
+	config.admin_addr = AdminAddress::Unix {
+		path: path.clone(),
+		mode: 0o600,
+	};
+	let (_drain_tx, drain_rx) = agent_core::drain::new();
+	let res = Service::new(
+		Arc::new(config),
+		crate::store::Stores::new(),
+		signal::Shutdown::new().trigger(),
+		drain_rx,
+	)
+	.await;
+	assert!(res.is_err());
+	assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_openapi_routes() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("admin.sock");
+	let mut config = crate::config::parse_config("{}".to_string(), None).unwrap();
+	config.admin_addr = AdminAddress::Unix {
+		path: path.clone(),
+		mode: 0o600,
+	};
+	let (_drain_tx, drain_rx) = agent_core::drain::new();
+	let admin = Service::new(
+		Arc::new(config),
+		crate::store::Stores::new(),
+		signal::Shutdown::new().trigger(),
+		drain_rx,
+	)
+	.await
+	.unwrap();
+	admin.spawn();
+
+	let get = |uri: &str| {
+		let (path, uri) = (path.clone(), uri.to_string());
+		async move {
+			let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+			let (mut sender, conn) =
+				hyper::client::conn::http1::handshake(hyper_util::rt::TokioIo::new(stream))
+					.await
+					.unwrap();
+			tokio::spawn(conn);
+			let req = ::http::Request::builder()
+				.uri(uri)
+				.header(hyper::header::HOST, "localhost")
+				.body(http_body_util::Empty::<Bytes>::new())
+				.unwrap();
+			let res = sender.send_request(req).await.unwrap();
+			let status = res.status();
+			(status, res.into_body().collect().await.unwrap().to_bytes())
+		}
+	};
+	let (status, body) = get("/openapi.json").await;
+	assert_eq!(status, hyper::StatusCode::OK);
+	let doc: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+	// Every documented read is served by the admin server. The configuration routes are served by
+	// the UI, which is not set up here.
+	for (p, item) in doc["paths"].as_object().unwrap() {
+		if item.get("get").is_none() || p.contains('{') || p.starts_with("/config") {
+			continue;
+		}
+		// Event streams do not end, so they cannot be read to the end
+		if item["get"]["responses"]["200"]["content"]
+			.get("text/event-stream")
+			.is_some()
+		{
+			continue;
+		}
+		assert_eq!(get(p).await.0, hyper::StatusCode::OK, "{p}");
+	}
+}
+
+fn authenticator(public_reads: bool) -> Authenticator {
+	Authenticator {
+		token: Some(SecretString::from("s3cret".to_string())),
+		jwt: None,
+		public_reads,
+	}
+}
+
+fn bearer(token: &str) -> hyper::HeaderMap {
+	let mut headers = hyper::HeaderMap::new();
+	headers.insert(
+		hyper::header::AUTHORIZATION,
+		HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+	);
+	headers
+}
+
+#[test]
+fn test_admin_auth() {
+	let none = hyper::HeaderMap::new();
+	let auth = authenticator(false);
+	assert!(auth.allows(&hyper::Method::POST, &bearer("s3cret")));
+	assert!(auth.allows(&hyper::Method::GET, &bearer("s3cret")));
+	assert!(!auth.allows(&hyper::Method::POST, &bearer("wrong")));
+	assert!(!auth.allows(&hyper::Method::POST, &bearer("s3cret2")));
+	assert!(!auth.allows(&hyper::Method::POST, &none));
+	assert!(!auth.allows(&hyper::Method::GET, &none));
+	assert!(auth.allows(&hyper::Method::OPTIONS, &none));
+
+	let auth = authenticator(true);
+	assert!(auth.allows(&hyper::Method::GET, &none));
+	assert!(!auth.allows(&hyper::Method::POST, &none));
+	assert!(!auth.allows(&hyper::Method::DELETE, &bearer("wrong")));
+
+	assert_eq!(
+		unauthorized_response().status(),
+		hyper::StatusCode::UNAUTHORIZED
+	);
+}
+
+#[test]
+fn test_admin_auth_config() {
+	// Exposing the admin API beyond localhost requires authentication
+	let res = crate::config::parse_config(
+		r#"{"config": {"adminAddr": "0.0.0.0:15000"}}"#.to_string(),
+		None,
+	);
+	assert!(res.is_err());
+	let config = crate::config::parse_config(
+		r#"{"config": {"adminAddr": "0.0.0.0:15000", "adminAuth": {"token": "s3cret"}}}"#.to_string(),
+		None,
+	)
+	.unwrap();
+	assert!(config.admin_auth.is_some());
+	// The token is never dumped
+	let dumped = serde_json::to_string(&config).unwrap();
+	assert!(!dumped.contains("s3cret"));
+
+	assert!(
+		crate::config::parse_config(
+			r#"{"config": {"adminAddr": "127.0.0.1:15000"}}"#.to_string(),
+			None
+		)
+		.is_ok()
+	);
+	assert!(
+		crate::config::parse_config(r#"{"config": {"adminAuth": {}}}"#.to_string(), None).is_err()
+	);
+}
@@ -3,6 +3,8 @@
 use std::convert::Infallible;
 use std::future::Future;
 use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
@@ -17,6 +19,7 @@ use hyper::server::conn::{http1, http2};
 use hyper::{Request, client};
 use hyper_util::client::legacy::connect::HttpConnector;
 use hyper_util::rt::{TokioExecutor, TokioTimer};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream};
 use tokio_stream::Stream;
 use tracing::{Instrument, debug, info, warn};
@@ -105,11 +108,77 @@ pub fn plaintext_response(code: hyper::StatusCode, body: String) -> Response {
 /// * Draining
 pub struct Server<S> {
 	name: String,
-	binds: Vec<TcpListener>,
+	binds: Vec<Listener>,
 	drain_rx: DrainWatcher,
 	state: S,
 }
 
+enum Listener {
+	Tcp(TcpListener),
+	// The socket file is removed once the listener is drained.
+	#[cfg(unix)]
+	Unix(tokio::net::UnixListener, PathBuf),
+}
+
+impl Listener {
+	fn describe(&self) -> String {
+		match self {
+			Listener::Tcp(l) => l
+				.local_addr()
+				.map(|a| a.to_string())
+				.unwrap_or_else(|_| "unknown".to_string()),
+			#[cfg(unix)]
+			Listener::Unix(_, path) => format!("unix:{}", path.display()),
+		}
+	}
+}
+
+/// Removes a socket file left behind by a previous process that did not shut down cleanly, which
+/// would otherwise make the bind fail. Anything other than a socket is left alone.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> anyhow::Result<()> {
+	use std::os::unix::fs::FileTypeExt;
+	match std::fs::symlink_metadata(path) {
+		Ok(m) if m.file_type().is_socket() => Ok(std::fs::remove_file(path)?),
+		Ok(_) => anyhow::bail!("{} exists and is not a socket", path.display()),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+		Err(e) => Err(e.into()),
+	}
+}
+
+/// Binds a Unix domain socket at `path` that is only ever reachable with the given file mode. The
+/// socket is created in a private directory next to `path`, given its mode there, and then moved
+/// into place.
+#[cfg(unix)]
+fn bind_private_socket(path: &Path, mode: u32) -> anyhow::Result<tokio::net::UnixListener> {
+	use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+	let name = path
+		.file_name()
+		.ok_or_else(|| anyhow::anyhow!("{} is not a socket path", path.display()))?;
+	let parent = path
+		.parent()
+		.filter(|p| !p.as_os_str().is_empty())
+		.unwrap_or(Path::new("."));
+	let dir = parent.join(format!(
+		".{}.{}",
+		name.to_string_lossy(),
+		std::process::id()
+	));
+	// A directory left behind by a process with the same pid is stale
+	let _ = std::fs::remove_dir_all(&dir);
+	std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+	let private = dir.join(name);
+	let bind = || -> anyhow::Result<_> {
+		let listener = tokio::net::UnixListener::bind(&private)?;
+		std::fs::set_permissions(&private, std::fs::Permissions::from_mode(mode))?;
+		std::fs::rename(&private, path)?;
+		Ok(listener)
+	};
+	let res = bind();
+	let _ = std::fs::remove_dir_all(&dir);
+	res
+}
+
 impl<S> Server<S> {
 	pub async fn bind(
 		name: &str,
@@ -119,7 +188,7 @@ impl<S> Server<S> {
 	) -> anyhow::Result<Self> {
 		let mut binds = vec![];
 		for addr in addrs.into_iter() {
-			binds.push(TcpListener::bind(&addr).await?)
+			binds.push(Listener::Tcp(TcpListener::bind(&addr).await?))
 		}
 		Ok(Server {
 			name: name.to_string(),
@@ -129,13 +198,36 @@ impl<S> Server<S> {
 		})
 	}
 
+	/// Binds to a Unix domain socket, restricting access to it with the given file mode.
+	#[cfg(unix)]
+	pub async fn bind_unix(
+		name: &str,
+		path: &Path,
+		mode: u32,
+		drain_rx: DrainWatcher,
+		s: S,
+	) -> anyhow::Result<Self> {
+		remove_stale_socket(path)?;
+		let listener = bind_private_socket(path, mode)?;
+		Ok(Server {
+			name: name.to_string(),
+			binds: vec![Listener::Unix(listener, path.to_path_buf())],
+			drain_rx,
+			state: s,
+		})
+	}
+
+	/// The address of the first TCP listener, if the server listens on TCP.
+	pub fn tcp_address(&self) -> Option<SocketAddr> {
+		self.binds.iter().find_map(|l| match l {
+			Listener::Tcp(l) => Some(l.local_addr().expect("local address must be ready")),
+			#[cfg(unix)]
+			Listener::Unix(..) => None,
+		})
+	}
+
 	pub fn address(&self) -> SocketAddr {
-		self
-			.binds
-			.first()
-			.expect("must have at least one address")
-			.local_addr()
-			.expect("local address must be ready")
+		self.tcp_address().expect("must have at least one address")
 	}
 
 	pub fn state_mut(&mut self) -> &mut S {
@@ -149,62 +241,52 @@ impl<S> Server<S> {
 		R: Future<Output = Result<crate::http::Response, anyhow::Error>> + Send + 'static,
 	{
 		use futures_util::StreamExt as OtherStreamExt;
-		let address = self.address();
 		let drain = self.drain_rx;
 		let state = Arc::new(self.state);
 		let f = Arc::new(f);
-		info!(
-				%address,
-				component=self.name,
-				"listener established",
-		);
 		for bind in self.binds {
+			let address = bind.describe();
+			info!(
+					%address,
+					component=self.name,
+					"listener established",
+			);
 			let drain_stream = drain.clone();
 			let drain_connections = drain.clone();
 			let state = state.clone();
 			let name = self.name.clone();
 			let f = f.clone();
 			tokio::spawn(async move {
-				let stream = tokio_stream::wrappers::TcpListenerStream::new(bind);
-				let mut stream = stream.take_until(Box::pin(drain_stream.wait_for_drain()));
-				while let Some(Ok(socket)) = stream.next().await {
-					socket.set_nodelay(true).unwrap();
-					let drain = drain_connections.clone();
-					let f = f.clone();
-					let state = state.clone();
-					tokio::spawn(async move {
-						let serve = http1_server()
-							.half_close(true)
-							.header_read_timeout(Duration::from_secs(2))
-							.max_buf_size(8 * 1024)
-							.serve_connection(
-								hyper_util::rt::TokioIo::new(socket),
-								hyper::service::service_fn(move |req| {
-									let state = state.clone();
-
-									// Failures would abort the whole connection; we just want to return an HTTP error
-									f(state, req).or_else(|err| async move {
-										Ok::<_, Infallible>(
-											::http::Response::builder()
-												.status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
-												.body(crate::http::Body::new(err.to_string()))
-												.expect("builder with known status code should not fail"),
-										)
-									})
-								}),
-							);
-						// Wait for drain to signal or connection serving to complete
-						match futures_util::future::select(Box::pin(drain.wait_for_drain()), serve).await {
-							// We got a shutdown request. Start gracful shutdown and wait for the pending requests to complete.
-							futures_util::future::Either::Left((_shutdown, mut serve)) => {
-								let drain = std::pin::Pin::new(&mut serve);
-								drain.graceful_shutdown();
-								serve.await
-							},
-							// Serving finished, just return the result.
-							futures_util::future::Either::Right((serve, _shutdown)) => serve,
+				match bind {
+					Listener::Tcp(bind) => {
+						let stream = tokio_stream::wrappers::TcpListenerStream::new(bind);
+						let mut stream = stream.take_until(Box::pin(drain_stream.wait_for_drain()));
+						while let Some(Ok(socket)) = stream.next().await {
+							socket.set_nodelay(true).unwrap();
+							tokio::spawn(serve_connection(
+								socket,
+								drain_connections.clone(),
+								state.clone(),
+								f.clone(),
+							));
 						}
-					});
+					},
+					#[cfg(unix)]
+					Listener::Unix(bind, path) => {
+						let stream = tokio_stream::wrappers::UnixListenerStream::new(bind);
+						let mut stream = stream.take_until(Box::pin(drain_stream.wait_for_drain()));
+						while let Some(Ok(socket)) = stream.next().await {
+							tokio::spawn(serve_connection(
+								socket,
+								drain_connections.clone(),
+								state.clone(),
+								f.clone(),
+							));
+						}
+						if let Err(e) = std::fs::remove_file(&path) {
+							warn!(path=%path.display(), "failed to remove socket: {e}");
+						}
+					},
 				}
 				info!(
 						%address,
@@ -215,3 +297,43 @@ impl<S> Server<S> {
 		}
 	}
 }
+
+async fn serve_connection<S, F, R, IO>(socket: IO, drain: DrainWatcher, state: Arc<S>, f: Arc<F>)
+where
+	S: Send + Sync + 'static,
+	F: Fn(Arc<S>, Request<hyper::body::Incoming>) -> R + Send + Sync + 'static,
+	R: Future<Output = Result<crate::http::Response, anyhow::Error>> + Send + 'static,
+	IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+	let serve = http1_server()
+		.half_close(true)
+		.header_read_timeout(Duration::from_secs(2))
+		.max_buf_size(8 * 1024)
+		.serve_connection(
+			hyper_util::rt::TokioIo::new(socket),
+			hyper::service::service_fn(move |req| {
+				let state = state.clone();
+
+				// Failures would abort the whole connection; we just want to return an HTTP error
+				f(state, req).or_else(|err| async move {
+					Ok::<_, Infallible>(
+						::http::Response::builder()
+							.status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+							.body(crate::http::Body::new(err.to_string()))
+							.expect("builder with known status code should not fail"),
+					)
+				})
+			}),
+		);
+	// Wait for drain to signal or connection serving to complete
+	let _ = match futures_util::future::select(Box::pin(drain.wait_for_drain()), serve).await {
+		// We got a shutdown request. Start gracful shutdown and wait for the pending requests to complete.
+		futures_util::future::Either::Left((_shutdown, mut serve)) => {
+			let drain = std::pin::Pin::new(&mut serve);
+			drain.graceful_shutdown();
+			serve.await
+		},
+		// Serving finished, just return the result.
+		futures_util::future::Either::Right((serve, _shutdown)) => serve,
+	};
+}
@@ -3,3 +3,4 @@ pub mod metrics_server;
 pub mod readiness_server;
 
 mod hyper_helpers;
+mod openapi;
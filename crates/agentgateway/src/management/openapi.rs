@@ -0,0 +1,365 @@
+use serde_json::{Value, json};
+
+/// The OpenAPI 3.0 description of the admin API, served at `/openapi.json`. The debug endpoints and
+/// the dashboard are not described.
+///
+/// This is maintained by hand: when adding or changing a route in `admin.rs` or `ui.rs`, update it
+/// here too. The tests check the schemas against the serialized types.
+pub fn document() -> Value {
+	let mut paths = json!({
+		"/config_dump": {
+			"get": {
+				"operationId": "getConfigDump",
+				"summary": "Dump the running configuration and the state of the stores",
+				"responses": {
+					"200": json_response("The configuration dump", json!({"type": "object"})),
+					"500": text_error(),
+				},
+			},
+		},
+		"/capabilities": {
+			"get": {
+				"operationId": "getCapabilities",
+				"summary": "Report the protocols, backend types and features supported by this build",
+				"responses": {
+					"200": json_response("The capabilities", schema_ref("Capabilities")),
+				},
+			},
+		},
+		"/backends/{backend}/targets/{name}/status": {
+			"get": {
+				"operationId": "getTargetStatus",
+				"summary": "Report the connection state of an MCP target",
+				"parameters": [
+					path_parameter("backend", "The name of the MCP backend"),
+					name_parameter("The name of the MCP target"),
+				],
+				"responses": {
+					"200": json_response("The connection state", schema_ref("TargetStatus")),
+					"404": text_error(),
+				},
+			},
+		},
+		"/events": {
+			"get": {
+				"operationId": "streamTargetEvents",
+				"summary": "Stream changes in the connection state of the MCP targets as server-sent events",
+				"description": "Each event is named after its `type` and carries a `TargetEvent` as JSON data. A client that falls behind misses the oldest events, and gets a `lagged` event with the number it missed.",
+				"responses": {
+					"200": {
+						"description": "The event stream",
+						"content": {"text/event-stream": {"schema": schema_ref("TargetEvent")}},
+					},
+				},
+			},
+		},
+		"/logging": {
+			"post": {
+				"operationId": "setLogLevel",
+				"summary": "Report the log level, or change it if `level` or `reset` is set",
+				"parameters": [
+					{
+						"name": "level",
+						"in": "query",
+						"description": "The new level, either global (`debug`) or per module (`mod1:debug,mod2:trace`).",
+						"schema": {"type": "string"},
+					},
+					{
+						"name": "reset",
+						"in": "query",
+						"description": "Reset the levels to the startup configuration before applying `level`.",
+						"schema": {"type": "string"},
+					},
+				],
+				"responses": {
+					"200": text_response("The current log level"),
+					"400": text_error(),
+				},
+			},
+		},
+		"/quitquitquit": {
+			"post": {
+				"operationId": "shutdown",
+				"summary": "Shut the process down gracefully",
+				"responses": {
+					"200": text_response("The shutdown completed"),
+				},
+			},
+		},
+		"/openapi.json": {
+			"get": {
+				"operationId": "getOpenAPI",
+				"summary": "This document",
+				"responses": {
+					"200": json_response("The OpenAPI document", json!({"type": "object"})),
+				},
+			},
+		},
+	});
+	if cfg!(feature = "ui") {
+		let paths = paths.as_object_mut().expect("paths is an object");
+		for (path, item) in config_paths() {
+			paths.insert(path.to_string(), item);
+		}
+	}
+	json!({
+		"openapi": "3.0.3",
+		"info": {
+			"title": "agentgateway admin API",
+			"version": agent_core::version::BuildInfo::new().version,
+		},
+		"paths": paths,
+		"components": {
+			"schemas": schemas(),
+			"securitySchemes": {
+				"bearerAuth": {
+					"type": "http",
+					"scheme": "bearer",
+					"description": "Required if the admin API is configured with a token or JWT authentication.",
+				},
+			},
+		},
+		"security": [{}, {"bearerAuth": []}],
+	})
+}
+
+/// The routes for the local configuration file, served by the UI.
+fn config_paths() -> Vec<(&'static str, Value)> {
+	let config = json!({
+		"type": "object",
+		"additionalProperties": true,
+		"description": "The local configuration, as described by `schema/local.json`.",
+	});
+	let merge_patch = |what: &str| {
+		json!({
+			"required": true,
+			"content": {
+				"application/json": {
+					"schema": {
+						"type": "object",
+						"additionalProperties": true,
+						"description": format!(
+							"A JSON merge patch (RFC 7396) of the {what}. Objects are merged field by field and `null` removes a field; lists are replaced as a whole. The name cannot be changed."
+						),
+					},
+				},
+			},
+		})
+	};
+	let patch_responses = |what: &str| {
+		json!({
+			"200": json_response(&format!("The patched {what}"), json!({"type": "object"})),
+			"400": error("The patch or the resulting configuration is invalid"),
+			"404": error(&format!("No {what} has the name")),
+			"409": error(&format!("More than one {what} has the name")),
+			"500": error("The configuration could not be read or written"),
+		})
+	};
+	vec![
+		(
+			"/config",
+			json!({
+				"get": {
+					"operationId": "getConfig",
+					"summary": "Read the local configuration file",
+					"responses": {
+						"200": json_response("The configuration", config.clone()),
+						"500": error("The configuration could not be read"),
+					},
+				},
+				"post": {
+					"operationId": "writeConfig",
+					"summary": "Validate and replace the local configuration file",
+					"requestBody": {
+						"required": true,
+						"content": {"application/json": {"schema": config}},
+					},
+					"responses": {
+						"200": json_response("The configuration was written", json!({
+							"type": "object",
+							"properties": {
+								"status": {"type": "string"},
+								"message": {"type": "string"},
+							},
+						})),
+						"400": error("The configuration is invalid"),
+						"500": error("The configuration could not be written"),
+					},
+				},
+			}),
+		),
+		(
+			"/config/targets/{name}",
+			json!({
+				"patch": {
+					"operationId": "patchTarget",
+					"summary": "Update an MCP target in the local configuration file",
+					"parameters": [name_parameter("The name of the MCP target")],
+					"requestBody": merge_patch("MCP target"),
+					"responses": patch_responses("MCP target"),
+				},
+			}),
+		),
+		(
+			"/config/routes/{name}",
+			json!({
+				"patch": {
+					"operationId": "patchRoute",
+					"summary": "Update a route, such as its authentication or authorization policies, in the local configuration file",
+					"parameters": [name_parameter("The name of the route")],
+					"requestBody": merge_patch("route"),
+					"responses": patch_responses("route"),
+				},
+			}),
+		),
+	]
+}
+
+fn schemas() -> Value {
+	let string = json!({"type": "string"});
+	let strings = json!({"type": "array", "items": {"type": "string"}});
+	let date_time = json!({"type": "string", "format": "date-time"});
+	let pid = json!({"type": "integer", "format": "int32", "minimum": 0});
+	json!({
+		"ErrorResponse": {
+			"type": "string",
+			"description": "The error message, returned by the configuration routes as a JSON string.",
+		},
+		"Capabilities": {
+			"type": "object",
+			"required": [
+				"version",
+				"listenerProtocols",
+				"backendTypes",
+				"mcpTargetTypes",
+				"authentication",
+				"protocolVersions",
+				"a2aMethods",
+				"xds",
+				"features",
+			],
+			"properties": {
+				"version": {"type": "object", "additionalProperties": {"type": "string"}},
+				"listenerProtocols": strings,
+				"backendTypes": strings,
+				"mcpTargetTypes": strings,
+				"authentication": strings,
+				"protocolVersions": {
+					"type": "object",
+					"required": ["mcp"],
+					"properties": {
+						"mcp": strings,
+					},
+				},
+				"a2aMethods": strings,
+				"xds": {"type": "boolean"},
+				"features": strings,
+			},
+		},
+		"TargetEvent": {
+			"type": "object",
+			"required": ["type", "target", "backend", "at"],
+			"properties": {
+				"type": {
+					"type": "string",
+					"enum": ["connected", "disconnected", "error", "exited"],
+				},
+				"target": string,
+				"backend": string,
+				"pid": pid,
+				"message": {
+					"type": "string",
+					"description": "The error, or the exit status of the process.",
+				},
+				"at": date_time,
+			},
+		},
+		"TargetStatus": {
+			"type": "object",
+			"required": ["backend", "name", "connected", "connections"],
+			"properties": {
+				"backend": string,
+				"name": string,
+				"connected": {
+					"type": "boolean",
+					"description": "Whether any session currently holds a connection to the target.",
+				},
+				"connections": {
+					"type": "array",
+					"items": {
+						"type": "object",
+						"required": ["connectedSince"],
+						"properties": {
+							"connectedSince": date_time,
+							"pid": pid,
+						},
+					},
+				},
+				"lastError": {
+					"type": "object",
+					"required": ["message", "at"],
+					"properties": {
+						"message": string,
+						"at": date_time,
+					},
+				},
+				"lastExit": {
+					"type": "object",
+					"description": "The last time the process of a stdio target exited.",
+					"required": ["status", "at"],
+					"properties": {
+						"pid": pid,
+						"status": string,
+						"at": date_time,
+					},
+				},
+			},
+		},
+	})
+}
+
+fn schema_ref(name: &str) -> Value {
+	json!({"$ref": format!("#/components/schemas/{name}")})
+}
+
+fn name_parameter(description: &str) -> Value {
+	path_parameter("name", description)
+}
+
+fn path_parameter(name: &str, description: &str) -> Value {
+	json!({
+		"name": name,
+		"in": "path",
+		"required": true,
+		"description": description,
+		"schema": {"type": "string"},
+	})
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+	json!({
+		"description": description,
+		"content": {"application/json": {"schema": schema}},
+	})
+}
+
+fn text_response(description: &str) -> Value {
+	json!({
+		"description": description,
+		"content": {"text/plain": {"schema": {"type": "string"}}},
+	})
+}
+
+/// Errors from the admin routes are plain text.
+fn text_error() -> Value {
+	text_response("The error message")
+}
+
+/// Errors from the configuration routes are an `ErrorResponse`.
+fn error(description: &str) -> Value {
+	json_response(description, schema_ref("ErrorResponse"))
+}
+
+#[cfg(test)]
+#[path = "openapi_tests.rs"]
+mod tests;
@@ -0,0 +1,80 @@
+use super::*;
+use crate::management::admin::Capabilities;
+use crate::mcp::openapi::{parse_openapi_schema, parse_schema};
+use crate::mcp::relay::status::Registry;
+use crate::*;
+
+#[test]
+fn test_document_parses() {
+	let doc = serde_json::to_string(&document()).unwrap();
+	let parsed = parse_schema(&doc, None).unwrap();
+	let operations = parsed
+		.paths
+		.iter()
+		.map(|(_, item)| item.as_item().unwrap().iter().count())
+		.sum::<usize>();
+	assert_eq!(
+		operations,
+		if cfg!(feature = "ui") { 11 } else { 7 },
+		"{:?}",
+		parsed.paths.paths.keys().collect::<Vec<_>>()
+	);
+	// Every operation has what is needed to call it as a tool
+	let tools = parse_openapi_schema(&parsed).unwrap();
+	assert_eq!(tools.len(), operations);
+}
+
+/// Checks that the fields of `value` are described by `schema`, and that required fields are set.
+fn assert_matches_schema(path: &str, value: &Value, schema: &Value) {
+	let Value::Object(fields) = value else {
+		return;
+	};
+	let Some(properties) = schema["properties"].as_object() else {
+		return;
+	};
+	for required in schema["required"].as_array().into_iter().flatten() {
+		assert!(
+			fields.contains_key(required.as_str().unwrap()),
+			"{path}: missing required {required}"
+		);
+	}
+	for (k, v) in fields {
+		let Some(s) = properties.get(k) else {
+			panic!("{path}: {k} is not in the schema");
+		};
+		match v {
+			Value::Array(items) => items
+				.iter()
+				.for_each(|i| assert_matches_schema(&format!("{path}.{k}"), i, &s["items"])),
+			v => assert_matches_schema(&format!("{path}.{k}"), v, s),
+		}
+	}
+}
+
+#[test]
+fn test_schemas_match_types() {
+	let schemas = &document()["components"]["schemas"];
+
+	let config = crate::config::parse_config("{}".to_string(), None).unwrap();
+	let caps = serde_json::to_value(Capabilities::new(&config)).unwrap();
+	assert_matches_schema("Capabilities", &caps, &schemas["Capabilities"]);
+
+	let registry = Arc::new(Registry::default());
+	let (backend, target) = (strng::new("backend"), strng::new("everything"));
+	let exited = registry.connected(backend.clone(), target.clone(), Some(1));
+	exited.exited("exit status: 1".to_string());
+	let _conn = registry.connected(backend.clone(), target.clone(), Some(2));
+	registry.error(backend, &target, "connection refused".to_string());
+	let status = serde_json::to_value(registry.status("backend", "everything").unwrap()).unwrap();
+	assert_matches_schema("TargetStatus", &status, &schemas["TargetStatus"]);
+	// Check the optional fields were covered
+	assert!(status.get("lastError").is_some() && status.get("lastExit").is_some());
+
+	let mut events = registry.subscribe();
+	let conn = registry.connected(strng::new("backend"), strng::new("everything"), Some(3));
+	conn.exited("exit status: 1".to_string());
+	for _ in 0..2 {
+		let event = serde_json::to_value(events.try_recv().unwrap()).unwrap();
+		assert_matches_schema("TargetEvent", &event, &schemas["TargetEvent"]);
+	}
+}
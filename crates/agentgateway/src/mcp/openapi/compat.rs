@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+use anyhow::Context;
+use rmcp::model::Tool;
+use serde_json::Value;
+
+use super::{UpstreamOpenAPICall, parse_openapi_schema, parse_schema};
+
+/// A change to the tools generated from an OpenAPI schema, between two versions of the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+	ToolAdded(String),
+	ToolRemoved(String),
+	ParameterAdded {
+		tool: String,
+		parameter: String,
+		required: bool,
+	},
+	ParameterRemoved {
+		tool: String,
+		parameter: String,
+	},
+	/// An optional parameter became required, or the reverse.
+	ParameterRequired {
+		tool: String,
+		parameter: String,
+		required: bool,
+	},
+}
+
+impl Change {
+	/// Whether calls that worked against the old tools may fail against the new ones.
+	pub fn is_breaking(&self) -> bool {
+		match self {
+			Change::ToolAdded(_) => false,
+			Change::ToolRemoved(_) => true,
+			Change::ParameterAdded { required, .. } => *required,
+			Change::ParameterRemoved { .. } => true,
+			Change::ParameterRequired { required, .. } => *required,
+		}
+	}
+}
+
+impl fmt::Display for Change {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let kind = if self.is_breaking() {
+			"breaking"
+		} else {
+			"non-breaking"
+		};
+		let optional = |required: &bool| if *required { "required" } else { "optional" };
+		match self {
+			Change::ToolAdded(tool) => write!(f, "{kind}: added tool {tool}"),
+			Change::ToolRemoved(tool) => write!(f, "{kind}: removed tool {tool}"),
+			Change::ParameterAdded {
+				tool,
+				parameter,
+				required,
+			} => write!(
+				f,
+				"{kind}: tool {tool}: added {} parameter {parameter}",
+				optional(required)
+			),
+			Change::ParameterRemoved { tool, parameter } => {
+				write!(f, "{kind}: tool {tool}: removed parameter {parameter}")
+			},
+			Change::ParameterRequired {
+				tool,
+				parameter,
+				required,
+			} => write!(
+				f,
+				"{kind}: tool {tool}: parameter {parameter} is now {}",
+				optional(required)
+			),
+		}
+	}
+}
+
+/// Compares the tools generated from two versions of an OpenAPI schema. Parameters are named by
+/// their path in the tool's input schema, such as `query.limit` or `body.owner.name`.
+pub fn compare(
+	old: &[(Tool, UpstreamOpenAPICall)],
+	new: &[(Tool, UpstreamOpenAPICall)],
+) -> Vec<Change> {
+	let tools = |tools: &[(Tool, UpstreamOpenAPICall)]| {
+		tools
+			.iter()
+			.map(|(tool, _)| (tool.name.to_string(), parameters(tool)))
+			.collect::<BTreeMap<_, _>>()
+	};
+	let (old, new) = (tools(old), tools(new));
+	let mut changes = vec![];
+	for (name, old_params) in &old {
+		let Some(new_params) = new.get(name) else {
+			changes.push(Change::ToolRemoved(name.clone()));
+			continue;
+		};
+		for (parameter, was_required) in old_params {
+			match new_params.get(parameter) {
+				None => changes.push(Change::ParameterRemoved {
+					tool: name.clone(),
+					parameter: parameter.clone(),
+				}),
+				Some(required) if required != was_required => changes.push(Change::ParameterRequired {
+					tool: name.clone(),
+					parameter: parameter.clone(),
+					required: *required,
+				}),
+				Some(_) => {},
+			}
+		}
+		for (parameter, required) in new_params {
+			if !old_params.contains_key(parameter) {
+				changes.push(Change::ParameterAdded {
+					tool: name.clone(),
+					parameter: parameter.clone(),
+					required: *required,
+				});
+			}
+		}
+	}
+	changes.extend(
+		new
+			.keys()
+			.filter(|name| !old.contains_key(*name))
+			.map(|name| Change::ToolAdded(name.clone())),
+	);
+	changes
+}
+
+/// Compares the tools generated from two OpenAPI schema files, in JSON or YAML.
+pub fn compare_files(old: &Path, new: &Path) -> anyhow::Result<Vec<Change>> {
+	let tools = |path: &Path| -> anyhow::Result<Vec<(Tool, UpstreamOpenAPICall)>> {
+		let contents = fs_err::read_to_string(path)?;
+		let schema = parse_schema(&contents, Some(path))?;
+		parse_openapi_schema(&schema).with_context(|| format!("failed to parse {}", path.display()))
+	};
+	Ok(compare(&tools(old)?, &tools(new)?))
+}
+
+/// The parameters of a tool, by path, and whether each is required. A parameter nested in an
+/// optional object is optional, whatever its parent requires.
+fn parameters(tool: &Tool) -> BTreeMap<String, bool> {
+	let mut parameters = BTreeMap::new();
+	collect_parameters(&tool.input_schema, "", true, &mut parameters);
+	parameters
+}
+
+fn collect_parameters(
+	schema: &serde_json::Map<String, Value>,
+	prefix: &str,
+	required: bool,
+	parameters: &mut BTreeMap<String, bool>,
+) {
+	let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+		return;
+	};
+	let required_names: BTreeSet<&str> = schema
+		.get("required")
+		.and_then(Value::as_array)
+		.into_iter()
+		.flatten()
+		.filter_map(Value::as_str)
+		.collect();
+	for (name, property) in properties {
+		let path = if prefix.is_empty() {
+			name.clone()
+		} else {
+			format!("{prefix}.{name}")
+		};
+		let required = required && required_names.contains(name.as_str());
+		match property.as_object() {
+			// Objects with properties are where parameters are grouped, such as `query`
+			Some(object) if object.get("properties").is_some_and(Value::is_object) => {
+				collect_parameters(object, &path, required, parameters)
+			},
+			_ => {
+				parameters.insert(path, required);
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+#[path = "compat_tests.rs"]
+mod tests;
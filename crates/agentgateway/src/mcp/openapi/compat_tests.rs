@@ -0,0 +1,142 @@
+use super::*;
+
+const OLD: &str = r#"
+openapi: 3.0.0
+info:
+  title: pets
+  version: "1.0"
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      parameters:
+        - name: limit
+          in: query
+          schema:
+            type: integer
+        - name: tag
+          in: query
+          schema:
+            type: string
+      responses: {}
+    post:
+      operationId: addPet
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema:
+              type: object
+              required: [name]
+              properties:
+                name:
+                  type: string
+                owner:
+                  type: object
+                  properties:
+                    email:
+                      type: string
+      responses: {}
+  /pets/{id}:
+    delete:
+      operationId: deletePet
+      parameters:
+        - name: id
+          in: path
+          required: true
+          schema:
+            type: string
+      responses: {}
+"#;
+
+const NEW: &str = r#"
+openapi: 3.0.0
+info:
+  title: pets
+  version: "2.0"
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      parameters:
+        - name: limit
+          in: query
+          required: true
+          schema:
+            type: integer
+        - name: cursor
+          in: query
+          schema:
+            type: string
+      responses: {}
+    post:
+      operationId: addPet
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema:
+              type: object
+              required: [name, species]
+              properties:
+                name:
+                  type: string
+                species:
+                  type: string
+                owner:
+                  type: object
+                  required: [email]
+                  properties:
+                    email:
+                      type: string
+      responses: {}
+  /pets/{id}:
+    get:
+      operationId: getPet
+      parameters:
+        - name: id
+          in: path
+          required: true
+          schema:
+            type: string
+      responses: {}
+"#;
+
+fn tools(schema: &str) -> Vec<(Tool, UpstreamOpenAPICall)> {
+	parse_openapi_schema(&parse_schema(schema, None).unwrap()).unwrap()
+}
+
+#[test]
+fn test_compare() {
+	let changes = compare(&tools(OLD), &tools(NEW));
+	let report = changes.iter().map(ToString::to_string).collect::<Vec<_>>();
+	assert_eq!(
+		report,
+		vec![
+			"breaking: tool addPet: added required parameter body.species".to_string(),
+			"breaking: removed tool deletePet".to_string(),
+			"breaking: tool listPets: parameter query.limit is now required".to_string(),
+			"breaking: tool listPets: removed parameter query.tag".to_string(),
+			"non-breaking: tool listPets: added optional parameter query.cursor".to_string(),
+			"non-breaking: added tool getPet".to_string(),
+		]
+	);
+	// `owner.email` is required within `owner`, but `owner` itself is optional
+	assert!(!changes.iter().any(
+		|c| matches!(c, Change::ParameterRequired { parameter, .. } if parameter == "body.owner.email")
+	));
+}
+
+#[test]
+fn test_is_breaking() {
+	assert!(compare(&tools(OLD), &tools(OLD)).is_empty());
+	assert!(
+		compare(&tools(OLD), &tools(NEW))
+			.iter()
+			.any(Change::is_breaking)
+	);
+	let removed = compare(&tools(OLD), &[]);
+	assert!(removed.iter().all(|c| matches!(c, Change::ToolRemoved(_))));
+	let additive = compare(&[], &tools(OLD));
+	assert!(!additive.iter().any(Change::is_breaking));
+}
@@ -1,31 +1,142 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::read_to_string;
+use std::io::Read;
+use std::path::Path;
 use std::sync::Arc;
 
+use agent_core::metrics::Recorder;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use http::Method;
-use http::header::{ACCEPT, CONTENT_TYPE};
+use http::StatusCode;
+use http::header::{
+	ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+	LAST_MODIFIED,
+};
 use http_body_util::BodyExt;
 use hyper_util::rt::TokioIo;
 use openapiv3::{OpenAPI, Parameter, ReferenceOr, RequestBody, Schema, SchemaKind, Type};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use rmcp::model::{JsonObject, Tool};
+use rmcp::model::{Content, ErrorData, JsonObject, ResourceContents, Tool, ToolAnnotations};
+use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use tracing::instrument;
 use url::Url;
 
 use crate::client;
+use crate::http::redact::{REDACTED, SensitiveHeaders};
 use crate::store::BackendPolicies;
-use crate::types::agent::Target;
+use crate::types::agent::{
+	ArgumentValidation, DeprecatedOperations, HttpScheme, HttpVersionPreference, StatusRange,
+	TagFilter, Target,
+};
+
+pub mod compat;
+pub mod remote;
+pub mod security;
+pub mod validate;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct UpstreamOpenAPICall {
 	pub method: String, /* TODO: Switch to Method, but will require getting rid of Serialize/Deserialize */
 	pub path: String,
+	/// The content type the request body is sent as, if the operation has one.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content_type: Option<String>,
+	/// How array values are serialized for each query parameter. Parameters not listed use the default
+	/// (`form` style, exploded).
+	#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+	pub query_styles: HashMap<String, ArrayQueryStyle>,
+	/// The security requirements of the operation: alternatives, each listing the schemes that must
+	/// all be satisfied.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub security: Vec<Vec<String>>,
+	/// If set, the tool is advertised with flat arguments, which are regrouped before calling the API.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub flat: Option<FlatArguments>,
+	/// Example arguments, in the nested layout, from the examples of the parameters and request body.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub example: Option<JsonObject>,
 	// todo: params
 }
 
+/// How an array query parameter is serialized, derived from the parameter's `style` and `explode`.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ArrayQueryStyle {
+	/// `form` with `explode: true`, the OpenAPI default: `ids=1&ids=2`
+	#[default]
+	Repeated,
+	/// `form` with `explode: false`: `ids=1,2`
+	CommaDelimited,
+	/// `spaceDelimited`: `ids=1%202`
+	SpaceDelimited,
+	/// `pipeDelimited`: `ids=1|2`
+	PipeDelimited,
+}
+
+impl ArrayQueryStyle {
+	fn new(style: &openapiv3::QueryStyle, explode: Option<bool>) -> Self {
+		use openapiv3::QueryStyle;
+		// `explode` defaults to true only for the form style. For the delimited styles, an exploded
+		// array is sent as repeated keys, the same as form.
+		match (style, explode) {
+			(QueryStyle::Form, Some(false)) => ArrayQueryStyle::CommaDelimited,
+			(QueryStyle::SpaceDelimited, None | Some(false)) => ArrayQueryStyle::SpaceDelimited,
+			(QueryStyle::PipeDelimited, None | Some(false)) => ArrayQueryStyle::PipeDelimited,
+			_ => ArrayQueryStyle::Repeated,
+		}
+	}
+
+	fn delimiter(&self) -> Option<&'static str> {
+		match self {
+			ArrayQueryStyle::Repeated => None,
+			ArrayQueryStyle::CommaDelimited => Some(","),
+			ArrayQueryStyle::SpaceDelimited => Some("%20"),
+			ArrayQueryStyle::PipeDelimited => Some("|"),
+		}
+	}
+}
+
+/// Request body content types we can send, in order of preference.
+const BODY_CONTENT_TYPES: [&str; 3] =
+	[JSON_CONTENT_TYPE, MULTIPART_CONTENT_TYPE, FORM_CONTENT_TYPE];
+const JSON_CONTENT_TYPE: &str = "application/json";
+const CONDITIONAL_HEADERS: [HeaderName; 2] = [IF_NONE_MATCH, IF_MODIFIED_SINCE];
+const MULTIPART_CONTENT_TYPE: &str = "multipart/form-data";
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+/// Connection-level headers, which are never forwarded from tool arguments.
+const HOP_BY_HOP_HEADERS: [HeaderName; 9] = [
+	http::header::CONNECTION,
+	HeaderName::from_static("proxy-connection"),
+	HeaderName::from_static("keep-alive"),
+	http::header::PROXY_AUTHENTICATE,
+	http::header::PROXY_AUTHORIZATION,
+	http::header::TE,
+	http::header::TRAILER,
+	http::header::TRANSFER_ENCODING,
+	http::header::UPGRADE,
+];
+/// Content codings we can decode, advertised on every upstream call.
+const ACCEPTED_ENCODINGS: &str = "gzip, deflate, br";
+/// Response media types returned to the caller as text. Anything else is returned base64 encoded.
+/// `type/*` matches any subtype, and `type/*+suffix` any subtype with that structured syntax suffix.
+const DEFAULT_TEXT_CONTENT_TYPES: [&str; 8] = [
+	"text/*",
+	"application/json",
+	"application/*+json",
+	"application/xml",
+	"application/*+xml",
+	"application/yaml",
+	"application/javascript",
+	"application/x-www-form-urlencoded",
+];
+/// The largest response body we read from the API, both as received and once decompressed.
+const MAX_RESPONSE_SIZE: usize = 2_097_152;
+
 #[derive(Debug, thiserror::Error)]
 pub enum ParseError {
 	#[error("missing fields")]
@@ -36,10 +147,14 @@ pub enum ParseError {
 	MissingComponents,
 	#[error("invalid reference: {0}")]
 	InvalidReference(String),
-	#[error("missing reference")]
+	#[error("missing reference: {0}")]
 	MissingReference(String),
-	#[error("unsupported reference")]
+	#[error("unsupported reference: {0}")]
 	UnsupportedReference(String),
+	#[error("too many $defs references, more than {0} would be inlined")]
+	TooManyReferences(usize),
+	#[error("multiple servers are not supported: {0}")]
+	MultipleServers(String),
 	#[error("information required: {0}")] // Corrected typo from "requireds"
 	InformationRequired(String),
 	#[error("serde error: {0}")]
@@ -66,17 +181,324 @@ pub enum ParseError {
 	HeaderValueSourceNotSupported(String),
 }
 
-pub(crate) fn get_server_prefix(server: &OpenAPI) -> Result<String, ParseError> {
-	match server.servers.len() {
-		0 => Ok("/".to_string()),
-		1 => Ok(server.servers[0].url.clone()),
-		_ => Err(ParseError::UnsupportedReference(format!(
-			"multiple servers are not supported: {:?}",
-			server.servers
-		))),
+impl ParseError {
+	/// A stable identifier for the kind of error, suitable for matching on in logs or tooling.
+	/// Unlike the error message, this will not change between releases.
+	pub fn code(&self) -> &'static str {
+		match self {
+			ParseError::MissingFields => "missing_fields",
+			ParseError::MissingSchema => "missing_schema",
+			ParseError::MissingComponents => "missing_components",
+			ParseError::InvalidReference(_) => "invalid_reference",
+			ParseError::MissingReference(_) => "missing_reference",
+			ParseError::UnsupportedReference(_) => "unsupported_reference",
+			ParseError::TooManyReferences(_) => "too_many_references",
+			ParseError::MultipleServers(_) => "multiple_servers",
+			ParseError::InformationRequired(_) => "information_required",
+			ParseError::SerdeError(_) => "serde_error",
+			ParseError::IoError(_) => "io_error",
+			ParseError::HttpError(_) => "http_error",
+			ParseError::InvalidUrl(_) => "invalid_url",
+			ParseError::SchemaSourceMissing => "schema_source_missing",
+			ParseError::UnsupportedSchemaFormat(_) => "unsupported_schema_format",
+			ParseError::LocalPathMissing => "local_path_missing",
+			ParseError::LocalInlineMissing => "local_inline_missing",
+			ParseError::InvalidHeader => "invalid_header",
+			ParseError::HeaderValueSourceNotSupported(_) => "header_value_source_not_supported",
+		}
 	}
 }
 
+/// Parses an OpenAPI document authored in either JSON or YAML.
+/// The format is taken from the file extension of `path`, if known, or otherwise detected from the
+/// content: documents starting with `{` are JSON, anything else is YAML.
+///
+/// References to JSON Schema `$defs` are inlined, see [resolve_local_definitions].
+pub(crate) fn parse_schema(contents: &str, path: Option<&Path>) -> anyhow::Result<OpenAPI> {
+	let ext = path
+		.and_then(|p| p.extension())
+		.and_then(|e| e.to_str())
+		.map(|e| e.to_ascii_lowercase());
+	let json = match ext.as_deref() {
+		Some("json") => true,
+		Some("yaml" | "yml") => false,
+		_ => contents.trim_start().starts_with('{'),
+	};
+	let format = if json { "JSON" } else { "YAML" };
+	let invalid =
+		|e: &dyn std::fmt::Display| anyhow::anyhow!("invalid OpenAPI schema (parsed as {format}): {e}");
+	let mut doc: Value = if json {
+		serde_json::from_str(contents).map_err(|e| invalid(&e))?
+	} else {
+		crate::yamlviajson::from_str(contents).map_err(|e| invalid(&e))?
+	};
+	resolve_local_definitions(&mut doc).map_err(|e| invalid(&e))?;
+	serde_json_path_to_error::from_slice(&serde_json::to_vec(&doc)?).map_err(|e| invalid(&e))
+}
+
+/// The most `$defs` references inlined in a single document. Definitions referencing each other
+/// several times grow exponentially when inlined, so the expansion is bounded.
+const MAX_INLINED_DEFINITIONS: usize = 10_000;
+
+/// Schema keywords whose value is a schema, or a list of schemas.
+const SUBSCHEMA_KEYWORDS: &[&str] = &[
+	"items",
+	"prefixItems",
+	"additionalItems",
+	"contains",
+	"additionalProperties",
+	"propertyNames",
+	"unevaluatedItems",
+	"unevaluatedProperties",
+	"allOf",
+	"anyOf",
+	"oneOf",
+	"not",
+	"if",
+	"then",
+	"else",
+	"contentSchema",
+];
+
+/// Schema keywords whose value maps names to schemas.
+const SUBSCHEMA_MAP_KEYWORDS: &[&str] = &["properties", "patternProperties", "dependentSchemas"];
+
+/// Inlines the `$ref`s pointing into JSON Schema `$defs` (`#/$defs/Name`), which OpenAPI 3.1
+/// allows in any schema. The definitions in scope are those of the schema holding the `$ref` and
+/// of its parents, the innermost taking precedence. Keywords next to the `$ref` are kept, overriding
+/// those of the definition. References to components are left to be resolved with the document.
+///
+/// `$defs` and `$ref` are only keywords in schema positions: the `schema` of parameters, headers
+/// and media types, the component schemas, and the subschemas of those. A property named `$ref`,
+/// or an example holding one, is left as is.
+///
+/// A definition referencing itself, directly or not, is inlined once; the recursive reference is
+/// replaced by an empty schema, which accepts any value. At most [MAX_INLINED_DEFINITIONS]
+/// references are inlined.
+fn resolve_local_definitions(doc: &mut Value) -> Result<(), ParseError> {
+	let mut resolver = LocalDefinitions::default();
+	let mut schemas = doc.pointer_mut("/components/schemas").map(Value::take);
+	if let Some(Value::Object(schemas)) = &mut schemas {
+		for schema in schemas.values_mut() {
+			resolver.schema(schema, &[])?;
+		}
+	}
+	resolver.document(doc)?;
+	if let (Some(slot), Some(schemas)) = (doc.pointer_mut("/components/schemas"), schemas) {
+		*slot = schemas;
+	}
+	Ok(())
+}
+
+#[derive(Default)]
+struct LocalDefinitions {
+	/// The references being inlined, to detect recursion.
+	active: Vec<String>,
+	/// The number of references inlined so far.
+	inlined: usize,
+}
+
+impl LocalDefinitions {
+	/// Walks the document outside of schemas, resolving the schemas found on the way.
+	fn document(&mut self, value: &mut Value) -> Result<(), ParseError> {
+		match value {
+			Value::Array(items) => {
+				for item in items {
+					self.document(item)?;
+				}
+			},
+			Value::Object(object) => {
+				for (k, v) in object.iter_mut() {
+					match k.as_str() {
+						"schema" => self.schema(v, &[])?,
+						"example" | "examples" => {},
+						k if k.starts_with("x-") => {},
+						_ => self.document(v)?,
+					}
+				}
+			},
+			_ => {},
+		}
+		Ok(())
+	}
+
+	/// Resolves a schema, or each schema of a list of them.
+	fn schemas(&mut self, value: &mut Value, scopes: &[&Value]) -> Result<(), ParseError> {
+		match value {
+			Value::Array(items) => {
+				for item in items {
+					self.schema(item, scopes)?;
+				}
+				Ok(())
+			},
+			_ => self.schema(value, scopes),
+		}
+	}
+
+	fn schema(&mut self, value: &mut Value, scopes: &[&Value]) -> Result<(), ParseError> {
+		let Value::Object(object) = value else {
+			return Ok(());
+		};
+		let defs = object.remove("$defs");
+		let mut inner = scopes.to_vec();
+		inner.extend(defs.as_ref());
+		let scopes = inner.as_slice();
+
+		let reference = object
+			.get("$ref")
+			.and_then(Value::as_str)
+			.filter(|r| r.starts_with("#/$defs/"))
+			.map(str::to_string);
+		let resolved = match reference {
+			Some(reference) => {
+				object.remove("$ref");
+				Some(self.definition(reference, scopes)?)
+			},
+			None => None,
+		};
+
+		for (k, v) in object.iter_mut() {
+			if SUBSCHEMA_KEYWORDS.contains(&k.as_str()) {
+				self.schemas(v, scopes)?;
+			} else if SUBSCHEMA_MAP_KEYWORDS.contains(&k.as_str())
+				&& let Value::Object(map) = v
+			{
+				for schema in map.values_mut() {
+					self.schema(schema, scopes)?;
+				}
+			}
+		}
+
+		if let Some(mut resolved) = resolved {
+			if let Value::Object(resolved) = &mut resolved {
+				for (k, v) in std::mem::take(object) {
+					resolved.insert(k, v);
+				}
+			}
+			*value = resolved;
+		}
+		Ok(())
+	}
+
+	/// Returns the resolved definition a `$ref` points to.
+	fn definition(&mut self, reference: String, scopes: &[&Value]) -> Result<Value, ParseError> {
+		let pointer = &reference["#/$defs".len()..];
+		let (scope, definition) = scopes
+			.iter()
+			.enumerate()
+			.rev()
+			.find_map(|(i, defs)| defs.pointer(pointer).map(|d| (i, d)))
+			.ok_or_else(|| ParseError::MissingReference(reference.clone()))?;
+		if self.active.contains(&reference) {
+			tracing::debug!("recursive reference {reference}, replaced by an empty schema");
+			return Ok(json!({}));
+		}
+		self.inlined += 1;
+		if self.inlined > MAX_INLINED_DEFINITIONS {
+			return Err(ParseError::TooManyReferences(MAX_INLINED_DEFINITIONS));
+		}
+		let mut resolved = definition.clone();
+		self.active.push(reference);
+		// The definition is resolved where it is defined, not where it is referenced
+		self.schema(&mut resolved, &scopes[..=scope])?;
+		self.active.pop();
+		Ok(resolved)
+	}
+}
+
+/// The parts of the schema's server URL used to call the API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ServerUrl {
+	/// The scheme, if the URL is absolute.
+	pub scheme: Option<HttpScheme>,
+	/// The host, if the URL is absolute.
+	pub host: Option<String>,
+	/// The port, if the URL sets one.
+	pub port: Option<u16>,
+	/// The path every operation path is appended to.
+	pub prefix: String,
+}
+
+/// Splits the schema's server URL into the parts used to call the API. At most one server is
+/// supported.
+pub(crate) fn get_server_url(server: &OpenAPI) -> Result<ServerUrl, ParseError> {
+	let url = match server.servers.as_slice() {
+		[] => "/",
+		[server] => server.url.as_str(),
+		_ => {
+			return Err(ParseError::MultipleServers(
+				server
+					.servers
+					.iter()
+					.map(|s| s.url.as_str())
+					.collect::<Vec<_>>()
+					.join(", "),
+			));
+		},
+	};
+	// Relative URLs, and URLs with variables that do not parse, are used as the prefix as they are
+	let Some(parsed) = url.contains("://").then(|| Url::parse(url).ok()).flatten() else {
+		return Ok(ServerUrl {
+			scheme: None,
+			host: None,
+			port: None,
+			prefix: url.to_string(),
+		});
+	};
+	let scheme = match parsed.scheme() {
+		"http" => HttpScheme::Http,
+		"https" => HttpScheme::Https,
+		other => {
+			return Err(ParseError::UnsupportedReference(format!(
+				"unsupported server URL scheme: {other}"
+			)));
+		},
+	};
+	Ok(ServerUrl {
+		scheme: Some(scheme),
+		host: parsed.host_str().map(str::to_string),
+		port: parsed.port(),
+		prefix: parsed.path().to_string(),
+	})
+}
+
+/// Picks the host and port to call the API with. The configured ones take precedence over the
+/// server URL's; without either, the port is the default for the scheme. Returns `None` if there is
+/// no host.
+pub(crate) fn resolve_address(
+	host: Option<&str>,
+	port: Option<u32>,
+	server: &ServerUrl,
+	scheme: HttpScheme,
+) -> Option<(String, u32)> {
+	let host = host.or(server.host.as_deref())?;
+	let port = port.or(server.port.map(u32::from)).unwrap_or(match scheme {
+		HttpScheme::Http => 80,
+		HttpScheme::Https => 443,
+	});
+	Some((host.to_string(), port))
+}
+
+/// Picks the scheme to call the API with: the configured one, then the server URL's, then `https`
+/// only if a `backendTLS` policy is set. Calls with `https` and no policy use the system trust roots.
+pub(crate) fn resolve_scheme(
+	configured: Option<HttpScheme>,
+	server: &ServerUrl,
+	policies: &mut BackendPolicies,
+) -> HttpScheme {
+	let scheme = configured
+		.or(server.scheme)
+		.unwrap_or(if policies.backend_tls.is_some() {
+			HttpScheme::Https
+		} else {
+			HttpScheme::Http
+		});
+	if scheme == HttpScheme::Https && policies.backend_tls.is_none() {
+		policies.backend_tls = Some(crate::http::backendtls::SYSTEM_TRUST.clone());
+	}
+	scheme
+}
+
 fn resolve_schema<'a>(
 	reference: &'a ReferenceOr<Schema>,
 	doc: &'a OpenAPI,
@@ -234,6 +656,33 @@ fn resolve_parameter<'a>(
 	}
 }
 
+/// The parameters of an operation, including those declared on its path. An operation parameter
+/// replaces a path parameter with the same name and location.
+fn operation_parameters<'a>(
+	item: &'a openapiv3::PathItem,
+	op: &'a openapiv3::Operation,
+	doc: &'a OpenAPI,
+) -> Result<Vec<&'a Parameter>, ParseError> {
+	let declared = op
+		.parameters
+		.iter()
+		.map(|p| resolve_parameter(p, doc))
+		.collect::<Result<Vec<_>, _>>()?;
+	let mut params = Vec::with_capacity(item.parameters.len() + declared.len());
+	for p in &item.parameters {
+		let p = resolve_parameter(p, doc)?;
+		let overridden = declared.iter().any(|d| {
+			std::mem::discriminant(*d) == std::mem::discriminant(p)
+				&& d.parameter_data_ref().name == p.parameter_data_ref().name
+		});
+		if !overridden {
+			params.push(p);
+		}
+	}
+	params.extend(declared);
+	Ok(params)
+}
+
 fn resolve_request_body<'a>(
 	reference: &'a ReferenceOr<RequestBody>,
 	doc: &'a OpenAPI,
@@ -269,6 +718,28 @@ fn resolve_request_body<'a>(
 /// That way the client code can properly separate the objects passed by the client.
 pub(crate) fn parse_openapi_schema(
 	open_api: &OpenAPI,
+) -> Result<Vec<(Tool, UpstreamOpenAPICall)>, ParseError> {
+	parse_openapi_tools(open_api, &ParseOptions::default())
+}
+
+/// Options for turning the operations of a schema into tools.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+	pub deprecated_operations: DeprecatedOperations,
+	pub tags: Option<TagFilter>,
+	/// If not empty, only operations with one of these methods, in any case, become tools.
+	pub allowed_methods: Vec<String>,
+	/// Annotate GET and HEAD operations as read-only, and the others as not, for read-only backends.
+	pub annotate_read_only: bool,
+}
+
+/// A parameter of an operation: its name, schema, whether it is required, and its example.
+type ParameterSchema = (String, JsonObject, bool, Option<Value>);
+
+/// Like [parse_openapi_schema], choosing which operations become tools with `options`.
+pub(crate) fn parse_openapi_tools(
+	open_api: &OpenAPI,
+	options: &ParseOptions,
 ) -> Result<Vec<(Tool, UpstreamOpenAPICall)>, ParseError> {
 	let tool_defs: Result<Vec<_>, _> = open_api
 		.paths
@@ -280,6 +751,17 @@ pub(crate) fn parse_openapi_schema(
 					.ok_or(ParseError::UnsupportedReference(path.to_string()))?;
 				let items: Result<Vec<_>, _> = item
 					.iter()
+					.filter(|(_, op)| {
+						!(op.deprecated && options.deprecated_operations == DeprecatedOperations::Exclude)
+					})
+					.filter(|(_, op)| options.tags.as_ref().is_none_or(|f| f.matches(&op.tags)))
+					.filter(|(method, _)| {
+						options.allowed_methods.is_empty()
+							|| options
+								.allowed_methods
+								.iter()
+								.any(|m| m.eq_ignore_ascii_case(method))
+					})
 					.map(
 						|(method, op)| -> Result<(Tool, UpstreamOpenAPICall), ParseError> {
 							let name = op
@@ -292,18 +774,32 @@ pub(crate) fn parse_openapi_schema(
 							// Build the schema
 							let mut final_schema = JsonSchema::default();
 
+							let mut content_type = None;
+							let mut example = JsonObject::new();
 							let body: Option<(String, serde_json::Value, bool)> = match op.request_body.as_ref() {
 								Some(body) => {
 									let body = resolve_request_body(body, open_api)?;
-									match body.content.get("application/json") {
-										Some(media_type) => {
+									let media_type = BODY_CONTENT_TYPES
+										.iter()
+										.find_map(|ct| body.content.get(*ct).map(|mt| (*ct, mt)));
+									match media_type {
+										Some((ct, media_type)) => {
+											content_type = Some(ct.to_string());
 											let schema_ref = media_type
 												.schema
 												.as_ref()
-												.ok_or(ParseError::MissingReference("application/json".to_string()))?;
+												.ok_or(ParseError::MissingReference(ct.to_string()))?;
 											let schema = resolve_nested_schema(schema_ref, open_api)?;
 											let body_schema =
 												serde_json::to_value(schema).map_err(ParseError::SerdeError)?;
+											let body_example = media_type
+												.example
+												.clone()
+												.or_else(|| first_example(&media_type.examples))
+												.or_else(|| body_schema.get("example").cloned());
+											if let Some(body_example) = body_example {
+												example.insert(BODY_NAME.clone(), body_example);
+											}
 											if body.required {
 												final_schema.required.push(BODY_NAME.clone());
 											}
@@ -325,33 +821,48 @@ pub(crate) fn parse_openapi_schema(
 								final_schema.properties.insert(name.clone(), schema.clone());
 							}
 
-							let mut param_schemas: HashMap<ParameterType, Vec<(String, JsonObject, bool)>> =
-								HashMap::new();
-							op.parameters
-								.iter()
-								.try_for_each(|p| -> Result<(), ParseError> {
-									let item = resolve_parameter(p, open_api)?;
+							let mut query_styles = HashMap::new();
+							let mut param_schemas: HashMap<ParameterType, Vec<ParameterSchema>> = HashMap::new();
+							operation_parameters(item, op, open_api)?
+								.into_iter()
+								.try_for_each(|item| -> Result<(), ParseError> {
 									let (name, schema, required) = build_schema_property(open_api, item)?;
+									let data = item.parameter_data_ref();
+									let param_example = data
+										.example
+										.clone()
+										.or_else(|| first_example(&data.examples))
+										.or_else(|| schema.get("example").cloned());
 									match item {
 										Parameter::Header { .. } => {
 											param_schemas
 												.entry(ParameterType::Header)
-												.or_insert_with(Vec::new)
-												.push((name, schema, required));
+												.or_default()
+												.push((name, schema, required, param_example));
 											Ok(())
 										},
-										Parameter::Query { .. } => {
+										Parameter::Query {
+											parameter_data,
+											style,
+											..
+										} => {
+											let array_style = ArrayQueryStyle::new(style, parameter_data.explode);
+											if array_style != ArrayQueryStyle::Repeated {
+												query_styles.insert(name.clone(), array_style);
+											}
 											param_schemas
 												.entry(ParameterType::Query)
-												.or_insert_with(Vec::new)
-												.push((name, schema, required));
+												.or_default()
+												.push((name, schema, required, param_example));
 											Ok(())
 										},
 										Parameter::Path { .. } => {
-											param_schemas
-												.entry(ParameterType::Path)
-												.or_insert_with(Vec::new)
-												.push((name, schema, required));
+											param_schemas.entry(ParameterType::Path).or_default().push((
+												name,
+												schema,
+												required,
+												param_example,
+											));
 											Ok(())
 										},
 										_ => Err(ParseError::UnsupportedReference(
@@ -364,14 +875,21 @@ pub(crate) fn parse_openapi_schema(
 								let sub_schema = JsonSchema {
 									required: props
 										.iter()
-										.flat_map(|(name, _, req)| if *req { Some(name.clone()) } else { None })
+										.flat_map(|(name, _, req, _)| if *req { Some(name.clone()) } else { None })
 										.collect(),
 									properties: props
 										.iter()
-										.map(|(name, s, _)| (name.clone(), json!(s)))
+										.map(|(name, s, _, _)| (name.clone(), json!(s)))
 										.collect(),
 									..Default::default()
 								};
+								let group_example: JsonObject = props
+									.iter()
+									.filter_map(|(name, _, _, ex)| Some((name.clone(), ex.clone()?)))
+									.collect();
+								if !group_example.is_empty() {
+									example.insert(param_type.to_string(), Value::Object(group_example));
+								}
 
 								if !sub_schema.required.is_empty() {
 									final_schema.required.push(param_type.to_string());
@@ -383,27 +901,44 @@ pub(crate) fn parse_openapi_schema(
 
 							let final_json =
 								serde_json::to_value(final_schema).map_err(ParseError::SerdeError)?;
-							let final_json = final_json
+							let mut final_json = final_json
 								.as_object()
 								.ok_or(ParseError::UnsupportedReference(
 									"final schema is not an object".to_string(),
 								))?
 								.clone();
+							let mut description = op
+								.description
+								.as_ref()
+								.unwrap_or_else(|| op.summary.as_ref().unwrap_or(&name))
+								.to_string();
+							if op.deprecated {
+								// Tool annotations have no field for this, so tell both agents and clients
+								final_json.insert("deprecated".to_string(), Value::Bool(true));
+								description = format!("Deprecated. {description}");
+							}
+							// Only reads are assumed not to change state, so read-only backends can call them
+							let annotations = options.annotate_read_only.then(|| ToolAnnotations {
+								read_only_hint: Some(
+									method.eq_ignore_ascii_case("get") || method.eq_ignore_ascii_case("head"),
+								),
+								..Default::default()
+							});
 							let tool = Tool {
-								annotations: None,
+								annotations,
 								name: Cow::Owned(name.clone()),
-								description: Some(Cow::Owned(
-									op.description
-										.as_ref()
-										.unwrap_or_else(|| op.summary.as_ref().unwrap_or(&name))
-										.to_string(),
-								)),
+								description: Some(Cow::Owned(description)),
 								input_schema: Arc::new(final_json),
 							};
 							let upstream = UpstreamOpenAPICall {
 								// method: Method::from_bytes(method.as_ref()).expect("todo"),
 								method: method.to_string(),
 								path: path.clone(),
+								content_type,
+								query_styles,
+								security: security::requirements(open_api, op),
+								flat: None,
+								example: (!example.is_empty()).then_some(example),
 							};
 							Ok((tool, upstream))
 						},
@@ -422,6 +957,221 @@ pub(crate) fn parse_openapi_schema(
 	}
 }
 
+/// The flat form of a tool's arguments, for callers that struggle with the nested grouping.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct FlatArguments {
+	/// The input schema advertised for the tool, with every argument a top-level property.
+	pub schema: JsonObject,
+	/// Where each top-level argument is sent.
+	pub locations: HashMap<String, ArgumentLocation>,
+}
+
+/// Where a flat argument goes in the nested arguments.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ArgumentLocation {
+	/// `path`, `query`, `header` or `body`.
+	pub group: String,
+	/// The name within the group, or None if the argument is the whole group, as for a body that is
+	/// not an object.
+	pub name: Option<String>,
+}
+
+/// Flattens the input schema of a tool, so every argument is a top-level property. Arguments with
+/// the same name in several groups are prefixed with their group, as in `path_id` and `body_id`. An
+/// argument is required if it is required within its group and the group is required.
+pub(crate) fn flatten_arguments(tool: &Tool) -> FlatArguments {
+	let schema = Value::Object(tool.input_schema.as_ref().clone());
+	let mut arguments = Vec::new();
+	for group in [&*PATH_NAME, &*QUERY_NAME, &*HEADER_NAME, &*BODY_NAME] {
+		let group_schema = &schema["properties"][group];
+		if group_schema.is_null() {
+			continue;
+		}
+		let group_required = is_required(&schema, group);
+		match group_schema["properties"].as_object() {
+			Some(properties) => {
+				for (name, property) in properties {
+					let required = group_required && is_required(group_schema, name);
+					arguments.push((group, Some(name), property, required));
+				}
+			},
+			None => arguments.push((group, None, group_schema, group_required)),
+		}
+	}
+	let name_of = |group: &String, name: Option<&String>| name.unwrap_or(group).clone();
+	let mut flat = FlatArguments::default();
+	let mut properties = JsonObject::new();
+	let mut required = Vec::new();
+	for (group, name, property, needed) in &arguments {
+		let plain = name_of(group, *name);
+		let collides = arguments
+			.iter()
+			.filter(|(g, n, _, _)| name_of(g, *n) == plain)
+			.count()
+			> 1;
+		let flat_name = if collides {
+			format!("{group}_{plain}")
+		} else {
+			plain
+		};
+		properties.insert(flat_name.clone(), (*property).clone());
+		if *needed {
+			required.push(Value::String(flat_name.clone()));
+		}
+		flat.locations.insert(
+			flat_name,
+			ArgumentLocation {
+				group: group.to_string(),
+				name: name.cloned(),
+			},
+		);
+	}
+	flat.schema = json!({
+		"type": "object",
+		"properties": properties,
+		"required": required,
+	})
+	.as_object()
+	.expect("schema is an object")
+	.clone();
+	flat
+}
+
+/// Regroups flat arguments by where they are sent. Arguments that are not in the schema are dropped.
+fn unflatten_arguments(flat: &FlatArguments, args: JsonObject) -> JsonObject {
+	let mut nested = JsonObject::new();
+	for (k, v) in args {
+		match flat.locations.get(&k) {
+			Some(ArgumentLocation {
+				group,
+				name: Some(name),
+			}) => {
+				if let Value::Object(group) = nested
+					.entry(group.clone())
+					.or_insert_with(|| Value::Object(JsonObject::new()))
+				{
+					group.insert(name.clone(), v);
+				}
+			},
+			Some(ArgumentLocation { group, name: None }) => {
+				nested.insert(group.clone(), v);
+			},
+			None => tracing::debug!("Argument '{}' is not in the schema, skipping", k),
+		}
+	}
+	nested
+}
+
+/// Appends the arguments of a tool to its description: where each goes in the arguments, its type,
+/// whether it is required, and its description. The nested input schema groups the arguments by
+/// where they are sent (`path`, `query`, `header` and `body`), which is easy for an agent to miss.
+/// The input schema itself is left as is.
+pub(crate) fn describe_arguments(tool: &mut Tool, call: &UpstreamOpenAPICall) {
+	let mut lines = Vec::new();
+	match &call.flat {
+		Some(flat) => describe_properties("", &Value::Object(flat.schema.clone()), &mut lines),
+		None => {
+			let schema = Value::Object(tool.input_schema.as_ref().clone());
+			for group in [&*PATH_NAME, &*QUERY_NAME, &*HEADER_NAME, &*BODY_NAME] {
+				let group_schema = &schema["properties"][group];
+				if group_schema.is_null() {
+					continue;
+				}
+				if group_schema["properties"].is_object() {
+					describe_properties(&format!("{group}."), group_schema, &mut lines);
+				} else {
+					// A body that is not an object is described as a whole
+					lines.push(describe_argument(
+						group,
+						group_schema,
+						is_required(&schema, group),
+					));
+				}
+			}
+		},
+	}
+	if lines.is_empty() {
+		return;
+	}
+	let description = tool.description.as_deref().unwrap_or_default();
+	tool.description = Some(Cow::Owned(format!(
+		"{description}\n\nArguments:\n{}",
+		lines.join("\n")
+	)));
+}
+
+/// Appends example arguments to the description of a tool, so an agent sees a concrete call. They
+/// are in the layout the tool is advertised with.
+pub(crate) fn describe_example(tool: &mut Tool, call: &UpstreamOpenAPICall) {
+	let Some(example) = &call.example else {
+		return;
+	};
+	let example = match &call.flat {
+		Some(flat) => flat
+			.locations
+			.iter()
+			.filter_map(|(flat_name, location)| {
+				let group = example.get(&location.group)?;
+				let value = match &location.name {
+					Some(name) => group.get(name)?,
+					None => group,
+				};
+				Some((flat_name.clone(), value.clone()))
+			})
+			.collect(),
+		None => example.clone(),
+	};
+	let description = tool.description.as_deref().unwrap_or_default();
+	tool.description = Some(Cow::Owned(format!(
+		"{description}\n\nExample arguments:\n{}",
+		Value::Object(example)
+	)));
+}
+
+/// The value of the first of an OpenAPI `examples` map that is inline and has one.
+fn first_example<'a>(
+	examples: impl IntoIterator<Item = (&'a String, &'a ReferenceOr<openapiv3::Example>)>,
+) -> Option<Value> {
+	examples
+		.into_iter()
+		.find_map(|(_, e)| e.as_item().and_then(|e| e.value.clone()))
+}
+
+fn describe_properties(prefix: &str, schema: &Value, lines: &mut Vec<String>) {
+	for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+		lines.push(describe_argument(
+			&format!("{prefix}{name}"),
+			property,
+			is_required(schema, name),
+		));
+	}
+}
+
+fn describe_argument(path: &str, schema: &Value, required: bool) -> String {
+	let mut line = format!("- {path}");
+	let attributes = schema["type"]
+		.as_str()
+		.into_iter()
+		.chain(required.then_some("required"))
+		.collect::<Vec<_>>();
+	if !attributes.is_empty() {
+		line.push_str(&format!(" ({})", attributes.join(", ")));
+	}
+	if let Some(description) = schema["description"].as_str() {
+		// Keep each argument on one line
+		let description = description.split_whitespace().collect::<Vec<_>>().join(" ");
+		line.push_str(&format!(": {description}"));
+	}
+	line
+}
+
+/// Whether `name` is listed in the `required` properties of an object schema.
+fn is_required(schema: &Value, name: &str) -> bool {
+	schema["required"]
+		.as_array()
+		.is_some_and(|r| r.iter().any(|r| r == name))
+}
+
 // Used to index the parameter types for the schema
 lazy_static::lazy_static! {
 	pub static ref BODY_NAME: String = "body".to_string();
@@ -478,6 +1228,9 @@ fn build_schema_property(
 	if let Some(desc) = &p.description {
 		schema.insert("description".to_string(), json!(desc));
 	}
+	if p.deprecated == Some(true) {
+		schema.insert("deprecated".to_string(), Value::Bool(true));
+	}
 
 	Ok((p.name.clone(), schema, p.required))
 }
@@ -507,9 +1260,91 @@ pub struct Handler {
 	pub client: client::Client,
 	pub tools: Vec<(Tool, UpstreamOpenAPICall)>,
 	pub policies: BackendPolicies,
+	pub success_statuses: Vec<StatusRange>,
+	/// Fill in schema defaults for omitted optional parameters.
+	pub apply_defaults: bool,
+	/// If set, arguments are validated before calling the API.
+	pub validation: Option<ArgumentValidation>,
+	/// The HTTP version to pin calls to.
+	pub http_version: HttpVersionPreference,
+	/// Response media types to return as text, in addition to [DEFAULT_TEXT_CONTENT_TYPES].
+	pub text_content_types: Vec<String>,
+	/// Headers added to every call. Values are marked sensitive.
+	pub static_headers: Vec<(HeaderName, HeaderValue)>,
+	/// Header names never forwarded from tool arguments, in addition to [HOP_BY_HOP_HEADERS].
+	pub denied_headers: Vec<HeaderName>,
+	/// Headers whose values are kept out of logs and error messages.
+	pub sensitive_headers: SensitiveHeaders,
+	/// The credentials of the schema's security schemes, by scheme name.
+	pub credentials: security::Credentials,
+	/// Send the reserved characters of path parameter values, such as `/`, without encoding them.
+	/// `?` and `#` are always encoded.
+	pub allow_reserved_path_characters: bool,
+	/// The scheme of the API's URL.
+	pub scheme: HttpScheme,
+	/// If set, whether each call reused an idle connection is recorded.
+	pub connection_metrics: Option<ConnectionMetrics>,
+}
+
+/// Where a [Handler] records the connections its calls were sent on.
+#[derive(Debug, Clone)]
+pub struct ConnectionMetrics {
+	pub metrics: Arc<crate::mcp::relay::metrics::Metrics>,
+	pub server: String,
+	pub target: String,
+}
+
+impl ConnectionMetrics {
+	fn record(&self, response: &crate::http::Response) {
+		let reused = response
+			.extensions()
+			.get::<hyper_util_fork::client::legacy::ConnectionReused>()
+			.is_some_and(|r| r.0);
+		self.metrics.record(
+			crate::mcp::relay::metrics::OpenAPIConnection {
+				server: self.server.clone(),
+				target: self.target.clone(),
+				connection: if reused { "reused" } else { "new" }.to_string(),
+			},
+			(),
+		);
+	}
 }
 
 impl Handler {
+	/// Checks the arguments of a tool call against the tool's input schema, if validation is enabled.
+	/// Path parameters are always checked, since a bad value would change the URL of the call.
+	/// Unknown tools are left for [Handler::call_tool] to report.
+	pub fn validate_arguments(&self, name: &str, args: Option<&JsonObject>) -> Result<(), ErrorData> {
+		let Some((tool, info)) = self.tools.iter().find(|(t, _)| t.name == name) else {
+			return Ok(());
+		};
+		let violations = match &self.validation {
+			Some(validation) => {
+				// Arguments are checked as the caller sent them
+				let schema = match &info.flat {
+					Some(flat) => Value::Object(flat.schema.clone()),
+					None => Value::Object(tool.input_schema.as_ref().clone()),
+				};
+				let args = Value::Object(args.cloned().unwrap_or_default());
+				validate::validate(&schema, &args, validation.reject_unknown_properties)
+			},
+			None => path_violations(tool, info, args),
+		};
+		if violations.is_empty() {
+			return Ok(());
+		}
+		let summary = violations
+			.iter()
+			.map(|v| format!("{}: {}", v.path, v.message))
+			.collect::<Vec<_>>()
+			.join("; ");
+		Err(ErrorData::invalid_params(
+			format!("invalid arguments for tool '{name}': {summary}"),
+			Some(json!({ "violations": violations })),
+		))
+	}
+
 	/// We need to use the parse the schema to get the correct args.
 	/// They are in the json schema under the "properties" key.
 	/// Body is under the "body" key.
@@ -532,14 +1367,18 @@ impl Handler {
 		&self,
 		name: &str,
 		args: Option<JsonObject>,
-	) -> Result<String, anyhow::Error> {
-		let (_tool, info) = self
+	) -> Result<Content, anyhow::Error> {
+		let (tool, info) = self
 			.tools
 			.iter()
 			.find(|(t, _info)| t.name == name)
 			.ok_or_else(|| anyhow::anyhow!("tool {} not found", name))?;
 
 		let args = args.unwrap_or_default();
+		let args = match &info.flat {
+			Some(flat) => unflatten_arguments(flat, args),
+			None => args,
+		};
 
 		// --- Parameter Extraction ---
 		let path_params = args
@@ -547,25 +1386,56 @@ impl Handler {
 			.and_then(Value::as_object)
 			.cloned()
 			.unwrap_or_default();
-		let query_params = args
+		let mut query_params = args
 			.get(&*QUERY_NAME)
 			.and_then(Value::as_object)
 			.cloned()
 			.unwrap_or_default();
-		let header_params = args
+		let mut header_params = args
 			.get(&*HEADER_NAME)
 			.and_then(Value::as_object)
 			.cloned()
 			.unwrap_or_default();
-		let body_value = args.get(&*BODY_NAME).cloned();
+		let mut body_value = args.get(&*BODY_NAME).cloned();
+
+		// The credentials the operation requires replace any passed as arguments
+		let credentials = security::select(&info.security, &self.credentials).unwrap_or_else(|| {
+			tracing::debug!(
+				"No credentials are configured for the security requirements of tool '{}'",
+				name
+			);
+			Vec::new()
+		});
+		for credential in &credentials {
+			if let security::Credential::Query(param, _) = credential {
+				query_params.remove(param);
+			}
+		}
+
+		// Explicit arguments take precedence over defaults; path parameters are always required.
+		if self.apply_defaults {
+			let properties = tool.input_schema.get("properties");
+			let schema_for = |name: &str| properties.and_then(|p| p.get(name));
+			fill_defaults(schema_for(&QUERY_NAME), &mut query_params);
+			fill_defaults(schema_for(&HEADER_NAME), &mut header_params);
+			if let Some(Value::Object(body)) = body_value.as_mut() {
+				fill_defaults(schema_for(&BODY_NAME), body);
+			}
+		}
 
 		// --- URL Construction ---
 		let mut path = info.path.clone();
 		// Substitute path parameters into the path template
+		let path_encoding = if self.allow_reserved_path_characters {
+			PATH_RESERVED
+		} else {
+			PATH_SEGMENT
+		};
 		for (key, value) in &path_params {
 			match value {
 				Value::String(s_val) => {
-					path = path.replace(&format!("{{{key}}}"), s_val);
+					let encoded = utf8_percent_encode(s_val, path_encoding).to_string();
+					path = path.replace(&format!("{{{key}}}"), &encoded);
 				},
 				Value::Number(n_val) => {
 					path = path.replace(&format!("{{{key}}}"), n_val.to_string().as_str());
@@ -583,7 +1453,11 @@ impl Handler {
 
 		let base_url = format!(
 			"{}://{}:{}{}{}",
-			"http", self.host, self.port, self.prefix, path
+			self.scheme.as_str(),
+			self.host,
+			self.port,
+			self.prefix,
+			path
 		);
 
 		// --- Request Building ---
@@ -600,11 +1474,18 @@ impl Handler {
 		let query_string = if !query_params.is_empty() {
 			let mut pairs = Vec::new();
 			for (k, v) in query_params.iter() {
-				if let Some(s) = v.as_str() {
-					pairs.push(format!("{k}={s}"));
+				let key = encode_query(k);
+				if let Some(s) = scalar_text(v) {
+					pairs.push(format!("{key}={}", encode_query(&s)));
+				} else if let Some(values) = v.as_array().and_then(|a| query_values(a)) {
+					let style = info.query_styles.get(k).copied().unwrap_or_default();
+					match style.delimiter() {
+						Some(delimiter) => pairs.push(format!("{key}={}", values.join(delimiter))),
+						None => pairs.extend(values.iter().map(|v| format!("{key}={v}"))),
+					}
 				} else {
 					tracing::warn!(
-						"Query parameter '{}' for tool '{}' is not a string (value: {:?}), skipping",
+						"Query parameter '{}' for tool '{}' is not a scalar or array of scalars (value: {:?}), skipping",
 						k,
 						name,
 						v
@@ -620,18 +1501,55 @@ impl Handler {
 			String::new()
 		};
 
-		let uri = format!("{base_url}{query_string}");
+		// Query credentials are only added to the request
+		let request_uri =
+			credentials
+				.iter()
+				.fold(
+					format!("{base_url}{query_string}"),
+					|uri, credential| match credential {
+						security::Credential::Query(param, value) => {
+							let separator = if uri.contains('?') { '&' } else { '?' };
+							format!(
+								"{uri}{separator}{}={}",
+								encode_query(param),
+								encode_query(value.expose_secret())
+							)
+						},
+						security::Credential::Header(..) => uri,
+					},
+				);
 		let mut headers = HeaderMap::new();
-		let mut rb = http::Request::builder().method(method).uri(uri);
+		// Conditional requests are only passed through for reads whose schema declares the headers, so
+		// other tools never see a 304.
+		let conditional =
+			(method == Method::GET || method == Method::HEAD) && declares_conditional_headers(tool);
+		let mut rb = http::Request::builder().method(method).uri(&request_uri);
 
 		rb = rb.header(ACCEPT, HeaderValue::from_static("application/json"));
 		for (key, value) in &header_params {
-			if let Some(s_val) = value.as_str() {
+			if let Some(s_val) = scalar_text(value) {
+				let s_val = s_val.as_ref();
 				match (
 					HeaderName::from_bytes(key.as_bytes()),
 					HeaderValue::from_str(s_val),
 				) {
-					(Ok(h_name), Ok(h_value)) => {
+					(Ok(h_name), Ok(_)) if self.is_denied_header(&h_name) => {
+						tracing::debug!(
+							"Header '{}' is not forwarded for tool '{}', skipping",
+							key,
+							name
+						)
+					},
+					(Ok(h_name), Ok(_)) if !conditional && CONDITIONAL_HEADERS.contains(&h_name) => {
+						tracing::debug!(
+							"Conditional header '{}' is not declared by tool '{}', skipping",
+							key,
+							name
+						)
+					},
+					(Ok(h_name), Ok(mut h_value)) => {
+						h_value.set_sensitive(self.sensitive_headers.is_sensitive(&h_name));
 						rb = rb.header(h_name, h_value);
 					},
 					(Err(_), _) => tracing::warn!(
@@ -639,16 +1557,22 @@ impl Handler {
 						key,
 						name
 					),
-					(_, Err(_)) => tracing::warn!(
+					(h_name, Err(_)) => tracing::warn!(
 						"Invalid header value '{}' for header '{}' in tool '{}', skipping",
-						s_val,
+						h_name
+							.map(|h| self.sensitive_headers.loggable(&h, s_val))
+							.unwrap_or(s_val),
 						key,
 						name
 					),
 				}
 			} else {
+				let value = match HeaderName::from_bytes(key.as_bytes()) {
+					Ok(h) if self.sensitive_headers.is_sensitive(&h) => Value::String(REDACTED.to_string()),
+					_ => value.clone(),
+				};
 				tracing::warn!(
-					"Header parameter '{}' for tool '{}' is not a string (value: {:?}), skipping",
+					"Header parameter '{}' for tool '{}' is not a scalar (value: {:?}), skipping",
 					key,
 					name,
 					value
@@ -656,17 +1580,59 @@ impl Handler {
 			}
 		}
 		// Build request body
-		let body = if let Some(body_val) = body_value {
-			rb = rb.header(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-			serde_json::to_vec(&body_val)?
+		let (content_type, body) = if let Some(body_val) = body_value {
+			let (content_type, body) = encode_body(info.content_type.as_deref(), tool, &body_val)
+				.map_err(|e| anyhow::anyhow!("invalid body for tool '{}': {}", name, e))?;
+			(Some(content_type), body)
 		} else {
-			Vec::new()
+			(None, Vec::new())
 		};
 
 		// Build the final request
 		let mut request = rb
 			.body(body.into())
 			.map_err(|e| anyhow::anyhow!("Failed to build request: {}", e))?;
+		// Static headers replace any the caller passed, but the body's content type always wins.
+		for (h_name, h_value) in &self.static_headers {
+			if content_type.is_some() && *h_name == CONTENT_TYPE {
+				continue;
+			}
+			request
+				.headers_mut()
+				.insert(h_name.clone(), h_value.clone());
+		}
+		for credential in &credentials {
+			if let security::Credential::Header(h_name, h_value) = credential {
+				request
+					.headers_mut()
+					.insert(h_name.clone(), h_value.clone());
+			}
+		}
+		if let Some(content_type) = content_type {
+			request.headers_mut().insert(CONTENT_TYPE, content_type);
+		}
+		// Callers can't choose the encoding, since we can only decode the ones we advertise.
+		request.headers_mut().insert(
+			ACCEPT_ENCODING,
+			HeaderValue::from_static(ACCEPTED_ENCODINGS),
+		);
+		if let Some(version) = self.http_version.version() {
+			*request.version_mut() = version;
+		}
+
+		// Captured before sending, in case the upstream echoes the request in an error
+		let secrets =
+			self
+				.sensitive_headers
+				.secrets(request.headers())
+				.with(
+					credentials
+						.iter()
+						.filter_map(|credential| match credential {
+							security::Credential::Query(_, value) => Some(value.expose_secret().to_string()),
+							security::Credential::Header(..) => None,
+						}),
+				);
 
 		// Make the request
 		let target = Target::try_from((self.host.as_str(), self.port as u16))?;
@@ -676,21 +1642,73 @@ impl Handler {
 				req: request,
 				target,
 				transport: self.policies.backend_tls.clone().into(),
+				proxy: self.policies.outbound_proxy.clone(),
 			})
 			.await?;
+		if let Some(m) = &self.connection_metrics {
+			m.record(&response);
+		}
 
 		// Read response body
 		let status = response.status();
-		let body = String::from_utf8(
-			axum::body::to_bytes(response.into_body(), 2_097_152)
-				.await?
-				.to_vec(),
-		)?;
+		if conditional && status == StatusCode::NOT_MODIFIED {
+			// Report the cached copy is still current, rather than failing the call
+			let header = |h: HeaderName| {
+				response
+					.headers()
+					.get(h)
+					.and_then(|v| v.to_str().ok())
+					.map(str::to_string)
+			};
+			return Ok(Content::text(
+				json!({
+					"status": StatusCode::NOT_MODIFIED.as_u16(),
+					"notModified": true,
+					"etag": header(ETAG),
+					"lastModified": header(LAST_MODIFIED),
+				})
+				.to_string(),
+			));
+		}
+		let encoding = response.headers().get(CONTENT_ENCODING).cloned();
+		let content_type = response
+			.headers()
+			.get(CONTENT_TYPE)
+			.and_then(|v| v.to_str().ok())
+			.map(media_type);
+		let body = axum::body::to_bytes(response.into_body(), MAX_RESPONSE_SIZE).await?;
+		let body = decode_body(encoding.as_ref(), body, MAX_RESPONSE_SIZE)
+			.map_err(|e| anyhow::anyhow!("failed to decode response for tool '{}': {}", name, e))?;
 
 		// Check if the request was successful
 		if status.is_success() {
-			Ok(body)
+			match content_type {
+				Some(mime) if !self.is_text_content_type(&mime) => {
+					// Binary content can't be represented as text, so it is returned base64 encoded
+					let data = STANDARD.encode(&body);
+					if mime.starts_with("image/") {
+						Ok(Content::image(data, mime))
+					} else {
+						// The query is left out of the resource's URI, as it may carry arguments the caller
+						// would not want repeated
+						Ok(Content::resource(ResourceContents::BlobResourceContents {
+							uri: base_url,
+							mime_type: Some(mime),
+							blob: data,
+						}))
+					}
+				},
+				_ => Ok(Content::text(String::from_utf8(body.to_vec())?)),
+			}
+		} else if self.success_statuses.iter().any(|r| r.contains(status)) {
+			// The status is meaningful to the caller, so return it along with the body
+			let body = secrets.scrub(&String::from_utf8_lossy(&body));
+			let body = serde_json::from_str::<Value>(&body).unwrap_or(Value::String(body));
+			Ok(Content::text(
+				json!({ "status": status.as_u16(), "body": body }).to_string(),
+			))
 		} else {
+			let body = secrets.scrub(&String::from_utf8_lossy(&body));
 			Err(anyhow::anyhow!(
 				"Upstream API call for tool '{}' failed with status {}: {}",
 				name,
@@ -701,7 +1719,314 @@ impl Handler {
 	}
 
 	pub fn tools(&self) -> Vec<Tool> {
-		self.tools.clone().into_iter().map(|(t, _)| t).collect()
+		self
+			.tools
+			.iter()
+			.map(|(t, info)| match &info.flat {
+				Some(flat) => Tool {
+					input_schema: Arc::new(flat.schema.clone()),
+					..t.clone()
+				},
+				None => t.clone(),
+			})
+			.collect()
+	}
+
+	fn is_denied_header(&self, name: &HeaderName) -> bool {
+		HOP_BY_HOP_HEADERS.contains(name) || self.denied_headers.contains(name)
+	}
+
+	fn is_text_content_type(&self, mime: &str) -> bool {
+		DEFAULT_TEXT_CONTENT_TYPES
+			.iter()
+			.copied()
+			.chain(self.text_content_types.iter().map(String::as_str))
+			.any(|pattern| media_type_matches(pattern, mime))
+	}
+}
+
+/// The lowercased media type of a `Content-Type` header value, without parameters.
+fn media_type(content_type: &str) -> String {
+	content_type
+		.split(';')
+		.next()
+		.unwrap_or_default()
+		.trim()
+		.to_ascii_lowercase()
+}
+
+/// Whether `mime` matches `pattern`, which may use `*` for the whole subtype (`text/*`) or for the
+/// part before a structured syntax suffix (`application/*+json`).
+fn media_type_matches(pattern: &str, mime: &str) -> bool {
+	let (Some((ptype, psub)), Some((mtype, msub))) = (pattern.split_once('/'), mime.split_once('/'))
+	else {
+		return false;
+	};
+	if !ptype.eq_ignore_ascii_case(mtype) {
+		return false;
+	}
+	match psub.strip_prefix('*') {
+		Some("") => true,
+		Some(suffix) => msub.len() > suffix.len() && msub.ends_with(&suffix.to_ascii_lowercase()),
+		None => psub.eq_ignore_ascii_case(msub),
+	}
+}
+
+/// Undoes the codings listed in a `Content-Encoding` header, last applied first. Each decoded
+/// stage is limited to `limit` bytes, so a small compressed body can't expand without bound.
+fn decode_body(
+	encoding: Option<&HeaderValue>,
+	body: bytes::Bytes,
+	limit: usize,
+) -> anyhow::Result<bytes::Bytes> {
+	let Some(encoding) = encoding else {
+		return Ok(body);
+	};
+	let encoding = encoding.to_str()?;
+	let mut body = body;
+	for coding in encoding.rsplit(',').map(str::trim) {
+		let decoder: Box<dyn Read + '_> = match coding.to_ascii_lowercase().as_str() {
+			"" | "identity" => continue,
+			"gzip" | "x-gzip" => Box::new(flate2::read::MultiGzDecoder::new(&body[..])),
+			// `deflate` should be zlib wrapped, but some servers send a raw deflate stream.
+			"deflate" if is_zlib(&body) => Box::new(flate2::read::ZlibDecoder::new(&body[..])),
+			"deflate" => Box::new(flate2::read::DeflateDecoder::new(&body[..])),
+			"br" => Box::new(brotli::Decompressor::new(&body[..], 4096)),
+			other => anyhow::bail!("unsupported content encoding '{other}'"),
+		};
+		let mut out = Vec::new();
+		decoder.take(limit as u64 + 1).read_to_end(&mut out)?;
+		if out.len() > limit {
+			anyhow::bail!("decompressed body exceeds {limit} bytes");
+		}
+		body = out.into();
+	}
+	Ok(body)
+}
+
+/// Whether `body` starts with a zlib header (RFC 1950) for a deflate stream.
+fn is_zlib(body: &[u8]) -> bool {
+	match body {
+		[cmf, flg, ..] => cmf & 0x0f == 8 && ((u16::from(*cmf) << 8) | u16::from(*flg)) % 31 == 0,
+		_ => false,
+	}
+}
+
+/// Escapes a multipart field name for a quoted `Content-Disposition` parameter, percent-encoding
+/// the characters that would otherwise end the parameter or the header, as browsers do.
+fn disposition_param(name: &str) -> String {
+	name
+		.replace('"', "%22")
+		.replace('\r', "%0D")
+		.replace('\n', "%0A")
+		.replace('\0', "%00")
+}
+
+/// Encodes the `body` argument of a tool call as the operation's content type. Operations without a
+/// recorded content type are sent as JSON.
+fn encode_body(
+	content_type: Option<&str>,
+	tool: &Tool,
+	body: &Value,
+) -> anyhow::Result<(HeaderValue, Vec<u8>)> {
+	match content_type.unwrap_or(JSON_CONTENT_TYPE) {
+		MULTIPART_CONTENT_TYPE => {
+			let fields = body
+				.as_object()
+				.ok_or_else(|| anyhow::anyhow!("multipart body must be an object"))?;
+			let boundary = format!("agentgateway-{:016x}", rand::random::<u64>());
+			let mut out = Vec::new();
+			for (k, v) in fields {
+				let name = disposition_param(k);
+				out.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+				if is_file_field(tool, k) {
+					// Binary content can't be represented in JSON, so files are passed base64 encoded.
+					let data = v
+						.as_str()
+						.and_then(|s| STANDARD.decode(s).ok())
+						.ok_or_else(|| anyhow::anyhow!("file field '{k}' must be a base64 encoded string"))?;
+					out.extend_from_slice(
+						format!(
+							"Content-Disposition: form-data; name=\"{name}\"; filename=\"{name}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+						)
+						.as_bytes(),
+					);
+					out.extend_from_slice(&data);
+				} else {
+					out.extend_from_slice(
+						format!("Content-Disposition: form-data; name=\"{name}\"\r\n\r\n").as_bytes(),
+					);
+					out.extend_from_slice(form_value(v).as_bytes());
+				}
+				out.extend_from_slice(b"\r\n");
+			}
+			out.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+			let content_type =
+				HeaderValue::from_str(&format!("{MULTIPART_CONTENT_TYPE}; boundary={boundary}"))?;
+			Ok((content_type, out))
+		},
+		FORM_CONTENT_TYPE => {
+			let fields = body
+				.as_object()
+				.ok_or_else(|| anyhow::anyhow!("form body must be an object"))?;
+			let mut form = url::form_urlencoded::Serializer::new(String::new());
+			for (k, v) in fields {
+				form.append_pair(k, &form_value(v));
+			}
+			Ok((
+				HeaderValue::from_static(FORM_CONTENT_TYPE),
+				form.finish().into_bytes(),
+			))
+		},
+		_ => Ok((
+			HeaderValue::from_static(JSON_CONTENT_TYPE),
+			serde_json::to_vec(body)?,
+		)),
+	}
+}
+
+/// Whether a body field holds file contents, as indicated by `format: binary` in its schema.
+fn is_file_field(tool: &Tool, field: &str) -> bool {
+	tool
+		.input_schema
+		.get("properties")
+		.and_then(|p| p.get(BODY_NAME.as_str()))
+		.and_then(|b| b.get("properties"))
+		.and_then(|p| p.get(field))
+		.and_then(|f| f.get("format"))
+		.and_then(Value::as_str)
+		== Some("binary")
+}
+
+/// Whether the tool declares `If-None-Match` or `If-Modified-Since` as header parameters.
+fn declares_conditional_headers(tool: &Tool) -> bool {
+	tool
+		.input_schema
+		.get("properties")
+		.and_then(|p| p.get(HEADER_NAME.as_str()))
+		.and_then(|h| h.get("properties"))
+		.and_then(Value::as_object)
+		.is_some_and(|headers| {
+			headers.keys().any(|k| {
+				CONDITIONAL_HEADERS
+					.iter()
+					.any(|h| h.as_str().eq_ignore_ascii_case(k))
+			})
+		})
+}
+
+/// Sets properties missing from `values` to their `default` in `schema`, if one is defined. Nested
+/// objects that are present are filled in the same way.
+fn fill_defaults(schema: Option<&Value>, values: &mut JsonObject) {
+	let Some(properties) = schema
+		.and_then(|s| s.get("properties"))
+		.and_then(Value::as_object)
+	else {
+		return;
+	};
+	for (k, property) in properties {
+		match values.get_mut(k) {
+			Some(Value::Object(nested)) => fill_defaults(Some(property), nested),
+			Some(_) => {},
+			None => {
+				if let Some(default) = property.get("default") {
+					values.insert(k.clone(), default.clone());
+				}
+			},
+		}
+	}
+}
+
+/// The characters encoded in path parameter values: all but letters, digits and `-._~`.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+	.remove(b'-')
+	.remove(b'.')
+	.remove(b'_')
+	.remove(b'~');
+
+/// Like [PATH_SEGMENT], but leaving the reserved characters of RFC 3986 as they are. `?` and `#`
+/// are still encoded, as they would end the path.
+const PATH_RESERVED: &AsciiSet = &PATH_SEGMENT
+	.remove(b':')
+	.remove(b'/')
+	.remove(b'[')
+	.remove(b']')
+	.remove(b'@')
+	.remove(b'!')
+	.remove(b'$')
+	.remove(b'&')
+	.remove(b'\'')
+	.remove(b'(')
+	.remove(b')')
+	.remove(b'*')
+	.remove(b'+')
+	.remove(b',')
+	.remove(b';')
+	.remove(b'=');
+
+/// Checks the path parameters of a call against their schemas, including missing required ones.
+fn path_violations(
+	tool: &Tool,
+	info: &UpstreamOpenAPICall,
+	args: Option<&JsonObject>,
+) -> Vec<validate::Violation> {
+	let Some(schema) = tool
+		.input_schema
+		.get("properties")
+		.and_then(|p| p.get(&*PATH_NAME))
+	else {
+		return vec![];
+	};
+	let args = args.cloned().unwrap_or_default();
+	let mut args = match &info.flat {
+		Some(flat) => unflatten_arguments(flat, args),
+		None => args,
+	};
+	let path = args
+		.remove(&*PATH_NAME)
+		.unwrap_or_else(|| Value::Object(JsonObject::new()));
+	validate::validate(schema, &path, false)
+		.into_iter()
+		.map(|v| validate::Violation {
+			path: if v.path.is_empty() {
+				PATH_NAME.to_string()
+			} else {
+				format!("{}.{}", *PATH_NAME, v.path)
+			},
+			message: v.message,
+		})
+		.collect()
+}
+
+/// Percent-encodes a query string key or value.
+fn encode_query(s: &str) -> String {
+	url::form_urlencoded::byte_serialize(s.as_bytes()).collect()
+}
+
+/// The text sent for a scalar parameter value: a string as is, and a number or boolean as written
+/// in JSON. `None` for anything else.
+fn scalar_text(v: &Value) -> Option<Cow<'_, str>> {
+	match v {
+		Value::String(s) => Some(Cow::Borrowed(s)),
+		Value::Number(n) => Some(Cow::Owned(n.to_string())),
+		Value::Bool(b) => Some(Cow::Owned(b.to_string())),
+		_ => None,
+	}
+}
+
+/// Encodes each element of an array query parameter. Returns `None` if any element is not a scalar.
+fn query_values(values: &[Value]) -> Option<Vec<String>> {
+	values
+		.iter()
+		.map(|v| scalar_text(v).map(|s| encode_query(&s)))
+		.collect()
+}
+
+/// Converts a JSON value to the text sent for a form field.
+fn form_value(v: &Value) -> String {
+	match v {
+		Value::String(s) => s.clone(),
+		other => other.to_string(),
 	}
 }
 
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use ::http::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use ::http::{HeaderName, HeaderValue, Method, StatusCode};
+use anyhow::{Context as _, anyhow, bail};
+use openapiv3::OpenAPI;
+
+use crate::client;
+use crate::http::backendtls::SYSTEM_TRUST;
+use crate::http::outboundproxy::OutboundProxy;
+use crate::types::agent::Target;
+use crate::*;
+
+#[cfg(test)]
+#[path = "remote_tests.rs"]
+mod tests;
+
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+// How long fetching the schema, including reading the body, may take.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+// Schemas larger than this are rejected.
+const MAX_SCHEMA_SIZE: usize = 10_485_760;
+
+/// Configuration for fetching an OpenAPI schema from a URL.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct RemoteSchemaConfig {
+	/// URL of the schema. Both `http` and `https` URLs are supported.
+	pub url: String,
+	/// Headers to send when fetching the schema, for example to authenticate to the server hosting it.
+	/// These are separate from any `backendAuth` policy, which only applies to tool calls.
+	#[serde(
+		default,
+		skip_serializing_if = "HashMap::is_empty",
+		serialize_with = "ser_redact"
+	)]
+	pub headers: HashMap<String, String>,
+	/// How long a fetched schema is used before checking the URL for changes. Defaults to 5 minutes.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "serde_dur_option"
+	)]
+	#[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+	pub refresh_interval: Option<Duration>,
+}
+
+/// An OpenAPI schema fetched from a URL.
+/// The last successfully fetched schema is shared by every session using the target. Once it is older
+/// than the refresh interval, it is revalidated using the `ETag` and `Last-Modified` headers from the
+/// previous response.
+#[derive(Debug)]
+pub struct RemoteSchema {
+	config: RemoteSchemaConfig,
+	state: std::sync::Mutex<State>,
+	// Held while fetching, so concurrent refreshes do not fetch the schema more than once.
+	fetching: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+	cached: Option<Cached>,
+	// Why the schema could not be fetched, if it never has been.
+	error: Option<String>,
+}
+
+#[derive(Debug)]
+struct Cached {
+	schema: Arc<OpenAPI>,
+	etag: Option<HeaderValue>,
+	last_modified: Option<HeaderValue>,
+	checked: Instant,
+}
+
+/// The outcome of checking a remote schema for changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refresh {
+	/// A new copy of the schema was fetched.
+	Updated,
+	/// The server reported the schema has not changed.
+	NotModified,
+	/// The schema could not be fetched.
+	Failed,
+}
+
+impl Refresh {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Refresh::Updated => "updated",
+			Refresh::NotModified => "not_modified",
+			Refresh::Failed => "failed",
+		}
+	}
+}
+
+impl serde::Serialize for RemoteSchema {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.config.serialize(serializer)
+	}
+}
+
+impl RemoteSchema {
+	pub fn new(config: RemoteSchemaConfig) -> Self {
+		Self {
+			config,
+			state: Default::default(),
+			fetching: Default::default(),
+		}
+	}
+
+	/// Returns the last successfully fetched schema, without fetching it.
+	pub fn schema(&self) -> anyhow::Result<Arc<OpenAPI>> {
+		let state = self.state.lock().expect("mutex acquired");
+		match (&state.cached, &state.error) {
+			(Some(c), _) => Ok(c.schema.clone()),
+			(None, Some(e)) => Err(anyhow!(
+				"failed to fetch OpenAPI schema from {}: {e}",
+				self.config.url
+			)),
+			(None, None) => Err(anyhow!(
+				"OpenAPI schema from {} has not been fetched",
+				self.config.url
+			)),
+		}
+	}
+
+	fn due(&self) -> bool {
+		let interval = self
+			.config
+			.refresh_interval
+			.unwrap_or(DEFAULT_REFRESH_INTERVAL);
+		let state = self.state.lock().expect("mutex acquired");
+		state
+			.cached
+			.as_ref()
+			.is_none_or(|c| c.checked.elapsed() >= interval)
+	}
+
+	/// Fetches the schema if it has never been fetched or is due for a refresh, returning the outcome
+	/// if it was checked. A failed refresh keeps the last known good schema, see [Self::schema].
+	pub async fn refresh(
+		&self,
+		client: &client::Client,
+		proxy: Option<OutboundProxy>,
+	) -> Option<Refresh> {
+		if !self.due() {
+			return None;
+		}
+		let _fetching = self.fetching.lock().await;
+		// Another caller may have refreshed the schema while this one waited.
+		if !self.due() {
+			return None;
+		}
+		let (etag, last_modified) = {
+			let state = self.state.lock().expect("mutex acquired");
+			match &state.cached {
+				Some(c) => (c.etag.clone(), c.last_modified.clone()),
+				None => (None, None),
+			}
+		};
+		let res = tokio::time::timeout(
+			FETCH_TIMEOUT,
+			self.fetch(client, proxy, etag, last_modified),
+		)
+		.await
+		.unwrap_or_else(|_| Err(anyhow!("timed out after {FETCH_TIMEOUT:?}")));
+		let mut state = self.state.lock().expect("mutex acquired");
+		match res {
+			Ok(Some(fetched)) => {
+				state.cached = Some(fetched);
+				state.error = None;
+				Some(Refresh::Updated)
+			},
+			Ok(None) => {
+				if let Some(c) = state.cached.as_mut() {
+					c.checked = Instant::now();
+				}
+				Some(Refresh::NotModified)
+			},
+			Err(e) => {
+				match state.cached.as_mut() {
+					Some(c) => {
+						warn!(
+							"failed to refresh OpenAPI schema from {}, using last known good schema: {e:#}",
+							self.config.url
+						);
+						// Wait for the next interval before trying again.
+						c.checked = Instant::now();
+					},
+					None => state.error = Some(format!("{e:#}")),
+				}
+				Some(Refresh::Failed)
+			},
+		}
+	}
+
+	/// Fetches the schema. Returns `None` if the server reports the cached schema is still current.
+	async fn fetch(
+		&self,
+		client: &client::Client,
+		proxy: Option<OutboundProxy>,
+		etag: Option<HeaderValue>,
+		last_modified: Option<HeaderValue>,
+	) -> anyhow::Result<Option<Cached>> {
+		let conditional = etag.is_some() || last_modified.is_some();
+		let url = url::Url::parse(&self.config.url)?;
+		let transport = match url.scheme() {
+			"http" => client::Transport::Plaintext,
+			"https" => client::Transport::Tls(SYSTEM_TRUST.clone()),
+			scheme => bail!("unsupported URL scheme {scheme}"),
+		};
+		let host = url.host_str().ok_or_else(|| anyhow!("URL has no host"))?;
+		let port = url
+			.port_or_known_default()
+			.ok_or_else(|| anyhow!("URL has no port"))?;
+		let target = Target::try_from((host, port))?;
+
+		let mut rb = ::http::Request::builder()
+			.method(Method::GET)
+			.uri(url.as_str());
+		for (k, v) in &self.config.headers {
+			rb = rb.header(HeaderName::try_from(k)?, HeaderValue::try_from(v)?);
+		}
+		if let Some(etag) = etag {
+			rb = rb.header(IF_NONE_MATCH, etag);
+		}
+		if let Some(last_modified) = last_modified {
+			rb = rb.header(IF_MODIFIED_SINCE, last_modified);
+		}
+		let req = rb.body(crate::http::Body::empty())?;
+
+		let resp = client
+			.call(client::Call {
+				req,
+				target,
+				transport,
+				proxy,
+			})
+			.await?;
+		let status = resp.status();
+		if status == StatusCode::NOT_MODIFIED && conditional {
+			return Ok(None);
+		}
+		if !status.is_success() {
+			bail!("received status code {status}");
+		}
+		let etag = resp.headers().get(ETAG).cloned();
+		let last_modified = resp.headers().get(LAST_MODIFIED).cloned();
+		let body = axum::body::to_bytes(resp.into_body(), MAX_SCHEMA_SIZE).await?;
+		let body = std::str::from_utf8(&body).context("schema is not valid UTF-8")?;
+		let schema = super::parse_schema(body, Some(Path::new(url.path())))?;
+		Ok(Some(Cached {
+			schema: Arc::new(schema),
+			etag,
+			last_modified,
+			checked: Instant::now(),
+		}))
+	}
+}
@@ -0,0 +1,164 @@
+use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::*;
+use crate::http::outboundproxy::LocalOutboundProxy;
+
+const SCHEMA: &str = r#"{
+	"openapi": "3.0.0",
+	"info": { "title": "users", "version": "1.0" },
+	"paths": {
+		"/users": { "get": { "operationId": "list_users", "responses": {} } }
+	}
+}"#;
+
+fn client() -> client::Client {
+	client::Client::new(
+		&client::Config {
+			resolver_cfg: ResolverConfig::default(),
+			resolver_opts: ResolverOpts::default(),
+		},
+		None,
+	)
+}
+
+fn remote(server: &MockServer, refresh_interval: Duration) -> RemoteSchema {
+	RemoteSchema::new(RemoteSchemaConfig {
+		url: format!("{}/openapi.json", server.uri()),
+		headers: HashMap::from([("authorization".to_string(), "Bearer secret".to_string())]),
+		refresh_interval: Some(refresh_interval),
+	})
+}
+
+#[tokio::test]
+async fn test_fetch_and_revalidate() {
+	let server = MockServer::start().await;
+	Mock::given(method("GET"))
+		.and(path("/openapi.json"))
+		.and(header("if-none-match", "\"v1\""))
+		.respond_with(ResponseTemplate::new(304))
+		.mount(&server)
+		.await;
+	Mock::given(method("GET"))
+		.and(path("/openapi.json"))
+		.and(header("authorization", "Bearer secret"))
+		.respond_with(
+			ResponseTemplate::new(200)
+				.insert_header("etag", "\"v1\"")
+				.set_body_string(SCHEMA),
+		)
+		.mount(&server)
+		.await;
+
+	let client = client();
+	let remote = remote(&server, Duration::ZERO);
+	assert_eq!(remote.refresh(&client, None).await, Some(Refresh::Updated));
+	let first = remote.schema().unwrap();
+	assert!(first.paths.paths.contains_key("/users"));
+
+	assert_eq!(
+		remote.refresh(&client, None).await,
+		Some(Refresh::NotModified)
+	);
+	assert!(Arc::ptr_eq(&first, &remote.schema().unwrap()));
+
+	// A failed refresh keeps serving the last known good schema
+	server.reset().await;
+	Mock::given(method("GET"))
+		.respond_with(ResponseTemplate::new(500))
+		.mount(&server)
+		.await;
+	assert_eq!(remote.refresh(&client, None).await, Some(Refresh::Failed));
+	assert!(Arc::ptr_eq(&first, &remote.schema().unwrap()));
+}
+
+#[tokio::test]
+async fn test_cached_within_interval() {
+	let server = MockServer::start().await;
+	Mock::given(method("GET"))
+		.and(path("/openapi.json"))
+		.respond_with(ResponseTemplate::new(200).set_body_string(SCHEMA))
+		.expect(1)
+		.mount(&server)
+		.await;
+
+	let client = client();
+	let remote = remote(&server, Duration::from_secs(60));
+	assert!(remote.schema().is_err());
+	assert_eq!(remote.refresh(&client, None).await, Some(Refresh::Updated));
+	let first = remote.schema().unwrap();
+	assert_eq!(remote.refresh(&client, None).await, None);
+	assert!(Arc::ptr_eq(&first, &remote.schema().unwrap()));
+}
+
+#[tokio::test]
+async fn test_initial_fetch_failure() {
+	let server = MockServer::start().await;
+	Mock::given(method("GET"))
+		.respond_with(ResponseTemplate::new(404))
+		.mount(&server)
+		.await;
+
+	let remote = remote(&server, Duration::ZERO);
+	assert_eq!(remote.refresh(&client(), None).await, Some(Refresh::Failed));
+	let err = remote.schema().unwrap_err();
+	assert!(format!("{err:#}").contains("404"), "{err:#}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_fetch_timeout() {
+	let server = MockServer::start().await;
+	Mock::given(method("GET"))
+		.respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(3600)))
+		.mount(&server)
+		.await;
+
+	let remote = remote(&server, Duration::ZERO);
+	assert_eq!(remote.refresh(&client(), None).await, Some(Refresh::Failed));
+	let err = remote.schema().unwrap_err();
+	assert!(format!("{err:#}").contains("timed out"), "{err:#}");
+}
+
+#[tokio::test]
+async fn test_fetch_through_proxy() {
+	// A proxy that refuses every request, so the fetch only fails if it went through it
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let proxy_addr = listener.local_addr().unwrap();
+	let (tx, mut connects) = tokio::sync::mpsc::unbounded_channel();
+	tokio::spawn(async move {
+		while let Ok((mut stream, _)) = listener.accept().await {
+			let mut head = Vec::new();
+			while !head.ends_with(b"\r\n\r\n") {
+				head.push(stream.read_u8().await.unwrap());
+			}
+			let _ = tx.send(String::from_utf8(head).unwrap());
+			let _ = stream.write_all(b"HTTP/1.1 403 Forbidden\r\n\r\n").await;
+		}
+	});
+
+	let server = MockServer::start().await;
+	Mock::given(method("GET"))
+		.respond_with(ResponseTemplate::new(200).set_body_string(SCHEMA))
+		.expect(0)
+		.mount(&server)
+		.await;
+	let outbound: LocalOutboundProxy = serde_json::from_value(
+		serde_json::json!({"url": format!("http://{proxy_addr}"), "noProxy": []}),
+	)
+	.unwrap();
+	let remote = remote(&server, Duration::ZERO);
+	assert_eq!(
+		remote
+			.refresh(&client(), Some(outbound.try_into().unwrap()))
+			.await,
+		Some(Refresh::Failed)
+	);
+	let head = connects.recv().await.unwrap();
+	assert!(
+		head.starts_with(&format!("GET http://{}/", server.address())),
+		"{head}"
+	);
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use openapiv3::{APIKeyLocation, OpenAPI, ReferenceOr, SecurityScheme};
+use reqwest::header::{AUTHORIZATION, HeaderName, HeaderValue};
+use secrecy::{ExposeSecret, SecretString};
+
+use tracing::warn;
+
+use crate::types::agent::SecurityCredential;
+
+/// A credential sent with calls to operations that require its security scheme.
+#[derive(Debug, Clone)]
+pub enum Credential {
+	/// An API key or bearer token sent in a header. The value is marked sensitive.
+	Header(HeaderName, HeaderValue),
+	/// An API key sent as a query parameter.
+	Query(String, SecretString),
+}
+
+/// Credentials by scheme name.
+pub type Credentials = Arc<HashMap<String, Credential>>;
+
+/// The credentials of a target, resolved against the schema they were last resolved for. A static
+/// schema is resolved when the configuration is loaded; a remote one each time a new copy of it is
+/// fetched, rather than for every session.
+#[derive(Debug, Clone, Default)]
+pub struct Resolved(Arc<Mutex<Option<ResolvedFor>>>);
+
+type ResolvedFor = (Arc<OpenAPI>, Credentials);
+
+impl Resolved {
+	/// Returns the credentials for `schema`, resolving them with [resolve] and logging its warnings if
+	/// they were resolved for another schema, or never.
+	pub fn get(
+		&self,
+		target: &str,
+		schema: &Arc<OpenAPI>,
+		configured: &[SecurityCredential],
+	) -> anyhow::Result<Credentials> {
+		let mut resolved = self.0.lock().expect("mutex acquired");
+		if let Some((s, credentials)) = resolved.as_ref()
+			&& Arc::ptr_eq(s, schema)
+		{
+			return Ok(credentials.clone());
+		}
+		let (credentials, warnings) = resolve(schema, configured)
+			.map_err(|e| e.context(format!("invalid security credentials for target {target}")))?;
+		for warning in warnings {
+			warn!("OpenAPI target {target}: {warning}");
+		}
+		let credentials = Arc::new(credentials);
+		*resolved = Some((schema.clone(), credentials.clone()));
+		Ok(credentials)
+	}
+}
+
+/// The security requirements of an operation, from the operation or else the whole schema. Each
+/// entry is an alternative, listing the schemes that must all be satisfied; an empty entry means
+/// the operation can also be called without credentials.
+pub fn requirements(open_api: &OpenAPI, op: &openapiv3::Operation) -> Vec<Vec<String>> {
+	op.security
+		.as_ref()
+		.or(open_api.security.as_ref())
+		.into_iter()
+		.flatten()
+		.map(|r| r.keys().cloned().collect())
+		.collect()
+}
+
+/// Matches the credentials configured for a target to the security schemes declared by its schema.
+/// API keys in a header or query parameter and HTTP bearer tokens are supported. Returns the
+/// credentials by scheme name, along with warnings for the schemes that can't be applied, such as
+/// OAuth2 flows, and for credentials of schemes the schema does not declare.
+pub fn resolve(
+	open_api: &OpenAPI,
+	configured: &[SecurityCredential],
+) -> anyhow::Result<(HashMap<String, Credential>, Vec<String>)> {
+	let schemes = open_api.components.as_ref().map(|c| &c.security_schemes);
+	let mut credentials = HashMap::new();
+	let mut warnings = Vec::new();
+	for (name, scheme) in schemes.into_iter().flatten() {
+		let unsupported = |what: &str| {
+			format!(
+				"security scheme '{name}' is not supported ({what}); configure its credentials with backend auth or static headers"
+			)
+		};
+		let scheme = match scheme {
+			ReferenceOr::Item(scheme) => scheme,
+			ReferenceOr::Reference { .. } => {
+				warnings.push(unsupported("a reference"));
+				continue;
+			},
+		};
+		let injection = match scheme {
+			SecurityScheme::APIKey {
+				location: APIKeyLocation::Header,
+				name: header,
+				..
+			} => Injection::Header(HeaderName::from_bytes(header.as_bytes()).map_err(|_| {
+				anyhow::anyhow!("invalid header name '{header}' for security scheme '{name}'")
+			})?),
+			SecurityScheme::APIKey {
+				location: APIKeyLocation::Query,
+				name: param,
+				..
+			} => Injection::Query(param.clone()),
+			SecurityScheme::APIKey {
+				location: APIKeyLocation::Cookie,
+				..
+			} => {
+				warnings.push(unsupported("API key in a cookie"));
+				continue;
+			},
+			SecurityScheme::HTTP { scheme, .. } if scheme.eq_ignore_ascii_case("bearer") => {
+				Injection::Bearer
+			},
+			SecurityScheme::HTTP { scheme, .. } => {
+				warnings.push(unsupported(&format!("HTTP {scheme}")));
+				continue;
+			},
+			SecurityScheme::OAuth2 { .. } => {
+				warnings.push(unsupported("OAuth2"));
+				continue;
+			},
+			SecurityScheme::OpenIDConnect { .. } => {
+				warnings.push(unsupported("OpenID Connect"));
+				continue;
+			},
+		};
+		let Some(configured) = configured.iter().find(|c| c.scheme == *name) else {
+			continue;
+		};
+		let credential = match injection {
+			Injection::Header(header) => {
+				Credential::Header(header, header_value(name, "", &configured.value)?)
+			},
+			Injection::Bearer => Credential::Header(
+				AUTHORIZATION,
+				header_value(name, "Bearer ", &configured.value)?,
+			),
+			Injection::Query(param) => Credential::Query(param, configured.value.clone()),
+		};
+		credentials.insert(name.clone(), credential);
+	}
+	for c in configured {
+		if !schemes.is_some_and(|s| s.contains_key(&c.scheme)) {
+			warnings.push(format!(
+				"a credential is configured for security scheme '{}', which the schema does not declare",
+				c.scheme
+			));
+		}
+	}
+	Ok((credentials, warnings))
+}
+
+/// Picks the credentials for a call: those of the first alternative every scheme of which has a
+/// credential. Returns None if no alternative can be satisfied.
+pub fn select<'a>(
+	requirements: &[Vec<String>],
+	credentials: &'a HashMap<String, Credential>,
+) -> Option<Vec<&'a Credential>> {
+	if requirements.is_empty() {
+		return Some(Vec::new());
+	}
+	requirements.iter().find_map(|alternative| {
+		alternative
+			.iter()
+			.map(|scheme| credentials.get(scheme))
+			.collect::<Option<Vec<_>>>()
+	})
+}
+
+enum Injection {
+	Header(HeaderName),
+	Query(String),
+	Bearer,
+}
+
+fn header_value(scheme: &str, prefix: &str, value: &SecretString) -> anyhow::Result<HeaderValue> {
+	let mut value = HeaderValue::from_str(&format!("{prefix}{}", value.expose_secret()))
+		.map_err(|_| anyhow::anyhow!("invalid credential for security scheme '{scheme}'"))?;
+	value.set_sensitive(true);
+	Ok(value)
+}
+
+#[cfg(test)]
+#[path = "security_tests.rs"]
+mod tests;
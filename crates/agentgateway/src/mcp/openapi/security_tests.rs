@@ -0,0 +1,155 @@
+use super::*;
+use crate::mcp::openapi::parse_schema;
+
+const SECURED_YAML: &str = r#"
+openapi: 3.0.0
+info: { title: secured, version: "1" }
+security:
+  - token: []
+paths:
+  /default:
+    get:
+      operationId: usesDefault
+      responses: {}
+  /either:
+    get:
+      operationId: either
+      security:
+        - key: []
+          tenant: []
+        - token: []
+      responses: {}
+  /public:
+    get:
+      operationId: public
+      security: []
+      responses: {}
+components:
+  securitySchemes:
+    key:
+      type: apiKey
+      in: header
+      name: X-API-Key
+    tenant:
+      type: apiKey
+      in: query
+      name: tenant
+    token:
+      type: http
+      scheme: bearer
+    oauth:
+      type: oauth2
+      flows:
+        clientCredentials:
+          tokenUrl: https://example.com/token
+          scopes: {}
+    session:
+      type: apiKey
+      in: cookie
+      name: session
+"#;
+
+fn credential(scheme: &str, value: &str) -> SecurityCredential {
+	SecurityCredential {
+		scheme: scheme.to_string(),
+		value: SecretString::from(value.to_string()),
+	}
+}
+
+fn operation<'a>(open_api: &'a OpenAPI, path: &str) -> &'a openapiv3::Operation {
+	open_api.paths.paths[path]
+		.as_item()
+		.unwrap()
+		.get
+		.as_ref()
+		.unwrap()
+}
+
+#[test]
+fn test_requirements() {
+	let open_api = parse_schema(SECURED_YAML, None).unwrap();
+	let requirements = |path| requirements(&open_api, operation(&open_api, path));
+	assert_eq!(requirements("/default"), vec![vec!["token"]]);
+	assert_eq!(
+		requirements("/either"),
+		vec![vec!["key", "tenant"], vec!["token"]]
+	);
+	assert!(requirements("/public").is_empty());
+}
+
+#[test]
+fn test_resolve() {
+	let open_api = parse_schema(SECURED_YAML, None).unwrap();
+	let (credentials, warnings) = resolve(
+		&open_api,
+		&[
+			credential("key", "api-key"),
+			credential("tenant", "acme"),
+			credential("token", "secret-token"),
+			credential("missing", "x"),
+		],
+	)
+	.unwrap();
+
+	let Credential::Header(name, value) = &credentials["key"] else {
+		panic!("expected a header");
+	};
+	assert_eq!(
+		(name.as_str(), value.to_str().unwrap()),
+		("x-api-key", "api-key")
+	);
+	assert!(value.is_sensitive());
+	let Credential::Header(name, value) = &credentials["token"] else {
+		panic!("expected a header");
+	};
+	assert_eq!(
+		(name, value.to_str().unwrap()),
+		(&AUTHORIZATION, "Bearer secret-token")
+	);
+	let Credential::Query(param, value) = &credentials["tenant"] else {
+		panic!("expected a query parameter");
+	};
+	assert_eq!((param.as_str(), value.expose_secret()), ("tenant", "acme"));
+
+	// Unsupported schemes and unknown credentials are reported, rather than ignored
+	assert_eq!(warnings.len(), 3, "{warnings:?}");
+	assert!(warnings[0].contains("'oauth' is not supported (OAuth2)"));
+	assert!(warnings[1].contains("'session' is not supported (API key in a cookie)"));
+	assert!(warnings[2].contains("'missing', which the schema does not declare"));
+}
+
+#[test]
+fn test_resolved_once_per_schema() {
+	let open_api = Arc::new(parse_schema(SECURED_YAML, None).unwrap());
+	let resolved = Resolved::default();
+	let first = resolved
+		.get("api", &open_api, &[credential("key", "k")])
+		.unwrap();
+	assert!(first.contains_key("key"));
+	// The same schema is not resolved again
+	let again = resolved.get("api", &open_api, &[]).unwrap();
+	assert!(Arc::ptr_eq(&first, &again));
+	// A new copy of the schema, as fetched by a refresh, is
+	let refreshed = Arc::new(parse_schema(SECURED_YAML, None).unwrap());
+	assert!(resolved.get("api", &refreshed, &[]).unwrap().is_empty());
+}
+
+#[test]
+fn test_select() {
+	let open_api = parse_schema(SECURED_YAML, None).unwrap();
+	let key_only = resolve(&open_api, &[credential("key", "k")]).unwrap().0;
+	let key_and_tenant = resolve(
+		&open_api,
+		&[credential("key", "k"), credential("tenant", "t")],
+	)
+	.unwrap()
+	.0;
+	let either = requirements(&open_api, operation(&open_api, "/either"));
+
+	// Every scheme of an alternative must have a credential
+	assert!(select(&either, &key_only).is_none());
+	let selected = select(&either, &key_and_tenant).unwrap();
+	assert_eq!(selected.len(), 2);
+	// Operations without requirements need no credentials
+	assert!(select(&[], &key_only).unwrap().is_empty());
+}
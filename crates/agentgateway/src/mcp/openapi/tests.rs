@@ -1,15 +1,25 @@
 use std::borrow::Cow;
 use std::sync::Arc;
+use std::time::Duration;
 
 use hickory_resolver::config::{ResolverConfig, ResolverOpts};
 use rmcp::model::Tool;
 use serde_json::json;
-use wiremock::matchers::{body_json, header, method, path, query_param};
+use wiremock::matchers::{body_json, header, headers, method, path, query_param};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 use super::*;
 use crate::client::Client;
 
+// The text of a tool call result that is expected to be text
+fn text(content: Content) -> String {
+	content
+		.as_text()
+		.expect("expected text content")
+		.text
+		.clone()
+}
+
 // Helper to create a handler and mock server for tests
 async fn setup() -> (MockServer, Handler) {
 	let server = MockServer::start().await;
@@ -62,6 +72,11 @@ async fn setup() -> (MockServer, Handler) {
 	let upstream_call_get = UpstreamOpenAPICall {
 		method: "GET".to_string(),
 		path: "/users/{user_id}".to_string(),
+		content_type: None,
+		query_styles: HashMap::new(),
+		security: vec![],
+		flat: None,
+		example: None,
 	};
 
 	let test_tool_post = Tool {
@@ -103,6 +118,11 @@ async fn setup() -> (MockServer, Handler) {
 	let upstream_call_post = UpstreamOpenAPICall {
 		method: "POST".to_string(),
 		path: "/users".to_string(),
+		content_type: Some("application/json".to_string()),
+		query_styles: HashMap::new(),
+		security: vec![],
+		flat: None,
+		example: None,
 	};
 
 	let handler = Handler {
@@ -115,6 +135,18 @@ async fn setup() -> (MockServer, Handler) {
 			(test_tool_post, upstream_call_post),
 		],
 		policies: BackendPolicies::default(),
+		success_statuses: vec![],
+		apply_defaults: false,
+		validation: None,
+		http_version: Default::default(),
+		text_content_types: vec![],
+		static_headers: vec![],
+		denied_headers: vec![],
+		sensitive_headers: Default::default(),
+		credentials: Default::default(),
+		allow_reserved_path_characters: false,
+		scheme: HttpScheme::Http,
+		connection_metrics: None,
 	};
 
 	(server, handler)
@@ -139,7 +171,7 @@ async fn test_call_tool_get_simple_success() {
 		.await;
 
 	assert!(result.is_ok());
-	assert_eq!(result.unwrap(), expected_response.to_string());
+	assert_eq!(text(result.unwrap()), expected_response.to_string());
 }
 
 #[tokio::test]
@@ -164,7 +196,7 @@ async fn test_call_tool_get_with_query() {
 		.await;
 
 	assert!(result.is_ok());
-	assert_eq!(result.unwrap(), expected_response.to_string());
+	assert_eq!(text(result.unwrap()), expected_response.to_string());
 }
 
 #[tokio::test]
@@ -188,7 +220,7 @@ async fn test_call_tool_get_with_header() {
 		.await;
 
 	assert!(result.is_ok());
-	assert_eq!(result.unwrap(), expected_response.to_string());
+	assert_eq!(text(result.unwrap()), expected_response.to_string());
 }
 
 #[tokio::test]
@@ -211,7 +243,7 @@ async fn test_call_tool_post_with_body() {
 		.await;
 
 	assert!(result.is_ok());
-	assert_eq!(result.unwrap(), expected_response.to_string());
+	assert_eq!(text(result.unwrap()), expected_response.to_string());
 }
 
 #[tokio::test]
@@ -242,7 +274,7 @@ async fn test_call_tool_post_all_params() {
 		.await;
 
 	assert!(result.is_ok());
-	assert_eq!(result.unwrap(), expected_response.to_string());
+	assert_eq!(text(result.unwrap()), expected_response.to_string());
 }
 
 #[tokio::test]
@@ -287,6 +319,30 @@ async fn test_call_tool_upstream_error() {
 	assert!(err.to_string().contains(&error_response.to_string()));
 }
 
+#[tokio::test]
+async fn test_call_tool_configured_success_status() {
+	let (server, mut handler) = setup().await;
+	handler.success_statuses = vec![StatusRange { min: 404, max: 404 }];
+
+	let user_id = "missing-user";
+	let not_found_response = json!({ "error": "User not found" });
+
+	Mock::given(method("GET"))
+		.and(path(format!("/users/{user_id}")))
+		.respond_with(ResponseTemplate::new(404).set_body_json(&not_found_response))
+		.mount(&server)
+		.await;
+
+	let args = json!({ "path": { "user_id": user_id } });
+	let result = handler
+		.call_tool("get_user", Some(args.as_object().unwrap().clone()))
+		.await;
+
+	assert!(result.is_ok());
+	let result: Value = serde_json::from_str(&text(result.unwrap())).unwrap();
+	assert_eq!(result, json!({ "status": 404, "body": not_found_response }));
+}
+
 #[tokio::test]
 async fn test_call_tool_invalid_header_value() {
 	let (server, handler) = setup().await;
@@ -311,7 +367,7 @@ async fn test_call_tool_invalid_header_value() {
 		.call_tool("get_user", Some(args.as_object().unwrap().clone()))
 		.await;
 	assert!(result.is_ok()); // Check that the call still succeeds despite the bad header
-	assert_eq!(result.unwrap(), json!({ "id": user_id }).to_string());
+	assert_eq!(text(result.unwrap()), json!({ "id": user_id }).to_string());
 	// We can't easily assert the log message here, but manual inspection of logs would show the warning.
 }
 
@@ -339,7 +395,7 @@ async fn test_call_tool_invalid_query_param_value() {
 		.call_tool("get_user", Some(args.as_object().unwrap().clone()))
 		.await;
 	assert!(result.is_ok());
-	assert_eq!(result.unwrap(), json!({ "id": user_id }).to_string());
+	assert_eq!(text(result.unwrap()), json!({ "id": user_id }).to_string());
 }
 
 #[tokio::test]
@@ -381,3 +437,2096 @@ async fn test_call_tool_invalid_path_param_value() {
 	// If the request *itself* failed before sending (e.g., invalid URL formed),
 	// the error might be different.
 }
+
+#[test]
+fn test_parse_error_code() {
+	let schema: OpenAPI = serde_json::from_value(json!({
+		"openapi": "3.0.0",
+		"info": { "title": "test", "version": "1.0" },
+		"paths": {
+			"/users": {
+				"post": {
+					"operationId": "create_user",
+					"requestBody": { "$ref": "#/components/requestBodies/Missing" },
+					"responses": {}
+				}
+			}
+		}
+	}))
+	.unwrap();
+	let err = parse_openapi_schema(&schema).unwrap_err();
+	assert_eq!(err.code(), "missing_components");
+
+	let schema: OpenAPI = serde_json::from_value(json!({
+		"openapi": "3.0.0",
+		"info": { "title": "test", "version": "1.0" },
+		"paths": {},
+		"servers": [{ "url": "http://a" }, { "url": "http://b" }]
+	}))
+	.unwrap();
+	let err = get_server_url(&schema).unwrap_err();
+	assert_eq!(err.code(), "multiple_servers");
+	assert_eq!(
+		err.to_string(),
+		"multiple servers are not supported: http://a, http://b"
+	);
+}
+
+const USERS_JSON: &str = r#"{
+	"openapi": "3.0.0",
+	"info": { "title": "users", "version": "1.0" },
+	"servers": [{ "url": "/api" }],
+	"paths": {
+		"/users/{user_id}": {
+			"get": {
+				"operationId": "get_user",
+				"description": "Get user details",
+				"parameters": [
+					{ "name": "user_id", "in": "path", "required": true, "schema": { "type": "string" } },
+					{ "name": "verbose", "in": "query", "schema": { "type": "boolean" } }
+				],
+				"responses": {}
+			}
+		}
+	}
+}"#;
+
+const USERS_YAML: &str = r#"
+openapi: 3.0.0
+info:
+  title: users
+  version: "1.0"
+servers:
+  - url: /api
+paths:
+  /users/{user_id}:
+    get:
+      operationId: get_user
+      description: Get user details
+      parameters:
+        - name: user_id
+          in: path
+          required: true
+          schema:
+            type: string
+        - name: verbose
+          in: query
+          schema:
+            type: boolean
+      responses: {}
+"#;
+
+#[test]
+fn test_parse_schema_json_and_yaml() {
+	let from_json = parse_schema(USERS_JSON, None).unwrap();
+	let from_yaml = parse_schema(USERS_YAML, None).unwrap();
+	assert_eq!(
+		serde_json::to_value(parse_openapi_schema(&from_json).unwrap()).unwrap(),
+		serde_json::to_value(parse_openapi_schema(&from_yaml).unwrap()).unwrap(),
+	);
+	assert_eq!(get_server_url(&from_json).unwrap().prefix, "/api");
+	assert_eq!(get_server_url(&from_yaml).unwrap().prefix, "/api");
+
+	// The file extension takes precedence over the content
+	let from_yaml = parse_schema(USERS_YAML, Some(Path::new("spec.yml"))).unwrap();
+	assert_eq!(from_json, from_yaml);
+	let err = parse_schema(USERS_YAML, Some(Path::new("spec.json"))).unwrap_err();
+	assert!(err.to_string().contains("parsed as JSON"), "{err}");
+
+	let err = parse_schema("openapi: [", None).unwrap_err();
+	assert!(err.to_string().contains("parsed as YAML"), "{err}");
+}
+
+#[test]
+fn test_parse_openapi_schema() {
+	let schema = include_str!("../../../../../examples/openapi/openapi.json");
+	let schema = parse_schema(schema, Some(Path::new("openapi.json"))).unwrap();
+	let tools = parse_openapi_schema(&schema).unwrap();
+	assert_eq!(tools.len(), 19);
+	for (tool, call) in &tools {
+		assert!(!tool.name.is_empty());
+		assert!(call.path.starts_with('/'), "{}", call.path);
+	}
+	let (_, call) = tools
+		.iter()
+		.find(|(tool, _)| tool.name == "getPetById")
+		.unwrap();
+	assert_eq!(call.method, "get");
+	assert_eq!(call.path, "/pet/{petId}");
+	assert!(tools.iter().all(|(tool, _)| tool.annotations.is_none()));
+	// For read-only backends, only reads are annotated as read-only
+	let options = ParseOptions {
+		annotate_read_only: true,
+		..Default::default()
+	};
+	let tools = parse_openapi_tools(&schema, &options).unwrap();
+	let read_only = |name: &str| {
+		let (tool, _) = tools.iter().find(|(tool, _)| tool.name == name).unwrap();
+		tool.annotations.as_ref().unwrap().read_only_hint
+	};
+	assert_eq!(read_only("getPetById"), Some(true));
+	assert_eq!(read_only("addPet"), Some(false));
+	assert_eq!(read_only("deletePet"), Some(false));
+	assert_eq!(get_server_url(&schema).unwrap().prefix, "/api/v3");
+}
+
+const UPLOAD_YAML: &str = r#"
+openapi: 3.0.0
+info:
+  title: files
+  version: "1.0"
+paths:
+  /files:
+    post:
+      operationId: upload_file
+      requestBody:
+        required: true
+        content:
+          multipart/form-data:
+            schema:
+              type: object
+              properties:
+                description:
+                  type: string
+                file:
+                  type: string
+                  format: binary
+      responses: {}
+  /login:
+    post:
+      operationId: login
+      requestBody:
+        content:
+          application/x-www-form-urlencoded:
+            schema:
+              type: object
+              properties:
+                user:
+                  type: string
+                remember:
+                  type: boolean
+      responses: {}
+"#;
+
+async fn setup_upload() -> (MockServer, Handler) {
+	let (server, mut handler) = setup().await;
+	let schema = parse_schema(UPLOAD_YAML, None).unwrap();
+	handler.tools = parse_openapi_schema(&schema).unwrap();
+	(server, handler)
+}
+
+#[test]
+fn test_parse_body_content_type() {
+	let schema = parse_schema(UPLOAD_YAML, None).unwrap();
+	let tools = parse_openapi_schema(&schema).unwrap();
+	let content_type = |name: &str| {
+		tools
+			.iter()
+			.find(|(t, _)| t.name == name)
+			.and_then(|(_, call)| call.content_type.clone())
+	};
+	assert_eq!(
+		content_type("upload_file").as_deref(),
+		Some("multipart/form-data")
+	);
+	assert_eq!(
+		content_type("login").as_deref(),
+		Some("application/x-www-form-urlencoded")
+	);
+}
+
+#[tokio::test]
+async fn test_call_tool_multipart() {
+	let (server, handler) = setup_upload().await;
+
+	Mock::given(method("POST"))
+		.and(path("/files"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("uploaded"))
+		.mount(&server)
+		.await;
+
+	let args = json!({
+		"body": { "description": "greeting", "file": "aGVsbG8gd29ybGQ=" }
+	});
+	let result = handler
+		.call_tool("upload_file", Some(args.as_object().unwrap().clone()))
+		.await;
+	assert_eq!(text(result.unwrap()), "uploaded");
+
+	let requests = server.received_requests().await.unwrap();
+	let req = requests.last().unwrap();
+	let content_type = req.headers.get("content-type").unwrap().to_str().unwrap();
+	assert!(
+		content_type.starts_with("multipart/form-data; boundary="),
+		"{content_type}"
+	);
+	let body = String::from_utf8(req.body.clone()).unwrap();
+	assert!(
+		body.contains("Content-Disposition: form-data; name=\"description\"\r\n\r\ngreeting\r\n"),
+		"{body}"
+	);
+	assert!(
+		body.contains("name=\"file\"; filename=\"file\"\r\nContent-Type: application/octet-stream\r\n\r\nhello world\r\n"),
+		"{body}"
+	);
+}
+
+#[tokio::test]
+async fn test_call_tool_multipart_escapes_names() {
+	let (server, handler) = setup_upload().await;
+
+	Mock::given(method("POST"))
+		.and(path("/files"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("uploaded"))
+		.mount(&server)
+		.await;
+
+	let args = json!({
+		"body": { "a\"\r\nX-Injected: 1\0": "value" }
+	});
+	let result = handler
+		.call_tool("upload_file", Some(args.as_object().unwrap().clone()))
+		.await;
+	assert_eq!(text(result.unwrap()), "uploaded");
+
+	let requests = server.received_requests().await.unwrap();
+	let body = String::from_utf8(requests.last().unwrap().body.clone()).unwrap();
+	assert!(
+		body.contains(
+			"Content-Disposition: form-data; name=\"a%22%0D%0AX-Injected: 1%00\"\r\n\r\nvalue\r\n"
+		),
+		"{body}"
+	);
+	assert!(!body.contains("\r\nX-Injected"), "{body}");
+}
+
+#[tokio::test]
+async fn test_call_tool_multipart_invalid_file() {
+	let (_server, handler) = setup_upload().await;
+
+	for file in [json!(42), json!("not base64!")] {
+		let args = json!({ "body": { "file": file } });
+		let err = handler
+			.call_tool("upload_file", Some(args.as_object().unwrap().clone()))
+			.await
+			.unwrap_err();
+		assert!(
+			err
+				.to_string()
+				.contains("file field 'file' must be a base64 encoded string"),
+			"{err}"
+		);
+	}
+}
+
+#[tokio::test]
+async fn test_call_tool_form() {
+	let (server, handler) = setup_upload().await;
+
+	Mock::given(method("POST"))
+		.and(path("/login"))
+		.and(header("content-type", "application/x-www-form-urlencoded"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+		.mount(&server)
+		.await;
+
+	let args = json!({ "body": { "user": "a b", "remember": true } });
+	let result = handler
+		.call_tool("login", Some(args.as_object().unwrap().clone()))
+		.await;
+	assert_eq!(text(result.unwrap()), "ok");
+
+	let requests = server.received_requests().await.unwrap();
+	let body = String::from_utf8(requests.last().unwrap().body.clone()).unwrap();
+	let mut pairs: Vec<_> = body.split('&').collect();
+	pairs.sort();
+	assert_eq!(pairs, vec!["remember=true", "user=a+b"]);
+}
+
+const SEARCH_YAML: &str = r#"
+openapi: 3.0.0
+info:
+  title: search
+  version: "1.0"
+paths:
+  /search:
+    get:
+      operationId: search
+      parameters:
+        - name: repeated
+          in: query
+          schema: { type: array, items: { type: string } }
+        - name: comma
+          in: query
+          explode: false
+          schema: { type: array, items: { type: string } }
+        - name: space
+          in: query
+          style: spaceDelimited
+          schema: { type: array, items: { type: string } }
+        - name: pipe
+          in: query
+          style: pipeDelimited
+          schema: { type: array, items: { type: integer } }
+        - name: pipe_exploded
+          in: query
+          style: pipeDelimited
+          explode: true
+          schema: { type: array, items: { type: integer } }
+      responses: {}
+"#;
+
+#[test]
+fn test_parse_query_styles() {
+	let schema = parse_schema(SEARCH_YAML, None).unwrap();
+	let tools = parse_openapi_schema(&schema).unwrap();
+	let (_, call) = &tools[0];
+	assert_eq!(
+		call.query_styles,
+		HashMap::from([
+			("comma".to_string(), ArrayQueryStyle::CommaDelimited),
+			("space".to_string(), ArrayQueryStyle::SpaceDelimited),
+			("pipe".to_string(), ArrayQueryStyle::PipeDelimited),
+		])
+	);
+}
+
+#[tokio::test]
+async fn test_call_tool_array_query_styles() {
+	let (server, mut handler) = setup().await;
+	let schema = parse_schema(SEARCH_YAML, None).unwrap();
+	handler.tools = parse_openapi_schema(&schema).unwrap();
+
+	Mock::given(method("GET"))
+		.and(path("/search"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+		.mount(&server)
+		.await;
+
+	let cases = [
+		("repeated", json!(["a", "b c"]), "repeated=a&repeated=b+c"),
+		("comma", json!(["a", "b,c"]), "comma=a,b%2Cc"),
+		("space", json!(["a", "b"]), "space=a%20b"),
+		("pipe", json!([1, 2, 3]), "pipe=1|2|3"),
+		(
+			"pipe_exploded",
+			json!([1, 2]),
+			"pipe_exploded=1&pipe_exploded=2",
+		),
+	];
+	for (param, value, expected) in cases {
+		let args = json!({ "query": { param: value } });
+		let result = handler
+			.call_tool("search", Some(args.as_object().unwrap().clone()))
+			.await;
+		assert_eq!(text(result.unwrap()), "ok");
+		let requests = server.received_requests().await.unwrap();
+		let query = requests.last().unwrap().url.query().unwrap().to_string();
+		assert_eq!(query, expected, "{param}");
+	}
+}
+
+const DEFAULTS_YAML: &str = r#"
+openapi: 3.0.0
+info:
+  title: defaults
+  version: "1.0"
+paths:
+  /items:
+    post:
+      operationId: create_item
+      parameters:
+        - name: limit
+          in: query
+          schema: { type: string, default: "10" }
+        - name: sort
+          in: query
+          schema: { type: string }
+        - name: page
+          in: query
+          schema: { type: integer, default: 1 }
+        - name: archived
+          in: query
+          schema: { type: boolean, default: false }
+        - name: x-mode
+          in: header
+          schema: { type: string, default: fast }
+        - name: x-retries
+          in: header
+          schema: { type: integer, default: 3 }
+      requestBody:
+        content:
+          application/json:
+            schema:
+              type: object
+              properties:
+                name: { type: string }
+                color: { type: string, default: red }
+                options:
+                  type: object
+                  properties:
+                    verbose: { type: boolean, default: false }
+      responses: {}
+"#;
+
+async fn setup_defaults(apply_defaults: bool) -> (MockServer, Handler) {
+	let (server, mut handler) = setup().await;
+	let schema = parse_schema(DEFAULTS_YAML, None).unwrap();
+	handler.tools = parse_openapi_schema(&schema).unwrap();
+	handler.apply_defaults = apply_defaults;
+	Mock::given(method("POST"))
+		.and(path("/items"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+		.mount(&server)
+		.await;
+	(server, handler)
+}
+
+#[tokio::test]
+async fn test_call_tool_apply_defaults() {
+	let (server, handler) = setup_defaults(true).await;
+
+	let args = json!({
+		"query": { "limit": "50" },
+		"body": { "name": "widget", "options": {} }
+	});
+	let result = handler
+		.call_tool("create_item", Some(args.as_object().unwrap().clone()))
+		.await;
+	assert_eq!(text(result.unwrap()), "ok");
+
+	let requests = server.received_requests().await.unwrap();
+	let req = requests.last().unwrap();
+	// The explicit argument wins over the default, and parameters without a default stay omitted.
+	// Number and boolean defaults are sent as written in JSON.
+	let mut query = req.url.query().unwrap().split('&').collect::<Vec<_>>();
+	query.sort();
+	assert_eq!(query, ["archived=false", "limit=50", "page=1"]);
+	assert_eq!(req.headers.get("x-mode").unwrap(), "fast");
+	assert_eq!(req.headers.get("x-retries").unwrap(), "3");
+	let body: Value = serde_json::from_slice(&req.body).unwrap();
+	assert_eq!(
+		body,
+		json!({ "name": "widget", "color": "red", "options": { "verbose": false } })
+	);
+}
+
+#[tokio::test]
+async fn test_call_tool_defaults_disabled() {
+	let (server, handler) = setup_defaults(false).await;
+
+	let args = json!({ "body": { "name": "widget" } });
+	let result = handler
+		.call_tool("create_item", Some(args.as_object().unwrap().clone()))
+		.await;
+	assert_eq!(text(result.unwrap()), "ok");
+
+	let requests = server.received_requests().await.unwrap();
+	let req = requests.last().unwrap();
+	assert_eq!(req.url.query(), None);
+	assert!(req.headers.get("x-mode").is_none());
+	let body: Value = serde_json::from_slice(&req.body).unwrap();
+	assert_eq!(body, json!({ "name": "widget" }));
+}
+
+#[tokio::test]
+async fn test_validate_arguments() {
+	let (server, mut handler) = setup().await;
+
+	let args = json!({ "body": { "name": 1 }, "extra": true });
+	let args = args.as_object().unwrap();
+	// Validation is disabled by default
+	assert!(
+		handler
+			.validate_arguments("create_user", Some(args))
+			.is_ok()
+	);
+
+	handler.validation = Some(ArgumentValidation {
+		reject_unknown_properties: true,
+	});
+	let err = handler
+		.validate_arguments("create_user", Some(args))
+		.unwrap_err();
+	assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+	let data = err.data.unwrap();
+	let mut violations: Vec<_> = data["violations"]
+		.as_array()
+		.unwrap()
+		.iter()
+		.map(|v| {
+			(
+				v["path"].as_str().unwrap().to_string(),
+				v["message"].as_str().unwrap().to_string(),
+			)
+		})
+		.collect();
+	violations.sort();
+	assert_eq!(
+		violations,
+		vec![
+			(
+				"body.email".to_string(),
+				"missing required property".to_string()
+			),
+			(
+				"body.name".to_string(),
+				"expected string, got number".to_string()
+			),
+			("extra".to_string(), "unknown property".to_string()),
+		]
+	);
+
+	handler.validation = Some(ArgumentValidation::default());
+	let args = json!({ "body": { "name": "a", "email": "a@example.com" }, "extra": true });
+	assert!(
+		handler
+			.validate_arguments("create_user", args.as_object())
+			.is_ok()
+	);
+	assert!(server.received_requests().await.unwrap().is_empty());
+}
+
+const CONDITIONAL_YAML: &str = r#"
+openapi: 3.0.0
+info:
+  title: conditional
+  version: "1.0"
+paths:
+  /items/{id}:
+    get:
+      operationId: get_item
+      parameters:
+        - name: id
+          in: path
+          required: true
+          schema: { type: string }
+        - name: If-None-Match
+          in: header
+          schema: { type: string }
+      responses: {}
+  /reports/{id}:
+    get:
+      operationId: get_report
+      parameters:
+        - name: id
+          in: path
+          required: true
+          schema: { type: string }
+        - name: X-Trace
+          in: header
+          schema: { type: string }
+      responses: {}
+"#;
+
+#[tokio::test]
+async fn test_call_tool_not_modified() {
+	let (server, mut handler) = setup().await;
+	let schema = parse_schema(CONDITIONAL_YAML, None).unwrap();
+	handler.tools = parse_openapi_schema(&schema).unwrap();
+
+	Mock::given(method("GET"))
+		.and(path("/items/1"))
+		.and(header("If-None-Match", "\"v1\""))
+		.respond_with(
+			ResponseTemplate::new(304)
+				.insert_header("ETag", "\"v1\"")
+				.insert_header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+		)
+		.mount(&server)
+		.await;
+
+	let args = json!({ "path": { "id": "1" }, "header": { "If-None-Match": "\"v1\"" } });
+	let result = handler
+		.call_tool("get_item", Some(args.as_object().unwrap().clone()))
+		.await
+		.unwrap();
+	let result: Value = serde_json::from_str(&text(result)).unwrap();
+	assert_eq!(
+		result,
+		json!({
+			"status": 304,
+			"notModified": true,
+			"etag": "\"v1\"",
+			"lastModified": "Wed, 21 Oct 2015 07:28:00 GMT",
+		})
+	);
+}
+
+#[tokio::test]
+async fn test_call_tool_undeclared_conditional_header() {
+	let (server, mut handler) = setup().await;
+	let schema = parse_schema(CONDITIONAL_YAML, None).unwrap();
+	handler.tools = parse_openapi_schema(&schema).unwrap();
+
+	Mock::given(method("GET"))
+		.and(path("/reports/1"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("report"))
+		.mount(&server)
+		.await;
+
+	let args = json!({
+		"path": { "id": "1" },
+		"header": { "X-Trace": "abc", "If-None-Match": "\"v1\"" }
+	});
+	let result = handler
+		.call_tool("get_report", Some(args.as_object().unwrap().clone()))
+		.await;
+	assert_eq!(text(result.unwrap()), "report");
+
+	let requests = server.received_requests().await.unwrap();
+	let req = requests.last().unwrap();
+	assert_eq!(req.headers.get("x-trace").unwrap(), "abc");
+	assert!(req.headers.get("if-none-match").is_none());
+}
+
+// Starts a server that accepts HTTP/1.1 and prior-knowledge HTTP/2, and responds with the version
+// each request used.
+async fn version_echo_server() -> std::net::SocketAddr {
+	let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move {
+		while let Ok((stream, _)) = listener.accept().await {
+			tokio::spawn(async move {
+				let svc =
+					hyper::service::service_fn(|req: ::http::Request<hyper::body::Incoming>| async move {
+						Ok::<_, std::convert::Infallible>(::http::Response::new(crate::http::Body::from(
+							format!("{:?}", req.version()),
+						)))
+					});
+				let _ = hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new())
+					.serve_connection(hyper_util::rt::TokioIo::new(stream), svc)
+					.await;
+			});
+		}
+	});
+	addr
+}
+
+#[tokio::test]
+async fn test_call_tool_http_version() {
+	let (_server, mut handler) = setup().await;
+	let addr = version_echo_server().await;
+	handler.host = addr.ip().to_string();
+	handler.port = addr.port() as u32;
+	let args = json!({ "path": { "user_id": "1" } });
+
+	for (pref, want) in [
+		(HttpVersionPreference::Auto, "HTTP/1.1"),
+		(HttpVersionPreference::Http1, "HTTP/1.1"),
+		(HttpVersionPreference::Http2, "HTTP/2.0"),
+	] {
+		handler.http_version = pref;
+		let result = handler
+			.call_tool("get_user", Some(args.as_object().unwrap().clone()))
+			.await
+			.unwrap();
+		assert_eq!(text(result), want, "{pref:?}");
+	}
+}
+
+#[test]
+fn test_http_version_alpn() {
+	let base = &*crate::http::backendtls::SYSTEM_TRUST;
+	let h1 = base.for_http_version(HttpVersionPreference::Http1);
+	assert_eq!(h1.config.alpn_protocols, vec![b"http/1.1".to_vec()]);
+	let h2 = base.for_http_version(HttpVersionPreference::Http2);
+	assert_eq!(h2.config.alpn_protocols, vec![b"h2".to_vec()]);
+	// The shared configuration is left untouched.
+	assert_eq!(base.config.alpn_protocols.len(), 2);
+	assert_eq!(base.for_http_version(HttpVersionPreference::Auto), *base);
+	// The same configuration is returned each time, so its connections are pooled together.
+	assert_eq!(base.for_http_version(HttpVersionPreference::Http1), h1);
+	assert_ne!(h1, h2);
+}
+
+fn compress(encoding: &str, data: &[u8]) -> Vec<u8> {
+	use std::io::Write;
+	match encoding {
+		"gzip" => {
+			let mut e = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+			e.write_all(data).unwrap();
+			e.finish().unwrap()
+		},
+		"deflate" => {
+			let mut e = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+			e.write_all(data).unwrap();
+			e.finish().unwrap()
+		},
+		"raw-deflate" => {
+			let mut e = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+			e.write_all(data).unwrap();
+			e.finish().unwrap()
+		},
+		"br" => {
+			let mut out = Vec::new();
+			{
+				let mut e = brotli::CompressorWriter::new(&mut out, 4096, 5, 22);
+				e.write_all(data).unwrap();
+			}
+			out
+		},
+		_ => unreachable!(),
+	}
+}
+
+#[tokio::test]
+async fn test_call_tool_compressed_response() {
+	let (server, handler) = setup().await;
+	let expected_response = json!({ "id": "1", "name": "Test User" });
+	let raw = expected_response.to_string();
+
+	for (encoding, header_value) in [
+		("gzip", "gzip"),
+		("deflate", "deflate"),
+		("raw-deflate", "deflate"),
+		("br", "br"),
+	] {
+		server.reset().await;
+		Mock::given(method("GET"))
+			.and(path("/users/1"))
+			.and(headers("accept-encoding", vec!["gzip", "deflate", "br"]))
+			.respond_with(
+				ResponseTemplate::new(200)
+					.insert_header("content-encoding", header_value)
+					.set_body_raw(compress(encoding, raw.as_bytes()), "application/json"),
+			)
+			.mount(&server)
+			.await;
+
+		let args = json!({ "path": { "user_id": "1" } });
+		let result = handler
+			.call_tool("get_user", Some(args.as_object().unwrap().clone()))
+			.await;
+		assert_eq!(text(result.unwrap()), raw, "{encoding}");
+	}
+}
+
+#[test]
+fn test_decode_body_limits() {
+	let data = vec![0u8; 10_000];
+	for encoding in ["gzip", "deflate", "br"] {
+		let compressed = bytes::Bytes::from(compress(encoding, &data));
+		let value = HeaderValue::from_static(match encoding {
+			"gzip" => "gzip",
+			"deflate" => "deflate",
+			_ => "br",
+		});
+		let decoded = decode_body(Some(&value), compressed.clone(), data.len()).unwrap();
+		assert_eq!(decoded.len(), data.len(), "{encoding}");
+		// A body that expands past the limit is rejected rather than read in full
+		assert!(
+			decode_body(Some(&value), compressed, 1_000).is_err(),
+			"{encoding}"
+		);
+	}
+
+	// Stacked codings are undone in reverse order
+	let stacked = compress("br", &compress("gzip", b"hello"));
+	let decoded = decode_body(
+		Some(&HeaderValue::from_static("gzip, br")),
+		stacked.into(),
+		100,
+	)
+	.unwrap();
+	assert_eq!(&decoded[..], b"hello");
+
+	let body = bytes::Bytes::from_static(b"hello");
+	assert_eq!(
+		decode_body(
+			Some(&HeaderValue::from_static("identity")),
+			body.clone(),
+			100
+		)
+		.unwrap(),
+		body
+	);
+	assert!(decode_body(Some(&HeaderValue::from_static("zstd")), body, 100).is_err());
+}
+
+#[tokio::test]
+async fn test_call_tool_binary_response() {
+	let (server, mut handler) = setup().await;
+	let png: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\xff\xfe";
+	let pdf: &[u8] = b"%PDF-1.7\n\xe2\xe3\xcf\xd3";
+
+	Mock::given(method("GET"))
+		.and(path("/users/png"))
+		.respond_with(ResponseTemplate::new(200).set_body_raw(png, "image/png"))
+		.mount(&server)
+		.await;
+	Mock::given(method("GET"))
+		.and(path("/users/pdf"))
+		.respond_with(ResponseTemplate::new(200).set_body_raw(pdf, "application/pdf"))
+		.mount(&server)
+		.await;
+	Mock::given(method("GET"))
+		.and(path("/users/csv"))
+		.respond_with(
+			ResponseTemplate::new(200).set_body_raw("id,name\n1,a\n", "application/vnd.api+csv"),
+		)
+		.mount(&server)
+		.await;
+
+	async fn call(handler: &Handler, id: &str) -> Content {
+		let args = json!({ "path": { "user_id": id } });
+		handler
+			.call_tool("get_user", Some(args.as_object().unwrap().clone()))
+			.await
+			.unwrap()
+	}
+
+	let result = call(&handler, "png").await;
+	let image = result.as_image().expect("expected image content");
+	assert_eq!(image.mime_type, "image/png");
+	assert_eq!(STANDARD.decode(&image.data).unwrap(), png);
+
+	let args = json!({ "path": { "user_id": "pdf" }, "query": { "verbose": "secret" } });
+	let result = handler
+		.call_tool("get_user", Some(args.as_object().unwrap().clone()))
+		.await
+		.unwrap();
+	let resource = result.as_resource().expect("expected resource content");
+	let ResourceContents::BlobResourceContents {
+		uri,
+		mime_type,
+		blob,
+	} = &resource.resource
+	else {
+		panic!("expected blob contents");
+	};
+	// The query is left out of the URI
+	assert!(uri.ends_with("/users/pdf"), "{uri}");
+	assert_eq!(mime_type.as_deref(), Some("application/pdf"));
+	assert_eq!(STANDARD.decode(blob).unwrap(), pdf);
+
+	// Unrecognized types are binary unless configured as text
+	assert!(call(&handler, "csv").await.as_resource().is_some());
+	handler.text_content_types = vec!["application/*+csv".to_string()];
+	assert_eq!(text(call(&handler, "csv").await), "id,name\n1,a\n");
+}
+
+#[test]
+fn test_media_type_matches() {
+	assert_eq!(
+		media_type("Application/JSON; charset=utf-8"),
+		"application/json"
+	);
+	assert!(media_type_matches("text/*", "text/plain"));
+	assert!(media_type_matches(
+		"application/*+json",
+		"application/problem+json"
+	));
+	assert!(!media_type_matches(
+		"application/*+json",
+		"application/+json"
+	));
+	assert!(!media_type_matches(
+		"application/*+json",
+		"application/json-seq"
+	));
+	assert!(media_type_matches("Application/JSON", "application/json"));
+	assert!(!media_type_matches("application/json", "image/json"));
+	assert!(!media_type_matches("text", "text/plain"));
+}
+
+#[tokio::test]
+async fn test_call_tool_static_and_denied_headers() {
+	let (server, mut handler) = setup().await;
+	handler.static_headers = vec![
+		(
+			HeaderName::from_static("x-api-key"),
+			HeaderValue::from_static("static-key"),
+		),
+		(CONTENT_TYPE, HeaderValue::from_static("text/plain")),
+	];
+	handler.denied_headers = vec![HeaderName::from_static("x-request-id")];
+
+	Mock::given(method("GET"))
+		.and(path("/users/1"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+		.mount(&server)
+		.await;
+	Mock::given(method("POST"))
+		.and(path("/users"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+		.mount(&server)
+		.await;
+
+	// Denied and hop-by-hop headers are dropped from the arguments
+	let args = json!({
+		"path": { "user_id": "1" },
+		"header": { "X-Request-ID": "abc", "Connection": "close" }
+	});
+	let result = handler
+		.call_tool("get_user", Some(args.as_object().unwrap().clone()))
+		.await;
+	assert_eq!(text(result.unwrap()), "ok");
+
+	// Static headers replace arguments, but not the body's content type
+	let args = json!({
+		"body": { "name": "a", "email": "a@example.com" },
+		"header": { "X-API-Key": "from-args" }
+	});
+	let result = handler
+		.call_tool("create_user", Some(args.as_object().unwrap().clone()))
+		.await;
+	assert_eq!(text(result.unwrap()), "ok");
+
+	let requests = server.received_requests().await.unwrap();
+	let get = &requests[0];
+	assert!(get.headers.get("x-request-id").is_none());
+	assert!(get.headers.get("connection").is_none_or(|v| v != "close"));
+	assert_eq!(get.headers.get("x-api-key").unwrap(), "static-key");
+	// Without a body, the static content type is sent as configured
+	assert_eq!(get.headers.get("content-type").unwrap(), "text/plain");
+
+	let post = &requests[1];
+	let api_keys: Vec<_> = post.headers.get_all("x-api-key").iter().collect();
+	assert_eq!(api_keys, vec!["static-key"]);
+	assert_eq!(
+		post.headers.get("content-type").unwrap(),
+		"application/json"
+	);
+}
+
+#[tokio::test]
+async fn test_call_tool_error_redacts_credentials() {
+	let (server, mut handler) = setup().await;
+	handler.sensitive_headers = SensitiveHeaders::new(vec![HeaderName::from_static("x-request-id")]);
+	let mut key = HeaderValue::from_static("static-secret");
+	key.set_sensitive(true);
+	handler.static_headers = vec![(reqwest::header::AUTHORIZATION, key)];
+
+	// An upstream that echoes the request's credentials in its error
+	Mock::given(method("GET"))
+		.and(path("/users/1"))
+		.respond_with(
+			ResponseTemplate::new(401)
+				.set_body_string("unauthorized: Authorization=static-secret, X-Request-ID=request-secret"),
+		)
+		.mount(&server)
+		.await;
+
+	let args = json!({ "path": { "user_id": "1" }, "header": { "X-Request-ID": "request-secret" } });
+	let err = handler
+		.call_tool("get_user", Some(args.as_object().unwrap().clone()))
+		.await
+		.unwrap_err()
+		.to_string();
+	assert!(!err.contains("static-secret"), "{err}");
+	assert!(!err.contains("request-secret"), "{err}");
+	assert!(
+		err.contains("Authorization=[REDACTED], X-Request-ID=[REDACTED]"),
+		"{err}"
+	);
+}
+
+const SECURED_YAML: &str = r#"
+openapi: 3.0.0
+info: { title: secured, version: "1" }
+paths:
+  /keyed:
+    get:
+      operationId: keyed
+      security:
+        - key: []
+          tenant: []
+      responses: {}
+  /bearer:
+    get:
+      operationId: bearer
+      security:
+        - token: []
+      responses: {}
+components:
+  securitySchemes:
+    key:
+      type: apiKey
+      in: header
+      name: X-API-Key
+    tenant:
+      type: apiKey
+      in: query
+      name: tenant
+    token:
+      type: http
+      scheme: bearer
+"#;
+
+#[tokio::test]
+async fn test_call_tool_security_credentials() {
+	let (server, mut handler) = setup().await;
+	let schema = parse_schema(SECURED_YAML, None).unwrap();
+	handler.tools = parse_openapi_schema(&schema).unwrap();
+	let credential = |scheme: &str, value: &str| crate::types::agent::SecurityCredential {
+		scheme: scheme.to_string(),
+		value: secrecy::SecretString::from(value.to_string()),
+	};
+	handler.credentials = Arc::new(
+		security::resolve(
+			&schema,
+			&[
+				credential("key", "the-api-key"),
+				credential("tenant", "tenant-secret"),
+				credential("token", "the-token"),
+			],
+		)
+		.unwrap()
+		.0,
+	);
+
+	Mock::given(method("GET"))
+		.and(path("/keyed"))
+		.respond_with(ResponseTemplate::new(403).set_body_string("forbidden: tenant=tenant-secret"))
+		.mount(&server)
+		.await;
+	Mock::given(method("GET"))
+		.and(path("/bearer"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+		.mount(&server)
+		.await;
+
+	// Each operation gets the credentials it requires, replacing any passed as arguments
+	let args = json!({ "query": { "tenant": "other" }, "header": { "X-API-Key": "from-args" } });
+	let err = handler
+		.call_tool("keyed", Some(args.as_object().unwrap().clone()))
+		.await
+		.unwrap_err()
+		.to_string();
+	assert!(!err.contains("tenant-secret"), "{err}");
+	let result = handler.call_tool("bearer", None).await;
+	assert_eq!(text(result.unwrap()), "ok");
+
+	let requests = server.received_requests().await.unwrap();
+	let keyed = &requests[0];
+	assert_eq!(keyed.url.query(), Some("tenant=tenant-secret"));
+	assert_eq!(keyed.headers.get("x-api-key").unwrap(), "the-api-key");
+	assert!(keyed.headers.get("authorization").is_none());
+	let bearer = &requests[1];
+	assert_eq!(
+		bearer.headers.get("authorization").unwrap(),
+		"Bearer the-token"
+	);
+	assert!(bearer.headers.get("x-api-key").is_none());
+}
+
+const DESCRIBED_YAML: &str = r#"
+openapi: 3.0.0
+info: { title: pets, version: "1" }
+paths:
+  /pets/{id}:
+    put:
+      operationId: updatePet
+      summary: Update a pet
+      parameters:
+        - name: id
+          in: path
+          required: true
+          description: The pet's ID.
+          schema: { type: integer }
+        - name: dryRun
+          in: query
+          description: |
+            Validate the update
+            without applying it.
+          schema: { type: boolean }
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema:
+              type: object
+              required: [name]
+              properties:
+                name: { type: string, description: The new name. }
+                tags: { type: array, items: { type: string } }
+      responses: {}
+  /pets:
+    get:
+      operationId: listPets
+      responses: {}
+"#;
+
+#[test]
+fn test_describe_arguments() {
+	let schema = parse_schema(DESCRIBED_YAML, None).unwrap();
+	let mut tools = parse_openapi_schema(&schema).unwrap();
+	let input_schemas = tools
+		.iter()
+		.map(|(tool, _)| tool.input_schema.clone())
+		.collect::<Vec<_>>();
+	for (tool, call) in &mut tools {
+		describe_arguments(tool, call);
+	}
+	let description = |name: &str| {
+		tools
+			.iter()
+			.find(|(t, _)| t.name == name)
+			.and_then(|(t, _)| t.description.clone())
+			.unwrap()
+	};
+	assert_eq!(
+		description("updatePet"),
+		"Update a pet
+
+Arguments:
+- path.id (integer, required): The pet's ID.
+- query.dryRun (boolean): Validate the update without applying it.
+- body.name (string, required): The new name.
+- body.tags (array)"
+	);
+	// Tools without arguments are unchanged
+	assert_eq!(description("listPets"), "listPets");
+	// The input schema is left as is
+	assert_eq!(
+		tools
+			.iter()
+			.map(|(t, _)| t.input_schema.clone())
+			.collect::<Vec<_>>(),
+		input_schemas
+	);
+}
+
+const EXAMPLE_YAML: &str = r#"
+openapi: 3.0.0
+info: { title: pets, version: "1" }
+paths:
+  /pets/{id}:
+    put:
+      operationId: updatePet
+      summary: Update a pet
+      parameters:
+        - name: id
+          in: path
+          required: true
+          example: 7
+          schema: { type: integer }
+        - name: dryRun
+          in: query
+          schema: { type: boolean, example: true }
+        - name: X-Trace
+          in: header
+          schema: { type: string }
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema:
+              type: object
+              properties:
+                name: { type: string }
+            examples:
+              rex: { value: { name: Rex } }
+              fido: { value: { name: Fido } }
+      responses: {}
+  /pets:
+    get:
+      operationId: listPets
+      responses: {}
+"#;
+
+#[test]
+fn test_describe_example() {
+	let schema = parse_schema(EXAMPLE_YAML, None).unwrap();
+	let mut tools = parse_openapi_schema(&schema).unwrap();
+	let (_, call) = &tools[0];
+	// The first of several examples is used, and arguments without one are left out
+	assert_eq!(
+		call.example,
+		Some(
+			json!({"path": {"id": 7}, "query": {"dryRun": true}, "body": {"name": "Rex"}})
+				.as_object()
+				.unwrap()
+				.clone()
+		)
+	);
+	assert_eq!(tools[1].1.example, None);
+
+	let mut flat = tools.clone();
+	for (tool, call) in &mut tools {
+		describe_example(tool, call);
+	}
+	let description = tools[0].0.description.as_deref().unwrap();
+	let (summary, example) = description.split_once("\n\nExample arguments:\n").unwrap();
+	assert_eq!(summary, "Update a pet");
+	assert_eq!(
+		serde_json::from_str::<Value>(example).unwrap(),
+		json!({"path": {"id": 7}, "query": {"dryRun": true}, "body": {"name": "Rex"}})
+	);
+	// Tools without examples are unchanged
+	assert_eq!(tools[1].0.description.as_deref(), Some("listPets"));
+
+	// With flat arguments, the example is flat too
+	let (tool, call) = &mut flat[0];
+	call.flat = Some(flatten_arguments(tool));
+	describe_example(tool, call);
+	let (_, example) = tool
+		.description
+		.as_deref()
+		.unwrap()
+		.split_once("Example arguments:\n")
+		.unwrap();
+	assert_eq!(
+		serde_json::from_str::<Value>(example).unwrap(),
+		json!({"id": 7, "dryRun": true, "name": "Rex"})
+	);
+}
+
+const FLAT_YAML: &str = r#"
+openapi: 3.0.0
+info: { title: pets, version: "1" }
+paths:
+  /pets/{id}:
+    put:
+      operationId: updatePet
+      parameters:
+        - name: id
+          in: path
+          required: true
+          schema: { type: string }
+        - name: dryRun
+          in: query
+          schema: { type: string }
+        - name: X-Trace
+          in: header
+          schema: { type: string }
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema:
+              type: object
+              required: [name]
+              properties:
+                id: { type: integer }
+                name: { type: string }
+      responses: {}
+"#;
+
+#[test]
+fn test_flatten_arguments() {
+	let schema = parse_schema(FLAT_YAML, None).unwrap();
+	let tools = parse_openapi_schema(&schema).unwrap();
+	let flat = flatten_arguments(&tools[0].0);
+	// The colliding `id`s are prefixed with where they are sent
+	assert_eq!(
+		Value::Object(flat.schema.clone()),
+		json!({
+			"type": "object",
+			"properties": {
+				"path_id": {"type": "string"},
+				"dryRun": {"type": "string"},
+				"X-Trace": {"type": "string"},
+				"body_id": {"type": "integer"},
+				"name": {"type": "string"},
+			},
+			"required": ["path_id", "name"],
+		})
+	);
+	assert_eq!(
+		flat.locations["body_id"],
+		ArgumentLocation {
+			group: "body".to_string(),
+			name: Some("id".to_string()),
+		}
+	);
+}
+
+#[tokio::test]
+async fn test_call_tool_flat_arguments() {
+	let (server, mut handler) = setup().await;
+	let schema = parse_schema(FLAT_YAML, None).unwrap();
+	handler.tools = parse_openapi_schema(&schema).unwrap();
+	for (tool, call) in &mut handler.tools {
+		call.flat = Some(flatten_arguments(tool));
+	}
+	handler.validation = Some(Default::default());
+
+	Mock::given(method("PUT"))
+		.and(path("/pets/abc"))
+		.and(query_param("dryRun", "true"))
+		.and(header("X-Trace", "t-1"))
+		.and(body_json(json!({ "id": 7, "name": "Rex" })))
+		.respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+		.mount(&server)
+		.await;
+
+	// The flat schema is advertised, and arguments are checked against it
+	let advertised = handler.tools();
+	assert!(advertised[0].input_schema["properties"]["path_id"].is_object());
+	let args = json!({
+		"path_id": "abc",
+		"dryRun": "true",
+		"X-Trace": "t-1",
+		"body_id": 7,
+		"name": "Rex",
+	});
+	let args = args.as_object().unwrap().clone();
+	handler
+		.validate_arguments("updatePet", Some(&args))
+		.unwrap();
+	let nested = json!({ "path": { "id": "abc" }, "body": { "name": "Rex" } });
+	assert!(
+		handler
+			.validate_arguments("updatePet", nested.as_object())
+			.is_err()
+	);
+
+	// Each argument is sent where the schema declares it
+	let result = handler.call_tool("updatePet", Some(args)).await;
+	assert_eq!(text(result.unwrap()), "ok");
+}
+
+const DESCRIPTION_YAML: &str = r#"
+openapi: 3.0.0
+info: { title: Pets, version: "1" }
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      summary: List pets
+      description: Lists the pets in the store, newest first.
+      responses: {}
+    post:
+      operationId: createPet
+      summary: Create a pet
+      responses: {}
+    delete:
+      operationId: deletePets
+      responses: {}
+"#;
+
+#[test]
+fn test_parse_description_fallback() {
+	let schema = parse_schema(DESCRIPTION_YAML, None).unwrap();
+	let descriptions = parse_openapi_schema(&schema)
+		.unwrap()
+		.into_iter()
+		.map(|(t, _)| (t.name.to_string(), t.description.unwrap().to_string()))
+		.collect::<HashMap<_, _>>();
+	assert_eq!(
+		descriptions["listPets"],
+		"Lists the pets in the store, newest first."
+	);
+	assert_eq!(descriptions["createPet"], "Create a pet");
+	// Without a description or summary, the operation ID is used
+	assert_eq!(descriptions["deletePets"], "deletePets");
+}
+
+const DEPRECATED_YAML: &str = r#"
+openapi: 3.0.0
+info: { title: Pets, version: "1" }
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      summary: List pets
+      parameters:
+        - name: page
+          in: query
+          deprecated: true
+          schema: { type: integer }
+      responses: {}
+  /pets/search:
+    get:
+      operationId: searchPets
+      summary: Search pets
+      deprecated: true
+      responses: {}
+"#;
+
+#[test]
+fn test_parse_deprecated_operations() {
+	let schema = parse_schema(DEPRECATED_YAML, None).unwrap();
+	let tools = parse_openapi_schema(&schema).unwrap();
+	let names = tools
+		.iter()
+		.map(|(t, _)| t.name.as_ref())
+		.collect::<Vec<_>>();
+	assert_eq!(names, vec!["listPets", "searchPets"]);
+
+	let (active, _) = &tools[0];
+	assert_eq!(active.description.as_deref(), Some("List pets"));
+	assert!(active.input_schema.get("deprecated").is_none());
+	assert_eq!(
+		active.input_schema["properties"]["query"]["properties"]["page"]["deprecated"],
+		json!(true)
+	);
+
+	let (deprecated, _) = &tools[1];
+	assert_eq!(
+		deprecated.description.as_deref(),
+		Some("Deprecated. Search pets")
+	);
+	assert_eq!(deprecated.input_schema["deprecated"], json!(true));
+
+	let options = ParseOptions {
+		deprecated_operations: DeprecatedOperations::Exclude,
+		..Default::default()
+	};
+	let tools = parse_openapi_tools(&schema, &options).unwrap();
+	let names = tools
+		.iter()
+		.map(|(t, _)| t.name.as_ref())
+		.collect::<Vec<_>>();
+	assert_eq!(names, vec!["listPets"]);
+}
+
+const TAGGED_YAML: &str = r#"
+openapi: 3.0.0
+info: { title: Pets, version: "1" }
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      tags: [public]
+      responses: {}
+    post:
+      operationId: createPet
+      tags: [public, admin]
+      responses: {}
+  /pets/stats:
+    get:
+      operationId: petStats
+      tags: [internal]
+      responses: {}
+  /health:
+    get:
+      operationId: health
+      responses: {}
+"#;
+
+fn tool_names(schema: &OpenAPI, tags: TagFilter) -> Vec<String> {
+	let options = ParseOptions {
+		tags: Some(tags),
+		..Default::default()
+	};
+	let mut names = parse_openapi_tools(schema, &options)
+		.unwrap()
+		.into_iter()
+		.map(|(t, _)| t.name.to_string())
+		.collect::<Vec<_>>();
+	names.sort();
+	names
+}
+
+#[test]
+fn test_parse_tag_filter() {
+	let schema = parse_schema(TAGGED_YAML, None).unwrap();
+
+	// Including a tag drops untagged operations unless asked otherwise
+	let include = TagFilter {
+		include: vec!["public".to_string()],
+		..Default::default()
+	};
+	assert_eq!(
+		tool_names(&schema, include.clone()),
+		vec!["createPet", "listPets"]
+	);
+	let include_untagged = TagFilter {
+		include_untagged: Some(true),
+		..include
+	};
+	assert_eq!(
+		tool_names(&schema, include_untagged),
+		vec!["createPet", "health", "listPets"]
+	);
+
+	// Excluding wins over including, and keeps untagged operations unless asked otherwise
+	let exclude = TagFilter {
+		include: vec!["public".to_string()],
+		exclude: vec!["admin".to_string()],
+		..Default::default()
+	};
+	assert_eq!(tool_names(&schema, exclude), vec!["listPets"]);
+	let exclude = TagFilter {
+		exclude: vec!["internal".to_string()],
+		..Default::default()
+	};
+	assert_eq!(
+		tool_names(&schema, exclude.clone()),
+		vec!["createPet", "health", "listPets"]
+	);
+	let exclude_untagged = TagFilter {
+		include_untagged: Some(false),
+		..exclude
+	};
+	assert_eq!(
+		tool_names(&schema, exclude_untagged),
+		vec!["createPet", "listPets"]
+	);
+}
+
+#[test]
+fn test_parse_allowed_methods() {
+	let schema = include_str!("../../../../../examples/openapi/openapi.json");
+	let schema = parse_schema(schema, Some(Path::new("openapi.json"))).unwrap();
+	assert_eq!(
+		parse_openapi_tools(&schema, &ParseOptions::default())
+			.unwrap()
+			.len(),
+		19
+	);
+
+	// Methods match in any case
+	let options = ParseOptions {
+		allowed_methods: vec!["GET".to_string(), "post".to_string(), "Put".to_string()],
+		..Default::default()
+	};
+	let tools = parse_openapi_tools(&schema, &options).unwrap();
+	assert!(!tools.is_empty());
+	assert!(
+		tools.iter().all(|(_, call)| call.method != "delete"),
+		"DELETE operations are excluded"
+	);
+	let names = tools
+		.iter()
+		.map(|(t, _)| t.name.as_ref())
+		.collect::<Vec<_>>();
+	assert!(names.contains(&"getPetById"));
+	assert!(names.contains(&"addPet"));
+	assert!(!names.contains(&"deletePet"));
+}
+
+const HEAD_YAML: &str = r#"
+openapi: 3.1.0
+info: { title: Files, version: "1" }
+paths:
+  /files/{name}:
+    head:
+      operationId: fileExists
+      parameters:
+        - name: name
+          in: path
+          required: true
+          schema: { type: string }
+      responses: {}
+    options:
+      operationId: fileOptions
+      parameters:
+        - name: name
+          in: path
+          required: true
+          schema: { type: string }
+      responses: {}
+"#;
+
+#[test]
+fn test_parse_head_and_options() {
+	let schema = parse_schema(HEAD_YAML, None).unwrap();
+	let options = ParseOptions {
+		annotate_read_only: true,
+		..Default::default()
+	};
+	let tools = parse_openapi_tools(&schema, &options).unwrap();
+	let methods = tools
+		.iter()
+		.map(|(t, call)| (t.name.as_ref(), call.method.as_str(), call.path.as_str()))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		methods,
+		vec![
+			("fileOptions", "options", "/files/{name}"),
+			("fileExists", "head", "/files/{name}"),
+		]
+	);
+	let (exists, _) = &tools[1];
+	assert_eq!(
+		exists.input_schema["properties"]["path"]["required"],
+		json!(["name"])
+	);
+	assert_eq!(
+		exists.annotations.as_ref().unwrap().read_only_hint,
+		Some(true)
+	);
+}
+
+const DEFS_YAML: &str = r##"
+openapi: 3.1.0
+info: { title: Pets, version: "1" }
+paths:
+  /pets:
+    post:
+      operationId: addPet
+      requestBody:
+        content:
+          application/json:
+            schema: { $ref: "#/components/schemas/Pet" }
+      responses: {}
+components:
+  schemas:
+    Pet:
+      type: object
+      $defs:
+        Tag:
+          type: object
+          properties:
+            name: { type: string }
+        Node:
+          type: object
+          properties:
+            children:
+              type: array
+              items: { $ref: "#/$defs/Node" }
+      properties:
+        tag:
+          $ref: "#/$defs/Tag"
+          description: The tag of the pet
+        family: { $ref: "#/$defs/Node" }
+"##;
+
+#[test]
+fn test_parse_local_definitions() {
+	let schema = parse_schema(DEFS_YAML, None).unwrap();
+	let tools = parse_openapi_schema(&schema).unwrap();
+	let (tool, _) = &tools[0];
+	let pet = &tool.input_schema["properties"]["body"]["properties"];
+	assert_eq!(
+		pet["tag"],
+		json!({
+			"type": "object",
+			"description": "The tag of the pet",
+			"properties": { "name": { "type": "string" } },
+		})
+	);
+	// The recursive reference is inlined once, then accepts anything
+	let children = &pet["family"]["properties"]["children"];
+	assert_eq!(children["type"], "array");
+	assert_eq!(children["items"], json!({}));
+
+	let missing = DEFS_YAML.replace(r##""#/$defs/Tag""##, r##""#/$defs/Label""##);
+	let err = parse_schema(&missing, None).unwrap_err();
+	assert!(
+		err.to_string().contains("missing reference: #/$defs/Label"),
+		"{err}"
+	);
+}
+
+#[test]
+fn test_parse_local_definitions_keywords() {
+	let doc = r##"
+openapi: 3.1.0
+info: { title: Refs, version: "1" }
+paths:
+  /refs:
+    post:
+      operationId: addRef
+      requestBody:
+        content:
+          application/json:
+            schema:
+              type: object
+              properties:
+                $ref: { type: string }
+                $defs: { type: string }
+                tag: { $ref: "#/$defs/Tag" }
+              example: { $ref: "#/$defs/Unknown" }
+              $defs:
+                Tag: { type: string, enum: [{ $ref: "#/$defs/Unknown" }] }
+      responses: {}
+"##;
+	let schema = parse_schema(doc, None).unwrap();
+	let tools = parse_openapi_schema(&schema).unwrap();
+	let (tool, _) = &tools[0];
+	let body = &tool.input_schema["properties"]["body"];
+	assert_eq!(body["properties"]["$ref"], json!({ "type": "string" }));
+	assert_eq!(body["properties"]["$defs"], json!({ "type": "string" }));
+	assert_eq!(
+		body["properties"]["tag"],
+		json!({ "type": "string", "enum": [{ "$ref": "#/$defs/Unknown" }] })
+	);
+}
+
+#[test]
+fn test_parse_local_definitions_expansion_limit() {
+	// Each definition references the next one twice, doubling the inlined size at each level
+	let defs = (0..20)
+		.map(|i| {
+			let next = i + 1;
+			format!(
+				"        D{i}: {{ allOf: [{{ $ref: \"#/$defs/D{next}\" }}, {{ $ref: \"#/$defs/D{next}\" }}] }}\n"
+			)
+		})
+		.collect::<String>();
+	let doc = format!(
+		r##"
+openapi: 3.1.0
+info: {{ title: Wide, version: "1" }}
+paths: {{}}
+components:
+  schemas:
+    Wide:
+      $ref: "#/$defs/D0"
+      $defs:
+{defs}        D20: {{ type: string }}
+"##
+	);
+	let err = parse_schema(&doc, None).unwrap_err();
+	assert!(
+		err.to_string().contains("too many $defs references"),
+		"{err}"
+	);
+}
+
+const TENANT_YAML: &str = r#"
+openapi: 3.0.0
+info: { title: Tenants, version: "1" }
+paths:
+  /tenants/{tenantId}/pets:
+    parameters:
+      - name: tenantId
+        in: path
+        required: true
+        schema: { type: string }
+      - name: limit
+        in: query
+        schema: { type: integer }
+    get:
+      operationId: listTenantPets
+      responses: {}
+    delete:
+      operationId: deleteTenantPets
+      parameters:
+        - name: limit
+          in: query
+          description: How many pets to delete
+          schema: { type: integer }
+      responses: {}
+"#;
+
+#[tokio::test]
+async fn test_path_level_parameters() {
+	let (server, mut handler) = setup().await;
+	let schema = parse_schema(TENANT_YAML, None).unwrap();
+	handler.tools = parse_openapi_schema(&schema).unwrap();
+	assert_eq!(handler.tools.len(), 2);
+	for (tool, _) in &handler.tools {
+		assert_eq!(
+			tool.input_schema["properties"]["path"],
+			json!({
+				"type": "object",
+				"properties": { "tenantId": { "type": "string" } },
+				"required": ["tenantId"],
+			}),
+			"{}",
+			tool.name
+		);
+	}
+	// The operation's own parameter replaces the path-level one
+	let (delete, _) = handler
+		.tools
+		.iter()
+		.find(|(t, _)| t.name == "deleteTenantPets")
+		.unwrap();
+	assert_eq!(
+		delete.input_schema["properties"]["query"]["properties"]["limit"]["description"],
+		json!("How many pets to delete")
+	);
+
+	Mock::given(method("GET"))
+		.and(path("/tenants/acme/pets"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("listed"))
+		.mount(&server)
+		.await;
+	Mock::given(method("DELETE"))
+		.and(path("/tenants/acme/pets"))
+		.and(query_param("limit", "2"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("deleted"))
+		.mount(&server)
+		.await;
+
+	let args = json!({ "path": { "tenantId": "acme" } });
+	let result = handler
+		.call_tool("listTenantPets", args.as_object().cloned())
+		.await;
+	assert_eq!(text(result.unwrap()), "listed");
+	let args = json!({ "path": { "tenantId": "acme" }, "query": { "limit": 2 } });
+	let result = handler
+		.call_tool("deleteTenantPets", args.as_object().cloned())
+		.await;
+	assert_eq!(text(result.unwrap()), "deleted");
+}
+
+const REPORT_YAML: &str = r#"
+openapi: 3.0.0
+info: { title: Reports, version: "1" }
+paths:
+  /reports/{period}/{name}:
+    get:
+      operationId: getReport
+      parameters:
+        - name: period
+          in: path
+          required: true
+          schema: { type: string, enum: [daily, weekly] }
+        - name: name
+          in: path
+          required: true
+          schema: { type: string }
+      responses: {}
+"#;
+
+#[tokio::test]
+async fn test_validate_path_arguments() {
+	let (_server, mut handler) = setup().await;
+	let schema = parse_schema(REPORT_YAML, None).unwrap();
+	handler.tools = parse_openapi_schema(&schema).unwrap();
+
+	// Path parameters are checked even when argument validation is disabled
+	let args = json!({ "path": { "period": "daily", "name": "sales" } });
+	assert!(
+		handler
+			.validate_arguments("getReport", args.as_object())
+			.is_ok()
+	);
+	let args = json!({ "path": { "period": "monthly" } });
+	let err = handler
+		.validate_arguments("getReport", args.as_object())
+		.unwrap_err();
+	assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+	let mut violations =
+		serde_json::from_value::<Vec<Value>>(err.data.unwrap()["violations"].clone())
+			.unwrap()
+			.into_iter()
+			.map(|v| {
+				format!(
+					"{}: {}",
+					v["path"].as_str().unwrap(),
+					v["message"].as_str().unwrap()
+				)
+			})
+			.collect::<Vec<_>>();
+	violations.sort();
+	assert_eq!(
+		violations,
+		vec![
+			"path.name: missing required property",
+			"path.period: \"monthly\" is not one of the allowed values: \"daily\", \"weekly\"",
+		]
+	);
+	// Other arguments are only checked when validation is enabled
+	let args = json!({ "path": { "period": "weekly", "name": "sales" }, "query": 1 });
+	assert!(
+		handler
+			.validate_arguments("getReport", args.as_object())
+			.is_ok()
+	);
+}
+
+#[tokio::test]
+async fn test_call_tool_encodes_path_arguments() {
+	let (server, mut handler) = setup().await;
+	let schema = parse_schema(REPORT_YAML, None).unwrap();
+	handler.tools = parse_openapi_schema(&schema).unwrap();
+
+	Mock::given(method("GET"))
+		.and(path("/reports/daily/q1%2Fsales%20eu"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("encoded"))
+		.mount(&server)
+		.await;
+	Mock::given(method("GET"))
+		.and(path("/reports/daily/q1/sales%20eu"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("reserved"))
+		.mount(&server)
+		.await;
+
+	let args = json!({ "path": { "period": "daily", "name": "q1/sales eu" } });
+	let result = handler
+		.call_tool("getReport", args.as_object().cloned())
+		.await;
+	assert_eq!(text(result.unwrap()), "encoded");
+
+	handler.allow_reserved_path_characters = true;
+	let result = handler
+		.call_tool("getReport", args.as_object().cloned())
+		.await;
+	assert_eq!(text(result.unwrap()), "reserved");
+
+	// `?` and `#` would end the path, so they are encoded regardless
+	Mock::given(method("GET"))
+		.and(path("/reports/daily/q1%3Fsales%23eu"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("query"))
+		.mount(&server)
+		.await;
+	let args = json!({ "path": { "period": "daily", "name": "q1?sales#eu" } });
+	let result = handler
+		.call_tool("getReport", args.as_object().cloned())
+		.await;
+	assert_eq!(text(result.unwrap()), "query");
+}
+
+fn server_url(url: &str) -> Result<ServerUrl, ParseError> {
+	let schema: OpenAPI = serde_json::from_value(json!({
+		"openapi": "3.0.0",
+		"info": { "title": "test", "version": "1.0" },
+		"servers": [{ "url": url }],
+		"paths": {},
+	}))
+	.unwrap();
+	get_server_url(&schema)
+}
+
+#[test]
+fn test_server_url_scheme() {
+	let relative = server_url("/api/v3").unwrap();
+	assert_eq!(
+		relative,
+		ServerUrl {
+			scheme: None,
+			host: None,
+			port: None,
+			prefix: "/api/v3".to_string(),
+		}
+	);
+	assert_eq!(
+		server_url("https://api.example.com/v3").unwrap(),
+		ServerUrl {
+			scheme: Some(HttpScheme::Https),
+			host: Some("api.example.com".to_string()),
+			port: None,
+			prefix: "/v3".to_string(),
+		}
+	);
+	assert!(server_url("ftp://files.example.com/").is_err());
+
+	// The configured scheme wins, then the server's; https calls always use TLS
+	let https = server_url("https://api.example.com").unwrap();
+	let mut policies = BackendPolicies::default();
+	assert_eq!(
+		resolve_scheme(None, &https, &mut policies),
+		HttpScheme::Https
+	);
+	assert!(policies.backend_tls.is_some());
+	let mut policies = BackendPolicies::default();
+	assert_eq!(
+		resolve_scheme(Some(HttpScheme::Http), &https, &mut policies),
+		HttpScheme::Http
+	);
+	assert!(policies.backend_tls.is_none());
+	assert_eq!(
+		resolve_scheme(None, &relative, &mut policies),
+		HttpScheme::Http
+	);
+	let mut policies = BackendPolicies {
+		backend_tls: Some(crate::http::backendtls::SYSTEM_TRUST.clone()),
+		..Default::default()
+	};
+	assert_eq!(
+		resolve_scheme(None, &relative, &mut policies),
+		HttpScheme::Https
+	);
+}
+
+#[tokio::test]
+async fn test_call_tool_https_scheme() {
+	let (server, mut handler) = setup().await;
+	let schema = server_url("https://localhost/api").unwrap();
+	handler.scheme = resolve_scheme(None, &schema, &mut handler.policies);
+	handler.prefix = schema.prefix;
+
+	// The mock server only speaks plaintext, so an https call fails during the TLS handshake
+	let args = json!({ "path": { "user_id": "1" } });
+	let result = handler
+		.call_tool("get_user", args.as_object().cloned())
+		.await;
+	assert!(result.is_err());
+	assert!(server.received_requests().await.unwrap().is_empty());
+}
+
+#[test]
+fn test_server_url_address() {
+	let address = |host: Option<&str>, port: Option<u32>, url: &str| {
+		let server = server_url(url).unwrap();
+		let scheme = server.scheme.unwrap_or_default();
+		resolve_address(host, port, &server, scheme)
+	};
+
+	// A URL with a port
+	let url = "https://api.example.com:8443/v1";
+	assert_eq!(server_url(url).unwrap().port, Some(8443));
+	assert_eq!(
+		address(None, None, url),
+		Some(("api.example.com".to_string(), 8443))
+	);
+	// The configuration takes precedence
+	assert_eq!(
+		address(Some("internal.svc"), Some(8080), url),
+		Some(("internal.svc".to_string(), 8080))
+	);
+	assert_eq!(
+		address(Some("internal.svc"), None, url),
+		Some(("internal.svc".to_string(), 8443))
+	);
+
+	// A URL without a port uses the default port of the scheme
+	assert_eq!(
+		address(None, None, "https://api.example.com/v1"),
+		Some(("api.example.com".to_string(), 443))
+	);
+	assert_eq!(
+		address(None, None, "http://api.example.com"),
+		Some(("api.example.com".to_string(), 80))
+	);
+	assert_eq!(
+		address(None, Some(9000), "http://api.example.com"),
+		Some(("api.example.com".to_string(), 9000))
+	);
+
+	// A relative URL needs a configured host
+	assert_eq!(address(None, None, "/v1"), None);
+	assert_eq!(
+		address(Some("localhost"), None, "/v1"),
+		Some(("localhost".to_string(), 80))
+	);
+}
+
+#[tokio::test]
+async fn test_call_tool_reuses_connections() {
+	let (server, mut handler) = setup().await;
+	let mut registry = prometheus_client::registry::Registry::default();
+	let metrics = Arc::new(crate::mcp::relay::metrics::Metrics::new(
+		&mut registry,
+		None,
+	));
+	handler.client = handler.client.with_pool(&client::PoolConfig {
+		max_idle_per_host: Some(1),
+		idle_timeout: Some(Duration::from_secs(30)),
+	});
+	handler.connection_metrics = Some(ConnectionMetrics {
+		metrics,
+		server: "backend".to_string(),
+		target: "api".to_string(),
+	});
+	// An HTTP/2 connection is pooled as soon as it is established, rather than once a response has
+	// been read, so each call can reuse it without waiting for the previous one to be released.
+	handler.http_version = HttpVersionPreference::Http2;
+
+	Mock::given(method("GET"))
+		.and(path("/users/1"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+		.mount(&server)
+		.await;
+
+	let args = json!({ "path": { "user_id": "1" } });
+	for _ in 0..3 {
+		let result = handler
+			.call_tool("get_user", args.as_object().cloned())
+			.await;
+		assert_eq!(text(result.unwrap()), "ok");
+	}
+
+	let mut encoded = String::new();
+	prometheus_client::encoding::text::encode(&mut encoded, &registry).unwrap();
+	let count = |connection: &str| {
+		encoded
+			.lines()
+			.find(|l| {
+				l.starts_with("openapi_connections_total{")
+					&& l.contains(&format!("connection=\"{connection}\""))
+			})
+			.and_then(|l| l.rsplit(' ').next()?.parse::<u64>().ok())
+			.unwrap_or(0)
+	};
+	assert_eq!((count("new"), count("reused")), (1, 2), "{encoded}");
+}
@@ -0,0 +1,160 @@
+use serde::Serialize;
+use serde_json::Value;
+
+#[cfg(test)]
+#[path = "validate_tests.rs"]
+mod tests;
+
+/// A single way in which a value does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+	/// Location of the offending value, such as `body.tags[1]`. Empty for the root value.
+	pub path: String,
+	pub message: String,
+}
+
+/// Validates `value` against the subset of JSON schema produced for OpenAPI tools: `type`, `nullable`,
+/// `enum`, `required`, `properties`, `additionalProperties` and `items`. Composite schemas (`allOf`,
+/// `oneOf`, `anyOf`) are not checked, so they never cause a violation.
+///
+/// If `reject_unknown` is set, objects may only contain declared properties unless the schema
+/// explicitly allows additional properties.
+pub fn validate(schema: &Value, value: &Value, reject_unknown: bool) -> Vec<Violation> {
+	let mut violations = Vec::new();
+	check(schema, value, "", reject_unknown, &mut violations);
+	violations
+}
+
+fn check(
+	schema: &Value,
+	value: &Value,
+	path: &str,
+	reject_unknown: bool,
+	out: &mut Vec<Violation>,
+) {
+	let Some(schema) = schema.as_object() else {
+		return;
+	};
+	if ["allOf", "oneOf", "anyOf"]
+		.iter()
+		.any(|k| schema.contains_key(*k))
+	{
+		return;
+	}
+	if value.is_null() && schema.get("nullable").and_then(Value::as_bool) == Some(true) {
+		return;
+	}
+	let types: Vec<&str> = match schema.get("type") {
+		Some(Value::String(t)) => vec![t.as_str()],
+		Some(Value::Array(ts)) => ts.iter().filter_map(Value::as_str).collect(),
+		_ => vec![],
+	};
+	if !types.is_empty() && !types.iter().any(|t| matches_type(t, value)) {
+		push(
+			out,
+			path,
+			format!("expected {}, got {}", types.join(" or "), type_name(value)),
+		);
+		return;
+	}
+	if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+		if !allowed.contains(value) {
+			push(
+				out,
+				path,
+				format!(
+					"{value} is not one of the allowed values: {}",
+					allowed
+						.iter()
+						.map(Value::to_string)
+						.collect::<Vec<_>>()
+						.join(", ")
+				),
+			);
+		}
+	}
+
+	match value {
+		Value::Object(obj) => {
+			if let Some(required) = schema.get("required").and_then(Value::as_array) {
+				for key in required.iter().filter_map(Value::as_str) {
+					if !obj.contains_key(key) {
+						push(
+							out,
+							&join(path, key),
+							"missing required property".to_string(),
+						);
+					}
+				}
+			}
+			let properties = schema.get("properties").and_then(Value::as_object);
+			let additional = schema.get("additionalProperties");
+			for (key, v) in obj {
+				let child = join(path, key);
+				if let Some(property) = properties.and_then(|p| p.get(key)) {
+					check(property, v, &child, reject_unknown, out);
+					continue;
+				}
+				match additional {
+					Some(Value::Bool(false)) => push(out, &child, "unknown property".to_string()),
+					Some(additional @ Value::Object(_)) => check(additional, v, &child, reject_unknown, out),
+					// Only reject undeclared properties if the schema declares some.
+					None if reject_unknown && properties.is_some() => {
+						push(out, &child, "unknown property".to_string())
+					},
+					_ => {},
+				}
+			}
+		},
+		Value::Array(items) => {
+			if let Some(item_schema) = schema.get("items") {
+				for (i, v) in items.iter().enumerate() {
+					check(item_schema, v, &format!("{path}[{i}]"), reject_unknown, out);
+				}
+			}
+		},
+		_ => {},
+	}
+}
+
+fn push(out: &mut Vec<Violation>, path: &str, message: String) {
+	out.push(Violation {
+		path: path.to_string(),
+		message,
+	});
+}
+
+fn matches_type(t: &str, value: &Value) -> bool {
+	match t {
+		"object" => value.is_object(),
+		"array" => value.is_array(),
+		"string" => value.is_string(),
+		"boolean" => value.is_boolean(),
+		"null" => value.is_null(),
+		"number" => value.is_number(),
+		"integer" => {
+			value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+		},
+		// Unknown types are not checked
+		_ => true,
+	}
+}
+
+fn type_name(value: &Value) -> &'static str {
+	match value {
+		Value::Null => "null",
+		Value::Bool(_) => "boolean",
+		Value::Number(_) => "number",
+		Value::String(_) => "string",
+		Value::Array(_) => "array",
+		Value::Object(_) => "object",
+	}
+}
+
+fn join(path: &str, key: &str) -> String {
+	if path.is_empty() {
+		key.to_string()
+	} else {
+		format!("{path}.{key}")
+	}
+}
@@ -0,0 +1,82 @@
+use serde_json::json;
+
+use super::*;
+
+fn schema() -> Value {
+	json!({
+		"type": "object",
+		"required": ["body"],
+		"properties": {
+			"body": {
+				"type": "object",
+				"required": ["name"],
+				"properties": {
+					"name": { "type": "string" },
+					"age": { "type": "integer" },
+					"status": { "type": "string", "enum": ["active", "inactive"] },
+					"nickname": { "type": "string", "nullable": true },
+					"tags": { "type": "array", "items": { "type": "string" } },
+					"labels": { "type": "object", "additionalProperties": { "type": "string" } },
+					"pet": { "oneOf": [{ "type": "string" }, { "type": "integer" }] }
+				}
+			}
+		}
+	})
+}
+
+#[test]
+fn test_valid() {
+	let args = json!({
+		"body": {
+			"name": "a",
+			"age": 3,
+			"status": "active",
+			"nickname": null,
+			"tags": ["x"],
+			"labels": { "k": "v" },
+			"pet": true
+		}
+	});
+	assert_eq!(validate(&schema(), &args, true), vec![]);
+}
+
+#[test]
+fn test_violations() {
+	let args = json!({
+		"body": {
+			"age": 3.5,
+			"status": "gone",
+			"tags": ["x", 1],
+			"labels": { "k": 1 }
+		}
+	});
+	let violations = validate(&schema(), &args, false);
+	let mut found: Vec<_> = violations
+		.iter()
+		.map(|v| (v.path.as_str(), v.message.as_str()))
+		.collect();
+	found.sort();
+	assert_eq!(
+		found,
+		vec![
+			("body.age", "expected integer, got number"),
+			("body.labels.k", "expected string, got number"),
+			("body.name", "missing required property"),
+			(
+				"body.status",
+				"\"gone\" is not one of the allowed values: \"active\", \"inactive\""
+			),
+			("body.tags[1]", "expected string, got number"),
+		]
+	);
+}
+
+#[test]
+fn test_unknown_properties() {
+	let args = json!({ "body": { "name": "a", "extra": 1 }, "other": {} });
+	assert_eq!(validate(&schema(), &args, false), vec![]);
+	let violations = validate(&schema(), &args, true);
+	let paths: Vec<_> = violations.iter().map(|v| v.path.as_str()).collect();
+	assert_eq!(paths, vec!["body.extra", "other"]);
+	assert!(violations.iter().all(|v| v.message == "unknown property"));
+}
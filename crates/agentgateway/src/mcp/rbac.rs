@@ -121,12 +121,21 @@ impl RuleSet {
 		)
 		.context("failed to build context")?;
 
+		// Besides matching the entity exactly (`resource == Resource::"..."`), the id is exposed as a
+		// string so a single rule can cover many resources with `like`, for example
+		// `resource.id like "file:///project/*"`.
 		let resource_entity = Entity::new(
 			resource.entity(),
-			HashMap::from([(
-				"target".to_string(),
-				RestrictedExpression::new_string(resource.target().to_string()),
-			)]),
+			HashMap::from([
+				(
+					"target".to_string(),
+					RestrictedExpression::new_string(resource.target().to_string()),
+				),
+				(
+					"id".to_string(),
+					RestrictedExpression::new_string(resource.id().to_string()),
+				),
+			]),
 			HashSet::from([resource.target_entity()]),
 		)
 		.context("build entity")?;
@@ -172,6 +181,13 @@ impl ResourceType {
 	fn target_entity(&self) -> EntityUid {
 		EntityUid::from_type_name_and_id(ENTITY_NAME_TARGET.clone(), EntityId::new(self.target()))
 	}
+	fn id(&self) -> &str {
+		match self {
+			ResourceType::Tool(r) => &r.id,
+			ResourceType::Prompt(r) => &r.id,
+			ResourceType::Resource(r) => &r.id,
+		}
+	}
 	fn target(&self) -> &str {
 		match self {
 			ResourceType::Tool(r) => &r.target,
@@ -190,3 +190,53 @@ fn test_rbac_check_array_contains_match() {
 		Ok(true)
 	);
 }
+
+fn resource(uri: &str) -> ResourceType {
+	ResourceType::Resource(ResourceId::new("server".to_string(), uri.to_string()))
+}
+
+#[test]
+fn test_rbac_resource_prefix_match() {
+	let policies = vec![
+		r#"permit(principal, action == Action::"call_tool", resource is Resource) when { resource.id like "file:///project/*" };"#,
+	];
+	let rbac = RuleSet::new(create_policy_set(policies));
+	let id = Identity::empty();
+	assert!(rbac.validate(&resource("file:///project/README.md"), &id));
+	assert!(rbac.validate(&resource("file:///project/src/main.rs"), &id));
+	assert!(!rbac.validate(&resource("file:///other/README.md"), &id));
+	assert!(!rbac.validate(&resource("file:///project"), &id));
+	// Only resources are covered by the rule
+	assert!(!rbac.validate(
+		&ResourceType::Tool(ResourceId::new(
+			"server".to_string(),
+			"file:///project/tool".to_string()
+		)),
+		&id
+	));
+}
+
+#[test]
+fn test_rbac_resource_glob_match() {
+	let policies = vec![
+		r#"permit(principal, action == Action::"call_tool", resource is Resource) when { resource.id like "db://*/tables/*.csv" };"#,
+	];
+	let rbac = RuleSet::new(create_policy_set(policies));
+	let id = Identity::empty();
+	assert!(rbac.validate(&resource("db://main/tables/users.csv"), &id));
+	assert!(!rbac.validate(&resource("db://main/tables/users.json"), &id));
+	assert!(!rbac.validate(&resource("db://main/views/users.csv"), &id));
+}
+
+#[test]
+fn test_rbac_resource_exact_precedence() {
+	// An exact forbid takes precedence over a pattern permit, as with any Cedar forbid.
+	let policies = vec![
+		r#"permit(principal, action == Action::"call_tool", resource is Resource) when { resource.id like "file:///project/*" };"#,
+		r#"forbid(principal, action == Action::"call_tool", resource == Resource::"file:///project/secrets.env");"#,
+	];
+	let rbac = RuleSet::new(create_policy_set(policies));
+	let id = Identity::empty();
+	assert!(rbac.validate(&resource("file:///project/config.env"), &id));
+	assert!(!rbac.validate(&resource("file:///project/secrets.env"), &id));
+}
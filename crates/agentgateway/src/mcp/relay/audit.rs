@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Mutex;
+
+use ::http::{HeaderValue, Method, header};
+use anyhow::{Context as _, anyhow, bail};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rmcp::model::{CallToolResult, JsonObject};
+use secrecy::ExposeSecret;
+use sha2::Sha256;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use super::RqCtx;
+use crate::client;
+use crate::http::backendtls::SYSTEM_TRUST;
+use crate::types::agent::{AuditArguments, AuditSink, BackendName, McpAuditLog, Target};
+use crate::*;
+
+/// How many records can wait to be written or sent before new ones are dropped.
+const QUEUE: usize = 1024;
+
+/// The audit logs of the MCP backends, shared across sessions so each backend writes to a single
+/// sink.
+#[derive(Debug, Default)]
+pub struct Registry {
+	logs: Mutex<HashMap<BackendName, Arc<AuditLog>>>,
+}
+
+impl Registry {
+	/// Returns the audit log of a backend, creating it if needed. A log is replaced when its
+	/// configuration changes. If the sink cannot be set up, the error is logged and None returned, to
+	/// be tried again by the next session.
+	pub fn get(
+		&self,
+		backend: &BackendName,
+		config: &McpAuditLog,
+		client: &client::Client,
+	) -> Option<Arc<AuditLog>> {
+		let mut logs = self.logs.lock().expect("mutex acquired");
+		match logs.get(backend) {
+			Some(log) if &log.config == config => Some(log.clone()),
+			_ => match sink(config, client) {
+				Ok(sink) => {
+					let log = Arc::new(AuditLog::new(backend.clone(), config.clone(), sink));
+					logs.insert(backend.clone(), log.clone());
+					Some(log)
+				},
+				Err(e) => {
+					error!(
+						"failed to set up the audit log of backend {}: {:#}",
+						backend, e
+					);
+					None
+				},
+			},
+		}
+	}
+
+	/// Drops the audit log of a backend if its configuration changed or was removed, so its sink is
+	/// closed rather than kept until the backend is next used.
+	pub fn prune(&self, backend: &BackendName, config: Option<&McpAuditLog>) {
+		let mut logs = self.logs.lock().expect("mutex acquired");
+		if logs.get(backend).is_some_and(|log| Some(&log.config) != config) {
+			logs.remove(backend);
+		}
+	}
+}
+
+/// Where audit records go. Writing must not block for long, as it happens as part of each call.
+pub trait Sink: Debug + Send + Sync {
+	fn write(&self, record: &Record);
+}
+
+/// A record of a tool call.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Record {
+	pub at: DateTime<Utc>,
+	pub server: Strng,
+	pub target: String,
+	pub tool: String,
+	/// The `sub` claim of the caller, if authenticated.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub identity: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub request_id: Option<String>,
+	pub outcome: Outcome,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub arguments_hash: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub arguments: Option<serde_json::Value>,
+	/// How long the call took. Not set for denied calls, which are not sent.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Outcome {
+	/// The tool returned a result.
+	Ok,
+	/// The tool returned a result flagged as an error.
+	ToolError,
+	/// The call failed, for example because the target could not be reached.
+	Error,
+	/// The authorization policy does not allow the caller to call the tool.
+	Denied,
+}
+
+/// The tool call a record is about.
+pub struct Call<'a> {
+	pub target: &'a str,
+	pub tool: &'a str,
+	pub arguments: Option<&'a JsonObject>,
+	pub rq_ctx: &'a RqCtx,
+}
+
+/// The audit log of an MCP backend.
+#[derive(Debug)]
+pub struct AuditLog {
+	server: BackendName,
+	config: McpAuditLog,
+	sink: Arc<dyn Sink>,
+}
+
+impl AuditLog {
+	pub fn new(server: BackendName, config: McpAuditLog, sink: Arc<dyn Sink>) -> Self {
+		Self {
+			server,
+			config,
+			sink,
+		}
+	}
+
+	/// Records a call the authorization policy denied.
+	pub fn denied(&self, call: Call<'_>) {
+		self
+			.sink
+			.write(&self.record(call, Outcome::Denied, None, None));
+	}
+
+	/// Records a call that was sent to the target.
+	pub fn called(
+		&self,
+		call: Call<'_>,
+		res: Result<&CallToolResult, &rmcp::Error>,
+		duration: Duration,
+	) {
+		let (outcome, error) = match res {
+			Ok(r) if r.is_error == Some(true) => (Outcome::ToolError, None),
+			Ok(_) => (Outcome::Ok, None),
+			Err(e) => (Outcome::Error, Some(e.message.to_string())),
+		};
+		let duration = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+		self
+			.sink
+			.write(&self.record(call, outcome, error, Some(duration)));
+	}
+
+	fn record(
+		&self,
+		call: Call<'_>,
+		outcome: Outcome,
+		error: Option<String>,
+		duration_ms: Option<u64>,
+	) -> Record {
+		let (arguments_hash, arguments) = match (self.config.arguments, call.arguments) {
+			(AuditArguments::None, _) | (_, None) => (None, None),
+			(AuditArguments::Hash, Some(arguments)) => {
+				let hash = self.config.hash_key.as_ref().map(|key| {
+					let json = serde_json::Value::Object(arguments.clone()).to_string();
+					let mut mac = Hmac::<Sha256>::new_from_slice(key.0.expose_secret().as_bytes())
+						.expect("HMAC takes keys of any size");
+					mac.update(json.as_bytes());
+					hex::encode(mac.finalize().into_bytes())
+				});
+				(hash, None)
+			},
+			(AuditArguments::Full, Some(arguments)) => {
+				let mut arguments = serde_json::Value::Object(arguments.clone());
+				crate::json::redact(&mut arguments, &self.config.redact);
+				(None, Some(arguments))
+			},
+		};
+		Record {
+			at: Utc::now(),
+			server: self.server.clone(),
+			target: call.target.to_string(),
+			tool: call.tool.to_string(),
+			identity: call
+				.rq_ctx
+				.identity
+				.get_claim("sub", ".")
+				.map(str::to_string),
+			request_id: call.rq_ctx.request_id.as_ref().map(|id| id.to_string()),
+			outcome,
+			error,
+			arguments_hash,
+			arguments,
+			duration_ms,
+		}
+	}
+}
+
+fn sink(config: &McpAuditLog, client: &client::Client) -> anyhow::Result<Arc<dyn Sink>> {
+	Ok(match &config.sink {
+		AuditSink::Stdout => Arc::new(LineSink::stdout()),
+		AuditSink::File(path) => Arc::new(LineSink::file(path)?),
+		AuditSink::Webhook { url } => Arc::new(WebhookSink::new(client.clone(), url)?),
+	})
+}
+
+/// Writes each record as a line from a background task, so calls do not wait on stdout or the
+/// disk.
+#[derive(Debug)]
+pub struct LineSink {
+	lines: mpsc::Sender<Vec<u8>>,
+}
+
+impl LineSink {
+	pub fn stdout() -> Self {
+		Self::new(tokio::io::stdout())
+	}
+
+	/// Appends the records to a file, creating it if needed.
+	pub fn file(path: &std::path::Path) -> anyhow::Result<Self> {
+		let file = std::fs::OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(path)
+			.with_context(|| format!("failed to open {}", path.display()))?;
+		Ok(Self::new(tokio::fs::File::from_std(file)))
+	}
+
+	fn new(mut out: impl AsyncWrite + Unpin + Send + 'static) -> Self {
+		let (tx, mut rx) = mpsc::channel::<Vec<u8>>(QUEUE);
+		tokio::spawn(async move {
+			while let Some(line) = rx.recv().await {
+				// Written at once, so records are not interleaved with other output
+				let res = match out.write_all(&line).await {
+					Ok(()) => out.flush().await,
+					Err(e) => Err(e),
+				};
+				if let Err(e) = res {
+					warn!("failed to write audit record: {}", e);
+				}
+			}
+		});
+		Self { lines: tx }
+	}
+}
+
+impl Sink for LineSink {
+	fn write(&self, record: &Record) {
+		let Ok(mut line) = serde_json::to_vec(record) else {
+			return;
+		};
+		line.push(b'\n');
+		if self.lines.try_send(line).is_err() {
+			warn!("audit log is not keeping up, dropping record");
+		}
+	}
+}
+
+/// Sends each record to a webhook from a background task, so calls do not wait on the webhook.
+#[derive(Debug)]
+pub struct WebhookSink {
+	records: mpsc::Sender<Vec<u8>>,
+}
+
+impl WebhookSink {
+	pub fn new(client: client::Client, url: &str) -> anyhow::Result<Self> {
+		let url = url::Url::parse(url)?;
+		let transport = match url.scheme() {
+			"http" => client::Transport::Plaintext,
+			"https" => client::Transport::Tls(SYSTEM_TRUST.clone()),
+			scheme => bail!("unsupported URL scheme {scheme}"),
+		};
+		let host = url.host_str().ok_or_else(|| anyhow!("URL has no host"))?;
+		let port = url
+			.port_or_known_default()
+			.ok_or_else(|| anyhow!("URL has no port"))?;
+		let target = Target::try_from((host, port))?;
+		let (tx, mut rx) = mpsc::channel::<Vec<u8>>(QUEUE);
+		tokio::spawn(async move {
+			while let Some(body) = rx.recv().await {
+				let req = ::http::Request::builder()
+					.method(Method::POST)
+					.uri(url.as_str())
+					.header(
+						header::CONTENT_TYPE,
+						HeaderValue::from_static("application/json"),
+					)
+					.body(body.into())
+					.expect("request with known method and valid URI");
+				let res = client
+					.call(client::Call {
+						req,
+						target: target.clone(),
+						transport: transport.clone(),
+						proxy: None,
+					})
+					.await;
+				match res {
+					Ok(resp) if resp.status().is_success() => {},
+					Ok(resp) => warn!("audit webhook returned status {}", resp.status()),
+					Err(e) => warn!("failed to send audit record: {}", e),
+				}
+			}
+		});
+		Ok(Self { records: tx })
+	}
+}
+
+impl Sink for WebhookSink {
+	fn write(&self, record: &Record) {
+		let Ok(body) = serde_json::to_vec(record) else {
+			return;
+		};
+		if self.records.try_send(body).is_err() {
+			warn!("audit webhook is not keeping up, dropping record");
+		}
+	}
+}
+
+#[cfg(test)]
+#[path = "audit_tests.rs"]
+mod tests;
@@ -0,0 +1,225 @@
+use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use serde_json::json;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::*;
+use crate::http::jwt::Claims;
+use crate::mcp::rbac::Identity;
+use crate::types::agent::AuditHashKey;
+
+#[derive(Debug, Default)]
+struct MemorySink {
+	records: Mutex<Vec<Record>>,
+}
+
+impl Sink for MemorySink {
+	fn write(&self, record: &Record) {
+		self.records.lock().unwrap().push(record.clone());
+	}
+}
+
+fn audit_log(arguments: AuditArguments, redact: &[&str]) -> (AuditLog, Arc<MemorySink>) {
+	let sink = Arc::new(MemorySink::default());
+	let config = McpAuditLog {
+		sink: AuditSink::Stdout,
+		arguments,
+		hash_key: Some(AuditHashKey(secrecy::SecretString::from("audit-key"))),
+		redact: redact.iter().map(|r| r.to_string()).collect(),
+	};
+	(
+		AuditLog::new(strng::new("backend"), config, sink.clone()),
+		sink,
+	)
+}
+
+fn rq_ctx() -> RqCtx {
+	let identity = Identity {
+		claims: Some(Claims {
+			inner: json!({"sub": "alice"}).as_object().unwrap().clone(),
+			jwt: Default::default(),
+		}),
+		connection_id: None,
+	};
+	RqCtx::new(identity, opentelemetry::Context::new())
+}
+
+fn call<'a>(arguments: &'a JsonObject, rq_ctx: &'a RqCtx) -> Call<'a> {
+	Call {
+		target: "weather",
+		tool: "forecast",
+		arguments: Some(arguments),
+		rq_ctx,
+	}
+}
+
+#[test]
+fn test_denied_and_called() {
+	let (log, sink) = audit_log(AuditArguments::Hash, &[]);
+	let rq_ctx = rq_ctx();
+	let arguments = json!({"city": "Paris"}).as_object().unwrap().clone();
+
+	log.denied(call(&arguments, &rq_ctx));
+	let ok = CallToolResult {
+		content: vec![],
+		is_error: None,
+	};
+	log.called(
+		call(&arguments, &rq_ctx),
+		Ok(&ok),
+		Duration::from_millis(12),
+	);
+	let failed = rmcp::Error::invalid_request("Service weather not found", None);
+	log.called(call(&arguments, &rq_ctx), Err(&failed), Duration::ZERO);
+
+	let records = sink.records.lock().unwrap();
+	let records = records
+		.iter()
+		.map(|r| serde_json::to_value(r).unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(records.len(), 3);
+	for r in &records {
+		assert_eq!(r["server"], "backend");
+		assert_eq!(r["target"], "weather");
+		assert_eq!(r["tool"], "forecast");
+		assert_eq!(r["identity"], "alice");
+		assert_eq!(
+			r["argumentsHash"],
+			"822a7b108f00269133a000ddbbceec61b915cba2d6b6dc7eaeb7113f41aafb46"
+		);
+		assert!(r.get("arguments").is_none());
+	}
+	assert_eq!(records[0]["outcome"], "denied");
+	assert!(records[0].get("durationMs").is_none());
+	assert_eq!(records[1]["outcome"], "ok");
+	assert_eq!(records[1]["durationMs"], 12);
+	assert_eq!(records[2]["outcome"], "error");
+	assert_eq!(records[2]["error"], "Service weather not found");
+}
+
+#[test]
+fn test_arguments() {
+	let rq_ctx = RqCtx::default();
+	let arguments = json!({"city": "Paris", "auth": {"token": "secret"}})
+		.as_object()
+		.unwrap()
+		.clone();
+
+	let (log, sink) = audit_log(AuditArguments::Full, &["auth.token"]);
+	log.denied(call(&arguments, &rq_ctx));
+	let (none, none_sink) = audit_log(AuditArguments::None, &[]);
+	none.denied(call(&arguments, &rq_ctx));
+
+	let record = sink.records.lock().unwrap()[0].clone();
+	assert_eq!(
+		record.arguments,
+		Some(json!({"city": "Paris", "auth": {"token": "[REDACTED]"}}))
+	);
+	assert_eq!(record.arguments_hash, None);
+	assert_eq!(record.identity, None);
+	let record = none_sink.records.lock().unwrap()[0].clone();
+	assert_eq!((record.arguments, record.arguments_hash), (None, None));
+}
+
+async fn read_lines(file: &std::path::Path, n: usize) -> Vec<String> {
+	for _ in 0..100 {
+		let contents = std::fs::read_to_string(file).unwrap_or_default();
+		if contents.lines().count() >= n {
+			return contents.lines().map(str::to_string).collect();
+		}
+		tokio::time::sleep(Duration::from_millis(10)).await;
+	}
+	panic!("{n} records were not written to {}", file.display());
+}
+
+#[tokio::test]
+async fn test_file_sink() {
+	let dir = tempfile::tempdir().unwrap();
+	let file = dir.path().join("audit.jsonl");
+	let config = McpAuditLog {
+		sink: AuditSink::File(file.clone()),
+		arguments: AuditArguments::Hash,
+		hash_key: None,
+		redact: vec![],
+	};
+	let log = AuditLog::new(
+		strng::new("backend"),
+		config.clone(),
+		Arc::new(LineSink::file(&file).unwrap()),
+	);
+	let rq_ctx = rq_ctx();
+	let arguments = JsonObject::new();
+	log.denied(call(&arguments, &rq_ctx));
+	read_lines(&file, 1).await;
+	// Records are appended to an existing file
+	let log = AuditLog::new(
+		strng::new("backend"),
+		config,
+		Arc::new(LineSink::file(&file).unwrap()),
+	);
+	log.denied(call(&arguments, &rq_ctx));
+
+	let lines = read_lines(&file, 2).await;
+	assert_eq!(lines.len(), 2);
+	for line in lines {
+		let record: serde_json::Value = serde_json::from_str(&line).unwrap();
+		assert_eq!(record["outcome"], "denied");
+	}
+}
+
+#[tokio::test]
+async fn test_webhook_sink() {
+	let server = MockServer::start().await;
+	Mock::given(method("POST"))
+		.and(path("/audit"))
+		.and(body_partial_json(
+			json!({"tool": "forecast", "outcome": "denied"}),
+		))
+		.respond_with(ResponseTemplate::new(200))
+		.expect(1)
+		.mount(&server)
+		.await;
+	let client = client::Client::new(
+		&client::Config {
+			resolver_cfg: ResolverConfig::default(),
+			resolver_opts: ResolverOpts::default(),
+		},
+		None,
+	);
+	let registry = Registry::default();
+	let config = McpAuditLog {
+		sink: AuditSink::Webhook {
+			url: format!("{}/audit", server.uri()),
+		},
+		arguments: AuditArguments::Hash,
+		hash_key: None,
+		redact: vec![],
+	};
+	let log = registry
+		.get(&strng::new("backend"), &config, &client)
+		.unwrap();
+	// The log is shared until its configuration changes
+	let shared = registry
+		.get(&strng::new("backend"), &config, &client)
+		.unwrap();
+	assert!(Arc::ptr_eq(&log, &shared));
+	// Pruning keeps it while the configuration is the same, and drops it once removed
+	registry.prune(&strng::new("backend"), Some(&config));
+	let shared = registry
+		.get(&strng::new("backend"), &config, &client)
+		.unwrap();
+	assert!(Arc::ptr_eq(&log, &shared));
+	registry.prune(&strng::new("backend"), None);
+	assert!(registry.logs.lock().unwrap().is_empty());
+
+	let rq_ctx = rq_ctx();
+	let arguments = JsonObject::new();
+	log.denied(call(&arguments, &rq_ctx));
+	for _ in 0..100 {
+		if !server.received_requests().await.unwrap().is_empty() {
+			break;
+		}
+		tokio::time::sleep(Duration::from_millis(10)).await;
+	}
+	server.verify().await;
+}
@@ -0,0 +1,92 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::types::agent::BackendName;
+use crate::*;
+
+/// The balancers of every MCP target group, shared across sessions so calls are spread across the
+/// members regardless of which session makes them.
+#[derive(Debug, Default)]
+pub struct Registry {
+	balancers: Mutex<HashMap<(BackendName, Strng), Arc<Balancer>>>,
+}
+
+impl Registry {
+	/// Returns the balancer for a group, creating it if needed. A balancer is reset when the members
+	/// or their weights change.
+	pub fn get(
+		&self,
+		backend: &BackendName,
+		group: &Strng,
+		members: Vec<(Strng, u32)>,
+	) -> Arc<Balancer> {
+		let mut balancers = self.balancers.lock().expect("mutex acquired");
+		let key = (backend.clone(), group.clone());
+		match balancers.get(&key) {
+			Some(b) if b.members == members => b.clone(),
+			_ => {
+				let b = Arc::new(Balancer::new(members));
+				balancers.insert(key, b.clone());
+				b
+			},
+		}
+	}
+
+	/// Drops the balancers of `backend` other than those of `groups`, so groups removed from the
+	/// configuration do not linger.
+	pub fn prune<'a>(&self, backend: &BackendName, groups: impl IntoIterator<Item = &'a Strng>) {
+		let keep: HashSet<&Strng> = groups.into_iter().collect();
+		self
+			.balancers
+			.lock()
+			.expect("mutex acquired")
+			.retain(|(b, g), _| b != backend || keep.contains(g));
+	}
+}
+
+/// Spreads calls across the members of a target group in proportion to their weights, using smooth
+/// weighted round robin: with weights 3 and 1, every four calls go `a, a, b, a`, rather than in a
+/// burst to each member.
+#[derive(Debug)]
+pub struct Balancer {
+	members: Vec<(Strng, u32)>,
+	// The current weight of each member, in the same order.
+	current: Mutex<Vec<i64>>,
+}
+
+impl Balancer {
+	pub fn new(members: Vec<(Strng, u32)>) -> Self {
+		let current = Mutex::new(vec![0; members.len()]);
+		Self { members, current }
+	}
+
+	/// The member names, in configuration order.
+	pub fn members(&self) -> impl Iterator<Item = &Strng> {
+		self.members.iter().map(|(name, _)| name)
+	}
+
+	/// Picks the member for the next call among those `available` accepts. Members with weight 0 are
+	/// never picked.
+	pub fn pick(&self, available: impl Fn(&str) -> bool) -> Option<Strng> {
+		let mut current = self.current.lock().expect("mutex acquired");
+		let mut total = 0;
+		let mut best: Option<usize> = None;
+		for (i, (name, weight)) in self.members.iter().enumerate() {
+			if *weight == 0 || !available(name) {
+				continue;
+			}
+			current[i] += *weight as i64;
+			total += *weight as i64;
+			if best.is_none_or(|b| current[i] > current[b]) {
+				best = Some(i);
+			}
+		}
+		let best = best?;
+		current[best] -= total;
+		Some(self.members[best].0.clone())
+	}
+}
+
+#[cfg(test)]
+#[path = "balancer_tests.rs"]
+mod tests;
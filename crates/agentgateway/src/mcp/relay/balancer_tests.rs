@@ -0,0 +1,88 @@
+use super::*;
+
+fn balancer(weights: &[(&str, u32)]) -> Balancer {
+	Balancer::new(
+		weights
+			.iter()
+			.map(|(name, weight)| (strng::new(name), *weight))
+			.collect(),
+	)
+}
+
+fn picks(b: &Balancer, n: usize, available: impl Fn(&str) -> bool) -> Vec<Strng> {
+	(0..n).map(|_| b.pick(&available).unwrap()).collect()
+}
+
+#[test]
+fn test_weighted_spread() {
+	let b = balancer(&[("a", 3), ("b", 1)]);
+	assert_eq!(picks(&b, 4, |_| true), vec!["a", "a", "b", "a"]);
+
+	let b = balancer(&[("a", 5), ("b", 3), ("c", 2), ("drained", 0)]);
+	let picked = picks(&b, 100, |_| true);
+	let count = |name: &str| picked.iter().filter(|p| p.as_str() == name).count();
+	assert_eq!(
+		(count("a"), count("b"), count("c"), count("drained")),
+		(50, 30, 20, 0)
+	);
+	// Calls are interleaved rather than sent in bursts
+	assert!(picked.windows(3).all(|w| !(w[0] == w[1] && w[1] == w[2])));
+}
+
+#[test]
+fn test_round_robin() {
+	let b = balancer(&[("a", 1), ("b", 1), ("c", 1)]);
+	assert_eq!(picks(&b, 6, |_| true), vec!["a", "b", "c", "a", "b", "c"]);
+}
+
+#[test]
+fn test_unavailable_members_are_skipped() {
+	let b = balancer(&[("a", 3), ("b", 1), ("c", 1)]);
+	let picked = picks(&b, 10, |m| m != "a");
+	assert_eq!(picked.iter().filter(|p| p.as_str() == "b").count(), 5);
+	assert_eq!(picked.iter().filter(|p| p.as_str() == "c").count(), 5);
+
+	assert!(b.pick(|_| false).is_none());
+	assert!(balancer(&[("a", 0)]).pick(|_| true).is_none());
+}
+
+#[test]
+fn test_registry_resets_on_config_change() {
+	let r = Registry::default();
+	let (backend, group) = (strng::new("backend"), strng::new("group"));
+	let members = vec![(strng::new("a"), 1), (strng::new("b"), 1)];
+	let b = r.get(&backend, &group, members.clone());
+	assert_eq!(b.pick(|_| true).unwrap(), "a");
+	// The same balancer is shared, so the next call goes to the other member
+	let b = r.get(&backend, &group, members);
+	assert_eq!(b.pick(|_| true).unwrap(), "b");
+
+	let b = r.get(
+		&backend,
+		&group,
+		vec![(strng::new("a"), 2), (strng::new("b"), 1)],
+	);
+	assert_eq!(b.pick(|_| true).unwrap(), "a");
+	assert_eq!(b.members().collect::<Vec<_>>(), vec!["a", "b"]);
+}
+
+#[test]
+fn test_registry_prune() {
+	let r = Registry::default();
+	let (backend, other_backend) = (strng::new("backend"), strng::new("other"));
+	let (a, b) = (strng::new("a"), strng::new("b"));
+	let members = vec![(strng::new("x"), 1), (strng::new("y"), 1)];
+	let pick = |backend: &BackendName, group: &Strng| {
+		let balancer = r.get(backend, group, members.clone());
+		balancer.pick(|_| true).unwrap()
+	};
+	for (backend, group) in [(&backend, &a), (&backend, &b), (&other_backend, &a)] {
+		assert_eq!(pick(backend, group), "x");
+	}
+
+	// Group b was removed from the backend, so it starts over if it is added back
+	r.prune(&backend, [&a]);
+	assert_eq!(pick(&backend, &a), "y");
+	assert_eq!(pick(&backend, &b), "x");
+	assert_eq!(pick(&other_backend, &a), "y");
+}
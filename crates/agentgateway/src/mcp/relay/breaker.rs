@@ -0,0 +1,217 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+
+use crate::types::agent::{BackendName, McpCircuitBreaker};
+use crate::*;
+
+const DEFAULT_CONSECUTIVE_FAILURES: u32 = 5;
+const DEFAULT_WINDOW: u32 = 20;
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// The circuit breakers of every MCP target, shared across sessions so failures seen by one client
+/// protect the others.
+#[derive(Debug, Default)]
+pub struct Registry {
+	breakers: Mutex<HashMap<(BackendName, Strng), Arc<CircuitBreaker>>>,
+}
+
+impl Registry {
+	/// Returns the breaker for a target, creating it if needed. A breaker is reset when its
+	/// configuration changes.
+	pub fn get(
+		&self,
+		backend: &BackendName,
+		target: &Strng,
+		config: &McpCircuitBreaker,
+	) -> Arc<CircuitBreaker> {
+		let mut breakers = self.breakers.lock().expect("mutex acquired");
+		let key = (backend.clone(), target.clone());
+		match breakers.get(&key) {
+			Some(b) if &b.config == config => b.clone(),
+			_ => {
+				let b = Arc::new(CircuitBreaker::new(config.clone()));
+				breakers.insert(key, b.clone());
+				b
+			},
+		}
+	}
+
+	/// Drops the breakers of `backend` other than those of `targets`, so targets removed from the
+	/// configuration, or no longer configured with a breaker, do not linger.
+	pub fn prune<'a>(&self, backend: &BackendName, targets: impl IntoIterator<Item = &'a Strng>) {
+		let keep: HashSet<&Strng> = targets.into_iter().collect();
+		self
+			.breakers
+			.lock()
+			.expect("mutex acquired")
+			.retain(|(b, t), _| b != backend || keep.contains(t));
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+	/// Calls go through, and their outcomes are tracked.
+	Closed,
+	/// Calls fail fast until the cooldown elapses.
+	Open,
+	/// A single probe call is let through to check whether the target recovered.
+	HalfOpen,
+}
+
+/// Stops calling a target that keeps failing. The breaker opens after too many failures, rejects
+/// calls while open, and after a cooldown lets a probe call through: if it succeeds the breaker
+/// closes, otherwise it opens again.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+	config: McpCircuitBreaker,
+	inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+	state: State,
+	consecutive_failures: u32,
+	// Outcomes of the most recent calls, true for failures. Only tracked if a failure ratio is set.
+	window: VecDeque<bool>,
+	opened_at: Option<Instant>,
+	// When the outstanding probe was let through. A probe that never reports back (for example,
+	// because the call was cancelled) is replaced once another cooldown elapses.
+	probe_started: Option<Instant>,
+}
+
+impl CircuitBreaker {
+	pub fn new(config: McpCircuitBreaker) -> Self {
+		Self {
+			config,
+			inner: Mutex::new(Inner {
+				state: State::Closed,
+				consecutive_failures: 0,
+				window: VecDeque::new(),
+				opened_at: None,
+				probe_started: None,
+			}),
+		}
+	}
+
+	fn cooldown(&self) -> Duration {
+		self.config.cooldown.unwrap_or(DEFAULT_COOLDOWN)
+	}
+
+	fn window_size(&self) -> usize {
+		self.config.window.unwrap_or(DEFAULT_WINDOW).max(1) as usize
+	}
+
+	pub fn state(&self) -> State {
+		self.inner.lock().expect("mutex acquired").state
+	}
+
+	/// Returns whether `try_acquire` would permit a call, without acquiring it.
+	pub fn available(&self) -> bool {
+		self.available_at(Instant::now())
+	}
+
+	fn available_at(&self, now: Instant) -> bool {
+		let cooldown = self.cooldown();
+		let inner = self.inner.lock().expect("mutex acquired");
+		let elapsed =
+			|t: Option<Instant>| t.is_none_or(|t| now.saturating_duration_since(t) >= cooldown);
+		match inner.state {
+			State::Closed => true,
+			State::Open => elapsed(inner.opened_at),
+			State::HalfOpen => elapsed(inner.probe_started),
+		}
+	}
+
+	/// Returns whether a call may be made. Every permitted call must report its outcome with
+	/// `record`.
+	pub fn try_acquire(&self) -> bool {
+		self.try_acquire_at(Instant::now())
+	}
+
+	fn try_acquire_at(&self, now: Instant) -> bool {
+		let cooldown = self.cooldown();
+		let mut inner = self.inner.lock().expect("mutex acquired");
+		match inner.state {
+			State::Closed => true,
+			State::Open => {
+				let elapsed = inner
+					.opened_at
+					.is_none_or(|t| now.saturating_duration_since(t) >= cooldown);
+				if elapsed {
+					inner.state = State::HalfOpen;
+					inner.probe_started = Some(now);
+				}
+				elapsed
+			},
+			State::HalfOpen => {
+				let stale = inner
+					.probe_started
+					.is_none_or(|t| now.saturating_duration_since(t) >= cooldown);
+				if stale {
+					inner.probe_started = Some(now);
+				}
+				stale
+			},
+		}
+	}
+
+	/// Records the outcome of a permitted call.
+	pub fn record(&self, success: bool) {
+		self.record_at(success, Instant::now())
+	}
+
+	fn record_at(&self, success: bool, now: Instant) {
+		let window_size = self.window_size();
+		let mut inner = self.inner.lock().expect("mutex acquired");
+		if success {
+			if inner.state != State::Closed {
+				debug!("circuit breaker closed");
+				// The failures that opened the breaker no longer count once a probe succeeds
+				inner.window.clear();
+			}
+			inner.state = State::Closed;
+			inner.consecutive_failures = 0;
+			inner.opened_at = None;
+			inner.probe_started = None;
+		} else {
+			inner.consecutive_failures += 1;
+		}
+		if self.config.failure_ratio.is_some() {
+			inner.window.push_back(!success);
+			while inner.window.len() > window_size {
+				inner.window.pop_front();
+			}
+		}
+		let trip = match inner.state {
+			// A failed probe reopens the breaker straight away
+			State::HalfOpen => !success,
+			State::Open => false,
+			State::Closed => {
+				let threshold = self
+					.config
+					.consecutive_failures
+					.unwrap_or(DEFAULT_CONSECUTIVE_FAILURES);
+				// Checked after successes too, as the window may fill up with one
+				let ratio_exceeded = self.config.failure_ratio.is_some_and(|ratio| {
+					inner.window.len() >= window_size
+						&& inner.window.iter().filter(|f| **f).count() as f64 >= ratio * window_size as f64
+				});
+				(!success && threshold > 0 && inner.consecutive_failures >= threshold) || ratio_exceeded
+			},
+		};
+		if trip {
+			debug!(
+				failures = inner.consecutive_failures,
+				"circuit breaker opened"
+			);
+			inner.state = State::Open;
+			inner.opened_at = Some(now);
+			inner.probe_started = None;
+			inner.window.clear();
+		}
+	}
+}
+
+#[cfg(test)]
+#[path = "breaker_tests.rs"]
+mod tests;
@@ -0,0 +1,153 @@
+use super::*;
+
+fn breaker(
+	consecutive_failures: Option<u32>,
+	failure_ratio: Option<f64>,
+	window: Option<u32>,
+) -> CircuitBreaker {
+	CircuitBreaker::new(McpCircuitBreaker {
+		consecutive_failures,
+		failure_ratio,
+		window,
+		cooldown: Some(Duration::from_secs(10)),
+	})
+}
+
+#[test]
+fn test_open_half_open_close() {
+	let b = breaker(Some(3), None, None);
+	let start = Instant::now();
+	for _ in 0..2 {
+		assert!(b.try_acquire_at(start));
+		b.record_at(false, start);
+	}
+	assert_eq!(b.state(), State::Closed);
+	assert!(b.try_acquire_at(start));
+	b.record_at(false, start);
+	assert_eq!(b.state(), State::Open);
+
+	// Calls fail fast until the cooldown elapses
+	assert!(!b.available_at(start + Duration::from_secs(5)));
+	assert!(!b.try_acquire_at(start + Duration::from_secs(5)));
+
+	// Then a single probe is let through
+	let probe = start + Duration::from_secs(10);
+	assert!(b.available_at(probe));
+	assert_eq!(b.state(), State::Open);
+	assert!(b.try_acquire_at(probe));
+	assert_eq!(b.state(), State::HalfOpen);
+	assert!(!b.available_at(probe));
+	assert!(!b.try_acquire_at(probe));
+
+	// A failed probe reopens the breaker
+	b.record_at(false, probe);
+	assert_eq!(b.state(), State::Open);
+	assert!(!b.try_acquire_at(probe + Duration::from_secs(5)));
+
+	// A successful probe closes it
+	let probe = probe + Duration::from_secs(10);
+	assert!(b.try_acquire_at(probe));
+	b.record_at(true, probe);
+	assert_eq!(b.state(), State::Closed);
+	assert!(b.try_acquire_at(probe));
+
+	// Consecutive failures start counting from zero again
+	for _ in 0..2 {
+		b.record_at(false, probe);
+	}
+	assert_eq!(b.state(), State::Closed);
+}
+
+#[test]
+fn test_success_resets_consecutive_failures() {
+	let b = breaker(Some(2), None, None);
+	let now = Instant::now();
+	for _ in 0..5 {
+		b.record_at(false, now);
+		b.record_at(true, now);
+	}
+	assert_eq!(b.state(), State::Closed);
+}
+
+#[test]
+fn test_failure_ratio() {
+	// Consecutive failures alone never trip this breaker
+	let b = breaker(Some(0), Some(0.5), Some(4));
+	let now = Instant::now();
+	// The ratio only applies once the window is full
+	b.record_at(false, now);
+	b.record_at(false, now);
+	assert_eq!(b.state(), State::Closed);
+	b.record_at(true, now);
+	b.record_at(true, now);
+	assert_eq!(b.state(), State::Open);
+
+	let b = breaker(Some(0), Some(0.5), Some(4));
+	for success in [false, true, true, true, false, true, true] {
+		b.record_at(success, now);
+	}
+	assert_eq!(b.state(), State::Closed);
+}
+
+#[test]
+fn test_stale_probe_is_replaced() {
+	let b = breaker(Some(1), None, None);
+	let start = Instant::now();
+	b.record_at(false, start);
+	let probe = start + Duration::from_secs(10);
+	assert!(b.try_acquire_at(probe));
+	// The probe never reports back
+	assert!(!b.try_acquire_at(probe + Duration::from_secs(5)));
+	assert!(b.try_acquire_at(probe + Duration::from_secs(10)));
+}
+
+#[test]
+fn test_registry_resets_on_config_change() {
+	let r = Registry::default();
+	let (backend, target) = (strng::new("backend"), strng::new("target"));
+	let config = McpCircuitBreaker {
+		consecutive_failures: Some(1),
+		failure_ratio: None,
+		window: None,
+		cooldown: None,
+	};
+	let b = r.get(&backend, &target, &config);
+	b.record(false);
+	assert_eq!(r.get(&backend, &target, &config).state(), State::Open);
+	assert_eq!(
+		r.get(&backend, &strng::new("other"), &config).state(),
+		State::Closed
+	);
+
+	let changed = McpCircuitBreaker {
+		consecutive_failures: Some(2),
+		..config
+	};
+	assert_eq!(r.get(&backend, &target, &changed).state(), State::Closed);
+}
+
+#[test]
+fn test_registry_prune() {
+	let r = Registry::default();
+	let (backend, other_backend) = (strng::new("backend"), strng::new("other"));
+	let (a, b) = (strng::new("a"), strng::new("b"));
+	let config = McpCircuitBreaker {
+		consecutive_failures: Some(1),
+		failure_ratio: None,
+		window: None,
+		cooldown: None,
+	};
+	for (backend, target) in [(&backend, &a), (&backend, &b), (&other_backend, &a)] {
+		r.get(backend, target, &config).record(false);
+	}
+
+	// Target b was removed from the backend
+	r.prune(&backend, [&a]);
+	assert_eq!(r.get(&backend, &a, &config).state(), State::Open);
+	assert_eq!(r.get(&backend, &b, &config).state(), State::Closed);
+	assert_eq!(r.get(&other_backend, &a, &config).state(), State::Open);
+
+	// The backend no longer has a breaker
+	r.prune(&backend, []);
+	assert_eq!(r.get(&backend, &a, &config).state(), State::Closed);
+}
@@ -0,0 +1,47 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::client;
+use crate::types::agent::{BackendName, UpstreamConnectionPool};
+use crate::*;
+
+/// The clients of the MCP targets with their own connection pool, shared across sessions so every
+/// session calling a target reuses the same idle connections.
+#[derive(Debug, Default)]
+pub struct Registry {
+	clients: Mutex<HashMap<(BackendName, Strng), (UpstreamConnectionPool, client::Client)>>,
+}
+
+impl Registry {
+	/// Returns the client for a target, creating it from `base` if needed. A client, along with its
+	/// idle connections, is replaced when the pool settings change.
+	pub fn get(
+		&self,
+		backend: &BackendName,
+		target: &Strng,
+		pool: &UpstreamConnectionPool,
+		base: &client::Client,
+	) -> client::Client {
+		let mut clients = self.clients.lock().expect("mutex acquired");
+		let key = (backend.clone(), target.clone());
+		match clients.get(&key) {
+			Some((settings, c)) if settings == pool => c.clone(),
+			_ => {
+				let c = base.with_pool(&pool.pool_config());
+				clients.insert(key, (pool.clone(), c.clone()));
+				c
+			},
+		}
+	}
+
+	/// Drops the clients of `backend` other than those of `targets`, so targets removed from the
+	/// configuration, or no longer configured with their own pool, do not keep idle connections open.
+	pub fn prune<'a>(&self, backend: &BackendName, targets: impl IntoIterator<Item = &'a Strng>) {
+		let keep: HashSet<&Strng> = targets.into_iter().collect();
+		self
+			.clients
+			.lock()
+			.expect("mutex acquired")
+			.retain(|(b, t), _| b != backend || keep.contains(t));
+	}
+}
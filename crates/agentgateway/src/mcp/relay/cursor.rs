@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rmcp::Error as McpError;
+use rmcp::model::PaginatedRequestParam;
+
+/// The position of a list aggregated from several targets: the targets with more to list, and the
+/// cursor each of them returned. Clients see it as an opaque cursor, the URL-safe base64 of its
+/// JSON.
+///
+/// A target that failed or missed the deadline is not in the cursor, so it is not listed again in
+/// later pages.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ListCursor {
+	targets: BTreeMap<String, String>,
+}
+
+impl ListCursor {
+	/// Decodes the cursor of a list request. A request without one lists from the beginning.
+	pub fn from_request(request: Option<&PaginatedRequestParam>) -> Result<Option<Self>, McpError> {
+		let Some(cursor) = request.and_then(|r| r.cursor.as_deref()) else {
+			return Ok(None);
+		};
+		URL_SAFE_NO_PAD
+			.decode(cursor)
+			.ok()
+			.and_then(|json| serde_json::from_slice(&json).ok())
+			.map(Some)
+			.ok_or_else(|| McpError::invalid_params("invalid cursor", None))
+	}
+
+	/// The request listing the next page of a target, or None if the target has nothing left to
+	/// list. Without a cursor, every target is listed from the beginning.
+	pub fn request(cursor: Option<&Self>, target: &str) -> Option<Option<PaginatedRequestParam>> {
+		match cursor {
+			None => Some(None),
+			Some(cursor) => {
+				let next = cursor.targets.get(target)?;
+				Some(Some(PaginatedRequestParam {
+					cursor: Some(next.clone()),
+				}))
+			},
+		}
+	}
+
+	/// Records where a target is after listing a page: the cursor it returned, if it has more.
+	pub fn insert(&mut self, target: &str, next_cursor: Option<String>) {
+		if let Some(next) = next_cursor {
+			self.targets.insert(target.to_string(), next);
+		}
+	}
+
+	/// The cursor to return to the client, or None once every target was fully listed.
+	pub fn encode(&self) -> Option<String> {
+		if self.targets.is_empty() {
+			return None;
+		}
+		let json = serde_json::to_vec(self).expect("cursor serializes");
+		Some(URL_SAFE_NO_PAD.encode(json))
+	}
+}
+
+#[cfg(test)]
+#[path = "cursor_tests.rs"]
+mod tests;
@@ -0,0 +1,102 @@
+use super::*;
+
+/// A target listing `items` in pages of `page_size`, with the offset of the next page as cursor.
+fn page(
+	items: &[&str],
+	page_size: usize,
+	request: Option<PaginatedRequestParam>,
+) -> (Vec<String>, Option<String>) {
+	let start = request
+		.and_then(|r| r.cursor)
+		.map(|c| c.parse::<usize>().unwrap())
+		.unwrap_or_default();
+	let end = (start + page_size).min(items.len());
+	let next = (end < items.len()).then(|| end.to_string());
+	(
+		items[start..end].iter().map(|i| i.to_string()).collect(),
+		next,
+	)
+}
+
+#[test]
+fn test_paging_multiple_targets() {
+	let targets: [(&str, &[&str], usize); 3] = [
+		("a", &["a1", "a2", "a3", "a4", "a5"], 2),
+		("b", &["b1"], 10),
+		("c", &["c1", "c2", "c3"], 2),
+	];
+	let mut listed = vec![];
+	let mut pages = 0;
+	let mut cursor: Option<String> = None;
+	loop {
+		let request = cursor
+			.clone()
+			.map(|c| PaginatedRequestParam { cursor: Some(c) });
+		let current = ListCursor::from_request(request.as_ref()).unwrap();
+		let mut next = ListCursor::default();
+		for (name, items, page_size) in targets {
+			let Some(request) = ListCursor::request(current.as_ref(), name) else {
+				continue;
+			};
+			let (items, next_cursor) = page(items, page_size, request);
+			next.insert(name, next_cursor);
+			listed.extend(items);
+		}
+		pages += 1;
+		cursor = next.encode();
+		if cursor.is_none() {
+			break;
+		}
+	}
+	assert_eq!(pages, 3);
+	listed.sort();
+	assert_eq!(
+		listed,
+		vec!["a1", "a2", "a3", "a4", "a5", "b1", "c1", "c2", "c3"]
+	);
+}
+
+#[test]
+fn test_cursor_encoding() {
+	let mut cursor = ListCursor::default();
+	assert_eq!(cursor.encode(), None);
+	cursor.insert("a", None);
+	assert_eq!(cursor.encode(), None, "fully listed targets are left out");
+	cursor.insert("a", Some("page/2".to_string()));
+	let encoded = cursor.encode().unwrap();
+	assert!(
+		encoded
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+		"{encoded}"
+	);
+	let request = PaginatedRequestParam {
+		cursor: Some(encoded),
+	};
+	let decoded = ListCursor::from_request(Some(&request)).unwrap().unwrap();
+	assert_eq!(decoded, cursor);
+	assert_eq!(
+		ListCursor::request(Some(&decoded), "a")
+			.unwrap()
+			.unwrap()
+			.cursor
+			.as_deref(),
+		Some("page/2")
+	);
+	assert!(ListCursor::request(Some(&decoded), "b").is_none());
+	// Without a cursor, every target is listed from the start
+	assert!(matches!(ListCursor::request(None, "b"), Some(None)));
+	assert_eq!(ListCursor::from_request(None).unwrap(), None);
+}
+
+#[test]
+fn test_invalid_cursor() {
+	for cursor in ["not base64!", "bm90IGpzb24"] {
+		let request = PaginatedRequestParam {
+			cursor: Some(cursor.to_string()),
+		};
+		let err = ListCursor::from_request(Some(&request)).unwrap_err();
+		assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+		assert_eq!(err.message, "invalid cursor");
+	}
+}
@@ -0,0 +1,91 @@
+use futures::stream::BoxStream;
+use futures::{Sink, SinkExt, StreamExt};
+use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::PollSender;
+
+use crate::client::Client;
+use crate::http::ext_proc::GrpcChannel;
+use crate::store::BackendPolicies;
+use crate::types::agent::Target;
+use crate::*;
+
+#[cfg(test)]
+#[path = "grpc_tests.rs"]
+mod tests;
+
+#[allow(warnings)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+pub mod proto {
+	tonic::include_proto!("agentgateway.dev.mcp");
+}
+
+// Number of messages buffered towards the server before sends start waiting.
+const BUFFER_SIZE: usize = 64;
+
+/// Opens an MCP session with a server speaking the `agentgateway.dev.mcp.Mcp` gRPC service, returning a
+/// sink and stream that can be used as an rmcp transport.
+pub(crate) async fn connect(
+	client: Client,
+	policies: BackendPolicies,
+	target: Target,
+) -> anyhow::Result<(
+	impl Sink<ClientJsonRpcMessage, Error = std::io::Error> + Send + Unpin + 'static,
+	FromServer,
+)> {
+	let channel = GrpcChannel {
+		target,
+		transport: policies.backend_tls.clone().into(),
+		client,
+		backend_auth: policies.backend_auth,
+		proxy: policies.outbound_proxy,
+	};
+	let (tx, rx) = tokio::sync::mpsc::channel::<ClientJsonRpcMessage>(BUFFER_SIZE);
+	let to_server = ReceiverStream::new(rx).filter_map(|msg| {
+		let res = match serde_json::to_vec(&msg) {
+			Ok(payload) => Some(proto::JsonRpcMessage { payload }),
+			Err(e) => {
+				warn!("failed to encode message for grpc MCP server: {e}");
+				None
+			},
+		};
+		futures::future::ready(res)
+	});
+	let response = proto::mcp_client::McpClient::new(channel)
+		.session(to_server)
+		.await?;
+	let from_server = response
+		.into_inner()
+		.take_while(|res| {
+			if let Err(status) = res {
+				debug!("grpc MCP stream ended: {status}");
+			}
+			futures::future::ready(res.is_ok())
+		})
+		.filter_map(|res| {
+			let msg = res.ok().and_then(|msg| {
+				serde_json::from_slice::<ServerJsonRpcMessage>(&msg.payload)
+					.inspect_err(|e| warn!("invalid message from grpc MCP server: {e}"))
+					.ok()
+			});
+			futures::future::ready(msg)
+		})
+		.boxed();
+	let to_server = PollSender::new(tx).sink_map_err(std::io::Error::other);
+	Ok((to_server, FromServer(from_server)))
+}
+
+/// The messages from the server. A named type rather than a `BoxStream`, as the compiler fails to
+/// prove a boxed stream is `'static` in the `Send` futures of the rmcp handler methods.
+pub(crate) struct FromServer(BoxStream<'static, ServerJsonRpcMessage>);
+
+impl futures::Stream for FromServer {
+	type Item = ServerJsonRpcMessage;
+
+	fn poll_next(
+		mut self: std::pin::Pin<&mut Self>,
+		cx: &mut std::task::Context<'_>,
+	) -> std::task::Poll<Option<Self::Item>> {
+		self.0.poll_next_unpin(cx)
+	}
+}
@@ -0,0 +1,110 @@
+use futures::stream::BoxStream;
+use rmcp::ServiceExt;
+use rmcp::model::CallToolRequestParam;
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+
+use super::*;
+
+/// A gRPC MCP server that answers `initialize`, `tools/list` and `tools/call` for a single `echo` tool.
+struct MockServer;
+
+fn reply(msg: Value) -> Option<Value> {
+	let id = msg.get("id")?.clone();
+	let result = match msg.get("method")?.as_str()? {
+		"initialize" => json!({
+			"protocolVersion": "2025-03-26",
+			"capabilities": {"tools": {}},
+			"serverInfo": {"name": "mock", "version": "1.0.0"},
+		}),
+		"tools/list" => json!({"tools": [{"name": "echo", "inputSchema": {"type": "object"}}]}),
+		"tools/call" => json!({
+			"content": [{"type": "text", "text": msg["params"]["arguments"]["text"]}],
+		}),
+		_ => {
+			return Some(json!({
+				"jsonrpc": "2.0",
+				"id": id,
+				"error": {"code": -32601, "message": "method not found"},
+			}));
+		},
+	};
+	Some(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+}
+
+#[tonic::async_trait]
+impl proto::mcp_server::Mcp for MockServer {
+	type SessionStream = BoxStream<'static, Result<proto::JsonRpcMessage, tonic::Status>>;
+
+	async fn session(
+		&self,
+		req: tonic::Request<tonic::Streaming<proto::JsonRpcMessage>>,
+	) -> Result<tonic::Response<Self::SessionStream>, tonic::Status> {
+		let out = req.into_inner().filter_map(|msg| async move {
+			let msg = serde_json::from_slice(&msg.ok()?.payload).ok()?;
+			let payload = serde_json::to_vec(&reply(msg)?).unwrap();
+			Some(Ok(proto::JsonRpcMessage { payload }))
+		});
+		Ok(tonic::Response::new(out.boxed()))
+	}
+}
+
+async fn mock_server() -> SocketAddr {
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(
+		tonic::transport::Server::builder()
+			.add_service(proto::mcp_server::McpServer::new(MockServer))
+			.serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+	);
+	addr
+}
+
+fn client() -> Client {
+	use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+	Client::new(
+		&client::Config {
+			resolver_cfg: ResolverConfig::default(),
+			resolver_opts: ResolverOpts::default(),
+		},
+		None,
+	)
+}
+
+#[tokio::test]
+async fn test_grpc_session() {
+	let addr = mock_server().await;
+	let transport = connect(client(), BackendPolicies::default(), Target::Address(addr))
+		.await
+		.unwrap();
+	let mcp = ().serve(transport).await.unwrap();
+	assert_eq!(mcp.peer_info().unwrap().server_info.name, "mock");
+
+	let tools = mcp.list_tools(None).await.unwrap().tools;
+	assert_eq!(
+		tools.iter().map(|t| t.name.as_ref()).collect::<Vec<_>>(),
+		vec!["echo"]
+	);
+
+	let res = mcp
+		.call_tool(CallToolRequestParam {
+			name: "echo".into(),
+			arguments: json!({"text": "hello"}).as_object().cloned(),
+		})
+		.await
+		.unwrap();
+	assert_eq!(res.content[0].as_text().unwrap().text, "hello");
+	mcp.cancel().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_grpc_connect_failure() {
+	// Nothing is listening once the listener is dropped
+	let addr = TcpListener::bind("127.0.0.1:0")
+		.await
+		.unwrap()
+		.local_addr()
+		.unwrap();
+	let res = connect(client(), BackendPolicies::default(), Target::Address(addr)).await;
+	assert!(res.is_err());
+}
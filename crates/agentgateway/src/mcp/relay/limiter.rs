@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use agent_core::metrics::Recorder;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::mcp::relay::metrics;
+use crate::types::agent::{BackendName, McpConcurrencyLimit};
+use crate::*;
+
+/// The concurrency limiters of every MCP target, shared across sessions so the limit covers all the
+/// calls made to a target.
+#[derive(Debug, Default)]
+pub struct Registry {
+	limiters: Mutex<HashMap<(BackendName, Strng), Arc<ConcurrencyLimiter>>>,
+}
+
+impl Registry {
+	/// Returns the limiter for a target, creating it if needed. A limiter is replaced when its
+	/// configuration changes; calls holding a permit from the old one still count towards the metrics.
+	pub fn get(
+		&self,
+		backend: &BackendName,
+		target: &Strng,
+		config: &McpConcurrencyLimit,
+		metrics: &Arc<metrics::Metrics>,
+	) -> Arc<ConcurrencyLimiter> {
+		let mut limiters = self.limiters.lock().expect("mutex acquired");
+		let key = (backend.clone(), target.clone());
+		match limiters.get(&key) {
+			Some(l) if &l.config == config => l.clone(),
+			_ => {
+				let l = Arc::new(ConcurrencyLimiter::new(
+					config.clone(),
+					metrics.clone(),
+					backend.to_string(),
+					target.to_string(),
+				));
+				limiters.insert(key, l.clone());
+				l
+			},
+		}
+	}
+
+	/// Drops the limiters of `backend` other than those of `targets`, so targets removed from the
+	/// configuration, or no longer configured with a limit, do not linger.
+	pub fn prune<'a>(&self, backend: &BackendName, targets: impl IntoIterator<Item = &'a Strng>) {
+		let keep: HashSet<&Strng> = targets.into_iter().collect();
+		self
+			.limiters
+			.lock()
+			.expect("mutex acquired")
+			.retain(|(b, t), _| b != backend || keep.contains(t));
+	}
+}
+
+/// Bounds the tool calls in flight to a target. Calls beyond the limit wait for a permit, in order,
+/// up to the configured queue depth; once the queue is full further calls are rejected.
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+	config: McpConcurrencyLimit,
+	semaphore: Arc<Semaphore>,
+	queued: AtomicU32,
+	metrics: Arc<metrics::Metrics>,
+	server: String,
+	target: String,
+}
+
+impl ConcurrencyLimiter {
+	pub fn new(
+		config: McpConcurrencyLimit,
+		metrics: Arc<metrics::Metrics>,
+		server: String,
+		target: String,
+	) -> Self {
+		let semaphore = Arc::new(Semaphore::new(config.max_concurrent.get() as usize));
+		Self {
+			config,
+			semaphore,
+			queued: AtomicU32::new(0),
+			metrics,
+			server,
+			target,
+		}
+	}
+
+	/// Waits for a permit to call the target, or returns None if the queue is full. The call is
+	/// counted as in flight until the permit is dropped.
+	pub async fn acquire(self: &Arc<Self>) -> Option<Permit> {
+		let permit = match self.semaphore.clone().try_acquire_owned() {
+			Ok(permit) => permit,
+			Err(_) => {
+				let max_queued = self.config.max_queued.unwrap_or(0);
+				self
+					.queued
+					.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |q| {
+						(q < max_queued).then_some(q + 1)
+					})
+					.ok()?;
+				// Left in the queue if the call is cancelled while waiting
+				let _queued = Queued::new(self);
+				self
+					.semaphore
+					.clone()
+					.acquire_owned()
+					.await
+					.expect("semaphore is never closed")
+			},
+		};
+		self.record_in_flight(1);
+		Some(Permit {
+			_permit: permit,
+			limiter: self.clone(),
+		})
+	}
+
+	/// The number of calls holding a permit.
+	pub fn in_flight(&self) -> usize {
+		self.config.max_concurrent.get() as usize - self.semaphore.available_permits()
+	}
+
+	/// The number of calls waiting for a permit.
+	pub fn queued(&self) -> u32 {
+		self.queued.load(Ordering::SeqCst)
+	}
+
+	fn record_in_flight(&self, delta: i64) {
+		self.metrics.record(
+			metrics::ToolCallsInFlight {
+				server: self.server.clone(),
+				target: self.target.clone(),
+			},
+			delta,
+		);
+	}
+
+	fn record_queued(&self, delta: i64) {
+		self.metrics.record(
+			metrics::ToolCallsQueued {
+				server: self.server.clone(),
+				target: self.target.clone(),
+			},
+			delta,
+		);
+	}
+}
+
+/// Counts a call as queued while it waits for a permit.
+struct Queued<'a>(&'a ConcurrencyLimiter);
+
+impl<'a> Queued<'a> {
+	fn new(limiter: &'a ConcurrencyLimiter) -> Self {
+		limiter.record_queued(1);
+		Self(limiter)
+	}
+}
+
+impl Drop for Queued<'_> {
+	fn drop(&mut self) {
+		self.0.queued.fetch_sub(1, Ordering::SeqCst);
+		self.0.record_queued(-1);
+	}
+}
+
+#[derive(Debug)]
+pub struct Permit {
+	_permit: OwnedSemaphorePermit,
+	limiter: Arc<ConcurrencyLimiter>,
+}
+
+impl Drop for Permit {
+	fn drop(&mut self) {
+		self.limiter.record_in_flight(-1);
+	}
+}
+
+#[cfg(test)]
+#[path = "limiter_tests.rs"]
+mod tests;
@@ -0,0 +1,103 @@
+use std::num::NonZeroU32;
+use std::sync::atomic::AtomicUsize;
+
+use prometheus_client::registry::Registry as MetricsRegistry;
+
+use super::*;
+
+fn limiter(max_concurrent: u32, max_queued: Option<u32>) -> Arc<ConcurrencyLimiter> {
+	let metrics = Arc::new(metrics::Metrics::new(&mut MetricsRegistry::default(), None));
+	Arc::new(ConcurrencyLimiter::new(
+		McpConcurrencyLimit {
+			max_concurrent: NonZeroU32::new(max_concurrent).unwrap(),
+			max_queued,
+		},
+		metrics,
+		"backend".to_string(),
+		"target".to_string(),
+	))
+}
+
+#[tokio::test]
+async fn test_limit_under_load() {
+	let l = limiter(3, Some(100));
+	let running = Arc::new(AtomicUsize::new(0));
+	let peak = Arc::new(AtomicUsize::new(0));
+	let calls = (0..50).map(|_| {
+		let (l, running, peak) = (l.clone(), running.clone(), peak.clone());
+		tokio::spawn(async move {
+			let _permit = l.acquire().await.expect("queued");
+			let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+			peak.fetch_max(now, Ordering::SeqCst);
+			tokio::time::sleep(Duration::from_millis(2)).await;
+			running.fetch_sub(1, Ordering::SeqCst);
+		})
+	});
+	for call in calls.collect::<Vec<_>>() {
+		call.await.unwrap();
+	}
+	assert_eq!(peak.load(Ordering::SeqCst), 3);
+	assert_eq!((l.in_flight(), l.queued()), (0, 0));
+}
+
+#[tokio::test]
+async fn test_queue_depth() {
+	let l = limiter(1, Some(1));
+	let first = l.acquire().await.unwrap();
+	assert_eq!(l.in_flight(), 1);
+
+	// The second call waits for the first
+	let second = tokio::spawn({
+		let l = l.clone();
+		async move { l.acquire().await.is_some() }
+	});
+	while l.queued() == 0 {
+		tokio::task::yield_now().await;
+	}
+	// The queue is full, so the third is rejected
+	assert!(l.acquire().await.is_none());
+
+	drop(first);
+	assert!(second.await.unwrap());
+	assert_eq!((l.in_flight(), l.queued()), (0, 0));
+
+	// Without a queue, calls beyond the limit are rejected straight away
+	let l = limiter(1, None);
+	let _first = l.acquire().await.unwrap();
+	assert!(l.acquire().await.is_none());
+}
+
+#[tokio::test]
+async fn test_cancelled_while_queued() {
+	let l = limiter(1, Some(1));
+	let _first = l.acquire().await.unwrap();
+	let waiting = tokio::time::timeout(Duration::from_millis(10), l.acquire()).await;
+	assert!(waiting.is_err());
+	// The cancelled call no longer holds a place in the queue
+	assert_eq!(l.queued(), 0);
+}
+
+#[test]
+fn test_registry_prune() {
+	let r = Registry::default();
+	let metrics = Arc::new(metrics::Metrics::new(&mut MetricsRegistry::default(), None));
+	let (backend, other_backend) = (strng::new("backend"), strng::new("other"));
+	let (a, b) = (strng::new("a"), strng::new("b"));
+	let config = McpConcurrencyLimit {
+		max_concurrent: NonZeroU32::new(1).unwrap(),
+		max_queued: None,
+	};
+	let get = |backend: &BackendName, target: &Strng| r.get(backend, target, &config, &metrics);
+	let keys = [(&backend, &a), (&backend, &b), (&other_backend, &a)];
+	let limiters = keys.map(|(backend, target)| get(backend, target));
+
+	// Target b was removed from the backend
+	r.prune(&backend, [&a]);
+	assert!(Arc::ptr_eq(&get(&backend, &a), &limiters[0]));
+	assert!(!Arc::ptr_eq(&get(&backend, &b), &limiters[1]));
+	assert!(Arc::ptr_eq(&get(&other_backend, &a), &limiters[2]));
+
+	// The backend no longer has a limit
+	r.prune(&backend, []);
+	assert!(!Arc::ptr_eq(&get(&backend, &a), &limiters[0]));
+}
@@ -1,9 +1,12 @@
 use std::collections::HashMap;
 
 use agent_core::metrics::Recorder;
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::{Counter as OtelCounter, Meter};
 use prometheus_client::encoding::EncodeLabelSet;
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
 use prometheus_client::registry::Registry;
 
 use crate::mcp::rbac;
@@ -15,8 +18,44 @@ pub struct Metrics {
 	list_calls: Family<ListCall, Counter>,
 	read_resource_calls: Family<GetResourceCall, Counter>,
 	get_prompt_calls: Family<GetPromptCall, Counter>,
+	sse_streams_abandoned: Family<SseStreamAbandoned, Counter>,
+	pool_connections: Family<PoolConnections, Gauge>,
+	pool_evictions: Family<PoolEviction, Counter>,
+	stdio_restarts: Family<StdioRestart, Counter>,
+	sse_reconnects: Family<SseReconnect, Counter>,
+	openapi_schema_refreshes: Family<OpenAPISchemaRefresh, Counter>,
+	circuit_breaker_rejections: Family<CircuitBreakerRejection, Counter>,
+	tool_calls_in_flight: Family<ToolCallsInFlight, Gauge>,
+	tool_calls_queued: Family<ToolCallsQueued, Gauge>,
+	concurrency_limit_rejections: Family<ConcurrencyLimitRejection, Counter>,
+	openapi_connections: Family<OpenAPIConnection, Counter>,
+	list_timeouts: Family<ListTimeout, Counter>,
 
 	additional_tags: Option<HashMap<String, String>>,
+	otel: Option<OtelCounters>,
+}
+
+/// The call counters, as OpenTelemetry instruments, to export them over OTLP too.
+#[derive(Debug)]
+struct OtelCounters {
+	tool_calls: OtelCounter<u64>,
+	tool_call_errors: OtelCounter<u64>,
+	list_calls: OtelCounter<u64>,
+	read_resource_calls: OtelCounter<u64>,
+	get_prompt_calls: OtelCounter<u64>,
+}
+
+/// The attributes of a call: its labels, then the additional tags.
+fn attributes(labels: &[(&'static str, &str)], params: &[(String, String)]) -> Vec<KeyValue> {
+	labels
+		.iter()
+		.map(|(k, v)| KeyValue::new(*k, v.to_string()))
+		.chain(
+			params
+				.iter()
+				.map(|(k, v)| KeyValue::new(k.clone(), v.clone())),
+		)
+		.collect()
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
@@ -35,6 +74,81 @@ pub struct GetPromptCall {
 	pub params: Vec<(String, String)>,
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct SseStreamAbandoned {
+	pub server: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct PoolConnections {
+	pub server: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct PoolEviction {
+	pub server: String,
+	pub target: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct StdioRestart {
+	pub server: String,
+	pub target: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct SseReconnect {
+	pub server: String,
+	pub target: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct OpenAPISchemaRefresh {
+	pub server: String,
+	pub target: String,
+	pub result: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CircuitBreakerRejection {
+	pub server: String,
+	pub target: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ToolCallsInFlight {
+	pub server: String,
+	pub target: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ToolCallsQueued {
+	pub server: String,
+	pub target: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ConcurrencyLimitRejection {
+	pub server: String,
+	pub target: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct OpenAPIConnection {
+	pub server: String,
+	pub target: String,
+	/// `new` if the call opened a connection, or `reused` if it was sent on an idle one.
+	pub connection: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ListTimeout {
+	pub server: String,
+	pub target: String,
+	/// What was listed: `tool`, `prompt`, `resource` or `resource_template`.
+	pub resource_type: String,
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
 pub struct ListCall {
 	pub resource_type: String,
@@ -96,16 +210,135 @@ impl Metrics {
 			get_prompt_calls.clone(),
 		);
 
+		let sse_streams_abandoned = Family::default();
+		registry.register(
+			"sse_streams_abandoned",
+			"The total number of SSE streams abandoned because the client disconnected",
+			sse_streams_abandoned.clone(),
+		);
+
+		let pool_connections = Family::default();
+		registry.register(
+			"pool_connections",
+			"The number of upstream connections currently held by relay pools",
+			pool_connections.clone(),
+		);
+
+		let pool_evictions = Family::default();
+		registry.register(
+			"pool_evictions",
+			"The total number of upstream connections evicted from relay pools after being idle",
+			pool_evictions.clone(),
+		);
+
+		let stdio_restarts = Family::default();
+		registry.register(
+			"stdio_restarts",
+			"The total number of stdio MCP server processes restarted after exiting",
+			stdio_restarts.clone(),
+		);
+
+		let sse_reconnects = Family::default();
+		registry.register(
+			"sse_reconnects",
+			"The total number of attempts to reconnect a dropped SSE stream to an MCP server",
+			sse_reconnects.clone(),
+		);
+
+		let openapi_schema_refreshes = Family::default();
+		registry.register(
+			"openapi_schema_refreshes",
+			"The total number of times a remote OpenAPI schema was checked for changes, by result",
+			openapi_schema_refreshes.clone(),
+		);
+
+		let circuit_breaker_rejections = Family::default();
+		registry.register(
+			"circuit_breaker_rejections",
+			"The total number of tool calls rejected because the target's circuit breaker was open",
+			circuit_breaker_rejections.clone(),
+		);
+
+		let tool_calls_in_flight = Family::default();
+		registry.register(
+			"tool_calls_in_flight",
+			"The number of tool calls currently sent to a target with a concurrency limit",
+			tool_calls_in_flight.clone(),
+		);
+
+		let tool_calls_queued = Family::default();
+		registry.register(
+			"tool_calls_queued",
+			"The number of tool calls waiting because the target's concurrency limit was reached",
+			tool_calls_queued.clone(),
+		);
+
+		let concurrency_limit_rejections = Family::default();
+		registry.register(
+			"concurrency_limit_rejections",
+			"The total number of tool calls rejected because the target's concurrency limit and queue were full",
+			concurrency_limit_rejections.clone(),
+		);
+
+		let openapi_connections = Family::default();
+		registry.register(
+			"openapi_connections",
+			"The total number of calls to OpenAPI targets, by whether they opened a new connection or reused an idle one",
+			openapi_connections.clone(),
+		);
+
+		let list_timeouts = Family::default();
+		registry.register(
+			"list_timeouts",
+			"The total number of targets left out of a list because they did not answer by the deadline",
+			list_timeouts.clone(),
+		);
+
 		Self {
 			tool_calls,
 			tool_call_errors,
 			list_calls,
 			read_resource_calls,
 			get_prompt_calls,
+			sse_streams_abandoned,
+			pool_connections,
+			pool_evictions,
+			stdio_restarts,
+			sse_reconnects,
+			openapi_schema_refreshes,
+			circuit_breaker_rejections,
+			tool_calls_in_flight,
+			tool_calls_queued,
+			concurrency_limit_rejections,
+			openapi_connections,
+			list_timeouts,
 			additional_tags,
+			otel: None,
 		}
 	}
 
+	/// Also records the tool, list, resource and prompt calls with OpenTelemetry instruments from
+	/// `meter`, under the same names. The other metrics are only served to Prometheus.
+	pub fn with_meter(mut self, meter: &Meter) -> Self {
+		let counter = |name: &'static str, description: &'static str| {
+			meter
+				.u64_counter(name)
+				.with_description(description)
+				.build()
+		};
+		self.otel = Some(OtelCounters {
+			tool_calls: counter("tool_calls", "The total number of tool calls"),
+			tool_call_errors: counter("tool_call_errors", "The total number of tool call errors"),
+			list_calls: counter("list_calls", "The total number of list calls"),
+			read_resource_calls: counter(
+				"read_resource_calls",
+				"The total number of read resource calls",
+			),
+			get_prompt_calls: counter("get_prompt_calls", "The total number of get prompt calls"),
+		});
+		self
+	}
+
 	fn add_additional_tags(&self, identity: &rbac::Identity, params: &mut Vec<(String, String)>) {
 		let Some(tags) = &self.additional_tags else {
 			return;
@@ -128,6 +361,15 @@ impl Metrics {
 impl Recorder<ToolCall, &rbac::Identity> for Metrics {
 	fn record(&self, mut tool_call: ToolCall, identity: &rbac::Identity) {
 		self.add_additional_tags(identity, &mut tool_call.params);
+		if let Some(otel) = &self.otel {
+			otel.tool_calls.add(
+				1,
+				&attributes(
+					&[("server", &tool_call.server), ("name", &tool_call.name)],
+					&tool_call.params,
+				),
+			);
+		}
 		self.tool_calls.get_or_create(&tool_call).inc();
 	}
 }
@@ -135,6 +377,19 @@ impl Recorder<ToolCall, &rbac::Identity> for Metrics {
 impl Recorder<ToolCallError, &rbac::Identity> for Metrics {
 	fn record(&self, mut tool_call_error: ToolCallError, identity: &rbac::Identity) {
 		self.add_additional_tags(identity, &mut tool_call_error.params);
+		if let Some(otel) = &self.otel {
+			otel.tool_call_errors.add(
+				1,
+				&attributes(
+					&[
+						("server", &tool_call_error.server),
+						("name", &tool_call_error.name),
+						("error_type", &tool_call_error.error_type),
+					],
+					&tool_call_error.params,
+				),
+			);
+		}
 		self.tool_call_errors.get_or_create(&tool_call_error).inc();
 	}
 }
@@ -142,6 +397,15 @@ impl Recorder<ToolCallError, &rbac::Identity> for Metrics {
 impl Recorder<ListCall, &rbac::Identity> for Metrics {
 	fn record(&self, mut list_call: ListCall, identity: &rbac::Identity) {
 		self.add_additional_tags(identity, &mut list_call.params);
+		if let Some(otel) = &self.otel {
+			otel.list_calls.add(
+				1,
+				&attributes(
+					&[("resource_type", &list_call.resource_type)],
+					&list_call.params,
+				),
+			);
+		}
 		self.list_calls.get_or_create(&list_call).inc();
 	}
 }
@@ -149,6 +413,18 @@ impl Recorder<ListCall, &rbac::Identity> for Metrics {
 impl Recorder<GetResourceCall, &rbac::Identity> for Metrics {
 	fn record(&self, mut get_resource_call: GetResourceCall, identity: &rbac::Identity) {
 		self.add_additional_tags(identity, &mut get_resource_call.params);
+		if let Some(otel) = &self.otel {
+			otel.read_resource_calls.add(
+				1,
+				&attributes(
+					&[
+						("server", &get_resource_call.server),
+						("uri", &get_resource_call.uri),
+					],
+					&get_resource_call.params,
+				),
+			);
+		}
 		self
 			.read_resource_calls
 			.get_or_create(&get_resource_call)
@@ -159,6 +435,109 @@ impl Recorder<GetResourceCall, &rbac::Identity> for Metrics {
 impl Recorder<GetPromptCall, &rbac::Identity> for Metrics {
 	fn record(&self, mut get_prompt_call: GetPromptCall, identity: &rbac::Identity) {
 		self.add_additional_tags(identity, &mut get_prompt_call.params);
+		if let Some(otel) = &self.otel {
+			otel.get_prompt_calls.add(
+				1,
+				&attributes(
+					&[
+						("server", &get_prompt_call.server),
+						("name", &get_prompt_call.name),
+					],
+					&get_prompt_call.params,
+				),
+			);
+		}
 		self.get_prompt_calls.get_or_create(&get_prompt_call).inc();
 	}
 }
+
+impl Recorder<SseStreamAbandoned, ()> for Metrics {
+	fn record(&self, sse_stream_abandoned: SseStreamAbandoned, _: ()) {
+		self
+			.sse_streams_abandoned
+			.get_or_create(&sse_stream_abandoned)
+			.inc();
+	}
+}
+
+impl Recorder<PoolConnections, i64> for Metrics {
+	fn record(&self, pool_connections: PoolConnections, delta: i64) {
+		self
+			.pool_connections
+			.get_or_create(&pool_connections)
+			.inc_by(delta);
+	}
+}
+
+impl Recorder<PoolEviction, ()> for Metrics {
+	fn record(&self, pool_eviction: PoolEviction, _: ()) {
+		self.pool_evictions.get_or_create(&pool_eviction).inc();
+	}
+}
+
+impl Recorder<StdioRestart, ()> for Metrics {
+	fn record(&self, stdio_restart: StdioRestart, _: ()) {
+		self.stdio_restarts.get_or_create(&stdio_restart).inc();
+	}
+}
+
+impl Recorder<SseReconnect, ()> for Metrics {
+	fn record(&self, sse_reconnect: SseReconnect, _: ()) {
+		self.sse_reconnects.get_or_create(&sse_reconnect).inc();
+	}
+}
+
+impl Recorder<OpenAPISchemaRefresh, ()> for Metrics {
+	fn record(&self, refresh: OpenAPISchemaRefresh, _: ()) {
+		self.openapi_schema_refreshes.get_or_create(&refresh).inc();
+	}
+}
+
+impl Recorder<CircuitBreakerRejection, ()> for Metrics {
+	fn record(&self, rejection: CircuitBreakerRejection, _: ()) {
+		self
+			.circuit_breaker_rejections
+			.get_or_create(&rejection)
+			.inc();
+	}
+}
+
+impl Recorder<ToolCallsInFlight, i64> for Metrics {
+	fn record(&self, in_flight: ToolCallsInFlight, delta: i64) {
+		self
+			.tool_calls_in_flight
+			.get_or_create(&in_flight)
+			.inc_by(delta);
+	}
+}
+
+impl Recorder<ToolCallsQueued, i64> for Metrics {
+	fn record(&self, queued: ToolCallsQueued, delta: i64) {
+		self.tool_calls_queued.get_or_create(&queued).inc_by(delta);
+	}
+}
+
+impl Recorder<ConcurrencyLimitRejection, ()> for Metrics {
+	fn record(&self, rejection: ConcurrencyLimitRejection, _: ()) {
+		self
+			.concurrency_limit_rejections
+			.get_or_create(&rejection)
+			.inc();
+	}
+}
+
+impl Recorder<OpenAPIConnection, ()> for Metrics {
+	fn record(&self, connection: OpenAPIConnection, _: ()) {
+		self.openapi_connections.get_or_create(&connection).inc();
+	}
+}
+
+impl Recorder<ListTimeout, ()> for Metrics {
+	fn record(&self, timeout: ListTimeout, _: ()) {
+		self.list_timeouts.get_or_create(&timeout).inc();
+	}
+}
+
+#[cfg(test)]
+#[path = "metrics_tests.rs"]
+mod tests;
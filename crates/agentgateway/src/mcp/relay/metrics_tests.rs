@@ -0,0 +1,63 @@
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+use prometheus_client::registry::Registry;
+
+use super::*;
+
+#[test]
+fn test_tool_call_otel() {
+	let exporter = InMemoryMetricExporter::default();
+	let provider = SdkMeterProvider::builder()
+		.with_reader(PeriodicReader::builder(exporter.clone()).build())
+		.build();
+	let metrics = Metrics::new(&mut Registry::default(), None).with_meter(&provider.meter("test"));
+	for _ in 0..2 {
+		metrics.record(
+			ToolCall {
+				server: "github".to_string(),
+				name: "list_issues".to_string(),
+				params: vec![],
+			},
+			&rbac::Identity::empty(),
+		);
+	}
+	provider.force_flush().unwrap();
+
+	let exported = exporter.get_finished_metrics().unwrap();
+	let metric = exported
+		.iter()
+		.flat_map(|m| m.scope_metrics())
+		.flat_map(|s| s.metrics())
+		.find(|m| m.name() == "tool_calls")
+		.expect("tool_calls is exported");
+	let AggregatedMetrics::U64(MetricData::Sum(sum)) = metric.data() else {
+		panic!("tool_calls is not a u64 sum: {:?}", metric.data());
+	};
+	let point = sum.data_points().next().expect("a data point");
+	assert_eq!(point.value(), 2);
+	let mut attributes: Vec<_> = point
+		.attributes()
+		.map(|kv| (kv.key.to_string(), kv.value.to_string()))
+		.collect();
+	attributes.sort();
+	assert_eq!(
+		attributes,
+		vec![
+			("name".to_string(), "list_issues".to_string()),
+			("server".to_string(), "github".to_string()),
+		]
+	);
+	// Prometheus keeps counting the same calls
+	assert_eq!(
+		metrics
+			.tool_calls
+			.get_or_create(&ToolCall {
+				server: "github".to_string(),
+				name: "list_issues".to_string(),
+				params: vec![],
+			})
+			.get(),
+		2
+	);
+}
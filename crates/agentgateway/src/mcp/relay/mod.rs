@@ -7,43 +7,114 @@ use http::{HeaderMap, HeaderName, HeaderValue};
 use itertools::Itertools;
 use opentelemetry::global::BoxedSpan;
 use opentelemetry::trace::{SpanContext, SpanKind, TraceContextExt, TraceState, Tracer};
-use opentelemetry::{Context, TraceFlags};
+use opentelemetry::{Context, KeyValue, TraceFlags};
 use rmcp::model::{CallToolRequestParam, Tool, *};
-use rmcp::service::{RequestContext, RunningService};
+use rmcp::service::{Peer, RequestContext, RunningService};
 use rmcp::transport::child_process::TokioChildProcess;
 use rmcp::{Error as McpError, RoleClient, RoleServer, ServerHandler, model};
 use std::any::{Any, TypeId};
 use std::borrow::Cow;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
+use std::future::Future;
 use std::hash::BuildHasherDefault;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::process::Command;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, RwLockWriteGuard};
 use tracing::instrument;
 
 use crate::client;
 use crate::http::jwt::Claims;
 use crate::mcp::rbac;
 use crate::mcp::rbac::{Identity, RuleSets};
+use crate::mcp::relay::cursor::ListCursor;
 use crate::mcp::sse::{MCPInfo, McpBackendGroup};
+use crate::proxy::inflight::InFlight;
 use crate::store::Stores;
-use crate::telemetry::log::AsyncLog;
+use crate::telemetry::log::{AsyncLog, RequestId};
 use crate::telemetry::trc::TraceParent;
 use crate::transport::stream::{TCPConnectionInfo, TLSConnectionInfo};
-use crate::types::agent::{McpAuthorization, McpBackend};
+use crate::types::agent::{
+	McpAuthorization, McpBackend, McpCapability, McpListDeadline, McpReadOnly, McpTargetSpec,
+	McpToolMerge, OpenAPISchema, PayloadLogging,
+};
 
+pub mod audit;
+pub mod balancer;
+pub mod breaker;
+pub mod clients;
+mod cursor;
+mod grpc;
+pub mod limiter;
 pub mod metrics;
 mod pool;
+pub mod status;
+mod stderr;
 pub mod upstream;
 
 const DELIMITER: &str = "_";
 
+/// Returned for calls rejected because the target's circuit breaker is open.
+pub const CIRCUIT_OPEN_ERROR_CODE: ErrorCode = ErrorCode(-32050);
+
+/// Returned for calls rejected because the target's concurrency limit and queue are full.
+pub const CONCURRENCY_LIMIT_ERROR_CODE: ErrorCode = ErrorCode(-32051);
+
+/// Returned for calls to tools that may change state, when the backend is read-only.
+pub const READ_ONLY_ERROR_CODE: ErrorCode = ErrorCode(-32052);
+
+/// Merges the capabilities of each target into those we advertise. We only advertise the
+/// capabilities we know how to relay.
+fn merge_capabilities(targets: impl IntoIterator<Item = ServerCapabilities>) -> ServerCapabilities {
+	let mut merged = ServerCapabilities::default();
+	for caps in targets {
+		if caps.tools.is_some() {
+			merged.tools = Some(ToolsCapability::default());
+		}
+		if caps.prompts.is_some() {
+			merged.prompts = Some(PromptsCapability::default());
+		}
+		if let Some(resources) = caps.resources {
+			let merged_resources = merged.resources.get_or_insert_default();
+			// Subscriptions are relayed to the owning target, so advertise them if any target supports them
+			if resources.subscribe == Some(true) {
+				merged_resources.subscribe = Some(true);
+			}
+		}
+		if caps.completions.is_some() {
+			merged.completions = Some(Default::default());
+		}
+		if caps.logging.is_some() {
+			merged.logging = Some(Default::default());
+		}
+	}
+	merged
+}
+
+fn capabilities_from_config(caps: &[McpCapability]) -> ServerCapabilities {
+	let mut merged = ServerCapabilities::default();
+	for cap in caps {
+		match cap {
+			McpCapability::Tools => merged.tools = Some(ToolsCapability::default()),
+			McpCapability::Prompts => merged.prompts = Some(PromptsCapability::default()),
+			McpCapability::Resources => merged.resources = Some(ResourcesCapability::default()),
+			McpCapability::Completions => merged.completions = Some(Default::default()),
+			McpCapability::Logging => merged.logging = Some(Default::default()),
+		}
+	}
+	merged
+}
+
+/// Protocol versions we will accept from a client, rather than answering with our own default.
+pub(crate) const SUPPORTED_PROTOCOL_VERSIONS: &[ProtocolVersion] =
+	&[ProtocolVersion::V_2025_03_26, ProtocolVersion::V_2024_11_05];
+
 #[derive(Clone, Debug)]
 pub struct RqCtx {
 	identity: Identity,
 	context: Context,
+	request_id: Option<RequestId>,
 }
 
 impl Default for RqCtx {
@@ -51,24 +122,55 @@ impl Default for RqCtx {
 		Self {
 			identity: Identity::default(),
 			context: Context::new(),
+			request_id: None,
 		}
 	}
 }
 
 impl RqCtx {
 	pub fn new(identity: Identity, context: Context) -> Self {
-		Self { identity, context }
+		Self {
+			identity,
+			context,
+			request_id: None,
+		}
+	}
+
+	pub fn with_request_id(mut self, request_id: Option<RequestId>) -> Self {
+		self.request_id = request_id;
+		self
 	}
 }
 
 #[derive(Clone)]
 pub struct Relay {
 	pool: Arc<RwLock<pool::ConnectionPool>>,
+	// The targets, for what can be looked up without the pool lock.
+	backend: McpBackendGroup,
 	metrics: Arc<metrics::Metrics>,
 	policies: RuleSets,
 	// If we have 1 target only, we don't prefix everything with 'target_'.
 	// Else this is empty
 	default_target_name: Option<String>,
+	info: ServerInfo,
+	// If the protocol version is configured we always advertise it, rather than negotiating.
+	fixed_protocol_version: bool,
+	// If the capabilities are configured we always advertise them, rather than those of the targets.
+	fixed_capabilities: bool,
+	// The capabilities advertised at the last initialize.
+	capabilities: Arc<std::sync::RwLock<ServerCapabilities>>,
+	tool_merge: Option<McpToolMerge>,
+	// Merged tool name to where calls to it are sent. Populated by list_tools.
+	merged_tools: Arc<std::sync::RwLock<HashMap<String, MergedRoute>>>,
+	logging: Option<PayloadLogging>,
+	audit: Option<Arc<audit::AuditLog>>,
+	read_only: Option<McpReadOnly>,
+	// Target name to the tools it annotates as read-only. Populated by list_tools, and by calls to
+	// tools not seen there, when the backend is read-only.
+	read_only_tools: Arc<std::sync::RwLock<HashMap<Strng, HashSet<String>>>>,
+	list_deadline: Option<McpListDeadline>,
+	inflight: Arc<InFlight>,
+	client: client::Client,
 }
 
 impl Relay {
@@ -77,17 +179,98 @@ impl Relay {
 		metrics: Arc<metrics::Metrics>,
 		policies: RuleSets,
 		client: client::Client,
+		idle_timeout: Option<Duration>,
 	) -> Self {
-		let default_target_name = if backend.targets.len() != 1 {
-			None
-		} else {
-			Some(backend.targets[0].name.to_string())
+		let default_target_name = match backend.target_names().as_slice() {
+			[name] => Some(name.to_string()),
+			_ => None,
 		};
+		let info = Self::server_info(&backend);
+		let fixed_protocol_version = backend.server_info.protocol_version.is_some();
+		let fixed_capabilities = backend.server_info.capabilities.is_some();
+		let inflight = backend.inflight.clone();
+		let capabilities = Arc::new(std::sync::RwLock::new(info.capabilities.clone()));
+		// Merging only applies when tools are prefixed by target
+		let tool_merge = backend
+			.tool_merge
+			.clone()
+			.filter(|_| default_target_name.is_none());
+		let pool = Arc::new(RwLock::new(pool::ConnectionPool::new(
+			client.clone(),
+			backend.clone(),
+			metrics.clone(),
+			idle_timeout,
+		)));
+		if let Some(idle_timeout) = idle_timeout {
+			// Connections are also evicted lazily on access, but sweep periodically so idle sessions do not
+			// hold on to upstream connections (or child processes) forever.
+			let weak = Arc::downgrade(&pool);
+			tokio::spawn(async move {
+				loop {
+					tokio::time::sleep(idle_timeout).await;
+					let Some(pool) = weak.upgrade() else {
+						return;
+					};
+					pool.write().await.evict_idle().await;
+				}
+			});
+		}
 		Self {
-			pool: Arc::new(RwLock::new(pool::ConnectionPool::new(client, backend))),
+			pool,
 			metrics,
 			policies,
 			default_target_name,
+			info,
+			fixed_protocol_version,
+			fixed_capabilities,
+			capabilities,
+			tool_merge,
+			merged_tools: Default::default(),
+			logging: backend.logging.clone(),
+			audit: backend.audit.clone(),
+			read_only: backend.read_only.clone(),
+			read_only_tools: Default::default(),
+			list_deadline: backend.list_deadline.clone(),
+			inflight,
+			backend,
+			client,
+		}
+	}
+
+	fn server_info(backend: &McpBackendGroup) -> ServerInfo {
+		let cfg = &backend.server_info;
+		let mut implementation = Implementation::from_build_env();
+		if let Some(name) = &cfg.name {
+			implementation.name = name.clone();
+		}
+		if let Some(version) = &cfg.version {
+			implementation.version = version.clone();
+		}
+		let instructions = cfg.instructions.clone().unwrap_or_else(|| {
+			format!(
+				"This server is a gateway to a set of MCP servers ({}). It is responsible for routing requests to the correct server and aggregating the results.",
+				backend.targets.iter().map(|t| t.name.as_str()).join(", ")
+			)
+		});
+		ServerInfo {
+			protocol_version: cfg
+				.protocol_version
+				.clone()
+				.unwrap_or(ProtocolVersion::V_2025_03_26),
+			capabilities: match &cfg.capabilities {
+				Some(caps) => capabilities_from_config(caps),
+				// Until targets are initialized, advertise everything we can relay
+				None => ServerCapabilities {
+					completions: None,
+					experimental: None,
+					logging: None,
+					prompts: Some(PromptsCapability::default()),
+					resources: Some(ResourcesCapability::default()),
+					tools: Some(ToolsCapability::default()),
+				},
+			},
+			server_info: implementation,
+			instructions: Some(instructions),
 		}
 	}
 
@@ -98,12 +281,42 @@ impl Relay {
 		if let Some(default) = self.default_target_name.as_ref() {
 			Ok((default.as_str(), res))
 		} else {
-			res
-				.split_once(DELIMITER)
+			// Names may contain the delimiter, so the longest name prefixing the resource wins.
+			let groups = self.backend.groups.keys();
+			self
+				.backend
+				.targets
+				.iter()
+				.map(|t| &t.name)
+				.chain(groups)
+				.filter_map(|name| {
+					let rest = res.strip_prefix(name.as_str())?.strip_prefix(DELIMITER)?;
+					Some((name.as_str(), rest))
+				})
+				.max_by_key(|(name, _)| name.len())
 				.ok_or(McpError::invalid_request("invalid resource name", None))
 		}
 	}
 
+	/// Splits a prefixed resource URI into its target and URI, checking the caller may access it.
+	fn authorize_resource<'a, 'b: 'a>(
+		&'a self,
+		uri: &'b str,
+		rq_ctx: &RqCtx,
+	) -> Result<(&'a str, &'b str), McpError> {
+		let (service_name, resource) = self.parse_resource_name(uri)?;
+		if !self.policies.validate(
+			&rbac::ResourceType::Resource(rbac::ResourceId::new(
+				service_name.to_string(),
+				resource.to_string(),
+			)),
+			&rq_ctx.identity,
+		) {
+			return Err(McpError::invalid_request("not allowed", None));
+		}
+		Ok((service_name, resource))
+	}
+
 	fn resource_name(&self, target: &str, name: &str) -> String {
 		if self.default_target_name.is_none() {
 			format!("{target}{DELIMITER}{name}")
@@ -112,6 +325,62 @@ impl Relay {
 		}
 	}
 
+	/// When requests listing from every target stop waiting for them, if there is a deadline.
+	fn list_deadline(&self, ext: &model::Extensions) -> Option<tokio::time::Instant> {
+		let cfg = self.list_deadline.as_ref()?;
+		let headers = ext.get::<Parts>().map(|http| &http.headers);
+		Some(tokio::time::Instant::now() + list_timeout(cfg, headers))
+	}
+
+	/// Waits for a list request sent to each target, until the deadline if there is one. Targets that
+	/// have not answered by then are left out of the results.
+	async fn fan_out<T, F: Future<Output = T>>(
+		&self,
+		server: &str,
+		resource_type: &str,
+		deadline: Option<tokio::time::Instant>,
+		requests: impl IntoIterator<Item = (Strng, F)>,
+	) -> Vec<T> {
+		let (results, timed_out) = join_until(deadline, requests).await;
+		self.timed_out(server, resource_type, timed_out);
+		results
+	}
+
+	/// Locks the pool for a list request, by the deadline if there is one. Fetching remote schemas and
+	/// waiting to restart targets count against it; if it passes first, no target is listed.
+	async fn lock_pool_until(
+		&self,
+		server: &str,
+		resource_type: &str,
+		deadline: Option<tokio::time::Instant>,
+	) -> Option<RwLockWriteGuard<'_, pool::ConnectionPool>> {
+		let Some(deadline) = deadline else {
+			return Some(self.lock_pool(None).await);
+		};
+		match tokio::time::timeout_at(deadline, self.lock_pool(None)).await {
+			Ok(pool) => Some(pool),
+			Err(_) => {
+				self.timed_out(server, resource_type, self.backend.target_names());
+				None
+			},
+		}
+	}
+
+	/// Logs and counts the targets left out of a list because they did not answer by the deadline.
+	fn timed_out(&self, server: &str, resource_type: &str, targets: Vec<Strng>) {
+		for target in targets {
+			tracing::warn!(mcp.target = %target, "target did not list {resource_type}s by the deadline");
+			self.metrics.record(
+				metrics::ListTimeout {
+					server: server.to_string(),
+					target: target.to_string(),
+					resource_type: resource_type.to_string(),
+				},
+				(),
+			);
+		}
+	}
+
 	fn setup_request(ext: &model::Extensions, span_name: &str) -> (BoxedSpan, RqCtx) {
 		let (s, rq, _) = Self::setup_request_log(ext, span_name);
 		(s, rq)
@@ -146,7 +415,12 @@ impl Relay {
 				.cloned()
 				.unwrap_or_default();
 
-			(RqCtx::new(Identity::new(claims.cloned(), id), ctx), log)
+			let request_id = http.extensions.get::<RequestId>().cloned();
+
+			(
+				RqCtx::new(Identity::new(claims.cloned(), id), ctx).with_request_id(request_id),
+				log,
+			)
 		} else {
 			(
 				RqCtx::new(Identity::new(None, None), Context::new()),
@@ -155,7 +429,12 @@ impl Relay {
 		};
 
 		let tracer = trcng::get_tracer();
-		let _span = trcng::start_span(span_name.to_string(), &rq_ctx.identity)
+		let attrs = rq_ctx
+			.request_id
+			.iter()
+			.map(|id| KeyValue::new("request.id", id.to_string()))
+			.collect();
+		let _span = trcng::start_span_with_attributes(span_name.to_string(), &rq_ctx.identity, attrs)
 			.with_kind(SpanKind::Server)
 			.start_with_context(tracer, &rq_ctx.context);
 		(_span, rq_ctx, log)
@@ -163,10 +442,53 @@ impl Relay {
 }
 
 impl Relay {
+	/// Locks the pool for a request to the named target, or to every target if None. A stdio target
+	/// due to be restarted is waited for before taking the lock.
+	async fn lock_pool(&self, target: Option<&str>) -> RwLockWriteGuard<'_, pool::ConnectionPool> {
+		self.refresh_schemas(target).await;
+		let backoff = self.pool.read().await.restart_backoff(target);
+		if let Some(backoff) = backoff {
+			tokio::time::sleep(backoff).await;
+		}
+		self.pool.write().await
+	}
+
+	/// Fetches the remote OpenAPI schemas of the named target, or of every target if None, that are due
+	/// for a refresh. This happens before the pool is locked, so a slow schema server does not hold up
+	/// requests to other targets; the pool then rebuilds the tools of targets whose schema changed.
+	async fn refresh_schemas(&self, target: Option<&str>) {
+		let targets = match target.and_then(|t| self.backend.find(t)) {
+			Some(tgt) => vec![tgt],
+			None => self.backend.targets.clone(),
+		};
+		let refreshes = targets.iter().filter_map(|tgt| {
+			let McpTargetSpec::OpenAPI(open) = &tgt.spec else {
+				return None;
+			};
+			let OpenAPISchema::Remote(remote) = &open.schema else {
+				return None;
+			};
+			let proxy = tgt.backend_policies.outbound_proxy.clone();
+			Some(async move { (tgt, remote.refresh(&self.client, proxy).await) })
+		});
+		for (tgt, refresh) in futures::future::join_all(refreshes).await {
+			if let Some(refresh) = refresh {
+				self.metrics.record(
+					metrics::OpenAPISchemaRefresh {
+						server: self.backend.name.to_string(),
+						target: tgt.name.to_string(),
+						result: refresh.as_str().to_string(),
+					},
+					(),
+				);
+			}
+		}
+	}
+
 	pub async fn remove_target(&self, name: &str) -> Result<(), tokio::task::JoinError> {
 		tracing::info!("removing target: {}", name);
 		let mut pool = self.pool.write().await;
-		match pool.remove(name).await {
+		match pool.remove(name).await.and_then(Arc::into_inner) {
 			Some(target) => {
 				match target.spec {
 					upstream::UpstreamTargetSpec::Mcp(m) => {
@@ -187,21 +509,9 @@ impl Relay {
 impl ServerHandler for Relay {
 	#[instrument(level = "debug", skip_all)]
 	fn get_info(&self) -> ServerInfo {
-		ServerInfo {
-            protocol_version: ProtocolVersion::V_2025_03_26,
-            capabilities: ServerCapabilities {
-                completions: None,
-                experimental: None,
-                logging: None,
-                prompts: Some(PromptsCapability::default()),
-                resources: Some(ResourcesCapability::default()),
-                tools: Some(ToolsCapability::default()),
-            },
-            server_info: Implementation::from_build_env(),
-            instructions: Some(
-                "This server is a gateway to a set of mcp servers. It is responsible for routing requests to the correct server and aggregating the results.".to_string(),
-            ),
-        }
+		let mut info = self.info.clone();
+		info.capabilities = self.capabilities.read().expect("mutex acquired").clone();
+		info
 	}
 
 	// The client will send an initialize request with their parameters. We will return our own static support
@@ -211,19 +521,54 @@ impl ServerHandler for Relay {
 		context: RequestContext<RoleServer>,
 	) -> Result<InitializeResult, McpError> {
 		let (_span, ref rq_ctx) = Self::setup_request(&context.extensions, "initialize");
+		let client_version = request.protocol_version.clone();
 
 		// List servers and initialize the ones that are not initialized
+		self.refresh_schemas(None).await;
 		let mut pool = self.pool.write().await;
 		// Initialize all targets
 		let connections = pool
 			.initialize(rq_ctx, &context.peer, request)
 			.await
-			.map_err(|e| McpError::internal_error(format!("Failed to list connections: {e}"), None))?;
+			.map_err(list_connections_error)?;
+		if !self.fixed_capabilities {
+			*self.capabilities.write().expect("mutex acquired") = pool.capabilities();
+		}
+		drop(pool);
+		// Merged tools are called by their plain name, so route them as soon as the targets connect
+		// rather than relying on the client listing tools first.
+		if let Some(merge) = &self.tool_merge {
+			let server = self.backend.name.to_string();
+			let deadline = self.list_deadline(&context.extensions);
+			let all = connections.iter().map(|(name, svc)| {
+				(name.clone(), async move {
+					(name.clone(), all_tools(svc, rq_ctx).await)
+				})
+			});
+			let by_target = self
+				.fan_out(&server, "tool", deadline, all)
+				.await
+				.into_iter()
+				.filter_map(|(name, tools)| match tools {
+					Ok(tools) => Some((name, tools)),
+					Err(e) => {
+						tracing::warn!(mcp.target = %name, "failed to list tools: {e}");
+						None
+					},
+				})
+				.collect();
+			let (merged, _) = merge_tools(by_target, merge.primary.as_deref());
+			let mut merged_tools = self.merged_tools.write().expect("mutex poisoned");
+			merged_tools.clear();
+			add_merged_tools(&mut merged_tools, &merged, merge.primary.as_deref());
+		}
 
-		// Return static server info about ourselves
-		// TODO: we should actually perform an intersection of what the downstream and we support. The problem
-		// is we may connect to many upstream servers, how do expose what exactly we can and cannot support?
-		Ok(self.get_info())
+		// Return server info about ourselves, advertising the union of what the targets support
+		let mut info = self.get_info();
+		if !self.fixed_protocol_version && SUPPORTED_PROTOCOL_VERSIONS.contains(&client_version) {
+			info.protocol_version = client_version;
+		}
+		Ok(info)
 	}
 
 	#[instrument(level = "debug", skip_all)]
@@ -233,31 +578,46 @@ impl ServerHandler for Relay {
 		context: RequestContext<RoleServer>,
 	) -> std::result::Result<ListResourcesResult, McpError> {
 		let (_span, ref rq_ctx) = Self::setup_request(&context.extensions, "list_resources");
+		let deadline = self.list_deadline(&context.extensions);
+		let cursor = ListCursor::from_request(request.as_ref())?;
 
-		let mut pool = self.pool.write().await;
-		let connections = pool
-			.list()
+		let server = self.backend.name.to_string();
+		let Some(mut pool) = self.lock_pool_until(&server, "resource", deadline).await else {
+			return Ok(ListResourcesResult::default());
+		};
+		let listed = pool
+			.list_until(rq_ctx, &context.peer, deadline)
 			.await
-			.map_err(|e| McpError::internal_error(format!("Failed to list connections: {e}"), None))?;
-		let all = connections.into_iter().map(|(_name, svc)| {
-			let request = request.clone();
-			async move {
+			.map_err(list_connections_error)?;
+		self.timed_out(&server, "resource", listed.timed_out);
+		let connections = listed.targets;
+		let all = connections.into_iter().filter_map(|(name, svc)| {
+			let request = ListCursor::request(cursor.as_ref(), &name)?;
+			Some((name.clone(), async move {
 				match svc.list_resources(request, rq_ctx).await {
-					Ok(r) => Ok(r.resources),
+					Ok(r) => Ok((name, r.next_cursor, r.resources)),
 					Err(e) => Err(e),
 				}
-			}
+			}))
 		});
 
 		// TODO: Handle errors
-		let (results, _errors): (Vec<_>, Vec<_>) = futures::future::join_all(all)
+		let (results, _errors): (Vec<_>, Vec<_>) = self
+			.fan_out(&server, "resource", deadline, all)
 			.await
 			.into_iter()
 			.partition_result();
 
+		let mut next = ListCursor::default();
 		Ok(ListResourcesResult {
-			resources: results.into_iter().flatten().collect(),
-			next_cursor: None,
+			resources: results
+				.into_iter()
+				.flat_map(|(name, next_cursor, resources)| {
+					next.insert(&name, next_cursor);
+					resources
+				})
+				.collect(),
+			next_cursor: next.encode(),
 		})
 	}
 
@@ -268,23 +628,34 @@ impl ServerHandler for Relay {
 		context: RequestContext<RoleServer>,
 	) -> std::result::Result<ListResourceTemplatesResult, McpError> {
 		let (_span, ref rq_ctx) = Self::setup_request(&context.extensions, "list_resource_templates");
+		let deadline = self.list_deadline(&context.extensions);
+		let cursor = ListCursor::from_request(request.as_ref())?;
 
-		let mut pool = self.pool.write().await;
-		let connections = pool
-			.list()
+		let server = self.backend.name.to_string();
+		let Some(mut pool) = self
+			.lock_pool_until(&server, "resource_template", deadline)
+			.await
+		else {
+			return Ok(ListResourceTemplatesResult::default());
+		};
+		let listed = pool
+			.list_until(rq_ctx, &context.peer, deadline)
 			.await
-			.map_err(|e| McpError::internal_error(format!("Failed to list connections: {e}"), None))?;
-		let all = connections.into_iter().map(|(_name, svc)| {
-			let request = request.clone();
-			async move {
+			.map_err(list_connections_error)?;
+		self.timed_out(&server, "resource_template", listed.timed_out);
+		let connections = listed.targets;
+		let all = connections.into_iter().filter_map(|(name, svc)| {
+			let request = ListCursor::request(cursor.as_ref(), &name)?;
+			Some((name.clone(), async move {
 				match svc.list_resource_templates(request, rq_ctx).await {
-					Ok(r) => Ok(r.resource_templates),
+					Ok(r) => Ok((name, r.next_cursor, r.resource_templates)),
 					Err(e) => Err(e),
 				}
-			}
+			}))
 		});
 
-		let (results, _errors): (Vec<_>, Vec<_>) = futures::future::join_all(all)
+		let (results, _errors): (Vec<_>, Vec<_>) = self
+			.fan_out(&server, "resource_template", deadline, all)
 			.await
 			.into_iter()
 			.partition_result();
@@ -297,9 +668,16 @@ impl ServerHandler for Relay {
 			&rq_ctx.identity,
 		);
 
+		let mut next = ListCursor::default();
 		Ok(ListResourceTemplatesResult {
-			resource_templates: results.into_iter().flatten().collect(),
-			next_cursor: None,
+			resource_templates: results
+				.into_iter()
+				.flat_map(|(name, next_cursor, resource_templates)| {
+					next.insert(&name, next_cursor);
+					resource_templates
+				})
+				.collect(),
+			next_cursor: next.encode(),
 		})
 	}
 
@@ -310,18 +688,27 @@ impl ServerHandler for Relay {
 		context: RequestContext<RoleServer>,
 	) -> std::result::Result<ListPromptsResult, McpError> {
 		let (_span, ref rq_ctx) = Self::setup_request(&context.extensions, "list_prompts");
+		let deadline = self.list_deadline(&context.extensions);
+		let cursor = ListCursor::from_request(request.as_ref())?;
 
-		let mut pool = self.pool.write().await;
-		let connections = pool
-			.list()
+		let server = self.backend.name.to_string();
+		let Some(mut pool) = self.lock_pool_until(&server, "prompt", deadline).await else {
+			return Ok(ListPromptsResult::default());
+		};
+		let listed = pool
+			.list_until(rq_ctx, &context.peer, deadline)
 			.await
-			.map_err(|e| McpError::internal_error(format!("Failed to list connections: {e}"), None))?;
+			.map_err(list_connections_error)?;
+		self.timed_out(&server, "prompt", listed.timed_out);
+		let connections = listed.targets;
 
-		let all = connections.into_iter().map(|(_name, svc)| {
-			let request = request.clone();
-			async move {
+		let all = connections.into_iter().filter_map(|(_name, svc)| {
+			let request = ListCursor::request(cursor.as_ref(), &_name)?;
+			Some((_name.clone(), async move {
 				match svc.list_prompts(request, rq_ctx).await {
-					Ok(r) => Ok(
+					Ok(r) => Ok((
+						_name.clone(),
+						r.next_cursor,
 						r.prompts
 							.into_iter()
 							.map(|p| Prompt {
@@ -330,13 +717,14 @@ impl ServerHandler for Relay {
 								arguments: p.arguments,
 							})
 							.collect::<Vec<_>>(),
-					),
+					)),
 					Err(e) => Err(e),
 				}
-			}
+			}))
 		});
 
-		let (results, _errors): (Vec<_>, Vec<_>) = futures::future::join_all(all)
+		let (results, _errors): (Vec<_>, Vec<_>) = self
+			.fan_out(&server, "prompt", deadline, all)
 			.await
 			.into_iter()
 			.partition_result();
@@ -348,9 +736,16 @@ impl ServerHandler for Relay {
 			},
 			&rq_ctx.identity,
 		);
+		let mut next = ListCursor::default();
 		Ok(ListPromptsResult {
-			prompts: results.into_iter().flatten().collect(),
-			next_cursor: None,
+			prompts: results
+				.into_iter()
+				.flat_map(|(name, next_cursor, prompts)| {
+					next.insert(&name, next_cursor);
+					prompts
+				})
+				.collect(),
+			next_cursor: next.encode(),
 		})
 	}
 
@@ -360,6 +755,48 @@ impl ServerHandler for Relay {
     fields(
         name=%request.uri,
     ),
+  )]
+	async fn subscribe(
+		&self,
+		request: SubscribeRequestParam,
+		context: RequestContext<RoleServer>,
+	) -> std::result::Result<(), McpError> {
+		let (_span, ref rq_ctx) = Self::setup_request(&context.extensions, "subscribe");
+		let (service_name, resource) = self.authorize_resource(&request.uri, rq_ctx)?;
+		let mut pool = self.lock_pool(Some(service_name)).await;
+		pool
+			.subscribe(rq_ctx, &context.peer, service_name, resource)
+			.await
+			.map_err(Into::into)
+	}
+
+	#[instrument(
+    level = "debug",
+    skip_all,
+    fields(
+        name=%request.uri,
+    ),
+  )]
+	async fn unsubscribe(
+		&self,
+		request: UnsubscribeRequestParam,
+		context: RequestContext<RoleServer>,
+	) -> std::result::Result<(), McpError> {
+		let (_span, ref rq_ctx) = Self::setup_request(&context.extensions, "unsubscribe");
+		let (service_name, resource) = self.authorize_resource(&request.uri, rq_ctx)?;
+		let mut pool = self.lock_pool(Some(service_name)).await;
+		pool
+			.unsubscribe(rq_ctx, &context.peer, service_name, resource)
+			.await
+			.map_err(Into::into)
+	}
+
+	#[instrument(
+    level = "debug",
+    skip_all,
+    fields(
+        name=%request.uri,
+    ),
   )]
 	async fn read_resource(
 		&self,
@@ -380,7 +817,7 @@ impl ServerHandler for Relay {
 			return Err(McpError::invalid_request("not allowed", None));
 		}
 
-		let mut pool = self.pool.write().await;
+		let mut pool = self.lock_pool(Some(service_name)).await;
 		let service_arc = pool
 			.get(rq_ctx, &context.peer, service_name)
 			.await
@@ -403,6 +840,97 @@ impl ServerHandler for Relay {
 		}
 	}
 
+	/// Forwards the log level to every target that supports logging. Targets that do not are skipped.
+	/// This is best effort: failures are logged per target, and the request only fails if every
+	/// target supporting logging failed.
+	#[instrument(level = "debug", skip_all)]
+	async fn set_level(
+		&self,
+		request: SetLevelRequestParam,
+		context: RequestContext<RoleServer>,
+	) -> std::result::Result<(), McpError> {
+		let (_span, ref rq_ctx) = Self::setup_request(&context.extensions, "set_level");
+		let mut pool = self.lock_pool(None).await;
+		let connections = pool
+			.list(rq_ctx, &context.peer)
+			.await
+			.map_err(list_connections_error)?;
+		let all = connections
+			.into_iter()
+			.filter(|(_, svc)| svc.capabilities().logging.is_some())
+			.map(|(name, svc)| {
+				let request = request.clone();
+				async move { (name, svc.set_level(request, rq_ctx).await) }
+			});
+		let results = futures::future::join_all(all).await;
+		let mut failed = vec![];
+		for (name, res) in &results {
+			if let Err(e) = res {
+				tracing::warn!(mcp.target = %name, "failed to set log level: {}", e.error_code());
+				failed.push(name.as_str());
+			}
+		}
+		if !results.is_empty() && failed.len() == results.len() {
+			return Err(McpError::internal_error(
+				format!("failed to set log level on {}", failed.join(", ")),
+				None,
+			));
+		}
+		Ok(())
+	}
+
+	#[instrument(level = "debug", skip_all)]
+	async fn complete(
+		&self,
+		request: CompleteRequestParam,
+		context: RequestContext<RoleServer>,
+	) -> std::result::Result<CompleteResult, McpError> {
+		let (_span, ref rq_ctx) = Self::setup_request(&context.extensions, "complete");
+
+		// Completions are for the arguments of a prompt or resource template, so route and authorize
+		// them the same way as the prompt or resource itself.
+		let (service_name, r#ref, resource) = match &request.r#ref {
+			Reference::Prompt(p) => {
+				let (service_name, prompt) = self.parse_resource_name(&p.name)?;
+				let resource = rbac::ResourceType::Prompt(rbac::ResourceId::new(
+					service_name.to_string(),
+					prompt.to_string(),
+				));
+				let r#ref = Reference::Prompt(PromptReference {
+					name: prompt.to_string(),
+				});
+				(service_name, r#ref, resource)
+			},
+			Reference::Resource(r) => {
+				let (service_name, uri) = self.parse_resource_name(&r.uri)?;
+				let resource = rbac::ResourceType::Resource(rbac::ResourceId::new(
+					service_name.to_string(),
+					uri.to_string(),
+				));
+				let r#ref = Reference::Resource(ResourceReference {
+					uri: uri.to_string(),
+				});
+				(service_name, r#ref, resource)
+			},
+		};
+		if !self.policies.validate(&resource, &rq_ctx.identity) {
+			return Err(McpError::invalid_request("not allowed", None));
+		}
+		let mut pool = self.lock_pool(Some(service_name)).await;
+		let svc = pool
+			.get(rq_ctx, &context.peer, service_name)
+			.await
+			.map_err(|_e| McpError::invalid_request(format!("Service {service_name} not found"), None))?;
+		let req = CompleteRequestParam {
+			r#ref,
+			argument: request.argument,
+		};
+		match svc.complete(req, rq_ctx).await {
+			Ok(r) => Ok(r),
+			Err(e) => Err(e.into()),
+		}
+	}
+
 	#[instrument(
     level = "debug",
     skip_all,
@@ -428,7 +956,7 @@ impl ServerHandler for Relay {
 		) {
 			return Err(McpError::invalid_request("not allowed", None));
 		}
-		let mut pool = self.pool.write().await;
+		let mut pool = self.lock_pool(Some(service_name)).await;
 		let svc = pool
 			.get(rq_ctx, &context.peer, service_name)
 			.await
@@ -459,17 +987,26 @@ impl ServerHandler for Relay {
 		mut context: RequestContext<RoleServer>,
 	) -> std::result::Result<ListToolsResult, McpError> {
 		let (_span, ref rq_ctx) = Self::setup_request(&context.extensions, "list_tools");
-		let mut pool = self.pool.write().await;
-		let connections = pool
-			.list()
+		let deadline = self.list_deadline(&context.extensions);
+		let cursor = ListCursor::from_request(request.as_ref())?;
+		let server = self.backend.name.to_string();
+		let Some(mut pool) = self.lock_pool_until(&server, "tool", deadline).await else {
+			return Ok(ListToolsResult::default());
+		};
+		let listed = pool
+			.list_until(rq_ctx, &context.peer, deadline)
 			.await
-			.map_err(|e| McpError::internal_error(format!("Failed to list connections: {e}"), None))?;
+			.map_err(list_connections_error)?;
+		self.timed_out(&server, "tool", listed.timed_out);
+		let connections = listed.targets;
 		let multi = connections.len() > 1;
-		let all = connections.into_iter().map(|(_name, svc_arc)| {
-			let request = request.clone();
-			async move {
+		let all = connections.into_iter().filter_map(|(_name, svc_arc)| {
+			let request = ListCursor::request(cursor.as_ref(), &_name)?;
+			Some((_name.clone(), async move {
 				match svc_arc.list_tools(request, rq_ctx).await {
-					Ok(r) => Ok(
+					Ok(r) => Ok((
+						_name.clone(),
+						r.next_cursor,
 						r.tools
 							.into_iter()
 							.filter(|t| {
@@ -481,23 +1018,72 @@ impl ServerHandler for Relay {
 									&rq_ctx.identity,
 								)
 							})
-							.map(|t| Tool {
-								annotations: None,
-								name: Cow::Owned(self.resource_name(_name.as_str(), &t.name)),
-								description: t.description,
-								input_schema: t.input_schema,
-							})
 							.collect::<Vec<_>>(),
-					),
+					)),
 					Err(e) => Err(e),
 				}
-			}
+			}))
 		});
 
-		let (results, _errors): (Vec<_>, Vec<_>) = futures::future::join_all(all)
+		let (results, _errors): (Vec<_>, Vec<_>) = self
+			.fan_out(&server, "tool", deadline, all)
 			.await
 			.into_iter()
 			.partition_result();
+		let mut next = ListCursor::default();
+		let results = results
+			.into_iter()
+			.map(|(name, next_cursor, tools)| {
+				next.insert(&name, next_cursor);
+				(name, tools)
+			})
+			.collect_vec();
+		// Later pages add to what the earlier pages listed
+		let first_page = cursor.is_none();
+
+		if self.read_only.is_some() {
+			let annotated = results
+				.iter()
+				.map(|(name, tools)| (name, annotated_read_only(tools)))
+				.collect_vec();
+			let mut read_only_tools = self.read_only_tools.write().expect("mutex poisoned");
+			for (name, tools) in annotated {
+				let listed = read_only_tools.entry(name.clone()).or_default();
+				if first_page {
+					listed.clear();
+				}
+				listed.extend(tools);
+			}
+		}
+
+		let (merged, results) = match &self.tool_merge {
+			Some(merge) => merge_tools(results, merge.primary.as_deref()),
+			None => (
+				vec![],
+				results
+					.into_iter()
+					.flat_map(|(name, tools)| tools.into_iter().map(move |t| (name.clone(), t)))
+					.collect(),
+			),
+		};
+		{
+			let mut merged_tools = self.merged_tools.write().expect("mutex poisoned");
+			if first_page {
+				merged_tools.clear();
+			}
+			let primary = self.tool_merge.as_ref().and_then(|m| m.primary.as_deref());
+			add_merged_tools(&mut merged_tools, &merged, primary);
+		}
+		let tools = merged
+			.into_iter()
+			.map(|m| m.tool)
+			.chain(results.into_iter().map(|(name, t)| Tool {
+				annotations: None,
+				name: Cow::Owned(self.resource_name(name.as_str(), &t.name)),
+				description: t.description,
+				input_schema: t.input_schema,
+			}))
+			.collect();
 
 		self.metrics.clone().record(
 			metrics::ListCall {
@@ -508,8 +1094,8 @@ impl ServerHandler for Relay {
 		);
 
 		Ok(ListToolsResult {
-			tools: results.into_iter().flatten().collect(),
-			next_cursor: None,
+			tools,
+			next_cursor: next.encode(),
 		})
 	}
 
@@ -525,8 +1111,40 @@ impl ServerHandler for Relay {
 		request: CallToolRequestParam,
 		context: RequestContext<RoleServer>,
 	) -> std::result::Result<CallToolResult, McpError> {
+		let _inflight = self.inflight.start();
 		let (_span, ref rq_ctx, log) = Self::setup_request_log(&context.extensions, "call_tool");
 		let tool_name = request.name.to_string();
+		// Whether the annotations of the tool decide if a read-only backend allows it
+		let check_annotations = match self
+			.read_only
+			.as_ref()
+			.and_then(|ro| configured_read_only(ro, &tool_name))
+		{
+			Some(false) => return Err(read_only_error(&tool_name)),
+			Some(true) => false,
+			None => self.read_only.is_some(),
+		};
+		let merged_route = self
+			.merged_tools
+			.read()
+			.expect("mutex poisoned")
+			.get(&tool_name)
+			.cloned();
+		if let Some(route) = merged_route {
+			return self
+				.call_merged_tool(
+					&tool_name,
+					route,
+					check_annotations,
+					request.arguments,
+					CallCtx {
+						rq_ctx,
+						peer: &context.peer,
+						log: &log,
+					},
+				)
+				.await;
+		}
 		let (service_name, tool) = self.parse_resource_name(&tool_name)?;
 		log.non_atomic_mutate(|l| {
 			l.tool_call_name = Some(tool.to_string());
@@ -539,16 +1157,182 @@ impl ServerHandler for Relay {
 			)),
 			&rq_ctx.identity,
 		) {
+			if let Some(audit) = &self.audit {
+				audit.denied(audit::Call {
+					target: service_name,
+					tool,
+					arguments: request.arguments.as_ref(),
+					rq_ctx,
+				});
+			}
 			return Err(McpError::invalid_request("not allowed", None));
 		}
-		let mut pool = self.pool.write().await;
+		if check_annotations
+			&& !self
+				.is_read_only(rq_ctx, &context.peer, service_name, tool)
+				.await?
+		{
+			return Err(read_only_error(&tool_name));
+		}
+		self
+			.call_target_tool(rq_ctx, &context.peer, service_name, tool, request.arguments)
+			.await
+			.map_err(Into::into)
+	}
+}
+
+impl Relay {
+	/// Whether a target annotates a tool as read-only. Clients may call tools without listing them
+	/// first, and targets may add tools, so the target is listed again for tools not seen so far.
+	async fn is_read_only(
+		&self,
+		rq_ctx: &RqCtx,
+		peer: &Peer<RoleServer>,
+		service_name: &str,
+		tool: &str,
+	) -> std::result::Result<bool, McpError> {
+		let known = self
+			.read_only_tools
+			.read()
+			.expect("mutex poisoned")
+			.get(service_name)
+			.is_some_and(|tools| tools.contains(tool));
+		if known {
+			return Ok(true);
+		}
+		let mut pool = self.lock_pool(Some(service_name)).await;
 		let svc = pool
-			.get(rq_ctx, &context.peer, service_name)
+			.get(rq_ctx, peer, service_name)
 			.await
 			.map_err(|_e| McpError::invalid_request(format!("Service {service_name} not found"), None))?;
+		let tools = annotated_read_only(&svc.list_tools(None, rq_ctx).await?.tools);
+		let read_only = tools.contains(tool);
+		// Added to, rather than replacing, what later pages of the last list found
+		self
+			.read_only_tools
+			.write()
+			.expect("mutex poisoned")
+			.entry(service_name.into())
+			.or_default()
+			.extend(tools);
+		Ok(read_only)
+	}
+
+	async fn call_target_tool(
+		&self,
+		rq_ctx: &RqCtx,
+		peer: &Peer<RoleServer>,
+		service_name: &str,
+		tool: &str,
+		arguments: Option<JsonObject>,
+	) -> std::result::Result<CallToolResult, CallError> {
+		let Some(audit) = &self.audit else {
+			return self
+				.send_target_tool(rq_ctx, peer, service_name, tool, arguments)
+				.await;
+		};
+		let start = Instant::now();
+		let audited_arguments = arguments.clone();
+		let res = self
+			.send_target_tool(rq_ctx, peer, service_name, tool, arguments)
+			.await;
+		audit.called(
+			audit::Call {
+				target: service_name,
+				tool,
+				arguments: audited_arguments.as_ref(),
+				rq_ctx,
+			},
+			res.as_ref().map_err(CallError::error),
+			start.elapsed(),
+		);
+		res
+	}
+
+	fn circuit_open(&self, service_name: &str) -> McpError {
+		self.metrics.record(
+			metrics::CircuitBreakerRejection {
+				server: self.backend.name.to_string(),
+				target: service_name.to_string(),
+			},
+			(),
+		);
+		McpError::new(
+			CIRCUIT_OPEN_ERROR_CODE,
+			format!("target {service_name} is unavailable: circuit breaker open"),
+			None,
+		)
+	}
+
+	async fn send_target_tool(
+		&self,
+		rq_ctx: &RqCtx,
+		peer: &Peer<RoleServer>,
+		service_name: &str,
+		tool: &str,
+		arguments: Option<JsonObject>,
+	) -> std::result::Result<CallToolResult, CallError> {
+		// For a target group, this picks the member the call is sent to. Calls wait for the picked
+		// target's concurrency limit before acquiring its circuit breaker. This covers every target
+		// type, including OpenAPI targets. Waiting calls hold neither the pool lock nor a circuit
+		// breaker probe.
+		let wait = async |target: &Strng| {
+			let limiter = self
+				.backend
+				.find(target)
+				.and_then(|t| t.concurrency_limit.clone());
+			let Some(limiter) = limiter else {
+				return Ok(None);
+			};
+			match limiter.acquire().await {
+				Some(permit) => Ok(Some(permit)),
+				None => {
+					self.metrics.record(
+						metrics::ConcurrencyLimitRejection {
+							server: self.backend.name.to_string(),
+							target: target.to_string(),
+						},
+						(),
+					);
+					Err(McpError::new(
+						CONCURRENCY_LIMIT_ERROR_CODE,
+						format!("target {service_name} is overloaded: too many calls in flight"),
+						None,
+					))
+				},
+			}
+		};
+		let acquired = self
+			.backend
+			.acquire(service_name, wait)
+			.await
+			.map_err(CallError::Unsent)?;
+		let Some((target, breaker, _permit)) = acquired else {
+			return Err(CallError::Unsent(self.circuit_open(service_name)));
+		};
+		let mut pool = self.lock_pool(Some(&target)).await;
+		let svc = match pool.get(rq_ctx, peer, &target).await {
+			Ok(svc) => svc,
+			Err(e) => {
+				// Failing to (re)connect is the most likely way a target is down
+				if let Some(breaker) = &breaker {
+					breaker.record(false);
+				}
+				tracing::warn!(mcp.target = %service_name, "failed to connect: {e:#}");
+				return Err(CallError::Unsent(McpError::invalid_request(
+					format!("Service {service_name} not found"),
+					Some(connect_error_data(&e)),
+				)));
+			},
+		};
+		let start = Instant::now();
+		// Logged as the request params, which the redacted paths are relative to
+		let logged_params = self.logging.as_ref().and_then(|l| {
+			l.body(|| Some(serde_json::json!({ "name": tool, "arguments": arguments })))
+		});
 		let req = CallToolRequestParam {
 			name: Cow::Owned(tool.to_string()),
-			arguments: request.arguments,
+			arguments,
 		};
 
 		self.metrics.record(
@@ -560,7 +1344,35 @@ impl ServerHandler for Relay {
 			&rq_ctx.identity,
 		);
 
-		match svc.call_tool(req, rq_ctx).await {
+		let res = svc.call_tool(req, rq_ctx).await;
+		let failed = res.as_ref().err().filter(|e| e.is_target_failure());
+		if let Some(breaker) = &breaker {
+			breaker.record(failed.is_none());
+		}
+		if let Some(e) = failed {
+			pool.record_error(&target, e.to_string());
+		}
+		if let Some(logging) = &self.logging {
+			let status = match &res {
+				Ok(r) if r.is_error == Some(true) => "tool_error".to_string(),
+				Ok(_) => "ok".to_string(),
+				Err(e) => e.error_code(),
+			};
+			let result = logging.body(|| res.as_ref().ok().and_then(|r| serde_json::to_value(r).ok()));
+			tracing::info!(
+				method = "tools/call",
+				target = service_name,
+				tool,
+				identity = rq_ctx.identity.get_claim("sub", ".").unwrap_or("unknown"),
+				request.id = rq_ctx.request_id.as_ref().map(tracing::field::display),
+				%status,
+				duration = %format!("{}ms", start.elapsed().as_millis()),
+				params = logged_params.as_deref(),
+				result = result.as_deref(),
+				"mcp call",
+			);
+		}
+		match res {
 			Ok(r) => Ok(r),
 			Err(e) => {
 				self.metrics.record(
@@ -572,8 +1384,340 @@ impl ServerHandler for Relay {
 					},
 					&rq_ctx.identity,
 				);
-				Err(e.into())
+				Err(CallError::Sent(e.into()))
 			},
 		}
 	}
+
+	// Call a merged tool on each target offering it, in order, until one succeeds. A call that
+	// reached a target is only retried on the next if every target annotates the tool as idempotent,
+	// as the first may have acted on it.
+	async fn call_merged_tool(
+		&self,
+		tool: &str,
+		route: MergedRoute,
+		check_annotations: bool,
+		arguments: Option<JsonObject>,
+		ctx: CallCtx<'_>,
+	) -> std::result::Result<CallToolResult, McpError> {
+		let CallCtx { rq_ctx, peer, log } = ctx;
+		let mut last_err = McpError::invalid_request("not allowed", None);
+		for target in route.targets {
+			if !self.policies.validate(
+				&rbac::ResourceType::Tool(rbac::ResourceId::new(target.to_string(), tool.to_string())),
+				&rq_ctx.identity,
+			) {
+				if let Some(audit) = &self.audit {
+					audit.denied(audit::Call {
+						target: &target,
+						tool,
+						arguments: arguments.as_ref(),
+						rq_ctx,
+					});
+				}
+				continue;
+			}
+			if check_annotations {
+				match self.is_read_only(rq_ctx, peer, &target, tool).await {
+					Ok(true) => {},
+					Ok(false) => {
+						last_err = read_only_error(tool);
+						continue;
+					},
+					Err(e) => {
+						last_err = e;
+						continue;
+					},
+				}
+			}
+			log.non_atomic_mutate(|l| {
+				l.tool_call_name = Some(tool.to_string());
+				l.target_name = Some(target.to_string());
+			});
+			match self
+				.call_target_tool(rq_ctx, peer, &target, tool, arguments.clone())
+				.await
+			{
+				Ok(r) => return Ok(r),
+				Err(CallError::Sent(e)) if !route.idempotent => return Err(e),
+				Err(e) => {
+					let e = McpError::from(e);
+					tracing::debug!("merged tool {tool} failed on target {target}, trying next: {e}");
+					last_err = e;
+				},
+			}
+		}
+		Err(last_err)
+	}
+}
+
+/// How long list requests wait for targets: the configured timeout, or the one the client asked for
+/// in the request header if it is shorter.
+fn list_timeout(cfg: &McpListDeadline, headers: Option<&HeaderMap>) -> Duration {
+	let requested = cfg
+		.header
+		.as_deref()
+		.zip(headers)
+		.and_then(|(name, headers)| headers.get(name)?.to_str().ok()?.parse::<u64>().ok())
+		.map(Duration::from_millis);
+	requested.map_or(cfg.timeout, |requested| requested.min(cfg.timeout))
+}
+
+/// Runs requests concurrently until they all finish or the deadline passes. Returns the results of
+/// the requests that finished, and the targets of those that did not. Those are dropped, which
+/// cancels them.
+async fn join_until<T, F: Future<Output = T>>(
+	deadline: Option<tokio::time::Instant>,
+	requests: impl IntoIterator<Item = (Strng, F)>,
+) -> (Vec<T>, Vec<Strng>) {
+	let all = requests.into_iter().map(|(target, req)| async move {
+		match deadline {
+			Some(deadline) => tokio::time::timeout_at(deadline, req)
+				.await
+				.map_err(|_| target),
+			None => Ok(req.await),
+		}
+	});
+	futures::future::join_all(all)
+		.await
+		.into_iter()
+		.partition_result()
+}
+
+/// How the configuration of a read-only backend classifies a tool, by the name clients call it:
+/// true if it does not change state, false if it does, and None if it is not listed.
+fn configured_read_only(config: &McpReadOnly, name: &str) -> Option<bool> {
+	if config.mutating_tools.iter().any(|t| t == name) {
+		Some(false)
+	} else if config.read_only_tools.iter().any(|t| t == name) {
+		Some(true)
+	} else {
+		None
+	}
+}
+
+/// The names of the tools annotated as read-only. As in MCP, tools without annotations are assumed
+/// to change state.
+fn annotated_read_only(tools: &[Tool]) -> HashSet<String> {
+	tools
+		.iter()
+		.filter(|t| t.annotations.as_ref().and_then(|a| a.read_only_hint) == Some(true))
+		.map(|t| t.name.to_string())
+		.collect()
+}
+
+/// Whether a tool is annotated as safe to call more than once: idempotent, or read-only.
+fn annotated_idempotent(tool: &Tool) -> bool {
+	tool
+		.annotations
+		.as_ref()
+		.is_some_and(|a| a.idempotent_hint == Some(true) || a.read_only_hint == Some(true))
+}
+
+fn list_connections_error(e: anyhow::Error) -> McpError {
+	tracing::warn!("failed to list connections: {e:#}");
+	McpError::internal_error(
+		format!("Failed to list connections: {}", connect_error_cause(&e)),
+		Some(connect_error_data(&e)),
+	)
+}
+
+/// Describes a failure to connect to a target, with the last lines a stdio process wrote to stderr
+/// before it exited, if any.
+fn connect_error_data(e: &anyhow::Error) -> serde_json::Value {
+	let lines = e
+		.downcast_ref::<stderr::StderrLines>()
+		.map(|l| l.0.as_slice())
+		.unwrap_or_default();
+	serde_json::json!({ "cause": connect_error_cause(e), "stderr": lines })
+}
+
+fn connect_error_cause(e: &anyhow::Error) -> String {
+	e.chain()
+		.filter(|c| !c.is::<stderr::StderrLines>())
+		.join(": ")
+}
+
+fn read_only_error(tool: &str) -> McpError {
+	McpError::new(
+		READ_ONLY_ERROR_CODE,
+		format!("tool {tool} is not allowed: it may change state, and this server is read-only"),
+		None,
+	)
+}
+
+/// A tool offered by multiple targets, presented to clients once.
+#[derive(Debug, Clone)]
+struct MergedTool {
+	tool: Tool,
+	// The targets offering the tool, in the order they should be called.
+	targets: Vec<Strng>,
+	// Whether every target annotates the tool as idempotent or read-only.
+	idempotent: bool,
+}
+
+/// Where calls to a merged tool are sent.
+#[derive(Debug, Clone, PartialEq)]
+struct MergedRoute {
+	// The targets offering the tool, in the order they should be called.
+	targets: Vec<Strng>,
+	// Whether a call that failed on a target may be sent to the next.
+	idempotent: bool,
+}
+
+/// A failed tool call, and whether it was sent to the target. Calls that were not, for example as
+/// the target could not be connected to, are safe to send elsewhere.
+#[derive(Debug)]
+enum CallError {
+	Unsent(McpError),
+	Sent(McpError),
+}
+
+impl CallError {
+	fn error(&self) -> &McpError {
+		match self {
+			CallError::Unsent(e) | CallError::Sent(e) => e,
+		}
+	}
+}
+
+impl From<CallError> for McpError {
+	fn from(e: CallError) -> Self {
+		match e {
+			CallError::Unsent(e) | CallError::Sent(e) => e,
+		}
+	}
+}
+
+/// The request a tool call is made for: who made it, the session it came on, and its log.
+#[derive(Clone, Copy)]
+struct CallCtx<'a> {
+	rq_ctx: &'a RqCtx,
+	peer: &'a Peer<RoleServer>,
+	log: &'a AsyncLog<MCPInfo>,
+}
+
+/// Every page of the tools offered by a target.
+async fn all_tools(
+	svc: &upstream::UpstreamTarget,
+	rq_ctx: &RqCtx,
+) -> Result<Vec<Tool>, upstream::UpstreamError> {
+	let mut tools = vec![];
+	let mut request = None;
+	loop {
+		let page = svc.list_tools(request, rq_ctx).await?;
+		tools.extend(page.tools);
+		let Some(cursor) = page.next_cursor else {
+			return Ok(tools);
+		};
+		request = Some(PaginatedRequestParam {
+			cursor: Some(cursor),
+		});
+	}
 }
+
+/// Collapse tools with the same name and compatible input schemas across targets. Returns the merged
+/// tools, and the remaining tools along with the target offering them.
+fn merge_tools(
+	by_target: Vec<(Strng, Vec<Tool>)>,
+	primary: Option<&str>,
+) -> (Vec<MergedTool>, Vec<(Strng, Tool)>) {
+	let mut order: Vec<String> = vec![];
+	let mut groups: HashMap<String, Vec<(Strng, Tool)>> = HashMap::new();
+	for (target, tools) in by_target {
+		for tool in tools {
+			let group = groups.entry(tool.name.to_string()).or_insert_with(|| {
+				order.push(tool.name.to_string());
+				vec![]
+			});
+			group.push((target.clone(), tool));
+		}
+	}
+
+	let mut merged = vec![];
+	let mut rest = vec![];
+	for name in order {
+		let group = groups.remove(&name).unwrap_or_default();
+		let compatible = group.len() > 1 && {
+			let first = schema_shape(
+				&serde_json::Value::Object((*group[0].1.input_schema).clone()),
+				false,
+			);
+			group[1..].iter().all(|(_, t)| {
+				schema_shape(&serde_json::Value::Object((*t.input_schema).clone()), false) == first
+			})
+		};
+		if !compatible {
+			if group.len() > 1 {
+				tracing::debug!("not merging tool {name}: input schemas differ between targets");
+			}
+			rest.extend(group);
+			continue;
+		}
+		let (mut targets, mut tools): (Vec<_>, Vec<_>) = group.into_iter().unzip();
+		// Stable sort keeps target order for everything but the primary
+		targets.sort_by_key(|t| Some(t.as_str()) != primary);
+		tracing::info!(
+			"merged tool {name} offered by targets {}",
+			targets.iter().join(", ")
+		);
+		let idempotent = tools.iter().all(annotated_idempotent);
+		let mut tool = tools.swap_remove(0);
+		tool.annotations = None;
+		merged.push(MergedTool {
+			tool,
+			targets,
+			idempotent,
+		});
+	}
+	(merged, rest)
+}
+
+/// Routes the merged tools of a page of tools/list. A tool merged on several pages, or on a page
+/// listed again, is routed to the union of its targets, the primary first.
+fn add_merged_tools(
+	routes: &mut HashMap<String, MergedRoute>,
+	merged: &[MergedTool],
+	primary: Option<&str>,
+) {
+	for m in merged {
+		let route = routes
+			.entry(m.tool.name.to_string())
+			.or_insert_with(|| MergedRoute {
+				targets: vec![],
+				idempotent: m.idempotent,
+			});
+		route.idempotent &= m.idempotent;
+		for target in &m.targets {
+			if !route.targets.contains(target) {
+				route.targets.push(target.clone());
+			}
+		}
+		route.targets.sort_by_key(|t| Some(t.as_str()) != primary);
+	}
+}
+
+// Strip documentation from a JSON schema so schemas can be compared structurally.
+fn schema_shape(v: &serde_json::Value, is_properties: bool) -> serde_json::Value {
+	match v {
+		serde_json::Value::Object(o) => serde_json::Value::Object(
+			o.iter()
+				.filter(|(k, _)| is_properties || !matches!(k.as_str(), "description" | "title"))
+				.map(|(k, v)| {
+					(
+						k.clone(),
+						schema_shape(v, !is_properties && k == "properties"),
+					)
+				})
+				.collect(),
+		),
+		serde_json::Value::Array(a) => {
+			serde_json::Value::Array(a.iter().map(|v| schema_shape(v, false)).collect())
+		},
+		v => v.clone(),
+	}
+}
+
+#[cfg(test)]
+#[path = "tests.rs"]
+mod tests;
@@ -3,7 +3,10 @@ use crate::client::Client;
 use crate::http::{Body, Error as HttpError, auth};
 use crate::mcp::sse::McpTarget;
 use crate::store::BackendPolicies;
-use crate::types::agent::{Backend, McpBackend, McpTargetSpec, Target};
+use crate::types::agent::{
+	ArgumentLayout, Backend, McpBackend, McpTargetSpec, OpenAPISchema, OpenAPITarget,
+	SseReconnectPolicy, Target, check_stdio_command,
+};
 use crate::{client, json};
 use agent_core::prelude::*;
 use anyhow::anyhow;
@@ -12,12 +15,13 @@ use futures::stream::BoxStream;
 use futures::{FutureExt, StreamExt};
 use http::Uri;
 use http::header::CONTENT_TYPE;
+use openapiv3::OpenAPI;
 use reqwest::header::ACCEPT;
 use reqwest::{Client as HttpClient, IntoUrl, Url};
 use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
 use rmcp::service::{NotificationContext, Peer, serve_client_with_ct, serve_directly_with_ct};
 use rmcp::transport::StreamableHttpClientTransport;
-use rmcp::transport::common::client_side_sse::BoxedSseResponse;
+use rmcp::transport::common::client_side_sse::{BoxedSseResponse, SseRetryPolicy};
 use rmcp::transport::common::http_header::{
 	EVENT_STREAM_MIME_TYPE, HEADER_LAST_EVENT_ID, HEADER_SESSION_ID, JSON_MIME_TYPE,
 };
@@ -28,20 +32,62 @@ use rmcp::transport::streamable_http_client::{
 };
 use rmcp::transport::{SseClientTransport, Transport};
 use rmcp::{ClientHandler, ServiceError};
+use secrecy::ExposeSecret;
 use sse_stream::{Error as SseError, Sse, SseStream};
+use std::collections::HashSet;
+
+/// The connected targets, by the name they are listed under, and those left out because they were
+/// not reconnected by the deadline.
+pub(crate) struct Listed<'a> {
+	pub targets: Vec<(Strng, &'a Arc<upstream::UpstreamTarget>)>,
+	pub timed_out: Vec<Strng>,
+}
 
 pub(crate) struct ConnectionPool {
 	backend: McpBackendGroup,
 	client: client::Client,
-	by_name: HashMap<Strng, upstream::UpstreamTarget>,
+	metrics: Arc<metrics::Metrics>,
+	by_name: HashMap<Strng, Arc<upstream::UpstreamTarget>>,
+	// When each connection was last handed out. Only tracked to support idle eviction.
+	last_used: HashMap<Strng, Instant>,
+	// If set, connections not used for this long are dropped and lazily re-established on next use.
+	idle_timeout: Option<Duration>,
+	// The initialize request from the downstream client, kept so evicted targets can be reconnected.
+	init_request: Option<InitializeRequestParam>,
+	// Number of times each stdio target has been restarted after its process exited.
+	restarts: HashMap<Strng, u32>,
+	// The schema each connected OpenAPI target's tools were built from, to detect remote schema changes.
+	openapi_schemas: HashMap<Strng, Arc<OpenAPI>>,
+	// The merged capabilities of the connected targets. Cleared whenever the set of targets changes.
+	capabilities: Option<ServerCapabilities>,
+	// Resources the client subscribed to, by target, so subscriptions survive reconnects. Upstream
+	// sessions belong to a single client, so dropping the pool when the client disconnects closes
+	// them along with their subscriptions.
+	subscriptions: HashMap<Strng, HashSet<String>>,
+	// Each connection as recorded in the shared status registry, removed from it when dropped.
+	connections: HashMap<Strng, status::Connection>,
 }
 
 impl ConnectionPool {
-	pub(crate) fn new(client: client::Client, backend: McpBackendGroup) -> Self {
+	pub(crate) fn new(
+		client: client::Client,
+		backend: McpBackendGroup,
+		metrics: Arc<metrics::Metrics>,
+		idle_timeout: Option<Duration>,
+	) -> Self {
 		Self {
 			backend,
 			client,
+			metrics,
 			by_name: HashMap::new(),
+			last_used: HashMap::new(),
+			idle_timeout,
+			init_request: None,
+			restarts: HashMap::new(),
+			openapi_schemas: HashMap::new(),
+			capabilities: None,
+			subscriptions: HashMap::new(),
+			connections: HashMap::new(),
 		}
 	}
 
@@ -51,60 +97,425 @@ impl ConnectionPool {
 		peer: &Peer<RoleServer>,
 		name: &str,
 	) -> anyhow::Result<&upstream::UpstreamTarget> {
-		// If it doesn't exist, they haven't initialized yet
+		let resolved = self.resolve(name);
+		let name = resolved.as_str();
+		self.evict_idle().await;
+		self.remove_exited(name).await;
+		self.refresh_openapi(name);
 		if !self.by_name.contains_key(name) {
-			return Err(anyhow::anyhow!(
-				"requested target {name} is not initialized",
-			));
+			// If we never saw an initialize, they haven't initialized yet
+			let Some(init_request) = self.init_request.clone() else {
+				return Err(anyhow::anyhow!(
+					"requested target {name} is not initialized",
+				));
+			};
+			// Otherwise the connection was evicted or its process exited; reconnect it
+			if let Some(tgt) = self.backend.find(name) {
+				let ct = tokio_util::sync::CancellationToken::new(); //TODO
+				debug!("reconnecting evicted target: {}", tgt.name);
+				self.connect(rq_ctx, &ct, &tgt, peer, init_request).await?;
+			}
 		}
-		let target = self.by_name.get(name);
+		self.touch(name);
+		let target = self.by_name.get(name).map(Arc::as_ref);
 		Ok(target.ok_or(McpError::invalid_request(
 			format!("Service {name} not found"),
 			None,
 		))?)
 	}
 
-	pub(crate) async fn remove(&mut self, name: &str) -> Option<upstream::UpstreamTarget> {
-		self.by_name.remove(name)
+	pub(crate) fn backend_name(&self) -> &str {
+		&self.backend.name
+	}
+
+	/// Records a failed call to a target in the shared status registry.
+	pub(crate) fn record_error(&self, name: &str, message: String) {
+		self
+			.backend
+			.status
+			.error(self.backend.name.clone(), &name.into(), message);
+	}
+
+	/// The target requests other than tool calls to `name` are sent to. For a target group, this is
+	/// the first member that is connected and whose circuit breaker is not open, so reads and
+	/// subscriptions stick to one member.
+	fn resolve(&self, name: &str) -> Strng {
+		let Some(group) = self.backend.groups.get(name) else {
+			return name.into();
+		};
+		group
+			.members()
+			.min_by_key(|m| {
+				let open = self
+					.backend
+					.circuit_breaker(m)
+					.is_some_and(|b| b.state() == breaker::State::Open);
+				(open, !self.by_name.contains_key(*m))
+			})
+			.cloned()
+			.unwrap_or_else(|| name.into())
+	}
+
+	pub(crate) async fn remove(&mut self, name: &str) -> Option<Arc<upstream::UpstreamTarget>> {
+		self.last_used.remove(name);
+		self.openapi_schemas.remove(name);
+		self.connections.remove(name);
+		self.capabilities = None;
+		let removed = self.by_name.remove(name);
+		if removed.is_some() {
+			self.record_size(-1);
+		}
+		removed
 	}
 
+	/// Connects every target, returning them with their own handle so they can be used once the pool
+	/// is unlocked.
 	pub(crate) async fn initialize(
 		&mut self,
 		rq_ctx: &RqCtx,
 		peer: &Peer<RoleServer>,
 		request: InitializeRequestParam,
-	) -> anyhow::Result<Vec<(Strng, &upstream::UpstreamTarget)>> {
+	) -> anyhow::Result<Vec<(Strng, Arc<upstream::UpstreamTarget>)>> {
+		if let Some(tgt) = self
+			.backend
+			.targets
+			.iter()
+			.find(|t| self.by_name.contains_key(&t.name))
+		{
+			anyhow::bail!("connection {} already initialized", tgt.name);
+		}
+		self
+			.connect_missing(rq_ctx, peer, request.clone(), true, None)
+			.await?;
+		self.init_request = Some(request);
+		let listed = self.list(rq_ctx, peer).await?;
+		Ok(
+			listed
+				.into_iter()
+				.map(|(name, target)| (name, target.clone()))
+				.collect(),
+		)
+	}
+
+	/// Connects each target that is not connected yet. Failing to connect a member of a target group
+	/// counts against its circuit breaker, and members whose breaker is open are not retried here;
+	/// the group only fails if none of its members is connected. Unless `required` is set, targets
+	/// that fail are logged and left out instead. Targets not connected by the deadline are left out
+	/// too, and returned, by the name they are listed under.
+	async fn connect_missing(
+		&mut self,
+		rq_ctx: &RqCtx,
+		peer: &Peer<RoleServer>,
+		request: InitializeRequestParam,
+		required: bool,
+		deadline: Option<tokio::time::Instant>,
+	) -> anyhow::Result<Vec<Strng>> {
+		let mut timed_out = Vec::new();
 		for tgt in self.backend.targets.clone() {
 			if self.by_name.contains_key(&tgt.name) {
-				anyhow::bail!("connection {} already initialized", tgt.name);
+				continue;
+			}
+			let group = self.backend.group_of(&tgt.name).cloned();
+			let open = tgt
+				.circuit_breaker
+				.as_ref()
+				.is_some_and(|b| b.state() == breaker::State::Open);
+			if group.is_some() && open {
+				continue;
+			}
+			let listed_as = group.clone().unwrap_or_else(|| tgt.name.clone());
+			if deadline.is_some_and(|d| tokio::time::Instant::now() >= d) {
+				if !timed_out.contains(&listed_as) {
+					timed_out.push(listed_as);
+				}
+				continue;
 			}
 			let ct = tokio_util::sync::CancellationToken::new(); //TODO
-			debug!("initializing target: {}", tgt.name);
-			self
-				.connect(rq_ctx, &ct, &tgt, peer, request.clone())
-				.await
-				.map_err(|e| {
-					error!("Failed to connect target {}: {}", tgt.name, e);
-					e // Propagate error
-				})?;
+			debug!("connecting target: {}", tgt.name);
+			let connect = self.connect(rq_ctx, &ct, &tgt, peer, request.clone());
+			let res = match deadline {
+				Some(deadline) => match tokio::time::timeout_at(deadline, connect).await {
+					Ok(res) => res,
+					Err(_) => {
+						if !timed_out.contains(&listed_as) {
+							timed_out.push(listed_as);
+						}
+						continue;
+					},
+				},
+				None => connect.await,
+			};
+			match (res, group) {
+				(Ok(()), _) => {},
+				(Err(e), None) if required => {
+					error!("Failed to connect target {}: {:#}", tgt.name, e);
+					return Err(e);
+				},
+				(Err(e), None) => {
+					warn!(
+						"failed to reconnect target {}, skipping it: {:#}",
+						tgt.name, e
+					);
+					self.record_error(&tgt.name, format!("{e:#}"));
+				},
+				(Err(e), Some(group)) => {
+					warn!(
+						"failed to connect target {} of group {}: {:#}",
+						tgt.name, group, e
+					);
+					if let Some(breaker) = &tgt.circuit_breaker {
+						breaker.record(false);
+					}
+				},
+			}
+		}
+		for (group, balancer) in &self.backend.groups {
+			if balancer.members().any(|m| self.by_name.contains_key(m)) || timed_out.contains(group) {
+				continue;
+			}
+			if required {
+				anyhow::bail!("no member of target group {group} could be connected");
+			}
+			warn!("no member of target group {group} could be reconnected, skipping it");
 		}
-		self.list().await
+		Ok(timed_out)
 	}
 
-	pub(crate) async fn list(&mut self) -> anyhow::Result<Vec<(Strng, &upstream::UpstreamTarget)>> {
+	pub(crate) async fn list(
+		&mut self,
+		rq_ctx: &RqCtx,
+		peer: &Peer<RoleServer>,
+	) -> anyhow::Result<Vec<(Strng, &Arc<upstream::UpstreamTarget>)>> {
+		Ok(self.list_until(rq_ctx, peer, None).await?.targets)
+	}
+
+	/// Like [Self::list], but targets that are not reconnected by the deadline are left out.
+	pub(crate) async fn list_until(
+		&mut self,
+		rq_ctx: &RqCtx,
+		peer: &Peer<RoleServer>,
+		deadline: Option<tokio::time::Instant>,
+	) -> anyhow::Result<Listed<'_>> {
+		self.evict_idle().await;
+		for tgt in self.backend.targets.clone() {
+			self.remove_exited(&tgt.name).await;
+			self.refresh_openapi(&tgt.name);
+		}
+		let mut timed_out = Vec::new();
+		if let Some(init_request) = self.init_request.clone() {
+			// Reconnect evicted targets. One failing should not hide the others, so it is left out.
+			timed_out = self
+				.connect_missing(rq_ctx, peer, init_request, false, deadline)
+				.await?;
+		}
+		for tgt in self.backend.targets.clone() {
+			self.touch(&tgt.name);
+		}
+		// A target group is listed once, from one of its members
 		let results = self
 			.backend
-			.targets
-			.iter()
-			.filter_map(|(tgt)| {
-				self
-					.by_name
-					.get(&tgt.name)
-					.map(|target: &upstream::UpstreamTarget| (tgt.name.clone(), target))
+			.target_names()
+			.into_iter()
+			.filter_map(|name| {
+				let target = self.resolve(&name);
+				self.by_name.get(&target).map(|target| (name, target))
 			})
 			.collect();
 
-		Ok(results)
+		Ok(Listed {
+			targets: results,
+			timed_out,
+		})
+	}
+
+	/// The union of the capabilities of the connected targets.
+	pub(crate) fn capabilities(&mut self) -> ServerCapabilities {
+		if let Some(caps) = &self.capabilities {
+			return caps.clone();
+		}
+		let caps = merge_capabilities(self.by_name.values().map(|t| t.capabilities()));
+		self.capabilities = Some(caps.clone());
+		caps
+	}
+
+	/// Drop any connections that have not been used within the idle timeout. MCP connections are
+	/// cancelled so the underlying transport (and child process, for stdio) is cleaned up.
+	pub(crate) async fn evict_idle(&mut self) {
+		let Some(idle_timeout) = self.idle_timeout else {
+			return;
+		};
+		let idle = self
+			.last_used
+			.iter()
+			.filter(|(_, last)| last.elapsed() >= idle_timeout)
+			.map(|(name, _)| name.clone())
+			.collect_vec();
+		for name in idle {
+			let Some(target) = self.remove(&name).await else {
+				continue;
+			};
+			debug!("evicting idle target: {}", name);
+			self.metrics.record(
+				metrics::PoolEviction {
+					server: self.backend.name.to_string(),
+					target: name.to_string(),
+				},
+				(),
+			);
+			// A request still holding the target drops it when done, which cancels it
+			if let Some(upstream::UpstreamTarget {
+				spec: upstream::UpstreamTargetSpec::Mcp(m),
+				..
+			}) = Arc::into_inner(target)
+				&& let Err(e) = m.cancel().await
+			{
+				warn!("failed to cancel evicted target {}: {}", name, e);
+			}
+		}
+	}
+
+	/// If the named stdio target's process has exited, the target and how many times it was restarted.
+	fn exited(&self, name: &str) -> Option<(Arc<McpTarget>, u32)> {
+		let Some(upstream::UpstreamTarget {
+			spec: upstream::UpstreamTargetSpec::Mcp(m),
+			..
+		}) = self.by_name.get(name).map(Arc::as_ref)
+		else {
+			return None;
+		};
+		if !m.peer().is_transport_closed() {
+			return None;
+		}
+		let tgt = self.backend.find(name)?;
+		if !matches!(tgt.spec, McpTargetSpec::Stdio { .. }) {
+			return None;
+		}
+		Some((tgt, self.restarts.get(name).copied().unwrap_or_default()))
+	}
+
+	/// The delay before restarting the named target, or the longest of any target if `name` is None,
+	/// if its process exited and its restart policy allows another restart. Callers wait this out
+	/// before locking the pool, so a restarting target does not hold up requests to the others.
+	pub(crate) fn restart_backoff(&self, name: Option<&str>) -> Option<Duration> {
+		let backoff = |name: &str| {
+			let (tgt, attempt) = self.exited(name)?;
+			let McpTargetSpec::Stdio {
+				restart: Some(restart),
+				..
+			} = &tgt.spec
+			else {
+				return None;
+			};
+			(attempt < restart.max_restarts).then(|| restart.backoff_for(attempt))
+		};
+		match name {
+			Some(name) => backoff(&self.resolve(name)),
+			None => self
+				.backend
+				.targets
+				.iter()
+				.filter_map(|tgt| backoff(&tgt.name))
+				.max(),
+		}
+	}
+
+	/// If the named target is a stdio server whose process has exited, and its restart policy allows
+	/// it, remove it from the pool so it is restarted on this request. The restart backoff is waited
+	/// out by the caller, see [Self::restart_backoff].
+	async fn remove_exited(&mut self, name: &str) {
+		let Some((tgt, attempt)) = self.exited(name) else {
+			return;
+		};
+		let McpTargetSpec::Stdio { cmd, restart, .. } = &tgt.spec else {
+			return;
+		};
+		if restart.as_ref().is_none_or(|r| attempt >= r.max_restarts) {
+			// Leave the closed connection in place; requests will fail with a transport error.
+			if let Some(conn) = self.connections.get(name) {
+				conn.exited("transport closed".to_string());
+			}
+			return;
+		}
+		let conn = self.connections.remove(name);
+		if let Some(upstream::UpstreamTarget {
+			spec: upstream::UpstreamTargetSpec::Mcp(m),
+			..
+		}) = self.remove(name).await.and_then(Arc::into_inner)
+		{
+			let status = match m.waiting().await {
+				Ok(reason) => format!("{reason:?}"),
+				Err(e) => e.to_string(),
+			};
+			warn!("stdio target {} ('{}') exited: {}", name, cmd, status);
+			if let Some(conn) = conn {
+				conn.exited(status);
+			}
+		}
+		self.restarts.insert(name.into(), attempt + 1);
+		self.metrics.record(
+			metrics::StdioRestart {
+				server: self.backend.name.to_string(),
+				target: name.to_string(),
+			},
+			(),
+		);
+		info!("restarting stdio target {} (attempt {})", name, attempt + 1);
+	}
+
+	/// If the named target is an OpenAPI target whose remote schema changed since its tools were built,
+	/// rebuild the target's tools. If the new schema cannot be used, the previous tools are kept.
+	fn refresh_openapi(&mut self, name: &str) {
+		let Some(current) = self.openapi_schemas.get(name).cloned() else {
+			return;
+		};
+		let Some(tgt) = self.backend.find(name) else {
+			return;
+		};
+		let McpTargetSpec::OpenAPI(open) = &tgt.spec else {
+			return;
+		};
+		let schema = match Self::openapi_schema(&open.schema) {
+			Ok(schema) => schema,
+			Err(e) => {
+				warn!(
+					"failed to refresh OpenAPI schema for target {}: {:#}",
+					name, e
+				);
+				return;
+			},
+		};
+		if Arc::ptr_eq(&schema, &current) {
+			return;
+		}
+		match self.openapi_target(&tgt, open, &schema) {
+			Ok(upstream) => {
+				info!(
+					"OpenAPI schema for target {} changed, rebuilding tools",
+					name
+				);
+				self.by_name.insert(name.into(), Arc::new(upstream));
+			},
+			Err(e) => warn!(
+				"failed to rebuild tools for target {}, keeping previous tools: {:#}",
+				name, e
+			),
+		}
+		self.openapi_schemas.insert(name.into(), schema);
+	}
+
+	fn touch(&mut self, name: &str) {
+		if self.idle_timeout.is_some() && self.by_name.contains_key(name) {
+			self.last_used.insert(name.into(), Instant::now());
+		}
+	}
+
+	fn record_size(&self, delta: i64) {
+		self.metrics.record(
+			metrics::PoolConnections {
+				server: self.backend.name.to_string(),
+			},
+			delta,
+		);
 	}
 
 	#[instrument(
@@ -127,148 +538,445 @@ impl ConnectionPool {
 			return Ok(());
 		}
 		trace!("connecting to target: {}", target.name);
-		let transport: upstream::UpstreamTarget = match &target.spec {
-			McpTargetSpec::Sse(sse) => {
-				debug!("starting sse transport for target: {}", target.name);
-				let path = match sse.path.as_str() {
-					"" => "/sse",
-					_ => sse.path.as_str(),
-				};
-				let url = format!("http://{}:{}{}", sse.host, sse.port, path);
-				let client =
-					ClientWrapper::new_with_client(self.client.clone(), target.backend_policies.clone());
-				let transport = SseClientTransport::start_with_client(
-					client,
-					SseClientConfig {
+		let mut pid = None;
+		let transport = async {
+			Ok::<_, anyhow::Error>(match &target.spec {
+				McpTargetSpec::Sse(sse) => {
+					debug!("starting sse transport for target: {}", target.name);
+					let path = match sse.path.as_str() {
+						"" => "/sse",
+						_ => sse.path.as_str(),
+					};
+					let url = format!("http://{}:{}{}", sse.host, sse.port, path);
+					let client =
+						ClientWrapper::new_with_client(self.client.clone(), target.backend_policies.clone());
+					let mut config = SseClientConfig {
 						sse_endpoint: url.into(),
 						..Default::default()
-					},
-				)
-				.await
-				.context("start sse client")?;
-
-				upstream::UpstreamTarget {
-					filters: target.filters.clone(),
-					spec: upstream::UpstreamTargetSpec::Mcp(
-						serve_client_with_ct(
-							PeerClientHandler {
-								peer: peer.clone(),
-								peer_client: None,
-								init_request,
-							},
-							transport,
-							ct.child_token(),
-						)
-						.await?,
-					),
-				}
+					};
+					config.retry_policy = Arc::new(ReconnectPolicy {
+						policy: sse.reconnect.clone(),
+						fallback: config.retry_policy.clone(),
+						metrics: self.metrics.clone(),
+						server: self.backend.name.to_string(),
+						target: target.name.to_string(),
+					});
+					let transport = SseClientTransport::start_with_client(client, config)
+						.await
+						.context("start sse client")?;
+
+					upstream::UpstreamTarget {
+						filters: target.filters.clone(),
+						spec: upstream::UpstreamTargetSpec::Mcp(
+							serve_client_with_ct(
+								self.peer_handler(&target.name, peer, init_request),
+								transport,
+								ct.child_token(),
+							)
+							.await?,
+						),
+					}
+				},
+				McpTargetSpec::Mcp(mcp) => {
+					debug!(
+						"starting streamable http transport for target: {}",
+						target.name
+					);
+					let path = match mcp.path.as_str() {
+						"" => "/mcp",
+						_ => mcp.path.as_str(),
+					};
+					let url = format!("http://{}:{}{}", mcp.host, mcp.port, path);
+					let client =
+						ClientWrapper::new_with_client(self.client.clone(), target.backend_policies.clone());
+					let client = reqwest::Client::new();
+					let mut transport = StreamableHttpClientTransport::with_client(
+						client,
+						StreamableHttpClientTransportConfig {
+							uri: url.into(),
+							..Default::default()
+						},
+					);
+					// transport.send(ClientJsonRpcMessage::response(
+					// 	ClientResult::InitializeResult(model::InitializeResult {
+					//
+					// 	}),
+					// 	RequestId::Number(1),
+					// )).await.unwrap()
+
+					upstream::UpstreamTarget {
+						filters: target.filters.clone(),
+						spec: upstream::UpstreamTargetSpec::Mcp(
+							serve_client_with_ct(
+								self.peer_handler(&target.name, peer, init_request),
+								transport,
+								ct.child_token(),
+							)
+							.await?,
+						),
+					}
+				},
+				McpTargetSpec::Stdio {
+					cmd,
+					args,
+					env,
+					env_inherit,
+					cwd,
+					identity_env,
+					..
+				} => {
+					debug!("starting stdio transport for target: {}", target.name);
+					let mut env = env.clone();
+					env.extend(caller_env(identity_env, &rq_ctx.identity));
+					let mut c = stdio_command(
+						cmd,
+						args,
+						&env,
+						*env_inherit,
+						cwd.as_deref(),
+						&self.backend.stdio_command_allowlist,
+					)?;
+					let (stderr, pipe) =
+						stderr::Stderr::capture(&target.name).context("failed to capture stderr")?;
+					c.stderr(pipe);
+					let process =
+						TokioChildProcess::new(c).context(format!("failed to run command '{cmd}'"))?;
+					pid = process.id();
+					let service = serve_client_with_ct(
+						self.peer_handler(&target.name, peer, init_request),
+						process,
+						ct.child_token(),
+					)
+					.await;
+					let service = match service {
+						Ok(service) => service,
+						Err(e) => {
+							// Give the process a moment to exit, so its last words are read
+							stderr.closed(Duration::from_millis(100)).await;
+							return Err(stderr.explain(e.into()));
+						},
+					};
+					upstream::UpstreamTarget {
+						filters: target.filters.clone(),
+						spec: upstream::UpstreamTargetSpec::Mcp(service),
+					}
+				},
+				McpTargetSpec::Grpc(grpc) => {
+					debug!("starting grpc transport for target: {}", target.name);
+					let port = u16::try_from(grpc.port).context("invalid grpc port")?;
+					let tgt = Target::try_from((grpc.host.as_str(), port))?;
+					let transport =
+						super::grpc::connect(self.client.clone(), target.backend_policies.clone(), tgt)
+							.await
+							.context("start grpc client")?;
+
+					upstream::UpstreamTarget {
+						filters: target.filters.clone(),
+						spec: upstream::UpstreamTargetSpec::Mcp(
+							serve_client_with_ct(
+								self.peer_handler(&target.name, peer, init_request),
+								transport,
+								ct.child_token(),
+							)
+							.await?,
+						),
+					}
+				},
+				McpTargetSpec::OpenAPI(open) => {
+					debug!("starting OpenAPI transport for target: {}", target.name);
+					let schema = Self::openapi_schema(&open.schema)?;
+					let upstream = self.openapi_target(target, open, &schema)?;
+					self.openapi_schemas.insert(target.name.clone(), schema);
+					upstream
+				},
+			})
+		}
+		.await;
+		let transport = match transport {
+			Ok(transport) => transport,
+			Err(e) => {
+				self
+					.backend
+					.status
+					.error(self.backend.name.clone(), &target.name, format!("{e:#}"));
+				return Err(e);
 			},
-			McpTargetSpec::Mcp(mcp) => {
-				debug!(
-					"starting streamable http transport for target: {}",
-					target.name
-				);
-				let path = match mcp.path.as_str() {
-					"" => "/mcp",
-					_ => mcp.path.as_str(),
-				};
-				let url = format!("http://{}:{}{}", mcp.host, mcp.port, path);
-				let client =
-					ClientWrapper::new_with_client(self.client.clone(), target.backend_policies.clone());
-				let client = reqwest::Client::new();
-				let mut transport = StreamableHttpClientTransport::with_client(
-					client,
-					StreamableHttpClientTransportConfig {
-						uri: url.into(),
-						..Default::default()
-					},
+		};
+		let conn = self
+			.backend
+			.status
+			.connected(self.backend.name.clone(), target.name.clone(), pid);
+		self.connections.insert(target.name.clone(), conn);
+		self.by_name.insert(target.name.clone(), Arc::new(transport));
+		self.capabilities = None;
+		self.touch(&target.name);
+		self.record_size(1);
+		self.resubscribe(rq_ctx, &target.name).await;
+		Ok(())
+	}
+
+	fn peer_handler(
+		&self,
+		name: &Strng,
+		peer: &Peer<RoleServer>,
+		init_request: InitializeRequestParam,
+	) -> PeerClientHandler {
+		let uri_prefix = uri_prefix(&self.backend, name);
+		PeerClientHandler {
+			peer: peer.clone(),
+			peer_client: None,
+			init_request,
+			uri_prefix,
+		}
+	}
+
+	pub(crate) async fn subscribe(
+		&mut self,
+		rq_ctx: &RqCtx,
+		peer: &Peer<RoleServer>,
+		name: &str,
+		uri: &str,
+	) -> Result<(), upstream::UpstreamError> {
+		// Keyed by the target actually subscribed to, so it can be restored when that one reconnects
+		let name = self.resolve(name);
+		let target = self.get(rq_ctx, peer, &name).await?;
+		target.subscribe(uri, rq_ctx).await?;
+		self
+			.subscriptions
+			.entry(name)
+			.or_default()
+			.insert(uri.to_string());
+		Ok(())
+	}
+
+	pub(crate) async fn unsubscribe(
+		&mut self,
+		rq_ctx: &RqCtx,
+		peer: &Peer<RoleServer>,
+		name: &str,
+		uri: &str,
+	) -> Result<(), upstream::UpstreamError> {
+		// Unsubscribe from the target that was subscribed to, even if another member of its group
+		// would be picked now
+		let subscribed = self.backend.groups.get(name).and_then(|g| {
+			g.members()
+				.find(|m| self.subscriptions.get(*m).is_some_and(|s| s.contains(uri)))
+				.cloned()
+		});
+		let resolved = subscribed.unwrap_or_else(|| self.resolve(name));
+		let name = resolved.as_str();
+		if let Some(subs) = self.subscriptions.get_mut(name) {
+			subs.remove(uri);
+		}
+		let target = self.get(rq_ctx, peer, name).await?;
+		target.unsubscribe(uri, rq_ctx).await
+	}
+
+	/// Restores the subscriptions on a target that was reconnected, for example after being evicted
+	/// for idleness or its process restarting.
+	async fn resubscribe(&self, rq_ctx: &RqCtx, name: &str) {
+		let (Some(subs), Some(target)) = (self.subscriptions.get(name), self.by_name.get(name)) else {
+			return;
+		};
+		for uri in subs {
+			if let Err(e) = target.subscribe(uri, rq_ctx).await {
+				warn!(
+					"failed to restore subscription to {} on target {}: {}",
+					uri,
+					name,
+					e.error_code()
 				);
-				// transport.send(ClientJsonRpcMessage::response(
-				// 	ClientResult::InitializeResult(model::InitializeResult {
-				//
-				// 	}),
-				// 	RequestId::Number(1),
-				// )).await.unwrap()
-
-				upstream::UpstreamTarget {
-					filters: target.filters.clone(),
-					spec: upstream::UpstreamTargetSpec::Mcp(
-						serve_client_with_ct(
-							PeerClientHandler {
-								peer: peer.clone(),
-								peer_client: None,
-								init_request,
-							},
-							transport,
-							ct.child_token(),
-						)
-						.await?,
-					),
-				}
-			},
-			McpTargetSpec::Stdio { cmd, args, env: _ } => {
-				debug!("starting stdio transport for target: {}", target.name);
-				let mut c = Command::new(cmd);
-				c.args(args);
-				upstream::UpstreamTarget {
-					filters: target.filters.clone(),
-					spec: upstream::UpstreamTargetSpec::Mcp(
-						serve_client_with_ct(
-							PeerClientHandler {
-								peer: peer.clone(),
-								peer_client: None,
-								init_request,
-							},
-							TokioChildProcess::new(c).context(format!("failed to run command '{cmd}'"))?,
-							ct.child_token(),
-						)
-						.await?,
-					),
-				}
-			},
-			McpTargetSpec::OpenAPI(open) => {
-				// Renamed for clarity
-				debug!("starting OpenAPI transport for target: {}", target.name);
+			}
+		}
+	}
+
+	/// Returns the schema for an OpenAPI target. Remote schemas are fetched before the pool is locked,
+	/// see [super::Relay::refresh_schemas], so this only returns the last fetched schema.
+	fn openapi_schema(schema: &OpenAPISchema) -> anyhow::Result<Arc<OpenAPI>> {
+		match schema {
+			OpenAPISchema::Static(schema) => Ok(schema.clone()),
+			OpenAPISchema::Remote(remote) => remote.schema(),
+		}
+	}
+
+	fn openapi_target(
+		&self,
+		target: &McpTarget,
+		open: &OpenAPITarget,
+		schema: &Arc<OpenAPI>,
+	) -> anyhow::Result<upstream::UpstreamTarget> {
+		// Keep the original error as the source, so the full chain is reported.
+		let options = open.parse_options(self.backend.read_only.is_some());
+		let mut tools = crate::mcp::openapi::parse_openapi_tools(schema, &options).map_err(|e| {
+			let code = e.code();
+			anyhow::Error::new(e).context(format!(
+				"failed to parse tools from OpenAPI schema for target {} ({code})",
+				target.name
+			))
+		})?;
+		if open.argument_layout == ArgumentLayout::Flat {
+			for (tool, call) in &mut tools {
+				call.flat = Some(crate::mcp::openapi::flatten_arguments(tool));
+			}
+		}
+		if open.describe_arguments {
+			for (tool, call) in &mut tools {
+				crate::mcp::openapi::describe_arguments(tool, call);
+			}
+		}
+		if open.include_examples {
+			for (tool, call) in &mut tools {
+				crate::mcp::openapi::describe_example(tool, call);
+			}
+		}
+
+		// The prefix is only used to call tools, so a schema whose operations are all filtered out
+		// does not need a valid one.
+		let server = if tools.is_empty() {
+			crate::mcp::openapi::ServerUrl {
+				scheme: None,
+				host: None,
+				port: None,
+				prefix: String::new(),
+			}
+		} else {
+			crate::mcp::openapi::get_server_url(schema).map_err(|e| {
+				let code = e.code();
+				anyhow::Error::new(e).context(format!(
+					"failed to get server prefix from OpenAPI schema for target {} ({code})",
+					target.name
+				))
+			})?
+		};
+		let prefix = match &open.path_prefix_override {
+			Some(o) => o.apply(&server.prefix),
+			None => server.prefix.clone(),
+		};
 
-				let tools = crate::mcp::openapi::parse_openapi_schema(&open.schema).map_err(|e| {
+		let static_headers = open
+			.static_headers
+			.iter()
+			.map(|h| {
+				let mut value = ::http::HeaderValue::from_str(h.value.expose_secret()).map_err(|_| {
 					anyhow::anyhow!(
-						"Failed to parse tools from OpenAPI schema for target {}: {}",
-						target.name,
-						e
+						"invalid value for static header '{}' of target {}",
+						h.name,
+						target.name
 					)
 				})?;
+				value.set_sensitive(true);
+				Ok((h.name.clone(), value))
+			})
+			.collect::<anyhow::Result<Vec<_>>>()?;
+		let sensitive_headers =
+			crate::http::redact::SensitiveHeaders::new(open.sensitive_headers.clone());
+
+		let credentials =
+			open
+				.resolved_credentials
+				.get(&target.name, schema, &open.security_credentials)?;
 
-				let prefix = crate::mcp::openapi::get_server_prefix(&open.schema).map_err(|e| {
+		let mut policies = target.backend_policies.clone();
+		let scheme = crate::mcp::openapi::resolve_scheme(open.scheme, &server, &mut policies);
+		let (host, port) =
+			crate::mcp::openapi::resolve_address(open.host.as_deref(), open.port, &server, scheme)
+				// Like the prefix, the address is not needed without tools
+				.or_else(|| tools.is_empty().then(Default::default))
+				.ok_or_else(|| {
 					anyhow::anyhow!(
-						"Failed to get server prefix from OpenAPI schema for target {}: {}",
-						target.name,
-						e
+						"OpenAPI target {} has no host, and the schema's server URL does not set one",
+						target.name
 					)
 				})?;
+		if let Some(tls) = &policies.backend_tls {
+			policies.backend_tls = Some(tls.for_http_version(open.http_version));
+		}
 
-				upstream::UpstreamTarget {
-					filters: target.filters.clone(), // From the outer 'target' variable
-					spec: upstream::UpstreamTargetSpec::OpenAPI(Box::new(crate::mcp::openapi::Handler {
-						host: open.host.clone(),
-						client: self.client.clone(),
-						policies: target.backend_policies.clone(),
-						tools,  // From parse_openapi_schema
-						prefix, // From get_server_prefix
-						port: open.port,
-					})),
-				}
-			},
-		};
-		self.by_name.insert(target.name.clone(), transport);
-		Ok(())
+		Ok(upstream::UpstreamTarget {
+			filters: target.filters.clone(),
+			spec: upstream::UpstreamTargetSpec::OpenAPI(Box::new(crate::mcp::openapi::Handler {
+				host,
+				client: target.client.clone().unwrap_or_else(|| self.client.clone()),
+				policies,
+				tools,
+				prefix,
+				port,
+				success_statuses: open.success_statuses.clone(),
+				apply_defaults: open.apply_defaults,
+				validation: open.validate_arguments.clone(),
+				http_version: open.http_version,
+				text_content_types: open.text_content_types.clone(),
+				static_headers,
+				denied_headers: open.denied_headers.clone(),
+				sensitive_headers,
+				credentials,
+				allow_reserved_path_characters: open.allow_reserved_path_characters,
+				scheme,
+				connection_metrics: Some(crate::mcp::openapi::ConnectionMetrics {
+					metrics: self.metrics.clone(),
+					server: self.backend.name.to_string(),
+					target: target.name.to_string(),
+				}),
+			})),
+		})
 	}
 }
 
+impl Drop for ConnectionPool {
+	fn drop(&mut self) {
+		self.record_size(-(self.by_name.len() as i64));
+	}
+}
+
+/// The environment variables set to claims of the caller, per the `identityEnv` of a stdio target.
+/// Variables for claims the caller does not have are left out.
+pub(crate) fn caller_env<'a>(
+	identity_env: &'a HashMap<String, String>,
+	identity: &'a Identity,
+) -> impl Iterator<Item = (String, String)> + 'a {
+	identity_env.iter().filter_map(|(var, claim)| {
+		let value = identity.get_claim(claim, ".")?;
+		Some((var.clone(), value.to_string()))
+	})
+}
+
+/// The command that starts the process of a stdio target. Fails if `allowlist` does not allow
+/// `cmd`, see [check_stdio_command].
+pub(crate) fn stdio_command(
+	cmd: &str,
+	args: &[String],
+	env: &HashMap<String, String>,
+	env_inherit: Option<bool>,
+	cwd: Option<&std::path::Path>,
+	allowlist: &[String],
+) -> anyhow::Result<Command> {
+	check_stdio_command(cmd, env.keys(), allowlist)?;
+	let mut c = Command::new(cmd);
+	c.args(args);
+	if !env_inherit.unwrap_or(true) {
+		c.env_clear();
+	}
+	c.envs(env);
+	if let Some(cwd) = cwd {
+		c.current_dir(cwd);
+	}
+	Ok(c)
+}
+
+/// The prefix of the resource URIs of `target` as clients see them: the name of its group, or its
+/// own name if it is not in one. Names are only prefixed when clients see more than one.
+pub(crate) fn uri_prefix(backend: &McpBackendGroup, target: &Strng) -> Option<String> {
+	let name = backend.group_of(target).unwrap_or(target);
+	(backend.target_names().len() != 1).then(|| format!("{name}{DELIMITER}"))
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct PeerClientHandler {
 	peer: Peer<RoleServer>,
 	peer_client: Option<Peer<RoleClient>>,
 	init_request: InitializeRequestParam,
+	// Prefix to add to resource URIs sent to the client, so they match the names we advertised.
+	uri_prefix: Option<String>,
 }
 
 impl ClientHandler for PeerClientHandler {
@@ -355,9 +1063,12 @@ impl ClientHandler for PeerClientHandler {
 
 	async fn on_resource_updated(
 		&self,
-		params: ResourceUpdatedNotificationParam,
+		mut params: ResourceUpdatedNotificationParam,
 		_context: NotificationContext<RoleClient>,
 	) {
+		if let Some(prefix) = &self.uri_prefix {
+			params.uri = format!("{prefix}{}", params.uri);
+		}
 		let _ = self
 			.peer
 			.notify_resource_updated(params)
@@ -442,6 +1153,7 @@ impl StreamableHttpClient for ClientWrapper {
 				req,
 				target,
 				transport: policies.backend_tls.clone().into(),
+				proxy: policies.outbound_proxy.clone(),
 			})
 			.await
 			.map_err(|e| StreamableHttpError::Client(HttpError::new(e)))?;
@@ -505,6 +1217,7 @@ impl StreamableHttpClient for ClientWrapper {
 				req,
 				target,
 				transport: policies.backend_tls.clone().into(),
+				proxy: policies.outbound_proxy.clone(),
 			})
 			.await
 			.map_err(|e| StreamableHttpError::Client(HttpError::new(e)))?;
@@ -561,6 +1274,7 @@ impl StreamableHttpClient for ClientWrapper {
 				req,
 				target,
 				transport: policies.backend_tls.clone().into(),
+				proxy: policies.outbound_proxy.clone(),
 			})
 			.await
 			.map_err(|e| StreamableHttpError::Client(HttpError::new(e)))?;
@@ -594,6 +1308,49 @@ impl StreamableHttpClient for ClientWrapper {
 	}
 }
 
+/// Applies a target's configured [SseReconnectPolicy] when rmcp re-establishes a dropped SSE stream,
+/// falling back to rmcp's default policy if none is configured.
+/// rmcp resumes the stream by passing the last seen event id back to [ClientWrapper::get_stream].
+#[derive(Debug)]
+struct ReconnectPolicy {
+	policy: Option<SseReconnectPolicy>,
+	fallback: Arc<dyn SseRetryPolicy>,
+	metrics: Arc<metrics::Metrics>,
+	server: String,
+	target: String,
+}
+
+impl SseRetryPolicy for ReconnectPolicy {
+	fn retry(&self, current_times: usize) -> Option<Duration> {
+		let attempt = u32::try_from(current_times).unwrap_or(u32::MAX);
+		let backoff = match &self.policy {
+			Some(policy) => policy.backoff_for(attempt),
+			None => self.fallback.retry(current_times),
+		};
+		let Some(backoff) = backoff else {
+			warn!(
+				"giving up reconnecting sse target {} after {} attempts",
+				self.target, current_times
+			);
+			return None;
+		};
+		self.metrics.record(
+			metrics::SseReconnect {
+				server: self.server.clone(),
+				target: self.target.clone(),
+			},
+			(),
+		);
+		debug!(
+			"reconnecting sse target {} in {:?} (attempt {})",
+			self.target,
+			backoff,
+			attempt + 1
+		);
+		Some(backoff)
+	}
+}
+
 impl SseClient for ClientWrapper {
 	type Error = HttpError;
 
@@ -629,6 +1386,9 @@ impl SseClient for ClientWrapper {
 						.with_kind(SpanKind::Client)
 						.start_with_context(tracer, &rq_ctx.context);
 					trcng::add_context_to_request(req.headers_mut(), &rq_ctx.context);
+					if let Some(id) = &rq_ctx.request_id {
+						id.insert_header(req.headers_mut());
+					}
 				},
 				None => {
 					trace!("No RqCtx found in extensions");
@@ -644,6 +1404,7 @@ impl SseClient for ClientWrapper {
 				req,
 				target,
 				transport: self.policies.backend_tls.clone().into(),
+				proxy: self.policies.outbound_proxy.clone(),
 			})
 			.await
 			.map_err(|e| SseTransportError::Client(HttpError::new(e)))
@@ -692,6 +1453,7 @@ impl SseClient for ClientWrapper {
 				req,
 				target,
 				transport: self.policies.backend_tls.clone().into(),
+				proxy: self.policies.outbound_proxy.clone(),
 			})
 			.await
 			.map_err(|e| SseTransportError::Client(HttpError::new(e)))
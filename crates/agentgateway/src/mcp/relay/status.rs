@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast;
+
+use crate::*;
+
+/// How many events a subscriber can fall behind before it misses the oldest ones.
+const EVENT_CAPACITY: usize = 256;
+
+/// The connection state of MCP targets across all sessions, by backend and target, recorded by the
+/// session pools so it can be reported by the admin API. Locks are only held to copy small records
+/// in or out, never across an await, so reading the state does not hold up requests.
+///
+/// Every change is also published as a [TargetEvent] to the subscribers, if any.
+#[derive(Debug)]
+pub struct Registry {
+	next_id: AtomicU64,
+	targets: RwLock<HashMap<(Strng, Strng), TargetState>>,
+	events: broadcast::Sender<TargetEvent>,
+}
+
+impl Default for Registry {
+	fn default() -> Self {
+		Self {
+			next_id: Default::default(),
+			targets: Default::default(),
+			events: broadcast::channel(EVENT_CAPACITY).0,
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+struct TargetState {
+	connections: HashMap<u64, ConnectionStatus>,
+	last_error: Option<ErrorStatus>,
+	last_exit: Option<ExitStatus>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetStatus {
+	pub backend: Strng,
+	pub name: Strng,
+	/// Whether any session currently holds a connection to the target.
+	pub connected: bool,
+	pub connections: Vec<ConnectionStatus>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_error: Option<ErrorStatus>,
+	/// The last time the process of a stdio target exited.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_exit: Option<ExitStatus>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionStatus {
+	pub connected_since: DateTime<Utc>,
+	/// The process id, for stdio targets.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub pid: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorStatus {
+	pub message: String,
+	pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExitStatus {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub pid: Option<u32>,
+	pub status: String,
+	pub at: DateTime<Utc>,
+}
+
+/// A change in the connection state of a target.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetEvent {
+	#[serde(rename = "type")]
+	pub kind: TargetEventKind,
+	pub target: Strng,
+	pub backend: Strng,
+	/// The process id, for stdio targets.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub pid: Option<u32>,
+	/// The error, or the exit status of the process.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub message: Option<String>,
+	pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TargetEventKind {
+	/// A session connected to the target.
+	Connected,
+	/// A session closed its connection to the target.
+	Disconnected,
+	/// Connecting or calling the target failed.
+	Error,
+	/// The process of a stdio target exited.
+	Exited,
+}
+
+impl TargetEventKind {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			TargetEventKind::Connected => "connected",
+			TargetEventKind::Disconnected => "disconnected",
+			TargetEventKind::Error => "error",
+			TargetEventKind::Exited => "exited",
+		}
+	}
+}
+
+impl Registry {
+	/// Subscribes to the events of all targets, from now on. A subscriber that falls too far behind
+	/// misses the oldest events rather than holding up the others.
+	pub fn subscribe(&self) -> broadcast::Receiver<TargetEvent> {
+		self.events.subscribe()
+	}
+
+	/// Records a new connection to a target. The connection is reported until the returned handle is
+	/// dropped.
+	pub fn connected(
+		self: &Arc<Self>,
+		backend: Strng,
+		target: Strng,
+		pid: Option<u32>,
+	) -> Connection {
+		let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+		let at = Utc::now();
+		self.with_target(&backend, &target, |t| {
+			t.connections.insert(
+				id,
+				ConnectionStatus {
+					connected_since: at,
+					pid,
+				},
+			);
+		});
+		self.publish(TargetEvent {
+			kind: TargetEventKind::Connected,
+			target: target.clone(),
+			backend: backend.clone(),
+			pid,
+			message: None,
+			at,
+		});
+		Connection {
+			registry: self.clone(),
+			backend,
+			target,
+			id,
+		}
+	}
+
+	pub fn error(&self, backend: Strng, target: &Strng, message: String) {
+		let at = Utc::now();
+		self.with_target(&backend, target, |t| {
+			t.last_error = Some(ErrorStatus {
+				message: message.clone(),
+				at,
+			})
+		});
+		self.publish(TargetEvent {
+			kind: TargetEventKind::Error,
+			target: target.clone(),
+			backend,
+			pid: None,
+			message: Some(message),
+			at,
+		});
+	}
+
+	/// Returns the state of a target of a backend, or None if no session tried to connect to it yet.
+	pub fn status(&self, backend: &str, target: &str) -> Option<TargetStatus> {
+		let targets = self.targets.read().expect("mutex acquired");
+		let t = targets.get(&(backend.into(), target.into()))?;
+		let mut connections = t.connections.values().cloned().collect::<Vec<_>>();
+		connections.sort_by_key(|c| c.connected_since);
+		Some(TargetStatus {
+			backend: backend.into(),
+			name: target.into(),
+			connected: !connections.is_empty(),
+			connections,
+			last_error: t.last_error.clone(),
+			last_exit: t.last_exit.clone(),
+		})
+	}
+
+	fn with_target<T>(
+		&self,
+		backend: &Strng,
+		target: &Strng,
+		f: impl FnOnce(&mut TargetState) -> T,
+	) -> T {
+		let mut targets = self.targets.write().expect("mutex acquired");
+		f(targets
+			.entry((backend.clone(), target.clone()))
+			.or_default())
+	}
+
+	fn publish(&self, event: TargetEvent) {
+		// Sending only fails if there are no subscribers
+		let _ = self.events.send(event);
+	}
+}
+
+/// A connection recorded in the registry, removed when dropped.
+#[derive(Debug)]
+pub struct Connection {
+	registry: Arc<Registry>,
+	backend: Strng,
+	target: Strng,
+	id: u64,
+}
+
+impl Connection {
+	/// Records that the process behind the connection exited. The connection is no longer reported.
+	pub fn exited(&self, status: String) {
+		let exit = self.registry.with_target(&self.backend, &self.target, |t| {
+			let c = t.connections.remove(&self.id)?;
+			let exit = ExitStatus {
+				pid: c.pid,
+				status,
+				at: Utc::now(),
+			};
+			t.last_exit = Some(exit.clone());
+			Some(exit)
+		});
+		if let Some(exit) = exit {
+			self.registry.publish(TargetEvent {
+				kind: TargetEventKind::Exited,
+				target: self.target.clone(),
+				backend: self.backend.clone(),
+				pid: exit.pid,
+				message: Some(exit.status),
+				at: exit.at,
+			});
+		}
+	}
+}
+
+impl Drop for Connection {
+	fn drop(&mut self) {
+		let removed = self.registry.with_target(&self.backend, &self.target, |t| {
+			t.connections.remove(&self.id)
+		});
+		// A connection whose process exited was already reported
+		if let Some(c) = removed {
+			self.registry.publish(TargetEvent {
+				kind: TargetEventKind::Disconnected,
+				target: self.target.clone(),
+				backend: self.backend.clone(),
+				pid: c.pid,
+				message: None,
+				at: Utc::now(),
+			});
+		}
+	}
+}
+
+#[cfg(test)]
+#[path = "status_tests.rs"]
+mod tests;
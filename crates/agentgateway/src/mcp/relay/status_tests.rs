@@ -0,0 +1,47 @@
+use super::*;
+
+#[test]
+fn test_connection_lifecycle() {
+	let r = Arc::new(Registry::default());
+	let (backend, target) = (strng::new("backend"), strng::new("everything"));
+	assert!(r.status("backend", "everything").is_none());
+
+	r.error(backend.clone(), &target, "connection refused".to_string());
+	let s = r.status("backend", "everything").unwrap();
+	assert!(!s.connected);
+	assert_eq!(s.last_error.unwrap().message, "connection refused");
+
+	let a = r.connected(backend.clone(), target.clone(), Some(42));
+	let b = r.connected(backend.clone(), target.clone(), None);
+	let s = r.status("backend", "everything").unwrap();
+	assert!(s.connected);
+	assert_eq!(s.connections.len(), 2);
+	// A target of the same name in another backend is tracked separately
+	assert!(r.status("other", "everything").is_none());
+	let c = r.connected(strng::new("other"), target.clone(), None);
+	assert_eq!(
+		r.status("other", "everything").unwrap().connections.len(),
+		1
+	);
+	drop(c);
+	assert_eq!(
+		r.status("backend", "everything").unwrap().connections.len(),
+		2
+	);
+
+	a.exited("exit status: 1".to_string());
+	let s = r.status("backend", "everything").unwrap();
+	assert_eq!(s.connections.len(), 1);
+	let exit = s.last_exit.unwrap();
+	assert_eq!(exit.pid, Some(42));
+	assert_eq!(exit.status, "exit status: 1");
+
+	drop(a);
+	drop(b);
+	let s = serde_json::to_value(r.status("backend", "everything").unwrap()).unwrap();
+	assert_eq!(s["backend"], "backend");
+	assert_eq!(s["connected"], false);
+	assert_eq!(s["connections"], serde_json::json!([]));
+	assert_eq!(s["lastError"]["message"], "connection refused");
+	assert_eq!(s["lastExit"]["pid"], 42);
+}
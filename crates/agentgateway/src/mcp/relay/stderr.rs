@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use std::io::PipeWriter;
+use std::sync::Mutex;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::unix::pipe;
+use tokio::sync::watch;
+use tracing::Instrument;
+
+use crate::*;
+
+/// How many of the last lines written to stderr are kept to explain a failure.
+const RETAINED_LINES: usize = 20;
+
+/// The stderr of the process of a stdio target. Each line is logged as it is written, and the last
+/// few are kept so failures to connect to the process can say why it exited.
+///
+/// The pipe is read by its own task, so the process never blocks on a full pipe whatever the state
+/// of the connection.
+#[derive(Debug, Clone)]
+pub struct Stderr {
+	lines: Arc<Mutex<VecDeque<String>>>,
+	closed: watch::Receiver<bool>,
+}
+
+impl Stderr {
+	/// Starts capturing the stderr of a process, returning the pipe to pass to it as stderr.
+	pub fn capture(target: &Strng) -> std::io::Result<(Self, PipeWriter)> {
+		let (reader, writer) = std::io::pipe()?;
+		// Only the read end is made non-blocking; the process writes to the other as usual
+		let reader = pipe::Receiver::from_owned_fd(reader.into())?;
+		let lines = Arc::new(Mutex::new(VecDeque::with_capacity(RETAINED_LINES)));
+		let (tx, closed) = watch::channel(false);
+		let retained = lines.clone();
+		tokio::spawn(
+			async move {
+				read_lines(reader, &retained).await;
+				tx.send_replace(true);
+			}
+			.instrument(tracing::warn_span!("stdio", target = %target)),
+		);
+		Ok((Self { lines, closed }, writer))
+	}
+
+	/// The last lines written, oldest first.
+	pub fn lines(&self) -> Vec<String> {
+		self
+			.lines
+			.lock()
+			.expect("mutex acquired")
+			.iter()
+			.cloned()
+			.collect()
+	}
+
+	/// Waits up to `timeout` for the process to close stderr, which it does when it exits.
+	pub async fn closed(&self, timeout: Duration) {
+		let mut closed = self.closed.clone();
+		let _ = tokio::time::timeout(timeout, closed.wait_for(|c| *c)).await;
+	}
+
+	/// Adds the last lines read from stderr to an error from the process. They can be recovered with
+	/// `downcast_ref::<StderrLines>`.
+	pub fn explain(&self, e: anyhow::Error) -> anyhow::Error {
+		let lines = self.lines();
+		if lines.is_empty() {
+			return e;
+		}
+		e.context(StderrLines(lines))
+	}
+}
+
+/// The last lines a process wrote to stderr, attached to an error from it.
+#[derive(Debug, Clone)]
+pub struct StderrLines(pub Vec<String>);
+
+impl std::fmt::Display for StderrLines {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "process stderr:\n{}", self.0.join("\n"))
+	}
+}
+
+async fn read_lines(reader: pipe::Receiver, retained: &Mutex<VecDeque<String>>) {
+	let mut lines = BufReader::new(reader).split(b'\n');
+	while let Ok(Some(line)) = lines.next_segment().await {
+		let line = String::from_utf8_lossy(&line).trim_end().to_string();
+		if line.is_empty() {
+			continue;
+		}
+		warn!("stderr: {}", line);
+		let mut retained = retained.lock().expect("mutex acquired");
+		if retained.len() == RETAINED_LINES {
+			retained.pop_front();
+		}
+		retained.push_back(line);
+	}
+}
+
+#[cfg(test)]
+#[path = "stderr_tests.rs"]
+mod tests;
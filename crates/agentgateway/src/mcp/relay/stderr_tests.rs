@@ -0,0 +1,27 @@
+use super::*;
+
+#[tokio::test]
+async fn test_capture_stderr() {
+	let (stderr, writer) = Stderr::capture(&strng::new("everything")).unwrap();
+	let script = r#"for i in $(seq 1 30); do echo "line $i" >&2; done; exit 1"#;
+	let status = tokio::process::Command::new("/bin/sh")
+		.args(["-c", script])
+		.stderr(writer)
+		.status()
+		.await
+		.unwrap();
+	assert!(!status.success());
+
+	stderr.closed(Duration::from_secs(5)).await;
+	let expected = (11..=30).map(|i| format!("line {i}")).collect::<Vec<_>>();
+	assert_eq!(stderr.lines(), expected);
+
+	let e = stderr.explain(anyhow::anyhow!("connection closed"));
+	let message = format!("{e:#}");
+	assert!(
+		message.starts_with("process stderr:\nline 11\n"),
+		"{message}"
+	);
+	assert!(message.ends_with("line 30: connection closed"), "{message}");
+	assert_eq!(e.downcast_ref::<StderrLines>().unwrap().0, expected);
+}
@@ -0,0 +1,1042 @@
+use agent_core::strng;
+use serde_json::json;
+
+use super::*;
+
+fn tool(name: &str, schema: serde_json::Value) -> Tool {
+	Tool::new(
+		name.to_string(),
+		"",
+		Arc::new(schema.as_object().unwrap().clone()),
+	)
+}
+
+fn schema(description: &str) -> serde_json::Value {
+	json!({
+		"type": "object",
+		"description": description,
+		"properties": {
+			"description": {"type": "string", "description": description},
+			"city": {"type": "string"},
+		},
+	})
+}
+
+#[test]
+fn test_merge_tools() {
+	let (merged, rest) = merge_tools(
+		vec![
+			(
+				strng::new("a"),
+				vec![
+					tool("weather", schema("from a")),
+					tool("only_a", schema("")),
+				],
+			),
+			(strng::new("b"), vec![tool("weather", schema("from b"))]),
+		],
+		Some("b"),
+	);
+	assert_eq!(merged.len(), 1);
+	assert_eq!(merged[0].tool.name, "weather");
+	assert_eq!(
+		merged[0].targets,
+		vec![strng::new("b"), strng::new("a")],
+		"primary should be tried first"
+	);
+	assert!(!merged[0].idempotent);
+	assert_eq!(rest.len(), 1);
+	assert_eq!(rest[0].0, strng::new("a"));
+	assert_eq!(rest[0].1.name, "only_a");
+}
+
+#[test]
+fn test_add_merged_tools() {
+	let page = |targets: &[&str]| {
+		merge_tools(
+			targets
+				.iter()
+				.map(|t| {
+					let mut weather = tool("weather", schema(""));
+					weather.annotations = Some(ToolAnnotations {
+						idempotent_hint: Some(*t != "c"),
+						..Default::default()
+					});
+					(strng::new(t), vec![weather])
+				})
+				.collect(),
+			Some("c"),
+		)
+		.0
+	};
+	let mut routes = HashMap::new();
+	add_merged_tools(&mut routes, &page(&["a", "b"]), Some("c"));
+	// Listing the same page again changes nothing
+	add_merged_tools(&mut routes, &page(&["a", "b"]), Some("c"));
+	assert_eq!(
+		routes["weather"],
+		MergedRoute {
+			targets: vec![strng::new("a"), strng::new("b")],
+			idempotent: true,
+		}
+	);
+	// A later page adds its targets, the primary still first. Calls are only retried if every target
+	// annotates the tool as idempotent.
+	add_merged_tools(&mut routes, &page(&["b", "c"]), Some("c"));
+	assert_eq!(
+		routes["weather"],
+		MergedRoute {
+			targets: vec![strng::new("c"), strng::new("a"), strng::new("b")],
+			idempotent: false,
+		}
+	);
+}
+
+#[test]
+fn test_merge_tools_incompatible_schema() {
+	let mut other = schema("");
+	other["properties"]["city"]["type"] = json!("integer");
+	let (merged, rest) = merge_tools(
+		vec![
+			(strng::new("a"), vec![tool("weather", schema(""))]),
+			(strng::new("b"), vec![tool("weather", other)]),
+		],
+		None,
+	);
+	assert!(merged.is_empty());
+	assert_eq!(rest.len(), 2);
+}
+
+#[test]
+fn test_schema_shape_keeps_property_names() {
+	let shape = schema_shape(&schema("docs"), false);
+	assert_eq!(
+		shape,
+		json!({
+			"type": "object",
+			"properties": {
+				"description": {"type": "string"},
+				"city": {"type": "string"},
+			},
+		})
+	);
+}
+
+#[test]
+fn test_merge_capabilities() {
+	let tools_only = ServerCapabilities {
+		tools: Some(ToolsCapability::default()),
+		..Default::default()
+	};
+	let merged = merge_capabilities([tools_only.clone()]);
+	assert!(merged.tools.is_some());
+	assert!(merged.prompts.is_none());
+	assert!(merged.resources.is_none());
+
+	let prompts = ServerCapabilities {
+		prompts: Some(PromptsCapability::default()),
+		// Not something we relay, so never advertised
+		experimental: Some(Default::default()),
+		..Default::default()
+	};
+	let merged = merge_capabilities([tools_only, prompts]);
+	assert!(merged.tools.is_some());
+	assert!(merged.prompts.is_some());
+	assert!(merged.resources.is_none());
+	assert!(merged.experimental.is_none());
+}
+
+#[test]
+fn test_capabilities_from_config() {
+	let caps = capabilities_from_config(&[McpCapability::Resources]);
+	assert!(caps.resources.is_some());
+	assert!(caps.tools.is_none());
+	assert!(caps.prompts.is_none());
+}
+
+#[test]
+fn test_merge_capabilities_completions_and_logging() {
+	let completions = ServerCapabilities {
+		completions: Some(Default::default()),
+		..Default::default()
+	};
+	let logging = ServerCapabilities {
+		logging: Some(Default::default()),
+		..Default::default()
+	};
+	let merged = merge_capabilities([completions, logging]);
+	assert!(merged.completions.is_some());
+	assert!(merged.logging.is_some());
+	assert!(
+		merge_capabilities([ServerCapabilities::default()])
+			.completions
+			.is_none()
+	);
+}
+
+#[test]
+fn test_merge_capabilities_subscribe() {
+	let plain = ServerCapabilities {
+		resources: Some(ResourcesCapability::default()),
+		..Default::default()
+	};
+	let merged = merge_capabilities([plain.clone()]);
+	assert_eq!(merged.resources.unwrap().subscribe, None);
+
+	let subscribable = ServerCapabilities {
+		resources: Some(ResourcesCapability {
+			subscribe: Some(true),
+			list_changed: None,
+		}),
+		..Default::default()
+	};
+	let merged = merge_capabilities([plain, subscribable]);
+	assert_eq!(merged.resources.unwrap().subscribe, Some(true));
+}
+
+fn group_backend(weights: &[(&str, u32)]) -> McpBackendGroup {
+	let cb = crate::types::agent::McpCircuitBreaker {
+		consecutive_failures: Some(1),
+		failure_ratio: None,
+		window: None,
+		cooldown: Some(Duration::from_secs(60)),
+	};
+	let targets = weights
+		.iter()
+		.map(|(name, _)| {
+			Arc::new(crate::mcp::sse::McpTarget {
+				name: strng::new(name),
+				spec: crate::types::agent::McpTargetSpec::Stdio {
+					cmd: "true".to_string(),
+					args: vec![],
+					env: Default::default(),
+					env_inherit: None,
+					cwd: None,
+					identity_env: Default::default(),
+					restart: None,
+				},
+				filters: vec![],
+				backend_policies: Default::default(),
+				circuit_breaker: Some(Arc::new(breaker::CircuitBreaker::new(cb.clone()))),
+				concurrency_limit: None,
+				client: None,
+			})
+		})
+		.collect();
+	let members = weights
+		.iter()
+		.map(|(name, weight)| (strng::new(name), *weight))
+		.collect();
+	McpBackendGroup {
+		name: strng::new("backend"),
+		targets,
+		groups: HashMap::from([(
+			strng::new("replicas"),
+			Arc::new(balancer::Balancer::new(members)),
+		)]),
+		server_info: Default::default(),
+		tool_merge: None,
+		logging: None,
+		status: Default::default(),
+		inflight: Default::default(),
+		stdio_command_allowlist: Arc::from(["true".to_string()]),
+		audit: None,
+		read_only: None,
+		list_deadline: None,
+	}
+}
+
+#[test]
+fn test_uri_prefix() {
+	// Members of a group are prefixed with its name
+	let backend = group_backend(&[("a", 1), ("b", 1)]);
+	assert_eq!(
+		pool::uri_prefix(&backend, &strng::new("a")).as_deref(),
+		None
+	);
+	let mut targets = backend.targets.clone();
+	targets.push(stdio_backend(&["c"], std::path::Path::new("/"), None).targets[0].clone());
+	let backend = McpBackendGroup { targets, ..backend };
+	assert_eq!(
+		pool::uri_prefix(&backend, &strng::new("a")).as_deref(),
+		Some("replicas_")
+	);
+	assert_eq!(
+		pool::uri_prefix(&backend, &strng::new("c")).as_deref(),
+		Some("c_")
+	);
+}
+
+#[tokio::test]
+async fn test_target_group_calls() {
+	let backend = group_backend(&[("a", 2), ("b", 1)]);
+	// Picks the target as a tool call does, then acquires its breaker
+	let acquire = |name: &str| {
+		let target = backend.pick(name, &HashSet::new())?;
+		let breaker = backend.circuit_breaker(&target).unwrap();
+		breaker.try_acquire().then_some((target, breaker))
+	};
+	let mut picked = vec![];
+	for _ in 0..6 {
+		let (target, breaker) = acquire("replicas").unwrap();
+		breaker.record(true);
+		picked.push(target);
+	}
+	assert_eq!(picked, vec!["a", "b", "a", "a", "b", "a"]);
+
+	// Members whose circuit breaker is open are skipped
+	let (target, breaker) = acquire("replicas").unwrap();
+	assert_eq!(target, "a");
+	breaker.record(false);
+	for _ in 0..3 {
+		let (target, breaker) = acquire("replicas").unwrap();
+		assert_eq!(target, "b");
+		breaker.record(true);
+	}
+
+	// Once every member is open, calls are rejected
+	let (_, breaker) = acquire("replicas").unwrap();
+	breaker.record(false);
+	assert!(acquire("replicas").is_none());
+
+	// Members can still be called by their own name, subject to their own breaker
+	assert!(acquire("a").is_none());
+}
+
+#[tokio::test]
+async fn test_target_group_lost_race() {
+	let backend = group_backend(&[("a", 1), ("b", 1)]);
+	let cb = crate::types::agent::McpCircuitBreaker {
+		consecutive_failures: Some(1),
+		failure_ratio: None,
+		window: None,
+		cooldown: Some(Duration::from_millis(10)),
+	};
+	let targets = backend
+		.targets
+		.iter()
+		.map(|t| {
+			Arc::new(crate::mcp::sse::McpTarget {
+				name: t.name.clone(),
+				spec: t.spec.clone(),
+				filters: vec![],
+				backend_policies: Default::default(),
+				circuit_breaker: Some(Arc::new(breaker::CircuitBreaker::new(cb.clone()))),
+				concurrency_limit: None,
+				client: None,
+			})
+		})
+		.collect();
+	let backend = McpBackendGroup { targets, ..backend };
+	// Once its cooldown elapses, a is picked for a probe call
+	let a = backend.circuit_breaker("a").unwrap();
+	assert!(a.try_acquire());
+	a.record(false);
+	tokio::time::sleep(Duration::from_millis(20)).await;
+	assert!(a.available());
+
+	// A concurrent call takes the probe between the pick and the acquire, so the call fails over
+	let (target, _, ()) = backend
+		.acquire("replicas", async |target: &Strng| {
+			if target == "a" {
+				assert!(a.try_acquire());
+			}
+			Ok::<_, ()>(())
+		})
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(target, "b");
+}
+
+/// A minimal MCP server for stdio targets, run with the target name and a directory. It offers a
+/// single tool, named after the target unless `<dir>/<name>.tool` names it, whose calls return the
+/// target name. It writes its pid to `<dir>/<name>.pid`, and exits if `<dir>/<name>.fail` exists.
+/// If `<dir>/<name>.logging` exists it supports logging, writing the level it is set to to
+/// `<dir>/<name>.level`. If `<dir>/<name>.idempotent` exists the tool is annotated as idempotent,
+/// and if `<dir>/<name>.error` exists its calls fail, counted in `<dir>/<name>.calls`.
+const STDIO_SERVER: &str = r#"
+[ -e "$2/$1.fail" ] && { echo "$1 failed to start" >&2; exit 1; }
+[ -e "$2/$1.slow" ] && sleep 5
+echo $$ > "$2/$1.pid"
+tool=$(cat "$2/$1.tool" 2>/dev/null || echo "$1")
+annotations='{}'
+[ -e "$2/$1.idempotent" ] && annotations='{"idempotentHint":true}'
+caps='{"tools":{}}'
+[ -e "$2/$1.logging" ] && caps='{"tools":{},"logging":{}}'
+while read -r line; do
+	id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+	[ -z "$id" ] && continue
+	case "$line" in
+	*'"method":"initialize"'*)
+		r='{"protocolVersion":"2025-03-26","capabilities":'"$caps"',"serverInfo":{"name":"sh","version":"1.0"}}' ;;
+	*'"method":"tools/list"'*)
+		r='{"tools":[{"name":"'"$tool"'","inputSchema":{"type":"object"},"annotations":'"$annotations"'}]}' ;;
+	*'"method":"tools/call"'*)
+		if [ -e "$2/$1.error" ]; then
+			echo >> "$2/$1.calls"
+			printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32603,"message":"%s failed"}}\n' "$id" "$1"
+			continue
+		fi
+		r='{"content":[{"type":"text","text":"'"$1"'"}]}' ;;
+	*'"method":"logging/setLevel"'*)
+		printf '%s' "$line" | sed -n 's/.*"level":"\([a-z]*\)".*/\1/p' > "$2/$1.level"
+		r='{}' ;;
+	*)
+		printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32601,"message":"unsupported"}}\n' "$id"
+		continue ;;
+	esac
+	printf '{"jsonrpc":"2.0","id":%s,"result":%s}\n' "$id" "$r"
+done
+"#;
+
+fn stdio_backend(
+	names: &[&str],
+	dir: &std::path::Path,
+	restart: Option<crate::types::agent::StdioRestartPolicy>,
+) -> McpBackendGroup {
+	let targets = names
+		.iter()
+		.map(|name| {
+			Arc::new(crate::mcp::sse::McpTarget {
+				name: strng::new(name),
+				spec: crate::types::agent::McpTargetSpec::Stdio {
+					cmd: "/bin/sh".to_string(),
+					args: vec![
+						"-c".to_string(),
+						STDIO_SERVER.to_string(),
+						"sh".to_string(),
+						name.to_string(),
+						dir.display().to_string(),
+					],
+					env: Default::default(),
+					env_inherit: None,
+					cwd: None,
+					identity_env: Default::default(),
+					restart: restart.clone(),
+				},
+				filters: vec![],
+				backend_policies: Default::default(),
+				circuit_breaker: None,
+				concurrency_limit: None,
+				client: None,
+			})
+		})
+		.collect();
+	McpBackendGroup {
+		name: strng::new("backend"),
+		targets,
+		groups: HashMap::new(),
+		server_info: Default::default(),
+		tool_merge: None,
+		logging: None,
+		status: Default::default(),
+		inflight: Default::default(),
+		stdio_command_allowlist: Arc::from(["/bin/sh".to_string()]),
+		audit: None,
+		read_only: None,
+		list_deadline: None,
+	}
+}
+
+/// Serves a relay over an in-memory stream, returning the client connected to it.
+async fn serve_relay(
+	backend: McpBackendGroup,
+	registry: &mut prometheus_client::registry::Registry,
+	idle_timeout: Option<Duration>,
+) -> RunningService<RoleClient, ()> {
+	serve_relay_with_policies(backend, registry, idle_timeout, RuleSets::from(vec![])).await
+}
+
+async fn serve_relay_with_policies(
+	backend: McpBackendGroup,
+	registry: &mut prometheus_client::registry::Registry,
+	idle_timeout: Option<Duration>,
+	policies: RuleSets,
+) -> RunningService<RoleClient, ()> {
+	use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+	use rmcp::ServiceExt;
+	let client = client::Client::new(
+		&client::Config {
+			resolver_cfg: ResolverConfig::default(),
+			resolver_opts: ResolverOpts::default(),
+		},
+		None,
+	);
+	let metrics = Arc::new(metrics::Metrics::new(registry, None));
+	let relay = Relay::new(backend, metrics, policies, client, idle_timeout);
+	let (server, client) = tokio::io::duplex(64 * 1024);
+	tokio::spawn(async move {
+		if let Ok(running) = relay.serve(server).await {
+			let _ = running.waiting().await;
+		}
+	});
+	().serve(client).await.unwrap()
+}
+
+async fn tool_names(client: &RunningService<RoleClient, ()>) -> Vec<String> {
+	let tools = client.list_tools(None).await.unwrap().tools;
+	tools
+		.into_iter()
+		.map(|t| t.name.to_string())
+		.sorted()
+		.collect()
+}
+
+fn metric(registry: &prometheus_client::registry::Registry, prefix: &str) -> u64 {
+	let mut encoded = String::new();
+	prometheus_client::encoding::text::encode(&mut encoded, registry).unwrap();
+	encoded
+		.lines()
+		.filter(|l| l.starts_with(prefix))
+		.filter_map(|l| l.rsplit(' ').next()?.parse::<u64>().ok())
+		.sum()
+}
+
+#[tokio::test]
+async fn test_pool_evicts_idle_targets() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut registry = prometheus_client::registry::Registry::default();
+	let backend = stdio_backend(&["a", "b"], dir.path(), None);
+	let client = serve_relay(backend, &mut registry, Some(Duration::from_millis(50))).await;
+	assert_eq!(tool_names(&client).await, vec!["a_a", "b_b"]);
+	assert_eq!(metric(&registry, "pool_evictions_total"), 0);
+
+	// Idle connections are dropped, and reconnected when next used
+	tokio::time::sleep(Duration::from_millis(100)).await;
+	assert_eq!(tool_names(&client).await, vec!["a_a", "b_b"]);
+	assert_eq!(metric(&registry, "pool_evictions_total"), 2);
+
+	// A target that cannot be reconnected is left out rather than failing the listing
+	std::fs::write(dir.path().join("b.fail"), "").unwrap();
+	tokio::time::sleep(Duration::from_millis(100)).await;
+	assert_eq!(tool_names(&client).await, vec!["a_a"]);
+	assert_eq!(metric(&registry, "pool_evictions_total"), 4);
+	client.cancel().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_target_names_with_delimiter() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut registry = prometheus_client::registry::Registry::default();
+	let backend = stdio_backend(&["a", "a_b"], dir.path(), None);
+	let client = serve_relay(backend, &mut registry, None).await;
+	assert_eq!(tool_names(&client).await, vec!["a_a", "a_b_a_b"]);
+	// Each tool is routed to the target with the longest matching name
+	for (tool, target) in [("a_a", "a"), ("a_b_a_b", "a_b")] {
+		let result = client
+			.call_tool(CallToolRequestParam {
+				name: tool.into(),
+				arguments: None,
+			})
+			.await
+			.unwrap();
+		assert_eq!(result.content[0].as_text().unwrap().text, target);
+	}
+	client.cancel().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_list_deadline_covers_reconnects() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut registry = prometheus_client::registry::Registry::default();
+	let mut backend = stdio_backend(&["a", "b"], dir.path(), None);
+	backend.list_deadline = Some(McpListDeadline {
+		timeout: Duration::from_millis(300),
+		header: None,
+	});
+	let client = serve_relay(backend, &mut registry, Some(Duration::from_millis(50))).await;
+	assert_eq!(tool_names(&client).await, vec!["a_a", "b_b"]);
+
+	// Once evicted, a target that is slow to reconnect is left out by the deadline. Reconnecting
+	// counts against it, so the list still answers in time.
+	std::fs::write(dir.path().join("b.slow"), "").unwrap();
+	tokio::time::sleep(Duration::from_millis(100)).await;
+	let start = Instant::now();
+	assert!(!tool_names(&client).await.contains(&"b_b".to_string()));
+	assert!(
+		start.elapsed() < Duration::from_secs(2),
+		"{:?}",
+		start.elapsed()
+	);
+	assert!(metric(&registry, "list_timeouts_total") >= 1);
+	client.cancel().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_connect_failure_cause() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut registry = prometheus_client::registry::Registry::default();
+	let backend = stdio_backend(&["a"], dir.path(), None);
+	let client = serve_relay(backend, &mut registry, Some(Duration::from_millis(50))).await;
+	assert_eq!(tool_names(&client).await, vec!["a"]);
+
+	// Once evicted, the target fails to reconnect, and the client is told what it wrote to stderr
+	std::fs::write(dir.path().join("a.fail"), "").unwrap();
+	tokio::time::sleep(Duration::from_millis(100)).await;
+	let err = client
+		.call_tool(CallToolRequestParam {
+			name: "a".into(),
+			arguments: None,
+		})
+		.await
+		.unwrap_err();
+	let rmcp::ServiceError::McpError(err) = err else {
+		panic!("expected an MCP error, got {err:?}");
+	};
+	assert_eq!(err.message, "Service a not found");
+	let data = err.data.unwrap();
+	assert_eq!(data["stderr"], serde_json::json!(["a failed to start"]));
+	assert!(!data["cause"].as_str().unwrap().is_empty(), "{data}");
+	client.cancel().await.unwrap();
+}
+
+#[derive(Debug, Default)]
+struct AuditRecords(std::sync::Mutex<Vec<audit::Record>>);
+
+impl audit::Sink for AuditRecords {
+	fn write(&self, record: &audit::Record) {
+		self.0.lock().unwrap().push(record.clone());
+	}
+}
+
+#[tokio::test]
+async fn test_audit_call_tool() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut registry = prometheus_client::registry::Registry::default();
+	let records = Arc::new(AuditRecords::default());
+	let config = crate::types::agent::McpAuditLog {
+		sink: crate::types::agent::AuditSink::Stdout,
+		arguments: crate::types::agent::AuditArguments::None,
+		hash_key: None,
+		redact: vec![],
+	};
+	let mut backend = stdio_backend(&["a"], dir.path(), None);
+	backend.audit = Some(Arc::new(audit::AuditLog::new(
+		backend.name.clone(),
+		config,
+		records.clone(),
+	)));
+	let mut policies = cedar_policy::PolicySet::new();
+	let permit = r#"permit(principal, action == Action::"call_tool", resource == Tool::"a");"#;
+	policies
+		.add(cedar_policy::Policy::parse(None, permit).unwrap())
+		.unwrap();
+	let policies = RuleSets::from(vec![rbac::RuleSet::new(policies)]);
+	let client = serve_relay_with_policies(backend, &mut registry, None, policies).await;
+
+	let call = |name: &'static str| CallToolRequestParam {
+		name: name.into(),
+		arguments: None,
+	};
+	client.call_tool(call("a")).await.unwrap();
+	client.call_tool(call("b")).await.unwrap_err();
+	let outcomes = records
+		.0
+		.lock()
+		.unwrap()
+		.iter()
+		.map(|r| (r.tool.clone(), r.outcome))
+		.collect_vec();
+	assert_eq!(
+		outcomes,
+		vec![
+			("a".to_string(), audit::Outcome::Ok),
+			("b".to_string(), audit::Outcome::Denied),
+		]
+	);
+	client.cancel().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_stdio_target_restarts() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut registry = prometheus_client::registry::Registry::default();
+	let restart = crate::types::agent::StdioRestartPolicy {
+		max_restarts: 1,
+		backoff: Some(Duration::from_millis(10)),
+	};
+	let backend = stdio_backend(&["a"], dir.path(), Some(restart));
+	let client = serve_relay(backend, &mut registry, None).await;
+	assert_eq!(tool_names(&client).await, vec!["a"]);
+	let pid_file = dir.path().join("a.pid");
+	let pid = std::fs::read_to_string(&pid_file).unwrap();
+
+	let killed = std::process::Command::new("kill")
+		.args(["-9", pid.trim()])
+		.status()
+		.unwrap();
+	assert!(killed.success());
+	// The exit is noticed once the transport closes; until then calls fail
+	let mut restarted = false;
+	for _ in 0..100 {
+		if tool_names(&client).await == vec!["a"] && std::fs::read_to_string(&pid_file).unwrap() != pid
+		{
+			restarted = true;
+			break;
+		}
+		tokio::time::sleep(Duration::from_millis(20)).await;
+	}
+	assert!(restarted, "stdio target was not restarted");
+	assert_eq!(metric(&registry, "stdio_restarts_total"), 1);
+	client.cancel().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_set_level() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut registry = prometheus_client::registry::Registry::default();
+	std::fs::write(dir.path().join("a.logging"), "").unwrap();
+	let backend = stdio_backend(&["a", "b"], dir.path(), None);
+	let client = serve_relay(backend, &mut registry, None).await;
+	assert!(client.peer_info().unwrap().capabilities.logging.is_some());
+
+	// The level is forwarded only to targets that support logging
+	client
+		.set_level(SetLevelRequestParam {
+			level: LoggingLevel::Warning,
+		})
+		.await
+		.unwrap();
+	assert_eq!(
+		std::fs::read_to_string(dir.path().join("a.level")).unwrap(),
+		"warning"
+	);
+	assert!(!dir.path().join("b.level").exists());
+	client.cancel().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_call_log_redacts_arguments() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut registry = prometheus_client::registry::Registry::default();
+	let mut backend = stdio_backend(&["a"], dir.path(), None);
+	backend.logging = Some(crate::types::agent::PayloadLogging {
+		include_bodies: true,
+		redact: vec!["arguments.password".to_string()],
+	});
+	// Relay tasks run on the test thread, so its subscriber sees their events
+	let logs: &'static std::sync::Mutex<Vec<u8>> = Box::leak(Default::default());
+	let subscriber = tracing_subscriber::fmt()
+		.with_writer(agent_core::telemetry::testing::MockWriter::new(logs))
+		.with_ansi(false)
+		.finish();
+	let _guard = tracing::subscriber::set_default(subscriber);
+	let client = serve_relay(backend, &mut registry, None).await;
+	client
+		.call_tool(CallToolRequestParam {
+			name: "a".into(),
+			arguments: json!({ "user": "alice", "password": "hunter2" })
+				.as_object()
+				.cloned(),
+		})
+		.await
+		.unwrap();
+	client.cancel().await.unwrap();
+
+	let logs = String::from_utf8(logs.lock().unwrap().clone()).unwrap();
+	let call = logs
+		.lines()
+		.find(|l| l.contains("mcp call"))
+		.expect("call is logged");
+	assert!(call.contains("alice"), "{call}");
+	assert!(call.contains("[REDACTED]"), "{call}");
+	assert!(!call.contains("hunter2"), "{call}");
+}
+
+#[tokio::test]
+async fn test_server_info() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut registry = prometheus_client::registry::Registry::default();
+
+	// By default the client's protocol version is accepted, and the targets are described
+	let backend = stdio_backend(&["a", "b"], dir.path(), None);
+	let client = serve_relay(backend, &mut registry, None).await;
+	let info = client.peer_info().unwrap();
+	assert_eq!(info.protocol_version, ProtocolVersion::default());
+	let instructions = info.instructions.as_deref().unwrap();
+	assert!(instructions.contains("(a, b)"), "{instructions}");
+	client.cancel().await.unwrap();
+
+	let backend = McpBackendGroup {
+		server_info: serde_json::from_value(json!({
+			"name": "gateway",
+			"version": "1.2.3",
+			"instructions": "Use the tools.",
+			"protocolVersion": "2024-11-05",
+		}))
+		.unwrap(),
+		..stdio_backend(&["a"], dir.path(), None)
+	};
+	let client = serve_relay(backend, &mut registry, None).await;
+	let info = client.peer_info().unwrap();
+	assert_eq!(info.protocol_version, ProtocolVersion::V_2024_11_05);
+	assert_eq!(info.server_info.name, "gateway");
+	assert_eq!(info.server_info.version, "1.2.3");
+	assert_eq!(info.instructions.as_deref(), Some("Use the tools."));
+	client.cancel().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_merged_tools_routed_on_connect() {
+	let dir = tempfile::tempdir().unwrap();
+	for name in ["a", "b"] {
+		std::fs::write(dir.path().join(format!("{name}.tool")), "weather").unwrap();
+	}
+	let mut registry = prometheus_client::registry::Registry::default();
+	let backend = McpBackendGroup {
+		tool_merge: Some(McpToolMerge {
+			primary: Some("b".to_string()),
+		}),
+		..stdio_backend(&["a", "b"], dir.path(), None)
+	};
+	let client = serve_relay(backend, &mut registry, None).await;
+	// The merged tool can be called without listing tools first
+	let result = client
+		.call_tool(CallToolRequestParam {
+			name: "weather".into(),
+			arguments: None,
+		})
+		.await
+		.unwrap();
+	assert_eq!(result.content[0].as_text().unwrap().text, "b");
+	assert_eq!(tool_names(&client).await, vec!["weather"]);
+	client.cancel().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_merged_tools_failover() {
+	let dir = tempfile::tempdir().unwrap();
+	for name in ["a", "b"] {
+		std::fs::write(dir.path().join(format!("{name}.tool")), "weather").unwrap();
+	}
+	std::fs::write(dir.path().join("a.error"), "").unwrap();
+	let call = async |dir: &std::path::Path| {
+		let mut registry = prometheus_client::registry::Registry::default();
+		let backend = McpBackendGroup {
+			tool_merge: Some(McpToolMerge {
+				primary: Some("a".to_string()),
+			}),
+			..stdio_backend(&["a", "b"], dir, None)
+		};
+		let client = serve_relay(backend, &mut registry, None).await;
+		let result = client
+			.call_tool(CallToolRequestParam {
+				name: "weather".into(),
+				arguments: None,
+			})
+			.await;
+		client.cancel().await.unwrap();
+		result
+	};
+
+	// The call reached the primary, which may have acted on it, so it is not sent to the other target
+	let err = call(dir.path()).await.unwrap_err();
+	assert!(err.to_string().contains("a failed"), "{err}");
+	let calls = std::fs::read_to_string(dir.path().join("a.calls")).unwrap();
+	assert_eq!(calls.lines().count(), 1);
+
+	// Tools every target annotates as idempotent are retried
+	for name in ["a", "b"] {
+		std::fs::write(dir.path().join(format!("{name}.idempotent")), "").unwrap();
+	}
+	let result = call(dir.path()).await.unwrap();
+	assert_eq!(result.content[0].as_text().unwrap().text, "b");
+}
+
+#[tokio::test]
+async fn test_stdio_command() {
+	let dir = tempfile::tempdir().unwrap();
+	let cwd = dir.path().canonicalize().unwrap();
+	let args = vec![
+		"-c".to_string(),
+		r#"pwd; echo "$TOOL_MODE:${HOME:-unset}""#.to_string(),
+	];
+	let env = HashMap::from([("TOOL_MODE".to_string(), "strict".to_string())]);
+	let allowlist = ["/bin/sh".to_string()];
+	let run = |env_inherit| {
+		let mut c =
+			pool::stdio_command("/bin/sh", &args, &env, env_inherit, Some(&cwd), &allowlist).unwrap();
+		async move { String::from_utf8(c.output().await.unwrap().stdout).unwrap() }
+	};
+
+	// The environment of the gateway is inherited unless disabled
+	let home = std::env::var("HOME").unwrap_or_else(|_| "unset".to_string());
+	assert_eq!(
+		run(None).await,
+		format!("{}\nstrict:{home}\n", cwd.display())
+	);
+	assert_eq!(
+		run(Some(false)).await,
+		format!("{}\nstrict:unset\n", cwd.display())
+	);
+}
+
+#[test]
+fn test_stdio_command_allowlist() {
+	let env = HashMap::new();
+	let command = |cmd: &str, allowlist: &[&str]| {
+		let allowlist = allowlist.iter().map(|a| a.to_string()).collect_vec();
+		pool::stdio_command(cmd, &[], &env, None, None, &allowlist).map(|_| ())
+	};
+	assert!(command("/usr/bin/uvx", &["/usr/bin/uvx", "npx"]).is_ok());
+	assert!(command("npx", &["/usr/bin/uvx", "npx"]).is_ok());
+	// Entries must match exactly, so a name does not allow the same name elsewhere
+	let err = command("/tmp/npx", &["/usr/bin/uvx", "npx"]).unwrap_err();
+	assert_eq!(
+		err.to_string(),
+		"command '/tmp/npx' is not in the stdio command allowlist"
+	);
+	assert!(command("uvx", &["/usr/bin/uvx"]).is_err());
+	// Nothing is allowed by default, and `*` must be set to allow any command
+	let err = command("npx", &[]).unwrap_err();
+	assert!(
+		err.to_string().contains("stdioCommandAllowlist is not set"),
+		"{err}"
+	);
+	assert!(command("npx", &["*"]).is_ok());
+	assert!(command("/tmp/npx", &["*"]).is_ok());
+
+	// A name is looked up in PATH, so the target may not set it
+	let path = HashMap::from([("PATH".to_string(), "/tmp".to_string())]);
+	let allowlist = ["npx".to_string(), "/usr/bin/uvx".to_string()];
+	assert!(pool::stdio_command("npx", &[], &path, None, None, &allowlist).is_err());
+	assert!(pool::stdio_command("/usr/bin/uvx", &[], &path, None, None, &allowlist).is_ok());
+	assert!(pool::stdio_command("npx", &[], &path, None, None, &["*".to_string()]).is_ok());
+}
+
+#[test]
+fn test_caller_env() {
+	let identity = Identity {
+		claims: Some(crate::http::jwt::Claims {
+			inner: json!({"sub": "alice", "org": {"id": "acme"}, "admin": true})
+				.as_object()
+				.unwrap()
+				.clone(),
+			jwt: Default::default(),
+		}),
+		connection_id: None,
+	};
+	let identity_env = HashMap::from([
+		("CALLER".to_string(), "sub".to_string()),
+		("CALLER_ORG".to_string(), "org.id".to_string()),
+		("CALLER_ADMIN".to_string(), "admin".to_string()),
+		("CALLER_EMAIL".to_string(), "email".to_string()),
+	]);
+	let env: HashMap<_, _> = pool::caller_env(&identity_env, &identity).collect();
+	assert_eq!(
+		env,
+		HashMap::from([
+			("CALLER".to_string(), "alice".to_string()),
+			("CALLER_ORG".to_string(), "acme".to_string()),
+		])
+	);
+	// Without authentication, none are set
+	assert_eq!(
+		pool::caller_env(&identity_env, &Identity::empty()).count(),
+		0
+	);
+}
+
+#[test]
+fn test_read_only_classification() {
+	let config = McpReadOnly {
+		read_only_tools: vec!["fs_stat".to_string()],
+		mutating_tools: vec!["fs_touch".to_string(), "fs_stat".to_string()],
+	};
+	// Listing a tool as mutating wins
+	assert_eq!(configured_read_only(&config, "fs_stat"), Some(false));
+	assert_eq!(configured_read_only(&config, "fs_touch"), Some(false));
+	let config = McpReadOnly {
+		read_only_tools: vec!["fs_stat".to_string()],
+		..Default::default()
+	};
+	assert_eq!(configured_read_only(&config, "fs_stat"), Some(true));
+	assert_eq!(configured_read_only(&config, "stat"), None);
+
+	let annotated = |read_only_hint| {
+		let mut t = tool("annotated", schema(""));
+		t.annotations = Some(ToolAnnotations {
+			read_only_hint,
+			..Default::default()
+		});
+		t
+	};
+	let mut read = annotated(Some(true));
+	read.name = "read".into();
+	let tools = annotated_read_only(&[
+		read,
+		annotated(Some(false)),
+		annotated(None),
+		tool("plain", schema("")),
+	]);
+	// Tools without annotations are assumed to change state
+	assert_eq!(tools, HashSet::from(["read".to_string()]));
+}
+
+#[test]
+fn test_list_timeout() {
+	let cfg = McpListDeadline {
+		timeout: Duration::from_secs(2),
+		header: Some("x-list-deadline-ms".to_string()),
+	};
+	let headers = |value: &str| {
+		let mut headers = HeaderMap::new();
+		headers.insert("x-list-deadline-ms", HeaderValue::from_str(value).unwrap());
+		headers
+	};
+	assert_eq!(list_timeout(&cfg, None), Duration::from_secs(2));
+	assert_eq!(
+		list_timeout(&cfg, Some(&headers("250"))),
+		Duration::from_millis(250)
+	);
+	// Clients may only shorten the deadline
+	assert_eq!(
+		list_timeout(&cfg, Some(&headers("5000"))),
+		Duration::from_secs(2)
+	);
+	assert_eq!(
+		list_timeout(&cfg, Some(&headers("soon"))),
+		Duration::from_secs(2)
+	);
+	let cfg = McpListDeadline {
+		header: None,
+		..cfg
+	};
+	assert_eq!(
+		list_timeout(&cfg, Some(&headers("250"))),
+		Duration::from_secs(2)
+	);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_join_until() {
+	struct Cancelled(Arc<std::sync::atomic::AtomicBool>);
+	impl Drop for Cancelled {
+		fn drop(&mut self) {
+			self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+		}
+	}
+	let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+	let request = |name: &str, delay: u64| {
+		let guard = Cancelled(cancelled.clone());
+		let name = strng::new(name);
+		(name.clone(), async move {
+			tokio::time::sleep(Duration::from_secs(delay)).await;
+			std::mem::forget(guard);
+			name
+		})
+	};
+
+	let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+	let (results, timed_out) =
+		join_until(Some(deadline), [request("fast", 1), request("slow", 60)]).await;
+	assert_eq!(results, vec![strng::new("fast")]);
+	assert_eq!(timed_out, vec![strng::new("slow")]);
+	// The slow request was dropped rather than left running
+	assert!(cancelled.load(std::sync::atomic::Ordering::SeqCst));
+
+	let (results, timed_out) = join_until(None, [request("slow", 60)]).await;
+	assert_eq!(results, vec![strng::new("slow")]);
+	assert!(timed_out.is_empty());
+}
@@ -5,9 +5,29 @@ use super::*;
 pub(crate) enum UpstreamError {
 	ServiceError(rmcp::ServiceError),
 	OpenAPIError(anyhow::Error),
+	InvalidArguments(ErrorData),
+}
+
+impl Display for UpstreamError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::ServiceError(e) => write!(f, "{e}"),
+			Self::OpenAPIError(e) => write!(f, "{e:#}"),
+			Self::InvalidArguments(e) => write!(f, "{}", e.message),
+		}
+	}
 }
 
 impl UpstreamError {
+	/// Whether the error means the target could not serve the call, rather than the target
+	/// rejecting it.
+	pub(crate) fn is_target_failure(&self) -> bool {
+		!matches!(
+			self,
+			Self::InvalidArguments(_) | Self::ServiceError(rmcp::ServiceError::McpError(_))
+		)
+	}
+
 	pub(crate) fn error_code(&self) -> String {
 		match self {
 			Self::ServiceError(e) => match e {
@@ -21,6 +41,7 @@ impl UpstreamError {
 				_ => "unknown".to_string(),
 			},
 			Self::OpenAPIError(_) => "openapi_error".to_string(),
+			Self::InvalidArguments(_) => "invalid_arguments".to_string(),
 		}
 	}
 }
@@ -40,6 +61,7 @@ impl From<UpstreamError> for ErrorData {
 	fn from(value: UpstreamError) -> Self {
 		match value {
 			UpstreamError::OpenAPIError(e) => ErrorData::internal_error(e.to_string(), None),
+			UpstreamError::InvalidArguments(e) => e,
 			UpstreamError::ServiceError(e) => match e {
 				rmcp::ServiceError::McpError(e) => e,
 				rmcp::ServiceError::Timeout { timeout } => {
@@ -106,6 +128,22 @@ impl Filter {
 	}
 }
 
+fn expect_empty(result: ServerResult) -> Result<(), UpstreamError> {
+	match result {
+		ServerResult::EmptyResult(_) => Ok(()),
+		_ => Err(UpstreamError::ServiceError(
+			rmcp::ServiceError::UnexpectedResponse,
+		)),
+	}
+}
+
+fn resources_unsupported() -> UpstreamError {
+	UpstreamError::InvalidArguments(ErrorData::invalid_request(
+		"target does not support resources",
+		None,
+	))
+}
+
 // UpstreamTarget defines a source for MCP information.
 pub(crate) struct UpstreamTarget {
 	pub(crate) filters: Vec<Filter>,
@@ -117,6 +155,20 @@ pub(crate) enum UpstreamTargetSpec {
 }
 
 impl UpstreamTarget {
+	/// The capabilities the target advertised when it was initialized.
+	pub(crate) fn capabilities(&self) -> ServerCapabilities {
+		match &self.spec {
+			UpstreamTargetSpec::Mcp(m) => m
+				.peer_info()
+				.map(|info| info.capabilities.clone())
+				.unwrap_or_default(),
+			UpstreamTargetSpec::OpenAPI(_) => ServerCapabilities {
+				tools: Some(ToolsCapability::default()),
+				..Default::default()
+			},
+		}
+	}
+
 	pub(crate) async fn list_tools(
 		&self,
 		request: Option<PaginatedRequestParam>,
@@ -166,6 +218,98 @@ impl UpstreamTarget {
 		}
 	}
 
+	pub(crate) async fn subscribe(&self, uri: &str, rq_ctx: &RqCtx) -> Result<(), UpstreamError> {
+		let UpstreamTargetSpec::Mcp(m) = &self.spec else {
+			return Err(resources_unsupported());
+		};
+		let mut extensions = rmcp::model::Extensions::new();
+		extensions.insert(rq_ctx.clone());
+		let result = m
+			.send_request(ClientRequest::SubscribeRequest(SubscribeRequest {
+				method: Default::default(),
+				params: SubscribeRequestParam {
+					uri: uri.to_string(),
+				},
+				extensions,
+			}))
+			.await?;
+		expect_empty(result)
+	}
+
+	pub(crate) async fn unsubscribe(&self, uri: &str, rq_ctx: &RqCtx) -> Result<(), UpstreamError> {
+		let UpstreamTargetSpec::Mcp(m) = &self.spec else {
+			return Err(resources_unsupported());
+		};
+		let mut extensions = rmcp::model::Extensions::new();
+		extensions.insert(rq_ctx.clone());
+		let result = m
+			.send_request(ClientRequest::UnsubscribeRequest(UnsubscribeRequest {
+				method: Default::default(),
+				params: UnsubscribeRequestParam {
+					uri: uri.to_string(),
+				},
+				extensions,
+			}))
+			.await?;
+		expect_empty(result)
+	}
+
+	pub(crate) async fn set_level(
+		&self,
+		request: SetLevelRequestParam,
+		rq_ctx: &RqCtx,
+	) -> Result<(), UpstreamError> {
+		match &self.spec {
+			UpstreamTargetSpec::Mcp(m) => {
+				let mut extensions = rmcp::model::Extensions::new();
+				extensions.insert(rq_ctx.clone());
+				let result = m
+					.send_request(ClientRequest::SetLevelRequest(SetLevelRequest {
+						method: Default::default(),
+						params: request,
+						extensions,
+					}))
+					.await?;
+				expect_empty(result)
+			},
+			// OpenAPI targets do not send log messages
+			UpstreamTargetSpec::OpenAPI(_) => Ok(()),
+		}
+	}
+
+	pub(crate) async fn complete(
+		&self,
+		request: CompleteRequestParam,
+		rq_ctx: &RqCtx,
+	) -> Result<CompleteResult, UpstreamError> {
+		match &self.spec {
+			UpstreamTargetSpec::Mcp(m) => {
+				let mut extensions = rmcp::model::Extensions::new();
+				extensions.insert(rq_ctx.clone());
+				let result = m
+					.send_request(ClientRequest::CompleteRequest(CompleteRequest {
+						method: Default::default(),
+						params: request,
+						extensions,
+					}))
+					.await?;
+				match result {
+					ServerResult::CompleteResult(result) => Ok(result),
+					_ => Err(UpstreamError::ServiceError(
+						rmcp::ServiceError::UnexpectedResponse,
+					)),
+				}
+			},
+			UpstreamTargetSpec::OpenAPI(_) => Ok(CompleteResult {
+				completion: CompletionInfo {
+					values: vec![],
+					total: None,
+					has_more: None,
+				},
+			}),
+		}
+	}
+
 	pub(crate) async fn get_prompt(
 		&self,
 		request: GetPromptRequestParam,
@@ -379,11 +523,13 @@ impl UpstreamTarget {
 				}
 			},
 			UpstreamTargetSpec::OpenAPI(m) => {
+				m.validate_arguments(request.name.as_ref(), request.arguments.as_ref())
+					.map_err(UpstreamError::InvalidArguments)?;
 				let res = m
 					.call_tool(request.name.as_ref(), request.arguments)
 					.await?;
 				Ok(CallToolResult {
-					content: vec![Content::text(res)],
+					content: vec![res],
 					is_error: None,
 				})
 			},
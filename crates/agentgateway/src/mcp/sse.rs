@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::ops::IndexMut;
 use std::sync::Arc;
@@ -10,19 +10,25 @@ use crate::llm::LLMRequest;
 use crate::mcp::rbac::RuleSets;
 use crate::mcp::relay::Relay;
 use crate::mcp::{rbac, relay};
+use crate::proxy::inflight::InFlight;
 use crate::store::{BackendPolicies, Stores};
 use crate::telemetry::log::AsyncLog;
 use crate::types::agent::{
-	BackendName, McpAuthentication, McpBackend, McpIDP, McpTarget as TypeMcpTarget, McpTargetSpec,
+	BackendName, McpAuthentication, McpBackend, McpIDP, McpListDeadline, McpReadOnly, McpServerInfo,
+	McpTarget as TypeMcpTarget, McpTargetSpec, McpToolMerge, McpTransport, PayloadLogging,
 	PolicyTarget, Target,
 };
-use crate::{client, json, mcp};
+use crate::{Config, client, json, mcp};
 use a2a_sdk::SendTaskStreamingResponseResult::Status;
 use agent_core::drain::DrainWatcher;
+use agent_core::metrics::Recorder;
 use agent_core::prelude::Strng;
 use agent_core::trcng;
 use anyhow::Result;
-use axum::extract::{ConnectInfo, OptionalFromRequestParts, Query, State};
+use axum::extract::ws::{
+	CloseFrame, Message as WsMessage, WebSocket, WebSocketUpgrade, close_code,
+};
+use axum::extract::{ConnectInfo, FromRequestParts, OptionalFromRequestParts, Query, State};
 use axum::http::StatusCode;
 use axum::http::header::HeaderMap;
 use axum::http::request::Parts;
@@ -58,6 +64,8 @@ use tower::ServiceExt;
 use tracing::warn;
 use url::form_urlencoded;
 
+const WS_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
 type SseTxs =
 	Arc<std::sync::RwLock<HashMap<SessionId, tokio::sync::mpsc::Sender<ClientJsonRpcMessage>>>>;
 
@@ -71,58 +79,158 @@ pub struct MCPInfo {
 pub struct App {
 	state: Stores,
 	metrics: Arc<relay::metrics::Metrics>,
+	breakers: Arc<relay::breaker::Registry>,
+	balancers: Arc<relay::balancer::Registry>,
+	limiters: Arc<relay::limiter::Registry>,
+	clients: Arc<relay::clients::Registry>,
+	audits: Arc<relay::audit::Registry>,
+	status: Arc<relay::status::Registry>,
 	drain: DrainWatcher,
+	inflight: Arc<InFlight>,
 	session: Arc<LocalSessionManager>,
 	client: client::Client,
 
 	sse_txs: SseTxs,
+	sse_buffer_size: usize,
+	connection_idle_timeout: Option<Duration>,
+	sse_keep_alive: Option<Duration>,
+	stdio_command_allowlist: Arc<[String]>,
 }
 
 impl App {
 	pub fn new(
+		cfg: &Config,
 		state: Stores,
 		metrics: Arc<relay::metrics::Metrics>,
 		client: client::Client,
 		drain: DrainWatcher,
+		inflight: Arc<InFlight>,
 	) -> Self {
 		let session: Arc<LocalSessionManager> = Arc::new(Default::default());
 		Self {
 			state,
 			metrics,
+			breakers: Default::default(),
+			balancers: Default::default(),
+			limiters: Default::default(),
+			clients: Default::default(),
+			audits: Default::default(),
+			status: Default::default(),
 			drain,
+			inflight,
 			session,
 			client,
 			sse_txs: Default::default(),
+			sse_buffer_size: cfg.mcp_sse_buffer_size,
+			connection_idle_timeout: cfg.mcp_connection_idle_timeout,
+			sse_keep_alive: cfg.mcp_sse_keep_alive,
+			stdio_command_allowlist: cfg.stdio_command_allowlist.clone().into(),
 		}
 	}
 
+	/// The connection state of the MCP targets, shared by all sessions.
+	pub fn target_status(&self) -> Arc<relay::status::Registry> {
+		self.status.clone()
+	}
+
 	pub async fn serve(
 		&self,
 		name: BackendName,
-		backends: McpBackend,
+		backends: Arc<McpBackend>,
 		mut req: Request,
 		log: AsyncLog<MCPInfo>,
 	) -> Response {
+		if let Some(transport) = McpTransport::for_path(req.uri().path())
+			&& !backends.allows(transport)
+		{
+			tracing::debug!(?transport, "mcp transport not enabled for backend");
+			return StatusCode::NOT_FOUND.into_response();
+		}
 		let (backends, authorization_policies, authn) = {
 			let binds = self.state.read_binds();
 			let (authorization_policies, authn) = binds.mcp_policies(name.clone());
+			let breaker_targets = backends
+				.circuit_breaker
+				.as_ref()
+				.map(|_| backends.targets.iter().map(|t| &t.name));
+			self
+				.breakers
+				.prune(&name, breaker_targets.into_iter().flatten());
+			let limited_targets = backends
+				.concurrency_limit
+				.as_ref()
+				.map(|_| backends.targets.iter().map(|t| &t.name));
+			self
+				.limiters
+				.prune(&name, limited_targets.into_iter().flatten());
+			let pooled_targets = backends.targets.iter().filter(|t| {
+				matches!(&t.spec, McpTargetSpec::OpenAPI(open) if open.connection_pool.is_some())
+			});
+			self.clients.prune(&name, pooled_targets.map(|t| &t.name));
+			self.balancers.prune(
+				&name,
+				backends.targets.iter().filter_map(|t| t.group.as_ref()),
+			);
+			self.audits.prune(&name, backends.audit.as_ref());
 			let nt = backends
 				.targets
 				.iter()
 				.map(|t| {
 					let backend_policies = binds.backend_policies(PolicyTarget::Backend(t.name.clone()));
+					let circuit_breaker = backends
+						.circuit_breaker
+						.as_ref()
+						.map(|cb| self.breakers.get(&name, &t.name, cb));
+					let concurrency_limit = backends
+						.concurrency_limit
+						.as_ref()
+						.map(|cl| self.limiters.get(&name, &t.name, cl, &self.metrics));
+					let client = match &t.spec {
+						McpTargetSpec::OpenAPI(open) => open
+							.connection_pool
+							.as_ref()
+							.map(|p| self.clients.get(&name, &t.name, p, &self.client)),
+						_ => None,
+					};
 					Arc::new(McpTarget {
 						name: t.name.clone(),
 						spec: t.spec.clone(),
 						filters: t.filters.clone(),
 						backend_policies,
+						circuit_breaker,
+						concurrency_limit,
+						client,
 					})
 				})
 				.collect_vec();
+			let groups = backends
+				.targets
+				.iter()
+				.filter_map(|t| Some((t.group.clone()?, (t.name.clone(), t.weight.unwrap_or(1)))))
+				.into_group_map()
+				.into_iter()
+				.map(|(group, members)| {
+					let balancer = self.balancers.get(&name, &group, members);
+					(group, balancer)
+				})
+				.collect();
 			(
 				McpBackendGroup {
 					name: name.clone(),
 					targets: nt,
+					groups,
+					server_info: backends.server_info.clone().unwrap_or_default(),
+					tool_merge: backends.tool_merge.clone(),
+					logging: backends.logging.clone(),
+					status: self.status.clone(),
+					inflight: self.inflight.child(),
+					stdio_command_allowlist: self.stdio_command_allowlist.clone(),
+					audit: backends
+						.audit
+						.as_ref()
+						.and_then(|a| self.audits.get(&name, a, &self.client)),
+					read_only: backends.read_only.clone(),
+					list_deadline: backends.list_deadline.clone(),
 				},
 				authorization_policies,
 				authn,
@@ -132,22 +240,44 @@ impl App {
 		let metrics = self.metrics.clone();
 		let sm = self.session.clone();
 		let client = self.client.clone();
+		let idle_timeout = self.connection_idle_timeout;
+		let drained = Self::drained(self.drain.clone(), backends.inflight.clone());
 		// Store an empty value, we will populate each field async
 		log.store(Some(MCPInfo::default()));
 		req.extensions_mut().insert(log);
 		match (req.uri().path(), req.method(), authn) {
 			("/sse", m, _) if m == Method::GET => Self::sse_get_handler(
 				self.sse_txs.clone(),
+				self.sse_buffer_size,
+				self.sse_keep_alive,
+				metrics.clone(),
+				name.clone(),
+				drained,
 				Relay::new(
 					backends.clone(),
 					metrics.clone(),
 					authorization_policies.clone(),
 					client.clone(),
+					idle_timeout,
 				),
 			)
 			.await
 			.into_response(),
 			("/sse", m, _) if m == Method::POST => self.sse_post_handler(req).await.into_response(),
+			("/ws", m, _) if m == Method::GET => Self::ws_handler(
+				req,
+				self.sse_buffer_size,
+				drained,
+				Relay::new(
+					backends.clone(),
+					metrics.clone(),
+					authorization_policies.clone(),
+					client.clone(),
+					idle_timeout,
+				),
+			)
+			.await
+			.into_response(),
 			("/.well-known/oauth-protected-resource", _, Some(auth)) => self
 				.protected_resource_metadata(req, auth)
 				.await
@@ -170,6 +300,7 @@ impl App {
 				.into_response(),
 			_ => {
 				// Assume this is streamable HTTP otherwise
+				let is_get = req.method() == Method::GET;
 				let streamable = StreamableHttpService::new(
 					move || {
 						Ok(Relay::new(
@@ -177,23 +308,66 @@ impl App {
 							metrics.clone(),
 							authorization_policies.clone(),
 							client.clone(),
+							idle_timeout,
 						))
 					},
 					sm,
 					StreamableHttpServerConfig {
+						sse_keep_alive: self.sse_keep_alive,
 						..Default::default()
 					},
 				);
-				streamable.handle(req).await.map(axum::body::Body::new)
+				let resp = streamable.handle(req).await.map(axum::body::Body::new);
+				if is_get && is_event_stream(&resp) {
+					// The stream for server-initiated messages would otherwise stay open until the client leaves
+					resp
+						.map(|body| axum::body::Body::from_stream(body.into_data_stream().take_until(drained)))
+				} else {
+					resp
+				}
 			},
 		}
 	}
+
+	/// Resolves once the gateway is draining and the session has no tool calls in flight. Long-lived
+	/// streams end then, rather than holding their connection open until it is forcefully closed,
+	/// without interrupting a call. A streamable HTTP GET carries no calls of its own, so it ends as
+	/// soon as the drain starts.
+	async fn drained(drain: DrainWatcher, inflight: Arc<InFlight>) {
+		drop(drain.wait_for_drain().await);
+		inflight.wait_idle().await;
+	}
+}
+
+/// Sends a `:ping` comment on an SSE stream after `interval` without events. Clients ignore comments,
+/// so they never interleave with the messages.
+fn sse_keep_alive(interval: Duration) -> KeepAlive {
+	KeepAlive::new().interval(interval).text("ping")
+}
+
+fn is_event_stream(resp: &Response) -> bool {
+	resp
+		.headers()
+		.get(header::CONTENT_TYPE)
+		.is_some_and(|ct| ct.as_bytes().starts_with(b"text/event-stream"))
 }
 
 #[derive(Debug, Clone)]
 pub struct McpBackendGroup {
 	pub name: BackendName,
 	pub targets: Vec<Arc<McpTarget>>,
+	/// The target groups, by name, with the balancer spreading calls across their members.
+	pub groups: HashMap<Strng, Arc<relay::balancer::Balancer>>,
+	pub server_info: McpServerInfo,
+	pub tool_merge: Option<McpToolMerge>,
+	pub logging: Option<PayloadLogging>,
+	pub status: Arc<relay::status::Registry>,
+	pub inflight: Arc<InFlight>,
+	/// The commands stdio targets may run.
+	pub stdio_command_allowlist: Arc<[String]>,
+	pub audit: Option<Arc<relay::audit::AuditLog>>,
+	pub read_only: Option<McpReadOnly>,
+	pub list_deadline: Option<McpListDeadline>,
 }
 
 impl McpBackendGroup {
@@ -204,6 +378,70 @@ impl McpBackendGroup {
 			.find(|target| target.name.as_str() == name)
 			.cloned()
 	}
+
+	pub fn circuit_breaker(&self, name: &str) -> Option<Arc<relay::breaker::CircuitBreaker>> {
+		self.find(name)?.circuit_breaker.clone()
+	}
+
+	/// Picks the target a tool call to `name` is sent to, other than those `excluded`. The members of
+	/// a target group are picked by weight, skipping those whose circuit breaker would reject the
+	/// call; None if every member's would. The caller still has to acquire the picked target's
+	/// breaker.
+	pub fn pick(&self, name: &str, excluded: &HashSet<Strng>) -> Option<Strng> {
+		let Some(group) = self.groups.get(name) else {
+			return (!excluded.contains(name)).then(|| name.into());
+		};
+		group.pick(|m| {
+			!excluded.contains(m) && self.circuit_breaker(m).is_none_or(|b| b.available())
+		})
+	}
+
+	/// Picks the target a tool call to `name` is sent to, and acquires its circuit breaker. `wait`
+	/// runs for the picked target first, for example to wait for a concurrency permit, so waiting
+	/// calls do not hold a probe. If a concurrent call took the breaker of the picked member of a
+	/// group meanwhile, another member is picked. None if every candidate's breaker rejects the call;
+	/// otherwise the outcome of the call must be recorded on the returned breaker, if any.
+	pub async fn acquire<P, E>(
+		&self,
+		name: &str,
+		mut wait: impl AsyncFnMut(&Strng) -> Result<P, E>,
+	) -> Result<Option<(Strng, Option<Arc<relay::breaker::CircuitBreaker>>, P)>, E> {
+		let mut rejected = HashSet::new();
+		loop {
+			let Some(target) = self.pick(name, &rejected) else {
+				return Ok(None);
+			};
+			let waited = wait(&target).await?;
+			let breaker = self.circuit_breaker(&target);
+			if breaker.as_ref().is_some_and(|b| !b.try_acquire()) {
+				rejected.insert(target);
+				continue;
+			}
+			return Ok(Some((target, breaker, waited)));
+		}
+	}
+
+	/// The group a target is a member of, if any.
+	pub fn group_of(&self, target: &str) -> Option<&Strng> {
+		self
+			.groups
+			.iter()
+			.find(|(_, b)| b.members().any(|m| m.as_str() == target))
+			.map(|(group, _)| group)
+	}
+
+	/// The names clients see: each target that is not in a group, and each group, in the order they
+	/// first appear.
+	pub fn target_names(&self) -> Vec<Strng> {
+		let mut names = Vec::new();
+		for t in &self.targets {
+			let name = self.group_of(&t.name).unwrap_or(&t.name);
+			if !names.contains(name) {
+				names.push(name.clone());
+			}
+		}
+		names
+	}
 }
 
 #[derive(Debug)]
@@ -212,6 +450,10 @@ pub struct McpTarget {
 	pub spec: crate::types::agent::McpTargetSpec,
 	pub filters: Vec<mcp::relay::upstream::Filter>,
 	pub backend_policies: BackendPolicies,
+	pub circuit_breaker: Option<Arc<relay::breaker::CircuitBreaker>>,
+	pub concurrency_limit: Option<Arc<relay::limiter::ConcurrencyLimiter>>,
+	/// The client for calls to the target, if it has its own connection pool.
+	pub client: Option<client::Client>,
 }
 
 impl App {
@@ -343,6 +585,11 @@ impl App {
 
 	async fn sse_get_handler(
 		sse_txs: SseTxs,
+		buffer_size: usize,
+		keep_alive: Option<Duration>,
+		metrics: Arc<relay::metrics::Metrics>,
+		backend: BackendName,
+		drained: impl Future<Output = ()> + Send + 'static,
 		relay: Relay,
 	) -> Result<Sse<impl Stream<Item = Result<Event, io::Error>>>, StatusCode> {
 		// it's 4KB
@@ -352,8 +599,8 @@ impl App {
 
 		use tokio_stream::wrappers::ReceiverStream;
 		use tokio_util::sync::PollSender;
-		let (from_client_tx, from_client_rx) = tokio::sync::mpsc::channel(64);
-		let (to_client_tx, to_client_rx) = tokio::sync::mpsc::channel(64);
+		let (from_client_tx, from_client_rx) = tokio::sync::mpsc::channel(buffer_size);
+		let (to_client_tx, to_client_rx) = tokio::sync::mpsc::channel(buffer_size);
 		sse_txs
 			.write()
 			.expect("mutex poisoned")
@@ -371,17 +618,30 @@ impl App {
 						tracing::error!("serving error: {:?}", e);
 					});
 
-				if let Err(e) = result {
-					tracing::error!(error = ?e, "initialize error");
+				let Ok(running) = result else {
 					sse_txs.write().expect("mutex poisoned").remove(&session);
 					return;
-				}
-				// Add a listener drain channel here.
+				};
+				tokio::pin!(drained);
 				tokio::select! {
-					_ = to_client_tx.closed() =>{
-						tracing::info!("client disconnected");
+					_ = to_client_tx.closed() => {
+						tracing::info!(%session, "client disconnected, abandoning stream");
+						metrics.record(
+							relay::metrics::SseStreamAbandoned {
+								server: backend.to_string(),
+							},
+							(),
+						);
+					}
+					_ = running.waiting() => {
+						tracing::debug!(%session, "server closed the stream");
+					}
+					_ = &mut drained => {
+						tracing::debug!(%session, "draining; closing the stream");
 					}
 				};
+				// Stop reading from the upstream servers; nobody is left to send the responses to.
+				ct.cancel();
 				sse_txs.write().expect("mutex poisoned").remove(&session);
 			});
 		}
@@ -397,6 +657,135 @@ impl App {
 				Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
 			}
 		}));
-		Ok(Sse::new(stream))
+		let sse = Sse::new(stream);
+		Ok(match keep_alive {
+			Some(interval) => sse.keep_alive(sse_keep_alive(interval)),
+			None => sse,
+		})
+	}
+
+	async fn ws_handler(
+		req: Request,
+		buffer_size: usize,
+		drained: impl Future<Output = ()> + Send + 'static,
+		relay: Relay,
+	) -> Response {
+		let (mut parts, _) = req.into_parts();
+		let ws = match WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+			Ok(ws) => ws,
+			Err(e) => return e.into_response(),
+		};
+		ws.on_upgrade(move |socket| Self::serve_ws(socket, parts, buffer_size, drained, relay))
+	}
+
+	async fn serve_ws(
+		socket: WebSocket,
+		parts: Parts,
+		buffer_size: usize,
+		drained: impl Future<Output = ()>,
+		relay: Relay,
+	) {
+		use tokio_util::sync::PollSender;
+		let session = generate_streamable_session_id();
+		tracing::debug!(%session, "websocket connection");
+
+		let (from_client_tx, from_client_rx) = tokio::sync::mpsc::channel(buffer_size);
+		let (to_client_tx, mut to_client_rx) = tokio::sync::mpsc::channel(buffer_size);
+		let ct = CancellationToken::new();
+		{
+			let ct = ct.child_token();
+			tokio::spawn(async move {
+				let stream = ReceiverStream::new(from_client_rx);
+				let sink = PollSender::new(to_client_tx).sink_map_err(std::io::Error::other);
+				match serve_server_with_ct(relay, (sink, stream), ct).await {
+					Ok(running) => {
+						let _ = running.waiting().await;
+					},
+					Err(e) => tracing::error!("serving error: {:?}", e),
+				}
+			});
+		}
+
+		let (mut ws_tx, mut ws_rx) = socket.split();
+		let mut keepalive = tokio::time::interval(WS_KEEPALIVE_INTERVAL);
+		tokio::pin!(drained);
+		loop {
+			tokio::select! {
+				msg = ws_rx.next() => match msg {
+					Some(Ok(WsMessage::Text(text))) => {
+						let mut message = match serde_json::from_str::<ClientJsonRpcMessage>(&text) {
+							Ok(message) => message,
+							Err(e) => {
+								tracing::warn!(%session, "invalid websocket message: {e}");
+								continue;
+							},
+						};
+						// Attach the upgrade request so auth and RBAC see the same context as HTTP transports.
+						if let ClientJsonRpcMessage::Request(req) = &mut message {
+							req.request.extensions_mut().insert(parts.clone());
+						}
+						if from_client_tx.send(message).await.is_err() {
+							break;
+						}
+					},
+					Some(Ok(WsMessage::Binary(_))) => {
+						tracing::warn!(%session, "ignoring binary websocket message");
+					},
+					// Pings are answered automatically
+					Some(Ok(WsMessage::Ping(_) | WsMessage::Pong(_))) => {},
+					Some(Ok(WsMessage::Close(_))) | None => {
+						tracing::debug!(%session, "client closed the websocket");
+						break;
+					},
+					Some(Err(e)) => {
+						tracing::debug!(%session, "websocket error: {e}");
+						break;
+					},
+				},
+				msg = to_client_rx.recv() => {
+					let Some(msg) = msg else {
+						tracing::debug!(%session, "server closed the websocket");
+						let _ = ws_tx
+							.send(WsMessage::Close(Some(CloseFrame {
+								code: close_code::NORMAL,
+								reason: "".into(),
+							})))
+							.await;
+						break;
+					};
+					let text = match serde_json::to_string(&msg) {
+						Ok(text) => text,
+						Err(e) => {
+							tracing::error!(%session, "failed to encode message: {e}");
+							continue;
+						},
+					};
+					if ws_tx.send(WsMessage::Text(text.into())).await.is_err() {
+						break;
+					}
+				},
+				_ = &mut drained => {
+					tracing::debug!(%session, "draining; closing the websocket");
+					let _ = ws_tx
+						.send(WsMessage::Close(Some(CloseFrame {
+							code: close_code::AWAY,
+							reason: "".into(),
+						})))
+						.await;
+					break;
+				},
+				_ = keepalive.tick() => {
+					if ws_tx.send(WsMessage::Ping(Default::default())).await.is_err() {
+						break;
+					}
+				},
+			}
+		}
+		// Stop reading from the upstream servers; nobody is left to send the responses to.
+		ct.cancel();
 	}
 }
+
+#[cfg(test)]
+#[path = "sse_tests.rs"]
+mod tests;
@@ -0,0 +1,227 @@
+use std::time::Instant;
+
+use agent_core::strng;
+use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use prometheus_client::registry::Registry;
+
+use super::*;
+
+fn relay(metrics: Arc<relay::metrics::Metrics>) -> Relay {
+	let backend = McpBackendGroup {
+		name: strng::new("backend"),
+		targets: vec![],
+		groups: HashMap::new(),
+		server_info: Default::default(),
+		tool_merge: None,
+		logging: None,
+		status: Default::default(),
+		inflight: Default::default(),
+		stdio_command_allowlist: Default::default(),
+		audit: None,
+		read_only: None,
+		list_deadline: None,
+	};
+	let client = client::Client::new(
+		&client::Config {
+			resolver_cfg: ResolverConfig::default(),
+			resolver_opts: ResolverOpts::default(),
+		},
+		None,
+	);
+	Relay::new(backend, metrics, RuleSets::from(vec![]), client, None)
+}
+
+fn initialize() -> [ClientJsonRpcMessage; 2] {
+	[
+		serde_json::from_value(json!({
+			"jsonrpc": "2.0",
+			"id": 1,
+			"method": "initialize",
+			"params": {
+				"protocolVersion": "2025-03-26",
+				"capabilities": {},
+				"clientInfo": { "name": "test", "version": "1.0" },
+			},
+		}))
+		.unwrap(),
+		serde_json::from_value(json!({
+			"jsonrpc": "2.0",
+			"method": "notifications/initialized",
+		}))
+		.unwrap(),
+	]
+}
+
+async fn next_frame(body: &mut axum::body::Body) -> String {
+	let frame = tokio::time::timeout(Duration::from_secs(5), body.frame())
+		.await
+		.expect("frame received")
+		.unwrap()
+		.unwrap();
+	String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap()
+}
+
+/// Opens an SSE stream as a client connecting to `/sse` would, returning its body.
+async fn sse_stream(keep_alive: Option<Duration>) -> axum::body::Body {
+	let mut registry = Registry::default();
+	let metrics = Arc::new(relay::metrics::Metrics::new(&mut registry, None));
+	let sse = App::sse_get_handler(
+		SseTxs::default(),
+		4,
+		keep_alive,
+		metrics.clone(),
+		strng::new("backend"),
+		futures::future::pending(),
+		relay(metrics),
+	)
+	.await
+	.unwrap();
+	sse.into_response().into_body()
+}
+
+#[tokio::test]
+async fn test_sse_keep_alive() {
+	let mut body = sse_stream(Some(Duration::from_millis(50))).await;
+
+	// Messages are sent as they are, and pings only follow once the stream is idle
+	assert!(next_frame(&mut body).await.starts_with("event: endpoint\n"));
+	let start = Instant::now();
+	assert_eq!(next_frame(&mut body).await, ": ping\n\n");
+	assert!(start.elapsed() >= Duration::from_millis(40));
+	assert_eq!(next_frame(&mut body).await, ": ping\n\n");
+
+	// Without a keep-alive, an idle stream sends nothing
+	let mut body = sse_stream(None).await;
+	assert!(next_frame(&mut body).await.starts_with("event: endpoint\n"));
+	let idle = tokio::time::timeout(Duration::from_millis(200), body.frame()).await;
+	assert!(idle.is_err(), "unexpected frame: {idle:?}");
+}
+
+#[tokio::test]
+async fn test_sse_stream_abandoned() {
+	let mut registry = Registry::default();
+	let metrics = Arc::new(relay::metrics::Metrics::new(&mut registry, None));
+	let sse_txs = SseTxs::default();
+	let sse = App::sse_get_handler(
+		sse_txs.clone(),
+		4,
+		Some(Duration::from_secs(60)),
+		metrics.clone(),
+		strng::new("backend"),
+		futures::future::pending(),
+		relay(metrics),
+	)
+	.await
+	.unwrap();
+	let mut body = sse.into_response().into_body();
+	assert!(next_frame(&mut body).await.starts_with("event: endpoint\n"));
+
+	let tx = sse_txs.read().unwrap().values().next().unwrap().clone();
+	for msg in initialize() {
+		tx.send(msg).await.unwrap();
+	}
+	assert!(next_frame(&mut body).await.contains("\"serverInfo\""));
+
+	// The client going away ends the session and is counted
+	drop(body);
+	tokio::time::timeout(Duration::from_secs(5), tx.closed())
+		.await
+		.expect("session closed");
+	assert!(sse_txs.read().unwrap().is_empty());
+	let mut encoded = String::new();
+	prometheus_client::encoding::text::encode(&mut encoded, &registry).unwrap();
+	assert!(
+		encoded.contains("sse_streams_abandoned_total{server=\"backend\"} 1"),
+		"{encoded}"
+	);
+}
+
+async fn next_ws<S>(ws: &mut tokio_tungstenite::WebSocketStream<S>) -> Value
+where
+	S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+	loop {
+		let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+			.await
+			.expect("message received")
+			.unwrap()
+			.unwrap();
+		if let tokio_tungstenite::tungstenite::Message::Text(text) = msg {
+			return serde_json::from_str(&text).unwrap();
+		}
+	}
+}
+
+#[tokio::test]
+async fn test_ws_round_trip() {
+	use tokio_tungstenite::tungstenite::Message;
+
+	let mut registry = Registry::default();
+	let relay = relay(Arc::new(relay::metrics::Metrics::new(&mut registry, None)));
+	let router = Router::new().route(
+		"/ws",
+		get(move |req: Request| App::ws_handler(req, 4, futures::future::pending(), relay.clone())),
+	);
+	let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move { axum::serve(listener, router).await });
+
+	let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws"))
+		.await
+		.unwrap();
+	let text = |msg: &ClientJsonRpcMessage| Message::text(serde_json::to_string(msg).unwrap());
+	let [init, initialized] = initialize();
+	let (init, initialized) = (text(&init), text(&initialized));
+	let list =
+		Message::text(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }).to_string());
+
+	// Requests and responses are exchanged as text messages, one JSON-RPC message each
+	ws.send(init).await.unwrap();
+	let res = next_ws(&mut ws).await;
+	assert_eq!(res["id"], 1);
+	assert!(res["result"]["serverInfo"].is_object(), "{res}");
+	ws.send(initialized).await.unwrap();
+	ws.send(list).await.unwrap();
+	let res = next_ws(&mut ws).await;
+	assert_eq!(res["id"], 2);
+	assert_eq!(res["result"]["tools"], json!([]));
+
+	// Once the client closes the socket, the server ends the connection
+	ws.close(None).await.unwrap();
+	tokio::time::timeout(Duration::from_secs(5), async {
+		while let Some(Ok(_)) = ws.next().await {}
+	})
+	.await
+	.expect("connection closed");
+}
+
+#[tokio::test]
+async fn test_drained_per_session() {
+	let (trigger, watcher) = agent_core::drain::new();
+	let inflight = Arc::new(InFlight::default());
+	let busy = inflight.child();
+	let call = busy.start();
+
+	let busy_drained = tokio::spawn(App::drained(watcher.clone(), busy));
+	let idle_drained = tokio::spawn(App::drained(watcher.clone(), inflight.child()));
+	drop(watcher);
+	let drain = tokio::spawn(trigger.start_drain_and_wait(agent_core::drain::DrainMode::Graceful));
+
+	// The session without calls ends its streams while another session's call is still running
+	tokio::time::timeout(Duration::from_secs(1), idle_drained)
+		.await
+		.expect("idle session drained")
+		.unwrap();
+	tokio::time::sleep(Duration::from_millis(10)).await;
+	assert!(!busy_drained.is_finished(), "a call is still in flight");
+
+	drop(call);
+	tokio::time::timeout(Duration::from_secs(1), busy_drained)
+		.await
+		.expect("busy session drained once its call finished")
+		.unwrap();
+	tokio::time::timeout(Duration::from_secs(1), drain)
+		.await
+		.expect("drain completes")
+		.unwrap();
+}
@@ -87,6 +87,30 @@ async fn test_sse_json() {
 	);
 }
 
+#[tokio::test]
+async fn test_sse_json_passthrough_until_error() {
+	let msg1 = "data: {\"msg\": 1}\n\n";
+	let msg2 = "data: not json\n\n";
+	let msg3 = "data: {\"msg\": 3}\n\n";
+	let body = http::Body::from_stream(futures_util::stream::iter(vec![
+		Ok::<_, std::io::Error>(Bytes::copy_from_slice(msg1.as_bytes())),
+		Ok::<_, std::io::Error>(Bytes::copy_from_slice(msg2.as_bytes())),
+		Ok::<_, std::io::Error>(Bytes::copy_from_slice(msg3.as_bytes())),
+	]));
+
+	let body = sse::json_passthrough_until_error::<Test>(body, |t| match t {
+		Ok(_) => Ok(()),
+		Err(_) => Err(Bytes::from_static(b"{\"error\":\"invalid\"}")),
+	});
+	let result = body.collect().await.unwrap();
+	let result_str = String::from_utf8_lossy(&result.to_bytes()).to_string();
+	// The invalid event is replaced, and nothing after it is sent
+	assert!(result_str.contains(r#"data: {"msg": 1}"#));
+	assert!(result_str.contains(r#"data: {"error":"invalid"}"#));
+	assert!(!result_str.contains("not json"));
+	assert!(!result_str.contains(r#"{"msg": 3}"#));
+}
+
 #[tokio::test]
 async fn test_sse_json_transform() {
 	let msg1 = "data: {\"msg\": 1, \"type\": \"input\"}\n\n";
@@ -155,3 +179,24 @@ data: [DONE]
 "#
 	);
 }
+
+#[tokio::test]
+async fn test_prefix() {
+	let run = |limit: usize| async move {
+		let chunks =
+			["hello ", "world"].map(|c| Ok::<_, Infallible>(http_body::Frame::data(Bytes::from(c))));
+		let body = http::Body::new(http_body_util::StreamBody::new(futures_util::stream::iter(
+			chunks,
+		)));
+		let (tx, rx) = tokio::sync::oneshot::channel();
+		let body = passthrough::prefix(body, limit, move |prefix, truncated| {
+			tx.send((prefix, truncated)).unwrap();
+		});
+		// The body is passed through in full either way
+		assert_eq!(body.collect().await.unwrap().to_bytes(), "hello world");
+		rx.await.unwrap()
+	};
+	assert_eq!(run(8).await, (Bytes::from("hello wo"), true));
+	assert_eq!(run(11).await, (Bytes::from("hello world"), false));
+	assert_eq!(run(64).await, (Bytes::from("hello world"), false));
+}
@@ -41,6 +41,57 @@ where
 	})
 }
 
+/// Passes the body through unchanged, calling `handler` with up to `limit` bytes from its start
+/// once it ends, and whether the body was longer than that.
+pub fn prefix<F>(body: http::Body, limit: usize, handler: F) -> http::Body
+where
+	F: FnOnce(Bytes, bool) + Send + 'static,
+{
+	let mut handler = Some(handler);
+	parser(
+		body,
+		Prefix {
+			limit,
+			prefix: BytesMut::new(),
+			truncated: false,
+			done: false,
+		},
+		move |(prefix, truncated)| {
+			if let Some(handler) = handler.take() {
+				handler(prefix, truncated)
+			}
+		},
+	)
+}
+
+struct Prefix {
+	limit: usize,
+	prefix: BytesMut,
+	truncated: bool,
+	done: bool,
+}
+
+impl Decoder for Prefix {
+	type Item = (Bytes, bool);
+	type Error = std::io::Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+		let take = (self.limit - self.prefix.len()).min(src.len());
+		self.prefix.extend_from_slice(&src[..take]);
+		self.truncated |= src.len() > take;
+		src.clear();
+		Ok(None)
+	}
+
+	fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+		self.decode(src)?;
+		if std::mem::replace(&mut self.done, true) {
+			return Ok(None);
+		}
+		Ok(Some((self.prefix.split().freeze(), self.truncated)))
+	}
+}
+
 impl<D, F> Body for PassthroughBody<D, F>
 where
 	D: Decoder + Send + 'static,
@@ -66,6 +66,42 @@ pub fn json_transform<I: DeserializeOwned, O: Serialize>(
 	})
 }
 
+/// Like json_passthrough, but `f` may reject an event by returning the data to send in its place.
+/// Once an event is rejected, all remaining upstream events are dropped so the client sees a clean
+/// end of the stream.
+pub fn json_passthrough_until_error<F: DeserializeOwned>(
+	b: http::Body,
+	mut f: impl FnMut(anyhow::Result<F>) -> Result<(), Bytes> + Send + 'static,
+) -> http::Body {
+	let decoder = SseDecoder::<Bytes>::with_max_size(2_097_152);
+	let encoder = SseEncoder::new();
+	let mut failed = false;
+
+	transform_parser(b, decoder, encoder, move |o| {
+		if failed {
+			return None;
+		}
+		let Frame::Event(Event::<Bytes> { data, .. }) = &o else {
+			return Some(o);
+		};
+		if data.as_ref() == b"[DONE]" {
+			return Some(o);
+		}
+		let obj = serde_json::from_slice::<F>(data);
+		match f(obj.map_err(anyhow::Error::from)) {
+			Ok(()) => Some(o),
+			Err(replacement) => {
+				failed = true;
+				Some(Frame::Event(Event::<Bytes> {
+					data: replacement,
+					name: std::borrow::Cow::Borrowed(""),
+					id: None,
+				}))
+			},
+		}
+	})
+}
+
 fn unwrap_sse_data(frame: Frame<Bytes>) -> Option<Bytes> {
 	let Frame::Event(Event::<Bytes> { data, .. }) = frame else {
 		return None;
@@ -1,25 +1,33 @@
 use crate::ProxyInputs;
+use crate::http::localratelimit::RateLimitLayer;
 use crate::store::Event;
-use crate::transport::stream::{BytesCounter, Extension, LoggingMode, Socket};
-use crate::types::agent::{Bind, BindName, Listener, ListenerProtocol};
+use crate::telemetry::metrics::BindLabels;
+use crate::transport::proxy_protocol;
+use crate::transport::stream::{BytesCounter, Extension, LoggingMode, Socket, TCPConnectionInfo};
+use crate::types::agent::{Bind, BindName, Listener, ListenerProtocol, ProxyProtocol};
 use agent_core::drain;
 use agent_core::drain::{DrainUpgrader, DrainWatcher};
+use agent_core::readiness::BlockReady;
 use anyhow::anyhow;
 use bytes::Bytes;
 use futures_util::FutureExt;
+use futures_util::stream::FuturesUnordered;
 use http::StatusCode;
+use hyper::body::Incoming;
 use hyper_util::rt::TokioIo;
 use hyper_util::server::conn::auto;
-use std::collections::HashMap;
+use hyper_util::service::TowerToHyperService;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::watch;
+use tokio::sync::{Semaphore, oneshot, watch};
+use tokio::task;
 use tokio::task::{AbortHandle, JoinSet};
 use tokio_stream::StreamExt;
-use tracing::{Instrument, debug, event, info, info_span, warn};
+use tracing::{Instrument, debug, error, event, info, info_span, warn};
 
 #[cfg(test)]
 #[path = "gateway_test.rs"]
@@ -35,7 +43,7 @@ impl Gateway {
 		Gateway { drain, pi }
 	}
 
-	pub async fn run(self) {
+	pub async fn run(self, ready: BlockReady) -> anyhow::Result<()> {
 		let drain = self.drain.clone();
 		let subdrain = self.drain.clone();
 		let mut js = JoinSet::new();
@@ -44,29 +52,45 @@ impl Gateway {
 			(binds.all(), binds.subscribe())
 		};
 		let mut active: HashMap<SocketAddr, AbortHandle> = HashMap::new();
-		let mut handle_bind = |js: &mut JoinSet<anyhow::Result<()>>, b: Event<Arc<Bind>>| {
+		let mut handle_bind = |js: &mut JoinSet<anyhow::Result<()>>,
+		                       b: Event<Arc<Bind>>,
+		                       bound: Option<oneshot::Sender<()>>|
+		 -> Option<task::Id> {
 			let b = match b {
 				Event::Add(b) => b,
 				Event::Remove(to_remove) => {
 					if let Some(h) = active.remove(&to_remove.address) {
 						h.abort();
 					}
-					return;
+					return None;
 				},
 			};
 			if active.contains_key(&b.address) {
 				debug!("bind already exists");
-				return;
+				return None;
 			}
 
 			debug!("add bind {}", b.address);
-			let task =
-				js.spawn(Self::run_bind(self.pi.clone(), subdrain.clone(), b.clone()).in_current_span());
+			let task = js.spawn(
+				Self::run_bind(self.pi.clone(), subdrain.clone(), b.clone(), bound).in_current_span(),
+			);
+			let id = task.id();
 			active.insert(b.address, task);
+			Some(id)
 		};
+		// Readiness waits for the initial binds to be listening, but not for anything they connect to.
+		// A bind that fails keeps readiness blocked, and is fatal if `fatal_bind_errors` is set.
+		let mut initial = HashSet::new();
+		let mut startup = FuturesUnordered::new();
 		for bind in initial_binds {
-			handle_bind(&mut js, Event::Add(bind))
+			let bind_ready = ready.subtask(&format!("bind {}", bind.key));
+			let (bound_tx, bound_rx) = oneshot::channel();
+			initial.extend(handle_bind(&mut js, Event::Add(bind), Some(bound_tx)));
+			startup.push(async move { (bound_rx.await.is_ok(), bind_ready) });
 		}
+		drop(ready);
+		let mut unbound = Vec::new();
+		let fatal_bind_errors = self.pi.cfg.fatal_bind_errors;
 
 		let mut wait = drain.wait_for_drain();
 		tokio::pin!(wait);
@@ -78,18 +102,44 @@ impl Gateway {
 						warn!("lagged on bind update");
 						continue;
 					};
-					handle_bind(&mut js, res);
+					handle_bind(&mut js, res, None);
+				}
+				Some((bound, bind_ready)) = startup.next() => {
+					if !bound {
+						// Held until the gateway stops, so readiness never reports a missing bind as ready.
+						unbound.push(bind_ready);
+					}
 				}
-				Some(res) = js.join_next() => {
-					warn!("bind complete {res:?}");
+				Some(res) = js.join_next_with_id() => {
+					match res {
+						Ok((id, Err(e))) if fatal_bind_errors && initial.contains(&id) => {
+							return Err(e);
+						}
+						res => warn!("bind complete {res:?}"),
+					}
 				}
 				_ = &mut wait => {
 					info!("stop listening for binds; drain started");
-					while let Some(res) = js.join_next().await  {
-						info!("bind complete {res:?}");
+					// Binds wait for the in-flight calls until the grace period expires, then close the
+					// remaining connections.
+					let grace_period = tokio::time::sleep(self.pi.cfg.termination_max_deadline);
+					tokio::pin!(grace_period);
+					loop {
+						tokio::select! {
+							res = js.join_next() => match res {
+								Some(res) => info!("bind complete {res:?}"),
+								None => break,
+							},
+							_ = &mut grace_period, if !grace_period.is_elapsed() => {
+								let in_flight = self.pi.inflight.count();
+								if in_flight > 0 {
+									warn!(in_flight, "grace period expired; aborting the calls still in flight");
+								}
+							}
+						}
 					}
 					info!("binds drained");
-					return
+					return Ok(())
 				}
 			}
 		}
@@ -99,12 +149,27 @@ impl Gateway {
 		pi: Arc<ProxyInputs>,
 		drain: DrainWatcher,
 		b: Arc<Bind>,
+		bound: Option<oneshot::Sender<()>>,
 	) -> anyhow::Result<()> {
 		let min_deadline = pi.cfg.termination_min_deadline;
 		let max_deadline = pi.cfg.termination_max_deadline;
 		let name = b.key.clone();
-		let listener = TcpListener::bind(b.address).await?; // TODO: nodelay
+		let proxy_protocol = b.proxy_protocol;
+		let connection_limit = b
+			.max_connections
+			.map(|n| Arc::new(Semaphore::new(n as usize)));
+		// TODO: nodelay
+		let listener = match TcpListener::bind(b.address).await {
+			Ok(listener) => listener,
+			Err(e) => {
+				error!(bind = name.as_str(), address = %b.address, "failed to bind: {e}");
+				return Err(anyhow::Error::new(e).context(format!("failed to bind {}", b.address)));
+			},
+		};
 		info!(bind = name.as_str(), "started bind");
+		if let Some(bound) = bound {
+			let _ = bound.send(());
+		}
 		let component = format!("bind {name}");
 
 		// Desired drain semantics:
@@ -128,9 +193,23 @@ impl Gateway {
 			// Having a weak reference allows us to listen() forever without blocking, but create blockers for accepted connections.
 			let (mut upgrader, weak) = drain.into_weak();
 			let (inner_trigger, inner_drain) = drain::new();
-			let handle_stream = |stream: TcpStream, upgrader: &DrainUpgrader| {
-				let mut stream = Socket::from_tcp(stream).expect("todo");
-				stream.with_logging(LoggingMode::Downstream);
+			let handle_stream = |mut stream: TcpStream, upgrader: &DrainUpgrader| {
+				let permit = match &connection_limit {
+					Some(limit) => match limit.clone().try_acquire_owned() {
+						Ok(permit) => Some(permit),
+						Err(_) => {
+							debug!(bind=?name, "connection limit reached; rejecting connection");
+							pi.metrics
+								.rejected_connections
+								.get_or_create(&BindLabels {
+									bind: name.clone().into(),
+								})
+								.inc();
+							return;
+						},
+					},
+					None => None,
+				};
 				let pi = pi.clone();
 				// We got the connection; make a strong drain blocker.
 				let drain = upgrader.upgrade(weak.clone());
@@ -139,6 +218,26 @@ impl Gateway {
 				let name = name.clone();
 				tokio::spawn(async move {
 					debug!(bind=?name, "connection started");
+					let header = match proxy_protocol {
+						Some(mode) => {
+							let required = mode == ProxyProtocol::Required;
+							proxy_protocol::read_header(&mut stream, required).await
+						},
+						None => Ok(None),
+					};
+					let header = match header {
+						Ok(header) => header,
+						Err(e) => {
+							warn!(bind=?name, "rejecting connection: {e}");
+							return;
+						},
+					};
+					let mut stream = Socket::from_tcp(stream).expect("todo");
+					stream.with_logging(LoggingMode::Downstream);
+					if let Some(header) = header {
+						debug!(bind=?name, ?header, "received PROXY protocol header");
+						stream.with_proxy_header(header);
+					}
 					tokio::select! {
 						// We took too long; shutdown now.
 						_ = force_shutdown.changed() => {
@@ -146,6 +245,7 @@ impl Gateway {
 						}
 						_ = Self::proxy_bind(name.clone(), stream, pi, drain) => {}
 					}
+					drop(permit);
 					debug!(bind=?name, dur=?start.elapsed(), "connection completed");
 				});
 			};
@@ -256,17 +356,31 @@ impl Gateway {
 			selected_listener,
 			target_address,
 		};
+		let rate_limit = proxy
+			.inputs
+			.stores
+			.read_binds()
+			.bind(&proxy.bind_name)
+			.and_then(|b| b.rate_limit.clone())
+			.map(|rl| RateLimitLayer::new(rl, proxy.bind_name.clone(), proxy.inputs.metrics.clone()));
 		let server = auto_server();
 		let connection = Arc::new(stream.get_ext());
-		let serve = server // TODO: tune all optinos
-			.serve_connection_with_upgrades(
-				TokioIo::new(stream),
-				hyper::service::service_fn(move |req| {
-					let proxy = proxy.clone();
-					let connection = connection.clone();
-					async move { proxy.proxy(connection, req).map(Ok::<_, Infallible>).await }
-				}),
-			);
+		let rate_limit_connection = connection.clone();
+		let service = tower::ServiceBuilder::new()
+			// Rate limits may be keyed by the client address, so it must be available beforehand.
+			.map_request(move |mut req: ::http::Request<Incoming>| {
+				rate_limit_connection.copy::<TCPConnectionInfo>(req.extensions_mut());
+				req
+			})
+			.option_layer(rate_limit)
+			.service_fn(move |req| {
+				let proxy = proxy.clone();
+				let connection = connection.clone();
+				async move { proxy.proxy(connection, req).map(Ok::<_, Infallible>).await }
+			});
+		let serve =
+			server // TODO: tune all optinos
+				.serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(service));
 		// Wrap it in the graceful watcher, will ensure GOAWAY/Connect:clone when we shutdown
 		let serve = drain.wrap_connection(serve);
 		let res = serve.await;
@@ -4,9 +4,9 @@ use crate::proxy::request_builder::RequestBuilder;
 use crate::store::Stores;
 use crate::transport::stream::{Socket, TCPConnectionInfo};
 use crate::types::agent::{
-	Backend, BackendReference, Bind, BindName, Listener, ListenerProtocol, ListenerSet, PathMatch,
-	Policy, PolicyTarget, Route, RouteBackend, RouteBackendReference, RouteMatch, RouteSet, Target,
-	TargetedPolicy,
+	A2aPolicy, Backend, BackendReference, Bind, BindName, Listener, ListenerProtocol, ListenerSet,
+	PathMatch, Policy, PolicyTarget, Route, RouteBackend, RouteBackendReference, RouteMatch, RouteSet,
+	Target, TargetedPolicy,
 };
 use crate::*;
 use crate::{ProxyInputs, client, mcp};
@@ -75,6 +75,7 @@ async fn local_ratelimit() {
 				tokens_per_fill: 1,
 				fill_interval: Duration::from_secs(1),
 				limit_type: Default::default(),
+				key: None,
 			}
 			.try_into()
 			.unwrap(),
@@ -85,6 +86,138 @@ async fn local_ratelimit() {
 	assert_eq!(res.status(), 200);
 	let res = send_request(io.clone(), Method::GET, "http://lo").await;
 	assert_eq!(res.status(), 429);
+	assert_eq!(res.headers().get(hyper::header::RETRY_AFTER).unwrap(), "1");
+}
+
+#[tokio::test]
+async fn request_id() {
+	let (_mock, _bind, io) = basic_setup().await;
+	let res = RequestBuilder::new(Method::GET, "http://lo")
+		.header("x-request-id", "client-id")
+		.send(io.clone())
+		.await
+		.unwrap();
+	assert_eq!(res.headers().get("x-request-id").unwrap(), "client-id");
+	let body = read_body(res.into_body()).await;
+	assert_eq!(body.headers.get("x-request-id").unwrap(), "client-id");
+
+	// Without one from the client, an id is generated and sent both ways
+	let res = send_request(io, Method::GET, "http://lo").await;
+	let id = res.headers().get("x-request-id").unwrap().clone();
+	assert_eq!(id.len(), 32);
+	let body = read_body(res.into_body()).await;
+	assert_eq!(body.headers.get("x-request-id").unwrap(), &id);
+}
+
+#[tokio::test]
+async fn a2a_errors_are_json_rpc() {
+	let mock = simple_mock().await;
+	let route = Route {
+		matches: vec![RouteMatch {
+			headers: vec![],
+			path: PathMatch::PathPrefix("/agent".into()),
+			method: None,
+			query: vec![],
+		}],
+		..basic_route(*mock.address())
+	};
+	let t = setup()
+		.unwrap()
+		.with_backend(*mock.address())
+		.with_bind(simple_bind(route))
+		.with_policy(TargetedPolicy {
+			name: strng::new("a2a"),
+			target: PolicyTarget::Backend(mock.address().to_string().into()),
+			policy: Policy::A2a(A2aPolicy {
+				validate_task_states: false,
+				logging: None,
+				max_request_size: Some(64),
+				deny_private_push_notification_urls: false,
+			}),
+		});
+	let io = t.serve_http(strng::new("bind"));
+	let call = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tasks/get", "params": {}});
+
+	// No route matches, so the error is raised before any backend policy applies
+	let res = RequestBuilder::new(Method::POST, "http://lo/other")
+		.json(&call)
+		.send(io.clone())
+		.await
+		.unwrap();
+	assert_eq!(res.status(), 404);
+	let body: serde_json::Value =
+		serde_json::from_slice(&read_body_raw(res.into_body()).await).unwrap();
+	assert_eq!(body["jsonrpc"], "2.0");
+	assert_eq!(body["id"], serde_json::Value::Null);
+	assert_eq!(body["error"]["code"], -32601);
+
+	let large = serde_json::json!({
+		"jsonrpc": "2.0",
+		"id": 2,
+		"method": "tasks/get",
+		"params": {"id": "x".repeat(128)},
+	});
+	let res = RequestBuilder::new(Method::POST, "http://lo/agent")
+		.json(&large)
+		.send(io)
+		.await
+		.unwrap();
+	assert_eq!(res.status(), 413);
+	let body: serde_json::Value =
+		serde_json::from_slice(&read_body_raw(res.into_body()).await).unwrap();
+	assert_eq!(body["error"]["code"], -32600);
+}
+
+#[tokio::test]
+async fn bind_failure_holds_readiness() {
+	let taken = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let t = setup().unwrap();
+	let bind = |key: &str, address: SocketAddr| Bind {
+		key: strng::new(key),
+		address,
+		..simple_bind(basic_route(address))
+	};
+	let t = t
+		.with_bind(bind("good", "127.0.0.1:0".parse().unwrap()))
+		.with_bind(bind("bad", taken.local_addr().unwrap()));
+	let ready = agent_core::readiness::Ready::new();
+	let task = ready.register_task("gateway");
+	let gw = tokio::spawn(Gateway::new(t.pi.clone(), t.drain_rx.clone()).run(task));
+
+	// The bind that is listening is released, the one that failed is not
+	let want = std::collections::HashSet::from(["bind bad".to_string()]);
+	tokio::time::timeout(Duration::from_secs(5), async {
+		while ready.pending() != want {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.unwrap_or_else(|_| panic!("pending tasks: {:?}", ready.pending()));
+	// Bind errors are not fatal by default, so the gateway keeps running
+	assert!(!gw.is_finished());
+	gw.abort();
+}
+
+#[tokio::test]
+async fn fatal_bind_error() {
+	let taken = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let address = taken.local_addr().unwrap();
+	let t = setup_config("config: {fatalBindErrors: true}")
+		.unwrap()
+		.with_bind(Bind {
+			address,
+			..simple_bind(basic_route(address))
+		});
+	let ready = agent_core::readiness::Ready::new();
+	let task = ready.register_task("gateway");
+	let res = tokio::time::timeout(
+		Duration::from_secs(5),
+		Gateway::new(t.pi.clone(), t.drain_rx.clone()).run(task),
+	)
+	.await
+	.expect("a failed bind should stop the gateway");
+	let err = res.unwrap_err();
+	assert!(err.to_string().contains(&address.to_string()), "{err}");
 }
 
 async fn send_request(io: Client<MemoryConnector, Body>, method: Method, url: &str) -> Response {
@@ -151,6 +284,9 @@ fn simple_bind(route: Route) -> Bind {
 			tcp_routes: Default::default(),
 			routes: RouteSet::from_list(vec![route]),
 		}]),
+		proxy_protocol: None,
+		max_connections: None,
+		rate_limit: None,
 	}
 }
 
@@ -264,11 +400,27 @@ impl TestBind {
 }
 
 fn setup() -> anyhow::Result<TestBind> {
+	setup_config("{}")
+}
+
+fn setup_config(config: &str) -> anyhow::Result<TestBind> {
 	agent_core::telemetry::testing::setup_test_logging();
-	let config = crate::config::parse_config("{}".to_string(), None)?;
+	let config = crate::config::parse_config(config.to_string(), None)?;
 	let stores = Stores::new();
 	let client = client::Client::new(&config.dns, None);
 	let (drain_tx, drain_rx) = drain::new();
+	let inflight = Arc::new(crate::proxy::inflight::InFlight::default());
+	let mcp_state = mcp::sse::App::new(
+		&config,
+		stores.clone(),
+		Arc::new(crate::mcp::relay::metrics::Metrics::new(
+			&mut Registry::default(),
+			None, // TODO custom tags
+		)),
+		client.clone(),
+		drain_rx.clone(),
+		inflight.clone(),
+	);
 	let pi = Arc::new(ProxyInputs {
 		cfg: Arc::new(config),
 		stores: stores.clone(),
@@ -278,16 +430,9 @@ fn setup() -> anyhow::Result<TestBind> {
 		))),
 		upstream: client.clone(),
 		ca: None,
+		inflight,
 
-		mcp_state: mcp::sse::App::new(
-			stores.clone(),
-			Arc::new(crate::mcp::relay::metrics::Metrics::new(
-				&mut Registry::default(),
-				None, // TODO custom tags
-			)),
-			client.clone(),
-			drain_rx.clone(),
-		),
+		mcp_state,
 	});
 	Ok(TestBind {
 		pi,
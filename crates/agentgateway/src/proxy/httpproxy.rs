@@ -53,7 +53,7 @@ use crate::http::{
 use crate::llm::{LLMRequest, LLMResponse, RequestResult};
 use crate::proxy::ProxyError;
 use crate::store::{BackendPolicies, Event, LLMRoutePolicies};
-use crate::telemetry::log::{AsyncLog, LogBody, RequestLog};
+use crate::telemetry::log::{AsyncLog, LogBody, REQUEST_ID_HEADER, RequestId, RequestLog};
 use crate::telemetry::trc::TraceParent;
 use crate::transport::stream::{Extension, Socket, TCPConnectionInfo, TLSConnectionInfo};
 use crate::types::agent;
@@ -89,8 +89,9 @@ async fn apply_request_policies(
 	}
 
 	for lrl in &policies.local_rate_limit {
-		if !lrl.check_request(req) {
-			return Err(ProxyError::RateLimitExceeded);
+		if let Err(retry_after) = lrl.check_request(req) {
+			log.record_rate_limited();
+			return Err(ProxyError::RateLimitExceeded(Some(retry_after)));
 		}
 	}
 
@@ -108,8 +109,8 @@ fn apply_llm_request_policies(
 	req: &LLMRequest,
 ) -> Result<(), ProxyError> {
 	for lrl in &policies.local_rate_limit {
-		if !lrl.check_llm_request(req) {
-			return Err(ProxyError::RateLimitExceeded);
+		if let Err(retry_after) = lrl.check_llm_request(req) {
+			return Err(ProxyError::RateLimitExceeded(Some(retry_after)));
 		}
 	}
 	Ok(())
@@ -240,11 +241,23 @@ impl HTTPProxy {
 		connection: Arc<Extension>,
 		req: ::http::Request<Incoming>,
 	) -> Response {
+		let request_id = RequestId::from_headers(req.headers());
+		let span = info_span!("request", id = %request_id);
 		let mut log: RequestLog = Default::default();
-		let ret = self.proxy_internal(connection, req, &mut log).await;
+		log.request_id = Some(request_id.clone());
+		let ret = self
+			.proxy_internal(connection, req, &mut log)
+			.instrument(span)
+			.await;
 
 		log.error = ret.as_ref().err().map(|e| e.to_string());
-		let resp = ret.unwrap_or_else(|err| err.as_response());
+		let mut resp = ret.unwrap_or_else(|err| match &log.a2a_call {
+			Some(call) => a2a::error_response_for(&err, call),
+			None => err.as_response(),
+		});
+		if !resp.headers().contains_key(REQUEST_ID_HEADER) {
+			request_id.insert_header(resp.headers_mut());
+		}
 
 		// Pass the log into the body so it finishes once the stream is entirely complete.
 		// We will also record trailer info there.
@@ -284,8 +297,12 @@ impl HTTPProxy {
 		normalize_uri(&connection, &mut req).map_err(ProxyError::Processing)?;
 		sensitive_headers(&mut req);
 		let mut req_upgrade = hop_by_hop_headers(&mut req);
+		// Forward the id, so backends can log it as well.
+		if let Some(id) = &log.request_id {
+			id.insert_header(req.headers_mut());
+			req.extensions_mut().insert(id.clone());
+		}
 
-		const ALWAYS_TRACE: bool = false; // todo configurable percentage
 		if let Some(tp) = trc::TraceParent::from_request(&req) {
 			// User has a span
 			// We will make a new span and report
@@ -297,8 +314,8 @@ impl HTTPProxy {
 			ns.insert_header(&mut req);
 			req.extensions_mut().insert(ns.clone());
 			log.outgoing_span = Some(ns);
-		} else if ALWAYS_TRACE {
-			log.tracer = self.inputs.tracer.clone();
+		} else if let Some(tracer) = self.inputs.tracer.as_ref().filter(|t| t.sample_new_trace()) {
+			log.tracer = Some(tracer.clone());
 			let mut ns = TraceParent::new();
 			ns.flags = 1;
 			ns.insert_header(&mut req);
@@ -320,6 +337,19 @@ impl HTTPProxy {
 
 		debug!(bind=%bind_name, listener=%selected_listener.key, "selected listener");
 
+		// A2A clients expect JSON-RPC errors, so record the call before anything can fail. The method
+		// and id are filled in once the body is read.
+		let a2a_listener = req.method() == ::http::Method::POST
+			&& inputs
+				.stores
+				.read_binds()
+				.serves_a2a(selected_listener.routes.iter());
+		if a2a_listener {
+			log.a2a_call = Some(a2a::Call {
+				method: "unknown",
+				id: None,
+			});
+		}
 		let (selected_route, path_match) = http::route::select_best_route(
 			inputs.stores.clone(),
 			inputs.cfg.network.clone(),
@@ -331,6 +361,9 @@ impl HTTPProxy {
 		.ok_or(ProxyError::RouteNotFound)?;
 		log.route_rule_name = selected_route.rule_name.clone();
 		log.route_name = Some(selected_route.route_name.clone());
+		if a2a_listener && !inputs.stores.read_binds().serves_a2a([selected_route.as_ref()]) {
+			log.a2a_call = None;
+		}
 
 		debug!(bind=%bind_name, listener=%selected_listener.key, route=%selected_route.key, "selected route");
 
@@ -509,6 +542,13 @@ impl HTTPProxy {
 		let override_dest = maybe_inference.mutate_request(&mut req).await?;
 		log.inference_pool = override_dest;
 
+		// MCP backends terminate WebSocket upgrades themselves rather than forwarding them upstream.
+		if let Backend::MCP(..) = &selected_backend.backend
+			&& let Some(upgrade) = req_upgrade.take()
+		{
+			req.extensions_mut().insert(upgrade.upgrade);
+		}
+
 		let call = make_backend_call(
 			self.inputs.clone(),
 			&route_policies,
@@ -545,6 +585,9 @@ impl HTTPProxy {
 			},
 		};
 		if resp.status() == StatusCode::SWITCHING_PROTOCOLS {
+			if let Backend::MCP(..) = &selected_backend.backend {
+				return Ok(resp);
+			}
 			return handle_upgrade(req_upgrade, resp).await;
 		}
 
@@ -683,6 +726,7 @@ async fn make_backend_call(
 					Some(BackendPolicies {
 						backend_tls: None,
 						backend_auth: None,
+						outbound_proxy: None,
 						a2a: None,
 						llm: None,
 						// Attach LLM provider, but don't use default setup
@@ -789,10 +833,20 @@ async fn make_backend_call(
 	};
 	// Apply auth before LLM request setup, so the providers can assume auth is in standardized header
 	auth::apply_backend_auth(policies.backend_auth.as_ref(), &mut req).await?;
-	let a2a_type = a2a::apply_to_request(policies.a2a.as_ref(), &mut req).await;
-	if let a2a::RequestType::Call(method) = a2a_type {
-		log.add(|l| l.a2a_method = Some(method));
+	let a2a_type = match a2a::apply_to_request(policies.a2a.as_ref(), &mut req).await? {
+		a2a::RequestType::Rejected(dr) => return Ok(Box::pin(async move { Ok(dr) })),
+		t => t,
+	};
+	if let a2a::RequestType::Call(call) = &a2a_type {
+		log.add(|l| l.a2a_call = Some(call.clone()));
 	}
+	let a2a_authz = match &a2a_type {
+		a2a::RequestType::AgentCard(..) => Some(a2a::SkillAuthorization {
+			backend: backend.name(),
+			rules: inputs.stores.read_binds().mcp_policies(backend.name()).0,
+		}),
+		_ => None,
+	};
 	if let Some((llm, true)) = &policies.llm_provider {
 		llm
 			.setup_request(&mut req)
@@ -800,14 +854,22 @@ async fn make_backend_call(
 	}
 	let (mut req, llm_request) = if let Some((llm, _)) = &policies.llm_provider {
 		let r = llm
-			.process_request(client, policies.llm.as_ref(), req)
+			.process_request(
+				client,
+				policies.llm.as_ref(),
+				policies.outbound_proxy.as_ref(),
+				req,
+			)
 			.await
 			.map_err(|e| ProxyError::Processing(e.into()))?;
 		let (mut req, llm_request) = match r {
 			RequestResult::Success(r, lr) => (r, lr),
 			RequestResult::Rejected(dr) => return Ok(Box::pin(async move { Ok(dr) })),
 		};
-		apply_llm_request_policies(route_policies, &llm_request)?;
+		if let Err(e) = apply_llm_request_policies(route_policies, &llm_request) {
+			log.add(|l| l.record_rate_limited());
+			return Err(e);
+		}
 		log.add(|l| l.llm_request = Some(llm_request.clone()));
 		(req, Some(llm_request))
 	} else {
@@ -820,15 +882,26 @@ async fn make_backend_call(
 		req,
 		target: backend_call.target,
 		transport,
+		proxy: policies.outbound_proxy.clone(),
 	};
 	let mut upstream = inputs.upstream.clone();
 	let llm_response_log = log.map(|l| l.llm_response.clone());
 	let rate_limit = route_policies.local_rate_limit.clone();
+	// A2A calls are waited for when shutting down
+	let inflight = matches!(a2a_type, a2a::RequestType::Call(_)).then(|| inputs.inflight.start());
+	let metrics = inputs.metrics.clone();
 	Ok(Box::pin(async move {
+		let _inflight = inflight;
 		let mut resp = upstream.call(call).await?;
-		a2a::apply_to_response(policies.a2a.as_ref(), a2a_type, &mut resp)
-			.await
-			.map_err(ProxyError::Processing)?;
+		a2a::apply_to_response(
+			policies.a2a.as_ref(),
+			a2a_type,
+			a2a_authz,
+			Some(metrics),
+			&mut resp,
+		)
+		.await
+		.map_err(ProxyError::Processing)?;
 		let resp = if let (Some((llm, _)), Some(llm_request)) = (policies.llm_provider, llm_request) {
 			llm
 				.process_response(
@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::sync::Notify;
+
+use crate::*;
+
+/// Counts the MCP tool calls and A2A calls being served, so a shutdown can wait for them to finish
+/// before closing the long-lived streams they are made on.
+#[derive(Debug, Default)]
+pub struct InFlight {
+	count: AtomicUsize,
+	idle: Notify,
+	parent: Option<Arc<InFlight>>,
+}
+
+impl InFlight {
+	/// A tracker for a single session. Its calls are also counted by `self`, but waiting on it only
+	/// waits for the session's own calls.
+	pub fn child(self: &Arc<Self>) -> Arc<Self> {
+		Arc::new(InFlight {
+			count: AtomicUsize::new(0),
+			idle: Notify::new(),
+			parent: Some(self.clone()),
+		})
+	}
+
+	/// Marks a call as started; it is finished when the guard is dropped.
+	pub fn start(self: &Arc<Self>) -> Guard {
+		let mut next = Some(self);
+		while let Some(inflight) = next {
+			inflight.count.fetch_add(1, Ordering::SeqCst);
+			next = inflight.parent.as_ref();
+		}
+		Guard(self.clone())
+	}
+
+	/// The number of calls currently being served.
+	pub fn count(&self) -> usize {
+		self.count.load(Ordering::SeqCst)
+	}
+
+	/// Waits until no calls are being served.
+	pub async fn wait_idle(&self) {
+		loop {
+			let notified = self.idle.notified();
+			tokio::pin!(notified);
+			// Register before checking the count, so a call finishing in between is not missed
+			notified.as_mut().enable();
+			if self.count() == 0 {
+				return;
+			}
+			notified.await;
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct Guard(Arc<InFlight>);
+
+impl Drop for Guard {
+	fn drop(&mut self) {
+		let mut next = Some(&self.0);
+		while let Some(inflight) = next {
+			if inflight.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+				inflight.idle.notify_waiters();
+			}
+			next = inflight.parent.as_ref();
+		}
+	}
+}
+
+#[cfg(test)]
+#[path = "inflight_tests.rs"]
+mod tests;
@@ -0,0 +1,48 @@
+use futures_util::FutureExt;
+
+use super::*;
+
+#[tokio::test]
+async fn test_wait_idle() {
+	let inflight = Arc::new(InFlight::default());
+	// Nothing is in flight yet
+	inflight.wait_idle().now_or_never().expect("idle");
+
+	let first = inflight.start();
+	let second = inflight.start();
+	assert_eq!(inflight.count(), 2);
+	let wait = tokio::spawn({
+		let inflight = inflight.clone();
+		async move { inflight.wait_idle().await }
+	});
+
+	drop(first);
+	tokio::time::sleep(Duration::from_millis(10)).await;
+	assert!(!wait.is_finished(), "a call is still in flight");
+
+	drop(second);
+	tokio::time::timeout(Duration::from_secs(1), wait)
+		.await
+		.expect("idle once the calls finish")
+		.unwrap();
+	assert_eq!(inflight.count(), 0);
+}
+
+#[tokio::test]
+async fn test_child() {
+	let inflight = Arc::new(InFlight::default());
+	let busy = inflight.child();
+	let idle = inflight.child();
+
+	let call = busy.start();
+	assert_eq!(inflight.count(), 1);
+	assert_eq!(busy.count(), 1);
+	// Another session's call does not hold this one open
+	idle.wait_idle().now_or_never().expect("idle");
+	assert!(busy.wait_idle().now_or_never().is_none());
+	assert!(inflight.wait_idle().now_or_never().is_none());
+
+	drop(call);
+	busy.wait_idle().now_or_never().expect("idle");
+	inflight.wait_idle().now_or_never().expect("idle");
+}
@@ -1,5 +1,6 @@
 mod gateway;
 pub mod httpproxy;
+pub mod inflight;
 #[cfg(test)]
 pub mod request_builder;
 pub mod tcpproxy;
@@ -55,11 +56,13 @@ pub enum ProxyError {
 	#[error("processing failed: {0}")]
 	ProcessingString(String),
 	#[error("rate limit exceeded")]
-	RateLimitExceeded,
+	RateLimitExceeded(Option<Duration>),
 	#[error("rate limit failed")]
 	RateLimitFailed,
 	#[error("invalid request")]
 	InvalidRequest,
+	#[error("request body too large")]
+	RequestTooLarge,
 	#[error("request upgrade failed, backend tried {1:?} but {0:?} was requested")]
 	UpgradeFailed(Option<HeaderValue>, Option<HeaderValue>),
 }
@@ -91,6 +94,7 @@ impl ProxyError {
 			// Should it be 4xx?
 			ProxyError::FilterError(_) => StatusCode::INTERNAL_SERVER_ERROR,
 			ProxyError::InvalidRequest => StatusCode::BAD_REQUEST,
+			ProxyError::RequestTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
 
 			ProxyError::JwtAuthenticationFailure(_) => StatusCode::FORBIDDEN,
 			ProxyError::AuthorizationFailed => StatusCode::FORBIDDEN,
@@ -102,15 +106,19 @@ impl ProxyError {
 			ProxyError::RequestTimeout => StatusCode::GATEWAY_TIMEOUT,
 			ProxyError::Processing(_) => StatusCode::SERVICE_UNAVAILABLE,
 			ProxyError::ProcessingString(_) => StatusCode::SERVICE_UNAVAILABLE,
-			ProxyError::RateLimitExceeded => StatusCode::TOO_MANY_REQUESTS,
+			ProxyError::RateLimitExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
 			ProxyError::RateLimitFailed => StatusCode::TOO_MANY_REQUESTS,
 		};
 		let msg = self.to_string();
-		::http::Response::builder()
+		let mut rb = ::http::Response::builder()
 			.status(code)
-			.header(hyper::header::CONTENT_TYPE, "text/plain")
-			.body(http::Body::from(msg))
-			.unwrap()
+			.header(hyper::header::CONTENT_TYPE, "text/plain");
+		if let ProxyError::RateLimitExceeded(Some(retry_after)) = self {
+			// Retry-After only has second granularity; round up so clients don't retry too early.
+			let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+			rb = rb.header(hyper::header::RETRY_AFTER, secs.max(1));
+		}
+		rb.body(http::Body::from(msg)).unwrap()
 	}
 }
 
@@ -16,8 +16,11 @@ use std::{fs, io};
 pub mod yamlviajson {
 	use futures_util::AsyncReadExt;
 	use serde::{Deserialize, de, ser};
+	use serde_path_to_error::Segment;
 	use serde_yaml::to_writer;
 
+	/// Errors name the path of the offending field, such as `binds[0].listeners[1].protocol`, and
+	/// where it is in the YAML source.
 	pub fn from_str<T>(s: &str) -> anyhow::Result<T>
 	where
 		T: for<'de> de::Deserialize<'de>,
@@ -28,7 +31,163 @@ pub mod yamlviajson {
 			let mut se_json = serde_json::Serializer::new(&mut buf);
 			serde_transcode::transcode(de_yaml, &mut se_json)?;
 		} // se_json is dropped here, releasing the mutable borrow on buf
-		Ok(serde_json_path_to_error::from_slice(&buf)?)
+		let mut de_json = serde_json::Deserializer::from_slice(&buf);
+		serde_path_to_error::deserialize(&mut de_json).map_err(|e| {
+			let mut path: Vec<Step> = e
+				.path()
+				.iter()
+				.map_while(|segment| match segment {
+					Segment::Seq { index } => Some(Step::Index(*index)),
+					Segment::Map { key } | Segment::Enum { variant: key } => Some(Step::Key(key.clone())),
+					Segment::Unknown => None,
+				})
+				.collect();
+			// The position in the transcoded JSON means nothing to the user
+			let inner = e.inner().to_string();
+			let message = match inner.rsplit_once(" at line ") {
+				Some((message, _)) => message.to_string(),
+				None => inner,
+			};
+			// Point at the unknown field itself, rather than the object holding it. Depending on the
+			// version, the path may already end with it.
+			if let Some((field, _)) = message
+				.strip_prefix("unknown field `")
+				.and_then(|m| m.split_once('`'))
+				&& !matches!(path.last(), Some(Step::Key(key)) if key == field)
+			{
+				path.push(Step::Key(field.to_string()));
+			}
+			let location = locate(s, &path)
+				.map(|l| format!(" at line {} column {}", l.line(), l.column()))
+				.unwrap_or_default();
+			if path.is_empty() {
+				anyhow::anyhow!("{message}{location}")
+			} else {
+				anyhow::anyhow!("{}: {message}{location}", path_string(&path))
+			}
+		})
+	}
+
+	/// A step of the path to a value: an index in a sequence, or a key in a mapping.
+	enum Step {
+		Index(usize),
+		Key(String),
+	}
+
+	/// Formats a path like `binds[0].listeners`.
+	fn path_string(path: &[Step]) -> String {
+		let mut s = String::new();
+		for step in path {
+			match step {
+				Step::Index(index) => s.push_str(&format!("[{index}]")),
+				Step::Key(key) => {
+					if !s.is_empty() {
+						s.push('.');
+					}
+					s.push_str(key);
+				},
+			}
+		}
+		s
+	}
+
+	/// Finds where the value at `path` starts in the YAML source. The YAML is walked down to the
+	/// value, which then fails to deserialize on purpose, so the YAML deserializer reports where it is.
+	fn locate(s: &str, path: &[Step]) -> Option<serde_yaml::Location> {
+		let de_yaml = serde_yaml::Deserializer::from_str(s);
+		de::DeserializeSeed::deserialize(Locate(path), de_yaml)
+			.err()
+			.and_then(|e| e.location())
+	}
+
+	struct Locate<'a>(&'a [Step]);
+
+	impl Locate<'_> {
+		/// Fails if this is the value looked for. Otherwise the path does not exist.
+		fn found<E: de::Error>(&self) -> Result<(), E> {
+			if self.0.is_empty() {
+				Err(E::custom("found"))
+			} else {
+				Ok(())
+			}
+		}
+	}
+
+	impl<'de> de::DeserializeSeed<'de> for Locate<'_> {
+		type Value = ();
+
+		fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+			deserializer.deserialize_any(self)
+		}
+	}
+
+	// Mappings and sequences are read to the end even once the path is not found, or the YAML
+	// deserializer would report them as too long.
+	impl<'de> de::Visitor<'de> for Locate<'_> {
+		type Value = ();
+
+		fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+			f.write_str("any value")
+		}
+
+		fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+			self.found()?;
+			let key = match &self.0[0] {
+				Step::Key(key) => Some(key),
+				Step::Index(_) => None,
+			};
+			while let Some(k) = map.next_key::<String>()? {
+				if key == Some(&k) {
+					map.next_value_seed(Locate(&self.0[1..]))?;
+				} else {
+					map.next_value::<de::IgnoredAny>()?;
+				}
+			}
+			Ok(())
+		}
+
+		fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+			self.found()?;
+			let index = match &self.0[0] {
+				Step::Index(index) => Some(*index),
+				Step::Key(_) => None,
+			};
+			for i in 0.. {
+				let next = if index == Some(i) {
+					seq.next_element_seed(Locate(&self.0[1..]))?
+				} else {
+					seq.next_element::<de::IgnoredAny>()?.map(|_| ())
+				};
+				if next.is_none() {
+					break;
+				}
+			}
+			Ok(())
+		}
+
+		fn visit_bool<E: de::Error>(self, _: bool) -> Result<(), E> {
+			self.found()
+		}
+
+		fn visit_i64<E: de::Error>(self, _: i64) -> Result<(), E> {
+			self.found()
+		}
+
+		fn visit_u64<E: de::Error>(self, _: u64) -> Result<(), E> {
+			self.found()
+		}
+
+		fn visit_f64<E: de::Error>(self, _: f64) -> Result<(), E> {
+			self.found()
+		}
+
+		fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> {
+			self.found()
+		}
+
+		fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+			self.found()
+		}
 	}
 
 	pub fn to_string<T>(value: &T) -> anyhow::Result<String>
@@ -48,6 +207,66 @@ pub fn is_default<T: Default + PartialEq>(t: &T) -> bool {
 	*t == Default::default()
 }
 
+/// Returns the names serde accepts for the variants of enum `T`.
+pub fn variants<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+	introspect::<T>().0
+}
+
+/// Returns the names serde accepts for the fields of struct `T`.
+pub fn fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+	introspect::<T>().1
+}
+
+fn introspect<'de, T: Deserialize<'de>>() -> (&'static [&'static str], &'static [&'static str]) {
+	// A deserializer that records the names it is asked for and then bails out.
+	struct Introspect<'a> {
+		variants: &'a mut &'static [&'static str],
+		fields: &'a mut &'static [&'static str],
+	}
+
+	impl<'de> Deserializer<'de> for Introspect<'_> {
+		type Error = serde::de::value::Error;
+
+		fn deserialize_any<V: serde::de::Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+			Err(serde::de::Error::custom("introspection"))
+		}
+
+		fn deserialize_enum<V: serde::de::Visitor<'de>>(
+			self,
+			_: &'static str,
+			variants: &'static [&'static str],
+			_: V,
+		) -> Result<V::Value, Self::Error> {
+			*self.variants = variants;
+			Err(serde::de::Error::custom("introspection"))
+		}
+
+		fn deserialize_struct<V: serde::de::Visitor<'de>>(
+			self,
+			_: &'static str,
+			fields: &'static [&'static str],
+			_: V,
+		) -> Result<V::Value, Self::Error> {
+			*self.fields = fields;
+			Err(serde::de::Error::custom("introspection"))
+		}
+
+		serde::forward_to_deserialize_any! {
+			bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+			bytes byte_buf option unit unit_struct newtype_struct seq tuple
+			tuple_struct map identifier ignored_any
+		}
+	}
+
+	let mut variants: &'static [&'static str] = &[];
+	let mut fields: &'static [&'static str] = &[];
+	let _ = T::deserialize(Introspect {
+		variants: &mut variants,
+		fields: &mut fields,
+	});
+	(variants, fields)
+}
+
 pub mod serde_dur {
 	use std::fmt::Display;
 
@@ -168,6 +387,18 @@ where
 	}
 }
 
+pub fn de_parse_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+	D: Deserializer<'de>,
+	T: for<'a> TryFrom<&'a str>,
+	for<'a> <T as TryFrom<&'a str>>::Error: Display,
+{
+	Vec::<String>::deserialize(deserializer)?
+		.iter()
+		.map(|s| T::try_from(s.as_str()).map_err(serde::de::Error::custom))
+		.collect()
+}
+
 pub fn de_bytes<S: Serializer, T: AsRef<[u8]>>(t: &T, serializer: S) -> Result<S::Ok, S::Error> {
 	let b = t.as_ref();
 	if let Ok(s) = std::str::from_utf8(b) {
@@ -189,6 +420,19 @@ where
 	Ok(SecretString::from(k.trim().to_string()))
 }
 
+pub fn deser_key_from_file_option<'de, D>(deserializer: D) -> Result<Option<SecretString>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let Some(input) = Option::<FileOrInline>::deserialize(deserializer)? else {
+		return Ok(None);
+	};
+	let k = input
+		.load()
+		.map_err(|e| serde::de::Error::custom(e.to_string()))?;
+	Ok(Some(SecretString::from(k.trim().to_string())))
+}
+
 pub fn de_as<'de, I, O, D>(deserializer: D) -> Result<O, D::Error>
 where
 	D: Deserializer<'de>,
@@ -219,6 +463,7 @@ where
 #[serde(untagged)]
 pub enum FileOrInline {
 	File { file: PathBuf },
+	Env { env: String },
 	Inline(String),
 }
 
@@ -226,6 +471,12 @@ impl FileOrInline {
 	pub fn load(&self) -> io::Result<String> {
 		match self {
 			FileOrInline::File { file } => fs_err::read_to_string(file),
+			FileOrInline::Env { env } => std::env::var(env).map_err(|e| {
+				io::Error::new(
+					io::ErrorKind::NotFound,
+					format!("environment variable {env}: {e}"),
+				)
+			}),
 			FileOrInline::Inline(s) => Ok(s.clone()),
 		}
 	}
@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, mpsc};
 use std::time::Duration;
 
@@ -27,6 +27,10 @@ use crate::types::proto::workload::{
 };
 use crate::{ConfigSource, client, serdes, store};
 
+#[cfg(test)]
+#[path = "state_manager_tests.rs"]
+mod tests;
+
 #[derive(serde::Serialize)]
 pub struct StateManager {
 	#[serde(flatten)]
@@ -46,6 +50,7 @@ pub const ADP_TYPE: Strng =
 impl StateManager {
 	pub async fn new(
 		config: &crate::XDSConfig,
+		stdio_command_allowlist: Vec<String>,
 		client: client::Client,
 		xds_metrics: agent_xds::Metrics,
 		awaiting_ready: tokio::sync::watch::Sender<()>,
@@ -72,6 +77,7 @@ impl StateManager {
 				stores: stores.clone(),
 				cfg: cfg.clone(),
 				client,
+				stdio_command_allowlist,
 			};
 			local_client.run().await?;
 		}
@@ -96,22 +102,26 @@ pub struct LocalClient {
 	pub cfg: ConfigSource,
 	pub stores: Stores,
 	pub client: Client,
+	pub stdio_command_allowlist: Vec<String>,
 }
 
 impl LocalClient {
 	pub async fn run(self) -> Result<(), anyhow::Error> {
-		if let ConfigSource::File(path) = &self.cfg {
+		match &self.cfg {
 			// Load initial state then watch
-			self.watch_config_file(path).await?;
-		} else {
+			ConfigSource::File(path) => self.watch_config_files(std::slice::from_ref(path)).await?,
+			ConfigSource::Files(paths) => self.watch_config_files(paths).await?,
 			// Load it once
-			self.reload_config(PreviousState::default()).await?;
+			ConfigSource::Static(_) => {
+				self.reload_config(PreviousState::default()).await?;
+			},
 		}
 
 		Ok(())
 	}
 
-	async fn watch_config_file(&self, path: &Path) -> anyhow::Result<()> {
+	/// Watches config files, or directories of them, reloading the config on changes.
+	async fn watch_config_files(&self, paths: &[PathBuf]) -> anyhow::Result<()> {
 		let (tx, mut rx) = tokio::sync::mpsc::channel(1);
 
 		// Create a watcher with a 250ms debounce
@@ -123,34 +133,71 @@ impl LocalClient {
 			})
 			.map_err(|e| anyhow::anyhow!("Failed to create file watcher: {}", e))?;
 
-		// Watch the config file
-		watcher
-			.watch(path, RecursiveMode::NonRecursive)
-			.map_err(|e| anyhow::anyhow!("Failed to watch config file: {}", e))?;
+		// Watch the config files. A file is watched through its directory, so it is still seen after
+		// being replaced by a rename, as the UI does. Watching a directory also catches files added to
+		// it.
+		let mut config_dirs = HashSet::new();
+		for path in paths {
+			let dir = if path.is_dir() {
+				path.clone()
+			} else {
+				let path = std::path::absolute(path)?;
+				path.parent().map(Path::to_path_buf).unwrap_or(path)
+			};
+			if config_dirs.insert(dir.clone()) {
+				watcher
+					.watch(&dir, RecursiveMode::NonRecursive)
+					.map_err(|e| anyhow::anyhow!("Failed to watch config file: {}", e))?;
+			}
 
-		info!("Watching config file: {}", path.display());
+			info!("Watching config file: {}", path.display());
+		}
 
 		let lc: LocalClient = self.to_owned();
-		let mut next_state = lc.reload_config(PreviousState::default()).await?;
+		let config_paths = paths
+			.iter()
+			.filter_map(|p| std::path::absolute(p).ok())
+			.collect_vec();
+		let (mut next_state, mut tls_files) = lc.reload_config(PreviousState::default()).await?;
+		// Certificates are typically rotated by replacing the file (or, in Kubernetes, a symlink), which
+		// would break a watch on the file itself. Watch the containing directories instead.
+		let mut tls_dirs = HashSet::new();
+		update_tls_watches(&mut watcher, &mut tls_dirs, &config_dirs, &tls_files);
 		tokio::task::spawn(async move {
 			use notify_debouncer_full::DebouncedEvent;
 			// Handle file change events
 			while let Some(Ok(events)) = rx.recv().await {
-				// Only process if we have actual content changes
-				if events
+				// Only process if we have actual content changes, to the config or the configured
+				// certificates; other files may share a directory with the certificates.
+				let changes = events
 					.iter()
-					.any(|e| matches!(e.kind, EventKind::Modify(_) | EventKind::Create(_)))
-				{
+					.filter(|e| {
+						matches!(
+							e.kind,
+							EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
+						)
+					})
+					.flat_map(|e| e.paths.iter())
+					.filter_map(|p| classify_change(p, &config_paths, &tls_files))
+					.collect_vec();
+				if changes.is_empty() {
+					continue;
+				}
+				if changes.contains(&Change::Config) {
 					info!("Config file changed, reloading...");
-					match lc.reload_config(next_state.clone()).await {
-						Ok(nxt) => {
-							next_state = nxt;
-							info!("Config reloaded successfully")
-						},
-						Err(e) => {
-							error!("Failed to reload config: {}", e)
-						},
-					}
+				} else {
+					info!("TLS certificate changed, reloading...");
+				}
+				match lc.reload_config(next_state.clone()).await {
+					Ok((nxt, files)) => {
+						info!("Config reloaded successfully: {}", next_state.changes(&nxt));
+						next_state = nxt;
+						update_tls_watches(&mut watcher, &mut tls_dirs, &config_dirs, &files);
+						tls_files = files;
+					},
+					Err(e) => {
+						error!("Failed to reload config: {}", e)
+					},
 				}
 			}
 			drop(watcher);
@@ -159,13 +206,14 @@ impl LocalClient {
 		Ok(())
 	}
 
-	async fn reload_config(&self, prev: PreviousState) -> anyhow::Result<PreviousState> {
-		let config_content = self.cfg.read_to_string().await?;
-		let config = crate::types::local::NormalizedLocalConfig::from(
-			self.client.clone(),
-			config_content.as_str(),
-		)
-		.await?;
+	async fn reload_config(
+		&self,
+		prev: PreviousState,
+	) -> anyhow::Result<(PreviousState, Vec<PathBuf>)> {
+		let config =
+			crate::types::local::NormalizedLocalConfig::from_source(self.client.clone(), &self.cfg)
+				.await?;
+		config.check_stdio_commands(&self.stdio_command_allowlist)?;
 		info!("loaded config from {:?}", self.cfg);
 
 		// Sync the state
@@ -180,15 +228,125 @@ impl LocalClient {
 				.discovery
 				.sync_local(config.services, config.workloads, prev.discovery);
 
-		Ok(PreviousState {
-			binds: next_binds,
-			discovery: next_discovery,
-		})
+		Ok((
+			PreviousState {
+				binds: next_binds,
+				discovery: next_discovery,
+			},
+			config.tls_files,
+		))
 	}
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+	Config,
+	Tls,
+}
+
+/// What a changed path is, if it is a config file or a configured TLS file. Kubernetes updates
+/// mounted secrets by swapping the `..data` symlink next to the files, so that counts as a change
+/// to the TLS files in its directory.
+fn classify_change(path: &Path, config_paths: &[PathBuf], tls_files: &[PathBuf]) -> Option<Change> {
+	let path = std::path::absolute(path).ok()?;
+	if config_paths
+		.iter()
+		.any(|c| path == *c || path.parent() == Some(c))
+	{
+		return Some(Change::Config);
+	}
+	let k8s_data = path.file_name() == Some("..data".as_ref());
+	tls_files
+		.iter()
+		.filter_map(|f| std::path::absolute(f).ok())
+		.any(|f| f == path || (k8s_data && f.parent() == path.parent()))
+		.then_some(Change::Tls)
+}
+
+/// Watch the directories containing the given TLS files, and stop watching directories no longer
+/// referenced. Directories watched for the config are left alone.
+fn update_tls_watches<W: Watcher, C: notify_debouncer_full::FileIdCache>(
+	watcher: &mut Debouncer<W, C>,
+	watched: &mut HashSet<PathBuf>,
+	config_dirs: &HashSet<PathBuf>,
+	tls_files: &[PathBuf],
+) {
+	let want: HashSet<PathBuf> = tls_files
+		.iter()
+		.filter_map(|f| std::path::absolute(f).ok())
+		.filter_map(|f| f.parent().map(Path::to_path_buf))
+		.collect();
+	for dir in watched.difference(&want).filter(|d| !config_dirs.contains(*d)) {
+		if let Err(e) = watcher.unwatch(dir) {
+			warn!("failed to stop watching {}: {}", dir.display(), e);
+		}
+	}
+	for dir in want.difference(watched).filter(|d| !config_dirs.contains(*d)) {
+		match watcher.watch(dir, RecursiveMode::NonRecursive) {
+			Ok(()) => info!("Watching TLS certificate directory: {}", dir.display()),
+			Err(e) => warn!("failed to watch {}: {}", dir.display(), e),
+		}
+	}
+	*watched = want;
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct PreviousState {
 	pub binds: store::BindPreviousState,
 	pub discovery: store::DiscoveryPreviousState,
 }
+
+impl PreviousState {
+	/// Summarizes which resources were added or removed going from this state to `next`.
+	/// Resources present in both may still have been updated in place.
+	fn changes(&self, next: &PreviousState) -> String {
+		let summary = [
+			diff("binds", &self.binds.binds, &next.binds.binds),
+			diff("policies", &self.binds.policies, &next.binds.policies),
+			diff("backends", &self.binds.backends, &next.binds.backends),
+			diff(
+				"services",
+				&self.discovery.services,
+				&next.discovery.services,
+			),
+			diff(
+				"workloads",
+				&self.discovery.workloads,
+				&next.discovery.workloads,
+			),
+		]
+		.into_iter()
+		.flatten()
+		.join("; ");
+		if summary.is_empty() {
+			"no resources added or removed".to_string()
+		} else {
+			summary
+		}
+	}
+}
+
+fn diff<T: std::hash::Hash + Eq + std::fmt::Display>(
+	kind: &str,
+	prev: &HashSet<T>,
+	next: &HashSet<T>,
+) -> Option<String> {
+	let added = next
+		.difference(prev)
+		.map(ToString::to_string)
+		.sorted()
+		.collect_vec();
+	let removed = prev
+		.difference(next)
+		.map(ToString::to_string)
+		.sorted()
+		.collect_vec();
+	let mut parts = Vec::new();
+	if !added.is_empty() {
+		parts.push(format!("added {}", added.join(", ")));
+	}
+	if !removed.is_empty() {
+		parts.push(format!("removed {}", removed.join(", ")));
+	}
+	(!parts.is_empty()).then(|| format!("{kind} {}", parts.join(", ")))
+}
@@ -0,0 +1,44 @@
+use super::*;
+
+#[test]
+fn test_previous_state_changes() {
+	let mut prev = PreviousState::default();
+	prev.binds.binds.insert(strng::new("bind/3000"));
+	prev.binds.policies.insert(strng::new("old-policy"));
+	prev.discovery.workloads.insert(strng::new("wl"));
+
+	let mut next = prev.clone();
+	assert_eq!(prev.changes(&next), "no resources added or removed");
+
+	next.binds.binds.insert(strng::new("bind/3001"));
+	next.binds.policies.clear();
+	next.binds.backends.insert(strng::new("b2"));
+	next.binds.backends.insert(strng::new("b1"));
+	assert_eq!(
+		prev.changes(&next),
+		"binds added bind/3001; policies removed old-policy; backends added b1, b2"
+	);
+}
+
+#[test]
+fn test_classify_change() {
+	let config = vec![PathBuf::from("/etc/gateway/config.yaml")];
+	let tls = vec![
+		PathBuf::from("/etc/certs/tls.crt"),
+		PathBuf::from("/etc/certs/tls.key"),
+	];
+	let classify = |p: &str| classify_change(Path::new(p), &config, &tls);
+	assert_eq!(classify("/etc/gateway/config.yaml"), Some(Change::Config));
+	assert_eq!(classify("/etc/certs/tls.crt"), Some(Change::Tls));
+	assert_eq!(classify("/etc/certs/tls.key"), Some(Change::Tls));
+	assert_eq!(classify("/etc/certs/..data"), Some(Change::Tls));
+	// Other files next to the certificates or the config are ignored
+	assert_eq!(classify("/etc/certs/ca.crt"), None);
+	assert_eq!(classify("/etc/certs/.tls.crt.swp"), None);
+	assert_eq!(classify("/etc/gateway/notes.txt"), None);
+
+	// Any file in a config directory is config
+	let config = vec![PathBuf::from("/etc/gateway")];
+	let classify = |p: &str| classify_change(Path::new(p), &config, &tls);
+	assert_eq!(classify("/etc/gateway/routes.yaml"), Some(Change::Config));
+}
@@ -10,6 +10,7 @@ use tracing::{Level, instrument};
 
 use crate::http::auth::BackendAuth;
 use crate::http::backendtls::BackendTLS;
+use crate::http::outboundproxy::OutboundProxy;
 use crate::http::{ext_authz, remoteratelimit};
 use crate::mcp::rbac::{RuleSet, RuleSets};
 use crate::store::Event;
@@ -40,6 +41,7 @@ pub struct Store {
 pub struct BackendPolicies {
 	pub backend_tls: Option<BackendTLS>,
 	pub backend_auth: Option<BackendAuth>,
+	pub outbound_proxy: Option<OutboundProxy>,
 	pub a2a: Option<A2aPolicy>,
 	// bool represents "should use default settings for provider"
 	pub llm_provider: Option<(llm::AIProvider, bool)>,
@@ -52,6 +54,7 @@ impl BackendPolicies {
 		Self {
 			backend_tls: other.backend_tls.or(self.backend_tls),
 			backend_auth: other.backend_auth.or(self.backend_auth),
+			outbound_proxy: other.outbound_proxy.or(self.outbound_proxy),
 			a2a: other.a2a.or(self.a2a),
 			llm: other.llm.or(self.llm),
 			llm_provider: other.llm_provider.or(self.llm_provider),
@@ -219,6 +222,20 @@ impl Store {
 				}
 			})
 			.next();
+		let outbound_proxy = self
+			// This is a terrible approach!
+			.policies_by_name
+			.values()
+			.filter_map(|p| {
+				if p.target != tgt {
+					return None;
+				};
+				match &p.policy {
+					Policy::OutboundProxy(op) => Some(op.clone()),
+					_ => None,
+				}
+			})
+			.next();
 		let a2a = self
 			// This is a terrible approach!
 			.policies_by_name
@@ -250,6 +267,7 @@ impl Store {
 		BackendPolicies {
 			backend_tls: tls,
 			backend_auth: auth,
+			outbound_proxy,
 			a2a,
 			llm,
 			// These are not attached policies but are represented in this struct for code organization
@@ -257,6 +275,19 @@ impl Store {
 		}
 	}
 
+	/// Reports whether any backend of the given routes has an A2A policy attached. This lets errors
+	/// that occur before a backend is known still be answered in JSON-RPC.
+	pub fn serves_a2a<'a>(&self, routes: impl IntoIterator<Item = &'a Route>) -> bool {
+		let backends: HashSet<BackendName> = routes
+			.into_iter()
+			.flat_map(|r| r.backends.iter().map(|b| b.backend.name()))
+			.collect();
+		self.policies_by_name.values().any(|p| match (&p.target, &p.policy) {
+			(PolicyTarget::Backend(b), Policy::A2a(_)) => backends.contains(b),
+			_ => false,
+		})
+	}
+
 	pub fn mcp_policies(&self, backend: BackendName) -> (RuleSets, Option<McpAuthentication>) {
 		let t = PolicyTarget::Backend(backend);
 		let rs = RuleSets::from(
@@ -296,6 +327,10 @@ impl Store {
 		self.by_name.get(&bind).map(|b| b.listeners.clone())
 	}
 
+	pub fn bind(&self, bind: &BindName) -> Option<Arc<Bind>> {
+		self.by_name.get(bind).cloned()
+	}
+
 	pub fn all(&self) -> Vec<Arc<Bind>> {
 		self.by_name.values().cloned().collect()
 	}
@@ -5,6 +5,8 @@ use std::sync::Arc;
 use std::task::{Context, Poll, ready};
 use std::time::{Instant, SystemTime};
 
+use ::http::{HeaderMap, HeaderName, HeaderValue};
+use agent_core::strng::{self, Strng};
 use crossbeam::atomic::AtomicCell;
 use http_body::{Body, Frame, SizeHint};
 use tracing::event;
@@ -16,7 +18,7 @@ use crate::types::agent::{
 	BackendName, GatewayName, ListenerName, RouteName, RouteRuleName, Target,
 };
 use crate::types::discovery::NamespacedHostname;
-use crate::{llm, mcp};
+use crate::{a2a, llm, mcp};
 
 /// AsyncLog is a wrapper around an item that can be atomically set.
 /// The intent is to provide additional info to the log after we have lost the RequestLog reference,
@@ -65,12 +67,54 @@ impl<T: Debug> Debug for AsyncLog<T> {
 	}
 }
 
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+// Longer incoming ids are replaced with a generated one, to keep log lines bounded.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// RequestId correlates the logs and traces of a single request, including the calls made to
+/// backends on its behalf. It is taken from the incoming `x-request-id` header when present.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(Strng);
+
+impl RequestId {
+	pub fn from_headers(headers: &HeaderMap) -> Self {
+		headers
+			.get(REQUEST_ID_HEADER)
+			.and_then(|v| v.to_str().ok())
+			.filter(|v| !v.is_empty() && v.len() <= MAX_REQUEST_ID_LEN)
+			.map(|v| RequestId(strng::new(v)))
+			.unwrap_or_else(Self::generate)
+	}
+
+	pub fn generate() -> Self {
+		RequestId(strng::new(format!("{:032x}", rand::random::<u128>())))
+	}
+
+	pub fn as_str(&self) -> &str {
+		self.0.as_str()
+	}
+
+	/// Sets the `x-request-id` header, replacing any existing value.
+	pub fn insert_header(&self, headers: &mut HeaderMap) {
+		if let Ok(v) = HeaderValue::from_str(self.as_str()) {
+			headers.insert(REQUEST_ID_HEADER, v);
+		}
+	}
+}
+
+impl std::fmt::Display for RequestId {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
 #[derive(Default, Debug)]
 pub struct RequestLog {
 	pub tracer: Option<trc::Tracer>,
 	pub metrics: Option<Arc<Metrics>>,
 
 	pub start: Option<Instant>,
+	pub request_id: Option<RequestId>,
 	pub tcp_info: Option<TCPConnectionInfo>,
 	pub tls_info: Option<TLSConnectionInfo>,
 
@@ -102,11 +146,29 @@ pub struct RequestLog {
 	pub llm_request: Option<llm::LLMRequest>,
 	pub llm_response: AsyncLog<llm::LLMResponse>,
 
-	pub a2a_method: Option<&'static str>,
+	pub a2a_call: Option<a2a::Call>,
 
 	pub inference_pool: Option<SocketAddr>,
 }
 
+impl RequestLog {
+	pub fn record_rate_limited(&self) {
+		if let Some(m) = &self.metrics {
+			m.rate_limited_requests
+				.get_or_create(&CommonTrafficLabels {
+					gateway: (&self.gateway_name).into(),
+					listener: (&self.listener_name).into(),
+					route: (&self.route_name).into(),
+					route_rule: (&self.route_rule_name).into(),
+					backend: (&self.backend_name).into(),
+					method: self.method.clone().into(),
+					status: Default::default(),
+				})
+				.inc();
+		}
+	}
+}
+
 impl Drop for RequestLog {
 	fn drop(&mut self) {
 		let tcp_info = self.tcp_info.as_ref().expect("tODO");
@@ -157,6 +219,7 @@ impl Drop for RequestLog {
 			endpoint = self.endpoint.as_ref().map(display),
 
 			src.addr = %tcp_info.peer_addr,
+			request.id = self.request_id.as_ref().map(display),
 
 			http.method = self.method.as_ref().map(display),
 			http.host = self.host.as_ref().map(display),
@@ -171,7 +234,7 @@ impl Drop for RequestLog {
 
 			jwt.sub = self.jwt_sub,
 
-			a2a.method = self.a2a_method.as_ref().map(display),
+			a2a.method = self.a2a_call.as_ref().map(|c| display(c.method)),
 
 			mcp.target = mcp.as_ref().and_then(|m| m.target_name.as_ref()).map(display),
 			mcp.tool = mcp.as_ref().and_then(|m| m.tool_call_name.as_ref()).map(display),
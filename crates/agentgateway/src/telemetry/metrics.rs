@@ -1,6 +1,6 @@
 use agent_core::metrics::{DefaultedUnknown, EncodeDisplay};
 use agent_core::strng::RichStrng;
-use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
 use prometheus_client::metrics::family::Family;
 use prometheus_client::registry::Registry;
 
@@ -16,11 +16,39 @@ pub struct CommonTrafficLabels {
 	pub status: DefaultedUnknown<EncodeDisplay<u16>>,
 }
 
+#[derive(Clone, Hash, Default, Debug, PartialEq, Eq, EncodeLabelSet)]
+pub struct BindLabels {
+	pub bind: DefaultedUnknown<RichStrng>,
+}
+
+#[derive(Clone, Hash, Debug, PartialEq, Eq, EncodeLabelSet)]
+pub struct JwtCacheLabels {
+	pub result: JwtCacheResult,
+}
+
+#[derive(Copy, Clone, Hash, Debug, PartialEq, Eq, EncodeLabelValue)]
+pub enum JwtCacheResult {
+	Hit,
+	Miss,
+}
+
+#[derive(Clone, Hash, Debug, PartialEq, Eq, EncodeLabelSet)]
+pub struct A2aTaskTransitionLabels {
+	pub from: EncodeDisplay<a2a_sdk::TaskState>,
+	pub to: EncodeDisplay<a2a_sdk::TaskState>,
+}
+
 type Counter = Family<CommonTrafficLabels, prometheus_client::metrics::counter::Counter>;
 
 #[derive(Debug)]
 pub struct Metrics {
 	pub requests: Counter,
+	pub rate_limited_requests: Counter,
+	pub rejected_connections: Family<BindLabels, prometheus_client::metrics::counter::Counter>,
+	pub rate_limited_bind_requests: Family<BindLabels, prometheus_client::metrics::counter::Counter>,
+	pub jwt_cache_lookups: Family<JwtCacheLabels, prometheus_client::metrics::counter::Counter>,
+	pub a2a_invalid_task_transitions:
+		Family<A2aTaskTransitionLabels, prometheus_client::metrics::counter::Counter>,
 }
 
 impl Metrics {
@@ -30,8 +58,42 @@ impl Metrics {
 			registry.register(name, help, m.clone());
 			m
 		};
+		let requests = build("requests", "The total number of HTTP requests sent");
+		let rate_limited_requests = build(
+			"rate_limited_requests",
+			"The total number of HTTP requests rejected by a local rate limit",
+		);
+		let rejected_connections = Family::default();
+		registry.register(
+			"rejected_connections",
+			"The total number of connections rejected because a bind was at its connection limit",
+			rejected_connections.clone(),
+		);
+		let rate_limited_bind_requests = Family::default();
+		registry.register(
+			"rate_limited_bind_requests",
+			"The total number of HTTP requests rejected by a bind rate limit",
+			rate_limited_bind_requests.clone(),
+		);
+		let jwt_cache_lookups = Family::default();
+		registry.register(
+			"jwt_cache_lookups",
+			"The total number of JWT validation cache lookups, by whether the token was found",
+			jwt_cache_lookups.clone(),
+		);
+		let a2a_invalid_task_transitions = Family::default();
+		registry.register(
+			"a2a_invalid_task_transitions",
+			"The total number of invalid A2A task state transitions seen in streaming responses",
+			a2a_invalid_task_transitions.clone(),
+		);
 		Metrics {
-			requests: build("requests", "The total number of HTTP requests sent"),
+			requests,
+			rate_limited_requests,
+			rejected_connections,
+			rate_limited_bind_requests,
+			jwt_cache_lookups,
+			a2a_invalid_task_transitions,
 		}
 	}
 }
@@ -1,28 +1,47 @@
+use std::collections::HashMap;
 use std::ops::Sub;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
+use agent_core::trcng;
 use http::Version;
 use opentelemetry::trace::{Span, SpanContext, SpanKind, TraceState, Tracer as _, TracerProvider};
 use opentelemetry::{Key, KeyValue, TraceFlags};
-use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
 use opentelemetry_sdk::trace::SdkTracerProvider;
 use tokio::io::AsyncWriteExt;
 pub use traceparent::TraceParent;
 
 use crate::http::Request;
+use crate::serdes::ser_redact;
 use crate::telemetry::log::RequestLog;
 
 #[derive(Clone, Debug)]
 pub struct Tracer {
 	pub tracer: Arc<opentelemetry_sdk::trace::SdkTracer>,
 	pub provider: SdkTracerProvider,
+	random_sampling: Option<f64>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct Config {
+	/// OTLP collector to send traces to. If unset, tracing is disabled.
 	pub endpoint: Option<String>,
+	pub protocol: trcng::Protocol,
+	/// Headers sent with each export, for example to authenticate to the collector.
+	#[serde(serialize_with = "ser_redact")]
+	pub headers: HashMap<String, String>,
+	/// Fraction of requests without an incoming trace to start a new trace for, between 0 and 1.
+	/// Requests that are already traced always follow the caller's sampling decision. If unset, HTTP
+	/// requests are only traced if the caller traces them, while MCP calls are always traced.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub random_sampling: Option<f64>,
+	pub service_name: String,
+	/// Whether to also export metrics to the OTLP collector. The Prometheus metrics are served either way.
+	#[serde(default)]
+	pub metrics: bool,
 }
 
 mod semconv {
@@ -40,35 +59,54 @@ mod semconv {
 	pub static PEER_ADDRESS: Key = Key::from_static_str("network.peer.address");
 }
 
+/// The resource describing this proxy, for both traces and metrics.
+fn resource(cfg: &Config) -> Resource {
+	Resource::builder()
+		.with_service_name(cfg.service_name.clone())
+		.with_attribute(KeyValue::new(
+			"service.version",
+			agent_core::version::BuildInfo::new().version,
+		))
+		.build()
+}
+
+/// Builds the provider exporting metrics to the OTLP collector, if both a collector and metrics
+/// export are configured.
+pub fn meter_provider(cfg: &Config) -> anyhow::Result<Option<SdkMeterProvider>> {
+	let Some(ep) = cfg.endpoint.as_ref().filter(|_| cfg.metrics) else {
+		return Ok(None);
+	};
+	let provider = SdkMeterProvider::builder()
+		.with_resource(resource(cfg))
+		.with_periodic_exporter(trcng::metric_exporter(ep, cfg.protocol, &cfg.headers)?)
+		.build();
+	Ok(Some(provider))
+}
+
 impl Tracer {
 	pub fn new(cfg: &Config) -> anyhow::Result<Option<Tracer>> {
 		let Some(ep) = &cfg.endpoint else {
 			return Ok(None);
 		};
 		let result = opentelemetry_sdk::trace::SdkTracerProvider::builder()
-			.with_resource(
-				Resource::builder()
-					.with_service_name("agentgateway")
-					.with_attribute(KeyValue::new(
-						"service.version",
-						agent_core::version::BuildInfo::new().version,
-					))
-					.build(),
-			)
-			.with_batch_exporter(
-				opentelemetry_otlp::SpanExporter::builder()
-					.with_tonic()
-					.with_endpoint(ep)
-					.build()?, //
-			)
+			.with_resource(resource(cfg))
+			.with_batch_exporter(trcng::span_exporter(ep, cfg.protocol, &cfg.headers)?)
 			.build();
 		let tracer = result.tracer("agentgateway");
 		Ok(Some(Tracer {
 			tracer: Arc::new(tracer),
 			provider: result,
+			random_sampling: cfg.random_sampling,
 		}))
 	}
 
+	/// Whether to start a new trace for a request that does not carry one.
+	pub fn sample_new_trace(&self) -> bool {
+		self
+			.random_sampling
+			.is_some_and(|r| r > 0.0 && rand::random::<f64>() < r)
+	}
+
 	pub fn shutdown(&self) {
 		self.provider.shutdown();
 	}
@@ -0,0 +1,196 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+#[cfg(test)]
+#[path = "proxy_protocol_tests.rs"]
+mod tests;
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+// The longest possible v1 header, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_HEADER_LEN: usize = 16;
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+// How long optional mode waits for the first data, before passing the connection through without a
+// header. Proxies send the header as soon as they connect.
+const OPTIONAL_HEADER_TIMEOUT: Duration = Duration::from_millis(100);
+// How often the rest of a header that arrived in pieces is checked for.
+const HEADER_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// A parsed PROXY protocol header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Header {
+	/// The connection was opened by the proxy itself (v2 `LOCAL`, or v1 `UNKNOWN`), so the socket addresses
+	/// should be used as-is.
+	Local,
+	/// The connection was proxied on behalf of `source`, which originally connected to `destination`.
+	Proxied {
+		source: SocketAddr,
+		destination: SocketAddr,
+	},
+}
+
+/// What the start of a stream holds.
+#[derive(Debug, PartialEq, Eq)]
+enum Parsed {
+	/// A complete header, along with its length.
+	Header(Header, usize),
+	/// The start of a header, with the length of the whole header once it is known.
+	Incomplete(Option<usize>),
+	/// Anything but a header.
+	NoHeader,
+}
+
+/// Reads a PROXY protocol header, either v1 or v2, from the start of the stream.
+/// If the stream does not start with a header, nothing is consumed and `None` is returned, unless `required`
+/// is set, in which case an error is returned.
+///
+/// The header is peeked until it is complete, so nothing is consumed unless there is one. In
+/// optional mode, a connection that sends nothing for a moment is passed through without a header,
+/// so clients that wait for the server to speak first are only briefly delayed.
+pub async fn read_header(stream: &mut TcpStream, required: bool) -> anyhow::Result<Option<Header>> {
+	let start = tokio::time::Instant::now();
+	let wait = if required {
+		HEADER_TIMEOUT
+	} else {
+		OPTIONAL_HEADER_TIMEOUT
+	};
+	let mut buf = vec![0u8; V1_MAX_LEN];
+	let Ok(n) = tokio::time::timeout(wait, stream.peek(&mut buf)).await else {
+		if required {
+			bail!("timed out waiting for PROXY protocol header");
+		}
+		return Ok(None);
+	};
+	let mut n = n?;
+	let (header, len) = loop {
+		let parsed = if n == 0 {
+			Parsed::NoHeader
+		} else {
+			parse(&buf[..n])?
+		};
+		match parsed {
+			Parsed::Header(header, len) => break (header, len),
+			Parsed::NoHeader if required => bail!("connection did not send a PROXY protocol header"),
+			Parsed::NoHeader => return Ok(None),
+			Parsed::Incomplete(len) => {
+				if start.elapsed() >= HEADER_TIMEOUT {
+					bail!("PROXY protocol header is incomplete");
+				}
+				if let Some(len) = len.filter(|len| *len > buf.len()) {
+					buf.resize(len, 0);
+				}
+				tokio::time::sleep(HEADER_POLL_INTERVAL).await;
+				n = stream.peek(&mut buf).await?;
+			},
+		}
+	};
+	// The header has already been received, so this does not wait.
+	stream.read_exact(&mut buf[..len]).await?;
+	Ok(Some(header))
+}
+
+/// Parses the header at the start of `buf`, which must not be empty.
+fn parse(buf: &[u8]) -> anyhow::Result<Parsed> {
+	if buf.starts_with(V2_SIGNATURE) {
+		let Some(header) = buf.first_chunk::<V2_HEADER_LEN>() else {
+			return Ok(Parsed::Incomplete(Some(V2_HEADER_LEN)));
+		};
+		// The length field covers the addresses and any TLVs
+		let len = V2_HEADER_LEN + u16::from_be_bytes([header[14], header[15]]) as usize;
+		if buf.len() < len {
+			return Ok(Parsed::Incomplete(Some(len)));
+		}
+		return Ok(Parsed::Header(
+			parse_v2(header, &buf[V2_HEADER_LEN..len])?,
+			len,
+		));
+	}
+	if buf.starts_with(V1_PREFIX) {
+		let line = &buf[..buf.len().min(V1_MAX_LEN)];
+		let Some(i) = line.windows(2).position(|w| w == b"\r\n") else {
+			if buf.len() >= V1_MAX_LEN {
+				bail!("PROXY protocol v1 header is too long");
+			}
+			return Ok(Parsed::Incomplete(Some(V1_MAX_LEN)));
+		};
+		return Ok(Parsed::Header(parse_v1(&buf[..i + 2])?, i + 2));
+	}
+	if V2_SIGNATURE.starts_with(buf) || V1_PREFIX.starts_with(buf) {
+		return Ok(Parsed::Incomplete(None));
+	}
+	Ok(Parsed::NoHeader)
+}
+
+/// Parses a v1 (text) header line, including the trailing CRLF.
+fn parse_v1(line: &[u8]) -> anyhow::Result<Header> {
+	let line = line
+		.strip_suffix(b"\r\n")
+		.ok_or_else(|| anyhow!("PROXY protocol v1 header is not terminated"))?;
+	let line = std::str::from_utf8(line)?;
+	let parts: Vec<&str> = line.split(' ').collect();
+	match parts.as_slice() {
+		["PROXY", "UNKNOWN", ..] => Ok(Header::Local),
+		["PROXY", proto @ ("TCP4" | "TCP6"), src, dst, sport, dport] => {
+			let src: IpAddr = src.parse()?;
+			let dst: IpAddr = dst.parse()?;
+			let v4 = *proto == "TCP4";
+			if src.is_ipv4() != v4 || dst.is_ipv4() != v4 {
+				bail!("PROXY protocol v1 address does not match protocol {proto}");
+			}
+			Ok(Header::Proxied {
+				source: SocketAddr::new(src, sport.parse()?),
+				destination: SocketAddr::new(dst, dport.parse()?),
+			})
+		},
+		_ => bail!("invalid PROXY protocol v1 header: {line:?}"),
+	}
+}
+
+/// Parses a v2 (binary) header, given the fixed 16 byte header and the variable length body.
+fn parse_v2(header: &[u8; V2_HEADER_LEN], body: &[u8]) -> anyhow::Result<Header> {
+	let version = header[12] >> 4;
+	if version != 2 {
+		bail!("unsupported PROXY protocol version {version}");
+	}
+	match header[12] & 0x0F {
+		// LOCAL
+		0x0 => return Ok(Header::Local),
+		// PROXY
+		0x1 => {},
+		cmd => bail!("unsupported PROXY protocol v2 command {cmd}"),
+	}
+	// Any TLVs following the addresses are ignored.
+	match header[13] >> 4 {
+		// AF_INET
+		0x1 => {
+			if body.len() < 12 {
+				bail!("PROXY protocol v2 IPv4 addresses are truncated");
+			}
+			let src = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+			let dst = Ipv4Addr::new(body[4], body[5], body[6], body[7]);
+			Ok(Header::Proxied {
+				source: SocketAddr::new(src.into(), u16::from_be_bytes([body[8], body[9]])),
+				destination: SocketAddr::new(dst.into(), u16::from_be_bytes([body[10], body[11]])),
+			})
+		},
+		// AF_INET6
+		0x2 => {
+			if body.len() < 36 {
+				bail!("PROXY protocol v2 IPv6 addresses are truncated");
+			}
+			let src = Ipv6Addr::from(<[u8; 16]>::try_from(&body[0..16])?);
+			let dst = Ipv6Addr::from(<[u8; 16]>::try_from(&body[16..32])?);
+			Ok(Header::Proxied {
+				source: SocketAddr::new(src.into(), u16::from_be_bytes([body[32], body[33]])),
+				destination: SocketAddr::new(dst.into(), u16::from_be_bytes([body[34], body[35]])),
+			})
+		},
+		// AF_UNSPEC and AF_UNIX carry no address we can use.
+		_ => Ok(Header::Local),
+	}
+}
@@ -0,0 +1,253 @@
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+
+use super::*;
+
+fn v2_header(cmd: u8, family: u8, body: &[u8]) -> Vec<u8> {
+	let mut out = V2_SIGNATURE.to_vec();
+	out.push(0x20 | cmd);
+	out.push(family);
+	out.extend_from_slice(&(body.len() as u16).to_be_bytes());
+	out.extend_from_slice(body);
+	out
+}
+
+fn v2_ipv4(src: [u8; 4], sport: u16, dst: [u8; 4], dport: u16) -> Vec<u8> {
+	let mut body = Vec::new();
+	body.extend_from_slice(&src);
+	body.extend_from_slice(&dst);
+	body.extend_from_slice(&sport.to_be_bytes());
+	body.extend_from_slice(&dport.to_be_bytes());
+	v2_header(0x1, 0x11, &body)
+}
+
+/// Sends `data` over a real TCP connection and reads the header from the accepted side. Returns the
+/// header and whatever data followed it.
+async fn roundtrip(data: Vec<u8>, required: bool) -> (anyhow::Result<Option<Header>>, Vec<u8>) {
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	let mut client = TcpStream::connect(addr).await.unwrap();
+	let (mut stream, _) = listener.accept().await.unwrap();
+	client.write_all(&data).await.unwrap();
+	client.shutdown().await.unwrap();
+	let header = read_header(&mut stream, required).await;
+	let mut rest = Vec::new();
+	stream.read_to_end(&mut rest).await.unwrap();
+	(header, rest)
+}
+
+#[test]
+fn test_parse_v1() {
+	assert_eq!(
+		parse_v1(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n").unwrap(),
+		Header::Proxied {
+			source: "192.168.0.1:56324".parse().unwrap(),
+			destination: "192.168.0.11:443".parse().unwrap(),
+		}
+	);
+	assert_eq!(
+		parse_v1(b"PROXY TCP6 2001:db8::1 2001:db8::2 1234 80\r\n").unwrap(),
+		Header::Proxied {
+			source: "[2001:db8::1]:1234".parse().unwrap(),
+			destination: "[2001:db8::2]:80".parse().unwrap(),
+		}
+	);
+	assert_eq!(parse_v1(b"PROXY UNKNOWN\r\n").unwrap(), Header::Local);
+	assert!(parse_v1(b"PROXY TCP4 2001:db8::1 192.168.0.11 1 2\r\n").is_err());
+	assert!(parse_v1(b"PROXY TCP4 192.168.0.1 192.168.0.11 99999 443\r\n").is_err());
+	assert!(parse_v1(b"PROXY UDP4 192.168.0.1 192.168.0.11 1 2\r\n").is_err());
+}
+
+#[test]
+fn test_parse_v2() {
+	let raw = v2_ipv4([10, 0, 0, 1], 4000, [10, 0, 0, 2], 8080);
+	let header: [u8; V2_HEADER_LEN] = raw[..V2_HEADER_LEN].try_into().unwrap();
+	assert_eq!(
+		parse_v2(&header, &raw[V2_HEADER_LEN..]).unwrap(),
+		Header::Proxied {
+			source: "10.0.0.1:4000".parse().unwrap(),
+			destination: "10.0.0.2:8080".parse().unwrap(),
+		}
+	);
+
+	let src: Ipv6Addr = "2001:db8::1".parse().unwrap();
+	let dst: Ipv6Addr = "2001:db8::2".parse().unwrap();
+	let mut body = Vec::new();
+	body.extend_from_slice(&src.octets());
+	body.extend_from_slice(&dst.octets());
+	body.extend_from_slice(&1234u16.to_be_bytes());
+	body.extend_from_slice(&443u16.to_be_bytes());
+	// Trailing TLVs are ignored
+	body.extend_from_slice(&[0x04, 0x00, 0x01, 0x00]);
+	let raw = v2_header(0x1, 0x21, &body);
+	let header: [u8; V2_HEADER_LEN] = raw[..V2_HEADER_LEN].try_into().unwrap();
+	assert_eq!(
+		parse_v2(&header, &raw[V2_HEADER_LEN..]).unwrap(),
+		Header::Proxied {
+			source: "[2001:db8::1]:1234".parse().unwrap(),
+			destination: "[2001:db8::2]:443".parse().unwrap(),
+		}
+	);
+
+	let raw = v2_header(0x0, 0x00, &[]);
+	let header: [u8; V2_HEADER_LEN] = raw[..V2_HEADER_LEN].try_into().unwrap();
+	assert_eq!(parse_v2(&header, &[]).unwrap(), Header::Local);
+
+	// Truncated addresses
+	let raw = v2_header(0x1, 0x11, &[10, 0, 0, 1]);
+	let header: [u8; V2_HEADER_LEN] = raw[..V2_HEADER_LEN].try_into().unwrap();
+	assert!(parse_v2(&header, &raw[V2_HEADER_LEN..]).is_err());
+}
+
+#[tokio::test]
+async fn test_read_v1() {
+	let mut data = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n".to_vec();
+	data.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+	let (header, rest) = roundtrip(data, true).await;
+	assert_eq!(
+		header.unwrap(),
+		Some(Header::Proxied {
+			source: "192.168.0.1:56324".parse().unwrap(),
+			destination: "192.168.0.11:443".parse().unwrap(),
+		})
+	);
+	assert_eq!(rest, b"GET / HTTP/1.1\r\n\r\n");
+}
+
+#[tokio::test]
+async fn test_read_v2() {
+	let mut data = v2_ipv4([10, 0, 0, 1], 4000, [10, 0, 0, 2], 8080);
+	data.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+	let (header, rest) = roundtrip(data, true).await;
+	assert_eq!(
+		header.unwrap(),
+		Some(Header::Proxied {
+			source: "10.0.0.1:4000".parse().unwrap(),
+			destination: "10.0.0.2:8080".parse().unwrap(),
+		})
+	);
+	assert_eq!(rest, b"GET / HTTP/1.1\r\n\r\n");
+}
+
+#[tokio::test]
+async fn test_read_no_header() {
+	let data = b"GET / HTTP/1.1\r\n\r\n".to_vec();
+	let (header, rest) = roundtrip(data.clone(), false).await;
+	assert_eq!(header.unwrap(), None);
+	assert_eq!(rest, data);
+
+	let (header, _) = roundtrip(data, true).await;
+	assert!(header.is_err());
+}
+
+#[tokio::test]
+async fn test_read_optional_header() {
+	let mut data = v2_ipv4([10, 0, 0, 1], 4000, [10, 0, 0, 2], 8080);
+	data.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+	let (header, rest) = roundtrip(data, false).await;
+	assert_eq!(
+		header.unwrap(),
+		Some(Header::Proxied {
+			source: "10.0.0.1:4000".parse().unwrap(),
+			destination: "10.0.0.2:8080".parse().unwrap(),
+		})
+	);
+	assert_eq!(rest, b"GET / HTTP/1.1\r\n\r\n");
+}
+
+#[tokio::test]
+async fn test_read_optional_does_not_wait() {
+	// A client that waits for the server to speak first is passed through after a moment
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let _client = TcpStream::connect(listener.local_addr().unwrap())
+		.await
+		.unwrap();
+	let (mut stream, _) = listener.accept().await.unwrap();
+	let header = tokio::time::timeout(
+		OPTIONAL_HEADER_TIMEOUT * 5,
+		read_header(&mut stream, false),
+	)
+	.await
+	.expect("optional mode should not wait for a header");
+	assert_eq!(header.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_read_late_header() {
+	// A header that has not arrived yet, or arrives in pieces, is waited for
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let mut client = TcpStream::connect(listener.local_addr().unwrap())
+		.await
+		.unwrap();
+	let (mut stream, _) = listener.accept().await.unwrap();
+	let mut data = v2_ipv4([10, 0, 0, 1], 4000, [10, 0, 0, 2], 8080);
+	data.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+	tokio::spawn(async move {
+		tokio::time::sleep(Duration::from_millis(20)).await;
+		client.write_all(&data[..10]).await.unwrap();
+		client.flush().await.unwrap();
+		tokio::time::sleep(Duration::from_millis(20)).await;
+		client.write_all(&data[10..]).await.unwrap();
+		client.shutdown().await.unwrap();
+	});
+	assert_eq!(
+		read_header(&mut stream, false).await.unwrap(),
+		Some(Header::Proxied {
+			source: "10.0.0.1:4000".parse().unwrap(),
+			destination: "10.0.0.2:8080".parse().unwrap(),
+		})
+	);
+	let mut rest = Vec::new();
+	stream.read_to_end(&mut rest).await.unwrap();
+	assert_eq!(rest, b"GET / HTTP/1.1\r\n\r\n");
+}
+
+#[tokio::test]
+async fn test_read_v2_tlvs() {
+	// TLVs may make the header longer than a single segment
+	let mut body = Vec::new();
+	body.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+	body.extend_from_slice(&4000u16.to_be_bytes());
+	body.extend_from_slice(&8080u16.to_be_bytes());
+	body.extend_from_slice(&[0x04, 0x0f, 0xa0]);
+	body.resize(body.len() + 4000, b'x');
+	let mut data = v2_header(0x1, 0x11, &body);
+	data.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+	let (header, rest) = roundtrip(data, true).await;
+	assert_eq!(
+		header.unwrap(),
+		Some(Header::Proxied {
+			source: "10.0.0.1:4000".parse().unwrap(),
+			destination: "10.0.0.2:8080".parse().unwrap(),
+		})
+	);
+	assert_eq!(rest, b"GET / HTTP/1.1\r\n\r\n");
+}
+
+#[test]
+fn test_parse_incomplete_header() {
+	assert_eq!(
+		parse(b"PROXY TCP4 192.168.0.1").unwrap(),
+		Parsed::Incomplete(Some(V1_MAX_LEN))
+	);
+	assert_eq!(parse(b"PRO").unwrap(), Parsed::Incomplete(None));
+	let data = v2_ipv4([10, 0, 0, 1], 4000, [10, 0, 0, 2], 8080);
+	assert_eq!(
+		parse(&data[..10]).unwrap(),
+		Parsed::Incomplete(Some(V2_HEADER_LEN))
+	);
+	assert_eq!(
+		parse(&data[..20]).unwrap(),
+		Parsed::Incomplete(Some(data.len()))
+	);
+	assert_eq!(parse(b"GET / HTTP/1.1").unwrap(), Parsed::NoHeader);
+}
+
+#[tokio::test]
+async fn test_read_long_v1_header() {
+	// v1 headers have a maximum length
+	let mut data = b"PROXY ".to_vec();
+	data.resize(200, b'A');
+	let (header, _) = roundtrip(data, true).await;
+	assert!(header.is_err());
+}
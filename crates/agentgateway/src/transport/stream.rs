@@ -15,6 +15,8 @@ use tokio::net::TcpStream;
 use tokio_rustls::TlsStream;
 use tracing::event;
 
+use crate::transport::proxy_protocol;
+
 #[derive(Debug, Clone)]
 pub struct TCPConnectionInfo {
 	pub peer_addr: SocketAddr,
@@ -62,6 +64,11 @@ pub struct HBONEConnectionInfo {
 	pub hbone_address: SocketAddr,
 }
 
+/// Marks an upstream connection to a forward proxy, which plain HTTP requests are sent to in
+/// absolute-form.
+#[derive(Debug, Clone)]
+pub struct ForwardProxyInfo;
+
 #[derive(Debug, Default)]
 pub struct Metrics {
 	counter: Option<BytesCounter>,
@@ -118,6 +125,9 @@ impl hyper_util_fork::client::legacy::connect::Connection for Socket {
 		{
 			con = con.negotiated_h2()
 		}
+		if self.ext.get::<ForwardProxyInfo>().is_some() {
+			con = con.proxy(true)
+		}
 		con
 	}
 }
@@ -197,6 +207,21 @@ impl Socket {
 		self.metrics.logging = l;
 	}
 
+	/// Use the client address received in a PROXY protocol header as the peer address of the connection.
+	pub fn with_proxy_header(&mut self, header: proxy_protocol::Header) {
+		let proxy_protocol::Header::Proxied { source, .. } = header else {
+			return;
+		};
+		let mut info = self.tcp().clone();
+		info.peer_addr = to_canonical(source);
+		self.ext.insert(info);
+	}
+
+	/// Marks the connection as made to a forward proxy, so requests are sent in absolute-form.
+	pub fn with_forward_proxy(&mut self) {
+		self.ext.insert(ForwardProxyInfo);
+	}
+
 	pub fn get_ext(&self) -> Extension {
 		self.ext.clone()
 	}
@@ -24,6 +24,14 @@ pub fn provider() -> Arc<CryptoProvider> {
 	})
 }
 
+/// Look up a cipher suite by its IANA name, such as `TLS13_AES_256_GCM_SHA384`.
+pub fn cipher_suite(name: &str) -> Option<rustls::SupportedCipherSuite> {
+	rustls::crypto::aws_lc_rs::ALL_CIPHER_SUITES
+		.iter()
+		.find(|s| format!("{:?}", s.suite()) == name)
+		.copied()
+}
+
 // pub fn provider() -> Arc<CryptoProvider> {
 // 	Arc::new(CryptoProvider {
 // 		// Limit to only the subset of ciphers that are FIPS compatible
@@ -5,7 +5,7 @@ use std::fmt::Display;
 use std::io::Cursor;
 use std::marker::PhantomData;
 use std::net::{IpAddr, SocketAddr};
-use std::num::NonZeroU16;
+use std::num::{NonZeroU16, NonZeroU32};
 use std::sync::Arc;
 use std::{cmp, net};
 
@@ -19,7 +19,7 @@ use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use rustls::{ClientConfig, ServerConfig};
 use rustls_pemfile::Item;
 
-use secrecy::SecretString;
+use secrecy::{ExposeSecret, SecretString};
 use serde::ser::SerializeMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
@@ -31,6 +31,7 @@ use crate::http::{
 	HeaderName, HeaderValue, StatusCode, ext_authz, filters, remoteratelimit, retry, status, timeout,
 	uri,
 };
+use crate::mcp::openapi::remote::{RemoteSchema, RemoteSchemaConfig};
 use crate::mcp::rbac::RuleSet;
 use crate::proxy::ProxyError;
 use crate::transport::tls;
@@ -45,6 +46,74 @@ pub struct Bind {
 	pub key: BindName,
 	pub address: SocketAddr,
 	pub listeners: ListenerSet,
+	/// Whether connections are expected to start with a PROXY protocol (v1 or v2) header.
+	pub proxy_protocol: Option<ProxyProtocol>,
+	/// The maximum number of concurrent connections. Connections beyond this are closed immediately.
+	pub max_connections: Option<u32>,
+	/// A request rate limit applied to every HTTP request on the bind, before routing.
+	pub rate_limit: Option<RateLimit>,
+}
+
+impl Bind {
+	/// Starts building a bind on `address`. It is keyed by its port, as binds from the local config
+	/// are, and has no listeners until added.
+	pub fn builder(address: SocketAddr) -> BindBuilder {
+		BindBuilder(Bind {
+			key: strng::format!("bind/{}", address.port()),
+			address,
+			listeners: Default::default(),
+			proxy_protocol: None,
+			max_connections: None,
+			rate_limit: None,
+		})
+	}
+}
+
+/// Builds a [Bind] in code, without a config file or XDS. See [Bind::builder].
+#[derive(Debug, Clone)]
+pub struct BindBuilder(Bind);
+
+impl BindBuilder {
+	/// Overrides the key derived from the port.
+	pub fn key(mut self, key: impl Into<BindName>) -> Self {
+		self.0.key = key.into();
+		self
+	}
+
+	pub fn listener(mut self, listener: Listener) -> Self {
+		self.0.listeners.insert(listener.key.clone(), listener);
+		self
+	}
+
+	pub fn proxy_protocol(mut self, proxy_protocol: ProxyProtocol) -> Self {
+		self.0.proxy_protocol = Some(proxy_protocol);
+		self
+	}
+
+	pub fn max_connections(mut self, max_connections: u32) -> Self {
+		self.0.max_connections = Some(max_connections);
+		self
+	}
+
+	pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+		self.0.rate_limit = Some(rate_limit);
+		self
+	}
+
+	pub fn build(self) -> Bind {
+		self.0
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum ProxyProtocol {
+	/// Use the addresses from a PROXY protocol header when one is sent, and the connection addresses otherwise.
+	/// The connection is never held waiting for a header; it is only detected if it has already arrived.
+	Optional,
+	/// Reject connections that do not start with a PROXY protocol header.
+	Required,
 }
 
 pub type BindName = Strng;
@@ -65,11 +134,137 @@ pub struct Listener {
 	pub tcp_routes: TCPRouteSet,
 }
 
+impl Listener {
+	/// Starts building a listener. It is keyed by its name and gateway, as listeners from the local
+	/// config are, and serves HTTP without routes until set otherwise.
+	pub fn builder(
+		name: impl Into<ListenerName>,
+		gateway_name: impl Into<GatewayName>,
+	) -> ListenerBuilder {
+		let name = name.into();
+		let gateway_name = gateway_name.into();
+		ListenerBuilder(Listener {
+			key: strng::format!("{}/{}", name, gateway_name),
+			name,
+			gateway_name,
+			hostname: Default::default(),
+			protocol: ListenerProtocol::HTTP,
+			routes: Default::default(),
+			tcp_routes: Default::default(),
+		})
+	}
+}
+
+/// Builds a [Listener] in code, without a config file or XDS. See [Listener::builder].
+///
+/// Policies, such as JWT authentication or authorization, apply to routes rather than listeners,
+/// and are set with [Route::builder].
+#[derive(Debug, Clone)]
+pub struct ListenerBuilder(Listener);
+
+impl ListenerBuilder {
+	/// Overrides the key derived from the name and gateway.
+	pub fn key(mut self, key: impl Into<ListenerKey>) -> Self {
+		self.0.key = key.into();
+		self
+	}
+
+	/// The hostname served, which can be a wildcard. Any hostname is served by default.
+	pub fn hostname(mut self, hostname: impl Into<Strng>) -> Self {
+		self.0.hostname = hostname.into();
+		self
+	}
+
+	pub fn protocol(mut self, protocol: ListenerProtocol) -> Self {
+		self.0.protocol = protocol;
+		self
+	}
+
+	/// Serves HTTPS, terminating TLS with `tls`.
+	pub fn tls(self, tls: TLSConfig) -> Self {
+		self.protocol(ListenerProtocol::HTTPS(tls))
+	}
+
+	pub fn route(mut self, route: Route) -> Self {
+		self.0.routes.insert(route);
+		self
+	}
+
+	pub fn tcp_route(mut self, route: TCPRoute) -> Self {
+		self.0.tcp_routes.insert(route);
+		self
+	}
+
+	pub fn build(self) -> Listener {
+		self.0
+	}
+}
+
 pub type GatewayName = Strng;
 
 #[derive(Debug, Clone)]
 pub struct TLSConfig {
 	pub config: Arc<ServerConfig>,
+	// Retained only to describe the config when serialized; the private key is never kept.
+	cert_chain: Vec<CertificateDer<'static>>,
+}
+
+impl TLSConfig {
+	pub fn new(
+		cert_chain: Vec<CertificateDer<'static>>,
+		private_key: PrivateKeyDer<'static>,
+		alpn_protocols: Vec<Vec<u8>>,
+		options: &TLSServerOptions,
+	) -> anyhow::Result<Self> {
+		let versions = match options.min_version {
+			None | Some(TLSVersion::V1_2) => tls::ALL_TLS_VERSIONS,
+			Some(TLSVersion::V1_3) => &[&rustls::version::TLS13],
+		};
+		let provider = match &options.cipher_suites {
+			None => tls::provider(),
+			Some(names) => Arc::new(rustls::crypto::CryptoProvider {
+				cipher_suites: names
+					.iter()
+					.map(|n| tls::cipher_suite(n).ok_or_else(|| anyhow!("unknown cipher suite {n}")))
+					.collect::<anyhow::Result<_>>()?,
+				..rustls::crypto::aws_lc_rs::default_provider()
+			}),
+		};
+		if !provider
+			.cipher_suites
+			.iter()
+			.any(|s| versions.iter().any(|v| v.version == s.version().version))
+		{
+			anyhow::bail!("none of the configured cipher suites support the allowed TLS versions");
+		}
+		let mut sc = ServerConfig::builder_with_provider(provider)
+			.with_protocol_versions(versions)?
+			.with_no_client_auth()
+			.with_single_cert(cert_chain.clone(), private_key)?;
+		sc.alpn_protocols = alpn_protocols;
+		Ok(TLSConfig {
+			config: Arc::new(sc),
+			cert_chain,
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum TLSVersion {
+	#[serde(rename = "1.2")]
+	V1_2,
+	#[serde(rename = "1.3")]
+	V1_3,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TLSServerOptions {
+	/// The minimum TLS version to accept. Defaults to TLS 1.2, but the default cipher suites are
+	/// TLS 1.3 only, so TLS 1.2 clients are only accepted if TLS 1.2 suites are configured.
+	pub min_version: Option<TLSVersion>,
+	/// The cipher suites to offer, by IANA name. Defaults to a FIPS compatible set of TLS 1.3 suites.
+	pub cipher_suites: Option<Vec<String>>,
 }
 
 impl serde::Serialize for TLSConfig {
@@ -77,8 +272,56 @@ impl serde::Serialize for TLSConfig {
 	where
 		S: Serializer,
 	{
-		// TODO: store raw pem
-		serializer.serialize_none()
+		#[derive(serde::Serialize)]
+		#[serde(rename_all = "camelCase")]
+		struct Certificate {
+			subject: String,
+			issuer: String,
+			subject_alt_names: Vec<String>,
+			not_after: String,
+		}
+
+		#[derive(serde::Serialize)]
+		#[serde(rename_all = "camelCase")]
+		struct Summary {
+			certificates: Vec<Certificate>,
+			alpn_protocols: Vec<String>,
+		}
+
+		use x509_parser::prelude::*;
+		let certificates = self
+			.cert_chain
+			.iter()
+			.filter_map(|der| X509Certificate::from_der(der).ok())
+			.map(|(_, c)| Certificate {
+				subject: c.subject().to_string(),
+				issuer: c.issuer().to_string(),
+				subject_alt_names: c
+					.subject_alternative_name()
+					.ok()
+					.flatten()
+					.map(|san| {
+						san
+							.value
+							.general_names
+							.iter()
+							.map(|n| n.to_string())
+							.collect()
+					})
+					.unwrap_or_default(),
+				not_after: c.validity().not_after.to_string(),
+			})
+			.collect();
+		Summary {
+			certificates,
+			alpn_protocols: self
+				.config
+				.alpn_protocols
+				.iter()
+				.map(|p| String::from_utf8_lossy(p).into_owned())
+				.collect(),
+		}
+		.serialize(serializer)
 	}
 }
 
@@ -149,6 +392,118 @@ pub struct Route {
 	pub policies: Option<TrafficPolicy>,
 }
 
+impl Route {
+	/// Starts building a route, keyed by its name, which matches any path and has no backends until
+	/// set otherwise.
+	pub fn builder(route_name: impl Into<RouteName>) -> RouteBuilder {
+		let route_name = route_name.into();
+		RouteBuilder {
+			route: Route {
+				key: route_name.clone(),
+				route_name,
+				rule_name: None,
+				hostnames: vec![],
+				matches: vec![],
+				filters: vec![],
+				backends: vec![],
+				policies: None,
+			},
+			jwt: None,
+			authorization: None,
+		}
+	}
+}
+
+/// Builds a [Route] in code, without a config file or XDS. See [Route::builder].
+///
+/// The JWT authentication and authorization set on the builder are policies, returned next to the
+/// route by [RouteBuilder::build]. They must be added to the store with it to apply.
+#[derive(Debug, Clone)]
+pub struct RouteBuilder {
+	route: Route,
+	jwt: Option<Jwt>,
+	authorization: Option<RuleSet>,
+}
+
+impl RouteBuilder {
+	/// Overrides the key, which defaults to the route name.
+	pub fn key(mut self, key: impl Into<RouteKey>) -> Self {
+		self.route.key = key.into();
+		self
+	}
+
+	pub fn rule_name(mut self, rule_name: impl Into<RouteRuleName>) -> Self {
+		self.route.rule_name = Some(rule_name.into());
+		self
+	}
+
+	/// Adds a hostname served by the route, which can be a wildcard. Any hostname of the listener is
+	/// served by default.
+	pub fn hostname(mut self, hostname: impl Into<Strng>) -> Self {
+		self.route.hostnames.push(hostname.into());
+		self
+	}
+
+	/// Adds a match. The route matches any request by default.
+	pub fn route_match(mut self, route_match: RouteMatch) -> Self {
+		self.route.matches.push(route_match);
+		self
+	}
+
+	pub fn filter(mut self, filter: RouteFilter) -> Self {
+		self.route.filters.push(filter);
+		self
+	}
+
+	pub fn backend(mut self, backend: RouteBackendReference) -> Self {
+		self.route.backends.push(backend);
+		self
+	}
+
+	pub fn traffic_policy(mut self, policy: TrafficPolicy) -> Self {
+		self.route.policies = Some(policy);
+		self
+	}
+
+	/// Requires requests to the route to carry a JWT validated by `jwt`.
+	pub fn jwt(mut self, jwt: Jwt) -> Self {
+		self.jwt = Some(jwt);
+		self
+	}
+
+	/// Authorizes MCP requests to the route's backends with `rules`.
+	pub fn rbac(mut self, rules: RuleSet) -> Self {
+		self.authorization = Some(rules);
+		self
+	}
+
+	/// Returns the route, and the policies targeting it or its backends.
+	pub fn build(self) -> (Route, Vec<TargetedPolicy>) {
+		let RouteBuilder {
+			route,
+			jwt,
+			authorization,
+		} = self;
+		let key = &route.key;
+		let mut policies = vec![];
+		if let Some(jwt) = jwt {
+			policies.push(TargetedPolicy {
+				name: strng::format!("{key}/jwt"),
+				target: PolicyTarget::RouteRule(key.clone()),
+				policy: Policy::JwtAuth(jwt),
+			});
+		}
+		if let Some(rules) = authorization {
+			policies.extend(route.backends.iter().map(|b| TargetedPolicy {
+				name: strng::format!("{key}/rbac/{}", b.backend.name()),
+				target: PolicyTarget::Backend(b.backend.name()),
+				policy: Policy::McpAuthorization(McpAuthorization(rules.clone())),
+			}));
+		}
+		(route, policies)
+	}
+}
+
 pub type RouteKey = Strng;
 pub type RouteName = Strng;
 pub type RouteRuleName = Strng;
@@ -341,7 +696,7 @@ pub enum Backend {
 	#[serde(rename = "host")]
 	Opaque(BackendName, Target), // Hostname or IP
 	#[serde(rename = "mcp")]
-	MCP(BackendName, McpBackend),
+	MCP(BackendName, Arc<McpBackend>),
 	#[serde(rename = "ai")]
 	AI(BackendName, crate::llm::AIBackend),
 	Dynamic {},
@@ -454,10 +809,371 @@ pub type BackendName = Strng;
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct McpBackend {
+	/// Target names must be unique within the backend, and use only ASCII letters, digits, `-`, `.`
+	/// and `_`.
+	#[serde(deserialize_with = "de_mcp_targets")]
 	pub targets: Vec<Arc<McpTarget>>,
+	/// The transports clients may use to connect. If unset, all transports are served.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub transports: Option<Vec<McpTransport>>,
+	/// Overrides what the gateway advertises about itself when clients initialize.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub server_info: Option<McpServerInfo>,
+	/// If set, tools offered by multiple targets with the same name and compatible input schemas are
+	/// listed once, without a target prefix.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tool_merge: Option<McpToolMerge>,
+	/// If set, each tool call is logged.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub logging: Option<PayloadLogging>,
+	/// If set, a record of each tool call, and of each call denied by the authorization policy, is
+	/// written to an audit log.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		deserialize_with = "de_audit"
+	)]
+	pub audit: Option<McpAuditLog>,
+	/// If set, only tools that do not change state can be called. Tools not classified by this
+	/// configuration must be annotated as read-only by their target; OpenAPI operations are read-only
+	/// if they are GET or HEAD.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub read_only: Option<McpReadOnly>,
+	/// If set, calls to a target that keeps failing are rejected for a while, rather than waiting on
+	/// it. Each target has its own breaker, shared by all sessions.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub circuit_breaker: Option<McpCircuitBreaker>,
+	/// If set, bounds the tool calls sent to each target at once, so a burst of calls does not
+	/// overwhelm it. Each target has its own limit, shared by all sessions.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub concurrency_limit: Option<McpConcurrencyLimit>,
+	/// If set, bounds how long requests listing tools, prompts or resources wait for the targets, so
+	/// one slow target does not hold up the whole list.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub list_deadline: Option<McpListDeadline>,
+}
+
+/// Thresholds for opening the circuit breaker of an MCP target. Calls that fail to reach the target
+/// count as failures; errors returned by the target itself, such as an unknown tool, do not.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct McpCircuitBreaker {
+	/// Open after this many consecutive failed calls. Defaults to 5; 0 disables this threshold.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub consecutive_failures: Option<u32>,
+	/// Also open once this fraction of the calls in the window failed, between 0 and 1.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		deserialize_with = "de_failure_ratio"
+	)]
+	pub failure_ratio: Option<f64>,
+	/// The number of most recent calls the failure ratio is computed over. The ratio is only checked
+	/// once this many calls were made. Defaults to 20.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub window: Option<u32>,
+	/// How long calls are rejected once the breaker opens, before a single probe call is let through
+	/// to check whether the target recovered. Defaults to 30s.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "serde_dur_option"
+	)]
+	#[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+	pub cooldown: Option<Duration>,
+}
+
+/// Limits on the tool calls in flight to an MCP target.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct McpConcurrencyLimit {
+	/// The most tool calls sent to the target at once. At least 1.
+	pub max_concurrent: NonZeroU32,
+	/// Calls beyond the limit wait, in order, for an earlier call to finish. Once this many are
+	/// waiting, further calls are rejected. Defaults to 0: calls beyond the limit are rejected.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub max_queued: Option<u32>,
+}
+
+/// Logging of the JSON-RPC calls handled by an MCP or A2A backend, for debugging.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct PayloadLogging {
+	/// Include the request params and response result in the log, rather than just the method,
+	/// caller and outcome.
+	#[serde(default)]
+	pub include_bodies: bool,
+	/// Dot separated paths, relative to the request params or response result, to mask before logging.
+	/// For example, `arguments.password`. A `*` segment matches any field or array element.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub redact: Vec<String>,
+}
+
+impl PayloadLogging {
+	/// Returns the body to log, if bodies are enabled, with redactions applied.
+	pub fn body(&self, value: impl FnOnce() -> Option<serde_json::Value>) -> Option<String> {
+		if !self.include_bodies {
+			return None;
+		}
+		let mut value = value()?;
+		crate::json::redact(&mut value, &self.redact);
+		Some(value.to_string())
+	}
+}
+
+/// The audit log of the tool calls handled by an MCP backend. Each record says who called which tool
+/// of which target, when, with which arguments, how long it took and how it ended.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct McpAuditLog {
+	/// Where the records are written.
+	pub sink: AuditSink,
+	/// How the arguments of calls are recorded. Defaults to `none`; `hash` requires `hashKey`.
+	#[serde(default)]
+	pub arguments: AuditArguments,
+	/// The key arguments are hashed with: inline, read from a file with `file: <path>`, or read from
+	/// an environment variable with `env: <name>`. Without the key, the hashes of guessable
+	/// arguments cannot be reversed.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub hash_key: Option<AuditHashKey>,
+	/// Dot separated paths, relative to the arguments, to mask when they are recorded in full. For
+	/// example, `password`. A `*` segment matches any field or array element.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub redact: Vec<String>,
+}
+
+/// Where audit records are written, each as a JSON object.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum AuditSink {
+	/// One record per line on stdout.
+	Stdout,
+	/// One record per line, appended to the file.
+	File(PathBuf),
+	/// One POST request per record. Records are sent in the background, and dropped if the webhook
+	/// does not keep up.
+	Webhook { url: String },
+}
+
+/// How the arguments of a call are recorded in the audit log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum AuditArguments {
+	/// The HMAC-SHA256 of the arguments as JSON, keyed with `hashKey`, so calls with the same
+	/// arguments can be matched without recording them.
+	Hash,
+	/// The arguments, with the `redact` paths masked.
+	Full,
+	/// Nothing.
+	#[default]
+	None,
+}
+
+/// The key of the audit log's argument hashes.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct AuditHashKey(
+	#[cfg_attr(feature = "schema", schemars(with = "FileOrInline"))]
+	#[serde(
+		serialize_with = "ser_redact",
+		deserialize_with = "deser_key_from_file"
+	)]
+	pub SecretString,
+);
+
+impl PartialEq for AuditHashKey {
+	fn eq(&self, other: &Self) -> bool {
+		self.0.expose_secret() == other.0.expose_secret()
+	}
+}
+
+fn de_audit<'de, D>(deserializer: D) -> Result<Option<McpAuditLog>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let audit = Option::<McpAuditLog>::deserialize(deserializer)?;
+	if let Some(audit) = &audit
+		&& audit.arguments == AuditArguments::Hash
+		&& audit.hash_key.is_none()
+	{
+		return Err(serde::de::Error::custom(
+			"audit arguments set to hash requires a hashKey; set one, or set arguments to full or none",
+		));
+	}
+	Ok(audit)
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct McpToolMerge {
+	/// The target to call first for merged tools. The other targets offering the tool are tried, in
+	/// order, if the call could not be sent. Calls that fail after reaching a target are only retried
+	/// if every target annotates the tool as idempotent or read-only.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub primary: Option<String>,
+}
+
+/// Classifies tools for a read-only MCP backend. Tools are named as clients call them, with their
+/// target prefix if there is one.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct McpReadOnly {
+	/// Tools that do not change state, whatever their annotations. For tools known to be safe whose
+	/// target does not annotate them.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub read_only_tools: Vec<String>,
+	/// Tools that change state, whatever their annotations.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub mutating_tools: Vec<String>,
+}
+
+/// A deadline for the requests an MCP backend sends to every target to list tools, prompts or
+/// resources.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct McpListDeadline {
+	/// How long to wait for the targets. Targets that have not answered by then are left out of the
+	/// list.
+	#[serde(with = "serde_dur")]
+	#[cfg_attr(feature = "schema", schemars(with = "String"))]
+	pub timeout: Duration,
+	/// If set, clients may ask for a shorter deadline with this request header, in milliseconds.
+	/// Longer deadlines are capped to `timeout`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub header: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct McpServerInfo {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub version: Option<String>,
+	/// Instructions for clients on how to use this server. If unset, a description of the
+	/// aggregated targets is returned.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub instructions: Option<String>,
+	/// The protocol version to advertise. If unset, the client's requested version is used if it is
+	/// supported, otherwise the latest supported version.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+	pub protocol_version: Option<rmcp::model::ProtocolVersion>,
+	/// The capabilities to advertise. If unset, the union of the capabilities of the connected
+	/// targets is advertised.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub capabilities: Option<Vec<McpCapability>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum McpCapability {
+	Tools,
+	Prompts,
+	Resources,
+	Completions,
+	Logging,
+}
+
+fn de_failure_ratio<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let ratio = Option::<f64>::deserialize(deserializer)?;
+	if let Some(ratio) = ratio
+		&& !(0.0..=1.0).contains(&ratio)
+	{
+		return Err(serde::de::Error::custom(format!(
+			"failureRatio must be between 0 and 1, got {ratio}"
+		)));
+	}
+	Ok(ratio)
+}
+
+fn de_mcp_targets<'de, D>(deserializer: D) -> Result<Vec<Arc<McpTarget>>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let targets = Vec::<Arc<McpTarget>>::deserialize(deserializer)?;
+	validate_mcp_target_names(&targets).map_err(serde::de::Error::custom)?;
+	// Credentials for a static schema are resolved once, so invalid ones are rejected with the rest of
+	// the configuration. Remote schemas are resolved when they are fetched.
+	for target in &targets {
+		if let McpTargetSpec::OpenAPI(open) = &target.spec
+			&& let OpenAPISchema::Static(schema) = &open.schema
+		{
+			open
+				.resolved_credentials
+				.get(&target.name, schema, &open.security_credentials)
+				.map_err(|e| serde::de::Error::custom(format!("{e:#}")))?;
+		}
+	}
+	Ok(targets)
+}
+
+/// Rejects target and group names that cannot be routed to unambiguously.
+pub fn validate_mcp_target_names(targets: &[Arc<McpTarget>]) -> anyhow::Result<()> {
+	let mut seen = std::collections::HashSet::new();
+	for target in targets {
+		let name = target.name.as_str();
+		validate_mcp_target_name(name)?;
+		if !seen.insert(name) {
+			anyhow::bail!("duplicate MCP target name {name:?}");
+		}
+		if target.weight.is_some() && target.group.is_none() {
+			anyhow::bail!("MCP target {name:?} has a weight but no group");
+		}
+	}
+	let groups = targets
+		.iter()
+		.filter_map(|t| Some((t.group.as_ref()?, t.weight.unwrap_or(1))))
+		.into_group_map();
+	for (group, weights) in groups {
+		validate_mcp_target_name(group)?;
+		if seen.contains(group.as_str()) {
+			anyhow::bail!("MCP target group {group:?} has the same name as a target");
+		}
+		if weights.iter().all(|w| *w == 0) {
+			anyhow::bail!("MCP target group {group:?} has no member with a positive weight");
+		}
+	}
+	Ok(())
+}
+
+fn validate_mcp_target_name(name: &str) -> anyhow::Result<()> {
+	if name.is_empty() {
+		anyhow::bail!("MCP target name must not be empty");
+	}
+	if let Some(c) = name
+		.chars()
+		.find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_')))
+	{
+		anyhow::bail!(
+			"MCP target name {name:?} contains {c:?}; only ASCII letters, digits, '-', '.' and '_' are allowed"
+		);
+	}
+	Ok(())
 }
 
 impl McpBackend {
+	pub fn allows(&self, transport: McpTransport) -> bool {
+		self
+			.transports
+			.as_ref()
+			.is_none_or(|t| t.contains(&transport))
+	}
+
 	pub fn find(&self, name: &str) -> Option<Arc<McpTarget>> {
 		self
 			.targets
@@ -476,10 +1192,45 @@ pub struct McpTarget {
 	pub spec: McpTargetSpec,
 	#[serde(default, skip_serializing_if = "Vec::is_empty")]
 	pub filters: Vec<mcp::relay::upstream::Filter>,
+	/// Targets with the same group are replicas of one server. They are presented to clients as a
+	/// single target named after the group, and each tool call is sent to one of them, in proportion
+	/// to their weights. Members whose circuit breaker is open are skipped.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub group: Option<Strng>,
+	/// The share of the group's tool calls sent to this target. Defaults to 1; a target with weight 0
+	/// receives no calls.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub weight: Option<u32>,
 }
 
 type McpTargetName = Strng;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum McpTransport {
+	/// The legacy HTTP+SSE transport, served on `/sse`.
+	Sse,
+	/// The streamable HTTP transport, served on all other paths.
+	StreamableHttp,
+	/// JSON-RPC over a WebSocket, served on `/ws`.
+	WebSocket,
+}
+
+impl McpTransport {
+	/// The transport a request to the given path is served by, if any.
+	pub fn for_path(path: &str) -> Option<McpTransport> {
+		match path {
+			"/sse" => Some(McpTransport::Sse),
+			"/ws" => Some(McpTransport::WebSocket),
+			"/.well-known/oauth-protected-resource"
+			| "/.well-known/oauth-authorization-server"
+			| "/client-registration" => None,
+			_ => Some(McpTransport::StreamableHttp),
+		}
+	}
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
@@ -493,11 +1244,127 @@ pub enum McpTargetSpec {
 		cmd: String,
 		#[serde(default, skip_serializing_if = "Vec::is_empty")]
 		args: Vec<String>,
+		/// Environment variables set for the process.
 		#[serde(default, skip_serializing_if = "HashMap::is_empty")]
 		env: HashMap<String, String>,
+		/// Whether the process inherits the environment of the gateway, with `env` set on top. If false,
+		/// the process only gets the variables in `env`. Defaults to true.
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		env_inherit: Option<bool>,
+		/// The working directory of the process. Defaults to the working directory of the gateway.
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		cwd: Option<PathBuf>,
+		/// Environment variables set to claims of the authenticated caller, mapping each variable to
+		/// the claim, such as `sub` or `org.id` for a nested claim. Only string claims are set.
+		///
+		/// The process belongs to a client session and is started on its first use, so the variables
+		/// hold the claims of the request that started it. They do not change if later requests in
+		/// the session carry different claims, nor does the process learn who makes each call.
+		#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+		identity_env: HashMap<String, String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		restart: Option<StdioRestartPolicy>,
 	},
 	#[serde(rename = "openapi")]
 	OpenAPI(OpenAPITarget),
+	#[serde(rename = "grpc")]
+	Grpc(GrpcTargetSpec),
+}
+
+impl McpTargetSpec {
+	/// Checks a stdio target may run its command, see [check_stdio_command]. Other targets pass.
+	pub fn check_stdio_command(&self, allowlist: &[String]) -> anyhow::Result<()> {
+		let McpTargetSpec::Stdio {
+			cmd,
+			env,
+			identity_env,
+			..
+		} = self
+		else {
+			return Ok(());
+		};
+		check_stdio_command(cmd, env.keys().chain(identity_env.keys()), allowlist)
+	}
+}
+
+/// The stdio command allowlist entry that allows any command.
+pub const ANY_STDIO_COMMAND: &str = "*";
+
+/// Checks `cmd` is in `allowlist`. No command is allowed unless listed, so an empty allowlist
+/// allows none; [ANY_STDIO_COMMAND] opts in to allowing any. A command given by name is looked up
+/// in the `PATH` of the process, so `vars`, the variables the target sets, may not include `PATH`:
+/// that would run whatever the target likes under an allowed name.
+pub fn check_stdio_command<'a>(
+	cmd: &str,
+	vars: impl IntoIterator<Item = &'a String>,
+	allowlist: &[String],
+) -> anyhow::Result<()> {
+	if allowlist.iter().any(|a| a == ANY_STDIO_COMMAND) {
+		static WARN: std::sync::Once = std::sync::Once::new();
+		WARN.call_once(|| {
+			warn!(
+				"stdioCommandAllowlist contains \"{ANY_STDIO_COMMAND}\", so stdio MCP targets may run any command. List the commands they may run instead"
+			)
+		});
+		return Ok(());
+	}
+	if allowlist.is_empty() {
+		anyhow::bail!(
+			"command '{cmd}' is not allowed, as stdioCommandAllowlist is not set. Set it to the commands stdio MCP targets may run, or to [\"{ANY_STDIO_COMMAND}\"] to allow any"
+		);
+	}
+	if !allowlist.iter().any(|a| a == cmd) {
+		anyhow::bail!("command '{cmd}' is not in the stdio command allowlist");
+	}
+	if !cmd.contains('/') && vars.into_iter().any(|v| v == "PATH") {
+		anyhow::bail!(
+			"command '{cmd}' is looked up in PATH, which may not be set by the target unless any command is allowed"
+		);
+	}
+	Ok(())
+}
+
+/// The delay before the first reconnect attempt, when none is configured.
+const DEFAULT_RECONNECT_BACKOFF: Duration = Duration::from_millis(500);
+
+/// The upper bound for any restart or reconnect delay.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Exponential backoff: the delay before attempt number `attempt` (starting from 0) is `base` doubled
+/// for each earlier attempt, capped at `MAX_BACKOFF`.
+fn exponential_backoff(base: Duration, attempt: u32) -> Duration {
+	base
+		.saturating_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX))
+		.min(MAX_BACKOFF)
+}
+
+/// Controls whether a stdio MCP server is restarted after its process exits. Restarts happen lazily,
+/// on the next request that needs the target.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct StdioRestartPolicy {
+	/// Maximum number of restarts over the lifetime of a session.
+	pub max_restarts: u32,
+	/// Delay before the first restart. The delay doubles for each subsequent restart, up to 30s. If
+	/// unset, the server is restarted immediately.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "serde_dur_option"
+	)]
+	#[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+	pub backoff: Option<Duration>,
+}
+
+impl StdioRestartPolicy {
+	/// The delay to wait before performing restart number `attempt` (starting from 0).
+	pub fn backoff_for(&self, attempt: u32) -> Duration {
+		self
+			.backoff
+			.map(|b| exponential_backoff(b, attempt))
+			.unwrap_or_default()
+	}
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -508,6 +1375,40 @@ pub struct SseTargetSpec {
 	pub host: String,
 	pub port: u32,
 	pub path: String,
+	/// Controls reconnecting the event stream if it drops. Reconnects resume from the last received
+	/// event using the `Last-Event-ID` header. If unset, a default exponential backoff is used.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub reconnect: Option<SseReconnectPolicy>,
+}
+
+/// Controls how a dropped SSE event stream is re-established.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SseReconnectPolicy {
+	/// Maximum number of consecutive reconnect attempts before the target is considered closed.
+	pub max_retries: u32,
+	/// Delay before the first reconnect attempt. The delay doubles for each subsequent attempt, up to
+	/// 30s. Defaults to 500ms.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "serde_dur_option"
+	)]
+	#[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+	pub backoff: Option<Duration>,
+}
+
+impl SseReconnectPolicy {
+	/// The delay to wait before performing reconnect attempt number `attempt` (starting from 0), or
+	/// `None` if no more attempts should be made.
+	pub fn backoff_for(&self, attempt: u32) -> Option<Duration> {
+		if attempt >= self.max_retries {
+			return None;
+		}
+		let base = self.backoff.unwrap_or(DEFAULT_RECONNECT_BACKOFF);
+		Some(exponential_backoff(base, attempt))
+	}
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -520,18 +1421,398 @@ pub struct StreamableHTTPTargetSpec {
 	pub path: String,
 }
 
+/// An MCP server exposing the `agentgateway.dev.mcp.Mcp` gRPC service (see `proto/mcp.proto`).
+/// TLS and authentication are configured with the `backendTLS` and `backendAuth` policies.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
-pub struct OpenAPITarget {
+pub struct GrpcTargetSpec {
 	pub host: String,
 	pub port: u32,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct OpenAPITarget {
+	/// The host of the API. Defaults to the host of the schema's server URL.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub host: Option<String>,
+	/// The port of the API. Defaults to the port of the schema's server URL, and otherwise to the
+	/// default port of the scheme.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub port: Option<u32>,
 	#[serde(deserialize_with = "de_openapi")]
 	#[cfg_attr(feature = "schema", schemars(with = "serde_json::value::RawValue"))]
-	pub schema: Arc<OpenAPI>,
+	pub schema: OpenAPISchema,
+	/// Non-2xx status codes that should be returned to the caller as a normal result rather than
+	/// an error. For example, an API that returns 404 for "not found" lookups.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub success_statuses: Vec<StatusRange>,
+	/// Send the schema's `default` value for optional query, header and body properties the caller
+	/// omitted. An explicit argument always takes precedence over the default; without this option,
+	/// omitted properties are not sent at all.
+	#[serde(default, skip_serializing_if = "is_default")]
+	pub apply_defaults: bool,
+	/// Add each tool's arguments to its description: their names, types, whether they are required
+	/// and their descriptions. This helps agents choose and call tools; the input schema is unchanged.
+	#[serde(default, skip_serializing_if = "is_default")]
+	pub describe_arguments: bool,
+	/// Add example arguments to each tool's description, from the `example` or first `examples` entry
+	/// of the operation's parameters and request body, so agents see a concrete call.
+	#[serde(default, skip_serializing_if = "is_default")]
+	pub include_examples: bool,
+	/// How tool arguments are laid out in the input schema.
+	#[serde(default, skip_serializing_if = "is_default")]
+	pub argument_layout: ArgumentLayout,
+	/// What to do with operations the schema marks deprecated.
+	#[serde(default, skip_serializing_if = "is_default")]
+	pub deprecated_operations: DeprecatedOperations,
+	/// If set, only operations whose `tags` match the filter are offered as tools.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tags: Option<TagFilter>,
+	/// If not empty, only operations with one of these HTTP methods, such as `GET`, are offered as
+	/// tools. Applied along with `tags`.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub allowed_methods: Vec<String>,
+	/// If set, tool call arguments are checked against the tool's input schema before calling the
+	/// API, and calls with invalid arguments are rejected with the list of problems.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub validate_arguments: Option<ArgumentValidation>,
+	/// Overrides the path prefix derived from the schema's `servers` entry, for APIs served behind a
+	/// gateway that adds its own prefix.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub path_prefix_override: Option<PathPrefixOverride>,
+	/// Send the reserved characters of path parameter values, such as `/` and `:`, without encoding
+	/// them. `?` and `#` are always encoded, as they would end the path. By default, everything but
+	/// letters, digits and `-._~` is percent-encoded, so a value is always a single path segment.
+	#[serde(default, skip_serializing_if = "is_default")]
+	pub allow_reserved_path_characters: bool,
+	/// The scheme to call the API with. Defaults to the scheme of the schema's server URL, if it is
+	/// absolute, and otherwise to `https` if a `backendTLS` policy is set and `http` if not. A
+	/// `backendTLS` policy is always used when set; with `https` and no policy, the system trust
+	/// roots are used.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub scheme: Option<HttpScheme>,
+	/// If set, the target gets its own pool of idle connections with these settings, rather than
+	/// sharing the gateway's. Connections are kept alive and reused across the calls of all tools and
+	/// sessions either way.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub connection_pool: Option<UpstreamConnectionPool>,
+	/// The HTTP version to use when calling the API.
+	#[serde(default, skip_serializing_if = "is_default")]
+	pub http_version: HttpVersionPreference,
+	/// Response media types to return as text, in addition to `text/*`, JSON, XML, YAML, JavaScript
+	/// and form data. Patterns may use `*` for the subtype, as in `application/*+csv`. Successful
+	/// responses with any other content type are returned base64 encoded, along with their type.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub text_content_types: Vec<String>,
+	/// Headers added to every call to the API, replacing any passed as tool arguments. They never
+	/// replace the `Content-Type` of a request body.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub static_headers: Vec<StaticHeader>,
+	/// Header names that are never forwarded from tool arguments. Hop-by-hop headers such as
+	/// `Connection` are always dropped.
+	#[serde(
+		default,
+		skip_serializing_if = "Vec::is_empty",
+		serialize_with = "ser_display_iter",
+		deserialize_with = "de_parse_vec"
+	)]
+	#[cfg_attr(feature = "schema", schemars(with = "Vec<String>"))]
+	pub denied_headers: Vec<HeaderName>,
+	/// Header names whose values are masked in logs and error messages, in addition to
+	/// `Authorization`, `Proxy-Authorization`, `Cookie` and the static headers.
+	#[serde(
+		default,
+		skip_serializing_if = "Vec::is_empty",
+		serialize_with = "ser_display_iter",
+		deserialize_with = "de_parse_vec"
+	)]
+	#[cfg_attr(feature = "schema", schemars(with = "Vec<String>"))]
+	pub sensitive_headers: Vec<HeaderName>,
+	/// Credentials for the security schemes declared by the schema. Each call sends the credentials
+	/// its operation requires. API keys in a header or query parameter and HTTP bearer tokens are
+	/// supported; other schemes, such as OAuth2, are reported when the schema is parsed.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub security_credentials: Vec<SecurityCredential>,
+	/// The security credentials, resolved against the schema.
+	#[serde(skip)]
+	pub resolved_credentials: crate::mcp::openapi::security::Resolved,
+}
+
+impl OpenAPITarget {
+	/// How the schema's operations become tools, for a backend that is `read_only` or not.
+	pub(crate) fn parse_options(&self, read_only: bool) -> crate::mcp::openapi::ParseOptions {
+		crate::mcp::openapi::ParseOptions {
+			deprecated_operations: self.deprecated_operations,
+			tags: self.tags.clone(),
+			allowed_methods: self.allowed_methods.clone(),
+			annotate_read_only: read_only,
+		}
+	}
 }
 
-fn de_openapi<'a, D>(deserializer: D) -> Result<Arc<OpenAPI>, D::Error>
+/// The credential for a security scheme of an OpenAPI target.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SecurityCredential {
+	/// The name of the scheme in the schema's `components.securitySchemes`.
+	pub scheme: String,
+	/// The API key or bearer token: inline, read from a file with `file: <path>`, or read from an
+	/// environment variable with `env: <name>`.
+	#[cfg_attr(feature = "schema", schemars(with = "FileOrInline"))]
+	#[serde(
+		serialize_with = "ser_redact",
+		deserialize_with = "deser_key_from_file"
+	)]
+	pub value: SecretString,
+}
+
+/// How the arguments of the tools of an OpenAPI target are laid out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum ArgumentLayout {
+	/// Arguments are grouped by where they are sent: `path`, `query`, `header` and `body`.
+	#[default]
+	Nested,
+	/// Every parameter, and each property of an object body, is a top-level argument. Arguments with
+	/// the same name in several places are prefixed with where they are sent, as in `path_id` and
+	/// `body_id`.
+	Flat,
+}
+
+/// What to do with the deprecated operations of an OpenAPI target.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum DeprecatedOperations {
+	/// Offer them as tools, marked deprecated: the description starts with `Deprecated.` and the
+	/// input schema has `deprecated: true`.
+	#[default]
+	Include,
+	/// Do not offer them as tools.
+	Exclude,
+}
+
+/// Chooses the operations of an OpenAPI target by their `tags`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct TagFilter {
+	/// If not empty, only operations with at least one of these tags are included.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub include: Vec<String>,
+	/// Operations with any of these tags are excluded, even if they also have an included tag.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub exclude: Vec<String>,
+	/// Whether operations without tags are included. By default they are, unless `include` is set.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub include_untagged: Option<bool>,
+}
+
+impl TagFilter {
+	/// Whether an operation with `tags` is included.
+	pub fn matches(&self, tags: &[String]) -> bool {
+		if tags.is_empty() {
+			return self.include_untagged.unwrap_or(self.include.is_empty());
+		}
+		if tags.iter().any(|t| self.exclude.contains(t)) {
+			return false;
+		}
+		self.include.is_empty() || tags.iter().any(|t| self.include.contains(t))
+	}
+}
+
+/// A header added to every call to an OpenAPI target.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct StaticHeader {
+	#[serde(serialize_with = "ser_display", deserialize_with = "de_parse")]
+	#[cfg_attr(feature = "schema", schemars(with = "String"))]
+	pub name: HeaderName,
+	/// The header value: inline, read from a file with `file: <path>`, or read from an environment
+	/// variable with `env: <name>`.
+	#[cfg_attr(feature = "schema", schemars(with = "FileOrInline"))]
+	#[serde(
+		serialize_with = "ser_redact",
+		deserialize_with = "deser_key_from_file"
+	)]
+	pub value: SecretString,
+}
+
+/// Settings for the idle connections kept open to an upstream API.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct UpstreamConnectionPool {
+	/// The most idle connections kept per upstream address. Unlimited if not set.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub max_idle_per_host: Option<u32>,
+	/// How long a connection may stay idle before it is closed. Defaults to 90s.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "serde_dur_option"
+	)]
+	#[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+	pub idle_timeout: Option<Duration>,
+}
+
+impl UpstreamConnectionPool {
+	pub fn pool_config(&self) -> crate::client::PoolConfig {
+		crate::client::PoolConfig {
+			max_idle_per_host: self.max_idle_per_host.map(|m| m as usize),
+			idle_timeout: self.idle_timeout,
+		}
+	}
+}
+
+/// The scheme used for calls to an upstream API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum HttpScheme {
+	#[default]
+	Http,
+	Https,
+}
+
+impl HttpScheme {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			HttpScheme::Http => "http",
+			HttpScheme::Https => "https",
+		}
+	}
+}
+
+/// The HTTP version used for calls to an upstream API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum HttpVersionPreference {
+	/// Negotiate the version with ALPN over TLS, and use HTTP/1.1 for plaintext connections.
+	#[default]
+	Auto,
+	/// Always use HTTP/1.1. Over TLS, only `http/1.1` is offered during ALPN.
+	Http1,
+	/// Always use HTTP/2. Over TLS, only `h2` is offered during ALPN; plaintext connections use
+	/// HTTP/2 with prior knowledge.
+	Http2,
+}
+
+impl HttpVersionPreference {
+	/// The version to pin requests to, if any.
+	pub fn version(self) -> Option<::http::Version> {
+		match self {
+			HttpVersionPreference::Auto => None,
+			HttpVersionPreference::Http1 => Some(::http::Version::HTTP_11),
+			HttpVersionPreference::Http2 => Some(::http::Version::HTTP_2),
+		}
+	}
+}
+
+/// A path prefix to use for an OpenAPI target instead of, or in front of, the schema's prefix.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct PathPrefixOverride {
+	pub prefix: String,
+	#[serde(default)]
+	pub mode: PathPrefixMode,
+}
+
+/// How a `PathPrefixOverride` is combined with the prefix derived from the schema.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum PathPrefixMode {
+	/// Use the override in place of the schema's prefix.
+	#[default]
+	Replace,
+	/// Put the override in front of the schema's prefix, so `/api/v2` and `/v1` become `/api/v2/v1`.
+	Prepend,
+}
+
+impl PathPrefixOverride {
+	/// Returns the prefix to use, given the prefix derived from the schema.
+	pub fn apply(&self, derived: &str) -> String {
+		let prefix = self.prefix.trim_end_matches('/');
+		match self.mode {
+			PathPrefixMode::Replace => prefix.to_string(),
+			PathPrefixMode::Prepend => {
+				let derived = derived.trim_matches('/');
+				if derived.is_empty() {
+					prefix.to_string()
+				} else {
+					format!("{prefix}/{derived}")
+				}
+			},
+		}
+	}
+}
+
+/// Controls validation of tool call arguments for OpenAPI targets.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ArgumentValidation {
+	/// Reject arguments containing properties that are not declared in the schema.
+	#[serde(default)]
+	pub reject_unknown_properties: bool,
+}
+
+/// An inclusive range of HTTP status codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct StatusRange {
+	pub min: u16,
+	pub max: u16,
+}
+
+impl<'de> serde::Deserialize<'de> for StatusRange {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		#[derive(serde::Deserialize)]
+		#[serde(rename_all = "camelCase")]
+		struct Raw {
+			min: u16,
+			max: u16,
+		}
+		let Raw { min, max } = Raw::deserialize(deserializer)?;
+		if min > max {
+			return Err(serde::de::Error::custom(format!(
+				"status range min {min} is greater than max {max}"
+			)));
+		}
+		Ok(StatusRange { min, max })
+	}
+}
+
+impl StatusRange {
+	pub fn contains(&self, status: StatusCode) -> bool {
+		(self.min..=self.max).contains(&status.as_u16())
+	}
+}
+
+/// The source of an OpenAPI target's schema.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(untagged)]
+pub enum OpenAPISchema {
+	/// A schema read from a file or inline when the configuration is loaded.
+	Static(Arc<OpenAPI>),
+	/// A schema fetched from a URL when the target is first used, and refreshed periodically.
+	Remote(Arc<RemoteSchema>),
+}
+
+fn de_openapi<'a, D>(deserializer: D) -> Result<OpenAPISchema, D::Error>
 where
 	D: serde::Deserializer<'a>,
 {
@@ -540,19 +1821,22 @@ where
 	enum Serde {
 		File(PathBuf),
 		Inline(String),
-		// Remote()
+		Remote(RemoteSchemaConfig),
 	}
 	let s = Serde::deserialize(deserializer)?;
 
-	let s = match s {
+	let (s, path) = match s {
 		Serde::File(f) => {
-			let f = std::fs::read(f).map_err(serde::de::Error::custom)?;
-			String::from_utf8(f).map_err(serde::de::Error::custom)?
+			let contents = std::fs::read(&f).map_err(serde::de::Error::custom)?;
+			let contents = String::from_utf8(contents).map_err(serde::de::Error::custom)?;
+			(contents, Some(f))
 		},
-		Serde::Inline(s) => s,
+		Serde::Inline(s) => (s, None),
+		Serde::Remote(cfg) => return Ok(OpenAPISchema::Remote(Arc::new(RemoteSchema::new(cfg)))),
 	};
-	let schema: OpenAPI = yamlviajson::from_str(s.as_str()).map_err(serde::de::Error::custom)?;
-	Ok(Arc::new(schema))
+	let schema = crate::mcp::openapi::parse_schema(s.as_str(), path.as_deref())
+		.map_err(serde::de::Error::custom)?;
+	Ok(OpenAPISchema::Static(Arc::new(schema)))
 }
 
 #[derive(Debug, Clone, Default)]
@@ -808,6 +2092,10 @@ impl RouteSet {
 		}
 	}
 
+	pub fn iter(&self) -> impl Iterator<Item = &Route> {
+		self.all.values()
+	}
+
 	fn hostname_matchers(r: &Route) -> Vec<HostnameMatch> {
 		if r.hostnames.is_empty() {
 			vec![HostnameMatch::None]
@@ -998,7 +2286,7 @@ pub enum PolicyTarget {
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum Policy {
-	// Supported targets: Backend, only when Backend type is MCP
+	// Supported targets: Backend, only when Backend type is MCP or A2A (filters agent card skills)
 	McpAuthorization(McpAuthorization),
 	// Supported targets: Backend, only when Backend type is MCP
 	McpAuthentication(McpAuthentication),
@@ -1010,6 +2298,8 @@ pub enum Policy {
 	// Supported targets: Backend; single policy allowed
 	BackendAuth(BackendAuth),
 	// Supported targets: Backend; single policy allowed
+	OutboundProxy(http::outboundproxy::OutboundProxy),
+	// Supported targets: Backend; single policy allowed
 	#[serde(rename = "ai")]
 	AI(llm::Policy),
 
@@ -1027,10 +2317,56 @@ pub enum Policy {
 	JwtAuth(crate::http::jwt::Jwt),
 }
 
+/// The policies that authenticate callers, or the gateway to a backend, named as they are configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthenticationPolicy {
+	McpAuthentication,
+	BackendAuth,
+	JwtAuth,
+	ExtAuthz,
+}
+
+impl Policy {
+	/// The kind of authentication the policy performs, if any.
+	pub fn authentication(&self) -> Option<AuthenticationPolicy> {
+		match self {
+			Policy::McpAuthentication(_) => Some(AuthenticationPolicy::McpAuthentication),
+			Policy::BackendAuth(_) => Some(AuthenticationPolicy::BackendAuth),
+			Policy::JwtAuth(_) => Some(AuthenticationPolicy::JwtAuth),
+			Policy::ExtAuthz(_) => Some(AuthenticationPolicy::ExtAuthz),
+			Policy::McpAuthorization(_)
+			| Policy::A2a(_)
+			| Policy::BackendTLS(_)
+			| Policy::OutboundProxy(_)
+			| Policy::AI(_)
+			| Policy::LocalRateLimit(_)
+			| Policy::RemoteRateLimit(_) => None,
+		}
+	}
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
-pub struct A2aPolicy {}
+pub struct A2aPolicy {
+	/// Warn when a streaming response moves a task through an invalid state transition, such as
+	/// `completed` to `working`. Useful for catching misbehaving agents.
+	#[serde(default)]
+	pub validate_task_states: bool,
+	/// If set, each call to the agent is logged.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub logging: Option<PayloadLogging>,
+	/// Maximum size, in bytes, of a request body sent to the agent. Larger requests are rejected with
+	/// a 413 before being parsed. Defaults to 4MiB.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub max_request_size: Option<usize>,
+	/// Reject push notification URLs that point at loopback, private or link-local addresses, so
+	/// clients cannot have the agent call internal services. Off by default, as agents often notify
+	/// services on the same network.
+	#[serde(default)]
+	pub deny_private_push_notification_urls: bool,
+}
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -1051,7 +2387,7 @@ impl McpAuthentication {
 	pub fn as_jwt(&self) -> anyhow::Result<http::jwt::LocalJwtConfig> {
 		Ok(http::jwt::LocalJwtConfig {
 			issuer: self.issuer.clone(),
-			audiences: vec![self.audience.clone()],
+			audiences: vec![http::jwt::AudienceMatch::Exact(self.audience.clone())],
 			jwks: FileInlineOrRemote::Remote {
 				url: match &self.provider {
 					None | Some(McpIDP::Auth0 { .. }) => {
@@ -1063,6 +2399,7 @@ impl McpAuthentication {
 					// Some(McpIDP::Keycloak { realm }) => format!("{}/realms/{realm}/protocol/openid-connect/certs", self.issuer).parse()?,
 				},
 			},
+			cache: None,
 		})
 	}
 }
@@ -1109,6 +2446,13 @@ impl TryFrom<(&str, u16)> for Target {
 	type Error = anyhow::Error;
 
 	fn try_from((host, port): (&str, u16)) -> Result<Self, Self::Error> {
+		// IPv6 literals may be enclosed in brackets, as they are in URIs.
+		if let Some(ip) = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+			let ip: net::Ipv6Addr = ip
+				.parse()
+				.map_err(|e| anyhow!("invalid IPv6 address {host}: {e}"))?;
+			return Ok(Target::Address(SocketAddr::new(ip.into(), port)));
+		}
 		match host.parse::<IpAddr>() {
 			Ok(target) => Ok(Target::Address(SocketAddr::new(target, port))),
 			Err(_) => Ok(Target::Hostname(host.into(), port)),
@@ -1120,9 +2464,12 @@ impl TryFrom<&str> for Target {
 	type Error = anyhow::Error;
 
 	fn try_from(hostport: &str) -> Result<Self, Self::Error> {
-		let Some((host, port)) = hostport.split_once(":") else {
+		let Some((host, port)) = hostport.rsplit_once(":") else {
 			anyhow::bail!("invalid host:port: {}", hostport);
 		};
+		if host.contains(':') && !host.starts_with('[') {
+			anyhow::bail!("invalid host:port: {hostport} (IPv6 addresses must be enclosed in brackets)");
+		}
 		let port: u16 = port.parse()?;
 		(host, port).try_into()
 	}
@@ -1137,3 +2484,7 @@ impl Display for Target {
 		write!(f, "{str}")
 	}
 }
+
+#[cfg(test)]
+#[path = "agent_tests.rs"]
+mod tests;
@@ -0,0 +1,472 @@
+use super::*;
+
+#[test]
+fn test_tls_config_serialize() {
+	let cert = rcgen::generate_simple_self_signed(vec!["example.com".to_string()]).unwrap();
+	let key_pem = cert.key_pair.serialize_pem();
+	let tls = TLSConfig::new(
+		parse_cert(cert.cert.pem().as_bytes()).unwrap(),
+		parse_key(key_pem.as_bytes()).unwrap(),
+		vec![b"h2".to_vec()],
+		&TLSServerOptions::default(),
+	)
+	.unwrap();
+
+	let js = serde_json::to_value(ListenerProtocol::HTTPS(tls)).unwrap();
+	let summary = &js["HTTPS"];
+	assert_eq!(summary["alpnProtocols"], serde_json::json!(["h2"]));
+	let certs = summary["certificates"].as_array().unwrap();
+	assert_eq!(certs.len(), 1);
+	let san = certs[0]["subjectAltNames"][0].as_str().unwrap();
+	assert!(san.contains("example.com"), "unexpected SAN {san}");
+
+	let raw = js.to_string();
+	let key_body = key_pem.lines().nth(1).unwrap();
+	assert!(
+		!raw.contains(key_body),
+		"private key must not be serialized"
+	);
+	assert!(!raw.contains("PRIVATE KEY"));
+}
+
+fn tls_config(options: TLSServerOptions) -> anyhow::Result<TLSConfig> {
+	let cert = rcgen::generate_simple_self_signed(vec!["example.com".to_string()]).unwrap();
+	TLSConfig::new(
+		parse_cert(cert.cert.pem().as_bytes()).unwrap(),
+		parse_key(cert.key_pair.serialize_pem().as_bytes()).unwrap(),
+		vec![],
+		&options,
+	)
+}
+
+// Send a ClientHello offering only the given version, and report whether the server accepted it.
+fn accepts_version(server: &TLSConfig, version: &'static rustls::SupportedProtocolVersion) -> bool {
+	let client =
+		ClientConfig::builder_with_provider(Arc::new(rustls::crypto::aws_lc_rs::default_provider()))
+			.with_protocol_versions(&[version])
+			.unwrap()
+			.with_root_certificates(rustls::RootCertStore::empty())
+			.with_no_client_auth();
+	let mut client =
+		rustls::ClientConnection::new(Arc::new(client), "example.com".try_into().unwrap()).unwrap();
+	let mut server = rustls::ServerConnection::new(server.config.clone()).unwrap();
+	let mut hello = vec![];
+	client.write_tls(&mut hello).unwrap();
+	server.read_tls(&mut hello.as_slice()).unwrap();
+	server.process_new_packets().is_ok()
+}
+
+#[test]
+fn test_tls_min_version() {
+	let all_suites = Some(vec![
+		"TLS13_AES_128_GCM_SHA256".to_string(),
+		"TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256".to_string(),
+	]);
+	let tls12 = tls_config(TLSServerOptions {
+		min_version: Some(TLSVersion::V1_2),
+		cipher_suites: all_suites.clone(),
+	})
+	.unwrap();
+	assert!(accepts_version(&tls12, &rustls::version::TLS12));
+	assert!(accepts_version(&tls12, &rustls::version::TLS13));
+
+	let tls13 = tls_config(TLSServerOptions {
+		min_version: Some(TLSVersion::V1_3),
+		cipher_suites: all_suites,
+	})
+	.unwrap();
+	assert!(!accepts_version(&tls13, &rustls::version::TLS12));
+	assert!(accepts_version(&tls13, &rustls::version::TLS13));
+
+	// The default suites are TLS 1.3 only, so TLS 1.2 is not accepted without configuring suites
+	let default = tls_config(TLSServerOptions::default()).unwrap();
+	assert!(!accepts_version(&default, &rustls::version::TLS12));
+	assert!(accepts_version(&default, &rustls::version::TLS13));
+}
+
+#[test]
+fn test_tls_invalid_options() {
+	let err = tls_config(TLSServerOptions {
+		min_version: None,
+		cipher_suites: Some(vec!["TLS_NOT_A_SUITE".to_string()]),
+	})
+	.unwrap_err();
+	assert!(err.to_string().contains("unknown cipher suite"), "{err}");
+
+	let err = tls_config(TLSServerOptions {
+		min_version: Some(TLSVersion::V1_3),
+		cipher_suites: Some(vec!["TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256".to_string()]),
+	})
+	.unwrap_err();
+	assert!(
+		err
+			.to_string()
+			.contains("none of the configured cipher suites"),
+		"{err}"
+	);
+}
+
+#[test]
+fn test_target_parse() {
+	assert_eq!(
+		Target::try_from("127.0.0.1:8080").unwrap().to_string(),
+		"127.0.0.1:8080"
+	);
+	let Target::Address(addr) = Target::try_from("[::1]:8080").unwrap() else {
+		panic!("expected an address");
+	};
+	assert_eq!(addr, "[::1]:8080".parse().unwrap());
+	let Target::Address(addr) = Target::try_from(("[2001:db8::1]", 443)).unwrap() else {
+		panic!("expected an address");
+	};
+	assert_eq!(addr, "[2001:db8::1]:443".parse().unwrap());
+	let Target::Hostname(host, port) =
+		Target::try_from("my-svc.default.svc.cluster.local:80").unwrap()
+	else {
+		panic!("expected a hostname");
+	};
+	assert_eq!(
+		(host.as_str(), port),
+		("my-svc.default.svc.cluster.local", 80)
+	);
+
+	assert!(Target::try_from("::1:8080").is_err());
+	assert!(Target::try_from(("[example.com]", 80)).is_err());
+	assert!(Target::try_from("example.com").is_err());
+}
+
+#[test]
+fn test_sse_reconnect_backoff() {
+	let policy: SseReconnectPolicy =
+		serde_json::from_value(serde_json::json!({"maxRetries": 3, "backoff": "100ms"})).unwrap();
+	assert_eq!(policy.backoff_for(0), Some(Duration::from_millis(100)));
+	assert_eq!(policy.backoff_for(1), Some(Duration::from_millis(200)));
+	assert_eq!(policy.backoff_for(2), Some(Duration::from_millis(400)));
+	assert_eq!(policy.backoff_for(3), None);
+
+	let policy: SseReconnectPolicy =
+		serde_json::from_value(serde_json::json!({"maxRetries": 1})).unwrap();
+	assert_eq!(policy.backoff_for(0), Some(Duration::from_millis(500)));
+	assert_eq!(policy.backoff_for(1), None);
+}
+
+#[test]
+fn test_stdio_restart_backoff() {
+	let policy: StdioRestartPolicy =
+		serde_json::from_value(serde_json::json!({"maxRestarts": 3, "backoff": "100ms"})).unwrap();
+	assert_eq!(policy.backoff_for(0), Duration::from_millis(100));
+	assert_eq!(policy.backoff_for(2), Duration::from_millis(400));
+
+	// Without a backoff, restarts are immediate
+	let policy: StdioRestartPolicy =
+		serde_json::from_value(serde_json::json!({"maxRestarts": 3})).unwrap();
+	assert_eq!(policy.backoff_for(0), Duration::ZERO);
+	assert_eq!(policy.backoff_for(5), Duration::ZERO);
+
+	// The delay is capped, and large attempt counts do not overflow
+	let policy: StdioRestartPolicy =
+		serde_json::from_value(serde_json::json!({"maxRestarts": 100, "backoff": "1s"})).unwrap();
+	assert_eq!(policy.backoff_for(4), Duration::from_secs(16));
+	assert_eq!(policy.backoff_for(5), Duration::from_secs(30));
+	assert_eq!(policy.backoff_for(64), Duration::from_secs(30));
+}
+
+#[test]
+fn test_path_prefix_override() {
+	let replace: PathPrefixOverride =
+		serde_json::from_value(serde_json::json!({"prefix": "/api/v2/"})).unwrap();
+	assert_eq!(replace.mode, PathPrefixMode::Replace);
+	assert_eq!(replace.apply("/v1"), "/api/v2");
+	assert_eq!(replace.apply("/"), "/api/v2");
+
+	let prepend: PathPrefixOverride =
+		serde_json::from_value(serde_json::json!({"prefix": "/api/v2", "mode": "prepend"})).unwrap();
+	assert_eq!(prepend.apply("/v1"), "/api/v2/v1");
+	assert_eq!(prepend.apply("/v1/"), "/api/v2/v1");
+	assert_eq!(prepend.apply("/"), "/api/v2");
+}
+
+#[test]
+fn test_openapi_header_names() {
+	let target = |headers: serde_json::Value| {
+		serde_json::from_value::<OpenAPITarget>(serde_json::json!({
+			"schema": {"inline": r#"{"openapi": "3.0.0", "info": {"title": "t", "version": "1"}, "paths": {}}"#},
+			"deniedHeaders": headers,
+			"sensitiveHeaders": headers,
+		}))
+	};
+	let open = target(serde_json::json!(["X-Internal"])).unwrap();
+	assert_eq!(
+		open.denied_headers,
+		vec![HeaderName::from_static("x-internal")]
+	);
+	assert_eq!(
+		open.sensitive_headers,
+		vec![HeaderName::from_static("x-internal")]
+	);
+	// Invalid names are rejected with the rest of the configuration
+	let err = target(serde_json::json!(["bad header"])).unwrap_err();
+	assert!(
+		err.to_string().contains("invalid HTTP header name"),
+		"{err}"
+	);
+}
+
+#[test]
+fn test_openapi_security_credentials() {
+	let backend = |value: serde_json::Value| {
+		serde_json::from_value::<McpBackend>(serde_json::json!({
+			"targets": [{
+				"name": "api",
+				"openapi": {
+					"schema": {"inline": r#"{"openapi": "3.0.0", "info": {"title": "t", "version": "1"}, "paths": {},
+						"components": {"securitySchemes": {"token": {"type": "http", "scheme": "bearer"}}}}"#},
+					"securityCredentials": [{"scheme": "token", "value": value}],
+				},
+			}],
+		}))
+	};
+	// Credentials are resolved when the configuration is loaded, here from the environment
+	let loaded = backend(serde_json::json!({"env": "CARGO_PKG_NAME"})).unwrap();
+	let McpTargetSpec::OpenAPI(open) = &loaded.targets[0].spec else {
+		panic!("expected an OpenAPI target");
+	};
+	let OpenAPISchema::Static(schema) = &open.schema else {
+		panic!("expected a static schema");
+	};
+	let credentials = open.resolved_credentials.get("api", schema, &[]).unwrap();
+	let crate::mcp::openapi::security::Credential::Header(_, value) = &credentials["token"] else {
+		panic!("expected a header");
+	};
+	assert_eq!(value, "Bearer agentgateway");
+
+	let err = backend(serde_json::json!({"env": "AGENTGATEWAY_TEST_UNSET"})).unwrap_err();
+	assert!(
+		err
+			.to_string()
+			.contains("environment variable AGENTGATEWAY_TEST_UNSET"),
+		"{err}"
+	);
+	// Invalid credentials are rejected with the rest of the configuration
+	let err = backend(serde_json::json!("bad\ntoken")).unwrap_err();
+	assert!(
+		err
+			.to_string()
+			.contains("invalid security credentials for target api"),
+		"{err}"
+	);
+}
+
+#[test]
+fn test_audit_hash_key() {
+	let backend = |audit: serde_json::Value| {
+		serde_json::from_value::<McpBackend>(serde_json::json!({
+			"targets": [{"name": "a", "stdio": {"cmd": "true"}}],
+			"audit": audit,
+		}))
+	};
+	// Arguments are not recorded by default, so no key is needed
+	let audit = backend(serde_json::json!({"sink": "stdout"}))
+		.unwrap()
+		.audit
+		.unwrap();
+	assert_eq!(audit.arguments, AuditArguments::None);
+	// Hashing them needs a key
+	let err = backend(serde_json::json!({"sink": "stdout", "arguments": "hash"})).unwrap_err();
+	assert!(err.to_string().contains("requires a hashKey"), "{err}");
+	backend(serde_json::json!({"sink": "stdout", "arguments": "hash", "hashKey": "k"})).unwrap();
+}
+
+#[test]
+fn test_mcp_circuit_breaker_failure_ratio() {
+	let breaker = |ratio: f64| {
+		serde_json::from_value::<McpCircuitBreaker>(serde_json::json!({"failureRatio": ratio}))
+	};
+	assert_eq!(breaker(0.5).unwrap().failure_ratio, Some(0.5));
+	assert!(breaker(1.0).is_ok());
+	let e = breaker(1.5).unwrap_err().to_string();
+	assert!(e.contains("between 0 and 1"), "{e}");
+	assert!(breaker(-0.1).is_err());
+}
+
+fn mcp_backend(names: &[&str]) -> Result<McpBackend, serde_json::Error> {
+	let targets = names
+		.iter()
+		.map(|name| serde_json::json!({"name": name, "stdio": {"cmd": "true"}}))
+		.collect::<Vec<_>>();
+	serde_json::from_value(serde_json::json!({ "targets": targets }))
+}
+
+#[test]
+fn test_mcp_target_names() {
+	let backend = mcp_backend(&["everything", "time-v2.internal"]).unwrap();
+	assert_eq!(backend.targets.len(), 2);
+	// Underscores are allowed, even though they also separate the target from the tool name
+	mcp_backend(&["my_server", "my"]).unwrap();
+
+	for (names, err) in [
+		(&["everything", ""][..], "must not be empty"),
+		(&[" "][..], "contains ' '"),
+		(&["every thing"][..], "contains ' '"),
+		(&["a/b"][..], "contains '/'"),
+		(
+			&["everything", "everything"][..],
+			"duplicate MCP target name",
+		),
+	] {
+		let e = mcp_backend(names).unwrap_err().to_string();
+		assert!(e.contains(err), "{names:?}: {e}");
+	}
+}
+
+#[test]
+fn test_mcp_transports() {
+	assert_eq!(McpTransport::for_path("/sse"), Some(McpTransport::Sse));
+	assert_eq!(McpTransport::for_path("/ws"), Some(McpTransport::WebSocket));
+	assert_eq!(
+		McpTransport::for_path("/mcp"),
+		Some(McpTransport::StreamableHttp)
+	);
+	assert_eq!(
+		McpTransport::for_path("/.well-known/oauth-protected-resource"),
+		None
+	);
+
+	// All transports are served unless restricted
+	let backend: McpBackend = serde_json::from_value(serde_json::json!({
+		"targets": [{"name": "everything", "stdio": {"cmd": "true"}}],
+	}))
+	.unwrap();
+	assert!(backend.allows(McpTransport::Sse));
+	assert!(backend.allows(McpTransport::WebSocket));
+	let backend: McpBackend = serde_json::from_value(serde_json::json!({
+		"targets": [{"name": "everything", "stdio": {"cmd": "true"}}],
+		"transports": ["streamableHttp", "webSocket"],
+	}))
+	.unwrap();
+	assert!(!backend.allows(McpTransport::Sse));
+	assert!(backend.allows(McpTransport::StreamableHttp));
+	assert!(backend.allows(McpTransport::WebSocket));
+}
+
+#[test]
+fn test_mcp_target_groups() {
+	let group = |targets: serde_json::Value| {
+		serde_json::from_value::<McpBackend>(serde_json::json!({ "targets": targets }))
+	};
+	let backend = group(serde_json::json!([
+		{"name": "a", "stdio": {"cmd": "true"}, "group": "replicas", "weight": 3},
+		{"name": "b", "stdio": {"cmd": "true"}, "group": "replicas"},
+		{"name": "time", "stdio": {"cmd": "true"}},
+	]))
+	.unwrap();
+	assert_eq!(backend.targets[0].group.as_deref(), Some("replicas"));
+	assert_eq!(backend.targets[1].weight, None);
+
+	for (targets, err) in [
+		(
+			serde_json::json!([
+				{"name": "a", "stdio": {"cmd": "true"}, "group": "b"},
+				{"name": "b", "stdio": {"cmd": "true"}},
+			]),
+			"has the same name as a target",
+		),
+		(
+			serde_json::json!([{"name": "a", "stdio": {"cmd": "true"}, "weight": 2}]),
+			"has a weight but no group",
+		),
+		(
+			serde_json::json!([
+				{"name": "a", "stdio": {"cmd": "true"}, "group": "replicas", "weight": 0},
+				{"name": "b", "stdio": {"cmd": "true"}, "group": "replicas", "weight": 0},
+			]),
+			"no member with a positive weight",
+		),
+	] {
+		let e = group(targets).unwrap_err().to_string();
+		assert!(e.contains(err), "{e}");
+	}
+}
+
+#[test]
+fn test_status_range() {
+	let range: StatusRange =
+		serde_json::from_value(serde_json::json!({"min": 404, "max": 404})).unwrap();
+	assert!(range.contains(StatusCode::NOT_FOUND));
+	let e = serde_json::from_value::<StatusRange>(serde_json::json!({"min": 500, "max": 400}))
+		.unwrap_err()
+		.to_string();
+	assert!(e.contains("min 500 is greater than max 400"), "{e}");
+}
+
+#[test]
+fn test_mcp_concurrency_limit() {
+	let limit: McpConcurrencyLimit =
+		serde_json::from_value(serde_json::json!({"maxConcurrent": 2})).unwrap();
+	assert_eq!(limit.max_concurrent.get(), 2);
+	assert!(
+		serde_json::from_value::<McpConcurrencyLimit>(serde_json::json!({"maxConcurrent": 0})).is_err()
+	);
+}
+
+#[test]
+fn test_listener_builder() {
+	let (route, policies) = Route::builder("route").build();
+	assert!(policies.is_empty());
+	let listener = Listener::builder("listener", "gateway")
+		.hostname("*.example.com")
+		.route(route)
+		.build();
+	assert_eq!(listener.key.as_str(), "listener/gateway");
+	assert_eq!(listener.hostname.as_str(), "*.example.com");
+	assert!(matches!(listener.protocol, ListenerProtocol::HTTP));
+	assert!(listener.routes.contains(&strng::new("route")));
+
+	let listener = Listener::builder("listener", "gateway")
+		.key("custom")
+		.protocol(ListenerProtocol::TCP)
+		.build();
+	assert_eq!(listener.key.as_str(), "custom");
+	assert_eq!(listener.name.as_str(), "listener");
+	assert!(matches!(listener.protocol, ListenerProtocol::TCP));
+
+	let bind = Bind::builder("[::]:8080".parse().unwrap())
+		.listener(listener)
+		.max_connections(10)
+		.build();
+	assert_eq!(bind.key.as_str(), "bind/8080");
+	assert!(bind.listeners.contains(&strng::new("custom")));
+	assert_eq!(bind.max_connections, Some(10));
+}
+
+#[test]
+fn test_route_builder() {
+	let backend = |name: &str| RouteBackendReference {
+		weight: 1,
+		backend: BackendReference::Backend(strng::new(name)),
+		filters: vec![],
+	};
+	let (route, policies) = Route::builder("route")
+		.key("listener/route/default")
+		.hostname("example.com")
+		.backend(backend("a"))
+		.backend(backend("b"))
+		.rbac(RuleSet::new(cedar_policy::PolicySet::new()))
+		.build();
+	assert_eq!(route.route_name.as_str(), "route");
+	assert_eq!(route.hostnames, vec![strng::new("example.com")]);
+	assert_eq!(route.backends.len(), 2);
+	let targets = policies.iter().map(|p| &p.target).collect::<Vec<_>>();
+	assert_eq!(
+		targets,
+		vec![
+			&PolicyTarget::Backend(strng::new("a")),
+			&PolicyTarget::Backend(strng::new("b")),
+		]
+	);
+	assert!(
+		policies
+			.iter()
+			.all(|p| matches!(p.policy, Policy::McpAuthorization(_)))
+	);
+}
@@ -42,16 +42,13 @@ impl TryFrom<&proto::agent::TlsConfig> for TLSConfig {
 	fn try_from(value: &proto::agent::TlsConfig) -> Result<Self, Self::Error> {
 		let cert_chain = parse_cert(&value.cert)?;
 		let private_key = parse_key(&value.private_key)?;
-		let mut sc = ServerConfig::builder_with_provider(transport::tls::provider())
-			.with_protocol_versions(transport::tls::ALL_TLS_VERSIONS)
-			.expect("server config must be valid")
-			.with_no_client_auth()
-			.with_single_cert(cert_chain, private_key)?;
 		// TODO: support h2
-		sc.alpn_protocols = vec![b"http/1.1".into()];
-		Ok(TLSConfig {
-			config: Arc::new(sc),
-		})
+		TLSConfig::new(
+			cert_chain,
+			private_key,
+			vec![b"http/1.1".into()],
+			&TLSServerOptions::default(),
+		)
 	}
 }
 
@@ -147,11 +144,14 @@ impl TryFrom<&proto::agent::Bind> for Bind {
 	type Error = ProtoError;
 
 	fn try_from(s: &proto::agent::Bind) -> Result<Self, Self::Error> {
-		Ok(Self {
-			key: s.key.clone().into(),
-			address: SocketAddr::from((IpAddr::from([0, 0, 0, 0]), s.port as u16)),
-			listeners: Default::default(),
-		})
+		Ok(
+			Bind::builder(SocketAddr::from((
+				IpAddr::from([0, 0, 0, 0]),
+				s.port as u16,
+			)))
+			.key(s.key.clone())
+			.build(),
+		)
 	}
 }
 
@@ -162,15 +162,11 @@ impl TryFrom<&proto::agent::Listener> for (Listener, BindName) {
 		let proto = proto::agent::Protocol::try_from(s.protocol)?;
 		let protocol = ListenerProtocol::try_from((proto, s.tls.as_ref()))
 			.map_err(|e| ProtoError::Generic(format!("{e}")))?;
-		let l = Listener {
-			key: strng::new(&s.key),
-			name: strng::new(&s.name),
-			hostname: s.hostname.clone().into(),
-			protocol,
-			gateway_name: strng::new(&s.gateway_name),
-			routes: Default::default(),
-			tcp_routes: Default::default(),
-		};
+		let l = Listener::builder(strng::new(&s.name), strng::new(&s.gateway_name))
+			.key(strng::new(&s.key))
+			.hostname(s.hostname.clone())
+			.protocol(protocol)
+			.build();
 		Ok((l, strng::new(&s.bind_key)))
 	}
 }
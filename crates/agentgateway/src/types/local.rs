@@ -7,12 +7,13 @@ use crate::store::LocalWorkload;
 use crate::transport::tls;
 use crate::types::agent::PolicyTarget::RouteRule;
 use crate::types::agent::{
-	A2aPolicy, Backend, BackendName, BackendReference, Bind, BindName, GatewayName, Listener,
-	ListenerKey, ListenerProtocol, ListenerSet, McpAuthentication, McpAuthorization, McpBackend,
-	PathMatch, Policy, PolicyTarget, Route, RouteBackend, RouteBackendReference, RouteFilter,
-	RouteMatch, RouteName, RouteRuleName, RouteSet, SimpleBackend, SimpleBackendReference, TCPRoute,
-	TCPRouteBackendReference, TCPRouteSet, TLSConfig, Target, TargetedPolicy, TrafficPolicy,
-	parse_cert, parse_key,
+	A2aPolicy, AuthenticationPolicy, Backend, BackendName, BackendReference, Bind, BindName,
+	GatewayName, Listener, ListenerKey, ListenerProtocol, ListenerSet, McpAuthentication,
+	McpAuthorization, McpBackend, McpTargetSpec, OpenAPISchema, OpenAPITarget, PathMatch, Policy,
+	PolicyTarget, ProxyProtocol, Route, RouteBackend, RouteBackendReference, RouteFilter, RouteMatch,
+	RouteName, RouteRuleName, SimpleBackend, SimpleBackendReference, TCPRoute,
+	TCPRouteBackendReference, TLSConfig, TLSServerOptions, TLSVersion, Target, TargetedPolicy,
+	TrafficPolicy, parse_cert, parse_key,
 };
 use crate::types::discovery::{NamespacedHostname, Service};
 use crate::*;
@@ -30,18 +31,264 @@ use std::io::Cursor;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
+
+#[cfg(test)]
+#[path = "local_tests.rs"]
+mod tests;
 
 impl NormalizedLocalConfig {
 	pub async fn from(client: client::Client, s: &str) -> anyhow::Result<NormalizedLocalConfig> {
-		// Avoid shell expanding the comment for schema. Probably there are better ways to do this!
-		let s = s.replace("# yaml-language-server: $schema", "#");
-		let s = shellexpand::full(&s)?;
-		let config: LocalConfig = serdes::yamlviajson::from_str(&s)?;
+		Self::from_prepared(client, &prepare(s)?).await
+	}
+
+	/// Reads the config of `source` and converts it. A config merged from several files had its
+	/// environment variables expanded file by file, so it is not expanded again.
+	pub async fn from_source(
+		client: client::Client,
+		source: &ConfigSource,
+	) -> anyhow::Result<NormalizedLocalConfig> {
+		let s = source.read_to_string().await?;
+		match source {
+			ConfigSource::Files(_) => Self::from_prepared(client, &s).await,
+			_ => Self::from(client, &s).await,
+		}
+	}
+
+	async fn from_prepared(client: client::Client, s: &str) -> anyhow::Result<NormalizedLocalConfig> {
+		let config: LocalConfig = serdes::yamlviajson::from_str(s)?;
 		let t = convert(client, config).await?;
 		Ok(t)
 	}
 }
 
+/// Prepares the config for parsing, expanding environment variables.
+fn prepare(s: &str) -> anyhow::Result<String> {
+	// Avoid shell expanding the comment for schema. Probably there are better ways to do this!
+	let s = s.replace("# yaml-language-server: $schema", "#");
+	expand_env(&s)
+}
+
+/// Expands `${VAR}` and `${VAR:-default}` references (as well as `$VAR` and `~`) in the config.
+/// A reference to an unset variable without a default is an error naming the variable.
+fn expand_env(s: &str) -> anyhow::Result<String> {
+	shellexpand::full(s)
+		.map(|s| s.into_owned())
+		.map_err(|e| match e.cause {
+			std::env::VarError::NotPresent => anyhow!(
+				"config references environment variable '{0}', which is not set (use ${{{0}:-default}} to provide a default)",
+				e.var_name
+			),
+			std::env::VarError::NotUnicode(_) => anyhow!(
+				"config references environment variable '{}', which is not valid unicode",
+				e.var_name
+			),
+		})
+}
+
+/// Lists the config files in `paths`: each file, and the `.yaml`, `.yml` and `.json` files in each
+/// directory, by name.
+pub fn config_files(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+	let mut files = vec![];
+	for path in paths {
+		if !path.is_dir() {
+			files.push(path.clone());
+			continue;
+		}
+		let mut found = fs_err::read_dir(path)?
+			.map(|e| e.map(|e| e.path()))
+			.collect::<Result<Vec<_>, _>>()?;
+		found.retain(|p| {
+			p.is_file()
+				&& p
+					.extension()
+					.and_then(|e| e.to_str())
+					.is_some_and(|e| matches!(e, "yaml" | "yml" | "json"))
+		});
+		found.sort();
+		files.extend(found);
+	}
+	Ok(files)
+}
+
+/// Reads and merges several config files, see [merge_configs].
+///
+/// Environment variables are expanded in each file before it is parsed, so a value such as
+/// `${PORT:-8080}` keeps the type it has once expanded. The merged config must not be expanded
+/// again; see [NormalizedLocalConfig::from_source].
+///
+/// Each file is checked on its own first, so that errors point to a line of the file rather than
+/// of the merged config.
+pub fn merge_files(paths: &[PathBuf]) -> anyhow::Result<String> {
+	let configs = config_files(paths)?
+		.into_iter()
+		.map(|path| {
+			let contents = prepare(&fs_err::read_to_string(&path)?)?;
+			let config = serdes::yamlviajson::from_str::<serde_json::Value>(&contents)
+				.map_err(|e| anyhow!("failed to parse {}: {e}", path.display()))?;
+			if !config.is_null() {
+				serdes::yamlviajson::from_str::<LocalConfig>(&contents)
+					.map_err(|e| anyhow!("failed to parse {}: {e}", path.display()))?;
+			}
+			Ok((path.display().to_string(), config))
+		})
+		.collect::<anyhow::Result<Vec<_>>>()?;
+	Ok(serde_json::to_string(&merge_configs(configs)?)?)
+}
+
+/// Merges configs split across files, each named for error messages:
+/// * `binds`, `workloads` and `services` are concatenated.
+/// * Binds on the same port are merged into one, with the listeners of each. A listener name may
+///   only be used once per port, and the other settings of the bind may only be set in one file.
+/// * `config`, and any other top level field, may only be set in one file.
+/// * A route or MCP target name may only be used in one file, as they are referred to by name.
+pub fn merge_configs(
+	configs: Vec<(String, serde_json::Value)>,
+) -> anyhow::Result<serde_json::Value> {
+	use serde_json::Value;
+	let mut merged = serde_json::Map::new();
+	// Which file set each top level field, bind setting and listener, to report conflicts
+	let mut origins: HashMap<String, String> = HashMap::new();
+	let mut binds: Vec<serde_json::Map<String, Value>> = vec![];
+	for (file, config) in configs {
+		let config = match config {
+			Value::Null => continue,
+			Value::Object(config) => config,
+			_ => bail!("{file}: the config must be a mapping"),
+		};
+		if let Some(binds) = config.get("binds") {
+			note_names(&mut origins, &file, binds)?;
+		}
+		for (key, value) in config {
+			match key.as_str() {
+				"binds" => {
+					let Value::Array(new_binds) = value else {
+						bail!("{file}: binds must be a list");
+					};
+					for bind in new_binds {
+						let Value::Object(bind) = bind else {
+							bail!("{file}: each bind must be a mapping");
+						};
+						let port = bind.get("port").cloned().unwrap_or_default().to_string();
+						match binds.iter_mut().find(|b| b.get("port") == bind.get("port")) {
+							None => {
+								for (k, v) in &bind {
+									if k == "listeners" {
+										note_listeners(&mut origins, &port, &file, v)?;
+									} else {
+										origins.insert(format!("port {port}: {k}"), file.clone());
+									}
+								}
+								binds.push(bind);
+							},
+							Some(existing) => {
+								for (k, v) in bind {
+									if k == "port" {
+										continue;
+									}
+									if k == "listeners" {
+										note_listeners(&mut origins, &port, &file, &v)?;
+										let Value::Array(listeners) = v else {
+											bail!("{file}: listeners must be a list");
+										};
+										match existing.entry("listeners").or_insert(Value::Array(vec![])) {
+											Value::Array(existing) => existing.extend(listeners),
+											_ => bail!("listeners must be a list"),
+										}
+										continue;
+									}
+									let what = format!("port {port}: {k}");
+									if let Some(prev) = origins.insert(what.clone(), file.clone()) {
+										bail!("{what} is set in both {prev} and {file}");
+									}
+									existing.insert(k, v);
+								}
+							},
+						}
+					}
+				},
+				"workloads" | "services" => {
+					let Value::Array(items) = value else {
+						bail!("{file}: {key} must be a list");
+					};
+					match merged.entry(key.clone()).or_insert(Value::Array(vec![])) {
+						Value::Array(existing) => existing.extend(items),
+						_ => unreachable!("only lists are inserted"),
+					}
+				},
+				_ => {
+					if let Some(prev) = origins.insert(key.clone(), file.clone()) {
+						bail!("{key} is set in both {prev} and {file}; it may only be set in one file");
+					}
+					merged.insert(key, value);
+				},
+			}
+		}
+	}
+	if !binds.is_empty() {
+		merged.insert(
+			"binds".to_string(),
+			Value::Array(binds.into_iter().map(Value::Object).collect()),
+		);
+	}
+	Ok(Value::Object(merged))
+}
+
+/// Records which file defines each named listener of a bind, failing if another file already did.
+fn note_listeners(
+	origins: &mut HashMap<String, String>,
+	port: &str,
+	file: &str,
+	listeners: &serde_json::Value,
+) -> anyhow::Result<()> {
+	let names = listeners
+		.as_array()
+		.into_iter()
+		.flatten()
+		.filter_map(|l| l.get("name").and_then(|n| n.as_str()));
+	for name in names {
+		let what = format!("listener '{name}' on port {port}");
+		match origins.insert(what.clone(), file.to_string()) {
+			Some(prev) if prev != file => bail!("{what} is defined in both {prev} and {file}"),
+			_ => {},
+		}
+	}
+	Ok(())
+}
+
+/// Records which file defines each named route and MCP target, failing if another file already did.
+fn note_names(
+	origins: &mut HashMap<String, String>,
+	file: &str,
+	binds: &serde_json::Value,
+) -> anyhow::Result<()> {
+	let named = |v: &serde_json::Value| v.get("name").and_then(|n| n.as_str()).map(str::to_string);
+	let routes = binds
+		.as_array()
+		.into_iter()
+		.flatten()
+		.flat_map(|b| array(b, "listeners"))
+		.flat_map(|l| array(l, "routes"));
+	for route in routes {
+		let targets = array(route, "backends")
+			.filter_map(|b| b.get("mcp"))
+			.flat_map(|m| array(m, "targets"))
+			.filter_map(named)
+			.map(|n| format!("MCP target '{n}'"));
+		let names = named(route)
+			.map(|n| format!("route '{n}'"))
+			.into_iter()
+			.chain(targets);
+		for what in names {
+			match origins.insert(what.clone(), file.to_string()) {
+				Some(prev) if prev != file => bail!("{what} is defined in both {prev} and {file}"),
+				_ => {},
+			}
+		}
+	}
+	Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct NormalizedLocalConfig {
 	pub binds: Vec<Bind>,
@@ -51,6 +298,225 @@ pub struct NormalizedLocalConfig {
 	// for now
 	pub workloads: Vec<LocalWorkload>,
 	pub services: Vec<Service>,
+	// Certificate and key files referenced by listeners. The config is reloaded when these change.
+	pub tls_files: Vec<PathBuf>,
+}
+
+impl NormalizedLocalConfig {
+	/// Checks each stdio MCP target may run its command, per the stdio command allowlist.
+	pub fn check_stdio_commands(&self, allowlist: &[String]) -> anyhow::Result<()> {
+		for backend in &self.backends {
+			let Backend::MCP(name, mcp) = backend else {
+				continue;
+			};
+			for target in &mcp.targets {
+				target
+					.spec
+					.check_stdio_command(allowlist)
+					.map_err(|e| anyhow!("MCP target '{}' of backend '{name}': {e}", target.name))?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Checks each MCP target could be set up, short of connecting to it: stdio commands are
+	/// allowed, and OpenAPI schemas, fetched if remote, yield tools and a server URL. Returns an error
+	/// for each target that could not.
+	pub async fn check_targets(
+		&self,
+		client: &client::Client,
+		allowlist: &[String],
+	) -> Vec<anyhow::Error> {
+		let mut errors = vec![];
+		for backend in &self.backends {
+			let Backend::MCP(name, mcp) = backend else {
+				continue;
+			};
+			let proxy = self
+				.policies
+				.iter()
+				.find_map(|p| match (&p.target, &p.policy) {
+					(PolicyTarget::Backend(b), Policy::OutboundProxy(proxy)) if b == name => {
+						Some(proxy.clone())
+					},
+					_ => None,
+				});
+			for target in &mcp.targets {
+				let checked = match &target.spec {
+					McpTargetSpec::OpenAPI(open) => {
+						check_openapi_target(client, proxy.clone(), open, mcp.read_only.is_some()).await
+					},
+					spec => spec.check_stdio_command(allowlist),
+				};
+				if let Err(e) = checked {
+					errors.push(e.context(format!("MCP target '{}' of backend '{name}'", target.name)));
+				}
+			}
+		}
+		errors
+	}
+}
+
+async fn check_openapi_target(
+	client: &client::Client,
+	proxy: Option<crate::http::outboundproxy::OutboundProxy>,
+	open: &OpenAPITarget,
+	read_only: bool,
+) -> anyhow::Result<()> {
+	let schema = match &open.schema {
+		OpenAPISchema::Static(schema) => schema.clone(),
+		OpenAPISchema::Remote(remote) => {
+			remote.refresh(client, proxy).await;
+			remote.schema()?
+		},
+	};
+	let tools = crate::mcp::openapi::parse_openapi_tools(&schema, &open.parse_options(read_only))?;
+	if !tools.is_empty() {
+		crate::mcp::openapi::get_server_url(&schema)?;
+	}
+	Ok(())
+}
+
+/// An error patching a single object of the local configuration document.
+#[derive(Debug, thiserror::Error)]
+pub enum PatchError {
+	#[error("{0} not found")]
+	NotFound(String),
+	#[error("{0} is defined more than once; edit the configuration directly")]
+	Ambiguous(String),
+	#[error("{0}")]
+	Invalid(String),
+}
+
+const ROUTE_FIELDS: &[&str] = &[
+	"name",
+	"ruleName",
+	"hostnames",
+	"matches",
+	"policies",
+	"backends",
+];
+const MCP_TARGET_TYPES: &[&str] = &["sse", "mcp", "stdio", "openapi", "grpc"];
+
+/// Applies a JSON merge patch (see [`json::merge_patch`]) to the route with the given name, and
+/// returns the patched route. Route policies, such as `backendAuth` and `mcpAuthorization`, are
+/// nested objects and merge field by field, so switching to another kind of `backendAuth` requires
+/// removing the current one with `null`. Lists, such as `hostnames` or the authorization `rules`, are
+/// replaced as a whole.
+pub fn patch_route(
+	config: &mut serde_json::Value,
+	name: &str,
+	patch: serde_json::Value,
+) -> Result<serde_json::Value, PatchError> {
+	let routes = routes_mut(config)
+		.filter(|r| r.get("name").and_then(|n| n.as_str()) == Some(name))
+		.collect();
+	apply_patch(
+		routes,
+		&format!("route '{name}'"),
+		name,
+		patch,
+		ROUTE_FIELDS,
+		|_| Ok(()),
+	)
+}
+
+/// Applies a JSON merge patch (see [`json::merge_patch`]) to the MCP target with the given name, and
+/// returns the patched target. Nested objects, such as the `env` of a stdio target, merge key by
+/// key; lists, such as `args` or the `staticHeaders` of an OpenAPI target, are replaced as a whole.
+/// To change the type of a target, the current type must be removed with `null`.
+pub fn patch_mcp_target(
+	config: &mut serde_json::Value,
+	name: &str,
+	patch: serde_json::Value,
+) -> Result<serde_json::Value, PatchError> {
+	let targets = routes_mut(config)
+		.flat_map(|r| array_mut(r, "backends"))
+		.filter_map(|b| b.get_mut("mcp"))
+		.flat_map(|m| array_mut(m, "targets"))
+		.filter(|t| t.get("name").and_then(|n| n.as_str()) == Some(name))
+		.collect();
+	let what = format!("MCP target '{name}'");
+	let fields = [
+		&["name", "filters", "group", "weight"][..],
+		MCP_TARGET_TYPES,
+	]
+	.concat();
+	apply_patch(targets, &what, name, patch, &fields, |patched| {
+		let types = MCP_TARGET_TYPES
+			.iter()
+			.filter(|t| patched.get(**t).is_some())
+			.collect_vec();
+		if types.len() != 1 {
+			return Err(PatchError::Invalid(format!(
+				"{what} must have exactly one of {}, found {}; set the current type to null to change it",
+				MCP_TARGET_TYPES.join(", "),
+				types.iter().join(", ")
+			)));
+		}
+		Ok(())
+	})
+}
+
+fn apply_patch(
+	found: Vec<&mut serde_json::Value>,
+	what: &str,
+	name: &str,
+	patch: serde_json::Value,
+	fields: &[&str],
+	validate: impl FnOnce(&serde_json::Value) -> Result<(), PatchError>,
+) -> Result<serde_json::Value, PatchError> {
+	let target = match <[_; 1]>::try_from(found) {
+		Ok([t]) => t,
+		Err(found) if found.is_empty() => return Err(PatchError::NotFound(what.to_string())),
+		Err(_) => return Err(PatchError::Ambiguous(what.to_string())),
+	};
+	let serde_json::Value::Object(patch_fields) = &patch else {
+		return Err(PatchError::Invalid(
+			"patch must be a JSON object".to_string(),
+		));
+	};
+	if let Some(k) = patch_fields.keys().find(|k| !fields.contains(&k.as_str())) {
+		return Err(PatchError::Invalid(format!(
+			"unknown field '{k}' for {what}"
+		)));
+	}
+	if patch_fields
+		.get("name")
+		.is_some_and(|n| n.as_str() != Some(name))
+	{
+		return Err(PatchError::Invalid(format!(
+			"the name of {what} cannot be changed"
+		)));
+	}
+	let mut patched = target.clone();
+	json::merge_patch(&mut patched, patch);
+	validate(&patched)?;
+	*target = patched.clone();
+	Ok(patched)
+}
+
+fn routes_mut(config: &mut serde_json::Value) -> impl Iterator<Item = &mut serde_json::Value> {
+	array_mut(config, "binds")
+		.flat_map(|b| array_mut(b, "listeners"))
+		.flat_map(|l| array_mut(l, "routes"))
+}
+
+fn array<'a>(
+	v: &'a serde_json::Value,
+	key: &'static str,
+) -> impl Iterator<Item = &'a serde_json::Value> {
+	v.get(key).and_then(|v| v.as_array()).into_iter().flatten()
+}
+
+fn array_mut<'a>(
+	v: &'a mut serde_json::Value,
+	key: &'static str,
+) -> impl Iterator<Item = &'a mut serde_json::Value> {
+	v.get_mut(key)
+		.and_then(|v| v.as_array_mut())
+		.into_iter()
+		.flatten()
 }
 
 #[cfg(feature = "schema")]
@@ -65,6 +531,10 @@ pub fn generate_schema() -> String {
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
 struct LocalConfig {
+	/// Process-wide settings. These are read once, when the process starts.
+	#[allow(dead_code)]
+	#[cfg_attr(feature = "schema", schemars(with = "Option<crate::RawConfig>"))]
+	config: Option<serde::de::IgnoredAny>,
 	#[serde(default)]
 	binds: Vec<LocalBind>,
 	#[serde(default)]
@@ -81,6 +551,21 @@ struct LocalConfig {
 struct LocalBind {
 	port: u16,
 	listeners: Vec<LocalListener>,
+	/// Parse a PROXY protocol (v1 or v2) header at the start of each connection, to recover the original
+	/// client address when running behind an L4 load balancer.
+	proxy_protocol: Option<ProxyProtocol>,
+	/// The maximum number of concurrent connections to accept. Connections beyond this are closed
+	/// immediately.
+	max_connections: Option<u32>,
+	/// A request rate limit applied to every HTTP request on the bind, before routing. Use `key:
+	/// sourceIp` to limit each client separately; `jwtSubject` is not supported, as JWTs are only
+	/// validated after routing.
+	#[serde(default)]
+	#[cfg_attr(
+		feature = "schema",
+		schemars(with = "Option<serde_json::value::RawValue>")
+	)]
+	rate_limit: Option<crate::http::localratelimit::RateLimit>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -119,6 +604,18 @@ enum LocalListenerProtocol {
 struct LocalTLSServerConfig {
 	cert: PathBuf,
 	key: PathBuf,
+	/// The minimum TLS version to accept. Defaults to 1.2, but the default cipher suites are TLS 1.3
+	/// only, so TLS 1.2 clients are only accepted if TLS 1.2 suites are configured.
+	#[serde(default)]
+	min_version: Option<TLSVersion>,
+	/// The cipher suites to offer. Defaults to a FIPS compatible set of TLS 1.3 suites.
+	/// Supported suites are `TLS13_AES_256_GCM_SHA384`, `TLS13_AES_128_GCM_SHA256`,
+	/// `TLS13_CHACHA20_POLY1305_SHA256`, `TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384`,
+	/// `TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256`, `TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256`,
+	/// `TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384`, `TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256` and
+	/// `TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256`.
+	#[serde(default)]
+	cipher_suites: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -170,10 +667,10 @@ pub enum LocalBackend {
 	},
 	// Rest are inlined
 	#[serde(rename = "host")]
-	Opaque(Target), // Hostname or IP
+	Opaque(#[cfg_attr(feature = "schema", schemars(with = "String"))] Target), // Hostname or IP
 	Dynamic {},
 	#[serde(rename = "mcp")]
-	MCP(McpBackend),
+	MCP(Box<McpBackend>),
 	#[serde(rename = "ai")]
 	AI(crate::llm::AIBackend),
 	Invalid,
@@ -185,7 +682,7 @@ impl LocalBackend {
 			LocalBackend::Service { .. } => None, // These stay as references
 			LocalBackend::Opaque(tgt) => Some(Backend::Opaque(name, tgt.clone())),
 			LocalBackend::Dynamic { .. } => Some(Backend::Dynamic {}),
-			LocalBackend::MCP(tgt) => Some(Backend::MCP(name, tgt.clone())),
+			LocalBackend::MCP(tgt) => Some(Backend::MCP(name, Arc::new((**tgt).clone()))),
 			LocalBackend::AI(tgt) => Some(Backend::AI(name, tgt.clone())),
 			LocalBackend::Invalid => Some(Backend::Invalid),
 		}
@@ -238,7 +735,7 @@ pub enum SimpleLocalBackend {
 		port: u16,
 	},
 	#[serde(rename = "host")]
-	Opaque(Target), // Hostname or IP
+	Opaque(#[cfg_attr(feature = "schema", schemars(with = "String"))] Target), // Hostname or IP
 	Invalid,
 }
 
@@ -290,6 +787,9 @@ struct FilterOrPolicy {
 	backend_tls: Option<http::backendtls::LocalBackendTLS>,
 	#[serde(default, skip_serializing_if = "Option::is_none")]
 	backend_auth: Option<BackendAuth>,
+	/// Connect to the backend through a forward proxy.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	outbound_proxy: Option<http::outboundproxy::LocalOutboundProxy>,
 	#[serde(default, skip_serializing_if = "Vec::is_empty")]
 	#[cfg_attr(feature = "schema", schemars(with = "serde_json::value::RawValue"))]
 	local_rate_limit: Vec<crate::http::localratelimit::RateLimit>,
@@ -309,6 +809,32 @@ struct FilterOrPolicy {
 	retry: Option<retry::Policy>,
 }
 
+/// The listener protocols accepted by the local configuration.
+pub(crate) fn listener_protocols() -> Vec<String> {
+	serdes::variants::<LocalListenerProtocol>()
+		.iter()
+		.map(|p| p.to_lowercase())
+		.collect()
+}
+
+/// The backend types accepted by the local configuration.
+pub(crate) fn backend_types() -> Vec<&'static str> {
+	serdes::variants::<LocalBackend>()
+		.iter()
+		.copied()
+		.filter(|b| *b != "invalid")
+		.collect()
+}
+
+/// The authentication policies accepted by the local configuration.
+pub(crate) fn authentication_policies() -> Vec<&'static str> {
+	serdes::variants::<AuthenticationPolicy>()
+		.iter()
+		.copied()
+		.filter(|p| serdes::fields::<FilterOrPolicy>().contains(p))
+		.collect()
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
@@ -323,24 +849,48 @@ async fn convert(client: client::Client, i: LocalConfig) -> anyhow::Result<Norma
 		workloads,
 		services,
 	} = i;
+	let tls_files = binds
+		.iter()
+		.flat_map(|b| b.listeners.iter())
+		.filter_map(|l| l.tls.as_ref())
+		.flat_map(|tls| [tls.cert.clone(), tls.key.clone()])
+		.collect();
 	let mut all_policies = vec![];
 	let mut all_backends = vec![];
 	let mut all_binds = vec![];
 	for b in binds {
+		let mut bind = Bind::builder(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), b.port));
 		let bind_name = strng::format!("bind/{}", b.port);
-		let mut ls = ListenerSet::default();
 		for (idx, l) in b.listeners.into_iter().enumerate() {
 			let (l, pol, backends) = convert_listener(client.clone(), bind_name.clone(), idx, l).await?;
 			all_policies.extend_from_slice(&pol);
 			all_backends.extend_from_slice(&backends);
-			ls.insert(l.key.clone(), l)
+			bind = bind.listener(l);
 		}
-		let b = Bind {
-			key: bind_name,
-			address: SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), b.port),
-			listeners: ls,
-		};
-		all_binds.push(b)
+		if let Some(rl) = &b.rate_limit
+			&& rl.limit_type != crate::http::localratelimit::RateLimitType::Requests
+		{
+			anyhow::bail!("bind {} rateLimit must be a request limit", b.port);
+		}
+		// JWTs are validated by route policies, so there is no subject yet
+		if let Some(rl) = &b.rate_limit
+			&& rl.key == Some(crate::http::localratelimit::RateLimitKey::JwtSubject)
+		{
+			anyhow::bail!(
+				"bind {} rateLimit cannot be keyed by jwtSubject, as it applies before JWTs are validated",
+				b.port
+			);
+		}
+		if let Some(p) = b.proxy_protocol {
+			bind = bind.proxy_protocol(p);
+		}
+		if let Some(n) = b.max_connections {
+			bind = bind.max_connections(n);
+		}
+		if let Some(rl) = b.rate_limit {
+			bind = bind.rate_limit(rl);
+		}
+		all_binds.push(bind.build())
 	}
 	Ok(NormalizedLocalConfig {
 		binds: all_binds,
@@ -348,6 +898,7 @@ async fn convert(client: client::Client, i: LocalConfig) -> anyhow::Result<Norma
 		backends: all_backends,
 		workloads,
 		services,
+		tls_files,
 	})
 }
 
@@ -403,36 +954,29 @@ async fn convert_listener(
 	}
 	let name = name.unwrap_or_else(|| strng::format!("listener{}", idx));
 	let gateway_name: GatewayName = gateway_name.unwrap_or(bind_name);
-	let key: ListenerKey = strng::format!("{}/{}", name, gateway_name);
+	let mut listener = Listener::builder(name, gateway_name)
+		.hostname(hostname.unwrap_or_default())
+		.protocol(protocol)
+		.build();
 
 	let mut all_policies = vec![];
 	let mut all_backends = vec![];
 
-	let mut rs = RouteSet::default();
 	for (idx, l) in routes.into_iter().flatten().enumerate() {
-		let (route, policies, backends) = convert_route(client.clone(), l, idx, key.clone()).await?;
+		let (route, policies, backends) =
+			convert_route(client.clone(), l, idx, listener.key.clone()).await?;
 		all_policies.extend_from_slice(&policies);
 		all_backends.extend_from_slice(&backends);
-		rs.insert(route)
+		listener.routes.insert(route)
 	}
 
-	let mut trs = TCPRouteSet::default();
 	for (idx, l) in tcp_routes.into_iter().flatten().enumerate() {
-		let (route, policies) = convert_tcp_route(l, idx, key.clone()).await?;
+		let (route, policies) = convert_tcp_route(l, idx, listener.key.clone()).await?;
 		all_policies.extend_from_slice(&policies);
-		trs.insert(route)
+		listener.tcp_routes.insert(route)
 	}
 
-	let l = Listener {
-		key,
-		name,
-		gateway_name,
-		hostname: hostname.unwrap_or_default(),
-		protocol,
-		routes: rs,
-		tcp_routes: trs,
-	};
-	Ok((l, all_policies, all_backends))
+	Ok((listener, all_policies, all_backends))
 }
 
 async fn convert_route(
@@ -524,6 +1068,7 @@ async fn convert_route(
 			ai,
 			backend_tls,
 			backend_auth,
+			outbound_proxy,
 			local_rate_limit,
 			remote_rate_limit,
 			jwt_auth,
@@ -579,6 +1124,9 @@ async fn convert_route(
 		if let Some(p) = backend_auth {
 			external_policies.push(backend_tgt(Policy::BackendAuth(p))?)
 		}
+		if let Some(p) = outbound_proxy {
+			external_policies.push(backend_tgt(Policy::OutboundProxy(p.try_into()?))?)
+		}
 		if let Some(p) = jwt_auth {
 			external_policies.push(tgt(Policy::JwtAuth(p.try_into(client.clone()).await?)))
 		}
@@ -714,16 +1262,15 @@ fn convert_tls_server(tls: LocalTLSServerConfig) -> anyhow::Result<TLSConfig> {
 	let cert_chain = crate::types::agent::parse_cert(&cert)?;
 	let key = fs_err::read(tls.key)?;
 	let private_key = crate::types::agent::parse_key(&key)?;
-
-	let mut ccb = ServerConfig::builder_with_provider(transport::tls::provider())
-		.with_protocol_versions(transport::tls::ALL_TLS_VERSIONS)
-		.expect("server config must be valid")
-		.with_no_client_auth()
-		.with_single_cert(cert_chain, private_key)?;
-	ccb.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
-	Ok(TLSConfig {
-		config: Arc::new(ccb),
-	})
+	TLSConfig::new(
+		cert_chain,
+		private_key,
+		vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+		&TLSServerOptions {
+			min_version: tls.min_version,
+			cipher_suites: tls.cipher_suites,
+		},
+	)
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
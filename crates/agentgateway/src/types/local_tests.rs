@@ -0,0 +1,729 @@
+use super::*;
+
+// Cargo sets CARGO_PKG_NAME when running tests, so it is always available at runtime.
+
+#[test]
+fn test_expand_env() {
+	let name = env!("CARGO_PKG_NAME");
+	assert_eq!(
+		expand_env("host: ${CARGO_PKG_NAME}.svc").unwrap(),
+		format!("host: {name}.svc")
+	);
+	// The variable takes precedence over the default
+	assert_eq!(
+		expand_env("host: ${CARGO_PKG_NAME:-other}").unwrap(),
+		format!("host: {name}")
+	);
+}
+
+#[test]
+fn test_expand_env_default() {
+	assert_eq!(
+		expand_env("port: ${AGENTGATEWAY_TEST_UNSET_PORT:-8080}").unwrap(),
+		"port: 8080"
+	);
+}
+
+#[test]
+fn test_expand_env_missing() {
+	let err = expand_env("port: ${AGENTGATEWAY_TEST_UNSET_PORT}").unwrap_err();
+	assert!(
+		err
+			.to_string()
+			.contains("environment variable 'AGENTGATEWAY_TEST_UNSET_PORT', which is not set"),
+		"{err}"
+	);
+}
+
+fn patch_config() -> serde_json::Value {
+	serde_json::json!({
+		"binds": [{
+			"port": 3000,
+			"listeners": [{
+				"routes": [{
+					"name": "mcp",
+					"policies": {
+						"backendAuth": {"passthrough": {}},
+						"mcpAuthorization": {"rules": ["permit(principal, action == Action::\"call_tool\", resource == Tool::\"echo\");"]},
+					},
+					"backends": [{
+						"mcp": {
+							"targets": [
+								{
+									"name": "petstore",
+									"openapi": {
+										"host": "localhost",
+										"port": 8080,
+										"schema": {"file": "petstore.json"},
+										"staticHeaders": [{"name": "x-api-key", "value": "a"}],
+									},
+								},
+								{"name": "everything", "stdio": {"cmd": "npx", "env": {"A": "1", "B": "2"}}},
+							],
+						},
+					}],
+				}],
+			}],
+		}],
+	})
+}
+
+#[test]
+fn test_patch_mcp_target_headers() {
+	let mut config = patch_config();
+	let patched = patch_mcp_target(
+		&mut config,
+		"petstore",
+		serde_json::json!({"openapi": {"staticHeaders": [{"name": "x-api-key", "value": "b"}]}}),
+	)
+	.unwrap();
+	// Only the headers change, and the list is replaced rather than appended to
+	let want = serde_json::json!({
+		"name": "petstore",
+		"openapi": {
+			"host": "localhost",
+			"port": 8080,
+			"schema": {"file": "petstore.json"},
+			"staticHeaders": [{"name": "x-api-key", "value": "b"}],
+		},
+	});
+	assert_eq!(patched, want);
+	assert_eq!(
+		json::traverse(
+			&config,
+			&[
+				"binds",
+				"0",
+				"listeners",
+				"0",
+				"routes",
+				"0",
+				"backends",
+				"0",
+				"mcp",
+				"targets",
+				"0"
+			]
+		),
+		Some(&want)
+	);
+
+	// Maps merge key by key
+	let patched = patch_mcp_target(
+		&mut config,
+		"everything",
+		serde_json::json!({"stdio": {"env": {"B": null, "C": "3"}}}),
+	)
+	.unwrap();
+	assert_eq!(
+		patched,
+		serde_json::json!({"name": "everything", "stdio": {"cmd": "npx", "env": {"A": "1", "C": "3"}}})
+	);
+}
+
+#[test]
+fn test_patch_route_auth() {
+	let mut config = patch_config();
+	let patched = patch_route(
+		&mut config,
+		"mcp",
+		// The policy is a single-key object, so the previous kind must be removed
+		serde_json::json!({"policies": {"backendAuth": {"passthrough": null, "key": "secret"}}}),
+	)
+	.unwrap();
+	// The authorization policy and backends are untouched
+	assert_eq!(
+		patched["policies"],
+		serde_json::json!({
+			"backendAuth": {"key": "secret"},
+			"mcpAuthorization": {"rules": ["permit(principal, action == Action::\"call_tool\", resource == Tool::\"echo\");"]},
+		})
+	);
+	assert_eq!(
+		patched["backends"],
+		patch_config()["binds"][0]["listeners"][0]["routes"][0]["backends"]
+	);
+}
+
+#[test]
+fn test_patch_errors() {
+	let mut config = patch_config();
+	let patch = |config: &mut serde_json::Value, name: &str, p: serde_json::Value| {
+		patch_mcp_target(config, name, p).unwrap_err().to_string()
+	};
+	assert_eq!(
+		patch(&mut config, "missing", serde_json::json!({})),
+		"MCP target 'missing' not found"
+	);
+	assert_eq!(
+		patch(&mut config, "petstore", serde_json::json!({"headers": {}})),
+		"unknown field 'headers' for MCP target 'petstore'"
+	);
+	assert_eq!(
+		patch(
+			&mut config,
+			"petstore",
+			serde_json::json!({"name": "other"})
+		),
+		"the name of MCP target 'petstore' cannot be changed"
+	);
+	assert!(
+		patch(
+			&mut config,
+			"petstore",
+			serde_json::json!({"sse": {"host": "localhost"}})
+		)
+		.starts_with("MCP target 'petstore' must have exactly one of")
+	);
+	// Nothing was changed by the failed patches
+	assert_eq!(config, patch_config());
+
+	// Changing the type works once the old one is removed
+	patch_mcp_target(
+		&mut config,
+		"petstore",
+		serde_json::json!({"openapi": null, "mcp": {"host": "localhost", "port": 3000, "path": "/mcp"}}),
+	)
+	.unwrap();
+
+	// Duplicate names cannot be patched
+	let mut config = patch_config();
+	let route = config["binds"][0]["listeners"][0]["routes"][0].clone();
+	config["binds"][0]["listeners"][0]["routes"]
+		.as_array_mut()
+		.unwrap()
+		.push(route);
+	assert!(matches!(
+		patch_route(&mut config, "mcp", serde_json::json!({})),
+		Err(PatchError::Ambiguous(_))
+	));
+}
+
+const EXAMPLES: [(&str, &str); 7] = [
+	("a2a", include_str!("../../../../examples/a2a/config.yaml")),
+	(
+		"authorization",
+		include_str!("../../../../examples/authorization/config.yaml"),
+	),
+	(
+		"basic",
+		include_str!("../../../../examples/basic/config.yaml"),
+	),
+	(
+		"multiplex",
+		include_str!("../../../../examples/multiplex/config.yaml"),
+	),
+	(
+		"openapi",
+		include_str!("../../../../examples/openapi/config.yaml"),
+	),
+	(
+		"telemetry",
+		include_str!("../../../../examples/telemetry/config.yaml"),
+	),
+	("tls", include_str!("../../../../examples/tls/config.yaml")),
+];
+
+#[test]
+fn test_examples_parse() {
+	for (name, contents) in EXAMPLES {
+		// Files are relative to the repository root, where the examples are run from
+		let root = concat!(env!("CARGO_MANIFEST_DIR"), "/../../");
+		let contents = contents.replace("file: ./", &format!("file: {root}"));
+		if let Err(e) = serdes::yamlviajson::from_str::<LocalConfig>(&contents) {
+			panic!("example {name} does not parse: {e}");
+		}
+	}
+}
+
+#[tokio::test]
+async fn test_check_stdio_commands() {
+	let client = client::Client::new(
+		&client::Config {
+			resolver_cfg: hickory_resolver::config::ResolverConfig::default(),
+			resolver_opts: hickory_resolver::config::ResolverOpts::default(),
+		},
+		None,
+	);
+	let stdio = |env: &str| {
+		format!(
+			r#"
+binds:
+- port: 3000
+  listeners:
+  - routes:
+    - backends:
+      - mcp:
+          name: default
+          targets:
+          - name: everything
+            stdio:
+              cmd: npx
+              env: {{{env}}}
+"#
+		)
+	};
+	let config = NormalizedLocalConfig::from(client.clone(), &stdio(""))
+		.await
+		.unwrap();
+	let allow = |cmds: &[&str]| cmds.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+	assert!(config.check_stdio_commands(&allow(&["npx"])).is_ok());
+	assert!(config.check_stdio_commands(&allow(&["*"])).is_ok());
+	let err = config.check_stdio_commands(&allow(&["uvx"])).unwrap_err();
+	assert_eq!(
+		err.to_string(),
+		"MCP target 'everything' of backend 'listener0/bind/3000/route0/default': command 'npx' is not in the stdio command allowlist"
+	);
+	// Nothing is allowed by default
+	assert!(config.check_stdio_commands(&[]).is_err());
+
+	// Setting PATH would run another npx under the allowed name
+	let config = NormalizedLocalConfig::from(client, &stdio("PATH: /tmp"))
+		.await
+		.unwrap();
+	assert!(config.check_stdio_commands(&allow(&["npx"])).is_err());
+	assert!(config.check_stdio_commands(&allow(&["*"])).is_ok());
+}
+
+#[tokio::test]
+async fn test_check_targets() {
+	let client = client::Client::new(
+		&client::Config {
+			resolver_cfg: hickory_resolver::config::ResolverConfig::default(),
+			resolver_opts: hickory_resolver::config::ResolverOpts::default(),
+		},
+		None,
+	);
+	let config = r#"
+binds:
+- port: 3000
+  listeners:
+  - routes:
+    - backends:
+      - mcp:
+          targets:
+          - name: everything
+            stdio:
+              cmd: npx
+          - name: remote
+            openapi:
+              schema:
+                remote:
+                  url: http://127.0.0.1:1/openapi.json
+          - name: servers
+            openapi:
+              schema:
+                inline: |
+                  openapi: 3.0.0
+                  info: { title: Servers, version: "1" }
+                  servers: [{ url: "http://a" }, { url: "http://b" }]
+                  paths:
+                    /a:
+                      get: { operationId: a, responses: {} }
+"#;
+	let config = NormalizedLocalConfig::from(client.clone(), config)
+		.await
+		.unwrap();
+	let errors = config
+		.check_targets(&client, &["uvx".to_string()])
+		.await
+		.iter()
+		.map(|e| format!("{e:#}"))
+		.collect::<Vec<_>>();
+	assert_eq!(errors.len(), 3, "{errors:?}");
+	assert!(
+		errors[0].starts_with("MCP target 'everything'"),
+		"{errors:?}"
+	);
+	assert!(errors[0].contains("allowlist"), "{errors:?}");
+	assert!(
+		errors[1].contains("failed to fetch OpenAPI schema"),
+		"{errors:?}"
+	);
+	assert!(errors[2].contains("multiple servers"), "{errors:?}");
+
+	assert_eq!(
+		config.check_targets(&client, &["npx".to_string()]).await.len(),
+		2
+	);
+}
+
+#[test]
+fn test_examples_match_schema() {
+	// The committed schema, so this runs without the schema feature
+	let committed = include_str!("../../../../schema/local.json");
+	#[cfg(feature = "schema")]
+	assert_eq!(
+		generate_schema(),
+		committed.trim_end(),
+		"schema/local.json is stale, run `make generate-schema`"
+	);
+	let schema: serde_json::Value = serde_json::from_str(committed).unwrap();
+	for (name, contents) in EXAMPLES {
+		let config: serde_json::Value = serdes::yamlviajson::from_str(contents).unwrap();
+		if let Err(e) = validate(&schema, &config, "") {
+			panic!("example {name} does not match the schema: {e}");
+		}
+	}
+
+	// Typos are caught, with the path to the field
+	let typo = serde_json::json!({"binds": [{"port": 3000, "listners": []}]});
+	assert_eq!(
+		validate(&schema, &typo, "").unwrap_err(),
+		"unknown field binds[0].listners"
+	);
+	let typo = serde_json::json!({"config": {"adminAdr": "localhost:15000"}});
+	assert_eq!(
+		validate(&schema, &typo, "").unwrap_err(),
+		"unknown field config.adminAdr"
+	);
+}
+
+/// Checks `value` against the parts of JSON Schema used by the generated schema.
+fn validate(
+	schema: &serde_json::Value,
+	value: &serde_json::Value,
+	path: &str,
+) -> Result<(), String> {
+	use serde_json::Value;
+	let Some(schema) = schema.as_object() else {
+		return match schema {
+			Value::Bool(false) => Err(format!("unexpected value at {path}")),
+			_ => Ok(()),
+		};
+	};
+	for key in ["oneOf", "anyOf"] {
+		if let Some(choices) = schema.get(key).and_then(Value::as_array) {
+			let errors = choices
+				.iter()
+				.filter_map(|s| validate(s, value, path).err())
+				.collect::<Vec<_>>();
+			if !choices.is_empty() && errors.len() == choices.len() {
+				return Err(format!(
+					"no variant matches at {path}: {}",
+					errors.join("; ")
+				));
+			}
+		}
+	}
+	if let Some(types) = schema.get("type") {
+		let types = match types {
+			Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+			t => vec![t.as_str().unwrap_or_default()],
+		};
+		let matches = |t: &str| match t {
+			"object" => value.is_object(),
+			"array" => value.is_array(),
+			"string" => value.is_string(),
+			"integer" => value.is_i64() || value.is_u64(),
+			"number" => value.is_number(),
+			"boolean" => value.is_boolean(),
+			"null" => value.is_null(),
+			_ => true,
+		};
+		if !types.into_iter().any(matches) {
+			return Err(format!("wrong type at {path}"));
+		}
+	}
+	if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+		&& !allowed.contains(value)
+	{
+		return Err(format!("unexpected value at {path}"));
+	}
+	if let Some(expected) = schema.get("const")
+		&& expected != value
+	{
+		return Err(format!("unexpected value at {path}"));
+	}
+	if let Value::Object(object) = value {
+		let properties = schema.get("properties").and_then(Value::as_object);
+		for (k, v) in object {
+			let field = if path.is_empty() {
+				k.clone()
+			} else {
+				format!("{path}.{k}")
+			};
+			match (
+				properties.and_then(|p| p.get(k)),
+				schema.get("additionalProperties"),
+			) {
+				(Some(s), _) => validate(s, v, &field)?,
+				(None, Some(Value::Bool(false))) => return Err(format!("unknown field {field}")),
+				(None, Some(s)) => validate(s, v, &field)?,
+				(None, None) => {},
+			}
+		}
+		for required in schema
+			.get("required")
+			.and_then(Value::as_array)
+			.into_iter()
+			.flatten()
+			.filter_map(Value::as_str)
+		{
+			if !object.contains_key(required) {
+				return Err(format!("missing field {required} at {path}"));
+			}
+		}
+	}
+	if let Value::Array(items) = value {
+		let prefix = schema.get("prefixItems").and_then(Value::as_array);
+		for (i, item) in items.iter().enumerate() {
+			let item_schema = prefix.and_then(|p| p.get(i)).or(schema.get("items"));
+			if let Some(s) = item_schema {
+				validate(s, item, &format!("{path}[{i}]"))?;
+			}
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn test_parse_error_location() {
+	let unknown = r#"binds:
+- port: 3000
+  listeners:
+  - name: default
+    protocl: HTTP
+"#;
+	let err = serdes::yamlviajson::from_str::<LocalConfig>(unknown)
+		.unwrap_err()
+		.to_string();
+	assert!(
+		err.starts_with("binds[0].listeners[0].protocl: unknown field `protocl`"),
+		"{err}"
+	);
+	assert!(err.ends_with(" at line 5 column 14"), "{err}");
+
+	let mismatch = r#"binds:
+- port: all
+  listeners: []
+"#;
+	let err = serdes::yamlviajson::from_str::<LocalConfig>(mismatch)
+		.unwrap_err()
+		.to_string();
+	assert_eq!(
+		err,
+		r#"binds[0].port: invalid type: string "all", expected u16 at line 2 column 9"#
+	);
+}
+
+fn merge(configs: &[(&str, serde_json::Value)]) -> anyhow::Result<serde_json::Value> {
+	merge_configs(
+		configs
+			.iter()
+			.map(|(name, config)| (name.to_string(), config.clone()))
+			.collect(),
+	)
+}
+
+#[test]
+fn test_merge_configs() {
+	use serde_json::json;
+	let a = json!({
+		"config": {"adminAddr": "localhost:15000"},
+		"binds": [{"port": 3000, "listeners": [{"name": "a", "hostname": "a.example.com"}]}],
+	});
+	let b = json!({
+		"binds": [
+			{"port": 3000, "listeners": [{"name": "b", "hostname": "b.example.com"}]},
+			{"port": 4000, "listeners": [{"name": "b"}]},
+		],
+		"services": [{"name": "svc"}],
+	});
+	let merged = merge(&[("a.yaml", a.clone()), ("b.yaml", b)]).unwrap();
+	assert_eq!(
+		merged,
+		json!({
+			"config": {"adminAddr": "localhost:15000"},
+			"binds": [
+				{"port": 3000, "listeners": [
+					{"name": "a", "hostname": "a.example.com"},
+					{"name": "b", "hostname": "b.example.com"},
+				]},
+				{"port": 4000, "listeners": [{"name": "b"}]},
+			],
+			"services": [{"name": "svc"}],
+		})
+	);
+	// Empty files are skipped
+	assert_eq!(
+		merge(&[
+			("a.yaml", a.clone()),
+			("empty.yaml", serde_json::Value::Null)
+		])
+		.unwrap(),
+		a
+	);
+}
+
+#[test]
+fn test_merge_configs_conflicts() {
+	use serde_json::json;
+	let a = json!({
+		"config": {},
+		"binds": [{"port": 3000, "maxConnections": 10, "listeners": [{"name": "a"}]}],
+	});
+	let err = |b: serde_json::Value| {
+		merge(&[("a.yaml", a.clone()), ("b.yaml", b)])
+			.unwrap_err()
+			.to_string()
+	};
+	assert_eq!(
+		err(json!({"binds": [{"port": 3000, "listeners": [{"name": "a"}]}]})),
+		"listener 'a' on port 3000 is defined in both a.yaml and b.yaml"
+	);
+	assert_eq!(
+		err(json!({"binds": [{"port": 3000, "maxConnections": 20}]})),
+		"port 3000: maxConnections is set in both a.yaml and b.yaml"
+	);
+	assert_eq!(
+		err(json!({"config": {}})),
+		"config is set in both a.yaml and b.yaml; it may only be set in one file"
+	);
+	// The same listener name is fine on another port
+	merge(&[
+		("a.yaml", a.clone()),
+		(
+			"b.yaml",
+			json!({"binds": [{"port": 4000, "listeners": [{"name": "a"}]}]}),
+		),
+	])
+	.unwrap();
+}
+
+#[test]
+fn test_merge_files() {
+	let dir = tempfile::tempdir().unwrap();
+	let teams = dir.path().join("teams");
+	fs_err::create_dir(&teams).unwrap();
+	let main = dir.path().join("main.yaml");
+	fs_err::write(&main, "config: {}\nbinds:\n- port: 3000\n  listeners: []\n").unwrap();
+	fs_err::write(
+		teams.join("b.yaml"),
+		"binds:\n- port: 3000\n  listeners:\n  - name: b\n",
+	)
+	.unwrap();
+	fs_err::write(
+		teams.join("a.yml"),
+		"binds:\n- port: 3000\n  listeners:\n  - name: a\n",
+	)
+	.unwrap();
+	fs_err::write(teams.join("README.md"), "not config").unwrap();
+
+	let files = config_files(&[main.clone(), teams.clone()]).unwrap();
+	assert_eq!(
+		files,
+		vec![main.clone(), teams.join("a.yml"), teams.join("b.yaml")]
+	);
+	let merged: serde_json::Value =
+		serde_json::from_str(&merge_files(&[main, teams.clone()]).unwrap()).unwrap();
+	assert_eq!(
+		merged["binds"][0]["listeners"],
+		serde_json::json!([{"name": "a"}, {"name": "b"}])
+	);
+
+	// Errors point to the line of the file they are in
+	fs_err::write(
+		teams.join("c.yaml"),
+		"binds:\n- port: 3000\n  listeners:\n  - name: c\n    protocol: QUIC\n",
+	)
+	.unwrap();
+	let err = merge_files(std::slice::from_ref(&teams))
+		.unwrap_err()
+		.to_string();
+	assert!(err.contains("c.yaml"), "{err}");
+	assert!(err.contains("at line 5"), "{err}");
+}
+
+#[tokio::test]
+async fn test_merge_files_expands_env() {
+	let dir = tempfile::tempdir().unwrap();
+	let main = dir.path().join("main.yaml");
+	fs_err::write(
+		&main,
+		"binds:\n- port: ${AGENTGATEWAY_TEST_UNSET_PORT:-8080}\n  listeners: []\n",
+	)
+	.unwrap();
+	let merged: serde_json::Value =
+		serde_json::from_str(&merge_files(std::slice::from_ref(&main)).unwrap()).unwrap();
+	assert_eq!(merged["binds"][0]["port"], 8080);
+
+	let client = client::Client::new(
+		&client::Config {
+			resolver_cfg: hickory_resolver::config::ResolverConfig::default(),
+			resolver_opts: hickory_resolver::config::ResolverOpts::default(),
+		},
+		None,
+	);
+	let config = NormalizedLocalConfig::from_source(client, &ConfigSource::Files(vec![main]))
+		.await
+		.unwrap();
+	assert_eq!(config.binds[0].address.port(), 8080);
+}
+
+#[test]
+fn test_merge_configs_duplicate_names() {
+	use serde_json::json;
+	let routes = |listener: &str, route: &str, target: &str| {
+		json!({"binds": [{"port": 3000, "listeners": [{"name": listener, "routes": [{
+			"name": route,
+			"backends": [{"mcp": {"targets": [{"name": target, "stdio": {"cmd": "echo"}}]}}],
+		}]}]}]})
+	};
+	let err = |a: serde_json::Value, b: serde_json::Value| {
+		merge(&[("a.yaml", a), ("b.yaml", b)])
+			.unwrap_err()
+			.to_string()
+	};
+	// Disjoint targets merge
+	merge(&[
+		("a.yaml", routes("a", "a", "a")),
+		("b.yaml", routes("b", "b", "b")),
+	])
+	.unwrap();
+	assert_eq!(
+		err(routes("a", "a", "a"), routes("b", "b", "a")),
+		"MCP target 'a' is defined in both a.yaml and b.yaml"
+	);
+	assert_eq!(
+		err(routes("a", "shared", "a"), routes("b", "shared", "b")),
+		"route 'shared' is defined in both a.yaml and b.yaml"
+	);
+}
+
+#[tokio::test]
+async fn test_bind_rate_limit_key() {
+	let client = client::Client::new(
+		&client::Config {
+			resolver_cfg: hickory_resolver::config::ResolverConfig::default(),
+			resolver_opts: hickory_resolver::config::ResolverOpts::default(),
+		},
+		None,
+	);
+	let config = |key: &str| {
+		format!(
+			r#"
+binds:
+- port: 3000
+  rateLimit:
+    maxTokens: 10
+    tokensPerFill: 10
+    fillInterval: 1s
+    key: {key}
+  listeners: []
+"#
+		)
+	};
+	assert!(
+		NormalizedLocalConfig::from(client.clone(), &config("sourceIp"))
+			.await
+			.is_ok()
+	);
+	let err = NormalizedLocalConfig::from(client, &config("jwtSubject"))
+		.await
+		.unwrap_err();
+	assert_eq!(
+		err.to_string(),
+		"bind 3000 rateLimit cannot be keyed by jwtSubject, as it applies before JWTs are validated"
+	);
+}
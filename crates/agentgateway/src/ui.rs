@@ -2,11 +2,12 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use crate::management::admin::{AdminFallback, AdminResponse, ConfigDumpHandler};
+use crate::types::local::{self, PatchError};
 use crate::{Config, ConfigSource, client, yamlviajson};
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Redirect, Response};
-use axum::routing::{get, post};
+use axum::routing::{get, patch, post};
 use axum::{Json, Router};
 use http::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE};
 use http::{HeaderName, HeaderValue, Method};
@@ -25,6 +26,8 @@ pub struct UiHandler {
 struct App {
 	state: Arc<Config>,
 	client: client::Client,
+	// Held while the config file is read and written, so concurrent updates are not lost.
+	write_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl App {
@@ -48,12 +51,15 @@ impl UiHandler {
 		let router = Router::new()
 			// Redirect to the UI
 			.route("/config", get(get_config).post(write_config))
+			.route("/config/targets/{name}", patch(patch_target))
+			.route("/config/routes/{name}", patch(patch_route))
 			.nest_service("/ui", ui_service)
 			.route("/", get(|| async { Redirect::permanent("/ui") }))
 			.layer(add_cors_layer())
 			.with_state(App {
 				state: cfg.clone(),
 				client: client::Client::new(&cfg.dns, None),
+				write_lock: Default::default(),
 			});
 		Self { router }
 	}
@@ -65,6 +71,24 @@ enum ErrorResponse {
 	String(String),
 	#[error("{0}")]
 	Anyhow(#[from] anyhow::Error),
+	/// The submitted configuration is invalid.
+	#[error("{0}")]
+	Invalid(String),
+	#[error("{0}")]
+	NotFound(String),
+	/// The request conflicts with the current configuration.
+	#[error("{0}")]
+	Conflict(String),
+}
+
+impl From<PatchError> for ErrorResponse {
+	fn from(e: PatchError) -> Self {
+		match e {
+			PatchError::NotFound(_) => ErrorResponse::NotFound(e.to_string()),
+			PatchError::Ambiguous(_) => ErrorResponse::Conflict(e.to_string()),
+			PatchError::Invalid(_) => ErrorResponse::Invalid(e.to_string()),
+		}
+	}
 }
 
 impl Serialize for ErrorResponse {
@@ -78,7 +102,13 @@ impl Serialize for ErrorResponse {
 
 impl IntoResponse for ErrorResponse {
 	fn into_response(self) -> Response {
-		(StatusCode::INTERNAL_SERVER_ERROR, Json(self)).into_response()
+		let status = match self {
+			ErrorResponse::Invalid(_) => StatusCode::BAD_REQUEST,
+			ErrorResponse::NotFound(_) => StatusCode::NOT_FOUND,
+			ErrorResponse::Conflict(_) => StatusCode::CONFLICT,
+			_ => StatusCode::INTERNAL_SERVER_ERROR,
+		};
+		(status, Json(self)).into_response()
 	}
 }
 
@@ -92,6 +122,49 @@ async fn write_config(
 	State(app): State<App>,
 	Json(config_json): Json<Value>,
 ) -> Result<Json<Value>, ErrorResponse> {
+	let _guard = app.write_lock.lock().await;
+	save_config(&app, &config_json).await?;
+
+	// Return success response
+	Ok(Json(
+		serde_json::json!({"status": "success", "message": "Configuration written successfully"}),
+	))
+}
+
+/// Merges a JSON merge patch (RFC 7396) into a single MCP target, and returns the patched target.
+async fn patch_target(
+	State(app): State<App>,
+	Path(name): Path<String>,
+	Json(patch): Json<Value>,
+) -> Result<Json<Value>, ErrorResponse> {
+	patch_config(&app, |c| local::patch_mcp_target(c, &name, patch)).await
+}
+
+/// Merges a JSON merge patch (RFC 7396) into a single route, such as to update its `backendAuth` or
+/// `mcpAuthorization` policies, and returns the patched route.
+async fn patch_route(
+	State(app): State<App>,
+	Path(name): Path<String>,
+	Json(patch): Json<Value>,
+) -> Result<Json<Value>, ErrorResponse> {
+	patch_config(&app, |c| local::patch_route(c, &name, patch)).await
+}
+
+async fn patch_config(
+	app: &App,
+	f: impl FnOnce(&mut Value) -> Result<Value, PatchError>,
+) -> Result<Json<Value>, ErrorResponse> {
+	let _guard = app.write_lock.lock().await;
+	let s = app.cfg()?.read_to_string().await?;
+	let mut config: Value = yamlviajson::from_str(&s).map_err(|e| ErrorResponse::Anyhow(e.into()))?;
+	let patched = f(&mut config)?;
+	save_config(app, &config).await?;
+	Ok(Json(patched))
+}
+
+/// Validates the configuration, and writes it to the config file if it is valid. Callers hold the
+/// write lock.
+async fn save_config(app: &App, config_json: &Value) -> Result<(), ErrorResponse> {
 	let config_source = app.cfg()?;
 
 	let file_path = match &config_source {
@@ -101,26 +174,43 @@ async fn write_config(
 				"Cannot write to static config".to_string(),
 			));
 		},
+		ConfigSource::Files(_) => {
+			return Err(ErrorResponse::String(
+				"Cannot write to config merged from several files".to_string(),
+			));
+		},
 	};
 	let yaml_content =
-		yamlviajson::to_string(&config_json).map_err(|e| ErrorResponse::Anyhow(e.into()))?;
+		yamlviajson::to_string(config_json).map_err(|e| ErrorResponse::Anyhow(e.into()))?;
 
+	let allowlist = &app.state.stdio_command_allowlist;
 	if let Err(e) =
 		crate::types::local::NormalizedLocalConfig::from(app.client.clone(), yaml_content.as_str())
 			.await
+			.and_then(|c| c.check_stdio_commands(allowlist))
 	{
-		return Err(ErrorResponse::String(e.to_string()));
+		return Err(ErrorResponse::Invalid(format!("{e:#}")));
 	}
 
-	// Write the YAML content to the file
-	fs_err::tokio::write(file_path, yaml_content)
-		.await
-		.map_err(|e| ErrorResponse::Anyhow(e.into()))?;
-
-	// Return success response
-	Ok(Json(
-		serde_json::json!({"status": "success", "message": "Configuration written successfully"}),
-	))
+	// Write to a temporary file next to the config and move it into place, so the config file is
+	// never seen partially written.
+	let tmp = file_path.with_file_name(format!(
+		".{}.{}.tmp",
+		file_path
+			.file_name()
+			.map(|n| n.to_string_lossy())
+			.unwrap_or_default(),
+		std::process::id()
+	));
+	let write = async {
+		fs_err::tokio::write(&tmp, yaml_content).await?;
+		fs_err::tokio::rename(&tmp, file_path).await
+	};
+	if let Err(e) = write.await {
+		let _ = fs_err::tokio::remove_file(&tmp).await;
+		return Err(ErrorResponse::Anyhow(e.into()));
+	}
+	Ok(())
 }
 
 pub fn add_cors_layer() -> CorsLayer {
@@ -145,6 +235,7 @@ pub fn add_cors_layer() -> CorsLayer {
 			Method::GET,
 			Method::POST,
 			Method::PUT,
+			Method::PATCH,
 			Method::DELETE,
 			Method::OPTIONS,
 		])
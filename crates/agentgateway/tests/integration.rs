@@ -30,3 +30,59 @@ async fn test_basic_proxy_comparison() -> anyhow::Result<()> {
 
 	Ok(())
 }
+
+#[tokio::test]
+async fn test_embedded_proxy() -> anyhow::Result<()> {
+	use common::compare::{find_free_port, wait_for_port};
+	agent_core::telemetry::testing::setup_test_logging();
+	let backend = wiremock::MockServer::start().await;
+	Mock::given(wiremock::matchers::path("/test"))
+		.respond_with(ResponseTemplate::new(200).set_body_string("Hello, World!"))
+		.mount(&backend)
+		.await;
+
+	let port = find_free_port().await?;
+	let config = format!(
+		r#"config:
+  adminAddr: localhost:0
+  statsAddr: 127.0.0.1:0
+  readinessAddr: 127.0.0.1:0
+binds:
+- port: {port}
+  listeners:
+  - name: default
+    protocol: HTTP
+    routes:
+    - name: default
+      backends:
+        - host: {}
+"#,
+		backend.address()
+	);
+	let token = tokio_util::sync::CancellationToken::new();
+	let proxy = agentgateway::app::Proxy::from_config(&config)?
+		.cancel_on(token.clone())
+		.run()
+		.await?;
+	wait_for_port(port).await?;
+	let body = reqwest::get(format!("http://127.0.0.1:{port}/test"))
+		.await?
+		.text()
+		.await?;
+	assert_eq!(body, "Hello, World!");
+	let readiness = proxy.readiness_address();
+	assert_ne!(readiness.port(), 0);
+
+	// Cancelling drains the proxy, even once the handle is dropped
+	drop(proxy);
+	token.cancel();
+	let start = std::time::Instant::now();
+	while tokio::net::TcpStream::connect(readiness).await.is_ok() {
+		assert!(
+			start.elapsed() < std::time::Duration::from_secs(10),
+			"proxy did not shut down"
+		);
+		tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+	}
+	Ok(())
+}
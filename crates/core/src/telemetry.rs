@@ -26,13 +26,46 @@ use tracing_subscriber::{Layer, Registry, filter, reload};
 
 pub static APPLICATION_START_TIME: Lazy<Instant> = Lazy::new(Instant::now);
 static LOG_HANDLE: OnceCell<LogHandle> = OnceCell::new();
+static LOG_WRITER: OnceCell<NonBlocking> = OnceCell::new();
+
+/// The format logs are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum LogFormat {
+	/// Human readable text, with fields written as `key=value`.
+	#[default]
+	Plain,
+	/// One JSON object per line, with span fields nested under the span name.
+	Json,
+}
+
+impl FromStr for LogFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"plain" => Ok(LogFormat::Plain),
+			"json" => Ok(LogFormat::Json),
+			_ => Err(format!("unknown log format {s:?}, expected plain or json")),
+		}
+	}
+}
 
 pub fn setup_logging() -> tracing_appender::non_blocking::WorkerGuard {
+	setup_logging_to(std::io::stdout())
+}
+
+/// Like [setup_logging], writing logs to `writer` rather than stdout, for example to keep stdout for
+/// a command's output.
+pub fn setup_logging_to(
+	writer: impl std::io::Write + Send + 'static,
+) -> tracing_appender::non_blocking::WorkerGuard {
 	Lazy::force(&APPLICATION_START_TIME);
 	let (non_blocking, _guard) = tracing_appender::non_blocking::NonBlockingBuilder::default()
 		.lossy(false)
 		.buffered_lines_limit(1000) // Buffer up to 1000 lines to avoid blocking on logs
-		.finish(std::io::stdout());
+		.finish(writer);
 	tracing_subscriber::registry()
 		.with(fmt_layer(non_blocking))
 		.init();
@@ -55,12 +88,22 @@ fn plain_fmt(writer: NonBlocking) -> Box<dyn Layer<Registry> + Send + Sync + 'st
 	Box::new(format)
 }
 
+fn format_layer(writer: NonBlocking, format: LogFormat) -> BoxLayer {
+	match format {
+		LogFormat::Plain => plain_fmt(writer),
+		LogFormat::Json => json_fmt(writer),
+	}
+}
+
 fn fmt_layer(writer: NonBlocking) -> Box<dyn Layer<Registry> + Send + Sync + 'static> {
-	let format = if env::var("LOG_FORMAT").unwrap_or("plain".to_string()) == "json" {
-		json_fmt(writer)
-	} else {
-		plain_fmt(writer)
-	};
+	// The format may be changed once the configuration is loaded, see set_format. An invalid format
+	// is reported then, as nothing can be logged yet.
+	let format = env::var("LOG_FORMAT")
+		.ok()
+		.and_then(|f| f.parse().ok())
+		.unwrap_or_default();
+	let _ = LOG_WRITER.set(writer.clone());
+	let format = format_layer(writer, format);
 	let filter = default_filter();
 	let (layer, reload) = reload::Layer::new(format.with_filter(filter));
 	LOG_HANDLE
@@ -117,6 +160,16 @@ pub fn set_level(reset: bool, level: &str) -> Result<(), Error> {
 	}
 }
 
+/// set_format dynamically changes the format logs are written in.
+pub fn set_format(format: LogFormat) -> Result<(), Error> {
+	let (Some(handle), Some(writer)) = (LOG_HANDLE.get(), LOG_WRITER.get()) else {
+		return Err(Error::Uninitialized);
+	};
+	Ok(handle.modify(|layer| {
+		*layer.inner_mut() = format_layer(writer.clone(), format);
+	})?)
+}
+
 pub fn get_current_loglevel() -> Result<String, Error> {
 	if let Some(handle) = LOG_HANDLE.get() {
 		Ok(handle.with_current(|f| f.filter().to_string())?)
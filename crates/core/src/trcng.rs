@@ -9,11 +9,15 @@ use opentelemetry::propagation::TextMapCompositePropagator;
 use opentelemetry::trace::{Span, SpanBuilder, Tracer as _};
 use opentelemetry::{Context, KeyValue};
 use opentelemetry_http::{HeaderExtractor, HeaderInjector};
-use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_otlp::tonic_types::metadata::MetadataMap;
+use opentelemetry_otlp::{
+	ExporterBuildError, MetricExporter, SpanExporter, WithExportConfig, WithHttpConfig,
+	WithTonicConfig,
+};
 use opentelemetry_sdk::Resource;
 use opentelemetry_sdk::error::OTelSdkResult;
 use opentelemetry_sdk::propagation::{BaggagePropagator, TraceContextPropagator};
-use opentelemetry_sdk::trace::{SdkTracerProvider, SpanProcessor};
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider, SpanProcessor};
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
@@ -46,8 +50,10 @@ pub fn start_span_with_attributes(
 			};
 			attrs.push(KeyValue::new(k, v))
 		}
-		base = base.with_attributes(attrs);
 	};
+	if !attrs.is_empty() {
+		base = base.with_attributes(attrs);
+	}
 	base
 }
 
@@ -70,15 +76,86 @@ fn get_tag_rules() -> Option<HashMap<String, String>> {
 fn set_tag_rules(rules: HashMap<String, String>) {
 	_ = TAG_RULES.get_or_init(|| (rules))
 }
-fn get_resource() -> Resource {
-	static RESOURCE: OnceLock<Resource> = OnceLock::new();
-	RESOURCE
-		.get_or_init(|| {
-			Resource::builder()
-				.with_service_name("agentgateway")
-				.build()
-		})
-		.clone()
+
+pub const DEFAULT_SERVICE_NAME: &str = "agentgateway";
+
+/// The transport used to send spans to an OTLP collector.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Protocol {
+	/// OTLP over gRPC, typically on port 4317.
+	#[default]
+	Grpc,
+	/// OTLP over HTTP with protobuf payloads, typically on port 4318.
+	Http,
+}
+
+/// Builds an OTLP span exporter, sending `headers` (for example, authentication) with each export.
+pub fn span_exporter(
+	endpoint: &str,
+	protocol: Protocol,
+	headers: &HashMap<String, String>,
+) -> Result<SpanExporter, ExporterBuildError> {
+	match protocol {
+		Protocol::Grpc => SpanExporter::builder()
+			.with_tonic()
+			.with_endpoint(endpoint)
+			.with_metadata(grpc_metadata(headers)?)
+			.build(),
+		Protocol::Http => SpanExporter::builder()
+			.with_http()
+			.with_endpoint(endpoint)
+			.with_headers(headers.clone())
+			.build(),
+	}
+}
+
+/// Builds an OTLP metric exporter, configured like [span_exporter]. Over HTTP, `endpoint` is the
+/// one for traces, so metrics are sent to the `/v1/metrics` path beside it.
+pub fn metric_exporter(
+	endpoint: &str,
+	protocol: Protocol,
+	headers: &HashMap<String, String>,
+) -> Result<MetricExporter, ExporterBuildError> {
+	match protocol {
+		Protocol::Grpc => MetricExporter::builder()
+			.with_tonic()
+			.with_endpoint(endpoint)
+			.with_metadata(grpc_metadata(headers)?)
+			.build(),
+		Protocol::Http => MetricExporter::builder()
+			.with_http()
+			.with_endpoint(metrics_endpoint(endpoint))
+			.with_headers(headers.clone())
+			.build(),
+	}
+}
+
+/// The OTLP/HTTP metrics endpoint of the collector at `endpoint`, which may be given with or
+/// without its `/v1/traces` path.
+fn metrics_endpoint(endpoint: &str) -> String {
+	let base = endpoint.trim_end_matches('/');
+	let base = base.strip_suffix("/v1/traces").unwrap_or(base);
+	format!("{base}/v1/metrics")
+}
+
+fn grpc_metadata(headers: &HashMap<String, String>) -> Result<MetadataMap, ExporterBuildError> {
+	let mut metadata = http::HeaderMap::new();
+	for (k, v) in headers {
+		let invalid = |e: &dyn std::fmt::Display| {
+			ExporterBuildError::InternalFailure(format!("invalid header {k}: {e}"))
+		};
+		let k = http::HeaderName::try_from(k).map_err(|e| invalid(&e))?;
+		let v = http::HeaderValue::try_from(v).map_err(|e| invalid(&e))?;
+		metadata.insert(k, v);
+	}
+	Ok(MetadataMap::from_headers(metadata))
+}
+
+/// Samples `ratio` of new traces, and follows the decision of the caller for existing traces.
+pub fn sampler(ratio: f64) -> Sampler {
+	Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(ratio)))
 }
 
 /// A custom span processor that enriches spans with baggage attributes. Baggage
@@ -117,11 +194,27 @@ pub struct Config {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Tracer {
 	#[serde(rename = "otlp")]
-	Otlp { endpoint: Option<String> },
+	Otlp {
+		endpoint: Option<String>,
+		#[serde(default)]
+		protocol: Protocol,
+		#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+		headers: HashMap<String, String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		sampling_ratio: Option<f64>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		service_name: Option<String>,
+	},
 }
 
 pub fn init_tracer(config: Config) -> Result<SdkTracerProvider, ExporterBuildError> {
-	let Tracer::Otlp { endpoint } = config.tracer;
+	let Tracer::Otlp {
+		endpoint,
+		protocol,
+		headers,
+		sampling_ratio,
+		service_name,
+	} = config.tracer;
 	let Some(endpoint) = endpoint else {
 		return Err(ExporterBuildError::NoHttpClient);
 	};
@@ -132,16 +225,17 @@ pub fn init_tracer(config: Config) -> Result<SdkTracerProvider, ExporterBuildErr
 		Box::new(trace_context_propagator),
 	]);
 
-	info!(endpoint, "initializing tracer");
+	info!(endpoint, ?protocol, "initializing tracer");
 	global::set_text_map_propagator(composite_propagator);
-	let exporter = SpanExporter::builder()
-		.with_tonic()
-		.with_endpoint(endpoint)
-		.build()?;
+	let exporter = span_exporter(&endpoint, protocol, &headers)?;
 
+	let resource = Resource::builder()
+		.with_service_name(service_name.unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string()))
+		.build();
 	let provider = SdkTracerProvider::builder()
 		.with_span_processor(EnrichWithBaggageSpanProcessor)
-		.with_resource(get_resource())
+		.with_resource(resource)
+		.with_sampler(sampler(sampling_ratio.unwrap_or(1.0)))
 		.with_batch_exporter(exporter)
 		.build();
 
@@ -150,3 +244,116 @@ pub fn init_tracer(config: Config) -> Result<SdkTracerProvider, ExporterBuildErr
 	set_tag_rules(config.tags);
 	Ok(provider)
 }
+
+#[cfg(test)]
+mod test {
+	use std::collections::HashMap;
+
+	use opentelemetry::InstrumentationScope;
+	use opentelemetry::trace::{
+		SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState,
+	};
+	use opentelemetry_sdk::metrics::data::ResourceMetrics;
+	use opentelemetry_sdk::metrics::exporter::PushMetricExporter as _;
+	use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanExporter as _, SpanLinks};
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+	use tokio::net::TcpListener;
+
+	use super::*;
+
+	fn span() -> SpanData {
+		SpanData {
+			span_context: SpanContext::new(
+				TraceId::from_bytes([1; 16]),
+				SpanId::from_bytes([1; 8]),
+				TraceFlags::SAMPLED,
+				false,
+				TraceState::default(),
+			),
+			parent_span_id: SpanId::INVALID,
+			span_kind: SpanKind::Server,
+			name: "test".into(),
+			start_time: std::time::SystemTime::now(),
+			end_time: std::time::SystemTime::now(),
+			attributes: Vec::new(),
+			dropped_attributes_count: 0,
+			events: SpanEvents::default(),
+			links: SpanLinks::default(),
+			status: Status::Unset,
+			instrumentation_scope: InstrumentationScope::builder("test").build(),
+		}
+	}
+
+	/// A minimal collector, which reports the head of each export request it accepts.
+	async fn collector() -> (std::net::SocketAddr, tokio::sync::mpsc::UnboundedReceiver<String>) {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		let (tx, exports) = tokio::sync::mpsc::unbounded_channel();
+		tokio::spawn(async move {
+			while let Ok((mut stream, _)) = listener.accept().await {
+				let mut head = Vec::new();
+				while !head.ends_with(b"\r\n\r\n") {
+					head.push(stream.read_u8().await.unwrap());
+				}
+				let _ = tx.send(String::from_utf8(head).unwrap());
+				let _ = stream
+					.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+					.await;
+			}
+		});
+		(addr, exports)
+	}
+
+	#[tokio::test]
+	async fn test_http_span_exporter() {
+		let (addr, mut exports) = collector().await;
+		let headers = HashMap::from([("authorization".to_string(), "Bearer secret".to_string())]);
+		let exporter = span_exporter(
+			&format!("http://{addr}/v1/traces"),
+			Protocol::Http,
+			&headers,
+		)
+		.unwrap();
+		exporter.export(vec![span()]).await.unwrap();
+
+		let head = exports.recv().await.unwrap().to_lowercase();
+		assert!(head.starts_with("post /v1/traces http/1.1\r\n"), "{head}");
+		assert!(
+			head.contains("content-type: application/x-protobuf\r\n"),
+			"{head}"
+		);
+		assert!(head.contains("authorization: bearer secret\r\n"), "{head}");
+	}
+	#[tokio::test]
+	async fn test_http_metric_exporter() {
+		let (addr, mut exports) = collector().await;
+		// Configured with the traces endpoint, as in the tracing config
+		let headers = HashMap::from([("authorization".to_string(), "Bearer secret".to_string())]);
+		let exporter = metric_exporter(
+			&format!("http://{addr}/v1/traces"),
+			Protocol::Http,
+			&headers,
+		)
+		.unwrap();
+		exporter.export(&ResourceMetrics::default()).await.unwrap();
+
+		let head = exports.recv().await.unwrap().to_lowercase();
+		assert!(head.starts_with("post /v1/metrics http/1.1\r\n"), "{head}");
+		assert!(head.contains("authorization: bearer secret\r\n"), "{head}");
+	}
+
+	#[test]
+	fn test_metrics_endpoint() {
+		for endpoint in [
+			"http://collector:4318",
+			"http://collector:4318/",
+			"http://collector:4318/v1/traces",
+		] {
+			assert_eq!(
+				metrics_endpoint(endpoint),
+				"http://collector:4318/v1/metrics",
+				"{endpoint}"
+			);
+		}
+	}
+}
@@ -86,6 +86,11 @@ macro_rules! e {
 	};
 }
 
+/// Added to the extensions of every response, reporting whether the request was sent on an idle
+/// connection taken from the pool rather than on a new one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnectionReused(pub bool);
+
 // We might change this... :shrug:
 type DefaultPoolKey = (http::uri::Scheme, http::uri::Authority);
 
@@ -323,6 +328,9 @@ where
 		if let Some(extra) = &pooled.conn_info.extra {
 			extra.set(res.extensions_mut());
 		}
+		res
+			.extensions_mut()
+			.insert(ConnectionReused(pooled.is_reused()));
 
 		// If pooled is HTTP/2, we can toss this reference immediately.
 		//
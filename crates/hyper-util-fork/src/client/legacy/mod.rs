@@ -1,7 +1,7 @@
 #[cfg(any(feature = "http1", feature = "http2"))]
 mod client;
 #[cfg(any(feature = "http1", feature = "http2"))]
-pub use client::{Builder, Client, Error, ResponseFuture};
+pub use client::{Builder, Client, ConnectionReused, Error, ResponseFuture};
 
 pub mod connect;
 #[doc(hidden)]